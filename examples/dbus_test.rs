@@ -277,6 +277,7 @@ async fn populate_mock_data(service: &CRDbusService) -> Result<(), Box<dyn std::
         ipv6_address: Some("fe80::1".to_string()),
         hw_address: Some("00:11:22:33:44:55".to_string()),
         mtu: 1500,
+        dhcp4: None,
     };
 
     let wlan0 = CRDeviceInfo {
@@ -288,6 +289,7 @@ async fn populate_mock_data(service: &CRDbusService) -> Result<(), Box<dyn std::
         ipv6_address: None,
         hw_address: Some("AA:BB:CC:DD:EE:FF".to_string()),
         mtu: 1500,
+        dhcp4: None,
     };
 
     service.network_control().add_device(eth0.clone()).await;
@@ -0,0 +1,260 @@
+//! IPv4 address conflict detection (RFC 5227)
+//!
+//! Before an address is configured it is probed with ARP requests that use
+//! the unspecified sender address; any host answering for it, or probing for
+//! it at the same time, is a conflict. Once the address is in use it is
+//! announced so that neighbours update their caches, and defended when
+//! another host claims it.
+
+use crate::error::{NetctlError, NetctlResult};
+use crate::netns::NetNamespace;
+use crate::rawsock::{PacketSocket, BROADCAST_MAC, ETH_P_ARP, ETH_P_IP};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::net::Ipv4Addr;
+use std::time::Duration;
+use tokio::time::{sleep, timeout_at, Instant};
+use tracing::debug;
+
+/// ARP request
+pub const ARP_REQUEST: u16 = 1;
+/// ARP reply
+pub const ARP_REPLY: u16 = 2;
+
+const ARP_LEN: usize = 28;
+
+/// Minimum time between two defenses of an address (DEFEND_INTERVAL)
+pub const DEFEND_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Probe and announcement timing (RFC 5227 section 1.1)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArpProbeConfig {
+    /// Upper bound of the random delay before the first probe
+    pub probe_wait: Duration,
+    /// Number of probes
+    pub probe_num: u32,
+    /// Minimum delay between probes
+    pub probe_min: Duration,
+    /// Maximum delay between probes
+    pub probe_max: Duration,
+    /// Time to wait for replies after the last probe
+    pub announce_wait: Duration,
+    /// Number of announcements
+    pub announce_num: u32,
+    /// Delay between announcements
+    pub announce_interval: Duration,
+}
+
+impl Default for ArpProbeConfig {
+    fn default() -> Self {
+        Self {
+            probe_wait: Duration::from_secs(1),
+            probe_num: 3,
+            probe_min: Duration::from_secs(1),
+            probe_max: Duration::from_secs(2),
+            announce_wait: Duration::from_secs(2),
+            announce_num: 2,
+            announce_interval: Duration::from_secs(2),
+        }
+    }
+}
+
+/// An Ethernet/IPv4 ARP packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArpPacket {
+    pub operation: u16,
+    pub sender_mac: [u8; 6],
+    pub sender_ip: Ipv4Addr,
+    pub target_mac: [u8; 6],
+    pub target_ip: Ipv4Addr,
+}
+
+impl ArpPacket {
+    pub fn encode(&self) -> [u8; ARP_LEN] {
+        let mut buf = [0u8; ARP_LEN];
+        buf[0..2].copy_from_slice(&1u16.to_be_bytes()); // Ethernet
+        buf[2..4].copy_from_slice(&ETH_P_IP.to_be_bytes());
+        buf[4] = 6;
+        buf[5] = 4;
+        buf[6..8].copy_from_slice(&self.operation.to_be_bytes());
+        buf[8..14].copy_from_slice(&self.sender_mac);
+        buf[14..18].copy_from_slice(&self.sender_ip.octets());
+        buf[18..24].copy_from_slice(&self.target_mac);
+        buf[24..28].copy_from_slice(&self.target_ip.octets());
+        buf
+    }
+
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        if buf.len() < ARP_LEN || buf[0..2] != [0, 1] || buf[2..4] != ETH_P_IP.to_be_bytes() || buf[4] != 6 || buf[5] != 4 {
+            return None;
+        }
+        let mac = |at: usize| -> [u8; 6] { buf[at..at + 6].try_into().unwrap_or_default() };
+        let ip = |at: usize| Ipv4Addr::new(buf[at], buf[at + 1], buf[at + 2], buf[at + 3]);
+        Some(Self {
+            operation: u16::from_be_bytes([buf[6], buf[7]]),
+            sender_mac: mac(8),
+            sender_ip: ip(14),
+            target_mac: mac(18),
+            target_ip: ip(24),
+        })
+    }
+}
+
+/// Format a MAC address as `aa:bb:cc:dd:ee:ff`
+pub fn format_mac(mac: &[u8; 6]) -> String {
+    crate::netlink::format_mac(mac)
+}
+
+/// ARP socket on one interface
+pub struct ArpSocket {
+    socket: PacketSocket,
+    mac: [u8; 6],
+}
+
+impl ArpSocket {
+    /// Open an ARP socket on the interface with index `ifindex` and hardware
+    /// address `mac`
+    pub fn open(namespace: &NetNamespace, ifindex: u32, mac: [u8; 6]) -> NetctlResult<Self> {
+        Ok(Self {
+            socket: PacketSocket::open(namespace, ifindex, ETH_P_ARP)?,
+            mac,
+        })
+    }
+
+    /// Broadcast an ARP request
+    pub async fn send_request(&self, sender_ip: Ipv4Addr, target_ip: Ipv4Addr) -> NetctlResult<()> {
+        let packet = ArpPacket {
+            operation: ARP_REQUEST,
+            sender_mac: self.mac,
+            sender_ip,
+            target_mac: [0; 6],
+            target_ip,
+        };
+        self.socket.send_to(&packet.encode(), BROADCAST_MAC).await
+    }
+
+    /// Receive the next ARP packet sent by another host
+    pub async fn recv(&self) -> NetctlResult<ArpPacket> {
+        let mut buf = [0u8; 64];
+        loop {
+            let frame = self.socket.recv(&mut buf).await?;
+            if frame.outgoing || frame.source == self.mac {
+                continue;
+            }
+            if let Some(packet) = ArpPacket::decode(&buf[..frame.len]) {
+                return Ok(packet);
+            }
+        }
+    }
+
+    /// Whether `packet` shows another host using, or probing for, `ip`
+    pub fn is_conflict(&self, packet: &ArpPacket, ip: Ipv4Addr) -> bool {
+        if packet.sender_mac == self.mac {
+            return false;
+        }
+        packet.sender_ip == ip
+            || (packet.operation == ARP_REQUEST && packet.sender_ip.is_unspecified() && packet.target_ip == ip)
+    }
+
+    /// Probe for `ip`; returns the hardware address of a host using it
+    pub async fn probe(&self, ip: Ipv4Addr, config: &ArpProbeConfig) -> NetctlResult<Option<[u8; 6]>> {
+        debug!("Probing {} with ARP", ip);
+        let mut deadline = Instant::now() + random_delay(Duration::ZERO, config.probe_wait);
+
+        for n in 0..=config.probe_num {
+            if let Some(mac) = self.watch(ip, deadline).await? {
+                return Ok(Some(mac));
+            }
+            if n == config.probe_num {
+                break;
+            }
+            self.send_request(Ipv4Addr::UNSPECIFIED, ip).await?;
+            deadline = Instant::now() + if n + 1 == config.probe_num {
+                config.announce_wait
+            } else {
+                random_delay(config.probe_min, config.probe_max)
+            };
+        }
+        Ok(None)
+    }
+
+    /// Announce that this host now uses `ip`
+    pub async fn announce(&self, ip: Ipv4Addr, config: &ArpProbeConfig) -> NetctlResult<()> {
+        for n in 0..config.announce_num {
+            if n > 0 {
+                sleep(config.announce_interval).await;
+            }
+            self.send_request(ip, ip).await?;
+        }
+        Ok(())
+    }
+
+    /// Wait for another host to claim `ip` while this host uses it
+    ///
+    /// Once the address is configured, probes for it are no longer
+    /// conflicts: the kernel answers them. Returns the hardware address of
+    /// the other host.
+    pub async fn wait_for_conflict(&self, ip: Ipv4Addr) -> NetctlResult<[u8; 6]> {
+        loop {
+            let packet = self.recv().await?;
+            if packet.sender_ip == ip && packet.sender_mac != self.mac {
+                return Ok(packet.sender_mac);
+            }
+        }
+    }
+
+    /// Defend `ip` with a single announcement
+    pub async fn defend(&self, ip: Ipv4Addr) -> NetctlResult<()> {
+        debug!("Defending {} with ARP", ip);
+        self.send_request(ip, ip).await
+    }
+
+    /// Wait until `deadline` for a packet conflicting with `ip`
+    async fn watch(&self, ip: Ipv4Addr, deadline: Instant) -> NetctlResult<Option<[u8; 6]>> {
+        loop {
+            match timeout_at(deadline, self.recv()).await {
+                Err(_) => return Ok(None),
+                Ok(Err(e)) => return Err(e),
+                Ok(Ok(packet)) if self.is_conflict(&packet, ip) => {
+                    debug!("ARP conflict for {} with {}", ip, format_mac(&packet.sender_mac));
+                    return Ok(Some(packet.sender_mac));
+                }
+                Ok(Ok(_)) => {}
+            }
+        }
+    }
+}
+
+fn random_delay(min: Duration, max: Duration) -> Duration {
+    if max <= min {
+        return min;
+    }
+    rand::thread_rng().gen_range(min..=max)
+}
+
+/// Hardware address of an interface as reported by netlink
+pub(crate) fn parse_mac(mac: Option<&str>, interface: &str) -> NetctlResult<[u8; 6]> {
+    mac.and_then(|mac| crate::netlink::parse_mac(mac).ok())
+        .and_then(|bytes| <[u8; 6]>::try_from(bytes).ok())
+        .ok_or_else(|| NetctlError::NotSupported(format!("{} has no Ethernet hardware address", interface)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arp_packet_roundtrip() {
+        let probe = ArpPacket {
+            operation: ARP_REQUEST,
+            sender_mac: [2, 0, 0, 0, 0, 1],
+            sender_ip: Ipv4Addr::UNSPECIFIED,
+            target_mac: [0; 6],
+            target_ip: Ipv4Addr::new(192, 0, 2, 10),
+        };
+        let encoded = probe.encode();
+        assert_eq!(&encoded[..8], &[0, 1, 8, 0, 6, 4, 0, 1]);
+        assert_eq!(ArpPacket::decode(&encoded), Some(probe));
+        assert_eq!(ArpPacket::decode(&encoded[..20]), None);
+    }
+}
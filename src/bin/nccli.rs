@@ -81,8 +81,11 @@ pub enum PrivilegedOp {
     // DHCP
     DhcpStart,
     DhcpStop,
+    DhcpTest,
 
     // DNS
+    DnsStart,
+    DnsStop,
     DnsSet,
     DnsFlush,
 
@@ -129,6 +132,9 @@ impl PrivilegedOp {
             Self::ApRestart => "restart access point",
             Self::DhcpStart => "start DHCP server",
             Self::DhcpStop => "stop DHCP server",
+            Self::DhcpTest => "send DHCP test messages",
+            Self::DnsStart => "start DNS server",
+            Self::DnsStop => "stop DNS server",
             Self::DnsSet => "set DNS configuration",
             Self::DnsFlush => "flush DNS cache",
             Self::RouteAddDefault => "add default route",
@@ -807,23 +813,176 @@ enum ApCommands {
 #[derive(Subcommand)]
 enum DhcpCommands {
     /// Start DHCP server
-    Start {
-        interface: String,
-        #[arg(long)]
-        range_start: String,
-        #[arg(long)]
-        range_end: String,
-        #[arg(long)]
-        gateway: String,
-        #[arg(long)]
-        dns: Vec<String>,
-    },
+    Start(Box<DhcpServerSpec>),
     /// Stop DHCP server
     Stop,
+    /// Restart DHCP server with its current configuration
+    Restart,
     /// Get DHCP status
     Status,
     /// Show active leases
     Leases,
+    /// Send test messages to the DHCP servers on a link
+    Test(Box<DhcpTestSpec>),
+}
+
+/// Message to send for dhcp test
+#[derive(Clone, Copy, ValueEnum)]
+enum DhcpTestKind {
+    /// DISCOVER, REQUEST of the first offer, then RELEASE
+    Sequence,
+    Discover,
+    Request,
+    Release,
+    Inform,
+    Decline,
+}
+
+/// Settings for dhcp test
+#[derive(clap::Args)]
+struct DhcpTestSpec {
+    /// Interface to send on
+    interface: String,
+    /// Message to send
+    #[arg(long = "type", value_enum, default_value = "sequence")]
+    kind: DhcpTestKind,
+    /// Client hardware address (random if not given)
+    #[arg(long)]
+    mac: Option<String>,
+    /// Server address (server identifier, and destination with --unicast)
+    #[arg(long)]
+    server: Option<String>,
+    /// Address to ask for (REQUEST, DECLINE)
+    #[arg(long)]
+    requested_ip: Option<String>,
+    /// Client address (RELEASE, INFORM)
+    #[arg(long)]
+    client_ip: Option<String>,
+    /// Server port
+    #[arg(long, default_value_t = 67)]
+    port: u16,
+    /// Send to the server address and let the server answer by unicast
+    #[arg(long)]
+    unicast: bool,
+    /// Extra option, as CODE:HEX; replaces a generated option with the same code
+    #[arg(long = "option")]
+    options: Vec<String>,
+    /// Time to wait for replies, in milliseconds
+    #[arg(long, default_value_t = 3000)]
+    timeout: u64,
+}
+
+impl DhcpTestSpec {
+    fn to_config(&self) -> NetctlResult<dhcpm::DhcpTestConfig> {
+        let options = self.options.iter()
+            .map(|option| {
+                let (code, value) = option.split_once(':')
+                    .and_then(|(code, value)| Some((code.parse().ok()?, value)))
+                    .ok_or_else(|| NetctlError::InvalidParameter(format!("Invalid option {}, expected CODE:HEX", option)))?;
+                Ok(dhcpm::DhcpOption { code, value: value.to_string() })
+            })
+            .collect::<NetctlResult<Vec<_>>>()?;
+        Ok(dhcpm::DhcpTestConfig {
+            interface: self.interface.clone(),
+            message_type: match self.kind {
+                DhcpTestKind::Sequence | DhcpTestKind::Discover => dhcpm::DhcpMessageType::Discover,
+                DhcpTestKind::Request => dhcpm::DhcpMessageType::Request,
+                DhcpTestKind::Release => dhcpm::DhcpMessageType::Release,
+                DhcpTestKind::Inform => dhcpm::DhcpMessageType::Inform,
+                DhcpTestKind::Decline => dhcpm::DhcpMessageType::Decline,
+            },
+            client_mac: self.mac.clone(),
+            server_ip: self.server.clone(),
+            server_port: Some(self.port),
+            client_ip: self.client_ip.clone(),
+            requested_ip: self.requested_ip.clone(),
+            broadcast: !self.unicast,
+            options: (!options.is_empty()).then_some(options),
+        })
+    }
+}
+
+/// DHCP server settings for dhcp start
+#[derive(clap::Args)]
+struct DhcpServerSpec {
+    /// Interface to serve
+    interface: String,
+    #[arg(long)]
+    range_start: String,
+    #[arg(long)]
+    range_end: String,
+    #[arg(long)]
+    gateway: String,
+    #[arg(long)]
+    dns: Vec<String>,
+    /// Served subnet in CIDR notation (default: the /24 of the gateway)
+    #[arg(long)]
+    subnet: Option<String>,
+    /// Further interface to serve the subnet on
+    #[arg(long = "interface")]
+    extra_interfaces: Vec<String>,
+    /// Further address range, as START-END
+    #[arg(long = "range")]
+    ranges: Vec<String>,
+    /// Fixed address, as MAC,IP[,HOSTNAME]
+    #[arg(long = "reserve")]
+    reservations: Vec<String>,
+    /// Lease time in seconds
+    #[arg(long, default_value_t = 3600)]
+    lease_time: u32,
+    /// Domain name (option 15)
+    #[arg(long)]
+    domain: Option<String>,
+    /// Domain search list entry (option 119)
+    #[arg(long)]
+    domain_search: Vec<String>,
+    /// NTP server (option 42)
+    #[arg(long)]
+    ntp: Vec<String>,
+    /// Classless static route, as DEST/LEN,GATEWAY (option 121)
+    #[arg(long)]
+    route: Vec<String>,
+    /// Interface MTU (option 26)
+    #[arg(long)]
+    mtu: Option<u16>,
+    /// Further option, as CODE:TYPE:VALUE with TYPE one of ip, ips, u8, u16, u32, str, hex
+    #[arg(long = "option")]
+    options: Vec<String>,
+    /// Ping addresses before offering them
+    #[arg(long)]
+    ping_check: bool,
+    /// Time to wait for a ping reply, in milliseconds
+    #[arg(long)]
+    ping_timeout: Option<u32>,
+    /// ARP-probe addresses before offering them
+    #[arg(long)]
+    probe_check: bool,
+}
+
+impl DhcpServerSpec {
+    fn to_config(&self) -> NetctlResult<dhcp::DhcpConfig> {
+        Ok(dhcp::DhcpConfig {
+            interface: self.interface.clone(),
+            range_start: self.range_start.clone(),
+            range_end: self.range_end.clone(),
+            gateway: self.gateway.clone(),
+            dns_servers: self.dns.clone(),
+            lease_time: self.lease_time,
+            domain: self.domain.clone(),
+            subnet: self.subnet.clone(),
+            extra_interfaces: self.extra_interfaces.clone(),
+            extra_ranges: self.ranges.iter().map(|r| r.parse()).collect::<NetctlResult<_>>()?,
+            reservations: self.reservations.iter().map(|r| r.parse()).collect::<NetctlResult<_>>()?,
+            ntp_servers: self.ntp.clone(),
+            domain_search: self.domain_search.clone(),
+            routes: self.route.iter().map(|r| r.parse()).collect::<NetctlResult<_>>()?,
+            mtu: self.mtu,
+            options: self.options.iter().map(|o| o.parse()).collect::<NetctlResult<_>>()?,
+            ping_check: self.ping_check,
+            ping_timeout_ms: self.ping_timeout,
+            probe_check: self.probe_check,
+        })
+    }
 }
 
 // ============================================================================
@@ -833,15 +992,31 @@ enum DhcpCommands {
 enum DnsCommands {
     /// Start DNS server
     Start {
+        /// Upstream servers (full recursion if none are given)
         #[arg(long)]
         forwarders: Vec<String>,
+        /// Address or interface to listen on (localhost if none are given)
+        #[arg(long)]
+        listen: Vec<String>,
+        /// Port to listen on
+        #[arg(long, default_value_t = 53)]
+        port: u16,
+        /// Network allowed to query, in CIDR notation
+        #[arg(long)]
+        allow: Vec<String>,
+        /// Disable DNSSEC validation
+        #[arg(long)]
+        no_dnssec: bool,
     },
     /// Stop DNS server
     Stop,
     /// Get DNS status
     Status,
     /// Flush DNS cache
-    Flush,
+    Flush {
+        /// Only flush names under this zone
+        zone: Option<String>,
+    },
 }
 
 // ============================================================================
@@ -850,7 +1025,23 @@ enum DnsCommands {
 #[derive(Subcommand)]
 enum RouteCommands {
     /// Show routing table
-    Show,
+    Show {
+        /// Table to show (name or number, "all" for every table)
+        #[arg(long, default_value = "main")]
+        table: String,
+        /// Only show IPv4 routes
+        #[arg(short = '4', long, conflicts_with = "ipv6")]
+        ipv4: bool,
+        /// Only show IPv6 routes
+        #[arg(short = '6', long)]
+        ipv6: bool,
+    },
+    /// Add a route
+    Add(RouteSpec),
+    /// Add a route or replace an existing one
+    Replace(RouteSpec),
+    /// Delete a route
+    Del(RouteSpec),
     /// Add default gateway
     AddDefault {
         gateway: String,
@@ -858,7 +1049,163 @@ enum RouteCommands {
         interface: Option<String>,
     },
     /// Delete default gateway
-    DelDefault,
+    DelDefault {
+        /// Only delete the route via this gateway
+        gateway: Option<String>,
+        #[arg(short, long)]
+        interface: Option<String>,
+    },
+    /// Manage routing policy rules
+    #[command(subcommand)]
+    Rule(RuleCommands),
+}
+
+#[derive(Subcommand)]
+enum RuleCommands {
+    /// Show routing policy rules
+    Show {
+        /// Only show IPv4 rules
+        #[arg(short = '4', long, conflicts_with = "ipv6")]
+        ipv4: bool,
+        /// Only show IPv6 rules
+        #[arg(short = '6', long)]
+        ipv6: bool,
+    },
+    /// Add a rule
+    Add(RuleSpec),
+    /// Delete a rule
+    Del(RuleSpec),
+}
+
+/// Rule description shared by rule add and del
+#[derive(clap::Args)]
+struct RuleSpec {
+    /// Rule priority
+    #[arg(long)]
+    priority: Option<u32>,
+    /// Source prefix
+    #[arg(long)]
+    from: Option<String>,
+    /// Destination prefix
+    #[arg(long)]
+    to: Option<String>,
+    /// Firewall mark (mark or mark/mask)
+    #[arg(long)]
+    fwmark: Option<String>,
+    /// Incoming interface
+    #[arg(long)]
+    iif: Option<String>,
+    /// Outgoing interface
+    #[arg(long)]
+    oif: Option<String>,
+    /// UID range (start-end)
+    #[arg(long)]
+    uidrange: Option<String>,
+    /// Routing table to look up (name or number)
+    #[arg(long)]
+    table: Option<String>,
+    /// Rule action (lookup, blackhole, unreachable, prohibit, nop)
+    #[arg(long, default_value = "lookup")]
+    action: String,
+    /// Invert the selectors
+    #[arg(long)]
+    not: bool,
+    /// IPv6 rule (implied by IPv6 prefixes)
+    #[arg(short = '6', long)]
+    ipv6: bool,
+}
+
+impl RuleSpec {
+    fn to_rule(&self) -> NetctlResult<RoutingRule> {
+        let mut rule = RoutingRule {
+            ipv6: self.ipv6,
+            priority: self.priority,
+            iif: self.iif.clone(),
+            oif: self.oif.clone(),
+            table: self.table.as_deref().map(routing::parse_table).transpose()?,
+            action: self.action.parse()?,
+            invert: self.not,
+            ..Default::default()
+        };
+        if let Some(from) = &self.from {
+            let (addr, len) = Route::parse_destination(from, self.ipv6)?;
+            rule = rule.from_prefix(addr, len);
+        }
+        if let Some(to) = &self.to {
+            let (addr, len) = Route::parse_destination(to, self.ipv6)?;
+            rule = rule.to_prefix(addr, len);
+        }
+        if let Some(fwmark) = &self.fwmark {
+            let (mark, mask) = RoutingRule::parse_fwmark(fwmark)?;
+            rule.fwmark = Some(mark);
+            rule.fwmask = mask;
+        }
+        rule.uid_range = self.uidrange.as_deref().map(RoutingRule::parse_uid_range).transpose()?;
+        Ok(rule)
+    }
+}
+
+/// Route description shared by add, replace and del
+#[derive(clap::Args)]
+struct RouteSpec {
+    /// Destination prefix (e.g. 10.0.0.0/24, 2001:db8::/32 or "default")
+    destination: String,
+    /// Gateway address
+    #[arg(long)]
+    via: Option<String>,
+    /// Output interface
+    #[arg(long)]
+    dev: Option<String>,
+    /// Route metric
+    #[arg(long)]
+    metric: Option<u32>,
+    /// Routing table (name or number)
+    #[arg(long, default_value = "main")]
+    table: String,
+    /// Route scope (global, site, link, host)
+    #[arg(long)]
+    scope: Option<String>,
+    /// Route protocol (static, boot, dhcp, ra, kernel or a number)
+    #[arg(long, default_value = "static")]
+    proto: String,
+    /// Route type (unicast, blackhole, unreachable, prohibit, throw)
+    #[arg(long = "type", default_value = "unicast")]
+    kind: String,
+    /// Preferred source address
+    #[arg(long)]
+    src: Option<String>,
+    /// Gateway is directly reachable on the interface
+    #[arg(long)]
+    onlink: bool,
+    /// Treat "default" as the IPv6 default route
+    #[arg(short = '6', long)]
+    ipv6: bool,
+}
+
+impl RouteSpec {
+    fn to_route(&self) -> NetctlResult<Route> {
+        let parse_addr = |addr: &str| {
+            addr.parse::<std::net::IpAddr>()
+                .map_err(|_| NetctlError::InvalidParameter(format!("Invalid address: {}", addr)))
+        };
+
+        let ipv6 = self.ipv6 || self.via.as_deref().is_some_and(|gw| gw.contains(':'));
+        let (destination, prefix_len) = Route::parse_destination(&self.destination, ipv6)?;
+        let mut route = Route::new(destination, prefix_len);
+        route.gateway = self.via.as_deref().map(parse_addr).transpose()?;
+        route.interface = self.dev.clone();
+        route.source = self.src.as_deref().map(parse_addr).transpose()?;
+        route.metric = self.metric;
+        route.table = routing::parse_table(&self.table)?;
+        route.protocol = self.proto.parse()?;
+        route.kind = self.kind.parse()?;
+        route.onlink = self.onlink;
+        route.scope = match &self.scope {
+            Some(scope) => scope.parse()?,
+            None => route.implied_scope(),
+        };
+        Ok(route)
+    }
 }
 
 // ============================================================================
@@ -1079,19 +1426,27 @@ fn get_required_privilege(command: &Commands) -> Option<PrivilegedOp> {
         // DHCP commands
         Commands::Dhcp(DhcpCommands::Start { .. }) => Some(PrivilegedOp::DhcpStart),
         Commands::Dhcp(DhcpCommands::Stop) => Some(PrivilegedOp::DhcpStop),
+        Commands::Dhcp(DhcpCommands::Restart) => Some(PrivilegedOp::DhcpStart),
         Commands::Dhcp(DhcpCommands::Status) => None,
         Commands::Dhcp(DhcpCommands::Leases) => None,
+        Commands::Dhcp(DhcpCommands::Test(_)) => Some(PrivilegedOp::DhcpTest),
 
         // DNS commands
-        Commands::Dns(DnsCommands::Start { .. }) => Some(PrivilegedOp::DhcpStart), // reuse
-        Commands::Dns(DnsCommands::Stop) => Some(PrivilegedOp::DhcpStop), // reuse
+        Commands::Dns(DnsCommands::Start { .. }) => Some(PrivilegedOp::DnsStart),
+        Commands::Dns(DnsCommands::Stop) => Some(PrivilegedOp::DnsStop),
         Commands::Dns(DnsCommands::Status) => None,
-        Commands::Dns(DnsCommands::Flush) => Some(PrivilegedOp::DnsFlush),
+        Commands::Dns(DnsCommands::Flush { .. }) => Some(PrivilegedOp::DnsFlush),
 
         // Route commands
-        Commands::Route(RouteCommands::Show) => None,
+        Commands::Route(RouteCommands::Show { .. }) => None,
+        Commands::Route(RouteCommands::Add(_)) => Some(PrivilegedOp::RouteAdd),
+        Commands::Route(RouteCommands::Replace(_)) => Some(PrivilegedOp::RouteAdd),
+        Commands::Route(RouteCommands::Del(_)) => Some(PrivilegedOp::RouteDelete),
         Commands::Route(RouteCommands::AddDefault { .. }) => Some(PrivilegedOp::RouteAddDefault),
-        Commands::Route(RouteCommands::DelDefault) => Some(PrivilegedOp::RouteDelete),
+        Commands::Route(RouteCommands::DelDefault { .. }) => Some(PrivilegedOp::RouteDelete),
+        Commands::Route(RouteCommands::Rule(RuleCommands::Show { .. })) => None,
+        Commands::Route(RouteCommands::Rule(RuleCommands::Add(_))) => Some(PrivilegedOp::RouteAdd),
+        Commands::Route(RouteCommands::Rule(RuleCommands::Del(_))) => Some(PrivilegedOp::RouteDelete),
 
         // Debug commands - read-only
         Commands::Debug(_) => None,
@@ -1698,6 +2053,56 @@ async fn handle_radio(cmd: &RadioCommands, cli: &Cli) -> NetctlResult<()> {
 // ============================================================================
 // CONNECTION COMMAND HANDLERS
 // ============================================================================
+
+/// Print one `setting.property: value` line of a connection profile
+fn print_setting(key: &str, value: &str) {
+    println!("{:<40}{}", format!("{}:", key), value);
+}
+
+/// Print the properties of an `[ipv4]` or `[ipv6]` section
+fn print_ip_settings(family: &str, ip: &IpConfigSection) {
+    let list = |values: Option<&Vec<String>>| match values {
+        Some(values) if !values.is_empty() => values.join(", "),
+        _ => "--".to_string(),
+    };
+    let addresses: Vec<String> = ip.address.iter().chain(ip.addresses.iter().flatten()).cloned().collect();
+
+    print_setting(&format!("{}.method", family), &ip.method);
+    print_setting(&format!("{}.addresses", family), &list(Some(&addresses)));
+    print_setting(&format!("{}.gateway", family), ip.gateway.as_deref().unwrap_or("--"));
+    print_setting(&format!("{}.dns", family), &list(ip.dns.as_ref()));
+    print_setting(&format!("{}.dns-search", family), &list(ip.dns_search.as_ref()));
+    print_setting(
+        &format!("{}.dns-priority", family),
+        &ip.dns_priority.map_or("--".to_string(), |p| p.to_string()),
+    );
+    print_setting(&format!("{}.routes", family), &list(ip.routes.as_ref()));
+    print_setting(
+        &format!("{}.route-metric", family),
+        &ip.route_metric.map_or("--".to_string(), |m| m.to_string()),
+    );
+    if family == "ipv4" {
+        print_setting("ipv4.link-local", ip.link_local.as_deref().unwrap_or("--"));
+        print_setting("ipv4.dhcp-timeout", &ip.dhcp_timeout.map_or("--".to_string(), |t| t.to_string()));
+        print_setting("ipv4.dad-timeout", &ip.dad_timeout.map_or("--".to_string(), |t| t.to_string()));
+    }
+    if family == "ipv6" {
+        print_setting("ipv6.addr-gen-mode", ip.addr_gen_mode.as_deref().unwrap_or("--"));
+        print_setting("ipv6.ip6-privacy", &ip.ip6_privacy.map_or("--".to_string(), |p| p.to_string()));
+        print_setting("ipv6.accept-ra", match ip.accept_ra {
+            Some(true) => "yes",
+            Some(false) => "no",
+            None => "--",
+        });
+        print_setting("ipv6.dhcp-mode", ip.dhcp_mode.as_deref().unwrap_or("--"));
+        print_setting("ipv6.prefix-delegation", &list(ip.prefix_delegation.as_ref()));
+        print_setting(
+            "ipv6.prefix-delegation-length",
+            &ip.prefix_delegation_length.map_or("--".to_string(), |l| l.to_string()),
+        );
+    }
+}
+
 async fn handle_connection(cmd: &ConnectionCommands, cli: &Cli) -> NetctlResult<()> {
     let config_dir = PathBuf::from("/etc/crrouter/netctl");
 
@@ -1727,26 +2132,31 @@ async fn handle_connection(cmd: &ConnectionCommands, cli: &Cli) -> NetctlResult<
                 // Show specific connection details
                 let config_path = config_dir.join(format!("{}.nctl", conn_id));
                 if config_path.exists() {
+                    let config = NetctlConnectionConfig::from_file(&config_path).await?;
+                    let device = config.connection.interface_name.clone().unwrap_or_else(|| "--".to_string());
+
                     if matches!(cli.mode, OutputMode::Multiline) {
                         // Multiline detailed view
-                        let content = std::fs::read_to_string(&config_path)
-                            .map_err(|e| NetctlError::Io(e))?;
-
-                        println!("connection.id:                          {}", conn_id);
-                        println!("connection.uuid:                        {}", format!("uuid-{}", conn_id));
-                        println!("connection.type:                        ethernet");
-                        println!("connection.interface-name:              --");
-                        println!("connection.autoconnect:                 yes");
-                        println!("");
+                        print_setting("connection.id", &config.connection.name);
+                        print_setting("connection.uuid", &config.connection.uuid);
+                        print_setting("connection.type", &config.connection.conn_type);
+                        print_setting("connection.interface-name", &device);
+                        print_setting("connection.autoconnect", if config.connection.autoconnect { "yes" } else { "no" });
+                        if let Some(ipv4) = &config.ipv4 {
+                            print_ip_settings("ipv4", ipv4);
+                        }
+                        if let Some(ipv6) = &config.ipv6 {
+                            print_ip_settings("ipv6", ipv6);
+                        }
+                        println!();
                         println!("Configuration file: {}", config_path.display());
-                        println!("{}", content);
                     } else {
                         // Single line or terse view
                         let values = vec![
-                            ("NAME", conn_id.to_string()),
-                            ("UUID", format!("uuid-{}", conn_id)),
-                            ("TYPE", "ethernet".to_string()),
-                            ("DEVICE", "--".to_string()),
+                            ("NAME", config.connection.name.clone()),
+                            ("UUID", config.connection.uuid.clone()),
+                            ("TYPE", config.connection.conn_type.clone()),
+                            ("DEVICE", device),
                         ];
                         println!("{}", formatter.format_line(values));
                     }
@@ -1807,7 +2217,7 @@ async fn handle_connection(cmd: &ConnectionCommands, cli: &Cli) -> NetctlResult<
                 println!("Connection '{}' successfully deactivated", id);
             }
         }
-        ConnectionCommands::Add { r#type, con_name, ifname, autoconnect, ssid, password, ip4, gw4, ip6: _, gw6: _ } => {
+        ConnectionCommands::Add { r#type, con_name, ifname, autoconnect, ssid, password, ip4, gw4, ip6, gw6 } => {
             let name = con_name.as_ref()
                 .or(ifname.as_ref())
                 .ok_or(NetctlError::InvalidParameter("Connection name or interface required".to_string()))?;
@@ -1871,6 +2281,28 @@ async fn handle_connection(cmd: &ConnectionCommands, cli: &Cli) -> NetctlResult<
                 config.push_str("\n");
             }
 
+            if let Some(ip) = ip6 {
+                config.push_str("[ipv6]\n");
+                if matches!(ip.as_str(), "auto" | "dhcp" | "link-local" | "ignore" | "disabled") {
+                    config.push_str(&format!("method = \"{}\"\n", ip));
+                } else {
+                    let invalid = || NetctlError::InvalidParameter(format!("Invalid IPv6 address: {}", ip));
+                    let (addr, prefix) = ip.split_once('/').ok_or_else(invalid)?;
+                    let prefix: u8 = prefix.parse().map_err(|_| invalid())?;
+                    if !validation::validate_ip_address(addr)?.is_ipv6() {
+                        return Err(invalid());
+                    }
+                    validation::validate_prefix_len(prefix, true)?;
+                    config.push_str("method = \"manual\"\n");
+                    config.push_str(&format!("address = \"{}\"\n", ip));
+                    if let Some(gw) = gw6 {
+                        validation::validate_ip_address(gw)?;
+                        config.push_str(&format!("gateway = \"{}\"\n", gw));
+                    }
+                }
+                config.push('\n');
+            }
+
             // Write config file with secure permissions (600)
             write_secure_config(&config_path, &config)?;
 
@@ -1991,6 +2423,41 @@ async fn handle_connection(cmd: &ConnectionCommands, cli: &Cli) -> NetctlResult<
 // ============================================================================
 // DEVICE COMMAND HANDLERS
 // ============================================================================
+/// Properties of a DHCPv4 lease as shown by `device show`
+fn dhcp4_lease_fields(lease: &DhcpLease) -> Vec<(&'static str, String)> {
+    let time = |secs: u64| chrono::DateTime::from_timestamp(secs as i64, 0)
+        .map_or(secs.to_string(), |t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string());
+    let mut fields = Vec::new();
+    if let Some(address) = &lease.ip_address {
+        fields.push(("ADDRESS", match lease.prefix_len() {
+            Some(len) => format!("{}/{}", address, len),
+            None => address.clone(),
+        }));
+    }
+    if let Some(mask) = &lease.subnet_mask {
+        fields.push(("SUBNET-MASK", mask.clone()));
+    }
+    if let Some(gateway) = &lease.gateway {
+        fields.push(("GATEWAY", gateway.clone()));
+    }
+    if !lease.dns_servers.is_empty() {
+        fields.push(("DNS", lease.dns_servers.join(", ")));
+    }
+    if let Some(lease_time) = lease.lease_time {
+        fields.push(("LEASE-TIME", lease_time.to_string()));
+    }
+    if let Some(acquired) = lease.acquired_at {
+        fields.push(("ACQUIRED", time(acquired)));
+    }
+    if let Some(expires) = lease.expires_at() {
+        fields.push(("EXPIRES", time(expires)));
+    }
+    if let Some(server) = &lease.server_address {
+        fields.push(("SERVER-ID", server.clone()));
+    }
+    fields
+}
+
 async fn handle_device(cmd: &DeviceCommands, cli: &Cli) -> NetctlResult<()> {
     let iface_ctrl = interface::InterfaceController::new();
 
@@ -2036,6 +2503,10 @@ async fn handle_device(cmd: &DeviceCommands, cli: &Cli) -> NetctlResult<()> {
         DeviceCommands::Show { device } => {
             if let Some(dev) = device {
                 let info = iface_ctrl.get_info(dev).await?;
+                let dhcp4 = DhcpClientController::new().saved_lease(dev).await.ok().flatten()
+                    .filter(|lease| lease.ip_address.is_some() && !lease.is_expired())
+                    .map(|lease| dhcp4_lease_fields(&lease))
+                    .unwrap_or_default();
 
                 if cli.terse {
                     println!("GENERAL.DEVICE:{}", dev);
@@ -2049,6 +2520,9 @@ async fn handle_device(cmd: &DeviceCommands, cli: &Cli) -> NetctlResult<()> {
                     if let Some(mtu) = info.mtu {
                         println!("GENERAL.MTU:{}", mtu);
                     }
+                    for (key, value) in &dhcp4 {
+                        println!("DHCP4.{}:{}", key, value);
+                    }
                 } else {
                     println!("GENERAL");
                     println!("  {:20} {}", "DEVICE:", dev);
@@ -2080,6 +2554,14 @@ async fn handle_device(cmd: &DeviceCommands, cli: &Cli) -> NetctlResult<()> {
                             }
                         }
                     }
+
+                    if !dhcp4.is_empty() {
+                        println!();
+                        println!("DHCP4");
+                        for (key, value) in &dhcp4 {
+                            println!("  {:20} {}", format!("{}:", key), value);
+                        }
+                    }
                 }
             } else {
                 // Show all devices
@@ -2295,7 +2777,7 @@ async fn handle_device_wifi(cmd: &WifiDeviceCommands, cli: &Cli) -> NetctlResult
             if cli.terse {
                 for result in results {
                     let ssid = result.ssid.unwrap_or_else(|| "".to_string());
-                    let signal = result.signal.unwrap_or_else(|| "0".to_string());
+                    let signal = result.signal.unwrap_or(0);
                     println!("{}:{}:{}:{}:*",
                             result.bssid,
                             ssid,
@@ -2313,17 +2795,14 @@ async fn handle_device_wifi(cmd: &WifiDeviceCommands, cli: &Cli) -> NetctlResult
                         "BARS",
                         "SECURITY");
                 for result in results {
-                    let ssid = result.ssid.unwrap_or_else(|| "".to_string());
-                    let freq = result.frequency.unwrap_or(0);
-                    let channel = if freq > 5000 {
-                        (freq - 5000) / 5
-                    } else if freq > 2400 {
-                        (freq - 2407) / 5
-                    } else {
-                        0
+                    let ssid = result.ssid.clone().unwrap_or_default();
+                    let channel = result.channel.unwrap_or(0);
+                    let signal_num = result.signal.unwrap_or(-100);
+                    let signal = result.signal.map(|dbm| format!("{} dBm", dbm)).unwrap_or_default();
+                    let security = match result.security_label() {
+                        label if label.is_empty() => "--".to_string(),
+                        label => label,
                     };
-                    let signal = result.signal.unwrap_or_else(|| "0".to_string());
-                    let signal_num: i32 = signal.trim_end_matches(" dBm").parse().unwrap_or(-100);
                     let bars = if signal_num > -50 {
                         "▂▄▆█"
                     } else if signal_num > -60 {
@@ -2337,14 +2816,14 @@ async fn handle_device_wifi(cmd: &WifiDeviceCommands, cli: &Cli) -> NetctlResult
                     };
 
                     println!("{:3} {:32} {:3} {:4} {:17} {:6} {:8} {:10}",
-                            "",
+                            if result.associated { "*" } else { "" },
                             ssid,
                             "Infra",
                             channel,
                             result.bssid,
                             signal,
                             bars,
-                            "*");
+                            security);
                 }
             }
         }
@@ -2867,29 +3346,117 @@ async fn handle_ap(cmd: &ApCommands, cli: &Cli) -> NetctlResult<()> {
 // DHCP COMMAND HANDLERS
 // ============================================================================
 async fn handle_dhcp(cmd: &DhcpCommands, cli: &Cli) -> NetctlResult<()> {
-    let config_path = PathBuf::from("/run/crrouter/netctl/dora.yaml");
-    let dhcp_ctrl = dhcp::DhcpController::new(config_path);
+    let dhcp_ctrl = dhcp::DhcpController::default();
 
     match cmd {
-        DhcpCommands::Start { interface, range_start, range_end, gateway, dns } => {
-            let config = dhcp::DhcpConfig {
-                interface: interface.clone(),
-                range_start: range_start.clone(),
-                range_end: range_end.clone(),
-                gateway: gateway.clone(),
-                dns_servers: dns.clone(),
-                ..Default::default()
-            };
-
-            dhcp_ctrl.write_config(&config).await?;
+        DhcpCommands::Start(spec) => {
+            let config = spec.to_config()?;
+            dhcp_ctrl.start(&config).await?;
+            if !cli.terse {
+                println!("DHCP server started ({})", dhcp_ctrl.config_path().display());
+            }
+        }
+        DhcpCommands::Stop => {
+            dhcp_ctrl.stop().await?;
             if !cli.terse {
-                println!("DHCP server configuration written");
-                println!("Note: Start dora manually: sudo /usr/local/bin/dora -c /run/crrouter/netctl/dora.yaml");
+                println!("DHCP server stopped");
             }
         }
-        DhcpCommands::Stop | DhcpCommands::Status | DhcpCommands::Leases => {
+        DhcpCommands::Restart => {
+            dhcp_ctrl.restart().await?;
             if !cli.terse {
-                println!("Not fully implemented yet");
+                println!("DHCP server restarted");
+            }
+        }
+        DhcpCommands::Status => {
+            let status = dhcp_ctrl.status().await?;
+            if cli.terse {
+                println!("{}", if status.running { "running" } else { "stopped" });
+                return Ok(());
+            }
+
+            let optional = |value: Option<String>| value.unwrap_or_else(|| "--".to_string());
+            println!("Running:       {}", if status.running { "yes" } else { "no" });
+            if status.running {
+                println!("PID:           {}", optional(status.pid.map(|p| p.to_string())));
+            }
+            println!("Config:        {}", dhcp_ctrl.config_path().display());
+            println!("Lease DB:      {}", dhcp_ctrl.lease_db().display());
+            println!("Active leases: {}", optional(status.active_leases.map(|n| n.to_string())));
+        }
+        DhcpCommands::Test(spec) => {
+            let config = spec.to_config()?;
+            let tester = dhcpm::DhcpmController::new(spec.interface.clone())?
+                .with_timeout(std::time::Duration::from_millis(spec.timeout));
+            let results = match spec.kind {
+                DhcpTestKind::Sequence => tester.run_sequence(&config).await?,
+                _ => vec![tester.send(&config).await?],
+            };
+
+            for result in &results {
+                let sent = dhcpv4::MessageType::from(result.message_type);
+                let optional = |value: &Option<String>| value.clone().unwrap_or_else(|| "--".to_string());
+                if cli.terse {
+                    if result.responses.is_empty() {
+                        println!("{} {}", sent, if result.success { "sent" } else { "none" });
+                    }
+                    for response in &result.responses {
+                        println!("{} {} {} {} {} {}", sent, response.message_type, optional(&response.source_ip),
+                            optional(&response.your_ip), optional(&response.server_ip), response.rtt_ms);
+                    }
+                    continue;
+                }
+
+                println!("{} from {} (xid 0x{:08x})", sent,
+                    result.client_mac.as_deref().unwrap_or("--"), result.xid);
+                if result.responses.len() > 1 {
+                    println!("  Warning: {} replies, more than one DHCP server is answering", result.responses.len());
+                }
+                for response in &result.responses {
+                    println!("  {} from {} ({}) after {} ms", response.message_type,
+                        optional(&response.source_ip), optional(&response.source_mac), response.rtt_ms);
+                    println!("    Address:     {}", optional(&response.your_ip));
+                    println!("    Server ID:   {}", optional(&response.server_ip));
+                    println!("    Subnet mask: {}", optional(&response.subnet_mask));
+                    println!("    Router:      {}", optional(&response.router));
+                    println!("    DNS:         {}", response.dns_servers.as_ref().map_or("--".to_string(), |dns| dns.join(", ")));
+                    println!("    Domain:      {}", optional(&response.domain_name));
+                    println!("    Lease time:  {}", response.lease_time.map_or("--".to_string(), |t| format!("{} s", t)));
+                    if let Some(message) = &response.message {
+                        println!("    Message:     {}", message);
+                    }
+                }
+                if let Some(error) = &result.error {
+                    println!("  {}", error);
+                } else if result.responses.is_empty() {
+                    println!("  Sent, no reply expected");
+                }
+            }
+
+            if let Some(error) = results.iter().find_map(|result| result.error.as_ref()) {
+                return Err(NetctlError::ServiceError(error.clone()));
+            }
+        }
+        DhcpCommands::Leases => {
+            let leases = dhcp_ctrl.leases().await?;
+            let expires = |secs: u64| chrono::DateTime::from_timestamp(secs as i64, 0)
+                .map_or(secs.to_string(), |t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string());
+            if cli.terse {
+                for lease in &leases {
+                    println!("{} {} {}", lease.ip_address,
+                        lease.mac_address.as_deref().unwrap_or(&lease.client_id), lease.expires_at);
+                }
+                return Ok(());
+            }
+
+            if leases.is_empty() {
+                println!("No active leases");
+                return Ok(());
+            }
+            println!("{:<16} {:<20} EXPIRES", "IP", "CLIENT");
+            for lease in &leases {
+                println!("{:<16} {:<20} {}", lease.ip_address.to_string(),
+                    lease.mac_address.as_deref().unwrap_or(&lease.client_id), expires(lease.expires_at));
             }
         }
     }
@@ -2899,9 +3466,54 @@ async fn handle_dhcp(cmd: &DhcpCommands, cli: &Cli) -> NetctlResult<()> {
 // ============================================================================
 // DNS COMMAND HANDLERS
 // ============================================================================
-async fn handle_dns(_cmd: &DnsCommands, cli: &Cli) -> NetctlResult<()> {
-    if !cli.terse {
-        println!("DNS commands not fully implemented yet");
+async fn handle_dns(cmd: &DnsCommands, cli: &Cli) -> NetctlResult<()> {
+    let dns_ctrl = dns::DnsController::new();
+
+    match cmd {
+        DnsCommands::Start { forwarders, listen, port, allow, no_dnssec } => {
+            let config = dns::DnsConfig {
+                listen: listen.clone(),
+                port: *port,
+                access_control: allow.clone(),
+                forwarders: forwarders.clone(),
+                dnssec: !no_dnssec,
+                ..Default::default()
+            };
+
+            dns_ctrl.start(&config).await?;
+            if !cli.terse {
+                println!("DNS server started ({})", dns_ctrl.config_path().display());
+            }
+        }
+        DnsCommands::Stop => {
+            dns_ctrl.stop().await?;
+            if !cli.terse {
+                println!("DNS server stopped");
+            }
+        }
+        DnsCommands::Status => {
+            let status = dns_ctrl.status().await?;
+            if cli.terse {
+                println!("{}", if status.running { "running" } else { "stopped" });
+                return Ok(());
+            }
+
+            let optional = |value: Option<String>| value.unwrap_or_else(|| "--".to_string());
+            println!("Running:    {}", if status.running { "yes" } else { "no" });
+            if status.running {
+                println!("PID:        {}", optional(status.pid.map(|p| p.to_string())));
+                println!("Version:    {}", optional(status.version));
+                println!("Uptime:     {}", optional(status.uptime_secs.map(|s| format!("{}s", s))));
+                println!("Queries:    {}", optional(status.queries.map(|q| q.to_string())));
+                println!("Cache hits: {}", optional(status.cache_hits.map(|h| h.to_string())));
+            }
+        }
+        DnsCommands::Flush { zone } => {
+            dns_ctrl.flush(zone.as_deref()).await?;
+            if !cli.terse {
+                println!("DNS cache flushed");
+            }
+        }
     }
     Ok(())
 }
@@ -2913,22 +3525,44 @@ async fn handle_route(cmd: &RouteCommands, cli: &Cli) -> NetctlResult<()> {
     let route_ctrl = routing::RoutingController::new();
 
     match cmd {
-        RouteCommands::Show => {
+        RouteCommands::Show { table, ipv4, ipv6 } => {
+            let table = match table.as_str() {
+                "all" => None,
+                name => Some(routing::parse_table(name)?),
+            };
+            let routes = route_ctrl.list_routes(table).await?;
             if !cli.terse {
                 println!("Route table:");
             }
-            let output = tokio::process::Command::new("ip")
-                .args(["route", "show"])
-                .output()
-                .await
-                .map_err(|e| NetctlError::CommandFailed {
-                    cmd: "ip route show".to_string(),
-                    code: None,
-                    stderr: e.to_string(),
-                })?;
-            let stdout = String::from_utf8(output.stdout)
-                .unwrap_or_else(|e| String::from_utf8_lossy(&e.into_bytes()).to_string());
-            println!("{}", stdout);
+            let wanted = |route: &&Route| match (ipv4, ipv6) {
+                (true, _) => !route.is_ipv6(),
+                (_, true) => route.is_ipv6(),
+                _ => true,
+            };
+            for route in routes.iter().filter(wanted) {
+                println!("{}", route);
+            }
+        }
+        RouteCommands::Add(spec) => {
+            let route = spec.to_route()?;
+            route_ctrl.add_route(&route).await?;
+            if !cli.terse {
+                println!("Added route {}", route);
+            }
+        }
+        RouteCommands::Replace(spec) => {
+            let route = spec.to_route()?;
+            route_ctrl.replace_route(&route).await?;
+            if !cli.terse {
+                println!("Replaced route {}", route);
+            }
+        }
+        RouteCommands::Del(spec) => {
+            let route = spec.to_route()?;
+            route_ctrl.delete_route(&route).await?;
+            if !cli.terse {
+                println!("Deleted route {}", route);
+            }
         }
         RouteCommands::AddDefault { gateway, interface } => {
             route_ctrl.add_default_gateway(&gateway, interface.as_deref()).await?;
@@ -2936,9 +3570,32 @@ async fn handle_route(cmd: &RouteCommands, cli: &Cli) -> NetctlResult<()> {
                 println!("Added default gateway {}", gateway);
             }
         }
-        RouteCommands::DelDefault => {
+        RouteCommands::DelDefault { gateway, interface } => {
+            let removed = route_ctrl.del_default_gateway(gateway.as_deref(), interface.as_deref()).await?;
+            if !cli.terse {
+                for route in removed {
+                    println!("Deleted route {}", route);
+                }
+            }
+        }
+        RouteCommands::Rule(RuleCommands::Show { ipv4, ipv6 }) => {
+            let rules = route_ctrl.list_rules().await?;
+            for rule in rules.iter().filter(|r| !(*ipv4 && r.ipv6) && (!*ipv6 || r.ipv6)) {
+                println!("{}", rule);
+            }
+        }
+        RouteCommands::Rule(RuleCommands::Add(spec)) => {
+            let rule = spec.to_rule()?;
+            route_ctrl.add_rule(&rule).await?;
+            if !cli.terse {
+                println!("Added rule {}", rule);
+            }
+        }
+        RouteCommands::Rule(RuleCommands::Del(spec)) => {
+            let rule = spec.to_rule()?;
+            route_ctrl.delete_rule(&rule).await?;
             if !cli.terse {
-                println!("Not implemented yet");
+                println!("Deleted rule {}", rule);
             }
         }
     }
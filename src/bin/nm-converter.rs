@@ -180,6 +180,11 @@ fn nm_to_netctl(
         .map(|v| v == "true")
         .unwrap_or(false);
 
+    let autoconnect_priority = connection_section
+        .get("autoconnect-priority")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+
     let interface_name = connection_section.get("interface-name").cloned();
 
     // Build connection section
@@ -188,6 +193,7 @@ fn nm_to_netctl(
         uuid,
         conn_type: conn_type.clone(),
         autoconnect,
+        autoconnect_priority,
         interface_name,
         plugin: None,  // Will be set based on type
     };
@@ -205,6 +211,8 @@ fn nm_to_netctl(
         None
     };
 
+    let ieee8021x = nm_config.get("802-1x").map(Ieee8021xSection::from_nm_keys);
+
     let vpn = if conn_type == "vpn" {
         Some(parse_vpn_section(nm_config)?)
     } else {
@@ -224,10 +232,13 @@ fn nm_to_netctl(
         connection,
         wifi,
         wifi_security,
+        ieee8021x,
         vpn,
         ethernet,
         ipv4,
         ipv6,
+        ipv6_ra: None,
+        routing_rules: Vec::new(),
     })
 }
 
@@ -243,12 +254,16 @@ fn parse_wifi_section(
     let mode = wifi_section.get("mode").cloned().unwrap_or_else(|| "infrastructure".to_string());
     let bssid = wifi_section.get("bssid").cloned();
     let channel = wifi_section.get("channel").and_then(|c| c.parse().ok());
+    let band = wifi_section.get("band").cloned();
+    let hidden = wifi_section.get("hidden").is_some_and(|h| h == "true");
 
     Ok(WifiSection {
         ssid,
         mode,
         bssid,
         channel,
+        band,
+        hidden,
     })
 }
 
@@ -267,11 +282,19 @@ fn parse_wifi_security_section(
 
     let psk = sec_section.get("psk").cloned();
     let password = sec_section.get("password").cloned();
+    // NetworkManager stores PMF as a number, 0 meaning the global default
+    let pmf = match sec_section.get("pmf").map(String::as_str) {
+        Some("1") => Some("disable".to_string()),
+        Some("2") => Some("optional".to_string()),
+        Some("3") => Some("required".to_string()),
+        _ => None,
+    };
 
     Ok(WifiSecuritySection {
         key_mgmt,
         psk,
         password,
+        pmf,
     })
 }
 
@@ -329,29 +352,91 @@ fn parse_ip_section(
         .clone();
 
     // Parse address1, address2, etc.
-    let address = section.get("address1").or_else(|| section.get("address")).cloned();
-
-    let gateway = if let Some(addr) = address.as_ref() {
-        // NetworkManager format: "192.168.1.100/24,192.168.1.1"
-        if let Some(pos) = addr.find(',') {
-            Some(addr[pos + 1..].to_string())
-        } else {
-            section.get("gateway").cloned()
+    let mut address_keys: Vec<(u32, &String)> = section
+        .iter()
+        .filter_map(|(key, value)| key.strip_prefix("address")?.parse().ok().map(|n| (n, value)))
+        .collect();
+    address_keys.sort();
+    let mut raw_addresses: Vec<&String> = address_keys.into_iter().map(|(_, value)| value).collect();
+    if raw_addresses.is_empty() {
+        raw_addresses.extend(section.get("address"));
+    }
+
+    // NetworkManager format: "192.168.1.100/24,192.168.1.1"
+    let mut gateway = section.get("gateway").cloned();
+    let mut addresses = Vec::new();
+    for raw in raw_addresses {
+        match raw.split_once(',') {
+            Some((addr, gw)) => {
+                gateway.get_or_insert_with(|| gw.to_string());
+                addresses.push(addr.to_string());
+            }
+            None => addresses.push(raw.clone()),
         }
-    } else {
-        section.get("gateway").cloned()
-    };
+    }
+    let mut addresses = addresses.into_iter();
+    let address = addresses.next();
+    let addresses: Vec<String> = addresses.collect();
 
     // Parse DNS servers
     let dns = section
         .get("dns")
         .map(|d| d.split(';').filter(|s| !s.is_empty()).map(|s| s.to_string()).collect());
+    let dns_search = section
+        .get("dns-search")
+        .map(|d| d.split(';').filter(|s| !s.is_empty()).map(|s| s.to_string()).collect());
+    let dns_priority = section.get("dns-priority").and_then(|p| p.parse::<i32>().ok());
+
+    // Parse route1, route2, etc. ("dest/len,gateway,metric")
+    let mut route_keys: Vec<(u32, &String)> = section
+        .iter()
+        .filter_map(|(key, value)| key.strip_prefix("route")?.parse().ok().map(|n| (n, value)))
+        .collect();
+    route_keys.sort();
+    let routes: Vec<String> = route_keys.into_iter().map(|(_, value)| value.clone()).collect();
+
+    let route_metric = section
+        .get("route-metric")
+        .and_then(|m| m.parse::<i64>().ok())
+        .and_then(|m| u32::try_from(m).ok());
+
+    // IPv6 interface identifiers and privacy extensions
+    let addr_gen_mode = section.get("addr-gen-mode").map(|mode| match mode.as_str() {
+        "0" => "eui64".to_string(),
+        "1" => "stable-privacy".to_string(),
+        other => other.to_string(),
+    });
+    let ip6_privacy = section
+        .get("ip6-privacy")
+        .and_then(|p| p.parse::<i32>().ok())
+        .and_then(|p| u32::try_from(p).ok());
+
+    // Link-local fallback of DHCP (NM: 4 = fallback)
+    let link_local = section.get("link-local")
+        .filter(|mode| matches!(mode.as_str(), "4" | "fallback"))
+        .map(|_| "fallback".to_string());
+    let dhcp_timeout = section.get("dhcp-timeout").and_then(|t| t.parse::<u64>().ok()).filter(|t| *t > 0);
+    // Same unit (ms) and meaning of -1 (200 ms default) as NetworkManager
+    let dad_timeout = section.get("dad-timeout").and_then(|t| t.parse::<i64>().ok()).filter(|t| *t >= -1);
 
     Ok(IpConfigSection {
         method,
         address,
+        addresses: (!addresses.is_empty()).then_some(addresses),
         gateway,
         dns,
-        routes: None,
+        dns_search,
+        dns_priority,
+        routes: (!routes.is_empty()).then_some(routes),
+        route_metric,
+        addr_gen_mode,
+        ip6_privacy,
+        accept_ra: None,
+        dhcp_mode: None,
+        prefix_delegation: None,
+        prefix_delegation_length: None,
+        link_local,
+        dhcp_timeout,
+        dad_timeout,
     })
 }
@@ -0,0 +1,100 @@
+//! Control of per-interface client tasks
+//!
+//! The DHCPv4 and DHCPv6 clients each run as one task per interface. The
+//! controller keeps a [`ClientHandle`] to the task: commands go in through a
+//! channel, and the task publishes its state and lease through a watch
+//! channel.
+
+use crate::error::{NetctlError, NetctlResult};
+use std::future::Future;
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::task::JoinHandle;
+
+pub(crate) enum Command {
+    Renew,
+    Release(oneshot::Sender<()>),
+    Stop(oneshot::Sender<()>),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ClientStatus<S, L> {
+    pub state: S,
+    pub lease: Option<L>,
+}
+
+/// Handle to a running client task
+pub(crate) struct ClientHandle<S, L> {
+    /// Protocol name for errors
+    name: &'static str,
+    commands: mpsc::Sender<Command>,
+    status: watch::Receiver<ClientStatus<S, L>>,
+    task: JoinHandle<()>,
+}
+
+impl<S: Copy, L: Clone> ClientHandle<S, L> {
+    /// Spawn `run`, which receives the commands and publishes the status,
+    /// starting out in `state`
+    pub fn spawn<F, Fut>(name: &'static str, state: S, run: F) -> Self
+    where
+        F: FnOnce(mpsc::Receiver<Command>, watch::Sender<ClientStatus<S, L>>) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let (commands_tx, commands) = mpsc::channel(8);
+        let (status_tx, status) = watch::channel(ClientStatus { state, lease: None });
+        Self {
+            name,
+            commands: commands_tx,
+            status,
+            task: tokio::spawn(run(commands, status_tx)),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    pub fn state(&self) -> S {
+        self.status.borrow().state
+    }
+
+    pub fn lease(&self) -> Option<L> {
+        self.status.borrow().lease.clone()
+    }
+
+    pub async fn renew(&self) -> NetctlResult<()> {
+        self.commands.send(Command::Renew).await
+            .map_err(|_| NetctlError::InvalidState(format!("{} client has exited", self.name)))
+    }
+
+    /// Stop the client without releasing the lease
+    pub async fn stop(self) {
+        self.finish(Command::Stop).await
+    }
+
+    /// Give the lease back to the server and stop the client
+    pub async fn release(self) {
+        self.finish(Command::Release).await
+    }
+
+    async fn finish(self, command: fn(oneshot::Sender<()>) -> Command) {
+        let (tx, rx) = oneshot::channel();
+        if self.commands.send(command(tx)).await.is_ok() {
+            let _ = rx.await;
+        }
+        let _ = self.task.await;
+    }
+}
+
+/// Run `future` unless a command arrives first
+///
+/// A closed command channel means the controller is gone and counts as a
+/// stop request.
+pub(crate) async fn or_command<T>(
+    commands: &mut mpsc::Receiver<Command>,
+    future: impl Future<Output = T>,
+) -> Result<T, Command> {
+    tokio::select! {
+        output = future => Ok(output),
+        command = commands.recv() => Err(command.unwrap_or_else(|| Command::Stop(oneshot::channel().0))),
+    }
+}
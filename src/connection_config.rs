@@ -1,11 +1,20 @@
 //! Connection configuration file reading and management
 
+use crate::arp::ArpProbeConfig;
+use crate::dhcpv6_client::Dhcpv6ClientOptions;
 use crate::error::{NetctlError, NetctlResult};
+use crate::ra::{RaConfig, RaPrefix};
+use crate::routing::{self, Route, RoutingRule};
+use crate::validation;
+use crate::wifi::WifiBand;
+use crate::wpa_supplicant::{EapConfig, EapMethod, Pmf, WpaNetwork, WpaSecurity};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::net::IpAddr;
 use std::path::Path;
+use std::time::Duration;
 use tokio::fs;
-use tracing::info;
+use tracing::{info, warn};
 
 /// Netctl connection configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,6 +24,9 @@ pub struct NetctlConnectionConfig {
     pub wifi: Option<WifiSection>,
     #[serde(rename = "wifi-security", skip_serializing_if = "Option::is_none")]
     pub wifi_security: Option<WifiSecuritySection>,
+    /// 802.1X authentication of WPA-Enterprise networks
+    #[serde(rename = "802-1x", skip_serializing_if = "Option::is_none")]
+    pub ieee8021x: Option<Ieee8021xSection>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub vpn: Option<VpnSection>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -23,6 +35,11 @@ pub struct NetctlConnectionConfig {
     pub ipv4: Option<IpConfigSection>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ipv6: Option<IpConfigSection>,
+    /// Router advertisements sent on the interface
+    #[serde(rename = "ipv6-ra", skip_serializing_if = "Option::is_none")]
+    pub ipv6_ra: Option<Ipv6RaSection>,
+    #[serde(rename = "routing-rule", default, skip_serializing_if = "Vec::is_empty")]
+    pub routing_rules: Vec<RoutingRuleSection>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,6 +50,9 @@ pub struct ConnectionSection {
     pub conn_type: String,
     #[serde(default)]
     pub autoconnect: bool,
+    /// Preference among WiFi profiles when auto-connecting, higher first
+    #[serde(rename = "autoconnect-priority", default, skip_serializing_if = "is_zero")]
+    pub autoconnect_priority: i32,
     #[serde(rename = "interface-name", skip_serializing_if = "Option::is_none")]
     pub interface_name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -48,6 +68,19 @@ pub struct WifiSection {
     pub bssid: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub channel: Option<u32>,
+    /// "2.4GHz", "5GHz" or "6GHz"; restricts the connection to the band
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub band: Option<String>,
+    /// The SSID is not broadcast and has to be probed for
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hidden: bool,
+}
+
+/// ARP probing time of static addresses without `dad-timeout`, as in NetworkManager
+const DEFAULT_DAD_TIMEOUT_MS: u64 = 200;
+
+fn is_zero(value: &i32) -> bool {
+    *value == 0
 }
 
 fn default_wifi_mode() -> String {
@@ -62,6 +95,208 @@ pub struct WifiSecuritySection {
     pub psk: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub password: Option<String>,
+    /// Management frame protection: "default", "disable", "optional" or "required"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pmf: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Ieee8021xSection {
+    /// EAP methods: "tls", "peap", "ttls", "pwd"
+    #[serde(default)]
+    pub eap: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anonymous_identity: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// Inner authentication of PEAP and TTLS, e.g. "mschapv2"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phase2_auth: Option<String>,
+    /// Inner EAP method of TTLS
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phase2_autheap: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_cert: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private_key_password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain_suffix_match: Option<String>,
+}
+
+impl WifiSection {
+    /// The wpa_supplicant network for this section and its security settings
+    pub fn wpa_network(
+        &self,
+        security: Option<&WifiSecuritySection>,
+        ieee8021x: Option<&Ieee8021xSection>,
+    ) -> NetctlResult<WpaNetwork> {
+        let (security, pmf) = match security {
+            Some(security) => (security.wpa_security(ieee8021x)?, security.pmf()?),
+            None => (WpaSecurity::Open, None),
+        };
+        Ok(WpaNetwork {
+            ssid: self.ssid.clone(),
+            security,
+            hidden: self.hidden,
+            pmf,
+            bssid: self.bssid.clone(),
+            frequencies: self.frequencies()?,
+        })
+    }
+
+    /// Frequencies the connection is restricted to by `channel` and `band`
+    pub fn frequencies(&self) -> NetctlResult<Vec<u32>> {
+        let band = self.band.as_deref().map(str::parse::<WifiBand>).transpose()
+            .map_err(|e| NetctlError::ConfigError(e.to_string()))?;
+        match self.channel {
+            Some(channel) => {
+                let band = band.or_else(|| WifiBand::of_channel(channel)).ok_or_else(|| {
+                    NetctlError::ConfigError(format!("WiFi channel {} needs a band", channel))
+                })?;
+                let freq = band.channel_frequency(channel).ok_or_else(|| {
+                    NetctlError::ConfigError(format!("Invalid WiFi channel {} for band {:?}", channel, band))
+                })?;
+                Ok(vec![freq])
+            }
+            None => Ok(band.map(WifiBand::frequencies).unwrap_or_default()),
+        }
+    }
+}
+
+impl WifiSecuritySection {
+    /// The wpa_supplicant security for `key-mgmt`
+    ///
+    /// The EAP modes take their credentials from `ieee8021x`.
+    pub fn wpa_security(&self, ieee8021x: Option<&Ieee8021xSection>) -> NetctlResult<WpaSecurity> {
+        let secret = || {
+            self.psk.clone().or_else(|| self.password.clone()).ok_or_else(|| {
+                NetctlError::ConfigError(format!("key-mgmt {} requires a psk", self.key_mgmt))
+            })
+        };
+        Ok(match self.key_mgmt.as_str() {
+            "none" => WpaSecurity::Open,
+            "owe" => WpaSecurity::Owe,
+            "wpa-psk" => WpaSecurity::Psk(secret()?),
+            "sae" => WpaSecurity::Sae(secret()?),
+            "wpa-psk+sae" => WpaSecurity::PskSae(secret()?),
+            key_mgmt @ ("wpa-eap" | "wpa-eap-suite-b-192") => {
+                let ieee8021x = ieee8021x.ok_or_else(|| {
+                    NetctlError::ConfigError(format!("key-mgmt {} requires an [802-1x] section", key_mgmt))
+                })?;
+                WpaSecurity::Eap {
+                    config: Box::new(ieee8021x.to_eap_config()?),
+                    suite_b_192: key_mgmt == "wpa-eap-suite-b-192",
+                }
+            }
+            other => return Err(NetctlError::ConfigError(format!("Unsupported key-mgmt: {}", other))),
+        })
+    }
+
+    /// The configured PMF setting, `None` to use the default of the key management
+    pub fn pmf(&self) -> NetctlResult<Option<Pmf>> {
+        match self.pmf.as_deref() {
+            None | Some("default") | Some("0") => Ok(None),
+            Some(pmf) => pmf.parse().map(Some).map_err(|e: NetctlError| NetctlError::ConfigError(e.to_string())),
+        }
+    }
+}
+
+impl Ieee8021xSection {
+    /// Build from the keys of a NetworkManager `[802-1x]` setting
+    pub fn from_nm_keys(keys: &HashMap<String, String>) -> Self {
+        let get = |key: &str| keys.get(key).cloned();
+        // Certificates are paths, possibly written as file:// URIs
+        let path = |key: &str| keys.get(key).map(|value| value.strip_prefix("file://").unwrap_or(value).to_string());
+
+        Self {
+            eap: keys.get("eap")
+                .map(|methods| methods.split(';').filter(|m| !m.is_empty()).map(str::to_string).collect())
+                .unwrap_or_default(),
+            identity: get("identity"),
+            anonymous_identity: get("anonymous-identity"),
+            password: get("password"),
+            phase2_auth: get("phase2-auth"),
+            phase2_autheap: get("phase2-autheap"),
+            ca_cert: path("ca-cert"),
+            client_cert: path("client-cert"),
+            private_key: path("private-key"),
+            private_key_password: get("private-key-password"),
+            domain_suffix_match: get("domain-suffix-match"),
+        }
+    }
+
+    /// Validated EAP settings for wpa_supplicant
+    pub fn to_eap_config(&self) -> NetctlResult<EapConfig> {
+        let missing = |key: &str, method: EapMethod| {
+            NetctlError::ConfigError(format!("802-1x {} is required for EAP-{}", key, method))
+        };
+        let methods = self.eap.iter().map(|method| method.parse()).collect::<NetctlResult<Vec<EapMethod>>>()
+            .map_err(|e| NetctlError::ConfigError(format!("Invalid 802-1x eap: {}", e)))?;
+        if methods.is_empty() {
+            return Err(NetctlError::ConfigError("802-1x eap must list at least one method".to_string()));
+        }
+
+        for &method in &methods {
+            if self.identity.is_none() {
+                return Err(missing("identity", method));
+            }
+            match method {
+                EapMethod::Tls => {
+                    if self.client_cert.is_none() {
+                        return Err(missing("client-cert", method));
+                    }
+                    if self.private_key.is_none() {
+                        return Err(missing("private-key", method));
+                    }
+                }
+                EapMethod::Peap | EapMethod::Pwd if self.password.is_none() => return Err(missing("password", method)),
+                EapMethod::Ttls => {
+                    if self.password.is_none() {
+                        return Err(missing("password", method));
+                    }
+                    if self.phase2_auth.is_none() && self.phase2_autheap.is_none() {
+                        return Err(missing("phase2-auth or phase2-autheap", method));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let certificate = |key: &str, path: &Option<String>| -> NetctlResult<Option<String>> {
+            let Some(path) = path else { return Ok(None) };
+            // NetworkManager keyfiles may store paths as file:// URIs
+            let path = path.strip_prefix("file://").unwrap_or(path);
+            if !path.starts_with('/') {
+                return Err(NetctlError::ConfigError(format!("802-1x {} must be an absolute path: {}", key, path)));
+            }
+            Ok(Some(path.to_string()))
+        };
+        let ca_cert = certificate("ca-cert", &self.ca_cert)?;
+        if ca_cert.is_none() && methods.iter().any(|method| *method != EapMethod::Pwd) {
+            warn!("802-1x has no ca-cert; the authentication server will not be verified");
+        }
+
+        Ok(EapConfig {
+            methods,
+            identity: self.identity.clone(),
+            anonymous_identity: self.anonymous_identity.clone(),
+            password: self.password.clone(),
+            phase2_auth: self.phase2_auth.clone(),
+            phase2_autheap: self.phase2_autheap.clone(),
+            ca_cert,
+            client_cert: certificate("client-cert", &self.client_cert)?,
+            private_key: certificate("private-key", &self.private_key)?,
+            private_key_password: self.private_key_password.clone(),
+            domain_suffix_match: self.domain_suffix_match.clone(),
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -165,15 +400,443 @@ pub struct EthernetSection {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IpConfigSection {
-    pub method: String,  // auto, manual, ignore, link-local
+    /// IPv4: auto, manual, link-local, ignore
+    /// IPv6: auto, dhcp, manual, link-local, ignore, disabled
+    pub method: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub address: Option<String>,
+    /// Additional addresses ("ip/prefix")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub addresses: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub gateway: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub dns: Option<Vec<String>>,
+    /// DNS search domains
+    #[serde(rename = "dns-search", skip_serializing_if = "Option::is_none")]
+    pub dns_search: Option<Vec<String>>,
+    /// Order of this connection's DNS settings in resolv.conf; lower values
+    /// come first (defaults to 50 for VPNs and 100 otherwise)
+    #[serde(rename = "dns-priority", skip_serializing_if = "Option::is_none")]
+    pub dns_priority: Option<i32>,
+    /// Static routes: "dest[/len] [via gw] [metric N] [table T]", or the
+    /// NetworkManager form "dest/len,gw,metric"
     #[serde(skip_serializing_if = "Option::is_none")]
     pub routes: Option<Vec<String>>,
+    /// Metric of the gateway and static routes of this connection
+    #[serde(rename = "route-metric", skip_serializing_if = "Option::is_none")]
+    pub route_metric: Option<u32>,
+    /// IPv6 interface identifiers: "eui64" or "stable-privacy"
+    #[serde(rename = "addr-gen-mode", skip_serializing_if = "Option::is_none")]
+    pub addr_gen_mode: Option<String>,
+    /// IPv6 privacy extensions (RFC 4941): 0 off, 1 on, 2 on and preferred
+    #[serde(rename = "ip6-privacy", skip_serializing_if = "Option::is_none")]
+    pub ip6_privacy: Option<u32>,
+    /// Accept IPv6 router advertisements (defaults to on for auto and dhcp)
+    #[serde(rename = "accept-ra", skip_serializing_if = "Option::is_none")]
+    pub accept_ra: Option<bool>,
+    /// DHCPv6: "stateful" requests addresses, "stateless" only other
+    /// configuration (defaults to stateful for method dhcp)
+    #[serde(rename = "dhcp-mode", skip_serializing_if = "Option::is_none")]
+    pub dhcp_mode: Option<String>,
+    /// LAN interfaces that each get a /64 of a prefix delegated by the
+    /// DHCPv6 server
+    #[serde(rename = "prefix-delegation", skip_serializing_if = "Option::is_none")]
+    pub prefix_delegation: Option<Vec<String>>,
+    /// Prefix length to ask the DHCPv6 server to delegate
+    #[serde(rename = "prefix-delegation-length", skip_serializing_if = "Option::is_none")]
+    pub prefix_delegation_length: Option<u8>,
+    /// IPv4 link-local address next to DHCP: "disabled" (default) or
+    /// "fallback" when no lease arrives within `dhcp-timeout`
+    #[serde(rename = "link-local", skip_serializing_if = "Option::is_none")]
+    pub link_local: Option<String>,
+    /// Seconds to wait for a DHCP lease before falling back to a
+    /// link-local address
+    #[serde(rename = "dhcp-timeout", skip_serializing_if = "Option::is_none")]
+    pub dhcp_timeout: Option<u64>,
+    /// Milliseconds of ARP probing before static IPv4 addresses are
+    /// configured: -1 (default) for 200 ms as in NetworkManager, 0 to skip
+    /// probing and conflict monitoring
+    #[serde(rename = "dad-timeout", skip_serializing_if = "Option::is_none")]
+    pub dad_timeout: Option<i64>,
+}
+
+/// Default of `dhcp-timeout`
+const DEFAULT_DHCP_TIMEOUT: u64 = 30;
+
+impl IpConfigSection {
+    /// Static addresses from `address` and `addresses`
+    pub fn all_addresses(&self, ipv6: bool) -> NetctlResult<Vec<(IpAddr, u8)>> {
+        self.address.iter()
+            .chain(self.addresses.iter().flatten())
+            .map(|address| {
+                let invalid = || NetctlError::ConfigError(format!("Invalid IP address format: {}", address));
+                let (ip, prefix) = address.split_once('/').ok_or_else(invalid)?;
+                let ip: IpAddr = ip.parse().map_err(|_| invalid())?;
+                let prefix: u8 = prefix.parse().map_err(|_| {
+                    NetctlError::ConfigError(format!("Invalid prefix length: {}", prefix))
+                })?;
+                if ip.is_ipv6() != ipv6 {
+                    return Err(NetctlError::ConfigError(format!(
+                        "{} is not an {} address", address, if ipv6 { "IPv6" } else { "IPv4" }
+                    )));
+                }
+                validation::validate_prefix_len(prefix, ipv6)?;
+                Ok((ip, prefix))
+            })
+            .collect()
+    }
+
+    /// Value of the `addr_gen_mode` sysctl for `addr-gen-mode`
+    pub fn addr_gen_mode_sysctl(&self) -> NetctlResult<Option<u32>> {
+        match self.addr_gen_mode.as_deref() {
+            None => Ok(None),
+            Some("eui64") => Ok(Some(0)),
+            // Stable privacy addresses with a kernel-generated secret
+            Some("stable-privacy") => Ok(Some(3)),
+            Some(other) => Err(NetctlError::ConfigError(format!("Invalid addr-gen-mode: {}", other))),
+        }
+    }
+
+    /// DHCPv6 client options for an `[ipv6]` section, `None` if the section
+    /// does not use DHCPv6
+    ///
+    /// Method "dhcp" always runs the client; "auto" only when `dhcp-mode` or
+    /// `prefix-delegation` is set, next to SLAAC.
+    pub fn dhcpv6_options(&self) -> NetctlResult<Option<Dhcpv6ClientOptions>> {
+        let wanted = match self.method.as_str() {
+            "dhcp" => true,
+            "auto" => self.dhcp_mode.is_some() || self.prefix_delegation.is_some(),
+            _ => false,
+        };
+        if !wanted {
+            return Ok(None);
+        }
+
+        let mut options = match self.dhcp_mode.as_deref() {
+            Some("stateful") => Dhcpv6ClientOptions::stateful(),
+            Some("stateless") => Dhcpv6ClientOptions::stateless(),
+            // Without a mode, "auto" leaves the address to SLAAC
+            None if self.method == "dhcp" => Dhcpv6ClientOptions::stateful(),
+            None => Dhcpv6ClientOptions::stateless(),
+            Some(other) => return Err(NetctlError::ConfigError(format!("Invalid dhcp-mode: {}", other))),
+        };
+        if let Some(lan_interfaces) = &self.prefix_delegation {
+            for interface in lan_interfaces {
+                validation::validate_interface_name(interface)?;
+            }
+            if let Some(len) = self.prefix_delegation_length {
+                if !(1..=64).contains(&len) {
+                    return Err(NetctlError::ConfigError(format!("Invalid prefix-delegation-length: {}", len)));
+                }
+            }
+            options = options.with_prefix_delegation(self.prefix_delegation_length, lan_interfaces.clone());
+        }
+        Ok(Some(options))
+    }
+
+    /// Time after which DHCP falls back to an IPv4 link-local address,
+    /// `None` unless `link-local = "fallback"`
+    pub fn link_local_fallback(&self) -> NetctlResult<Option<Duration>> {
+        match self.link_local.as_deref() {
+            None | Some("disabled") => Ok(None),
+            Some("fallback") => Ok(Some(Duration::from_secs(self.dhcp_timeout.unwrap_or(DEFAULT_DHCP_TIMEOUT)))),
+            Some(other) => Err(NetctlError::ConfigError(format!("Invalid link-local: {}", other))),
+        }
+    }
+
+    /// ARP probing of static IPv4 addresses, `None` if `dad-timeout = 0`
+    ///
+    /// Three probes are spread over the timeout, which defaults to
+    /// NetworkManager's 200 ms rather than the seconds of RFC 5227 timing.
+    pub fn arp_probe_config(&self) -> NetctlResult<Option<ArpProbeConfig>> {
+        let ms = match self.dad_timeout {
+            None | Some(-1) => DEFAULT_DAD_TIMEOUT_MS,
+            Some(0) => return Ok(None),
+            Some(ms) if ms > 0 => ms.unsigned_abs(),
+            Some(ms) => return Err(NetctlError::ConfigError(format!("Invalid dad-timeout: {}", ms))),
+        };
+        let total = Duration::from_millis(ms);
+        let step = total / 3;
+        Ok(Some(ArpProbeConfig {
+            probe_wait: Duration::ZERO,
+            probe_num: 3,
+            probe_min: step,
+            probe_max: step,
+            announce_wait: total - step * 2,
+            ..ArpProbeConfig::default()
+        }))
+    }
+
+    /// Default route via the configured gateway
+    pub fn gateway_route(&self, interface: &str, ipv6: bool, metric: u32) -> NetctlResult<Option<Route>> {
+        let Some(gateway) = &self.gateway else {
+            return Ok(None);
+        };
+        let gateway = validation::validate_ip_address(gateway)?;
+        if gateway.is_ipv6() != ipv6 {
+            return Err(NetctlError::ConfigError(format!("Gateway {} does not match the address family", gateway)));
+        }
+        let mut route = Route::default_via(gateway);
+        route.interface = Some(interface.to_string());
+        route.metric = Some(metric);
+        route.validate()?;
+        Ok(Some(route))
+    }
+
+    /// Static routes from `routes`, bound to the interface
+    ///
+    /// Routes without an explicit metric get `metric`.
+    pub fn static_routes(&self, interface: &str, ipv6: bool, metric: u32) -> NetctlResult<Vec<Route>> {
+        let mut routes = Vec::new();
+        for spec in self.routes.iter().flatten() {
+            let mut route = Self::parse_route(spec, ipv6)?;
+            if route.is_ipv6() != ipv6 {
+                return Err(NetctlError::ConfigError(format!("Route {} does not match the address family", spec)));
+            }
+            route.interface = Some(interface.to_string());
+            route.metric.get_or_insert(metric);
+            route.scope = route.implied_scope();
+            route.validate()?;
+            routes.push(route);
+        }
+        Ok(routes)
+    }
+
+    /// Parse a single `routes` entry
+    pub fn parse_route(spec: &str, ipv6: bool) -> NetctlResult<Route> {
+        let invalid = || NetctlError::ConfigError(format!("Invalid route: {}", spec));
+
+        let (destination, gateway, metric, table) = if spec.contains(',') {
+            let mut fields = spec.split(',').map(str::trim);
+            let destination = fields.next().unwrap_or_default();
+            let gateway = fields.next().filter(|gw| !gw.is_empty());
+            let metric = fields.next().filter(|m| !m.is_empty());
+            if fields.next().is_some() {
+                return Err(invalid());
+            }
+            (destination, gateway, metric, None)
+        } else {
+            let mut words = spec.split_whitespace();
+            let destination = words.next().ok_or_else(invalid)?;
+            let (mut gateway, mut metric, mut table) = (None, None, None);
+            while let Some(key) = words.next() {
+                let value = words.next().ok_or_else(invalid)?;
+                match key {
+                    "via" => gateway = Some(value),
+                    "metric" => metric = Some(value),
+                    "table" => table = Some(value),
+                    _ => return Err(invalid()),
+                }
+            }
+            (destination, gateway, metric, table)
+        };
+
+        let (dest, prefix_len) = Route::parse_destination(destination, ipv6)?;
+        let mut route = Route::new(dest, prefix_len);
+        if let Some(gateway) = gateway {
+            route.gateway = Some(validation::validate_ip_address(gateway)?);
+        }
+        if let Some(metric) = metric {
+            route.metric = Some(metric.parse().map_err(|_| invalid())?);
+        }
+        if let Some(table) = table {
+            route.table = routing::parse_table(table)?;
+        }
+        Ok(route)
+    }
+}
+
+/// Router advertisements (`[ipv6-ra]`) for hosts on the interface
+///
+/// Prefixes delegated to the interface by the DHCPv6 client of another
+/// connection are advertised in addition to the configured ones.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Ipv6RaSection {
+    /// Prefixes to advertise ("prefix/len"); defaults to the prefixes of
+    /// the static `[ipv6]` addresses
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prefixes: Option<Vec<String>>,
+    /// Valid lifetime of the configured prefixes in seconds
+    #[serde(rename = "valid-lifetime", skip_serializing_if = "Option::is_none")]
+    pub valid_lifetime: Option<u32>,
+    /// Preferred lifetime of the configured prefixes in seconds
+    #[serde(rename = "preferred-lifetime", skip_serializing_if = "Option::is_none")]
+    pub preferred_lifetime: Option<u32>,
+    /// Hosts get addresses through DHCPv6 (M flag)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub managed: bool,
+    /// Hosts get other configuration through DHCPv6 (O flag)
+    #[serde(rename = "other-config", default, skip_serializing_if = "std::ops::Not::not")]
+    pub other_config: bool,
+    /// Seconds hosts may use this router as default router, 0 for none
+    #[serde(rename = "router-lifetime", skip_serializing_if = "Option::is_none")]
+    pub router_lifetime: Option<u16>,
+    /// low, medium (default) or high
+    #[serde(rename = "router-preference", skip_serializing_if = "Option::is_none")]
+    pub router_preference: Option<String>,
+    /// Bounds of the interval between advertisements in seconds
+    #[serde(rename = "min-interval", skip_serializing_if = "Option::is_none")]
+    pub min_interval: Option<u64>,
+    #[serde(rename = "max-interval", skip_serializing_if = "Option::is_none")]
+    pub max_interval: Option<u64>,
+    #[serde(rename = "hop-limit", skip_serializing_if = "Option::is_none")]
+    pub hop_limit: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<u32>,
+    /// More-specific routes: "prefix/len[,low|medium|high]"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub routes: Option<Vec<String>>,
+    /// DNS servers announced to hosts (RDNSS)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dns: Option<Vec<String>>,
+    /// Search domains announced to hosts (DNSSL)
+    #[serde(rename = "dns-search", skip_serializing_if = "Option::is_none")]
+    pub dns_search: Option<Vec<String>>,
+    /// Lifetime of the DNS servers and search domains in seconds
+    #[serde(rename = "dns-lifetime", skip_serializing_if = "Option::is_none")]
+    pub dns_lifetime: Option<u32>,
+}
+
+impl Ipv6RaSection {
+    /// Sender configuration, taking default prefixes from `ipv6`
+    pub fn to_ra_config(&self, ipv6: Option<&IpConfigSection>) -> NetctlResult<RaConfig> {
+        let invalid = |e: NetctlError| NetctlError::ConfigError(format!("[ipv6-ra]: {}", e));
+        let mut config = RaConfig::default();
+
+        let candidates: Vec<RaPrefix> = match &self.prefixes {
+            Some(prefixes) => prefixes.iter().map(|p| p.parse()).collect::<NetctlResult<_>>().map_err(invalid)?,
+            None => match ipv6.filter(|ip| ip.method == "manual") {
+                Some(ipv6) => ipv6.all_addresses(true)?.into_iter()
+                    .map(|(address, prefix_len)| match address {
+                        IpAddr::V6(address) => RaPrefix::new(address, prefix_len),
+                        IpAddr::V4(_) => unreachable!("all_addresses checks the family"),
+                    })
+                    .filter(|prefix| !prefix.prefix.is_unicast_link_local())
+                    .collect(),
+                None => Vec::new(),
+            },
+        };
+        for prefix in candidates {
+            let prefix = prefix.with_lifetimes(
+                self.valid_lifetime.unwrap_or(prefix.valid_lifetime),
+                self.preferred_lifetime.unwrap_or(prefix.preferred_lifetime),
+            );
+            if !config.prefixes.contains(&prefix) {
+                config.prefixes.push(prefix);
+            }
+        }
+
+        config.managed = self.managed;
+        config.other_config = self.other_config;
+        if let Some(lifetime) = self.router_lifetime {
+            config.router_lifetime = lifetime;
+        }
+        if let Some(preference) = &self.router_preference {
+            config.router_preference = preference.parse().map_err(invalid)?;
+        }
+        if let Some(max) = self.max_interval {
+            config.max_interval = Duration::from_secs(max);
+            // Keep the default ratio of RFC 4861 unless both are given
+            config.min_interval = Duration::from_secs(max / 3);
+        }
+        if let Some(min) = self.min_interval {
+            config.min_interval = Duration::from_secs(min);
+        }
+        if let Some(hop_limit) = self.hop_limit {
+            config.hop_limit = hop_limit;
+        }
+        config.mtu = self.mtu;
+        config.routes = self.routes.iter().flatten()
+            .map(|route| route.parse())
+            .collect::<NetctlResult<_>>()
+            .map_err(invalid)?;
+        config.dns_servers = self.dns.iter().flatten()
+            .map(|server| server.parse().map_err(|_| {
+                NetctlError::ConfigError(format!("[ipv6-ra]: Invalid IPv6 DNS server: {}", server))
+            }))
+            .collect::<NetctlResult<_>>()?;
+        config.dns_search = self.dns_search.clone().unwrap_or_default();
+        config.dns_lifetime = self.dns_lifetime;
+
+        config.validate().map_err(invalid)?;
+        Ok(config)
+    }
+}
+
+/// Routing policy rule (`[[routing-rule]]`) installed while the connection
+/// is active
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RoutingRuleSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u32>,
+    /// Source prefix, e.g. "10.10.0.0/24"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    /// Destination prefix
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    /// "mark" or "mark/mask", decimal or 0x-prefixed hex
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fwmark: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iif: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oif: Option<String>,
+    /// "start-end"
+    #[serde(rename = "uid-range", skip_serializing_if = "Option::is_none")]
+    pub uid_range: Option<String>,
+    /// Table name ("main", "default", "local") or number
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub table: Option<String>,
+    /// lookup (default), blackhole, unreachable, prohibit or nop
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    /// "ipv4" (default) or "ipv6"; implied by IPv6 prefixes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub family: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub invert: bool,
+}
+
+impl RoutingRuleSection {
+    /// Convert to a routing rule
+    pub fn to_rule(&self) -> NetctlResult<RoutingRule> {
+        let ipv6 = match self.family.as_deref() {
+            None | Some("ipv4") => false,
+            Some("ipv6") => true,
+            Some(other) => {
+                return Err(NetctlError::ConfigError(format!("Invalid routing rule family: {}", other)));
+            }
+        };
+
+        let mut rule = RoutingRule {
+            ipv6,
+            priority: self.priority,
+            iif: self.iif.clone(),
+            oif: self.oif.clone(),
+            table: self.table.as_deref().map(routing::parse_table).transpose()?,
+            action: self.action.as_deref().map(str::parse).transpose()?.unwrap_or_default(),
+            invert: self.invert,
+            ..Default::default()
+        };
+        if let Some(from) = &self.from {
+            let (addr, len) = Route::parse_destination(from, ipv6)?;
+            rule = rule.from_prefix(addr, len);
+        }
+        if let Some(to) = &self.to {
+            let (addr, len) = Route::parse_destination(to, ipv6)?;
+            rule = rule.to_prefix(addr, len);
+        }
+        if let Some(fwmark) = &self.fwmark {
+            let (mark, mask) = RoutingRule::parse_fwmark(fwmark)?;
+            rule.fwmark = Some(mark);
+            rule.fwmask = mask;
+        }
+        rule.uid_range = self.uid_range.as_deref().map(RoutingRule::parse_uid_range).transpose()?;
+        rule.validate()?;
+        Ok(rule)
+    }
 }
 
 impl NetctlConnectionConfig {
@@ -201,6 +864,13 @@ impl NetctlConnectionConfig {
         Ok(())
     }
 
+    /// The wpa_supplicant network of a WiFi connection
+    pub fn wpa_network(&self) -> NetctlResult<WpaNetwork> {
+        let wifi = self.wifi.as_ref()
+            .ok_or_else(|| NetctlError::ConfigError("WiFi connection must have [wifi] section".to_string()))?;
+        wifi.wpa_network(self.wifi_security.as_ref(), self.ieee8021x.as_ref())
+    }
+
     /// Convert to plugin ConnectionConfig format
     pub fn to_plugin_config(&self) -> crate::plugin::ConnectionConfig {
         let mut settings = HashMap::new();
@@ -252,6 +922,14 @@ impl NetctlConnectionConfig {
                 settings.insert("config_file".to_string(), serde_json::json!(config_file));
             }
             settings.insert("connection-type".to_string(), serde_json::json!(vpn.connection_type));
+            settings.insert("vpn_type".to_string(), serde_json::json!(vpn.connection_type));
+
+            // The WireGuard backend takes the [vpn.wireguard] keys as they are
+            if let Some(serde_json::Value::Object(wireguard)) = vpn.wireguard.as_ref()
+                .and_then(|wg| serde_json::to_value(wg).ok())
+            {
+                settings.extend(wireguard);
+            }
         }
 
         // Add IP settings
@@ -266,6 +944,19 @@ impl NetctlConnectionConfig {
                 settings.insert("ipv4_dns".to_string(), serde_json::json!(dns));
             }
         }
+        if let Some(dns) = self.ipv6.as_ref().and_then(|ipv6| ipv6.dns.as_ref()) {
+            settings.insert("ipv6_dns".to_string(), serde_json::json!(dns));
+        }
+
+        // Search and routing domains of both families
+        let ip_sections = || self.ipv4.iter().chain(self.ipv6.iter());
+        let dns_search: Vec<&String> = ip_sections().flat_map(|ip| ip.dns_search.iter().flatten()).collect();
+        if !dns_search.is_empty() {
+            settings.insert("dns_search".to_string(), serde_json::json!(dns_search));
+        }
+        if let Some(priority) = ip_sections().filter_map(|ip| ip.dns_priority).min() {
+            settings.insert("dns_priority".to_string(), serde_json::json!(priority));
+        }
 
         crate::plugin::ConnectionConfig {
             uuid: self.connection.uuid.clone(),
@@ -340,3 +1031,296 @@ impl Default for ConnectionConfigManager {
         Self::new("/etc/netctl/connections")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ip_config_routes() {
+        let ipv4: IpConfigSection = toml::from_str(r#"
+            method = "manual"
+            address = "192.168.1.100/24"
+            gateway = "192.168.1.1"
+            routes = ["10.10.0.0/16 via 192.168.1.254", "10.20.0.0/16,192.168.1.253,20", "172.16.0.0/12 table 100"]
+        "#).unwrap();
+
+        let gateway = ipv4.gateway_route("eth0", false, 100).unwrap().unwrap();
+        assert_eq!(gateway.to_string(), "default via 192.168.1.1 dev eth0 proto static metric 100");
+
+        let routes = ipv4.static_routes("eth0", false, 100).unwrap();
+        assert_eq!(routes.len(), 3);
+        assert_eq!(routes[0].gateway, Some("192.168.1.254".parse().unwrap()));
+        assert_eq!(routes[0].metric, Some(100));
+        assert_eq!(routes[1].metric, Some(20));
+        assert_eq!(routes[2].table, 100);
+        assert_eq!(routes[2].scope, crate::routing::RouteScope::Link);
+
+        assert!(IpConfigSection::parse_route("10.0.0.0/8 via", false).is_err());
+        assert!(IpConfigSection::parse_route("10.0.0.0/8 dev eth0", false).is_err());
+        assert!(IpConfigSection::parse_route("10.0.0.0/8,1.2.3.4,5,6", false).is_err());
+    }
+
+    #[test]
+    fn test_ipv6_config() {
+        let ipv6: IpConfigSection = toml::from_str(r#"
+            method = "manual"
+            address = "2001:db8::10/64"
+            addresses = ["fd00::10/64"]
+            gateway = "fe80::1"
+            routes = ["2001:db8:100::/48 via fe80::2"]
+            addr-gen-mode = "stable-privacy"
+            ip6-privacy = 2
+        "#).unwrap();
+
+        let addresses = ipv6.all_addresses(true).unwrap();
+        assert_eq!(addresses, vec![("2001:db8::10".parse().unwrap(), 64), ("fd00::10".parse().unwrap(), 64)]);
+        assert!(ipv6.all_addresses(false).is_err());
+        assert_eq!(ipv6.addr_gen_mode_sysctl().unwrap(), Some(3));
+
+        let gateway = ipv6.gateway_route("eth0", true, 1024).unwrap().unwrap();
+        assert!(gateway.is_ipv6() && gateway.is_default());
+        assert!(ipv6.gateway_route("eth0", false, 1024).is_err());
+        assert_eq!(ipv6.static_routes("eth0", true, 1024).unwrap()[0].prefix_len, 48);
+        assert!(ipv6.static_routes("eth0", false, 1024).is_err());
+    }
+
+    #[test]
+    fn test_dhcpv6_options() {
+        let ipv6: IpConfigSection = toml::from_str(r#"
+            method = "dhcp"
+            prefix-delegation = ["br-lan", "wlan0"]
+            prefix-delegation-length = 56
+        "#).unwrap();
+        let options = ipv6.dhcpv6_options().unwrap().unwrap();
+        assert!(options.request_address);
+        let pd = options.prefix_delegation.unwrap();
+        assert_eq!(pd.prefix_len_hint, Some(56));
+        assert_eq!(pd.lan_interfaces, vec!["br-lan", "wlan0"]);
+
+        // SLAAC with only a delegated prefix
+        let ipv6: IpConfigSection = toml::from_str(r#"
+            method = "auto"
+            prefix-delegation = ["br-lan"]
+        "#).unwrap();
+        let options = ipv6.dhcpv6_options().unwrap().unwrap();
+        assert!(!options.request_address && options.prefix_delegation.is_some());
+
+        let ipv6: IpConfigSection = toml::from_str(r#"method = "auto""#).unwrap();
+        assert!(ipv6.dhcpv6_options().unwrap().is_none());
+        let ipv6: IpConfigSection = toml::from_str(r#"
+            method = "dhcp"
+            dhcp-mode = "stateless"
+        "#).unwrap();
+        assert!(ipv6.dhcpv6_options().unwrap().unwrap().is_stateless());
+        let ipv6: IpConfigSection = toml::from_str(r#"
+            method = "dhcp"
+            dhcp-mode = "sometimes"
+        "#).unwrap();
+        assert!(ipv6.dhcpv6_options().is_err());
+    }
+
+    #[test]
+    fn test_link_local_fallback() {
+        let ipv4: IpConfigSection = toml::from_str(r#"method = "auto""#).unwrap();
+        assert_eq!(ipv4.link_local_fallback().unwrap(), None);
+        let ipv4: IpConfigSection = toml::from_str(r#"
+            method = "auto"
+            link-local = "fallback"
+        "#).unwrap();
+        assert_eq!(ipv4.link_local_fallback().unwrap(), Some(Duration::from_secs(30)));
+        let ipv4: IpConfigSection = toml::from_str(r#"
+            method = "auto"
+            link-local = "fallback"
+            dhcp-timeout = 10
+        "#).unwrap();
+        assert_eq!(ipv4.link_local_fallback().unwrap(), Some(Duration::from_secs(10)));
+        let ipv4: IpConfigSection = toml::from_str(r#"
+            method = "auto"
+            link-local = "always"
+        "#).unwrap();
+        assert!(ipv4.link_local_fallback().is_err());
+    }
+
+    #[test]
+    fn test_arp_probe_config() {
+        let ipv4: IpConfigSection = toml::from_str(r#"method = "manual""#).unwrap();
+        let probe = ipv4.arp_probe_config().unwrap().unwrap();
+        assert_eq!(probe.probe_min * 2 + probe.announce_wait, Duration::from_millis(DEFAULT_DAD_TIMEOUT_MS));
+        let ipv4: IpConfigSection = toml::from_str(r#"
+            method = "manual"
+            dad-timeout = -1
+        "#).unwrap();
+        assert_eq!(ipv4.arp_probe_config().unwrap(), Some(probe));
+        let ipv4: IpConfigSection = toml::from_str(r#"
+            method = "manual"
+            dad-timeout = 0
+        "#).unwrap();
+        assert_eq!(ipv4.arp_probe_config().unwrap(), None);
+        let ipv4: IpConfigSection = toml::from_str(r#"
+            method = "manual"
+            dad-timeout = 300
+        "#).unwrap();
+        let probe = ipv4.arp_probe_config().unwrap().unwrap();
+        assert_eq!(probe.probe_wait, Duration::ZERO);
+        assert_eq!(probe.probe_min * 2 + probe.announce_wait, Duration::from_millis(300));
+        let ipv4: IpConfigSection = toml::from_str(r#"
+            method = "manual"
+            dad-timeout = -5
+        "#).unwrap();
+        assert!(ipv4.arp_probe_config().is_err());
+    }
+
+    #[test]
+    fn test_wifi_network_options() {
+        let config: NetctlConnectionConfig = toml::from_str(r#"
+            [connection]
+            name = "home"
+            uuid = "00000000-0000-0000-0000-000000000002"
+            type = "wifi"
+
+            [wifi]
+            ssid = "home"
+            bssid = "02:00:00:AA:BB:CC"
+            channel = 36
+            hidden = true
+
+            [wifi-security]
+            key-mgmt = "sae"
+            psk = "correct horse"
+        "#).unwrap();
+        let network = config.wpa_network().unwrap();
+        assert_eq!(network.security, WpaSecurity::Sae("correct horse".to_string()));
+        assert!(network.hidden);
+        assert_eq!(network.bssid.as_deref(), Some("02:00:00:AA:BB:CC"));
+        assert_eq!(network.frequencies, vec![5180]);
+        assert_eq!(network.pmf, None);
+
+        let security = |key_mgmt: &str, psk: Option<&str>, pmf: Option<&str>| WifiSecuritySection {
+            key_mgmt: key_mgmt.to_string(),
+            psk: psk.map(str::to_string),
+            password: None,
+            pmf: pmf.map(str::to_string),
+        };
+        let wifi = config.wifi.clone().unwrap();
+        let transition = wifi.wpa_network(Some(&security("wpa-psk+sae", Some("password123"), Some("required"))), None).unwrap();
+        assert_eq!(transition.security, WpaSecurity::PskSae("password123".to_string()));
+        assert_eq!(transition.pmf, Some(Pmf::Required));
+        assert_eq!(wifi.wpa_network(Some(&security("owe", None, None)), None).unwrap().security, WpaSecurity::Owe);
+        assert_eq!(wifi.wpa_network(Some(&security("none", None, Some("default"))), None).unwrap().security, WpaSecurity::Open);
+        assert!(wifi.wpa_network(Some(&security("wpa-psk", None, None)), None).is_err());
+        assert!(wifi.wpa_network(Some(&security("wep", None, None)), None).is_err());
+        assert!(wifi.wpa_network(Some(&security("sae", Some("pw"), Some("sometimes"))), None).is_err());
+
+        // The band restricts to all of its channels, or disambiguates the channel
+        let band = WifiSection { channel: None, band: Some("2.4GHz".to_string()), ..wifi.clone() };
+        assert_eq!(band.frequencies().unwrap().len(), 14);
+        let six = WifiSection { channel: Some(5), band: Some("6GHz".to_string()), ..wifi.clone() };
+        assert_eq!(six.frequencies().unwrap(), vec![5975]);
+        assert!(WifiSection { channel: Some(233), band: None, ..six.clone() }.frequencies().is_err());
+        assert!(WifiSection { channel: Some(36), ..band }.frequencies().is_err());
+        // Odd and gap channels are not 5 GHz channels, with or without a band
+        assert!(WifiSection { channel: Some(37), band: None, ..wifi.clone() }.frequencies().is_err());
+        assert!(WifiSection { channel: Some(80), band: Some("5GHz".to_string()), ..wifi.clone() }.frequencies().is_err());
+    }
+
+    #[test]
+    fn test_8021x_section() {
+        let config: NetctlConnectionConfig = toml::from_str(r#"
+            [connection]
+            name = "office"
+            uuid = "00000000-0000-0000-0000-000000000001"
+            type = "wifi"
+
+            [wifi]
+            ssid = "office"
+
+            [wifi-security]
+            key-mgmt = "wpa-eap"
+
+            [802-1x]
+            eap = ["peap"]
+            identity = "alice"
+            password = "secret"
+            phase2-auth = "mschapv2"
+            ca-cert = "file:///etc/ssl/ca.pem"
+            domain-suffix-match = "example.com"
+        "#).unwrap();
+        let network = config.wpa_network().unwrap();
+        let WpaSecurity::Eap { config: eap, suite_b_192: false } = network.security else { panic!("expected EAP") };
+        assert_eq!(eap.methods, vec![EapMethod::Peap]);
+        assert_eq!(eap.ca_cert.as_deref(), Some("/etc/ssl/ca.pem"));
+        assert_eq!(eap.phase2_auth.as_deref(), Some("mschapv2"));
+
+        // TLS needs a client certificate and key, TTLS an inner method
+        let tls = Ieee8021xSection {
+            eap: vec!["tls".to_string()],
+            identity: Some("alice".to_string()),
+            client_cert: Some("/etc/ssl/alice.pem".to_string()),
+            ..Default::default()
+        };
+        assert!(tls.to_eap_config().is_err());
+        assert!(Ieee8021xSection { private_key: Some("/etc/ssl/alice.key".to_string()), ..tls.clone() }.to_eap_config().is_ok());
+        let ttls = Ieee8021xSection { eap: vec!["ttls".to_string()], password: Some("secret".to_string()), ..tls.clone() };
+        assert!(ttls.to_eap_config().is_err());
+        assert!(Ieee8021xSection { eap: vec!["leap".to_string()], ..tls.clone() }.to_eap_config().is_err());
+        assert!(Ieee8021xSection { ca_cert: Some("ca.pem".to_string()), ..tls.clone() }.to_eap_config().is_err());
+
+        // NetworkManager keyfile keys
+        let keys: HashMap<String, String> = [("eap", "tls;peap;"), ("identity", "bob"), ("client-cert", "file:///c.pem")]
+            .into_iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let section = Ieee8021xSection::from_nm_keys(&keys);
+        assert_eq!(section.eap, vec!["tls", "peap"]);
+        assert_eq!(section.client_cert.as_deref(), Some("/c.pem"));
+
+        let mut missing = config.clone();
+        missing.ieee8021x = None;
+        assert!(missing.wpa_network().is_err());
+    }
+
+    #[test]
+    fn test_ipv6_ra_config() {
+        let config: NetctlConnectionConfig = toml::from_str(r#"
+            [connection]
+            name = "lan"
+            uuid = "6d1b1d36-1c1b-4bd0-9d4c-3a4cfa1c2b10"
+            type = "ethernet"
+            interface-name = "br-lan"
+
+            [ipv6]
+            method = "manual"
+            addresses = ["fd00:1::1/64", "fd00:1::2/64", "fd00:2::1/64"]
+
+            [ipv6-ra]
+            other-config = true
+            max-interval = 60
+            router-preference = "high"
+            routes = ["fd00:100::/48"]
+            dns = ["fd00:1::1"]
+            dns-search = ["lan"]
+        "#).unwrap();
+        let ra = config.ipv6_ra.as_ref().unwrap().to_ra_config(config.ipv6.as_ref()).unwrap();
+        // One prefix per subnet of the static addresses
+        assert_eq!(ra.prefixes.len(), 2);
+        assert_eq!(ra.prefixes[1].prefix, "fd00:2::".parse::<std::net::Ipv6Addr>().unwrap());
+        assert!(ra.other_config && !ra.managed);
+        assert_eq!(ra.min_interval, Duration::from_secs(20));
+        assert_eq!(ra.router_preference, crate::ra::RouterPreference::High);
+        assert_eq!(ra.routes[0].prefix_len, 48);
+        assert_eq!(ra.dns_search, vec!["lan"]);
+
+        let section: Ipv6RaSection = toml::from_str(r#"
+            prefixes = ["2001:db8:1::/64"]
+            valid-lifetime = 3600
+            preferred-lifetime = 1800
+        "#).unwrap();
+        let ra = section.to_ra_config(None).unwrap();
+        assert_eq!(ra.prefixes, vec![RaPrefix::new("2001:db8:1::".parse().unwrap(), 64).with_lifetimes(3600, 1800)]);
+
+        for invalid in [r#"prefixes = ["2001:db8::/64"]
+            preferred-lifetime = 100000"#, r#"dns = ["10.0.0.1"]"#, r#"router-preference = "top""#, "min-interval = 1"] {
+            let section: Ipv6RaSection = toml::from_str(invalid).unwrap();
+            assert!(section.to_ra_config(None).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -4,16 +4,29 @@
 //! connection lifecycle including WiFi association, DHCP client control,
 //! and static IP configuration.
 
+use crate::arp::{self, ArpProbeConfig, ArpSocket, DEFEND_INTERVAL};
 use crate::error::{NetctlError, NetctlResult};
-use crate::connection_config::{ConnectionConfigManager, NetctlConnectionConfig};
+use crate::connection_config::{ConnectionConfigManager, IpConfigSection, NetctlConnectionConfig};
 use crate::interface::InterfaceController;
 use crate::wpa_supplicant::WpaSupplicantController;
-use crate::dhcp_client::DhcpClientController;
+use crate::dhcp_client::{DhcpClientController, DhcpClientOptions, DhcpEvent, DhcpLease};
+use crate::dhcpv6_client::{Dhcpv6ClientController, Dhcpv6ClientOptions, Dhcpv6Event, LanPrefix};
+use crate::dns::DnsController;
+use crate::ipv4ll::LinkLocalController;
+use crate::netns::NetNamespace;
+use crate::network_monitor::{NetworkEvent, NetworkMonitor};
+use crate::ra::{RaController, RaPrefix};
+use crate::resolver::{ResolverEntry, ResolverManager};
+use crate::routing::{Route, RoutingController, RoutingRule};
 use crate::vpn::{VpnManager, wireguard, openvpn};
 use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr};
 use std::sync::Arc;
 use std::path::PathBuf;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::{broadcast, RwLock};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
 use tracing::{debug, error, info, warn};
 
 /// Active connection state
@@ -29,10 +42,38 @@ pub struct ActiveConnection {
     pub conn_type: String,
     /// Whether DHCP is running
     pub dhcp_active: bool,
+    /// Whether the DHCPv6 client is running
+    pub dhcp6_active: bool,
+    /// Whether router advertisements are sent on the interface
+    pub ra_active: bool,
+    /// Whether an IPv4 link-local address is managed, on its own or as
+    /// DHCP fallback
+    pub link_local_active: bool,
+    /// Routing policy rules installed for this connection
+    pub routing_rules: Vec<RoutingRule>,
+    /// Static addresses added for this connection (address, prefix length)
+    pub addresses: Vec<(String, u8)>,
+    /// Gateway and static routes installed for this connection
+    pub routes: Vec<Route>,
+    /// DNS settings registered with the resolver for this connection
+    pub dns: Option<ResolverEntry>,
+    /// DNS settings of the current DHCP lease
+    pub lease_dns: LeaseDns,
+    /// DNS settings of the current DHCPv6 lease
+    pub lease6_dns: LeaseDns,
+    /// IPv6 sysctls changed for this connection (key, previous value)
+    pub ipv6_sysctls: Vec<(String, String)>,
     /// Configuration
     pub config: NetctlConnectionConfig,
 }
 
+/// DNS servers and search domains learned from a lease
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct LeaseDns {
+    pub servers: Vec<String>,
+    pub search: Vec<String>,
+}
+
 /// Connection manager
 pub struct ConnectionManager {
     /// Configuration manager
@@ -43,10 +84,24 @@ pub struct ConnectionManager {
     wpa_supplicant: Arc<WpaSupplicantController>,
     /// DHCP client controller
     dhcp_client: Arc<DhcpClientController>,
+    /// DHCPv6 client controller
+    dhcpv6_client: Arc<Dhcpv6ClientController>,
+    /// Router advertisement controller
+    ra: Arc<RaController>,
+    /// IPv4 link-local address controller
+    link_local: Arc<LinkLocalController>,
+    /// Routing controller
+    routing_controller: Arc<RoutingController>,
+    /// System resolver manager
+    resolver: Arc<ResolverManager>,
     /// VPN manager
     vpn_manager: Arc<VpnManager>,
     /// Active connections (interface/uuid -> connection)
     active_connections: Arc<RwLock<HashMap<String, ActiveConnection>>>,
+    /// ARP conflict monitors of static IPv4 addresses (interface -> task)
+    conflict_monitors: Arc<RwLock<HashMap<String, JoinHandle<()>>>>,
+    /// Monitor that address conflicts are reported through
+    network_monitor: Option<Arc<NetworkMonitor>>,
 }
 
 impl ConnectionManager {
@@ -58,21 +113,38 @@ impl ConnectionManager {
             ConnectionConfigManager::default()
         };
 
+        // Routing domains are forwarded through the local unbound
+        let resolver = Arc::new(ResolverManager::new().with_split_dns(Arc::new(DnsController::new())));
+
         // Initialize VPN manager with backends
         let mut vpn_manager = VpnManager::new(PathBuf::from("/etc/netctl"));
         vpn_manager.register_backend("wireguard", wireguard::create_backend);
         vpn_manager.register_backend("openvpn", openvpn::create_backend);
+        vpn_manager.set_resolver(resolver.clone());
 
         Self {
             config_manager,
             interface_controller: Arc::new(InterfaceController::new()),
             wpa_supplicant: Arc::new(WpaSupplicantController::new()),
-            dhcp_client: Arc::new(DhcpClientController::new()),
+            dhcp_client: Arc::new(DhcpClientController::new().with_resolver(resolver.clone())),
+            dhcpv6_client: Arc::new(Dhcpv6ClientController::new()),
+            ra: Arc::new(RaController::new()),
+            link_local: Arc::new(LinkLocalController::new()),
+            routing_controller: Arc::new(RoutingController::new()),
+            resolver,
             vpn_manager: Arc::new(vpn_manager),
             active_connections: Arc::new(RwLock::new(HashMap::new())),
+            conflict_monitors: Arc::new(RwLock::new(HashMap::new())),
+            network_monitor: None,
         }
     }
 
+    /// Report address conflicts as `NetworkEvent::AddressConflict` on `monitor`
+    pub fn with_network_monitor(mut self, monitor: Arc<NetworkMonitor>) -> Self {
+        self.network_monitor = Some(monitor);
+        self
+    }
+
     /// Initialize the connection manager
     pub async fn initialize(&self) -> NetctlResult<()> {
         info!("Initializing connection manager");
@@ -116,13 +188,47 @@ impl ConnectionManager {
             }
         }
 
-        // Bring interface up
-        info!("Bringing interface {} up", interface);
-        self.interface_controller.up(&interface).await?;
+        let mut active_conn = ActiveConnection {
+            name: name.to_string(),
+            uuid: config.connection.uuid.clone(),
+            interface: interface.clone(),
+            conn_type: config.connection.conn_type.clone(),
+            dhcp_active: false,
+            dhcp6_active: false,
+            ra_active: false,
+            link_local_active: false,
+            routing_rules: Vec::new(),
+            addresses: Vec::new(),
+            routes: Vec::new(),
+            dns: None,
+            lease_dns: LeaseDns::default(),
+            lease6_dns: LeaseDns::default(),
+            ipv6_sysctls: Vec::new(),
+            config: config.clone(),
+        };
 
-        // Handle WiFi connection
-        if config.connection.conn_type == "wifi" {
-            self.activate_wifi(&config, &interface).await?;
+        // IPv6 settings that must be in place before the link comes up
+        if config.connection.conn_type != "vpn" {
+            if let Err(e) = self.configure_ipv6_sysctls(&config, &mut active_conn).await {
+                self.stop_connection(&active_conn).await;
+                return Err(e);
+            }
+        }
+
+        let link = async {
+            // Bring interface up
+            info!("Bringing interface {} up", interface);
+            self.interface_controller.up(&interface).await?;
+
+            // Handle WiFi connection
+            if config.connection.conn_type == "wifi" {
+                self.activate_wifi(&config, &interface).await?;
+            }
+            Ok::<(), NetctlError>(())
+        };
+        if let Err(e) = link.await {
+            self.stop_connection(&active_conn).await;
+            return Err(e);
         }
 
         // Handle VPN connection
@@ -130,19 +236,22 @@ impl ConnectionManager {
             return self.activate_vpn(name, &config).await;
         }
 
-        // Handle IP configuration
-        let dhcp_active = self.configure_ip(&config, &interface).await?;
-
-        // Store active connection
-        let active_conn = ActiveConnection {
-            name: name.to_string(),
-            uuid: config.connection.uuid.clone(),
-            interface: interface.clone(),
-            conn_type: config.connection.conn_type.clone(),
-            dhcp_active,
-            config,
+        // Install routing policy rules and IP configuration
+        let configured = match self.add_routing_rules(&config).await {
+            Ok(rules) => {
+                active_conn.routing_rules = rules;
+                self.configure_ip(&config, &mut active_conn).await
+            }
+            Err(e) => Err(e),
         };
+        if let Err(e) = configured {
+            // Clients started before the failing step would otherwise keep
+            // running with nothing left to stop them
+            self.stop_connection(&active_conn).await;
+            return Err(e);
+        }
 
+        // Store active connection
         self.active_connections.write().await.insert(
             interface.clone(),
             active_conn
@@ -154,28 +263,24 @@ impl ConnectionManager {
 
     /// Activate WiFi connection
     async fn activate_wifi(&self, config: &NetctlConnectionConfig, interface: &str) -> NetctlResult<()> {
-        let wifi = config.wifi.as_ref()
-            .ok_or_else(|| NetctlError::ConfigError(
-                "WiFi connection must have [wifi] section".to_string()
-            ))?;
-
-        info!("Connecting to WiFi network '{}' on {}", wifi.ssid, interface);
+        let network = config.wpa_network()?;
 
-        // Get password from wifi-security section
-        let password = config.wifi_security.as_ref()
-            .and_then(|sec| sec.psk.as_ref().or(sec.password.as_ref()))
-            .map(|s| s.as_str());
+        info!("Connecting to WiFi network '{}' on {}", network.ssid, interface);
 
         // Connect to WiFi
-        self.wpa_supplicant.connect(interface, &wifi.ssid, password).await?;
+        self.wpa_supplicant.connect_network(interface, &network).await?;
 
-        info!("WiFi connected: {}", wifi.ssid);
+        info!("WiFi connected: {}", network.ssid);
         Ok(())
     }
 
     /// Configure IP (DHCP or static)
-    async fn configure_ip(&self, config: &NetctlConnectionConfig, interface: &str) -> NetctlResult<bool> {
-        let mut dhcp_active = false;
+    ///
+    /// Everything that gets installed is recorded in `conn` so that
+    /// `stop_connection` can take it down again.
+    async fn configure_ip(&self, config: &NetctlConnectionConfig, conn: &mut ActiveConnection) -> NetctlResult<()> {
+        let interface = conn.interface.clone();
+        let interface = interface.as_str();
 
         // Check IPv4 configuration
         if let Some(ipv4) = &config.ipv4 {
@@ -186,10 +291,12 @@ impl ConnectionManager {
                     // Wait a moment for link to be fully up (especially for WiFi)
                     tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
 
-                    match self.dhcp_client.start(interface).await {
+                    let fallback = ipv4.link_local_fallback()?;
+                    match self.start_dhcp(Some(ipv4), fallback, conn).await {
                         Ok(()) => {
                             info!("DHCP client started successfully on {}", interface);
-                            dhcp_active = true;
+                            conn.dhcp_active = true;
+                            conn.link_local_active = fallback.is_some();
                         }
                         Err(e) => {
                             error!("Failed to start DHCP client on {}: {}", interface, e);
@@ -200,38 +307,15 @@ impl ConnectionManager {
                 }
                 "manual" => {
                     info!("Configuring static IP on {} (IPv4 method: manual)", interface);
-
-                    if let Some(address) = &ipv4.address {
-                        // Parse address/prefix (e.g., "192.168.1.100/24")
-                        let parts: Vec<&str> = address.split('/').collect();
-                        if parts.len() == 2 {
-                            let ip = parts[0];
-                            if let Ok(prefix) = parts[1].parse::<u8>() {
-                                debug!("Setting IP address {}/{} on {}", ip, prefix, interface);
-                                self.interface_controller.set_ip(interface, ip, prefix).await?;
-                            } else {
-                                return Err(NetctlError::ConfigError(
-                                    format!("Invalid prefix length: {}", parts[1])
-                                ));
-                            }
-                        } else {
-                            return Err(NetctlError::ConfigError(
-                                format!("Invalid IP address format: {}", address)
-                            ));
-                        }
-                    }
-
-                    // TODO: Configure gateway and DNS
-                    if let Some(gateway) = &ipv4.gateway {
-                        debug!("Gateway configured: {}", gateway);
-                        // self.routing_controller.add_default_route(gateway, interface).await?;
-                    }
+                    self.configure_static(ipv4, false, conn).await?;
                 }
                 "ignore" => {
                     info!("IPv4 method is 'ignore', skipping IP configuration");
                 }
                 "link-local" => {
-                    info!("IPv4 method is 'link-local', relying on auto-configuration");
+                    info!("Claiming an IPv4 link-local address on {} (IPv4 method: link-local)", interface);
+                    self.link_local.start(interface).await?;
+                    conn.link_local_active = true;
                 }
                 method => {
                     warn!("Unknown IPv4 method '{}', treating as ignore", method);
@@ -243,14 +327,438 @@ impl ConnectionManager {
 
             tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
 
-            if let Err(e) = self.dhcp_client.start(interface).await {
+            if let Err(e) = self.start_dhcp(None, None, conn).await {
                 warn!("Failed to start DHCP client on {}: {}", interface, e);
             } else {
-                dhcp_active = true;
+                conn.dhcp_active = true;
+            }
+        }
+
+        // Check IPv6 configuration; the sysctls were set before link up
+        if let Some(ipv6) = &config.ipv6 {
+            match ipv6.method.as_str() {
+                "manual" => {
+                    info!("Configuring static IPv6 on {} (IPv6 method: manual)", interface);
+                    self.configure_static(ipv6, true, conn).await?;
+                }
+                "auto" => info!("Using SLAAC on {} (IPv6 method: auto)", interface),
+                method => debug!("IPv6 method '{}' on {}", method, interface),
+            }
+            if let Some(options) = ipv6.dhcpv6_options()? {
+                info!("Starting DHCPv6 client on {} (IPv6 method: {})", interface, ipv6.method);
+                match self.start_dhcpv6(options, conn).await {
+                    Ok(()) => conn.dhcp6_active = true,
+                    Err(e) => {
+                        error!("Failed to start DHCPv6 client on {}: {}", interface, e);
+                        warn!("Continuing without DHCPv6 on {}", interface);
+                    }
+                }
+            }
+        }
+
+        // The lease is usually not there yet; later ones come in through
+        // update_dhcp_dns()
+        if conn.dhcp_active {
+            match self.dhcp_client.status(interface).await {
+                Ok(lease) => conn.lease_dns = lease.as_ref().map(LeaseDns::from).unwrap_or_default(),
+                Err(e) => debug!("No DHCP lease on {} yet: {}", interface, e),
+            }
+        }
+        apply_dns(&self.resolver, conn).await?;
+
+        if let Some(ra) = &config.ipv6_ra {
+            let ra_config = ra.to_ra_config(config.ipv6.as_ref())?;
+            match self.ra.start(interface, ra_config).await {
+                Ok(()) => conn.ra_active = true,
+                Err(e) => warn!("Failed to start router advertisements on {}: {}", interface, e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Start the DHCP client for a connection and follow its leases
+    ///
+    /// The client configures address, routes and lease DNS servers itself;
+    /// each new lease is merged with the DNS settings of the profile here.
+    /// With a `fallback` delay, a link-local address is claimed if no lease
+    /// arrives in time or the lease expires, and removed again on the next
+    /// lease.
+    async fn start_dhcp(
+        &self,
+        ipv4: Option<&IpConfigSection>,
+        fallback: Option<Duration>,
+        conn: &ActiveConnection,
+    ) -> NetctlResult<()> {
+        let options = DhcpClientOptions {
+            route_metric: ipv4.and_then(|ip| ip.route_metric)
+                .unwrap_or_else(|| default_route_metric(&conn.conn_type)),
+            ..Default::default()
+        };
+
+        // Subscribe first so that a fast lease is not missed
+        let mut events = self.dhcp_client.subscribe();
+        self.dhcp_client.start_with_options(&conn.interface, options).await?;
+
+        let interface = conn.interface.clone();
+        let active_connections = self.active_connections.clone();
+        let resolver = self.resolver.clone();
+        let link_local = self.link_local.clone();
+        tokio::spawn(async move {
+            let mut fallback_at = fallback.map(|delay| Instant::now() + delay);
+            loop {
+                let event = tokio::select! {
+                    event = events.recv() => event,
+                    _ = sleep_until_some(fallback_at) => {
+                        fallback_at = None;
+                        info!("No DHCP lease on {}, falling back to a link-local address", interface);
+                        if let Err(e) = link_local.start(&interface).await {
+                            warn!("Failed to start link-local configuration on {}: {}", interface, e);
+                        }
+                        continue;
+                    }
+                };
+                let lease_dns = match event {
+                    Ok(event) if event.interface() != interface => continue,
+                    Ok(DhcpEvent::Bound(lease) | DhcpEvent::Renewed(lease)) => {
+                        fallback_at = None;
+                        if fallback.is_some() && link_local.is_running(&interface).await {
+                            info!("DHCP lease on {}, removing the link-local address", interface);
+                            if let Err(e) = link_local.stop(&interface).await {
+                                warn!("Failed to stop link-local configuration on {}: {}", interface, e);
+                            }
+                        }
+                        LeaseDns::from(&lease)
+                    }
+                    // The client removed the lease DNS servers, keep the configured ones
+                    Ok(DhcpEvent::Expired { .. }) => {
+                        if fallback.is_some() {
+                            fallback_at = Some(Instant::now());
+                        }
+                        LeaseDns::default()
+                    }
+                    Ok(DhcpEvent::Released { .. } | DhcpEvent::Stopped { .. }) => break,
+                    Ok(DhcpEvent::Declined { .. }) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+
+                let mut active = active_connections.write().await;
+                let Some(conn) = active.get_mut(&interface).filter(|conn| conn.dhcp_active) else {
+                    continue;
+                };
+                conn.lease_dns = lease_dns;
+                if let Err(e) = apply_dns(&resolver, conn).await {
+                    warn!("Failed to update DNS settings of {}: {}", interface, e);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Start the DHCPv6 client for a connection and follow its leases
+    ///
+    /// The client configures addresses and delegated prefixes itself; the
+    /// DNS servers of each lease are merged with those of the profile and
+    /// the DHCP lease here, and the LAN prefixes are handed to the router
+    /// advertisements of their interfaces.
+    async fn start_dhcpv6(&self, options: Dhcpv6ClientOptions, conn: &ActiveConnection) -> NetctlResult<()> {
+        let lan_interfaces = options.prefix_delegation.as_ref()
+            .map(|pd| pd.lan_interfaces.clone())
+            .unwrap_or_default();
+        let mut events = self.dhcpv6_client.subscribe();
+        self.dhcpv6_client.start_with_options(&conn.interface, options).await?;
+
+        let interface = conn.interface.clone();
+        let active_connections = self.active_connections.clone();
+        let resolver = self.resolver.clone();
+        let ra = self.ra.clone();
+        tokio::spawn(async move {
+            loop {
+                let lease_dns = match events.recv().await {
+                    Ok(event) if event.interface() != interface => continue,
+                    Ok(Dhcpv6Event::Bound(lease) | Dhcpv6Event::Renewed(lease)) => {
+                        advertise_delegated(&ra, &lan_interfaces, &lease.lan_prefixes).await;
+                        LeaseDns {
+                            servers: lease.dns_servers.iter().map(|ip| ip.to_string()).collect(),
+                            search: lease.domain_search,
+                        }
+                    }
+                    Ok(Dhcpv6Event::Expired { .. }) => {
+                        advertise_delegated(&ra, &lan_interfaces, &[]).await;
+                        LeaseDns::default()
+                    }
+                    Ok(Dhcpv6Event::Released { .. } | Dhcpv6Event::Stopped { .. }) => {
+                        advertise_delegated(&ra, &lan_interfaces, &[]).await;
+                        break;
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+
+                let mut active = active_connections.write().await;
+                let Some(conn) = active.get_mut(&interface).filter(|conn| conn.dhcp6_active) else {
+                    continue;
+                };
+                if conn.lease6_dns == lease_dns {
+                    continue;
+                }
+                conn.lease6_dns = lease_dns;
+                if let Err(e) = apply_dns(&resolver, conn).await {
+                    warn!("Failed to update DNS settings of {}: {}", interface, e);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Update the DNS servers and search domains learned from a DHCP lease
+    /// on `interface`
+    ///
+    /// They are merged with the ones configured in the profile and replace
+    /// those of any previous lease.
+    pub async fn update_dhcp_dns(&self, interface: &str, servers: &[String], search: &[String]) -> NetctlResult<()> {
+        let mut active = self.active_connections.write().await;
+        let conn = active.get_mut(interface)
+            .ok_or_else(|| NetctlError::NotFound(format!("No active connection on {}", interface)))?;
+        if !conn.dhcp_active {
+            return Err(NetctlError::InvalidState(format!("DHCP is not active on {}", interface)));
+        }
+        info!("DHCP DNS servers on {}: [{}]", interface, servers.join(", "));
+        conn.lease_dns = LeaseDns { servers: servers.to_vec(), search: search.to_vec() };
+        apply_dns(&self.resolver, conn).await
+    }
+
+    /// Add the static addresses, gateway and routes of one address family
+    async fn configure_static(&self, ip: &IpConfigSection, ipv6: bool, conn: &mut ActiveConnection) -> NetctlResult<()> {
+        let interface = conn.interface.clone();
+        let interface = interface.as_str();
+
+        let addresses = ip.all_addresses(ipv6)?;
+        let probe = if ipv6 { None } else { ip.arp_probe_config()? };
+        let acd = match &probe {
+            Some(probe) => self.probe_addresses(interface, &addresses, probe).await?,
+            None => None,
+        };
+
+        for (address, prefix) in addresses {
+            let address = address.to_string();
+            debug!("Adding IP address {}/{} on {}", address, prefix, interface);
+            match self.interface_controller.add_ip(interface, &address, prefix).await {
+                Ok(()) => conn.addresses.push((address, prefix)),
+                Err(NetctlError::AlreadyExists(_)) => {
+                    warn!("Address {}/{} already configured on {}, not managing it", address, prefix, interface);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        if let (Some(acd), Some(probe)) = (acd, probe) {
+            self.start_conflict_monitor(acd, probe).await;
+        }
+
+        let metric = ip.route_metric.unwrap_or_else(|| default_route_metric(&conn.conn_type));
+        let routes = ip.gateway_route(interface, ipv6, metric)?
+            .into_iter()
+            .chain(ip.static_routes(interface, ipv6, metric)?);
+        for route in routes {
+            debug!("Adding route: {}", route);
+            match self.routing_controller.add_route(&route).await {
+                Ok(()) => conn.routes.push(route),
+                Err(NetctlError::AlreadyExists(_)) => {
+                    warn!("Route already exists, not managing it: {}", route);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Probe static IPv4 addresses with ARP before they are configured
+    /// (RFC 5227)
+    ///
+    /// Fails with `AddressConflict` if another host uses one of them.
+    /// Returns the sockets to monitor the addresses with afterwards, or
+    /// `None` if the interface has no Ethernet hardware address.
+    async fn probe_addresses(
+        &self,
+        interface: &str,
+        addresses: &[(IpAddr, u8)],
+        probe: &ArpProbeConfig,
+    ) -> NetctlResult<Option<ConflictMonitor>> {
+        let addresses: Vec<Ipv4Addr> = addresses.iter()
+            .filter_map(|(address, _)| match address {
+                IpAddr::V4(address) => Some(*address),
+                IpAddr::V6(_) => None,
+            })
+            .collect();
+        if addresses.is_empty() {
+            return Ok(None);
+        }
+
+        let info = self.interface_controller.get_info(interface).await?;
+        let index = info.index
+            .ok_or_else(|| NetctlError::NotFound(format!("Interface {} has no index", interface)))?;
+        let mac = match arp::parse_mac(info.mac_address.as_deref(), interface) {
+            Ok(mac) => mac,
+            Err(e) => {
+                debug!("Not probing addresses on {}: {}", interface, e);
+                return Ok(None);
+            }
+        };
+
+        info!("Probing {} address(es) on {} for conflicts", addresses.len(), interface);
+        let mut sockets = Vec::with_capacity(addresses.len());
+        for address in addresses {
+            sockets.push((address, ArpSocket::open(&NetNamespace::Current, index, mac)?));
+        }
+        let results = futures::future::try_join_all(
+            sockets.iter().map(|(address, socket)| socket.probe(*address, probe))
+        ).await?;
+        for ((address, _), mac) in sockets.iter().zip(results) {
+            if let Some(mac) = mac {
+                let mac = arp::format_mac(&mac);
+                error!("Address {} on {} is already in use by {}", address, interface, mac);
+                return Err(NetctlError::AddressConflict { address: address.to_string(), mac });
+            }
+        }
+
+        Ok(Some(ConflictMonitor { interface: interface.to_string(), index, sockets }))
+    }
+
+    /// Announce probed addresses and defend them until the connection goes
+    /// down, reporting each conflict to the network monitor
+    async fn start_conflict_monitor(&self, acd: ConflictMonitor, probe: ArpProbeConfig) {
+        let interface = acd.interface.clone();
+        let task = tokio::spawn(acd.run(probe, self.network_monitor.clone()));
+        if let Some(previous) = self.conflict_monitors.write().await.insert(interface, task) {
+            previous.abort();
+        }
+    }
+
+    /// Apply the IPv6 sysctls implied by the `[ipv6]` section
+    ///
+    /// This runs before the link is brought up so that addr-gen-mode applies
+    /// to the link-local address. Previous values are recorded in `conn`.
+    async fn configure_ipv6_sysctls(&self, config: &NetctlConnectionConfig, conn: &mut ActiveConnection) -> NetctlResult<()> {
+        let Some(ipv6) = &config.ipv6 else {
+            return Ok(());
+        };
+
+        let (disable, slaac) = match ipv6.method.as_str() {
+            "ignore" => return Ok(()),
+            "disabled" => (true, false),
+            "auto" => (false, true),
+            "dhcp" | "manual" | "link-local" => (false, false),
+            method => {
+                return Err(NetctlError::ConfigError(format!("Unknown IPv6 method '{}'", method)));
+            }
+        };
+        let accept_ra = ipv6.accept_ra.unwrap_or(matches!(ipv6.method.as_str(), "auto" | "dhcp"));
+
+        let mut sysctls = vec![("disable_ipv6", u32::from(disable))];
+        if !disable {
+            sysctls.push(("accept_ra", u32::from(accept_ra)));
+            sysctls.push(("autoconf", u32::from(slaac)));
+            if let Some(mode) = ipv6.addr_gen_mode_sysctl()? {
+                sysctls.push(("addr_gen_mode", mode));
+            }
+            if let Some(privacy) = ipv6.ip6_privacy {
+                if privacy > 2 {
+                    return Err(NetctlError::ConfigError(format!("Invalid ip6-privacy: {}", privacy)));
+                }
+                sysctls.push(("use_tempaddr", privacy));
+            }
+        }
+
+        let interface = conn.interface.clone();
+        for (key, value) in sysctls {
+            let previous = self.interface_controller.get_ipv6_sysctl(&interface, key).await?;
+            if previous == value.to_string() {
+                continue;
+            }
+            debug!("Setting net.ipv6.conf.{}.{} = {}", interface, key, value);
+            self.interface_controller.set_ipv6_sysctl(&interface, key, &value.to_string()).await?;
+            conn.ipv6_sysctls.push((key.to_string(), previous));
+        }
+
+        Ok(())
+    }
+
+    /// Remove the rules, routes, DNS servers and addresses recorded for a
+    /// connection, and restore the IPv6 sysctls it changed
+    async fn remove_ip_config(&self, conn: &ActiveConnection) {
+        let interface = conn.interface.as_str();
+
+        self.delete_routing_rules(&conn.routing_rules).await;
+
+        if let Some(monitor) = self.conflict_monitors.write().await.remove(interface) {
+            monitor.abort();
+        }
+
+        for route in &conn.routes {
+            debug!("Removing route: {}", route);
+            match self.routing_controller.delete_route(route).await {
+                // The kernel drops routes along with their gateway's subnet
+                Ok(()) | Err(NetctlError::NotFound(_)) => {}
+                Err(e) => warn!("Failed to remove route '{}': {}", route, e),
+            }
+        }
+
+        if conn.dns.is_some() {
+            if let Err(e) = self.resolver.clear(interface).await {
+                warn!("Failed to remove DNS settings of {}: {}", interface, e);
+            }
+        }
+
+        for (address, prefix) in &conn.addresses {
+            debug!("Removing IP address {}/{} from {}", address, prefix, interface);
+            if let Err(e) = self.interface_controller.del_ip(interface, address, *prefix).await {
+                warn!("Failed to remove address {}/{} from {}: {}", address, prefix, interface, e);
+            }
+        }
+
+        for (key, previous) in conn.ipv6_sysctls.iter().rev() {
+            debug!("Restoring net.ipv6.conf.{}.{} = {}", interface, key, previous);
+            if let Err(e) = self.interface_controller.set_ipv6_sysctl(interface, key, previous).await {
+                warn!("Failed to restore IPv6 sysctl {} on {}: {}", key, interface, e);
+            }
+        }
+    }
+
+    /// Install the routing policy rules of a connection
+    ///
+    /// Returns the rules that were added so they can be removed again on
+    /// deactivation. Rules that already exist are left alone and not
+    /// recorded. If any rule fails, the ones added so far are rolled back.
+    async fn add_routing_rules(&self, config: &NetctlConnectionConfig) -> NetctlResult<Vec<RoutingRule>> {
+        let rules = config.routing_rules.iter()
+            .map(|section| section.to_rule())
+            .collect::<NetctlResult<Vec<_>>>()?;
+
+        let mut added = Vec::new();
+        for rule in rules {
+            debug!("Adding routing rule: {}", rule);
+            match self.routing_controller.add_rule(&rule).await {
+                Ok(()) => added.push(rule),
+                Err(NetctlError::AlreadyExists(_)) => {
+                    warn!("Routing rule already exists, not managing it: {}", rule);
+                }
+                Err(e) => {
+                    self.delete_routing_rules(&added).await;
+                    return Err(e);
+                }
             }
         }
+        Ok(added)
+    }
 
-        Ok(dhcp_active)
+    /// Remove previously installed routing policy rules
+    async fn delete_routing_rules(&self, rules: &[RoutingRule]) {
+        for rule in rules {
+            debug!("Removing routing rule: {}", rule);
+            if let Err(e) = self.routing_controller.delete_rule(rule).await {
+                warn!("Failed to remove routing rule '{}': {}", rule, e);
+            }
+        }
     }
 
     /// Activate VPN connection
@@ -273,13 +781,34 @@ impl ConnectionManager {
 
         info!("VPN connected on interface: {}", interface);
 
-        // Store active connection
+        // Install routing policy rules
+        let routing_rules = match self.add_routing_rules(config).await {
+            Ok(rules) => rules,
+            Err(e) => {
+                if let Err(e) = self.vpn_manager.disconnect(&uuid).await {
+                    warn!("Failed to disconnect VPN {}: {}", uuid, e);
+                }
+                return Err(e);
+            }
+        };
+
+        // Store active connection; VpnManager registers its DNS settings
         let active_conn = ActiveConnection {
             name: name.to_string(),
             uuid: config.connection.uuid.clone(),
             interface: interface.clone(),
             conn_type: "vpn".to_string(),
             dhcp_active: false,
+            dhcp6_active: false,
+            ra_active: false,
+            link_local_active: false,
+            routing_rules,
+            addresses: Vec::new(),
+            routes: Vec::new(),
+            dns: None,
+            lease_dns: LeaseDns::default(),
+            lease6_dns: LeaseDns::default(),
+            ipv6_sysctls: Vec::new(),
             config: config.clone(),
         };
 
@@ -292,6 +821,64 @@ impl ConnectionManager {
         Ok(())
     }
 
+    /// Stop everything a connection started and remove what it installed
+    ///
+    /// Used on deactivation and when activation fails part way; only the
+    /// clients `conn` records as started are stopped.
+    async fn stop_connection(&self, conn: &ActiveConnection) {
+        let interface = conn.interface.as_str();
+
+        // Tell hosts to stop using this router while its addresses are still there
+        if conn.ra_active {
+            info!("Stopping router advertisements on {}", interface);
+            if let Err(e) = self.ra.stop(interface).await {
+                warn!("Failed to stop router advertisements on {}: {}", interface, e);
+            }
+        }
+
+        // Remove rules, routes, DNS servers and addresses added on activation
+        self.remove_ip_config(conn).await;
+
+        // Stop DHCP if active
+        if conn.dhcp_active {
+            info!("Stopping DHCP client on {}", interface);
+            if let Err(e) = self.dhcp_client.release(interface).await {
+                warn!("Failed to release DHCP lease on {}: {}", interface, e);
+            }
+            if let Err(e) = self.dhcp_client.stop(interface).await {
+                warn!("Failed to stop DHCP client on {}: {}", interface, e);
+            }
+        }
+        if conn.link_local_active {
+            info!("Removing IPv4 link-local address on {}", interface);
+            if let Err(e) = self.link_local.stop(interface).await {
+                warn!("Failed to stop link-local configuration on {}: {}", interface, e);
+            }
+        }
+        if conn.dhcp6_active {
+            info!("Stopping DHCPv6 client on {}", interface);
+            if let Err(e) = self.dhcpv6_client.release(interface).await {
+                warn!("Failed to release DHCPv6 lease on {}: {}", interface, e);
+            }
+        }
+
+        // Disconnect WiFi if it's a WiFi connection
+        if conn.conn_type == "wifi" {
+            info!("Disconnecting WiFi on {}", interface);
+            if let Err(e) = self.wpa_supplicant.disconnect(interface).await {
+                warn!("Failed to disconnect WiFi on {}: {}", interface, e);
+            }
+        }
+
+        // Disconnect VPN if it's a VPN connection
+        if conn.conn_type == "vpn" {
+            info!("Disconnecting VPN on {}", interface);
+            if let Err(e) = self.vpn_manager.disconnect(&conn.uuid).await {
+                warn!("Failed to disconnect VPN {}: {}", conn.uuid, e);
+            }
+        }
+    }
+
     /// Deactivate a connection on an interface
     pub async fn deactivate_connection(&self, interface: &str) -> NetctlResult<()> {
         info!("Deactivating connection on interface {}", interface);
@@ -303,32 +890,7 @@ impl ConnectionManager {
         };
 
         if let Some(conn) = active_conn {
-            // Stop DHCP if active
-            if conn.dhcp_active {
-                info!("Stopping DHCP client on {}", interface);
-                if let Err(e) = self.dhcp_client.release(interface).await {
-                    warn!("Failed to release DHCP lease on {}: {}", interface, e);
-                }
-                if let Err(e) = self.dhcp_client.stop(interface).await {
-                    warn!("Failed to stop DHCP client on {}: {}", interface, e);
-                }
-            }
-
-            // Disconnect WiFi if it's a WiFi connection
-            if conn.conn_type == "wifi" {
-                info!("Disconnecting WiFi on {}", interface);
-                if let Err(e) = self.wpa_supplicant.disconnect(interface).await {
-                    warn!("Failed to disconnect WiFi on {}: {}", interface, e);
-                }
-            }
-
-            // Disconnect VPN if it's a VPN connection
-            if conn.conn_type == "vpn" {
-                info!("Disconnecting VPN on {}", interface);
-                if let Err(e) = self.vpn_manager.disconnect(&conn.uuid).await {
-                    warn!("Failed to disconnect VPN {}: {}", conn.uuid, e);
-                }
-            }
+            self.stop_connection(&conn).await;
 
             // Bring interface down
             info!("Bringing interface {} down", interface);
@@ -403,16 +965,230 @@ impl ConnectionManager {
         self.dhcp_client.clone()
     }
 
+    /// Get DHCPv6 client controller reference
+    pub fn dhcpv6_client(&self) -> Arc<Dhcpv6ClientController> {
+        self.dhcpv6_client.clone()
+    }
+
+    /// Get router advertisement controller reference
+    pub fn ra_controller(&self) -> Arc<RaController> {
+        self.ra.clone()
+    }
+
+    /// Get IPv4 link-local controller reference
+    pub fn link_local_controller(&self) -> Arc<LinkLocalController> {
+        self.link_local.clone()
+    }
+
     /// Get WPA supplicant controller reference
     pub fn wpa_supplicant(&self) -> Arc<WpaSupplicantController> {
         self.wpa_supplicant.clone()
     }
 }
 
+/// ARP sockets watching the static IPv4 addresses of one interface
+struct ConflictMonitor {
+    interface: String,
+    index: u32,
+    sockets: Vec<(Ipv4Addr, ArpSocket)>,
+}
+
+impl ConflictMonitor {
+    /// Announce every address, then defend it (RFC 5227 section 2.4 (b))
+    ///
+    /// The addresses are static, so they are kept: a conflict is defended
+    /// and reported at most once per `DEFEND_INTERVAL`.
+    async fn run(self, probe: ArpProbeConfig, monitor: Option<Arc<NetworkMonitor>>) {
+        let watchers = self.sockets.iter().map(|(address, socket)| {
+            let monitor = monitor.clone();
+            let probe = &probe;
+            let (interface, index) = (&self.interface, self.index);
+            async move {
+                if let Err(e) = socket.announce(*address, probe).await {
+                    debug!("Failed to announce {} on {}: {}", address, interface, e);
+                }
+                let mut last_defense: Option<Instant> = None;
+                loop {
+                    let mac = match socket.wait_for_conflict(*address).await {
+                        Ok(mac) => arp::format_mac(&mac),
+                        Err(e) => {
+                            warn!("Stopped watching {} on {} for conflicts: {}", address, interface, e);
+                            return;
+                        }
+                    };
+                    if last_defense.is_some_and(|at| at.elapsed() < DEFEND_INTERVAL) {
+                        continue;
+                    }
+                    warn!("Address {} on {} is claimed by {}, defending it", address, interface, mac);
+                    if let Some(monitor) = &monitor {
+                        monitor.emit(NetworkEvent::AddressConflict {
+                            index,
+                            name: interface.clone(),
+                            address: address.to_string(),
+                            mac,
+                        });
+                    }
+                    if let Err(e) = socket.defend(*address).await {
+                        debug!("Failed to defend {} on {}: {}", address, interface, e);
+                    }
+                    last_defense = Some(Instant::now());
+                }
+            }
+        });
+        futures::future::join_all(watchers).await;
+    }
+}
+
+/// Sleep until `deadline`, or forever without one
+async fn sleep_until_some(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Hand the LAN prefixes of a DHCPv6 lease to the router advertisements
+/// of their interfaces; an empty list withdraws them
+async fn advertise_delegated(ra: &RaController, lan_interfaces: &[String], lan_prefixes: &[LanPrefix]) {
+    for lan in lan_interfaces {
+        let prefixes = lan_prefixes.iter()
+            .filter(|prefix| &prefix.interface == lan)
+            .map(RaPrefix::from)
+            .collect();
+        if let Err(e) = ra.set_delegated_prefixes(lan, prefixes).await {
+            warn!("Failed to advertise delegated prefixes on {}: {}", lan, e);
+        }
+    }
+}
+
+/// Default metric for the routes of a connection type
+///
+/// Lower metrics win, so wired links are preferred over WiFi and VPNs
+/// over both.
+fn default_route_metric(conn_type: &str) -> u32 {
+    match conn_type {
+        "vpn" => 50,
+        "wifi" => 600,
+        _ => 100,
+    }
+}
+
+/// Default resolver priority of a connection type
+///
+/// VPN nameservers go first so that internal names resolve while the
+/// tunnel is up.
+fn default_dns_priority(conn_type: &str) -> i32 {
+    match conn_type {
+        "vpn" => 50,
+        _ => 100,
+    }
+}
+
+/// Resolver entry for the `dns`, `dns-search` and `dns-priority` settings of
+/// a profile
+///
+/// Sections with method "ignore" or "disabled" don't contribute. If both
+/// families set a priority, the lower one is used.
+fn connection_dns(interface: &str, conn_type: &str, config: &NetctlConnectionConfig) -> ResolverEntry {
+    let mut entry = ResolverEntry {
+        interface: interface.to_string(),
+        ..Default::default()
+    };
+    let mut priority = None;
+
+    let sections = config.ipv4.iter().chain(config.ipv6.iter())
+        .filter(|ip| !matches!(ip.method.as_str(), "ignore" | "disabled"));
+    for ip in sections {
+        push_unique(&mut entry.servers, ip.dns.as_deref().unwrap_or_default());
+        for domain in ip.dns_search.iter().flatten() {
+            entry.add_domain(domain);
+        }
+        if let Some(p) = ip.dns_priority {
+            priority = Some(priority.map_or(p, |q: i32| q.min(p)));
+        }
+    }
+
+    entry.priority = priority.unwrap_or_else(|| default_dns_priority(conn_type));
+    entry
+}
+
+/// Register the DNS settings of a connection with the resolver
+///
+/// Configured servers and domains come before the ones learned from DHCP,
+/// which come before those from DHCPv6.
+async fn apply_dns(resolver: &ResolverManager, conn: &mut ActiveConnection) -> NetctlResult<()> {
+    let mut entry = connection_dns(&conn.interface, &conn.conn_type, &conn.config);
+    for lease in [&conn.lease_dns, &conn.lease6_dns] {
+        push_unique(&mut entry.servers, &lease.servers);
+        for domain in &lease.search {
+            entry.add_domain(domain);
+        }
+    }
+
+    if entry.is_empty() {
+        if conn.dns.take().is_some() {
+            resolver.clear(&conn.interface).await?;
+        }
+        return Ok(());
+    }
+    if conn.dns.as_ref() != Some(&entry) {
+        resolver.set(entry.clone()).await?;
+        conn.dns = Some(entry);
+    }
+    Ok(())
+}
+
+impl From<&DhcpLease> for LeaseDns {
+    /// Search domains are the search list, else the domain name
+    fn from(lease: &DhcpLease) -> Self {
+        let search = if lease.domain_search.is_empty() {
+            lease.domain_name.iter().cloned().collect()
+        } else {
+            lease.domain_search.clone()
+        };
+        Self { servers: lease.dns_servers.clone(), search }
+    }
+}
+
+fn push_unique(list: &mut Vec<String>, values: &[String]) {
+    for value in values {
+        if !list.contains(value) {
+            list.push(value.clone());
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_connection_dns() {
+        let config: NetctlConnectionConfig = toml::from_str(r#"
+            [connection]
+            name = "office"
+            uuid = "2b1c8c1e-5d0a-4a49-9a55-0d1f1d6e4c11"
+            type = "ethernet"
+
+            [ipv4]
+            method = "auto"
+            dns = ["10.0.0.53"]
+            dns-search = ["office.example", "~corp.example"]
+
+            [ipv6]
+            method = "manual"
+            address = "fd00::10/64"
+            dns = ["fd00::53", "10.0.0.53"]
+            dns-priority = 20
+        "#).unwrap();
+
+        let entry = connection_dns("eth0", "ethernet", &config);
+        assert_eq!(entry.servers, vec!["10.0.0.53", "fd00::53"]);
+        assert_eq!(entry.search, vec!["office.example"]);
+        assert_eq!(entry.routing_domains, vec!["corp.example"]);
+        assert_eq!(entry.priority, 20);
+    }
+
     #[tokio::test]
     async fn test_connection_manager_creation() {
         let manager = ConnectionManager::new(None);
@@ -3,6 +3,7 @@
 //! D-Bus interface for individual network devices
 
 use super::types::*;
+use crate::dhcp_client::DhcpLease;
 #[allow(unused_imports)]
 use crate::error::NetctlResult;
 use std::collections::HashMap;
@@ -59,6 +60,16 @@ impl CRDevice {
         }
     }
 
+    /// Update the DHCPv4 lease, taking the IPv4 address from it
+    pub async fn set_dhcp4_lease(&self, lease: Option<DhcpLease>) {
+        let mut info = self.info.write().await;
+        if let Some(address) = lease.as_ref().and_then(|lease| lease.ip_address.clone()) {
+            info!("CR Device {}: DHCPv4 lease of {}", info.interface, address);
+            info.ipv4_address = Some(address);
+        }
+        info.dhcp4 = lease;
+    }
+
     /// Get device info
     pub async fn get_info(&self) -> CRDeviceInfo {
         self.info.read().await.clone()
@@ -128,6 +139,7 @@ impl CRDevice {
         if let Some(ref hw) = info.hw_address {
             props.insert("HwAddress".to_string(), Value::new(hw.clone()));
         }
+        props.extend(info.ip4_lease_properties());
 
         debug!("CR Device {}: Returning all properties", info.interface);
         props
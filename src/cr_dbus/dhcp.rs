@@ -1,68 +1,229 @@
 //! CR DHCP Server D-Bus interface
 //!
-//! D-Bus interface for DHCP server management
+//! D-Bus interface for DHCP server management. While dora runs, its lease
+//! database is watched and every change is reported as `LeaseAssigned` and
+//! `LeaseExpired` signals; leases that run out without dora touching the
+//! database are swept on the same tick.
 
 use super::types::*;
+use crate::dhcp::{DhcpConfig, DhcpController, DhcpServerLease};
 use crate::error::{NetctlError, NetctlResult};
+use crate::validation;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::SystemTime;
 use tokio::sync::RwLock;
-use tracing::{info, debug};
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+use tracing::{info, debug, warn};
 use zbus::{Connection, fdo, interface};
 use zbus::object_server::SignalEmitter;
 use zbus::zvariant::Value;
 
+/// How often the lease database is checked for changes
+const LEASE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Leases that appeared and disappeared between two looks at the server
+#[derive(Debug, Clone, Default)]
+pub struct LeaseChanges {
+    /// New leases, and leases that moved to another address or client
+    pub assigned: Vec<CRDhcpLease>,
+    /// Leases that ran out or were released
+    pub expired: Vec<CRDhcpLease>,
+}
+
+impl LeaseChanges {
+    pub fn is_empty(&self) -> bool {
+        self.assigned.is_empty() && self.expired.is_empty()
+    }
+}
+
+/// Compare two lease lists by client and address
+///
+/// A renewal only moves the expiry and is no change.
+fn diff_leases(old: &[CRDhcpLease], new: &[CRDhcpLease]) -> LeaseChanges {
+    let same = |a: &CRDhcpLease, b: &CRDhcpLease| a.mac_address == b.mac_address && a.ip_address == b.ip_address;
+    LeaseChanges {
+        assigned: new.iter().filter(|lease| !old.iter().any(|o| same(o, lease))).cloned().collect(),
+        expired: old.iter().filter(|lease| !new.iter().any(|n| same(n, lease))).cloned().collect(),
+    }
+}
+
+/// Latest modification of the lease database, including its write-ahead log
+fn lease_db_modified(path: &std::path::Path) -> Option<SystemTime> {
+    let mut wal = path.as_os_str().to_owned();
+    wal.push("-wal");
+    [path.to_path_buf(), wal.into()].iter()
+        .filter_map(|file| std::fs::metadata(file).and_then(|meta| meta.modified()).ok())
+        .max()
+}
+
 /// CR DHCP Server D-Bus interface
 #[derive(Clone)]
 pub struct CRDhcp {
-    /// Whether DHCP server is running
-    running: Arc<RwLock<bool>>,
+    /// dora controller
+    controller: Arc<DhcpController>,
     /// Current configuration
     config: Arc<RwLock<Option<DhcpConfig>>>,
     /// Active DHCP leases
     leases: Arc<RwLock<Vec<CRDhcpLease>>>,
 }
 
-#[derive(Debug, Clone)]
-struct DhcpConfig {
-    interface: String,
-    range_start: String,
-    range_end: String,
-    gateway: String,
-    dns_servers: Vec<String>,
-}
-
 impl CRDhcp {
     /// Create a new CR DHCP interface
     pub fn new() -> Self {
         Self {
-            running: Arc::new(RwLock::new(false)),
+            controller: Arc::new(DhcpController::default()),
             config: Arc::new(RwLock::new(None)),
             leases: Arc::new(RwLock::new(Vec::new())),
         }
     }
 
-    /// Set running state
-    pub async fn set_running(&self, running: bool) {
-        let mut r = self.running.write().await;
-        *r = running;
-        info!("CR DHCP: Server running state set to {}", running);
+    /// Manage dora through `controller`
+    pub fn with_controller(mut self, controller: DhcpController) -> Self {
+        self.controller = Arc::new(controller);
+        self
     }
 
     /// Add a lease
-    pub async fn add_lease(&self, lease: CRDhcpLease) {
+    ///
+    /// Returns false if the client already holds that address, in which
+    /// case only the expiry and hostname are updated.
+    pub async fn add_lease(&self, lease: CRDhcpLease) -> bool {
         let mut leases = self.leases.write().await;
-        leases.push(lease);
+        match leases.iter_mut().find(|l| l.mac_address == lease.mac_address && l.ip_address == lease.ip_address) {
+            Some(existing) => {
+                *existing = lease;
+                false
+            }
+            None => {
+                leases.push(lease);
+                true
+            }
+        }
     }
 
-    /// Remove expired leases
-    pub async fn remove_expired_leases(&self) {
+    /// Remove expired leases, returning them
+    pub async fn remove_expired_leases(&self) -> Vec<CRDhcpLease> {
         let mut leases = self.leases.write().await;
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .expect("System time is before UNIX epoch")
             .as_secs();
-        leases.retain(|lease| lease.expiry > now);
+        let (kept, expired) = leases.drain(..).partition(|lease| lease.expiry > now);
+        *leases = kept;
+        expired
+    }
+
+    /// Replace the tracked leases with those in dora's lease database
+    ///
+    /// Hostnames come from the reservations of the current configuration,
+    /// as dora does not store them.
+    pub async fn refresh_leases(&self) -> NetctlResult<LeaseChanges> {
+        let (lease_time, hostnames) = match self.config.read().await.as_ref() {
+            Some(config) => (
+                config.lease_time,
+                config.reservations.iter()
+                    .filter_map(|r| Some((r.mac.to_ascii_lowercase(), r.hostname.clone()?)))
+                    .collect(),
+            ),
+            None => (DhcpConfig::default().lease_time, HashMap::new()),
+        };
+        let current: Vec<CRDhcpLease> = self.controller.leases().await?.iter()
+            .map(|lease| cr_lease(lease, lease_time, &hostnames))
+            .collect();
+        let mut leases = self.leases.write().await;
+        let changes = diff_leases(&leases, &current);
+        *leases = current;
+        Ok(changes)
+    }
+
+    /// Follow dora's lease database and report lease changes as signals
+    ///
+    /// The database is read again whenever it changed; expired leases are
+    /// removed on every tick. Leases already there when the monitor starts
+    /// are taken as they are, without signals.
+    pub fn start_lease_monitor(&self, connection: Connection) -> JoinHandle<()> {
+        let dhcp = self.clone();
+        tokio::spawn(async move {
+            let emitter = match SignalEmitter::new(&connection, CR_DHCP_PATH) {
+                Ok(emitter) => emitter,
+                Err(e) => {
+                    warn!("CR DHCP: Cannot emit lease signals: {}", e);
+                    return;
+                }
+            };
+            let mut ticker = interval(LEASE_POLL_INTERVAL);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            let mut first = true;
+            let mut following = false;
+            let mut last_modified = None;
+
+            loop {
+                ticker.tick().await;
+                if !dhcp.controller.is_running().await.unwrap_or(false) {
+                    if following {
+                        debug!("CR DHCP: Server stopped, no longer following leases");
+                        dhcp.leases.write().await.clear();
+                        following = false;
+                    }
+                    first = false;
+                    continue;
+                }
+
+                let modified = lease_db_modified(dhcp.controller.lease_db());
+                if !following || modified != last_modified {
+                    match dhcp.refresh_leases().await {
+                        Ok(changes) if !first => Self::emit_lease_changes(&emitter, &changes).await,
+                        Ok(_) => {}
+                        Err(e) => debug!("CR DHCP: Failed to read leases: {}", e),
+                    }
+                    following = true;
+                    last_modified = modified;
+                }
+                first = false;
+
+                let expired = dhcp.remove_expired_leases().await;
+                Self::emit_lease_changes(&emitter, &LeaseChanges { expired, ..Default::default() }).await;
+            }
+        })
+    }
+
+    async fn emit_lease_changes(emitter: &SignalEmitter<'_>, changes: &LeaseChanges) {
+        for lease in &changes.expired {
+            info!("CR DHCP: Lease of {} for {} ended", lease.ip_address, lease.mac_address);
+            if let Err(e) = Self::lease_expired(emitter, &lease.mac_address, &lease.ip_address).await {
+                warn!("CR DHCP: Failed to emit LeaseExpired: {}", e);
+            }
+        }
+        for lease in &changes.assigned {
+            info!("CR DHCP: Leased {} to {}", lease.ip_address, lease.mac_address);
+            let hostname = lease.hostname.as_deref().unwrap_or("");
+            if let Err(e) = Self::lease_assigned(emitter, &lease.mac_address, &lease.ip_address, hostname, lease.expiry).await {
+                warn!("CR DHCP: Failed to emit LeaseAssigned: {}", e);
+            }
+        }
+    }
+}
+
+/// D-Bus form of a server lease; dora only stores the expiry, so the start
+/// is derived from the configured lease time
+fn cr_lease(lease: &DhcpServerLease, lease_time: u32, hostnames: &HashMap<String, String>) -> CRDhcpLease {
+    CRDhcpLease {
+        mac_address: lease.mac_address.clone().unwrap_or_else(|| lease.client_id.clone()),
+        ip_address: lease.ip_address.to_string(),
+        hostname: lease.mac_address.as_ref().and_then(|mac| hostnames.get(mac).cloned()),
+        expiry: lease.expires_at,
+        start_time: lease.expires_at.saturating_sub(u64::from(lease_time)),
+    }
+}
+
+/// Map a netctl error to a D-Bus error
+fn dbus_error(err: NetctlError) -> fdo::Error {
+    match err {
+        NetctlError::InvalidParameter(msg) => fdo::Error::InvalidArgs(msg),
+        NetctlError::PermissionDenied(msg) => fdo::Error::AccessDenied(msg),
+        other => fdo::Error::Failed(other.to_string()),
     }
 }
 
@@ -76,6 +237,7 @@ impl CRDhcp {
         range_end: &str,
         gateway: &str,
         dns_servers: Vec<String>,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
     ) -> fdo::Result<()> {
         info!(
             "CR DHCP: Starting DHCP server on {} (range: {} - {})",
@@ -94,64 +256,70 @@ impl CRDhcp {
         if gateway.is_empty() {
             return Err(fdo::Error::InvalidArgs("Gateway cannot be empty".to_string()));
         }
+        validation::validate_interface_name(interface).map_err(dbus_error)?;
 
         // Check if already running
-        let running = self.running.read().await;
-        if *running {
+        if self.controller.is_running().await.map_err(dbus_error)? {
             return Err(fdo::Error::Failed("DHCP server already running".to_string()));
         }
-        drop(running);
 
-        // Store configuration
         let config = DhcpConfig {
             interface: interface.to_string(),
             range_start: range_start.to_string(),
             range_end: range_end.to_string(),
             gateway: gateway.to_string(),
             dns_servers,
+            ..Default::default()
         };
+        self.controller.start(&config).await.map_err(dbus_error)?;
 
-        let mut cfg = self.config.write().await;
-        *cfg = Some(config);
-        drop(cfg);
+        // Store configuration
+        *self.config.write().await = Some(config);
 
-        // Set running state
-        self.set_running(true).await;
+        // Leases left from an earlier run are not news
+        if let Err(e) = self.refresh_leases().await {
+            debug!("CR DHCP: Failed to read leases: {}", e);
+        }
 
-        // Actual DHCP server start will be handled by integration layer
+        if let Err(e) = Self::server_started(&emitter, interface).await {
+            warn!("CR DHCP: Failed to emit ServerStarted: {}", e);
+        }
 
         Ok(())
     }
 
     /// Stop DHCP server
-    async fn stop_server(&self) -> fdo::Result<()> {
+    async fn stop_server(&self, #[zbus(signal_emitter)] emitter: SignalEmitter<'_>) -> fdo::Result<()> {
         info!("CR DHCP: Stopping DHCP server");
 
-        let running = self.running.read().await;
-        if !*running {
+        if !self.controller.is_running().await.map_err(dbus_error)? {
             return Err(fdo::Error::Failed("DHCP server not running".to_string()));
         }
-        drop(running);
 
-        // Clear configuration
-        let mut cfg = self.config.write().await;
-        *cfg = None;
-        drop(cfg);
+        self.controller.stop().await.map_err(dbus_error)?;
 
-        // Set running state
-        self.set_running(false).await;
+        // Clear configuration
+        *self.config.write().await = None;
+        self.leases.write().await.clear();
 
-        // Actual DHCP server stop will be handled by integration layer
+        if let Err(e) = Self::server_stopped(&emitter).await {
+            warn!("CR DHCP: Failed to emit ServerStopped: {}", e);
+        }
 
         Ok(())
     }
 
     /// Get DHCP server status
-    async fn get_status(&self) -> HashMap<String, Value<'static>> {
+    async fn get_status(&self) -> fdo::Result<HashMap<String, Value<'static>>> {
         let mut status = HashMap::new();
 
-        let running = self.running.read().await;
-        status.insert("Running".to_string(), Value::new(*running));
+        let server = self.controller.status().await.map_err(dbus_error)?;
+        status.insert("Running".to_string(), Value::new(server.running));
+        if let Some(pid) = server.pid {
+            status.insert("Pid".to_string(), Value::new(pid));
+        }
+        status.insert("Supervised".to_string(), Value::new(server.supervised));
+        status.insert("Restarts".to_string(), Value::new(server.restarts));
 
         if let Some(ref config) = *self.config.read().await {
             status.insert("Interface".to_string(), Value::new(config.interface.clone()));
@@ -161,15 +329,23 @@ impl CRDhcp {
             status.insert("DNSServers".to_string(), Value::new(config.dns_servers.clone()));
         }
 
-        let leases = self.leases.read().await;
-        status.insert("LeaseCount".to_string(), Value::new(leases.len() as u32));
+        let lease_count = match server.active_leases {
+            Some(count) => count as u32,
+            None => self.leases.read().await.len() as u32,
+        };
+        status.insert("LeaseCount".to_string(), Value::new(lease_count));
 
         debug!("CR DHCP: Returning status");
-        status
+        Ok(status)
     }
 
     /// Get all DHCP leases
-    async fn get_leases(&self) -> Vec<HashMap<String, Value<'static>>> {
+    async fn get_leases(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<Vec<HashMap<String, Value<'static>>>> {
+        let changes = self.refresh_leases().await.map_err(dbus_error)?;
+        Self::emit_lease_changes(&emitter, &changes).await;
         let leases = self.leases.read().await;
         let mut result = Vec::new();
 
@@ -189,12 +365,12 @@ impl CRDhcp {
         }
 
         debug!("CR DHCP: Returning {} leases", result.len());
-        result
+        Ok(result)
     }
 
     /// Check if DHCP server is running
-    async fn is_running(&self) -> bool {
-        *self.running.read().await
+    async fn is_running(&self) -> fdo::Result<bool> {
+        self.controller.is_running().await.map_err(dbus_error)
     }
 
     // ============ D-Bus Signals ============
@@ -208,12 +384,15 @@ impl CRDhcp {
     async fn server_stopped(signal_emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
 
     /// LeaseAssigned signal - emitted when a lease is assigned
+    ///
+    /// `hostname` is empty if unknown; `expiry` is a Unix timestamp.
     #[zbus(signal)]
     async fn lease_assigned(
         signal_emitter: &SignalEmitter<'_>,
         mac_address: &str,
         ip_address: &str,
         hostname: &str,
+        expiry: u64,
     ) -> zbus::Result<()>;
 
     /// LeaseExpired signal - emitted when a lease expires
@@ -272,13 +451,14 @@ pub mod signals {
         mac_address: &str,
         ip_address: &str,
         hostname: &str,
+        expiry: u64,
     ) -> NetctlResult<()> {
         if let Ok(iface_ref) = conn
             .object_server()
             .interface::<_, CRDhcp>(CR_DHCP_PATH)
             .await
         {
-            CRDhcp::lease_assigned(iface_ref.signal_emitter(), mac_address, ip_address, hostname)
+            CRDhcp::lease_assigned(iface_ref.signal_emitter(), mac_address, ip_address, hostname, expiry)
                 .await
                 .map_err(|e| NetctlError::ServiceError(format!("Failed to emit LeaseAssigned: {}", e)))?;
         }
@@ -303,3 +483,101 @@ pub mod signals {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dhcp::DhcpReservation;
+
+    fn lease(mac: &str, ip: &str, expiry: u64) -> CRDhcpLease {
+        CRDhcpLease {
+            mac_address: mac.to_string(),
+            ip_address: ip.to_string(),
+            hostname: None,
+            expiry,
+            start_time: expiry.saturating_sub(3600),
+        }
+    }
+
+    #[test]
+    fn test_diff_leases() {
+        let old = vec![lease("aa:00:00:00:00:01", "10.0.0.10", 100), lease("aa:00:00:00:00:02", "10.0.0.11", 100)];
+        let new = vec![
+            // Renewed, no change
+            lease("aa:00:00:00:00:01", "10.0.0.10", 200),
+            // Same client, new address
+            lease("aa:00:00:00:00:02", "10.0.0.12", 200),
+            lease("aa:00:00:00:00:03", "10.0.0.13", 200),
+        ];
+        let changes = diff_leases(&old, &new);
+        let ips = |leases: &[CRDhcpLease]| leases.iter().map(|l| l.ip_address.clone()).collect::<Vec<_>>();
+        assert_eq!(ips(&changes.assigned), vec!["10.0.0.12", "10.0.0.13"]);
+        assert_eq!(ips(&changes.expired), vec!["10.0.0.11"]);
+        assert!(diff_leases(&new, &new).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_track_leases() {
+        let dhcp = CRDhcp::new();
+        assert!(dhcp.add_lease(lease("aa:00:00:00:00:01", "10.0.0.10", u64::MAX)).await);
+        assert!(!dhcp.add_lease(lease("aa:00:00:00:00:01", "10.0.0.10", u64::MAX - 1)).await);
+        assert!(dhcp.add_lease(lease("aa:00:00:00:00:02", "10.0.0.11", 1)).await);
+
+        let expired = dhcp.remove_expired_leases().await;
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].ip_address, "10.0.0.11");
+        let leases = dhcp.leases.read().await;
+        assert_eq!(leases.len(), 1);
+        assert_eq!(leases[0].expiry, u64::MAX - 1);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_leases_from_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("leases.db");
+        let dhcp = CRDhcp::new()
+            .with_controller(DhcpController::new(dir.path().join("dora.yaml")).with_lease_db(&db));
+        *dhcp.config.write().await = Some(DhcpConfig {
+            reservations: vec![DhcpReservation {
+                mac: "AA:BB:CC:DD:EE:FF".to_string(),
+                ip: "10.0.0.10".to_string(),
+                hostname: Some("printer".to_string()),
+            }],
+            ..Default::default()
+        });
+
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+        let sql = format!(
+            "CREATE TABLE leases(ip INTEGER NOT NULL, client_id BLOB, leased BOOLEAN NOT NULL DEFAULT 0, \
+             expires_at INTEGER NOT NULL, network INTEGER NOT NULL, probation BOOLEAN NOT NULL DEFAULT 0, \
+             PRIMARY KEY(ip));\
+             INSERT INTO leases VALUES (167772170, x'01aabbccddeeff', 1, {0}, 167772160, 0);",
+            now + 3600
+        );
+        match std::process::Command::new("sqlite3").arg(&db).arg(sql).status() {
+            Ok(status) if status.success() => {}
+            _ => {
+                println!("SKIP: sqlite3 not available");
+                return;
+            }
+        }
+        let modified = lease_db_modified(&db);
+        assert!(modified.is_some());
+
+        let changes = dhcp.refresh_leases().await.unwrap();
+        assert_eq!(changes.assigned.len(), 1);
+        assert_eq!(changes.assigned[0].mac_address, "aa:bb:cc:dd:ee:ff");
+        assert_eq!(changes.assigned[0].hostname.as_deref(), Some("printer"));
+        assert_eq!(changes.assigned[0].expiry, now + 3600);
+        assert!(dhcp.refresh_leases().await.unwrap().is_empty());
+
+        // Released by the client
+        let status = std::process::Command::new("sqlite3").arg(&db)
+            .arg("UPDATE leases SET leased = 0;").status().unwrap();
+        assert!(status.success());
+        let changes = dhcp.refresh_leases().await.unwrap();
+        assert!(changes.assigned.is_empty());
+        assert_eq!(changes.expired.len(), 1);
+        assert!(dhcp.leases.read().await.is_empty());
+    }
+}
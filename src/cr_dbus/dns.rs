@@ -3,11 +3,14 @@
 //! D-Bus interface for DNS server management
 
 use super::types::*;
+use crate::dns::{DnsConfig, DnsController};
 use crate::error::{NetctlError, NetctlResult};
+use crate::interface::InterfaceController;
 use std::collections::HashMap;
+use std::net::IpAddr;
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use tracing::{info, debug};
+use tracing::{info, debug, warn};
 use zbus::{Connection, fdo, interface};
 use zbus::object_server::SignalEmitter;
 use zbus::zvariant::Value;
@@ -15,8 +18,8 @@ use zbus::zvariant::Value;
 /// CR DNS Server D-Bus interface
 #[derive(Clone)]
 pub struct CRDns {
-    /// Whether DNS server is running
-    running: Arc<RwLock<bool>>,
+    /// unbound controller
+    controller: Arc<DnsController>,
     /// DNS forwarders (upstream DNS servers)
     forwarders: Arc<RwLock<Vec<String>>>,
     /// Listen address
@@ -29,20 +32,13 @@ impl CRDns {
     /// Create a new CR DNS interface
     pub fn new() -> Self {
         Self {
-            running: Arc::new(RwLock::new(false)),
+            controller: Arc::new(DnsController::new()),
             forwarders: Arc::new(RwLock::new(Vec::new())),
             listen_address: Arc::new(RwLock::new(None)),
             listen_port: Arc::new(RwLock::new(53)),
         }
     }
 
-    /// Set running state
-    pub async fn set_running(&self, running: bool) {
-        let mut r = self.running.write().await;
-        *r = running;
-        info!("CR DNS: Server running state set to {}", running);
-    }
-
     /// Add a forwarder
     pub async fn add_forwarder_internal(&self, forwarder: String) {
         let mut forwarders = self.forwarders.write().await;
@@ -61,6 +57,65 @@ impl CRDns {
             false
         }
     }
+
+    /// unbound configuration for the current settings
+    async fn config(&self) -> DnsConfig {
+        let listen: Vec<String> = self.listen_address.read().await.iter().cloned().collect();
+        let mut access_control = Vec::new();
+        for address in &listen {
+            access_control.extend(local_network(address).await);
+        }
+
+        DnsConfig {
+            listen,
+            port: *self.listen_port.read().await,
+            access_control,
+            forwarders: self.forwarders.read().await.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Push the current settings to a running server
+    async fn reload_if_running(&self) -> fdo::Result<()> {
+        if self.controller.is_running().await.map_err(dbus_error)? {
+            self.controller.reload(&self.config().await).await.map_err(dbus_error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Map a netctl error to a D-Bus error
+fn dbus_error(err: NetctlError) -> fdo::Error {
+    match err {
+        NetctlError::InvalidParameter(msg) => fdo::Error::InvalidArgs(msg),
+        NetctlError::PermissionDenied(msg) => fdo::Error::AccessDenied(msg),
+        other => fdo::Error::Failed(other.to_string()),
+    }
+}
+
+fn validate_forwarder(forwarder: &str) -> fdo::Result<()> {
+    forwarder.parse::<IpAddr>()
+        .map(|_| ())
+        .map_err(|_| fdo::Error::InvalidArgs(format!("Invalid forwarder address: {}", forwarder)))
+}
+
+/// Subnet of the local interface address `address`, so clients on that
+/// link are allowed to query
+async fn local_network(address: &str) -> Option<String> {
+    let ip: IpAddr = address.parse().ok()?;
+    if ip.is_unspecified() || ip.is_loopback() {
+        return None;
+    }
+
+    let ifaces = InterfaceController::new();
+    for name in ifaces.list().await.ok()? {
+        let Ok(info) = ifaces.get_info(&name).await else { continue };
+        if let Some(addr) = info.addresses.iter().find(|a| a.address == address) {
+            return Some(format!("{}/{}", addr.address, addr.prefix_len));
+        }
+    }
+    warn!("CR DNS: {} is not a local address, only localhost may query", address);
+    None
 }
 
 #[interface(name = "org.crrouter.NetworkControl.DNS")]
@@ -71,6 +126,7 @@ impl CRDns {
         listen_address: &str,
         listen_port: u16,
         forwarders: Vec<String>,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
     ) -> fdo::Result<()> {
         info!(
             "CR DNS: Starting DNS server on {}:{}",
@@ -86,83 +142,80 @@ impl CRDns {
             return Err(fdo::Error::InvalidArgs("Listen port cannot be 0".to_string()));
         }
 
+        for forwarder in &forwarders {
+            validate_forwarder(forwarder)?;
+        }
+
         // Check if already running
-        let running = self.running.read().await;
-        if *running {
+        if self.controller.is_running().await.map_err(dbus_error)? {
             return Err(fdo::Error::Failed("DNS server already running".to_string()));
         }
-        drop(running);
 
         // Set configuration
-        let mut addr = self.listen_address.write().await;
-        *addr = Some(listen_address.to_string());
-        drop(addr);
-
-        let mut port = self.listen_port.write().await;
-        *port = listen_port;
-        drop(port);
+        *self.listen_address.write().await = Some(listen_address.to_string());
+        *self.listen_port.write().await = listen_port;
+        *self.forwarders.write().await = forwarders;
 
-        // Set forwarders
-        let mut fwd = self.forwarders.write().await;
-        *fwd = forwarders;
-        drop(fwd);
+        self.controller.start(&self.config().await).await.map_err(dbus_error)?;
 
-        // Set running state
-        self.set_running(true).await;
-
-        // Actual DNS server start will be handled by integration layer
+        if let Err(e) = Self::server_started(&emitter, listen_address, listen_port).await {
+            warn!("CR DNS: Failed to emit ServerStarted: {}", e);
+        }
 
         Ok(())
     }
 
     /// Stop DNS server
-    async fn stop_server(&self) -> fdo::Result<()> {
+    async fn stop_server(&self, #[zbus(signal_emitter)] emitter: SignalEmitter<'_>) -> fdo::Result<()> {
         info!("CR DNS: Stopping DNS server");
 
-        let running = self.running.read().await;
-        if !*running {
+        if !self.controller.is_running().await.map_err(dbus_error)? {
             return Err(fdo::Error::Failed("DNS server not running".to_string()));
         }
-        drop(running);
 
-        // Clear configuration
-        let mut addr = self.listen_address.write().await;
-        *addr = None;
-        drop(addr);
+        self.controller.stop().await.map_err(dbus_error)?;
 
-        // Set running state
-        self.set_running(false).await;
+        // Clear configuration
+        *self.listen_address.write().await = None;
 
-        // Actual DNS server stop will be handled by integration layer
+        if let Err(e) = Self::server_stopped(&emitter).await {
+            warn!("CR DNS: Failed to emit ServerStopped: {}", e);
+        }
 
         Ok(())
     }
 
     /// Add a DNS forwarder
-    async fn add_forwarder(&self, forwarder: &str) -> fdo::Result<()> {
+    async fn add_forwarder(&self, forwarder: &str, #[zbus(signal_emitter)] emitter: SignalEmitter<'_>) -> fdo::Result<()> {
         info!("CR DNS: Adding forwarder: {}", forwarder);
 
         if forwarder.is_empty() {
             return Err(fdo::Error::InvalidArgs("Forwarder address cannot be empty".to_string()));
         }
-
-        // Basic validation - check if it's a valid IP address format
-        if !forwarder.contains('.') && !forwarder.contains(':') {
-            return Err(fdo::Error::InvalidArgs("Invalid forwarder address format".to_string()));
-        }
+        validate_forwarder(forwarder)?;
 
         self.add_forwarder_internal(forwarder.to_string()).await;
+        self.reload_if_running().await?;
+
+        if let Err(e) = Self::forwarder_added(&emitter, forwarder).await {
+            warn!("CR DNS: Failed to emit ForwarderAdded: {}", e);
+        }
 
         Ok(())
     }
 
     /// Remove a DNS forwarder
-    async fn remove_forwarder(&self, forwarder: &str) -> fdo::Result<()> {
+    async fn remove_forwarder(&self, forwarder: &str, #[zbus(signal_emitter)] emitter: SignalEmitter<'_>) -> fdo::Result<()> {
         info!("CR DNS: Removing forwarder: {}", forwarder);
 
         if !self.remove_forwarder_internal(forwarder).await {
             return Err(fdo::Error::Failed(format!("Forwarder not found: {}", forwarder)));
         }
+        self.reload_if_running().await?;
+
+        if let Err(e) = Self::forwarder_removed(&emitter, forwarder).await {
+            warn!("CR DNS: Failed to emit ForwarderRemoved: {}", e);
+        }
 
         Ok(())
     }
@@ -175,11 +228,26 @@ impl CRDns {
     }
 
     /// Get DNS server status
-    async fn get_status(&self) -> HashMap<String, Value<'static>> {
+    async fn get_status(&self) -> fdo::Result<HashMap<String, Value<'static>>> {
         let mut status = HashMap::new();
 
-        let running = self.running.read().await;
-        status.insert("Running".to_string(), Value::new(*running));
+        let server = self.controller.status().await.map_err(dbus_error)?;
+        status.insert("Running".to_string(), Value::new(server.running));
+        if let Some(pid) = server.pid {
+            status.insert("Pid".to_string(), Value::new(pid));
+        }
+        if let Some(version) = server.version {
+            status.insert("Version".to_string(), Value::new(version));
+        }
+        if let Some(uptime) = server.uptime_secs {
+            status.insert("Uptime".to_string(), Value::new(uptime));
+        }
+        if let Some(queries) = server.queries {
+            status.insert("Queries".to_string(), Value::new(queries));
+        }
+        if let Some(hits) = server.cache_hits {
+            status.insert("CacheHits".to_string(), Value::new(hits));
+        }
 
         if let Some(ref addr) = *self.listen_address.read().await {
             status.insert("ListenAddress".to_string(), Value::new(addr.clone()));
@@ -193,12 +261,12 @@ impl CRDns {
         status.insert("ForwarderCount".to_string(), Value::new(forwarders.len() as u32));
 
         debug!("CR DNS: Returning status");
-        status
+        Ok(status)
     }
 
     /// Check if DNS server is running
     async fn is_running(&self) -> bool {
-        *self.running.read().await
+        self.controller.is_running().await.unwrap_or(false)
     }
 
     /// Set DNS forwarders (replaces all existing forwarders)
@@ -210,15 +278,18 @@ impl CRDns {
             if forwarder.is_empty() {
                 return Err(fdo::Error::InvalidArgs("Forwarder address cannot be empty".to_string()));
             }
-            if !forwarder.contains('.') && !forwarder.contains(':') {
-                return Err(fdo::Error::InvalidArgs(format!("Invalid forwarder address: {}", forwarder)));
-            }
+            validate_forwarder(forwarder)?;
         }
 
-        let mut fwd = self.forwarders.write().await;
-        *fwd = forwarders;
+        *self.forwarders.write().await = forwarders;
+        self.reload_if_running().await
+    }
 
-        Ok(())
+    /// Flush the DNS cache; an empty zone flushes everything
+    async fn flush_cache(&self, zone: &str) -> fdo::Result<()> {
+        info!("CR DNS: Flushing cache{}", if zone.is_empty() { String::new() } else { format!(" for {}", zone) });
+        let zone = (!zone.is_empty()).then_some(zone);
+        self.controller.flush(zone).await.map_err(dbus_error)
     }
 
     // ============ D-Bus Signals ============
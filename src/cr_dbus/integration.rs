@@ -17,8 +17,10 @@ use crate::device::{DeviceController, Device};
 use crate::wpa_supplicant::{WpaSupplicantController, WpaSecurityType};
 use crate::network_monitor::{NetworkMonitor, NetworkEvent};
 use crate::connection_manager::ConnectionManager;
-use crate::dhcp_client::DhcpClientController;
+use crate::wifi_autoselect::WifiAutoselect;
+use crate::dhcp_client::{DhcpClientController, DhcpEvent};
 use crate::interface::InterfaceController;
+use crate::routing::{Route, RouteType, RT_TABLE_MAIN};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{info, warn, error, debug};
@@ -51,6 +53,8 @@ pub struct CRDbusService {
     running: Arc<RwLock<bool>>,
     /// WPA Supplicant controller for WiFi operations
     wpa_supplicant: Arc<WpaSupplicantController>,
+    /// Picks and follows the WiFi network of auto-connected interfaces
+    wifi_autoselect: WifiAutoselect,
     /// Primary WiFi interface name (e.g., wlan0)
     wifi_interface: Arc<RwLock<Option<String>>>,
     /// Network event monitor
@@ -169,8 +173,10 @@ impl CRDbusService {
 
         // Create network monitoring and connection management components
         let network_monitor = Arc::new(NetworkMonitor::new());
-        let connection_manager = Arc::new(ConnectionManager::new(None));
-        let dhcp_client = Arc::new(DhcpClientController::new());
+        // Address conflicts on active connections come in with the netlink events
+        let connection_manager = Arc::new(ConnectionManager::new(None).with_network_monitor(network_monitor.clone()));
+        // Share the connection manager's client so an interface never runs two
+        let dhcp_client = connection_manager.dhcp_client();
         let interface_controller = Arc::new(InterfaceController::new());
 
         // Initialize connection manager
@@ -190,6 +196,9 @@ impl CRDbusService {
             }
         }
 
+        let wpa_supplicant = Arc::new(WpaSupplicantController::new());
+        let wifi_autoselect = WifiAutoselect::new(wpa_supplicant.clone(), connection_manager.clone());
+
         let service = Arc::new(Self {
             connection: Arc::new(connection),
             network_control,
@@ -201,7 +210,8 @@ impl CRDbusService {
             routing,
             privilege,
             running: Arc::new(RwLock::new(true)),
-            wpa_supplicant: Arc::new(WpaSupplicantController::new()),
+            wpa_supplicant,
+            wifi_autoselect,
             wifi_interface: Arc::new(RwLock::new(None)),
             network_monitor,
             connection_manager,
@@ -262,12 +272,38 @@ impl CRDbusService {
             info!("Network event handler stopped");
         });
 
+        // Keep the IPv4 details of devices in line with their DHCP leases
+        let mut dhcp_rx = self.dhcp_client.subscribe();
+        let service = self.clone();
+        tokio::spawn(async move {
+            while let Ok(event) = dhcp_rx.recv().await {
+                service.handle_dhcp_event(event).await;
+            }
+        });
+
+        // Report clients of the DHCP server as they come and go
+        self.dhcp.start_lease_monitor(self.connection.as_ref().clone());
+
         // Check initial state of existing interfaces
         self.initialize_existing_interfaces().await;
 
         Ok(())
     }
 
+    /// Update the device of a DHCP lease change
+    async fn handle_dhcp_event(&self, event: DhcpEvent) {
+        let device_path = format!("{}/{}", CR_DEVICE_PATH_PREFIX, event.interface());
+        let lease = match event {
+            DhcpEvent::Bound(lease) | DhcpEvent::Renewed(lease) => Some(lease),
+            // The client keeps trying; nothing was configured
+            DhcpEvent::Declined { .. } => return,
+            DhcpEvent::Expired { .. } | DhcpEvent::Released { .. } | DhcpEvent::Stopped { .. } => None,
+        };
+        if let Err(e) = self.network_control.update_device_dhcp4(&device_path, lease).await {
+            debug!("Failed to update DHCP lease of device: {}", e);
+        }
+    }
+
     /// Initialize existing interfaces based on their current state
     ///
     /// This handles interfaces that already exist at boot time.
@@ -297,6 +333,12 @@ impl CRDbusService {
                 continue;
             }
 
+            // For WiFi interfaces: pick a known network and connect
+            if Self::is_wifi_interface(&name) {
+                self.spawn_wifi_auto_connect(&name, tokio::time::Duration::ZERO);
+                continue; // DHCP will start when link comes up
+            }
+
             // Check if we have a config for this interface
             let config = match self.find_connection_config_for_interface(&name).await {
                 Some(c) => c,
@@ -306,15 +348,6 @@ impl CRDbusService {
             let (config_name, cfg) = config;
             info!("Found config '{}' for interface {}", config_name, name);
 
-            // For WiFi interfaces: try to connect
-            if Self::is_wifi_interface(&name) && cfg.wifi.is_some() {
-                info!("Initiating WiFi auto-connect for {}", name);
-                if let Err(e) = self.try_wifi_auto_connect(&name).await {
-                    warn!("WiFi auto-connect failed for {}: {}", name, e);
-                }
-                continue; // DHCP will start when link comes up
-            }
-
             // For ethernet/other interfaces: bring link layer UP first, then check carrier
             if cfg.ipv4.as_ref().map(|v| v.method.as_str()) == Some("auto") {
                 info!("Bringing up interface {} link layer", name);
@@ -365,9 +398,7 @@ impl CRDbusService {
                 info!("Interface added: {} (index {})", name, index);
                 // Try WiFi auto-connect if this is a WiFi interface with config
                 if Self::is_wifi_interface(&name) {
-                    if let Err(e) = self.try_wifi_auto_connect(&name).await {
-                        debug!("WiFi auto-connect failed for {}: {}", name, e);
-                    }
+                    self.spawn_wifi_auto_connect(&name, tokio::time::Duration::ZERO);
                 }
             }
             NetworkEvent::InterfaceRemoved { name, index } => {
@@ -380,6 +411,39 @@ impl CRDbusService {
             NetworkEvent::LinkPropertiesChanged { name, .. } => {
                 debug!("Link properties changed on {}", name);
             }
+            NetworkEvent::RouteAdded { route } => {
+                self.handle_route_change(&route, true).await?;
+            }
+            NetworkEvent::RouteRemoved { route } => {
+                self.handle_route_change(&route, false).await?;
+            }
+            NetworkEvent::AddressConflict { name, address, mac, .. } => {
+                warn!("Address {} on {} is claimed by {}", address, name, mac);
+            }
+        }
+        Ok(())
+    }
+
+    /// Emit Routing signals for a kernel route change
+    ///
+    /// Changes to the main table's default route are also reported as
+    /// DefaultGatewayChanged (with an empty gateway when it is removed).
+    async fn handle_route_change(&self, route: &Route, added: bool) -> NetctlResult<()> {
+        let destination = route.destination_prefix();
+        if added {
+            let gateway = route.gateway.map(|gw| gw.to_string()).unwrap_or_default();
+            let interface = route.interface.clone().unwrap_or_default();
+            super::routing::signals::emit_route_added(&self.connection, &destination, &gateway, &interface).await?;
+        } else {
+            super::routing::signals::emit_route_removed(&self.connection, &destination).await?;
+        }
+
+        if route.is_default() && route.table == RT_TABLE_MAIN && route.kind == RouteType::Unicast {
+            let gateway = match (added, route.gateway) {
+                (true, Some(gw)) => gw.to_string(),
+                _ => String::new(),
+            };
+            super::routing::signals::emit_default_gateway_changed(&self.connection, &gateway, route.is_ipv6()).await?;
         }
         Ok(())
     }
@@ -442,16 +506,13 @@ impl CRDbusService {
                         warn!("Failed to stop DHCP on {}: {}", interface, e);
                     }
                 }
+            }
 
-                // Try WiFi reconnect if this is a WiFi interface
-                if Self::is_wifi_interface(interface) && cfg.wifi.is_some() {
-                    info!("WiFi link down on {}, attempting reconnect", interface);
-                    // Small delay before reconnect attempt
-                    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-                    if let Err(e) = self.try_wifi_auto_connect(interface).await {
-                        warn!("WiFi reconnect failed for {}: {}", interface, e);
-                    }
-                }
+            // Try WiFi reconnect, unless auto-selection already follows the
+            // connection and picks the next network itself
+            if Self::is_wifi_interface(interface) && !self.wifi_autoselect.is_watching(interface) {
+                // Small delay before reconnect attempt
+                self.spawn_wifi_auto_connect(interface, tokio::time::Duration::from_secs(2));
             }
 
             // Update D-Bus device state
@@ -503,30 +564,33 @@ impl CRDbusService {
         interface.starts_with("wlan") || interface.starts_with("wl")
     }
 
-    /// Try to auto-connect WiFi based on config
-    async fn try_wifi_auto_connect(&self, interface: &str) -> NetctlResult<()> {
-        // Find config for this interface
-        let (name, config) = self.find_connection_config_for_interface(interface).await
-            .ok_or_else(|| NetctlError::NotFound(format!("No config found for {}", interface)))?;
-
-        // Check if it's a WiFi config
-        let wifi = config.wifi.as_ref()
-            .ok_or_else(|| NetctlError::ConfigError(format!("Config '{}' is not a WiFi config", name)))?;
-
-        let ssid = &wifi.ssid;
-
-        // Get password from wifi-security section
-        let psk = config.wifi_security.as_ref().and_then(|sec| {
-            sec.psk.as_deref().or(sec.password.as_deref())
+    /// Auto-connect WiFi to the best known network in range, after `delay`
+    ///
+    /// Runs in the background so the scan and connection attempts do not
+    /// hold up event handling. Interfaces no autoconnect WiFi profile
+    /// applies to are left alone.
+    fn spawn_wifi_auto_connect(&self, interface: &str, delay: tokio::time::Duration) {
+        let autoselect = self.wifi_autoselect.clone();
+        let interface = interface.to_string();
+        tokio::spawn(async move {
+            match autoselect.has_profiles(&interface).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    debug!("No WiFi connection auto-connects {}", interface);
+                    return;
+                }
+                Err(e) => {
+                    warn!("Failed to load connections for {}: {}", interface, e);
+                    return;
+                }
+            }
+            tokio::time::sleep(delay).await;
+            info!("Initiating WiFi auto-connect for {}", interface);
+            match autoselect.select(&interface).await {
+                Ok(name) => info!("WiFi auto-connect of {} succeeded (config: {})", interface, name),
+                Err(e) => warn!("WiFi auto-connect failed for {}: {}", interface, e),
+            }
         });
-
-        info!("Auto-connecting WiFi {} to SSID '{}' (config: {})", interface, ssid, name);
-
-        // Connect via wpa_supplicant
-        self.wpa_supplicant.connect(interface, ssid, psk).await?;
-
-        info!("WiFi auto-connect initiated for {} -> '{}'", interface, ssid);
-        Ok(())
     }
 
     /// Get network control interface
@@ -617,6 +681,15 @@ impl CRDbusService {
                         }
                     }
 
+                    // A lease left by a previous run whose address is still configured
+                    if let Ok(Some(lease)) = self.dhcp_client.saved_lease(&device.name).await {
+                        let configured = lease.ip_address.as_ref()
+                            .is_some_and(|ip| device.addresses.contains(ip));
+                        if configured && !lease.is_expired() {
+                            device_info.dhcp4 = Some(lease);
+                        }
+                    }
+
                     // Determine device state based on flags
                     device_info.state = if device.flags.contains(&"UP".to_string()) {
                         CRDeviceState::Activated
@@ -811,14 +884,14 @@ impl CRDbusService {
         info!("Starting WiFi scan on {}", interface);
         self.wifi.set_scanning(true).await;
 
-        // Trigger scan
-        self.wpa_supplicant.scan(&interface).await?;
-
-        // Wait a bit for scan to complete
-        tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
-
-        // Get scan results
-        let scan_results = self.wpa_supplicant.scan_results(&interface).await?;
+        // Scan and wait for wpa_supplicant to report the results
+        let scan_results = match self.wpa_supplicant.scan_and_wait(&interface).await {
+            Ok(results) => results,
+            Err(e) => {
+                self.wifi.set_scanning(false).await;
+                return Err(e);
+            }
+        };
 
         // Convert to CRAccessPointInfo
         let mut access_points = Vec::new();
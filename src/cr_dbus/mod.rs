@@ -247,4 +247,21 @@ mod tests {
         assert_eq!(info.state, CRVpnState::Disconnected);
         assert!(info.path.contains("my_vpn"));
     }
+
+    #[test]
+    fn test_route_to_dict() {
+        let mut route = crate::routing::Route::new("10.0.0.0".parse().unwrap(), 8);
+        route.kind = crate::routing::RouteType::Blackhole;
+        route.table = 100;
+        let dict = routing::route_to_dict(&route);
+        assert_eq!(dict["Destination"], zbus::zvariant::Value::new("10.0.0.0/8"));
+        assert_eq!(dict["Type"], zbus::zvariant::Value::new(CRRouteType::Blackhole as u32));
+        assert_eq!(dict["Table"], zbus::zvariant::Value::new(100u32));
+        assert!(!dict.contains_key("Gateway"));
+
+        let default = crate::routing::Route::default_via("fe80::1".parse().unwrap());
+        let dict = routing::route_to_dict(&default);
+        assert_eq!(dict["Destination"], zbus::zvariant::Value::new("default"));
+        assert_eq!(dict["Gateway"], zbus::zvariant::Value::new("fe80::1"));
+    }
 }
@@ -3,6 +3,7 @@
 //! Main D-Bus interface for controlling network operations through the CR router
 
 use super::types::*;
+use crate::dhcp_client::DhcpLease;
 use crate::error::{NetctlError, NetctlResult};
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -73,6 +74,28 @@ impl CRNetworkControl {
         }
     }
 
+    /// Update the DHCPv4 lease of a device
+    ///
+    /// A new lease also becomes the device's IPv4 address; losing the lease
+    /// clears the address if it came from it.
+    pub async fn update_device_dhcp4(&self, path: &str, lease: Option<DhcpLease>) -> NetctlResult<()> {
+        let mut devices = self.devices.write().await;
+        let device = devices.get_mut(path)
+            .ok_or_else(|| NetctlError::NotFound(format!("Device {} not found", path)))?;
+        match lease.as_ref().and_then(|lease| lease.ip_address.clone()) {
+            Some(address) => device.ipv4_address = Some(address),
+            None => {
+                let previous = device.dhcp4.as_ref().and_then(|lease| lease.ip_address.as_ref());
+                if previous.is_some() && previous == device.ipv4_address.as_ref() {
+                    device.ipv4_address = None;
+                }
+            }
+        }
+        debug!("CR: Device {} DHCPv4 lease updated", path);
+        device.dhcp4 = lease;
+        Ok(())
+    }
+
     /// Get device information (internal)
     pub async fn get_device_info_internal(&self, path: &str) -> Option<CRDeviceInfo> {
         let devices = self.devices.read().await;
@@ -154,6 +177,7 @@ impl CRNetworkControl {
                 info.insert("HwAddress".to_string(), Value::new(hw.clone()));
             }
             info.insert("Mtu".to_string(), Value::new(device.mtu));
+            info.extend(device.ip4_lease_properties());
 
             Ok(info)
         } else {
@@ -4,57 +4,191 @@
 
 use super::types::*;
 use crate::error::{NetctlError, NetctlResult};
+use crate::netns::NetNamespace;
+use crate::routing::{Route, RouteProtocol, RouteType, RoutingController, RoutingRule, RT_TABLE_MAIN};
+use netlink_packet_route::route::RouteScope as NlRouteScope;
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::RwLock;
 use tracing::{info, debug, warn};
 use zbus::{Connection, fdo, interface};
 use zbus::object_server::SignalEmitter;
 use zbus::zvariant::Value;
 
 /// CR Routing D-Bus interface
+///
+/// All methods operate directly on the kernel routing tables. Route change
+/// signals are emitted by the service from the network monitor, so they
+/// cover changes made through this interface and by anything else.
 #[derive(Clone)]
 pub struct CRRouting {
-    /// Routing table (destination -> route info)
-    routes: Arc<RwLock<HashMap<String, CRRouteInfo>>>,
-    /// Default gateway (IPv4)
-    default_gateway: Arc<RwLock<Option<String>>>,
-    /// Default gateway (IPv6)
-    default_gateway6: Arc<RwLock<Option<String>>>,
+    /// Kernel routing table access
+    controller: Arc<RoutingController>,
 }
 
 impl CRRouting {
     /// Create a new CR Routing interface
     pub fn new() -> Self {
+        Self::with_namespace(NetNamespace::Current)
+    }
+
+    /// Create a CR Routing interface that manages a network namespace
+    pub fn with_namespace(namespace: NetNamespace) -> Self {
         Self {
-            routes: Arc::new(RwLock::new(HashMap::new())),
-            default_gateway: Arc::new(RwLock::new(None)),
-            default_gateway6: Arc::new(RwLock::new(None)),
+            controller: Arc::new(RoutingController::with_namespace(namespace)),
         }
     }
 
-    /// Add a route internally
-    pub async fn add_route_internal(&self, route: CRRouteInfo) {
-        let mut routes = self.routes.write().await;
-        let key = route.destination.clone();
-        routes.insert(key, route);
+    /// Routes in the main table whose destination matches `destination`
+    async fn find_routes(&self, destination: &str) -> NetctlResult<Vec<Route>> {
+        let (addr, prefix_len) = Route::parse_destination(destination, false)?;
+        let wants_default = destination == "default";
+        Ok(self.controller.list_routes(Some(RT_TABLE_MAIN)).await?
+            .into_iter()
+            .filter(|r| {
+                if wants_default {
+                    r.is_default()
+                } else {
+                    r.destination == addr && r.prefix_len == prefix_len
+                }
+            })
+            .collect())
     }
+}
 
-    /// Remove a route internally
-    pub async fn remove_route_internal(&self, destination: &str) -> bool {
-        let mut routes = self.routes.write().await;
-        routes.remove(destination).is_some()
+/// Map a library error onto a D-Bus error
+fn dbus_error(err: NetctlError) -> fdo::Error {
+    match err {
+        NetctlError::InvalidParameter(msg) => fdo::Error::InvalidArgs(msg),
+        NetctlError::PermissionDenied(msg) => fdo::Error::AccessDenied(msg),
+        other => fdo::Error::Failed(other.to_string()),
     }
+}
 
-    /// Set default gateway
-    pub async fn set_default_gateway_internal(&self, gateway: Option<String>, ipv6: bool) {
-        if ipv6 {
-            let mut gw = self.default_gateway6.write().await;
-            *gw = gateway;
-        } else {
-            let mut gw = self.default_gateway.write().await;
-            *gw = gateway;
-        }
+/// D-Bus representation of a route
+pub(crate) fn route_to_dict(route: &Route) -> HashMap<String, Value<'static>> {
+    let mut route_info = HashMap::new();
+    route_info.insert("Destination".to_string(), Value::new(route.destination_prefix()));
+
+    if let Some(gw) = route.gateway {
+        route_info.insert("Gateway".to_string(), Value::new(gw.to_string()));
+    }
+
+    if let Some(ref iface) = route.interface {
+        route_info.insert("Interface".to_string(), Value::new(iface.clone()));
+    }
+
+    if let Some(src) = route.source {
+        route_info.insert("Source".to_string(), Value::new(src.to_string()));
+    }
+
+    route_info.insert("Metric".to_string(), Value::new(route.metric.unwrap_or(0)));
+
+    let route_type_u32: u32 = cr_route_type(route.kind).into();
+    route_info.insert("Type".to_string(), Value::new(route_type_u32));
+
+    route_info.insert("Table".to_string(), Value::new(route.table));
+    let scope: u8 = NlRouteScope::from(route.scope).into();
+    route_info.insert("Scope".to_string(), Value::new(scope as u32));
+    route_info.insert("Protocol".to_string(), Value::new(route.protocol.to_string()));
+    route_info.insert("OnLink".to_string(), Value::new(route.onlink));
+
+    route_info
+}
+
+/// D-Bus representation of a policy rule
+pub(crate) fn rule_to_dict(rule: &RoutingRule) -> HashMap<String, Value<'static>> {
+    let mut rule_info = HashMap::new();
+    rule_info.insert("IPv6".to_string(), Value::new(rule.ipv6));
+    rule_info.insert("Action".to_string(), Value::new(rule.action.to_string()));
+    rule_info.insert("Invert".to_string(), Value::new(rule.invert));
+
+    if let Some(priority) = rule.priority {
+        rule_info.insert("Priority".to_string(), Value::new(priority));
+    }
+    if let Some(from) = rule.from {
+        rule_info.insert("From".to_string(), Value::new(format!("{}/{}", from, rule.from_len)));
+    }
+    if let Some(to) = rule.to {
+        rule_info.insert("To".to_string(), Value::new(format!("{}/{}", to, rule.to_len)));
+    }
+    if let Some(mark) = rule.fwmark {
+        rule_info.insert("FwMark".to_string(), Value::new(mark));
+    }
+    if let Some(mask) = rule.fwmask {
+        rule_info.insert("FwMask".to_string(), Value::new(mask));
+    }
+    if let Some(ref iif) = rule.iif {
+        rule_info.insert("Iif".to_string(), Value::new(iif.clone()));
+    }
+    if let Some(ref oif) = rule.oif {
+        rule_info.insert("Oif".to_string(), Value::new(oif.clone()));
+    }
+    if let Some((start, end)) = rule.uid_range {
+        rule_info.insert("UidStart".to_string(), Value::new(start));
+        rule_info.insert("UidEnd".to_string(), Value::new(end));
+    }
+    if let Some(table) = rule.table {
+        rule_info.insert("Table".to_string(), Value::new(table));
+    }
+
+    rule_info
+}
+
+/// Build a policy rule from its D-Bus representation (see [`rule_to_dict`])
+pub(crate) fn rule_from_dict(settings: &HashMap<String, Value<'_>>) -> fdo::Result<RoutingRule> {
+    fn get<'a, T>(settings: &'a HashMap<String, Value<'_>>, key: &str) -> fdo::Result<Option<T>>
+    where
+        T: TryFrom<&'a Value<'a>>,
+        <T as TryFrom<&'a Value<'a>>>::Error: std::fmt::Display,
+    {
+        settings.get(key)
+            .map(|v| T::try_from(v).map_err(|e| fdo::Error::InvalidArgs(format!("Invalid {}: {}", key, e))))
+            .transpose()
+    }
+
+    let mut rule = RoutingRule {
+        ipv6: get::<bool>(settings, "IPv6")?.unwrap_or(false),
+        priority: get::<u32>(settings, "Priority")?,
+        fwmark: get::<u32>(settings, "FwMark")?,
+        fwmask: get::<u32>(settings, "FwMask")?,
+        iif: get::<&str>(settings, "Iif")?.map(str::to_string),
+        oif: get::<&str>(settings, "Oif")?.map(str::to_string),
+        table: get::<u32>(settings, "Table")?,
+        invert: get::<bool>(settings, "Invert")?.unwrap_or(false),
+        ..Default::default()
+    };
+
+    if let Some(action) = get::<&str>(settings, "Action")? {
+        rule.action = action.parse().map_err(dbus_error)?;
+    }
+    if let Some(from) = get::<&str>(settings, "From")? {
+        let (addr, len) = Route::parse_destination(from, rule.ipv6).map_err(dbus_error)?;
+        rule = rule.from_prefix(addr, len);
+    }
+    if let Some(to) = get::<&str>(settings, "To")? {
+        let (addr, len) = Route::parse_destination(to, rule.ipv6).map_err(dbus_error)?;
+        rule = rule.to_prefix(addr, len);
+    }
+    match (get::<u32>(settings, "UidStart")?, get::<u32>(settings, "UidEnd")?) {
+        (Some(start), end) => rule.uid_range = Some((start, end.unwrap_or(start))),
+        (None, Some(_)) => return Err(fdo::Error::InvalidArgs("UidEnd requires UidStart".to_string())),
+        (None, None) => {}
+    }
+
+    Ok(rule)
+}
+
+fn cr_route_type(kind: RouteType) -> CRRouteType {
+    match kind {
+        RouteType::Unicast => CRRouteType::Unicast,
+        RouteType::Local => CRRouteType::Local,
+        RouteType::Broadcast => CRRouteType::Broadcast,
+        RouteType::Anycast => CRRouteType::Anycast,
+        RouteType::Multicast => CRRouteType::Multicast,
+        RouteType::Blackhole => CRRouteType::Blackhole,
+        RouteType::Unreachable => CRRouteType::Unreachable,
+        RouteType::Prohibit => CRRouteType::Prohibit,
+        RouteType::Throw | RouteType::Other(_) => CRRouteType::Unknown,
     }
 }
 
@@ -78,107 +212,73 @@ impl CRRouting {
             return Err(fdo::Error::InvalidArgs("Destination cannot be empty".to_string()));
         }
 
-        let mut route = CRRouteInfo::new(destination.to_string());
+        let (addr, prefix_len) = Route::parse_destination(destination, gateway.contains(':'))
+            .map_err(dbus_error)?;
+        let mut route = Route::new(addr, prefix_len);
 
         if !gateway.is_empty() {
-            route.gateway = Some(gateway.to_string());
+            route.gateway = Some(gateway.parse()
+                .map_err(|_| fdo::Error::InvalidArgs(format!("Invalid gateway: {}", gateway)))?);
         }
 
         if !interface.is_empty() {
             route.interface = Some(interface.to_string());
         }
 
-        route.metric = metric;
-
-        self.add_route_internal(route).await;
-
-        // Actual route addition will be handled by integration layer
+        if metric > 0 {
+            route.metric = Some(metric);
+        }
+        route.scope = route.implied_scope();
 
-        Ok(())
+        self.controller.add_route(&route).await.map_err(dbus_error)
     }
 
     /// Remove a route
+    ///
+    /// Removes every route to `destination` in the main table.
     async fn remove_route(&self, destination: &str) -> fdo::Result<()> {
         info!("CR Routing: Removing route {}", destination);
 
-        if !self.remove_route_internal(destination).await {
+        let routes = self.find_routes(destination).await.map_err(dbus_error)?;
+        if routes.is_empty() {
             return Err(fdo::Error::Failed(format!("Route not found: {}", destination)));
         }
 
-        // Actual route removal will be handled by integration layer
+        for route in &routes {
+            self.controller.delete_route(route).await.map_err(dbus_error)?;
+        }
 
         Ok(())
     }
 
-    /// Get all routes
-    async fn get_routes(&self) -> Vec<HashMap<String, Value<'static>>> {
-        let routes = self.routes.read().await;
-        let mut result = Vec::new();
-
-        for (_dest, route) in routes.iter() {
-            let mut route_info = HashMap::new();
-            route_info.insert("Destination".to_string(), Value::new(route.destination.clone()));
-
-            if let Some(ref gw) = route.gateway {
-                route_info.insert("Gateway".to_string(), Value::new(gw.clone()));
-            }
-
-            if let Some(ref iface) = route.interface {
-                route_info.insert("Interface".to_string(), Value::new(iface.clone()));
-            }
-
-            route_info.insert("Metric".to_string(), Value::new(route.metric));
-
-            let route_type_u32: u32 = route.route_type.into();
-            route_info.insert("Type".to_string(), Value::new(route_type_u32));
-
-            route_info.insert("Table".to_string(), Value::new(route.table));
-            route_info.insert("Scope".to_string(), Value::new(route.scope));
-
-            result.push(route_info);
-        }
+    /// Get all routes from all routing tables
+    async fn get_routes(&self) -> fdo::Result<Vec<HashMap<String, Value<'static>>>> {
+        let routes = self.controller.list_routes(None).await.map_err(dbus_error)?;
+        let result: Vec<_> = routes.iter().map(route_to_dict).collect();
 
         debug!("CR Routing: Returning {} routes", result.len());
-        result
+        Ok(result)
     }
 
     /// Get route count
-    async fn get_route_count(&self) -> u32 {
-        let routes = self.routes.read().await;
-        routes.len() as u32
+    async fn get_route_count(&self) -> fdo::Result<u32> {
+        let routes = self.controller.list_routes(None).await.map_err(dbus_error)?;
+        Ok(routes.len() as u32)
     }
 
-    /// Get a specific route
+    /// Get a specific route from the main table
     async fn get_route(&self, destination: &str) -> fdo::Result<HashMap<String, Value<'static>>> {
-        let routes = self.routes.read().await;
-
-        if let Some(route) = routes.get(destination) {
-            let mut route_info = HashMap::new();
-            route_info.insert("Destination".to_string(), Value::new(route.destination.clone()));
-
-            if let Some(ref gw) = route.gateway {
-                route_info.insert("Gateway".to_string(), Value::new(gw.clone()));
-            }
-
-            if let Some(ref iface) = route.interface {
-                route_info.insert("Interface".to_string(), Value::new(iface.clone()));
-            }
-
-            route_info.insert("Metric".to_string(), Value::new(route.metric));
-
-            let route_type_u32: u32 = route.route_type.into();
-            route_info.insert("Type".to_string(), Value::new(route_type_u32));
+        let routes = self.find_routes(destination).await.map_err(dbus_error)?;
 
-            route_info.insert("Table".to_string(), Value::new(route.table));
-            route_info.insert("Scope".to_string(), Value::new(route.scope));
-
-            Ok(route_info)
-        } else {
-            Err(fdo::Error::Failed(format!("Route not found: {}", destination)))
-        }
+        routes.iter()
+            .min_by_key(|r| r.metric.unwrap_or(0))
+            .map(route_to_dict)
+            .ok_or_else(|| fdo::Error::Failed(format!("Route not found: {}", destination)))
     }
 
     /// Set default gateway
+    ///
+    /// Replaces the default route of the gateway's address family.
     async fn set_default_gateway(&self, gateway: &str, interface: &str) -> fdo::Result<()> {
         info!("CR Routing: Setting default gateway to {} dev {}", gateway, interface);
 
@@ -186,81 +286,101 @@ impl CRRouting {
             return Err(fdo::Error::InvalidArgs("Gateway cannot be empty".to_string()));
         }
 
-        // Determine if IPv6 based on presence of colons
-        let is_ipv6 = gateway.contains(':');
-
-        // Create default route
-        let mut route = CRRouteInfo::new("default".to_string());
-        route.gateway = Some(gateway.to_string());
+        let gw = gateway.parse()
+            .map_err(|_| fdo::Error::InvalidArgs(format!("Invalid gateway: {}", gateway)))?;
+        let mut route = Route::default_via(gw);
 
         if !interface.is_empty() {
             route.interface = Some(interface.to_string());
         }
 
-        route.metric = 0; // Default gateway has lowest metric
-
-        // Store in routes
-        self.add_route_internal(route).await;
-
-        // Also store in default gateway field
-        self.set_default_gateway_internal(Some(gateway.to_string()), is_ipv6).await;
-
-        // Actual default gateway setting will be handled by integration layer
+        // Drop other default routes of this family so the new one is the only one
+        let existing = self.controller.default_routes(route.is_ipv6()).await.map_err(dbus_error)?;
+        for old in existing.iter().filter(|r| r.gateway != route.gateway || r.metric.is_some()) {
+            self.controller.delete_route(old).await.map_err(dbus_error)?;
+        }
 
-        Ok(())
+        self.controller.replace_route(&route).await.map_err(dbus_error)
     }
 
     /// Get default gateway
-    async fn get_default_gateway(&self) -> HashMap<String, Value<'static>> {
+    async fn get_default_gateway(&self) -> fdo::Result<HashMap<String, Value<'static>>> {
         let mut result = HashMap::new();
 
-        if let Some(ref gw) = *self.default_gateway.read().await {
-            result.insert("Gateway".to_string(), Value::new(gw.clone()));
+        let v4 = self.controller.default_routes(false).await.map_err(dbus_error)?;
+        if let Some(gw) = v4.first().and_then(|r| r.gateway) {
+            result.insert("Gateway".to_string(), Value::new(gw.to_string()));
             result.insert("IPv6".to_string(), Value::new(false));
+            if let Some(ref iface) = v4[0].interface {
+                result.insert("Interface".to_string(), Value::new(iface.clone()));
+            }
         }
 
-        if let Some(ref gw6) = *self.default_gateway6.read().await {
-            result.insert("Gateway6".to_string(), Value::new(gw6.clone()));
+        let v6 = self.controller.default_routes(true).await.map_err(dbus_error)?;
+        if let Some(gw6) = v6.first().and_then(|r| r.gateway) {
+            result.insert("Gateway6".to_string(), Value::new(gw6.to_string()));
             result.insert("IPv6".to_string(), Value::new(true));
+            if let Some(ref iface) = v6[0].interface {
+                result.insert("Interface6".to_string(), Value::new(iface.clone()));
+            }
         }
 
         debug!("CR Routing: Returning default gateway info");
-        result
+        Ok(result)
     }
 
     /// Clear default gateway
     async fn clear_default_gateway(&self, ipv6: bool) -> fdo::Result<()> {
         info!("CR Routing: Clearing default gateway (IPv6: {})", ipv6);
 
-        // Clear from default gateway field
-        self.set_default_gateway_internal(None, ipv6).await;
-
-        // Remove default route from routing table
-        self.remove_route_internal("default").await;
-
-        // Actual default gateway clearing will be handled by integration layer
+        let routes = self.controller.default_routes(ipv6).await.map_err(dbus_error)?;
+        for route in &routes {
+            self.controller.delete_route(route).await.map_err(dbus_error)?;
+        }
 
         Ok(())
     }
 
     /// Clear all routes (dangerous operation, use with caution)
+    ///
+    /// Removes every route in the main table that was not installed by the
+    /// kernel itself; connected routes of configured addresses stay.
     async fn clear_all_routes(&self) -> fdo::Result<()> {
         warn!("CR Routing: Clearing ALL routes - this may break connectivity!");
 
-        let mut routes = self.routes.write().await;
-        routes.clear();
+        let routes = self.controller.list_routes(Some(RT_TABLE_MAIN)).await.map_err(dbus_error)?;
+        for route in routes.iter().filter(|r| r.protocol != RouteProtocol::Kernel) {
+            if let Err(e) = self.controller.delete_route(route).await {
+                warn!("CR Routing: Failed to delete route {}: {}", route, e);
+            }
+        }
 
-        // Clear default gateways
-        let mut gw = self.default_gateway.write().await;
-        *gw = None;
-        drop(gw);
+        Ok(())
+    }
 
-        let mut gw6 = self.default_gateway6.write().await;
-        *gw6 = None;
+    /// Add a routing policy rule
+    ///
+    /// Keys: Priority, From, To, FwMark, FwMask, Iif, Oif, UidStart, UidEnd,
+    /// Table, Action (lookup, blackhole, unreachable, prohibit, nop), IPv6
+    /// and Invert. Lookup rules need a Table.
+    async fn add_rule(&self, rule: HashMap<String, Value<'_>>) -> fdo::Result<()> {
+        let rule = rule_from_dict(&rule)?;
+        info!("CR Routing: Adding rule {}", rule);
+        self.controller.add_rule(&rule).await.map_err(dbus_error)
+    }
 
-        // Actual route clearing will be handled by integration layer
+    /// Remove the first routing policy rule matching the given keys
+    async fn remove_rule(&self, rule: HashMap<String, Value<'_>>) -> fdo::Result<()> {
+        let rule = rule_from_dict(&rule)?;
+        info!("CR Routing: Removing rule {}", rule);
+        self.controller.delete_rule(&rule).await.map_err(dbus_error)
+    }
 
-        Ok(())
+    /// Get all routing policy rules (IPv4 and IPv6)
+    async fn get_rules(&self) -> fdo::Result<Vec<HashMap<String, Value<'static>>>> {
+        let rules = self.controller.list_rules().await.map_err(dbus_error)?;
+        debug!("CR Routing: Returning {} rules", rules.len());
+        Ok(rules.iter().map(rule_to_dict).collect())
     }
 
     // ============ D-Bus Signals ============
@@ -2,8 +2,10 @@
 //!
 //! Common types and enums used across the CR D-Bus interface
 
+use crate::dhcp_client::DhcpLease;
 use serde::{Deserialize, Serialize};
-use zbus::zvariant::Type;
+use std::collections::HashMap;
+use zbus::zvariant::{Type, Value};
 
 /// CR D-Bus service name
 pub const CR_DBUS_SERVICE: &str = "org.crrouter.NetworkControl";
@@ -215,6 +217,9 @@ pub struct CRDeviceInfo {
     pub hw_address: Option<String>,
     /// MTU
     pub mtu: u32,
+    /// DHCPv4 lease the IPv4 configuration came from
+    #[serde(default)]
+    pub dhcp4: Option<DhcpLease>,
 }
 
 impl CRDeviceInfo {
@@ -230,8 +235,43 @@ impl CRDeviceInfo {
             ipv6_address: None,
             hw_address: None,
             mtu: 1500,
+            dhcp4: None,
         }
     }
+
+    /// IPv4 details of the DHCP lease as D-Bus properties
+    ///
+    /// Empty unless the device has a lease.
+    pub fn ip4_lease_properties(&self) -> HashMap<String, Value<'static>> {
+        let mut props = HashMap::new();
+        let Some(lease) = &self.dhcp4 else {
+            return props;
+        };
+
+        if let Some(len) = lease.prefix_len() {
+            props.insert("IPv4Prefix".to_string(), Value::new(u32::from(len)));
+        }
+        if let Some(ref mask) = lease.subnet_mask {
+            props.insert("IPv4Netmask".to_string(), Value::new(mask.clone()));
+        }
+        if let Some(ref gateway) = lease.gateway {
+            props.insert("IPv4Gateway".to_string(), Value::new(gateway.clone()));
+        }
+        props.insert("IPv4Dns".to_string(), Value::new(lease.dns_servers.clone()));
+        if let Some(lease_time) = lease.lease_time {
+            props.insert("IPv4LeaseTime".to_string(), Value::new(lease_time));
+        }
+        if let Some(acquired) = lease.acquired_at {
+            props.insert("IPv4LeaseAcquired".to_string(), Value::new(acquired));
+        }
+        if let Some(expires) = lease.expires_at() {
+            props.insert("IPv4LeaseExpires".to_string(), Value::new(expires));
+        }
+        if let Some(ref server) = lease.server_address {
+            props.insert("IPv4DhcpServer".to_string(), Value::new(server.clone()));
+        }
+        props
+    }
 }
 
 /// WiFi access point information
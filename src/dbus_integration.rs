@@ -142,6 +142,15 @@ async fn handle_network_event(
                 warn!("Failed to emit PropertiesChanged signal: {}", e);
             }
         }
+
+        NetworkEvent::RouteAdded { .. } | NetworkEvent::RouteRemoved { .. } => {
+            // NetworkManager exposes routes through IP4Config objects only
+        }
+
+        NetworkEvent::AddressConflict { name, address, mac, .. } => {
+            // NetworkManager has no signal for conflicts on an active connection
+            warn!("Address {} on {} is claimed by {}", address, name, mac);
+        }
     }
 
     Ok(())
@@ -6,6 +6,7 @@
 
 use crate::error::{NetctlError, NetctlResult};
 use crate::interface::{InterfaceController, InterfaceInfo};
+use crate::netns::NetNamespace;
 use crate::wifi::WifiController;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -171,6 +172,29 @@ impl DeviceController {
         }
     }
 
+    /// Create a device controller bound to a network namespace
+    ///
+    /// Driver and bus metadata come from sysfs, which only describes the
+    /// namespace it was mounted in, so those fields stay empty for devices in
+    /// other namespaces.
+    pub fn with_namespace(namespace: NetNamespace) -> Self {
+        Self {
+            interface_ctrl: InterfaceController::with_namespace(namespace.clone()),
+            wifi_ctrl: WifiController::with_namespace(namespace),
+            device_cache: tokio::sync::RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Network namespace this controller operates in
+    pub fn namespace(&self) -> &NetNamespace {
+        self.interface_ctrl.namespace()
+    }
+
+    /// Move a device into another network namespace
+    pub async fn move_to_namespace(&self, name: &str, target: &NetNamespace) -> NetctlResult<()> {
+        self.interface_ctrl.move_to_namespace(name, target).await
+    }
+
     /// List all devices
     pub async fn list(&self) -> NetctlResult<Vec<String>> {
         self.interface_ctrl.list().await
@@ -1,20 +1,93 @@
 //! DHCP server management via dora
+//!
+//! Renders the dora configuration and runs dora as a child process. dora
+//! stays in the foreground, so the controller writes its pid file itself.
+//! A controller that started dora also supervises it and restarts it when it
+//! exits unexpectedly; one that only finds it running through the pid file
+//! can still stop it. Leases are read from dora's SQLite lease database with
+//! the sqlite3 shell.
 
-use crate::error::NetctlResult;
+use crate::config::NetctlConfig;
+use crate::dhcpv4::packet::{encode_classless_routes, encode_domain_list, prefix_to_mask, ClasslessRoute};
+use crate::error::{NetctlError, NetctlResult};
 use crate::validation;
 use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr};
 use std::path::PathBuf;
+use std::process::Stdio;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::fs;
+use tokio::process::{Child, Command};
+use tokio::sync::{oneshot, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout, Duration, Instant};
+use tracing::{debug, error, info, warn};
+
+/// Default location of dora's lease database
+pub const DEFAULT_LEASE_DB: &str = "/var/lib/dora/leases.db";
+
+/// Time dora gets to fail on a bad configuration before it counts as started
+const STARTUP_GRACE: Duration = Duration::from_millis(500);
+/// Time dora gets to exit after SIGTERM before it is killed
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+/// Wait before the first restart; doubled after each quick failure
+const RESTART_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(30);
+/// A run this long resets the backoff and the failure count
+const STABLE_RUN: Duration = Duration::from_secs(60);
+/// Quick failures in a row before the supervisor gives up
+const MAX_QUICK_FAILURES: u32 = 5;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DhcpConfig {
+    /// Interface the server listens on
     pub interface: String,
     pub range_start: String,
     pub range_end: String,
     pub gateway: String,
     pub dns_servers: Vec<String>,
+    /// Lease time in seconds
     pub lease_time: u32,
     pub domain: Option<String>,
+    /// Served subnet in CIDR notation; the /24 around the gateway if unset
+    #[serde(default)]
+    pub subnet: Option<String>,
+    /// Further interfaces the subnet is served on
+    #[serde(default)]
+    pub extra_interfaces: Vec<String>,
+    /// Address ranges besides `range_start`-`range_end`
+    #[serde(default)]
+    pub extra_ranges: Vec<DhcpRange>,
+    /// Fixed addresses by MAC
+    #[serde(default)]
+    pub reservations: Vec<DhcpReservation>,
+    /// NTP servers (option 42)
+    #[serde(default)]
+    pub ntp_servers: Vec<String>,
+    /// Domain search list (option 119)
+    #[serde(default)]
+    pub domain_search: Vec<String>,
+    /// Classless static routes (option 121)
+    #[serde(default)]
+    pub routes: Vec<DhcpStaticRoute>,
+    /// Interface MTU (option 26)
+    #[serde(default)]
+    pub mtu: Option<u16>,
+    /// Further options, sent as given
+    #[serde(default)]
+    pub options: Vec<DhcpOption>,
+    /// Ping an address before offering it
+    #[serde(default)]
+    pub ping_check: bool,
+    /// Time to wait for a ping reply
+    #[serde(default)]
+    pub ping_timeout_ms: Option<u32>,
+    /// ARP-probe an address before offering it
+    #[serde(default)]
+    pub probe_check: bool,
 }
 
 impl Default for DhcpConfig {
@@ -27,81 +100,1078 @@ impl Default for DhcpConfig {
             dns_servers: vec!["10.255.24.1".to_string()],
             lease_time: 3600,
             domain: Some("local".to_string()),
+            subnet: None,
+            extra_interfaces: Vec::new(),
+            extra_ranges: Vec::new(),
+            reservations: Vec::new(),
+            ntp_servers: Vec::new(),
+            domain_search: Vec::new(),
+            routes: Vec::new(),
+            mtu: None,
+            options: Vec::new(),
+            ping_check: false,
+            ping_timeout_ms: None,
+            probe_check: false,
         }
     }
 }
 
-pub struct DhcpController {
+/// Options the configuration renders itself; custom options may not use them
+const GENERATED_OPTIONS: [u8; 10] = [1, 3, 6, 12, 15, 26, 42, 51, 119, 121];
+
+/// A pool of addresses handed out to clients
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DhcpRange {
+    pub start: String,
+    pub end: String,
+}
+
+impl FromStr for DhcpRange {
+    type Err = NetctlError;
+
+    /// Parse `START-END`
+    fn from_str(s: &str) -> NetctlResult<Self> {
+        let (start, end) = s.split_once('-')
+            .ok_or_else(|| NetctlError::InvalidParameter(format!("Invalid range {}, expected START-END", s)))?;
+        Ok(Self { start: start.trim().to_string(), end: end.trim().to_string() })
+    }
+}
+
+/// A fixed address for one client
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DhcpReservation {
+    pub mac: String,
+    pub ip: String,
+    /// Host name sent to the client (option 12)
+    #[serde(default)]
+    pub hostname: Option<String>,
+}
+
+impl FromStr for DhcpReservation {
+    type Err = NetctlError;
+
+    /// Parse `MAC,IP[,HOSTNAME]`
+    fn from_str(s: &str) -> NetctlResult<Self> {
+        let mut parts = s.split(',').map(str::trim);
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(mac), Some(ip), hostname, None) => Ok(Self {
+                mac: mac.to_string(),
+                ip: ip.to_string(),
+                hostname: hostname.filter(|h| !h.is_empty()).map(str::to_string),
+            }),
+            _ => Err(NetctlError::InvalidParameter(format!(
+                "Invalid reservation {}, expected MAC,IP[,HOSTNAME]", s
+            ))),
+        }
+    }
+}
+
+/// A classless static route pushed to clients
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DhcpStaticRoute {
+    /// Destination in CIDR notation
+    pub destination: String,
+    /// Router in the served subnet, or 0.0.0.0 for an on-link route
+    pub gateway: String,
+}
+
+impl FromStr for DhcpStaticRoute {
+    type Err = NetctlError;
+
+    /// Parse `DESTINATION/LEN,GATEWAY`
+    fn from_str(s: &str) -> NetctlResult<Self> {
+        let (destination, gateway) = s.split_once(',')
+            .ok_or_else(|| NetctlError::InvalidParameter(format!("Invalid route {}, expected DEST/LEN,GATEWAY", s)))?;
+        Ok(Self { destination: destination.trim().to_string(), gateway: gateway.trim().to_string() })
+    }
+}
+
+/// Value of a custom DHCP option, rendered with dora's type tags
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum DhcpOptionValue {
+    Ip(String),
+    Ips(Vec<String>),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    Str(String),
+    /// Raw option payload in hex
+    Hex(String),
+}
+
+/// A custom DHCP option
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DhcpOption {
+    pub code: u8,
+    pub value: DhcpOptionValue,
+}
+
+impl FromStr for DhcpOption {
+    type Err = NetctlError;
+
+    /// Parse `CODE:TYPE:VALUE`, with TYPE one of ip, ips (comma separated),
+    /// u8, u16, u32, str and hex
+    fn from_str(s: &str) -> NetctlResult<Self> {
+        let invalid = || NetctlError::InvalidParameter(format!("Invalid option {}, expected CODE:TYPE:VALUE", s));
+        let mut parts = s.splitn(3, ':');
+        let (Some(code), Some(kind), Some(value)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(invalid());
+        };
+        let code = code.trim().parse().map_err(|_| invalid())?;
+        let value = match kind.trim() {
+            "ip" => DhcpOptionValue::Ip(value.trim().to_string()),
+            "ips" => DhcpOptionValue::Ips(value.split(',').map(|ip| ip.trim().to_string()).collect()),
+            "u8" => DhcpOptionValue::U8(value.trim().parse().map_err(|_| invalid())?),
+            "u16" => DhcpOptionValue::U16(value.trim().parse().map_err(|_| invalid())?),
+            "u32" => DhcpOptionValue::U32(value.trim().parse().map_err(|_| invalid())?),
+            "str" => DhcpOptionValue::Str(value.to_string()),
+            "hex" => DhcpOptionValue::Hex(value.trim().to_string()),
+            _ => return Err(invalid()),
+        };
+        Ok(Self { code, value })
+    }
+}
+
+/// An IPv4 subnet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Subnet {
+    network: Ipv4Addr,
+    prefix_len: u8,
+}
+
+impl Subnet {
+    /// Parse `ADDRESS/LEN`, dropping host bits
+    fn parse(cidr: &str) -> NetctlResult<Self> {
+        let invalid = || NetctlError::InvalidParameter(format!("Invalid subnet: {}", cidr));
+        let (addr, len) = cidr.split_once('/').ok_or_else(invalid)?;
+        let addr: Ipv4Addr = addr.trim().parse().map_err(|_| invalid())?;
+        let prefix_len: u8 = len.trim().parse().map_err(|_| invalid())?;
+        if prefix_len > 32 {
+            return Err(invalid());
+        }
+        let mask = u32::from(prefix_to_mask(prefix_len));
+        Ok(Self { network: Ipv4Addr::from(u32::from(addr) & mask), prefix_len })
+    }
+
+    fn mask(&self) -> Ipv4Addr {
+        prefix_to_mask(self.prefix_len)
+    }
+
+    fn broadcast(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.network) | !u32::from(self.mask()))
+    }
+
+    fn contains(&self, ip: Ipv4Addr) -> bool {
+        u32::from(ip) & u32::from(self.mask()) == u32::from(self.network)
+    }
+
+    /// Whether `ip` can be given to a host: inside the subnet and neither
+    /// the network nor the broadcast address
+    fn is_host(&self, ip: Ipv4Addr) -> bool {
+        self.contains(ip) && ip != self.network && ip != self.broadcast()
+    }
+}
+
+impl std::fmt::Display for Subnet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
+fn parse_ipv4(value: &str, what: &str) -> NetctlResult<Ipv4Addr> {
+    match validation::validate_ip_address(value)? {
+        IpAddr::V4(ip) => Ok(ip),
+        IpAddr::V6(_) => Err(NetctlError::InvalidParameter(format!("{} must be an IPv4 address: {}", what, value))),
+    }
+}
+
+pub(crate) fn parse_hex(value: &str) -> NetctlResult<Vec<u8>> {
+    let digits: String = value.chars().filter(|c| !matches!(c, ':' | ' ')).collect();
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        return Err(NetctlError::InvalidParameter(format!("Invalid hex value: {}", value)));
+    }
+    (0..digits.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16)
+            .map_err(|_| NetctlError::InvalidParameter(format!("Invalid hex value: {}", value))))
+        .collect()
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Double-quoted YAML string
+fn yaml_str(value: &str) -> NetctlResult<String> {
+    let value = validation::sanitize_config_value(value)?;
+    Ok(format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")))
+}
+
+fn yaml_ips(ips: &[Ipv4Addr]) -> String {
+    ips.iter().map(Ipv4Addr::to_string).collect::<Vec<_>>().join(", ")
+}
+
+/// A DHCP configuration checked against its subnet
+struct ValidatedConfig {
+    subnet: Subnet,
+    gateway: Ipv4Addr,
+    interfaces: Vec<String>,
+    ranges: Vec<(Ipv4Addr, Ipv4Addr)>,
+    reservations: Vec<(String, Ipv4Addr, Option<String>)>,
+    dns_servers: Vec<Ipv4Addr>,
+    ntp_servers: Vec<Ipv4Addr>,
+    routes: Vec<ClasslessRoute>,
+}
+
+impl DhcpConfig {
+    /// The served subnet, in CIDR notation
+    pub fn effective_subnet(&self) -> NetctlResult<String> {
+        self.subnet().map(|subnet| subnet.to_string())
+    }
+
+    fn subnet(&self) -> NetctlResult<Subnet> {
+        match &self.subnet {
+            Some(cidr) => Subnet::parse(cidr),
+            None => Subnet::parse(&format!("{}/24", parse_ipv4(&self.gateway, "Gateway")?)),
+        }
+    }
+
+    /// Check the configuration for consistency
+    ///
+    /// The ranges, gateway, reservations and route gateways must lie in the
+    /// subnet, ranges must not overlap and reservations must be unique.
+    pub fn validate(&self) -> NetctlResult<()> {
+        self.check().map(|_| ())
+    }
+
+    fn check(&self) -> NetctlResult<ValidatedConfig> {
+        let invalid = |msg: String| NetctlError::InvalidParameter(msg);
+
+        let mut interfaces = Vec::new();
+        for interface in std::iter::once(&self.interface).chain(&self.extra_interfaces) {
+            validation::validate_interface_name(interface)?;
+            if !interfaces.contains(interface) {
+                interfaces.push(interface.clone());
+            }
+        }
+
+        let subnet = self.subnet()?;
+        if subnet.prefix_len == 0 || subnet.prefix_len > 30 {
+            return Err(invalid(format!("Subnet {} has no room for clients", subnet)));
+        }
+
+        let gateway = parse_ipv4(&self.gateway, "Gateway")?;
+        if !subnet.is_host(gateway) {
+            return Err(invalid(format!("Gateway {} is not a host address in {}", gateway, subnet)));
+        }
+
+        let mut ranges: Vec<(Ipv4Addr, Ipv4Addr)> = Vec::new();
+        let primary = DhcpRange { start: self.range_start.clone(), end: self.range_end.clone() };
+        for range in std::iter::once(&primary).chain(&self.extra_ranges) {
+            let start = parse_ipv4(&range.start, "Range start")?;
+            let end = parse_ipv4(&range.end, "Range end")?;
+            if start > end {
+                return Err(invalid(format!("Range {}-{} ends before it starts", start, end)));
+            }
+            if !subnet.is_host(start) || !subnet.is_host(end) {
+                return Err(invalid(format!("Range {}-{} is not inside {}", start, end, subnet)));
+            }
+            if let Some((s, e)) = ranges.iter().find(|(s, e)| start <= *e && *s <= end) {
+                return Err(invalid(format!("Range {}-{} overlaps {}-{}", start, end, s, e)));
+            }
+            ranges.push((start, end));
+        }
+
+        let mut reservations: Vec<(String, Ipv4Addr, Option<String>)> = Vec::new();
+        for reservation in &self.reservations {
+            validation::validate_mac_address(&reservation.mac)?;
+            let mac = reservation.mac.to_ascii_lowercase();
+            let ip = parse_ipv4(&reservation.ip, "Reservation")?;
+            if !subnet.is_host(ip) {
+                return Err(invalid(format!("Reservation {} for {} is not a host address in {}", ip, mac, subnet)));
+            }
+            if ip == gateway {
+                return Err(invalid(format!("Reservation for {} uses the gateway address {}", mac, ip)));
+            }
+            if reservations.iter().any(|(m, _, _)| *m == mac) {
+                return Err(invalid(format!("Duplicate reservation for {}", mac)));
+            }
+            if reservations.iter().any(|(_, i, _)| *i == ip) {
+                return Err(invalid(format!("Address {} is reserved twice", ip)));
+            }
+            if let Some(hostname) = &reservation.hostname {
+                validation::validate_hostname(hostname)?;
+            }
+            reservations.push((mac, ip, reservation.hostname.clone()));
+        }
+
+        let dns_servers = self.dns_servers.iter()
+            .map(|dns| parse_ipv4(dns, "DNS server"))
+            .collect::<NetctlResult<Vec<_>>>()?;
+        let ntp_servers = self.ntp_servers.iter()
+            .map(|ntp| parse_ipv4(ntp, "NTP server"))
+            .collect::<NetctlResult<Vec<_>>>()?;
+
+        if let Some(domain) = &self.domain {
+            validation::validate_hostname(domain)?;
+        }
+        for domain in &self.domain_search {
+            validation::validate_hostname(domain)?;
+        }
+
+        let mut routes = Vec::new();
+        for route in &self.routes {
+            let destination = Subnet::parse(&route.destination)?;
+            let route_gateway = parse_ipv4(&route.gateway, "Route gateway")?;
+            if !route_gateway.is_unspecified() && !subnet.is_host(route_gateway) {
+                return Err(invalid(format!(
+                    "Gateway {} of route {} is not in {}", route_gateway, destination, subnet
+                )));
+            }
+            routes.push(ClasslessRoute {
+                destination: destination.network,
+                prefix_len: destination.prefix_len,
+                gateway: route_gateway,
+            });
+        }
+
+        if let Some(mtu) = self.mtu {
+            validation::validate_mtu(u32::from(mtu))?;
+        }
+        if self.lease_time == 0 {
+            return Err(invalid("Lease time must be positive".to_string()));
+        }
+
+        let mut codes = Vec::new();
+        for option in &self.options {
+            if option.code == 0 || option.code == 255 {
+                return Err(invalid(format!("Option {} cannot be set", option.code)));
+            }
+            if GENERATED_OPTIONS.contains(&option.code) {
+                return Err(invalid(format!(
+                    "Option {} is set from the configuration and cannot be given as a custom option", option.code
+                )));
+            }
+            if codes.contains(&option.code) {
+                return Err(invalid(format!("Option {} is given twice", option.code)));
+            }
+            codes.push(option.code);
+            match &option.value {
+                DhcpOptionValue::Ip(ip) => { parse_ipv4(ip, "Option value")?; }
+                DhcpOptionValue::Ips(ips) => {
+                    if ips.is_empty() {
+                        return Err(invalid(format!("Option {} has no addresses", option.code)));
+                    }
+                    for ip in ips {
+                        parse_ipv4(ip, "Option value")?;
+                    }
+                }
+                DhcpOptionValue::Str(value) => { validation::sanitize_config_value(value)?; }
+                DhcpOptionValue::Hex(value) => { parse_hex(value)?; }
+                DhcpOptionValue::U8(_) | DhcpOptionValue::U16(_) | DhcpOptionValue::U32(_) => {}
+            }
+        }
+
+        Ok(ValidatedConfig {
+            subnet,
+            gateway,
+            interfaces,
+            ranges,
+            reservations,
+            dns_servers,
+            ntp_servers,
+            routes,
+        })
+    }
+}
+
+/// A lease handed out by the DHCP server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DhcpServerLease {
+    pub ip_address: Ipv4Addr,
+    /// Hardware address, if the client identifier is one
+    pub mac_address: Option<String>,
+    /// Client identifier in hex
+    pub client_id: String,
+    /// Time the lease runs out, in seconds since the epoch
+    pub expires_at: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DhcpServerStatus {
+    pub running: bool,
+    pub pid: Option<i32>,
+    /// Started by this controller, which restarts it when it exits
+    pub supervised: bool,
+    /// Times dora was restarted after exiting unexpectedly
+    pub restarts: u32,
+    /// Active leases, if the lease database could be read
+    pub active_leases: Option<usize>,
+}
+
+/// How dora is run
+#[derive(Debug, Clone)]
+struct DoraCommand {
+    bin: PathBuf,
     config_path: PathBuf,
-    #[allow(dead_code)]
-    dora_bin: PathBuf,
+    lease_db: PathBuf,
+    pid_file: PathBuf,
+    log_file: PathBuf,
+}
+
+impl DoraCommand {
+    /// Spawn dora in its own process group and record its pid
+    async fn spawn(&self) -> NetctlResult<Child> {
+        if let Some(dir) = self.lease_db.parent() {
+            fs::create_dir_all(dir).await?;
+        }
+        let log = std::fs::OpenOptions::new().create(true).append(true).open(&self.log_file)?;
+
+        let child = Command::new(&self.bin)
+            .arg("-c").arg(&self.config_path)
+            .arg("-d").arg(&self.lease_db)
+            .stdin(Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log)
+            // Keep running when the process that started it is interrupted
+            .process_group(0)
+            .spawn()
+            .map_err(|e| NetctlError::ServiceError(format!("Failed to start dora: {}", e)))?;
+
+        let pid = child.id()
+            .ok_or_else(|| NetctlError::ServiceError("dora exited immediately".to_string()))?;
+        fs::write(&self.pid_file, format!("{}\n", pid)).await?;
+        debug!("dora running with pid {}", pid);
+        Ok(child)
+    }
+
+    /// End of the log, for error messages
+    async fn log_tail(&self) -> String {
+        let log = fs::read_to_string(&self.log_file).await.unwrap_or_default();
+        let lines: Vec<&str> = log.lines().rev().take(5).collect();
+        lines.into_iter().rev().collect::<Vec<_>>().join("\n")
+    }
+}
+
+/// Task keeping a started dora running
+struct Supervisor {
+    stop: oneshot::Sender<()>,
+    restarts: Arc<AtomicU32>,
+    task: JoinHandle<()>,
+}
+
+pub struct DhcpController {
+    dora: DoraCommand,
+    sqlite_bin: PathBuf,
+    supervisor: Mutex<Option<Supervisor>>,
 }
 
 impl DhcpController {
+    /// Create a controller writing the dora configuration to `config_path`
+    ///
+    /// The pid file and dora's log are kept next to it.
     pub fn new(config_path: PathBuf) -> Self {
         Self {
-            config_path,
-            dora_bin: PathBuf::from("/usr/local/bin/dora"),
+            dora: DoraCommand {
+                bin: PathBuf::from("/usr/local/bin/dora"),
+                lease_db: PathBuf::from(DEFAULT_LEASE_DB),
+                pid_file: config_path.with_extension("pid"),
+                log_file: config_path.with_extension("log"),
+                config_path,
+            },
+            sqlite_bin: PathBuf::from("sqlite3"),
+            supervisor: Mutex::new(None),
         }
     }
 
-    pub fn generate_config(&self, config: &DhcpConfig) -> NetctlResult<String> {
-        // Validate all user-provided configuration values
-        validation::validate_interface_name(&config.interface)?;
-        validation::validate_ip_address(&config.range_start)?;
-        validation::validate_ip_address(&config.range_end)?;
-        validation::validate_ip_address(&config.gateway)?;
+    /// Use the dora binary at `bin`
+    pub fn with_dora_bin(mut self, bin: impl Into<PathBuf>) -> Self {
+        self.dora.bin = bin.into();
+        self
+    }
 
-        // Validate all DNS server IPs
-        for dns in &config.dns_servers {
-            validation::validate_ip_address(dns)?;
-        }
+    /// Keep dora's lease database at `path`
+    pub fn with_lease_db(mut self, path: impl Into<PathBuf>) -> Self {
+        self.dora.lease_db = path.into();
+        self
+    }
+
+    /// Path of the generated dora configuration
+    pub fn config_path(&self) -> &PathBuf {
+        &self.dora.config_path
+    }
+
+    /// Path of dora's lease database
+    pub fn lease_db(&self) -> &PathBuf {
+        &self.dora.lease_db
+    }
 
-        // Use sanitized values for config generation
-        let interface = validation::sanitize_config_value(&config.interface)?;
-        let range_start = validation::sanitize_config_value(&config.range_start)?;
-        let range_end = validation::sanitize_config_value(&config.range_end)?;
-        let gateway = validation::sanitize_config_value(&config.gateway)?;
+    /// Render the dora configuration
+    ///
+    /// The configuration is validated first, see [`DhcpConfig::validate`].
+    pub fn generate_config(&self, config: &DhcpConfig) -> NetctlResult<String> {
+        let checked = config.check()?;
 
         let mut yaml = String::new();
-        yaml.push_str(&format!("{}:\n", interface));
-        yaml.push_str(&format!("  interfaces:\n    - {}\n", interface));
+        yaml.push_str(&format!("{}:\n", checked.interfaces[0]));
+        yaml.push_str("  interfaces:\n");
+        for interface in &checked.interfaces {
+            yaml.push_str(&format!("    - {}\n", interface));
+        }
         yaml.push_str("  ranges:\n");
-        yaml.push_str(&format!("    - start: {}\n", range_start));
-        yaml.push_str(&format!("      end: {}\n", range_end));
-        yaml.push_str("  options:\n");
-        yaml.push_str("    - opt: 1\n      val: !ip 255.255.255.0\n");
-        yaml.push_str(&format!("    - opt: 3\n      val: !ip {}\n", gateway));
+        for (start, end) in &checked.ranges {
+            yaml.push_str(&format!("    - start: {}\n", start));
+            yaml.push_str(&format!("      end: {}\n", end));
+        }
 
-        if !config.dns_servers.is_empty() {
-            // Sanitize DNS servers
-            let dns_sanitized: Result<Vec<_>, _> = config.dns_servers.iter()
-                .map(|dns| validation::sanitize_config_value(dns))
-                .collect();
-            let dns_servers = dns_sanitized?;
-            yaml.push_str(&format!("    - opt: 6\n      val: !ips [{}]\n",
-                dns_servers.join(", ")));
+        if !checked.reservations.is_empty() {
+            yaml.push_str("  reservations:\n");
+            for (mac, ip, hostname) in &checked.reservations {
+                yaml.push_str(&format!("    - mac: {}\n", mac));
+                yaml.push_str(&format!("      ip: {}\n", ip));
+                if let Some(hostname) = hostname {
+                    yaml.push_str("      options:\n");
+                    yaml.push_str(&format!("        - opt: 12\n          val: !str {}\n", yaml_str(hostname)?));
+                }
+            }
         }
 
+        yaml.push_str("  options:\n");
+        yaml.push_str(&format!("    - opt: 1\n      val: !ip {}\n", checked.subnet.mask()));
+        yaml.push_str(&format!("    - opt: 3\n      val: !ip {}\n", checked.gateway));
+        if !checked.dns_servers.is_empty() {
+            yaml.push_str(&format!("    - opt: 6\n      val: !ips [{}]\n", yaml_ips(&checked.dns_servers)));
+        }
+        if let Some(domain) = &config.domain {
+            yaml.push_str(&format!("    - opt: 15\n      val: !str {}\n", yaml_str(domain)?));
+        }
+        if let Some(mtu) = config.mtu {
+            yaml.push_str(&format!("    - opt: 26\n      val: !u16 {}\n", mtu));
+        }
+        if !checked.ntp_servers.is_empty() {
+            yaml.push_str(&format!("    - opt: 42\n      val: !ips [{}]\n", yaml_ips(&checked.ntp_servers)));
+        }
         yaml.push_str(&format!("    - opt: 51\n      val: !u32 {}\n", config.lease_time));
-        yaml.push_str("  ping_check: false\n  probe_check: false\n");
+        if !config.domain_search.is_empty() {
+            yaml.push_str(&format!("    - opt: 119\n      val: !hex {}\n",
+                to_hex(&encode_domain_list(&config.domain_search))));
+        }
+        if !checked.routes.is_empty() {
+            yaml.push_str(&format!("    - opt: 121\n      val: !hex {}\n",
+                to_hex(&encode_classless_routes(&checked.routes))));
+        }
+
+        let mut options: Vec<&DhcpOption> = config.options.iter().collect();
+        options.sort_by_key(|option| option.code);
+        for option in options {
+            let value = match &option.value {
+                DhcpOptionValue::Ip(ip) => format!("!ip {}", parse_ipv4(ip, "Option value")?),
+                DhcpOptionValue::Ips(ips) => {
+                    let ips = ips.iter()
+                        .map(|ip| parse_ipv4(ip, "Option value"))
+                        .collect::<NetctlResult<Vec<_>>>()?;
+                    format!("!ips [{}]", yaml_ips(&ips))
+                }
+                DhcpOptionValue::U8(n) => format!("!u8 {}", n),
+                DhcpOptionValue::U16(n) => format!("!u16 {}", n),
+                DhcpOptionValue::U32(n) => format!("!u32 {}", n),
+                DhcpOptionValue::Str(s) => format!("!str {}", yaml_str(s)?),
+                DhcpOptionValue::Hex(hex) => format!("!hex {}", to_hex(&parse_hex(hex)?)),
+            };
+            yaml.push_str(&format!("    - opt: {}\n      val: {}\n", option.code, value));
+        }
+
+        yaml.push_str(&format!("  ping_check: {}\n", config.ping_check));
+        if config.ping_check {
+            if let Some(ms) = config.ping_timeout_ms {
+                yaml.push_str(&format!("  ping_timeout_ms: {}\n", ms));
+            }
+        }
+        yaml.push_str(&format!("  probe_check: {}\n", config.probe_check));
 
         Ok(yaml)
     }
 
     pub async fn write_config(&self, config: &DhcpConfig) -> NetctlResult<()> {
         let yaml = self.generate_config(config)?;
+        let config_path = &self.dora.config_path;
 
         // Validate the config path to prevent path traversal
         // Use the parent directory of config_path as the allowed base
-        if let Some(base_dir) = self.config_path.parent() {
-            let validated_path = validation::validate_config_path(&self.config_path, base_dir)?;
+        if let Some(base_dir) = config_path.parent() {
+            fs::create_dir_all(base_dir).await?;
+            let validated_path = validation::validate_config_path(config_path, base_dir)?;
             fs::write(&validated_path, yaml).await?;
         } else {
             // If no parent directory, write directly (shouldn't normally happen)
-            fs::write(&self.config_path, yaml).await?;
+            fs::write(config_path, yaml).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the configuration and start dora
+    pub async fn start(&self, config: &DhcpConfig) -> NetctlResult<()> {
+        if self.is_running().await? {
+            return Err(NetctlError::AlreadyExists("dora already running".to_string()));
+        }
+
+        self.write_config(config).await?;
+        info!("Starting dora on {} with {}", config.interface, self.dora.config_path.display());
+        self.launch().await
+    }
+
+    /// Stop dora, whether this controller started it or not
+    pub async fn stop(&self) -> NetctlResult<()> {
+        if let Some(supervisor) = self.supervisor.lock().await.take() {
+            if !supervisor.task.is_finished() {
+                info!("Stopping dora");
+                let _ = supervisor.stop.send(());
+                let _ = supervisor.task.await;
+                let _ = fs::remove_file(&self.dora.pid_file).await;
+                return Ok(());
+            }
+        }
+
+        let Some(pid) = self.running_pid().await? else {
+            return Ok(());
+        };
+
+        info!("Stopping dora (pid {})", pid);
+        Command::new("kill").arg("-TERM").arg(pid.to_string()).output().await?;
+
+        for _ in 0..10 {
+            sleep(Duration::from_millis(500)).await;
+            if !self.is_running().await? {
+                let _ = fs::remove_file(&self.dora.pid_file).await;
+                return Ok(());
+            }
+        }
+
+        Err(NetctlError::Timeout("dora did not stop".to_string()))
+    }
+
+    /// Stop dora and start it again with the configuration on disk
+    pub async fn restart(&self) -> NetctlResult<()> {
+        if !fs::try_exists(&self.dora.config_path).await.unwrap_or(false) {
+            return Err(NetctlError::NotFound(format!(
+                "No DHCP server configuration at {}", self.dora.config_path.display()
+            )));
+        }
+
+        self.stop().await?;
+        info!("Restarting dora");
+        self.launch().await
+    }
+
+    pub async fn status(&self) -> NetctlResult<DhcpServerStatus> {
+        let pid = self.running_pid().await?;
+        let (supervised, restarts) = match &*self.supervisor.lock().await {
+            Some(supervisor) if !supervisor.task.is_finished() => {
+                (true, supervisor.restarts.load(Ordering::Relaxed))
+            }
+            Some(supervisor) => (false, supervisor.restarts.load(Ordering::Relaxed)),
+            None => (false, 0),
+        };
+
+        let active_leases = match self.leases().await {
+            Ok(leases) => Some(leases.len()),
+            Err(e) => {
+                debug!("Failed to read DHCP leases: {}", e);
+                None
+            }
+        };
+
+        Ok(DhcpServerStatus {
+            running: pid.is_some(),
+            pid,
+            supervised,
+            restarts,
+            active_leases,
+        })
+    }
+
+    pub async fn is_running(&self) -> NetctlResult<bool> {
+        Ok(self.running_pid().await?.is_some())
+    }
+
+    /// Leases that are currently held by clients
+    ///
+    /// Returns no leases if dora has not created its database yet.
+    pub async fn leases(&self) -> NetctlResult<Vec<DhcpServerLease>> {
+        if !fs::try_exists(&self.dora.lease_db).await.unwrap_or(false) {
+            return Ok(Vec::new());
+        }
+
+        let output = Command::new(&self.sqlite_bin)
+            .arg("-readonly")
+            .arg("-batch")
+            // dora may be writing
+            .arg("-cmd").arg(".timeout 2000")
+            .arg(&self.dora.lease_db)
+            .arg("SELECT ip, hex(client_id), expires_at FROM leases WHERE leased = 1 AND probation = 0 ORDER BY ip;")
+            .output()
+            .await
+            .map_err(|e| NetctlError::ServiceError(format!("Failed to run sqlite3: {}", e)))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(NetctlError::ServiceError(format!(
+                "Failed to read lease database: {}", validation::sanitize_error_message(&stderr)
+            )));
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        Ok(parse_leases(&String::from_utf8_lossy(&output.stdout))
+            .into_iter()
+            .filter(|lease| lease.expires_at > now)
+            .collect())
+    }
+
+    /// Start dora with the configuration on disk and supervise it
+    async fn launch(&self) -> NetctlResult<()> {
+        let mut child = self.dora.spawn().await?;
+
+        // dora exits right away on a bad configuration
+        sleep(STARTUP_GRACE).await;
+        if let Some(status) = child.try_wait()? {
+            let _ = fs::remove_file(&self.dora.pid_file).await;
+            return Err(NetctlError::ServiceError(format!(
+                "dora exited with {}: {}",
+                status,
+                validation::sanitize_error_message(&self.dora.log_tail().await)
+            )));
         }
 
+        let (stop, stop_rx) = oneshot::channel();
+        let restarts = Arc::new(AtomicU32::new(0));
+        let task = tokio::spawn(supervise(self.dora.clone(), child, restarts.clone(), stop_rx));
+        *self.supervisor.lock().await = Some(Supervisor { stop, restarts, task });
         Ok(())
     }
+
+    /// PID of the running dora, verified through /proc
+    async fn running_pid(&self) -> NetctlResult<Option<i32>> {
+        let Ok(pid_str) = fs::read_to_string(&self.dora.pid_file).await else {
+            return Ok(None);
+        };
+        let Ok(pid) = pid_str.trim().parse::<i32>() else {
+            return Ok(None);
+        };
+
+        // Make sure a stale PID file does not point at an unrelated process
+        match fs::read_to_string(format!("/proc/{}/cmdline", pid)).await {
+            Ok(cmdline) if cmdline.contains("dora") => Ok(Some(pid)),
+            _ => Ok(None),
+        }
+    }
+}
+
+impl Default for DhcpController {
+    /// Keep the configuration in the netctl state directory
+    fn default() -> Self {
+        Self::new(NetctlConfig::default().paths.state_dir.join("dora.yaml"))
+    }
+}
+
+/// Restart dora whenever it exits until told to stop
+///
+/// Quick failures back off exponentially; after too many in a row the
+/// server is left stopped.
+async fn supervise(dora: DoraCommand, mut child: Child, restarts: Arc<AtomicU32>, mut stop: oneshot::Receiver<()>) {
+    let mut backoff = RESTART_BACKOFF;
+    let mut quick_failures = 0;
+    loop {
+        let started = Instant::now();
+        let status = tokio::select! {
+            status = child.wait() => status,
+            _ = &mut stop => {
+                terminate(&mut child).await;
+                return;
+            }
+        };
+
+        match status {
+            Ok(status) => warn!("dora exited unexpectedly with {}", status),
+            Err(e) => warn!("Failed to wait for dora: {}", e),
+        }
+        if started.elapsed() >= STABLE_RUN {
+            backoff = RESTART_BACKOFF;
+            quick_failures = 0;
+        }
+        quick_failures += 1;
+        if quick_failures > MAX_QUICK_FAILURES {
+            error!("dora keeps exiting, giving up: {}", dora.log_tail().await);
+            let _ = fs::remove_file(&dora.pid_file).await;
+            return;
+        }
+
+        tokio::select! {
+            _ = sleep(backoff) => {}
+            _ = &mut stop => return,
+        }
+        backoff = (backoff * 2).min(MAX_RESTART_BACKOFF);
+
+        match dora.spawn().await {
+            Ok(next) => {
+                child = next;
+                let count = restarts.fetch_add(1, Ordering::Relaxed) + 1;
+                info!("Restarted dora ({} restarts)", count);
+            }
+            Err(e) => {
+                error!("Failed to restart dora: {}", e);
+                let _ = fs::remove_file(&dora.pid_file).await;
+                return;
+            }
+        }
+    }
+}
+
+/// SIGTERM, then SIGKILL if dora does not exit in time
+async fn terminate(child: &mut Child) {
+    if let Some(pid) = child.id() {
+        let _ = Command::new("kill").arg("-TERM").arg(pid.to_string()).output().await;
+        if timeout(STOP_TIMEOUT, child.wait()).await.is_ok() {
+            return;
+        }
+        warn!("dora did not exit after SIGTERM, killing it");
+    }
+    let _ = child.kill().await;
+}
+
+/// Rows of `ip|hex(client_id)|expires_at`
+fn parse_leases(output: &str) -> Vec<DhcpServerLease> {
+    output.lines().filter_map(|line| {
+        let mut fields = line.trim().split('|');
+        let ip: u32 = fields.next()?.parse().ok()?;
+        let client_id = fields.next()?.to_ascii_lowercase();
+        let expires_at = fields.next()?.parse().ok()?;
+        Some(DhcpServerLease {
+            ip_address: Ipv4Addr::from(ip),
+            mac_address: client_mac(&client_id),
+            client_id,
+            expires_at,
+        })
+    }).collect()
+}
+
+/// Hardware address in a client identifier: the bare address, or hardware
+/// type 1 (Ethernet) followed by the address
+fn client_mac(client_id: &str) -> Option<String> {
+    let bytes: Vec<u8> = (0..client_id.len()).step_by(2)
+        .map(|i| u8::from_str_radix(client_id.get(i..i + 2)?, 16).ok())
+        .collect::<Option<_>>()?;
+    let mac: [u8; 6] = match bytes.as_slice() {
+        [1, mac @ ..] if mac.len() == 6 => mac.try_into().ok()?,
+        mac if mac.len() == 6 => mac.try_into().ok()?,
+        _ => return None,
+    };
+    Some(crate::arp::format_mac(&mac))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_default_config() {
+        let yaml = DhcpController::new("/tmp/dora.yaml".into()).generate_config(&DhcpConfig::default()).unwrap();
+        assert!(yaml.starts_with("wlan0:\n  interfaces:\n    - wlan0\n"));
+        assert!(yaml.contains("    - start: 10.255.24.10\n      end: 10.255.24.250\n"));
+        assert!(yaml.contains("    - opt: 1\n      val: !ip 255.255.255.0\n"));
+        assert!(yaml.contains("    - opt: 15\n      val: !str \"local\"\n"));
+        assert!(!yaml.contains("reservations:"));
+        assert!(yaml.ends_with("  ping_check: false\n  probe_check: false\n"));
+    }
+
+    #[test]
+    fn test_generate_rich_config() {
+        let config = DhcpConfig {
+            interface: "br-lan".to_string(),
+            range_start: "172.16.0.100".to_string(),
+            range_end: "172.16.0.199".to_string(),
+            gateway: "172.16.0.1".to_string(),
+            dns_servers: vec!["172.16.0.1".to_string()],
+            domain: None,
+            subnet: Some("172.16.0.0/22".to_string()),
+            extra_interfaces: vec!["wlan0".to_string()],
+            extra_ranges: vec!["172.16.2.10-172.16.3.254".parse().unwrap()],
+            reservations: vec!["AA:BB:CC:DD:EE:FF,172.16.1.5,printer".parse().unwrap()],
+            ntp_servers: vec!["172.16.0.1".to_string(), "172.16.0.2".to_string()],
+            domain_search: vec!["lan".to_string()],
+            routes: vec!["10.0.0.0/8,172.16.0.254".parse().unwrap()],
+            mtu: Some(1492),
+            options: vec!["66:str:tftp.lan".parse().unwrap(), "28:ip:172.16.3.255".parse().unwrap()],
+            ping_check: true,
+            ping_timeout_ms: Some(500),
+            ..Default::default()
+        };
+        assert_eq!(config.effective_subnet().unwrap(), "172.16.0.0/22");
+
+        let yaml = DhcpController::new("/tmp/dora.yaml".into()).generate_config(&config).unwrap();
+        assert!(yaml.starts_with("br-lan:\n  interfaces:\n    - br-lan\n    - wlan0\n"));
+        assert!(yaml.contains("    - start: 172.16.2.10\n      end: 172.16.3.254\n"));
+        assert!(yaml.contains("  reservations:\n    - mac: aa:bb:cc:dd:ee:ff\n      ip: 172.16.1.5\n      options:\n        - opt: 12\n          val: !str \"printer\"\n"));
+        assert!(yaml.contains("    - opt: 1\n      val: !ip 255.255.252.0\n"));
+        assert!(yaml.contains("    - opt: 26\n      val: !u16 1492\n"));
+        assert!(yaml.contains("    - opt: 42\n      val: !ips [172.16.0.1, 172.16.0.2]\n"));
+        assert!(yaml.contains("    - opt: 119\n      val: !hex 036c616e00\n"));
+        assert!(yaml.contains("    - opt: 121\n      val: !hex 080aac1000fe\n"));
+        assert!(!yaml.contains("opt: 15\n"));
+        // Custom options come last, ordered by code
+        let tftp = yaml.find("opt: 66\n      val: !str \"tftp.lan\"").unwrap();
+        assert!(yaml.find("opt: 28\n      val: !ip 172.16.3.255").unwrap() < tftp);
+        assert!(yaml.find("opt: 121").unwrap() < tftp);
+        assert!(yaml.ends_with("  ping_check: true\n  ping_timeout_ms: 500\n  probe_check: false\n"));
+    }
+
+    #[test]
+    fn test_validate_config() {
+        let valid = DhcpConfig::default();
+        assert!(valid.validate().is_ok());
+
+        let invalid = [
+            DhcpConfig { subnet: Some("10.255.24.0/31".to_string()), ..valid.clone() },
+            DhcpConfig { gateway: "10.255.25.1".to_string(), dns_servers: Vec::new(), subnet: Some("10.255.24.0/24".to_string()), ..valid.clone() },
+            DhcpConfig { gateway: "10.255.24.0".to_string(), ..valid.clone() },
+            DhcpConfig { range_end: "10.255.24.255".to_string(), ..valid.clone() },
+            DhcpConfig { range_start: "10.255.24.251".to_string(), ..valid.clone() },
+            DhcpConfig { extra_ranges: vec!["10.255.24.200-10.255.24.220".parse().unwrap()], ..valid.clone() },
+            DhcpConfig { reservations: vec!["aa:bb:cc:dd:ee:ff,10.255.25.5".parse().unwrap()], ..valid.clone() },
+            DhcpConfig { reservations: vec!["aa:bb:cc:dd:ee:ff,10.255.24.1".parse().unwrap()], ..valid.clone() },
+            DhcpConfig {
+                reservations: vec![
+                    "aa:bb:cc:dd:ee:ff,10.255.24.5".parse().unwrap(),
+                    "AA:BB:CC:DD:EE:FF,10.255.24.6".parse().unwrap(),
+                ],
+                ..valid.clone()
+            },
+            DhcpConfig { reservations: vec!["aa:bb:cc:dd:ee:ff,10.255.24.5,bad host".parse().unwrap()], ..valid.clone() },
+            DhcpConfig { routes: vec!["10.0.0.0/8,192.168.1.1".parse().unwrap()], ..valid.clone() },
+            DhcpConfig { mtu: Some(40), ..valid.clone() },
+            DhcpConfig { options: vec!["51:u32:60".parse().unwrap()], ..valid.clone() },
+            DhcpConfig { options: vec!["66:hex:abc".parse().unwrap()], ..valid.clone() },
+            DhcpConfig { ntp_servers: vec!["fe80::1".to_string()], ..valid.clone() },
+        ];
+        for config in invalid {
+            assert!(config.validate().is_err(), "{:?} should be rejected", config);
+        }
+
+        // On-link routes need no gateway
+        let on_link = DhcpConfig { routes: vec!["10.0.0.0/8,0.0.0.0".parse().unwrap()], ..valid };
+        assert!(on_link.validate().is_ok());
+    }
+
+    #[test]
+    fn test_parse_config_items() {
+        assert!("10.0.0.1".parse::<DhcpRange>().is_err());
+        assert!("aa:bb:cc:dd:ee:ff".parse::<DhcpReservation>().is_err());
+        assert!("aa:bb:cc:dd:ee:ff,10.0.0.2,a,b".parse::<DhcpReservation>().is_err());
+        assert_eq!("aa:bb:cc:dd:ee:ff, 10.0.0.2".parse::<DhcpReservation>().unwrap().hostname, None);
+        assert_eq!("6:ips:10.0.0.1,10.0.0.2".parse::<DhcpOption>().unwrap().value,
+            DhcpOptionValue::Ips(vec!["10.0.0.1".to_string(), "10.0.0.2".to_string()]));
+        assert_eq!("60:str:a:b".parse::<DhcpOption>().unwrap().value, DhcpOptionValue::Str("a:b".to_string()));
+        assert!("300:u8:1".parse::<DhcpOption>().is_err());
+        assert!("66:float:1".parse::<DhcpOption>().is_err());
+    }
+
+    #[test]
+    fn test_parse_leases() {
+        let output = "167772170|01AABBCCDDEEFF|1700000000\n\
+                      167772171|112233445566|1700000100\n\
+                      167772172|FF00000001|1700000200\n\
+                      garbage\n";
+        let leases = parse_leases(output);
+        assert_eq!(leases.len(), 3);
+        assert_eq!(leases[0].ip_address, Ipv4Addr::new(10, 0, 0, 10));
+        assert_eq!(leases[0].mac_address.as_deref(), Some("aa:bb:cc:dd:ee:ff"));
+        assert_eq!(leases[0].expires_at, 1700000000);
+        assert_eq!(leases[1].mac_address.as_deref(), Some("11:22:33:44:55:66"));
+        assert_eq!(leases[2].mac_address, None);
+        assert_eq!(leases[2].client_id, "ff00000001");
+    }
+
+    #[tokio::test]
+    async fn test_leases_from_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("leases.db");
+        let controller = DhcpController::new(dir.path().join("dora.yaml")).with_lease_db(&db);
+        assert!(controller.leases().await.unwrap().is_empty());
+
+        // dora's schema, with a held, an offered, an expired and a declined address
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let sql = format!(
+            "CREATE TABLE leases(ip INTEGER NOT NULL, client_id BLOB, leased BOOLEAN NOT NULL DEFAULT 0, \
+             expires_at INTEGER NOT NULL, network INTEGER NOT NULL, probation BOOLEAN NOT NULL DEFAULT 0, \
+             PRIMARY KEY(ip));\
+             INSERT INTO leases VALUES (167772170, x'01aabbccddeeff', 1, {0}, 167772160, 0);\
+             INSERT INTO leases VALUES (167772171, x'01aabbccddee00', 0, {0}, 167772160, 0);\
+             INSERT INTO leases VALUES (167772172, x'01aabbccddee01', 1, {1}, 167772160, 0);\
+             INSERT INTO leases VALUES (167772173, NULL, 1, {0}, 167772160, 1);",
+            now + 3600, now - 10
+        );
+        match std::process::Command::new("sqlite3").arg(&db).arg(sql).status() {
+            Ok(status) if status.success() => {}
+            _ => {
+                println!("SKIP: sqlite3 not available");
+                return;
+            }
+        }
+
+        let leases = controller.leases().await.unwrap();
+        assert_eq!(leases, vec![DhcpServerLease {
+            ip_address: Ipv4Addr::new(10, 0, 0, 10),
+            mac_address: Some("aa:bb:cc:dd:ee:ff".to_string()),
+            client_id: "01aabbccddeeff".to_string(),
+            expires_at: now + 3600,
+        }]);
+        assert_eq!(controller.status().await.unwrap().active_leases, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_supervised_lifecycle() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        // Stands in for dora: runs until terminated
+        let bin = dir.path().join("dora");
+        std::fs::write(&bin, "#!/bin/sh\nwhile :; do sleep 0.1; done\n").unwrap();
+        std::fs::set_permissions(&bin, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let controller = DhcpController::new(dir.path().join("dora.yaml"))
+            .with_dora_bin(&bin)
+            .with_lease_db(dir.path().join("leases.db"));
+        let config = DhcpConfig {
+            interface: "eth0".to_string(),
+            ..Default::default()
+        };
+        controller.start(&config).await.unwrap();
+        assert!(dir.path().join("dora.yaml").exists());
+        assert!(matches!(controller.start(&config).await, Err(NetctlError::AlreadyExists(_))));
+
+        let status = controller.status().await.unwrap();
+        assert!(status.running && status.supervised);
+        let pid = status.pid.unwrap();
+
+        // A crash is noticed and dora comes back with a new pid
+        Command::new("kill").arg("-KILL").arg(pid.to_string()).output().await.unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let status = loop {
+            let status = controller.status().await.unwrap();
+            if status.restarts == 1 && status.running {
+                break status;
+            }
+            assert!(Instant::now() < deadline, "dora was not restarted");
+            sleep(Duration::from_millis(100)).await;
+        };
+        assert_ne!(status.pid, Some(pid));
+
+        controller.restart().await.unwrap();
+        assert!(controller.is_running().await.unwrap());
+
+        controller.stop().await.unwrap();
+        assert!(!controller.is_running().await.unwrap());
+        assert!(!dir.path().join("dora.pid").exists());
+        controller.stop().await.unwrap();
+    }
 }
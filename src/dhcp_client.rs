@@ -1,14 +1,32 @@
-//! DHCP client management via crdhcpc
+//! DHCP client management
 //!
-//! This module provides integration with the crdhcpc DHCP client daemon.
-//! It handles starting, stopping, renewing, and monitoring DHCP leases on interfaces.
-
+//! Leases are acquired by the built-in DHCPv4 client ([`crate::dhcpv4`]),
+//! which configures the address, routes, MTU and DNS servers itself and
+//! publishes every lease change as a [`DhcpEvent`]. The crdhcpc daemon can
+//! be used instead by creating the controller with [`DhcpClientController::with_paths`].
+//!
+//! Current leases of either backend are kept as JSON files in the state
+//! directory (`<state_dir>/dhcp/<interface>.lease`), so that other processes
+//! can show them and a restarted client can ask for the same address.
+
+use crate::arp::ArpProbeConfig;
+use crate::config::NetctlConfig;
+use crate::dhcpv4::client::{ClientHandle, NativeClient};
+use crate::dhcpv4::packet::{mask_to_prefix, prefix_to_mask};
 use crate::error::{NetctlError, NetctlResult};
+use crate::interface::InterfaceController;
+use crate::netns::NetNamespace;
+use crate::resolver::ResolverManager;
 use crate::validation;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::process::Command;
+use tokio::sync::{broadcast, Mutex};
 use tracing::{debug, info, warn};
 
 /// Path to crdhcpc binary
@@ -16,6 +34,12 @@ const CRDHCPC_BIN: &str = "/usr/local/bin/crdhcpc";
 /// Default path to crdhcpc config
 const CRDHCPC_CONFIG: &str = "/etc/dhcp-client.toml";
 
+/// Route metric of DHCP routes unless configured otherwise
+pub const DEFAULT_ROUTE_METRIC: u32 = 100;
+
+/// Directory below the state directory holding the lease files
+const LEASE_DIR: &str = "dhcp";
+
 /// DHCP client state
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DhcpClientState {
@@ -33,8 +57,9 @@ pub enum DhcpClientState {
     Failed,
 }
 
+
 /// DHCP lease information
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct DhcpLease {
     /// Interface name
     pub interface: String,
@@ -52,54 +77,574 @@ pub struct DhcpLease {
     pub acquired_at: Option<u64>,
     /// DHCP server address
     pub server_address: Option<String>,
+    /// Domain name (option 15)
+    #[serde(default)]
+    pub domain_name: Option<String>,
+    /// Domain search list (option 119)
+    #[serde(default)]
+    pub domain_search: Vec<String>,
+    /// Interface MTU (option 26)
+    #[serde(default)]
+    pub mtu: Option<u32>,
+    /// Classless static routes (option 121) as `prefix/len [via gateway]`
+    #[serde(default)]
+    pub routes: Vec<String>,
+    /// Seconds until the client starts renewing (T1)
+    #[serde(default)]
+    pub renewal_time: Option<u32>,
+    /// Seconds until the client starts rebinding (T2)
+    #[serde(default)]
+    pub rebinding_time: Option<u32>,
+}
+
+impl DhcpLease {
+    /// Parse the lease printed by `crdhcpc status`
+    ///
+    /// Accepts a JSON object as well as `key: value` or `key=value` lines.
+    /// Keys are matched case-insensitively and under their common aliases
+    /// (`ip`/`address`, `router`/`gateway`, `server_id`, ...); unknown keys
+    /// are ignored, and an address given as `a.b.c.d/len` also sets the mask.
+    pub fn parse_status(interface: &str, text: &str) -> DhcpLease {
+        let mut lease = DhcpLease {
+            interface: interface.to_string(),
+            ..Default::default()
+        };
+
+        let text = text.trim();
+        if text.starts_with('{') {
+            if let Ok(serde_json::Value::Object(fields)) = serde_json::from_str(text) {
+                for (key, value) in fields {
+                    let value = match value {
+                        serde_json::Value::String(s) => s,
+                        serde_json::Value::Array(items) => items.iter()
+                            .map(|item| item.as_str().map(str::to_string).unwrap_or_else(|| item.to_string()))
+                            .collect::<Vec<_>>()
+                            .join(","),
+                        serde_json::Value::Null => continue,
+                        other => other.to_string(),
+                    };
+                    lease.set_status_field(&key, &value);
+                }
+                return lease;
+            }
+        }
+
+        for line in text.lines() {
+            let Some(split) = line.find([':', '=']) else { continue };
+            lease.set_status_field(&line[..split], &line[split + 1..]);
+        }
+        lease
+    }
+
+    fn set_status_field(&mut self, key: &str, value: &str) {
+        let key = key.trim().to_ascii_lowercase().replace([' ', '-', '.'], "_");
+        let value = value.trim().trim_matches('"');
+        if value.is_empty() {
+            return;
+        }
+        let list = || -> Vec<String> {
+            value.trim_matches(['[', ']'])
+                .split([',', ' '])
+                .map(|item| item.trim().trim_matches('"'))
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+
+        match key.as_str() {
+            "ip" | "ip_address" | "ipv4" | "ipv4_address" | "address" | "yiaddr" => {
+                let (address, prefix_len) = match value.split_once('/') {
+                    Some((address, len)) => (address, len.parse::<u8>().ok()),
+                    None => (value, None),
+                };
+                if let Ok(address) = address.parse::<Ipv4Addr>() {
+                    self.ip_address = Some(address.to_string());
+                    if let Some(len) = prefix_len.filter(|len| *len <= 32) {
+                        self.subnet_mask = Some(prefix_to_mask(len).to_string());
+                    }
+                }
+            }
+            "subnet_mask" | "netmask" | "mask" => {
+                if let Ok(mask) = value.parse::<Ipv4Addr>() {
+                    self.subnet_mask = Some(mask.to_string());
+                }
+            }
+            "prefix_len" | "prefix_length" | "prefix" => {
+                if let Some(len) = value.trim_start_matches('/').parse::<u8>().ok().filter(|len| *len <= 32) {
+                    self.subnet_mask = Some(prefix_to_mask(len).to_string());
+                }
+            }
+            "gateway" | "router" | "routers" => self.gateway = list().into_iter().next(),
+            "dns" | "dns_server" | "dns_servers" | "nameservers" => self.dns_servers = list(),
+            "server" | "server_id" | "server_address" | "server_identifier" | "dhcp_server" => {
+                self.server_address = Some(value.to_string());
+            }
+            "lease_time" | "lease" => self.lease_time = parse_seconds(value),
+            "renewal_time" | "t1" => self.renewal_time = parse_seconds(value),
+            "rebinding_time" | "t2" => self.rebinding_time = parse_seconds(value),
+            "acquired_at" | "acquired" | "obtained" | "bound_at" => self.acquired_at = parse_timestamp(value),
+            "domain_name" | "domain" => self.domain_name = Some(value.to_string()),
+            "domain_search" | "search" => self.domain_search = list(),
+            "mtu" => self.mtu = value.parse().ok(),
+            _ => {}
+        }
+    }
+
+    /// Prefix length of the subnet mask
+    pub fn prefix_len(&self) -> Option<u8> {
+        mask_to_prefix(self.subnet_mask.as_deref()?.parse().ok()?)
+    }
+
+    /// Time the lease runs out, in seconds since the epoch
+    pub fn expires_at(&self) -> Option<u64> {
+        Some(self.acquired_at? + u64::from(self.lease_time?))
+    }
+
+    /// Whether the lease has run out
+    ///
+    /// A lease without timing information never expires.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at().is_some_and(|expires| expires <= unix_now())
+    }
+}
+
+/// `3600`, `3600s` or `3600 seconds`
+fn parse_seconds(value: &str) -> Option<u32> {
+    value.trim_end_matches("seconds").trim_end_matches('s').trim().parse().ok()
+}
+
+/// Seconds since the epoch or an RFC 3339 time
+fn parse_timestamp(value: &str) -> Option<u64> {
+    value.parse().ok().or_else(|| {
+        chrono::DateTime::parse_from_rfc3339(value).ok()
+            .and_then(|time| u64::try_from(time.timestamp()).ok())
+    })
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Lease files in a directory, one per interface
+#[derive(Debug, Clone)]
+pub(crate) struct LeaseStore {
+    dir: PathBuf,
+}
+
+impl LeaseStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, interface: &str) -> PathBuf {
+        self.dir.join(format!("{}.lease", interface))
+    }
+
+    pub async fn load(&self, interface: &str) -> NetctlResult<Option<DhcpLease>> {
+        match tokio::fs::read_to_string(self.path(interface)).await {
+            Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write the lease, replacing the previous one atomically
+    pub async fn save(&self, lease: &DhcpLease) -> NetctlResult<()> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let path = self.path(&lease.interface);
+        let tmp = path.with_extension("lease.tmp");
+        tokio::fs::write(&tmp, serde_json::to_string_pretty(lease)?).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        Ok(())
+    }
+
+    pub async fn remove(&self, interface: &str) -> NetctlResult<()> {
+        match tokio::fs::remove_file(self.path(interface)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Lease changes published by the built-in client
+#[derive(Debug, Clone, PartialEq)]
+pub enum DhcpEvent {
+    /// A new lease was acquired and configured
+    Bound(DhcpLease),
+    /// The lease was extended, possibly with changed parameters
+    Renewed(DhcpLease),
+    /// An offered address was declined because another host uses it
+    Declined {
+        interface: String,
+        address: Ipv4Addr,
+        /// Hardware address of the host using it
+        mac: String,
+    },
+    /// The lease ran out or the server refused to extend it
+    Expired { interface: String },
+    /// The lease was given back to the server
+    Released { interface: String },
+    /// The client was stopped
+    Stopped { interface: String },
+}
+
+impl DhcpEvent {
+    /// Interface the event is about
+    pub fn interface(&self) -> &str {
+        match self {
+            DhcpEvent::Bound(lease) | DhcpEvent::Renewed(lease) => &lease.interface,
+            DhcpEvent::Declined { interface, .. }
+            | DhcpEvent::Expired { interface }
+            | DhcpEvent::Released { interface }
+            | DhcpEvent::Stopped { interface } => interface,
+        }
+    }
+}
+
+/// Retransmission timing of the built-in client (RFC 2131 section 4.1)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpTiming {
+    /// First retransmission timeout while acquiring a lease
+    pub initial_retransmit: Duration,
+    /// Upper bound of the doubling retransmission timeout
+    pub max_retransmit: Duration,
+    /// Minimum retransmission interval while renewing or rebinding
+    pub min_lease_retransmit: Duration,
+    /// Wait after declining an address before starting over
+    pub decline_backoff: Duration,
+}
+
+impl Default for DhcpTiming {
+    fn default() -> Self {
+        Self {
+            initial_retransmit: Duration::from_secs(4),
+            max_retransmit: Duration::from_secs(64),
+            min_lease_retransmit: Duration::from_secs(60),
+            decline_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Per-interface settings of the built-in client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpClientOptions {
+    /// Host name sent to the server (option 12)
+    pub hostname: Option<String>,
+    /// Client identifier (option 61); defaults to the hardware address
+    pub client_id: Option<Vec<u8>>,
+    /// Metric of the routes installed from the lease
+    pub route_metric: u32,
+    /// Apply the interface MTU offered by the server
+    pub apply_mtu: bool,
+    /// Probe offered addresses with ARP and decline those in use
+    pub arp_check: Option<ArpProbeConfig>,
+    /// Address to ask for first (INIT-REBOOT)
+    pub requested_address: Option<Ipv4Addr>,
+    pub timing: DhcpTiming,
+}
+
+impl Default for DhcpClientOptions {
+    fn default() -> Self {
+        Self {
+            hostname: None,
+            client_id: None,
+            route_metric: DEFAULT_ROUTE_METRIC,
+            apply_mtu: true,
+            arp_check: Some(ArpProbeConfig::default()),
+            requested_address: None,
+            timing: DhcpTiming::default(),
+        }
+    }
+}
+
+/// How leases are acquired
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DhcpClientBackend {
+    /// Built-in DHCPv4 client
+    Native,
+    /// External crdhcpc daemon
+    Crdhcpc {
+        /// Path to crdhcpc binary
+        bin: PathBuf,
+        /// Path to config file
+        config: PathBuf,
+    },
 }
 
 /// DHCP client controller
 pub struct DhcpClientController {
-    /// Path to crdhcpc binary
-    crdhcpc_bin: PathBuf,
-    /// Path to config file
-    config_path: PathBuf,
+    backend: DhcpClientBackend,
+    /// Network namespace the interfaces live in
+    namespace: NetNamespace,
+    /// Resolver the built-in client registers lease DNS servers with
+    resolver: Option<Arc<ResolverManager>>,
+    /// Options used by `start`
+    options: DhcpClientOptions,
+    /// Running built-in clients by interface
+    clients: Mutex<HashMap<String, ClientHandle>>,
+    /// Address of the last lease per interface, asked for again on restart
+    last_addresses: Mutex<HashMap<String, Ipv4Addr>>,
+    /// Where current leases are persisted
+    leases: LeaseStore,
+    events: broadcast::Sender<DhcpEvent>,
 }
 
 impl DhcpClientController {
-    /// Create a new DHCP client controller
+    /// Create a controller using the built-in client
     pub fn new() -> Self {
-        Self {
-            crdhcpc_bin: PathBuf::from(CRDHCPC_BIN),
-            config_path: PathBuf::from(CRDHCPC_CONFIG),
-        }
+        Self::with_backend(DhcpClientBackend::Native)
     }
 
-    /// Create a controller with custom paths
+    /// Create a controller using crdhcpc at custom paths
     pub fn with_paths(crdhcpc_bin: PathBuf, config_path: PathBuf) -> Self {
+        Self::with_backend(DhcpClientBackend::Crdhcpc {
+            bin: crdhcpc_bin,
+            config: config_path,
+        })
+    }
+
+    /// Create a controller using crdhcpc at its default location
+    pub fn crdhcpc() -> Self {
+        Self::with_paths(PathBuf::from(CRDHCPC_BIN), PathBuf::from(CRDHCPC_CONFIG))
+    }
+
+    fn with_backend(backend: DhcpClientBackend) -> Self {
+        let (events, _) = broadcast::channel(64);
         Self {
-            crdhcpc_bin,
-            config_path,
+            backend,
+            namespace: NetNamespace::Current,
+            resolver: None,
+            options: DhcpClientOptions::default(),
+            clients: Mutex::new(HashMap::new()),
+            last_addresses: Mutex::new(HashMap::new()),
+            leases: LeaseStore::new(NetctlConfig::default().paths.state_dir.join(LEASE_DIR)),
+            events,
         }
     }
 
-    /// Check if crdhcpc is installed
+    /// Run the built-in client on interfaces in `namespace`
+    pub fn with_namespace(namespace: NetNamespace) -> Self {
+        Self::new().in_namespace(namespace)
+    }
+
+    /// Builder form of [`with_namespace`](Self::with_namespace)
+    pub fn in_namespace(mut self, namespace: NetNamespace) -> Self {
+        self.namespace = namespace;
+        self
+    }
+
+    /// Register the DNS servers of leases with `resolver`
+    pub fn with_resolver(mut self, resolver: Arc<ResolverManager>) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    /// Options used by [`start`](Self::start)
+    pub fn with_options(mut self, options: DhcpClientOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Keep lease files in `dir` instead of the state directory
+    pub fn with_lease_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.leases = LeaseStore::new(dir.into());
+        self
+    }
+
+    /// Directory holding the lease files
+    pub fn lease_dir(&self) -> &Path {
+        self.leases.dir()
+    }
+
+    pub fn backend(&self) -> &DhcpClientBackend {
+        &self.backend
+    }
+
+    /// Receive lease events of the built-in client
+    pub fn subscribe(&self) -> broadcast::Receiver<DhcpEvent> {
+        self.events.subscribe()
+    }
+
+    /// Check if the DHCP client is available
     pub async fn is_installed(&self) -> bool {
-        tokio::fs::metadata(&self.crdhcpc_bin).await.is_ok()
+        match &self.backend {
+            DhcpClientBackend::Native => true,
+            DhcpClientBackend::Crdhcpc { bin, .. } => tokio::fs::metadata(bin).await.is_ok(),
+        }
     }
 
     /// Start DHCP client on an interface
     pub async fn start(&self, interface: &str) -> NetctlResult<()> {
+        self.start_with_options(interface, self.options.clone()).await
+    }
+
+    /// Start DHCP client on an interface with per-interface options
+    ///
+    /// Options only apply to the built-in client. Starting a client that is
+    /// already running does nothing.
+    pub async fn start_with_options(&self, interface: &str, mut options: DhcpClientOptions) -> NetctlResult<()> {
+        validation::validate_interface_name(interface)?;
+
+        let DhcpClientBackend::Native = &self.backend else {
+            return self.crdhcpc_start(interface).await;
+        };
+
+        let mut clients = self.clients.lock().await;
+        if clients.get(interface).is_some_and(|client| !client.is_finished()) {
+            debug!("DHCP client already running on {}", interface);
+            return Ok(());
+        }
+
+        let info = InterfaceController::with_namespace(self.namespace.clone()).get_info(interface).await?;
+        let ifindex = info.index
+            .ok_or_else(|| NetctlError::NotFound(format!("Interface {} has no index", interface)))?;
+        let mac = crate::arp::parse_mac(info.mac_address.as_deref(), interface)?;
+        if options.requested_address.is_none() {
+            options.requested_address = self.last_addresses.lock().await.get(interface).copied();
+        }
+        if options.requested_address.is_none() {
+            // Left behind by a previous run of the service
+            options.requested_address = self.saved_lease(interface).await.ok().flatten()
+                .filter(|lease| !lease.is_expired())
+                .and_then(|lease| lease.ip_address?.parse().ok());
+        }
+
+        info!("Starting DHCP client on interface {}", interface);
+        let client = NativeClient {
+            interface: interface.to_string(),
+            ifindex,
+            mac,
+            namespace: self.namespace.clone(),
+            options,
+            resolver: self.resolver.clone(),
+            leases: self.leases.clone(),
+            events: self.events.clone(),
+        };
+        clients.insert(interface.to_string(), client.spawn());
+        Ok(())
+    }
+
+    /// Stop DHCP client on an interface
+    ///
+    /// The built-in client removes the configuration of its lease but keeps
+    /// the lease itself, which it asks for again on the next start.
+    pub async fn stop(&self, interface: &str) -> NetctlResult<()> {
+        validation::validate_interface_name(interface)?;
+
+        let DhcpClientBackend::Native = &self.backend else {
+            return self.crdhcpc_stop(interface).await;
+        };
+
+        let Some(client) = self.clients.lock().await.remove(interface) else {
+            debug!("No DHCP client running on {}", interface);
+            return Ok(());
+        };
+        info!("Stopping DHCP client on interface {}", interface);
+        if let Some(address) = client.address() {
+            self.last_addresses.lock().await.insert(interface.to_string(), address);
+        }
+        client.stop().await;
+        Ok(())
+    }
+
+    /// Release DHCP lease on an interface
+    ///
+    /// The built-in client tells the server, removes the configuration and
+    /// exits.
+    pub async fn release(&self, interface: &str) -> NetctlResult<()> {
+        validation::validate_interface_name(interface)?;
+
+        let DhcpClientBackend::Native = &self.backend else {
+            return self.crdhcpc_release(interface).await;
+        };
+
+        self.last_addresses.lock().await.remove(interface);
+        if let Err(e) = self.leases.remove(interface).await {
+            warn!("Failed to remove DHCP lease file of {}: {}", interface, e);
+        }
+        let Some(client) = self.clients.lock().await.remove(interface) else {
+            return Ok(());
+        };
+        info!("Releasing DHCP lease on interface {}", interface);
+        client.release().await;
+        Ok(())
+    }
+
+    /// Renew DHCP lease on an interface
+    pub async fn renew(&self, interface: &str) -> NetctlResult<()> {
+        validation::validate_interface_name(interface)?;
+
+        let DhcpClientBackend::Native = &self.backend else {
+            return self.crdhcpc_renew(interface).await;
+        };
+
+        let clients = self.clients.lock().await;
+        let client = clients.get(interface)
+            .ok_or_else(|| NetctlError::NotFound(format!("No DHCP client running on {}", interface)))?;
+        info!("Renewing DHCP lease on interface {}", interface);
+        client.renew().await
+    }
+
+    /// Get DHCP status for an interface
+    ///
+    /// Returns `None` if no client runs on the interface; a running client
+    /// without a lease yields a lease without an address.
+    pub async fn status(&self, interface: &str) -> NetctlResult<Option<DhcpLease>> {
         validation::validate_interface_name(interface)?;
 
+        let DhcpClientBackend::Native = &self.backend else {
+            return self.crdhcpc_status(interface).await;
+        };
+
+        Ok(self.clients.lock().await.get(interface)
+            .filter(|client| !client.is_finished())
+            .map(|client| client.lease().unwrap_or_else(|| DhcpLease {
+                interface: interface.to_string(),
+                ..Default::default()
+            })))
+    }
+
+    /// Lease last persisted for an interface
+    ///
+    /// Unlike [`status`](Self::status) this does not need the client that
+    /// acquired the lease, so it works from other processes and after the
+    /// client was stopped. The lease may have expired since.
+    pub async fn saved_lease(&self, interface: &str) -> NetctlResult<Option<DhcpLease>> {
+        validation::validate_interface_name(interface)?;
+        self.leases.load(interface).await
+    }
+
+    /// State of the built-in client on an interface
+    pub async fn state(&self, interface: &str) -> DhcpClientState {
+        match self.clients.lock().await.get(interface) {
+            Some(client) if !client.is_finished() => client.state(),
+            _ => DhcpClientState::Stopped,
+        }
+    }
+
+    /// Check if DHCP client is running on an interface
+    pub async fn is_running(&self, interface: &str) -> bool {
+        self.status(interface).await.ok().flatten().is_some()
+    }
+
+    async fn crdhcpc_start(&self, interface: &str) -> NetctlResult<()> {
+        let (crdhcpc_bin, config_path) = self.crdhcpc_paths()?;
         if !self.is_installed().await {
             return Err(NetctlError::NotFound(format!(
                 "crdhcpc not found at {}. Please install crdhcpc from ../crdhpcd",
-                self.crdhcpc_bin.display()
+                crdhcpc_bin.display()
             )));
         }
 
         info!("Starting DHCP client on interface {}", interface);
 
-        let output = Command::new(&self.crdhcpc_bin)
+        let output = Command::new(crdhcpc_bin)
             .arg("-c")
-            .arg(&self.config_path)
+            .arg(config_path)
             .arg("start")
             .arg(interface)
             .output()
@@ -125,10 +670,8 @@ impl DhcpClientController {
         Ok(())
     }
 
-    /// Stop DHCP client on an interface
-    pub async fn stop(&self, interface: &str) -> NetctlResult<()> {
-        validation::validate_interface_name(interface)?;
-
+    async fn crdhcpc_stop(&self, interface: &str) -> NetctlResult<()> {
+        let (crdhcpc_bin, config_path) = self.crdhcpc_paths()?;
         if !self.is_installed().await {
             // Not an error if not installed
             debug!("crdhcpc not installed, skipping stop");
@@ -137,9 +680,9 @@ impl DhcpClientController {
 
         info!("Stopping DHCP client on interface {}", interface);
 
-        let output = Command::new(&self.crdhcpc_bin)
+        let output = Command::new(crdhcpc_bin)
             .arg("-c")
-            .arg(&self.config_path)
+            .arg(config_path)
             .arg("stop")
             .arg(interface)
             .output()
@@ -159,19 +702,17 @@ impl DhcpClientController {
         Ok(())
     }
 
-    /// Release DHCP lease on an interface
-    pub async fn release(&self, interface: &str) -> NetctlResult<()> {
-        validation::validate_interface_name(interface)?;
-
+    async fn crdhcpc_release(&self, interface: &str) -> NetctlResult<()> {
+        let (crdhcpc_bin, config_path) = self.crdhcpc_paths()?;
         if !self.is_installed().await {
             return Ok(());
         }
 
         info!("Releasing DHCP lease on interface {}", interface);
 
-        let output = Command::new(&self.crdhcpc_bin)
+        let output = Command::new(crdhcpc_bin)
             .arg("-c")
-            .arg(&self.config_path)
+            .arg(config_path)
             .arg("release")
             .arg(interface)
             .output()
@@ -186,14 +727,15 @@ impl DhcpClientController {
             let stderr = String::from_utf8_lossy(&output.stderr);
             warn!("Failed to release DHCP lease on {}: {}", interface, stderr);
         }
+        if let Err(e) = self.leases.remove(interface).await {
+            warn!("Failed to remove DHCP lease file of {}: {}", interface, e);
+        }
 
         Ok(())
     }
 
-    /// Renew DHCP lease on an interface
-    pub async fn renew(&self, interface: &str) -> NetctlResult<()> {
-        validation::validate_interface_name(interface)?;
-
+    async fn crdhcpc_renew(&self, interface: &str) -> NetctlResult<()> {
+        let (crdhcpc_bin, config_path) = self.crdhcpc_paths()?;
         if !self.is_installed().await {
             return Err(NetctlError::NotFound(
                 "crdhcpc not installed".to_string()
@@ -202,9 +744,9 @@ impl DhcpClientController {
 
         info!("Renewing DHCP lease on interface {}", interface);
 
-        let output = Command::new(&self.crdhcpc_bin)
+        let output = Command::new(crdhcpc_bin)
             .arg("-c")
-            .arg(&self.config_path)
+            .arg(config_path)
             .arg("renew")
             .arg(interface)
             .output()
@@ -227,17 +769,15 @@ impl DhcpClientController {
         Ok(())
     }
 
-    /// Get DHCP status for an interface
-    pub async fn status(&self, interface: &str) -> NetctlResult<Option<DhcpLease>> {
-        validation::validate_interface_name(interface)?;
-
+    async fn crdhcpc_status(&self, interface: &str) -> NetctlResult<Option<DhcpLease>> {
+        let (crdhcpc_bin, config_path) = self.crdhcpc_paths()?;
         if !self.is_installed().await {
             return Ok(None);
         }
 
-        let output = Command::new(&self.crdhcpc_bin)
+        let output = Command::new(crdhcpc_bin)
             .arg("-c")
-            .arg(&self.config_path)
+            .arg(config_path)
             .arg("status")
             .arg(interface)
             .output()
@@ -253,28 +793,45 @@ impl DhcpClientController {
             return Ok(None);
         }
 
-        let _stdout = String::from_utf8_lossy(&output.stdout);
-        // Parse the output to extract lease information
-        // For now, return a basic structure - actual parsing depends on crdhcpc output format
-        Ok(Some(DhcpLease {
-            interface: interface.to_string(),
-            ip_address: None,
-            subnet_mask: None,
-            gateway: None,
-            dns_servers: Vec::new(),
-            lease_time: None,
-            acquired_at: None,
-            server_address: None,
-        }))
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut lease = DhcpLease::parse_status(interface, &stdout);
+        let saved = self.leases.load(interface).await.unwrap_or_else(|e| {
+            warn!("Failed to read saved DHCP lease of {}: {}", interface, e);
+            None
+        });
+
+        if lease.ip_address.is_none() {
+            // crdhcpc only printed its state; fall back to the lease seen last
+            if let Some(saved) = saved.filter(|saved| !saved.is_expired()) {
+                lease = saved;
+            }
+        } else {
+            if lease.acquired_at.is_none() {
+                // Still the same lease: keep the time it was first seen
+                lease.acquired_at = saved
+                    .filter(|saved| saved.ip_address == lease.ip_address)
+                    .and_then(|saved| saved.acquired_at)
+                    .or_else(|| Some(unix_now()));
+            }
+            if let Err(e) = self.leases.save(&lease).await {
+                warn!("Failed to save DHCP lease of {}: {}", interface, e);
+            }
+        }
+        Ok(Some(lease))
     }
 
-    /// Check if DHCP client is running on an interface
-    pub async fn is_running(&self, interface: &str) -> bool {
-        self.status(interface).await.ok().flatten().is_some()
+    fn crdhcpc_paths(&self) -> NetctlResult<(&PathBuf, &PathBuf)> {
+        match &self.backend {
+            DhcpClientBackend::Crdhcpc { bin, config } => Ok((bin, config)),
+            DhcpClientBackend::Native => Err(NetctlError::NotSupported(
+                "Operation requires the crdhcpc backend".to_string()
+            )),
+        }
     }
 
-    /// Start DHCP daemon (manages all interfaces)
+    /// Start crdhcpc daemon (manages all interfaces)
     pub async fn start_daemon(&self) -> NetctlResult<()> {
+        let (crdhcpc_bin, config_path) = self.crdhcpc_paths()?;
         if !self.is_installed().await {
             return Err(NetctlError::NotFound(
                 "crdhcpc not installed".to_string()
@@ -283,9 +840,9 @@ impl DhcpClientController {
 
         info!("Starting DHCP client daemon");
 
-        let mut child = Command::new(&self.crdhcpc_bin)
+        let mut child = Command::new(crdhcpc_bin)
             .arg("-c")
-            .arg(&self.config_path)
+            .arg(config_path)
             .arg("daemon")
             .stdin(Stdio::null())
             .stdout(Stdio::null())
@@ -341,8 +898,14 @@ mod tests {
     #[test]
     fn test_dhcp_client_controller_creation() {
         let controller = DhcpClientController::new();
-        assert_eq!(controller.crdhcpc_bin, PathBuf::from(CRDHCPC_BIN));
-        assert_eq!(controller.config_path, PathBuf::from(CRDHCPC_CONFIG));
+        assert_eq!(controller.backend, DhcpClientBackend::Native);
+        assert_eq!(controller.options.route_metric, DEFAULT_ROUTE_METRIC);
+
+        let controller = DhcpClientController::crdhcpc();
+        assert_eq!(controller.backend, DhcpClientBackend::Crdhcpc {
+            bin: PathBuf::from(CRDHCPC_BIN),
+            config: PathBuf::from(CRDHCPC_CONFIG),
+        });
     }
 
     #[test]
@@ -351,7 +914,71 @@ mod tests {
             PathBuf::from("/custom/bin/crdhcpc"),
             PathBuf::from("/custom/config.toml"),
         );
-        assert_eq!(controller.crdhcpc_bin, PathBuf::from("/custom/bin/crdhcpc"));
-        assert_eq!(controller.config_path, PathBuf::from("/custom/config.toml"));
+        assert_eq!(controller.backend, DhcpClientBackend::Crdhcpc {
+            bin: PathBuf::from("/custom/bin/crdhcpc"),
+            config: PathBuf::from("/custom/config.toml"),
+        });
+    }
+
+    #[test]
+    fn test_parse_status() {
+        let text = "Interface: eth0\n\
+                    State: Bound\n\
+                    IP Address: 192.168.1.50/24\n\
+                    Gateway: 192.168.1.1\n\
+                    DNS Servers: 192.168.1.1, 9.9.9.9\n\
+                    Lease Time: 3600 seconds\n\
+                    Server ID: 192.168.1.1\n\
+                    acquired_at=1700000000\n";
+        let lease = DhcpLease::parse_status("eth0", text);
+        assert_eq!(lease.ip_address.as_deref(), Some("192.168.1.50"));
+        assert_eq!(lease.subnet_mask.as_deref(), Some("255.255.255.0"));
+        assert_eq!(lease.prefix_len(), Some(24));
+        assert_eq!(lease.gateway.as_deref(), Some("192.168.1.1"));
+        assert_eq!(lease.dns_servers, vec!["192.168.1.1", "9.9.9.9"]);
+        assert_eq!(lease.lease_time, Some(3600));
+        assert_eq!(lease.server_address.as_deref(), Some("192.168.1.1"));
+        assert_eq!(lease.acquired_at, Some(1700000000));
+        assert_eq!(lease.expires_at(), Some(1700003600));
+        assert!(lease.is_expired());
+
+        let json = r#"{"interface": "eth0", "ip": "10.0.0.7", "netmask": "255.255.0.0",
+                       "routers": ["10.0.0.1"], "dns": ["10.0.0.53"], "lease_time": 600,
+                       "acquired_at": "2024-01-01T00:00:00Z", "server_id": "10.0.0.2"}"#;
+        let lease = DhcpLease::parse_status("eth0", json);
+        assert_eq!(lease.ip_address.as_deref(), Some("10.0.0.7"));
+        assert_eq!(lease.prefix_len(), Some(16));
+        assert_eq!(lease.gateway.as_deref(), Some("10.0.0.1"));
+        assert_eq!(lease.dns_servers, vec!["10.0.0.53"]);
+        assert_eq!(lease.lease_time, Some(600));
+        assert_eq!(lease.acquired_at, Some(1704067200));
+        assert_eq!(lease.server_address.as_deref(), Some("10.0.0.2"));
+
+        // Only a state: no address
+        let lease = DhcpLease::parse_status("eth0", "state: acquiring\n");
+        assert_eq!(lease.ip_address, None);
+        assert!(!lease.is_expired());
+    }
+
+    #[tokio::test]
+    async fn test_lease_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let controller = DhcpClientController::new().with_lease_dir(dir.path());
+        assert_eq!(controller.saved_lease("eth0").await.unwrap(), None);
+
+        let lease = DhcpLease {
+            interface: "eth0".to_string(),
+            ip_address: Some("10.0.0.7".to_string()),
+            lease_time: Some(600),
+            acquired_at: Some(unix_now()),
+            ..Default::default()
+        };
+        controller.leases.save(&lease).await.unwrap();
+        assert!(dir.path().join("eth0.lease").exists());
+        assert_eq!(controller.saved_lease("eth0").await.unwrap(), Some(lease));
+
+        controller.leases.remove("eth0").await.unwrap();
+        controller.leases.remove("eth0").await.unwrap();
+        assert_eq!(controller.saved_lease("eth0").await.unwrap(), None);
     }
 }
@@ -1,12 +1,47 @@
-//! DHCP testing and diagnostics via dhcpm
+//! DHCP testing and diagnostics
 //!
-//! This module provides DHCP client testing capabilities using the dhcpm library.
-//! It allows sending mocked DHCP messages for testing DHCP servers without affecting
-//! actual network configuration.
+//! Sends hand-built DHCP messages and records what servers answer, without
+//! configuring anything on the interface. Messages go out through a packet
+//! socket with the IP and UDP headers built here, so tests also work on
+//! interfaces without an address. Every reply seen within the wait time is
+//! decoded, which makes a second (rogue) server answering next to the real
+//! one visible.
+//!
+//! Messages are sent from a random client hardware address unless one is
+//! given. Replies to such an address only reach this host if they are
+//! broadcast, so [`DhcpTestConfig::broadcast`] is on by default.
 
+use crate::arp;
+use crate::dhcp::{parse_hex, to_hex};
+use crate::dhcpv4::packet::{options, DhcpPacket, MessageType, BOOTREPLY, CLIENT_PORT, FLAG_BROADCAST, SERVER_PORT};
 use crate::error::{NetctlError, NetctlResult};
+use crate::interface::InterfaceController;
+use crate::netns::NetNamespace;
+use crate::rawsock::{self, PacketSocket, BROADCAST_MAC, ETH_P_IP};
 use crate::validation;
 use serde::{Deserialize, Serialize};
+use std::net::{Ipv4Addr, SocketAddrV4};
+use tokio::time::{timeout_at, Duration, Instant};
+use tracing::debug;
+
+/// Time to wait for replies unless configured otherwise
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Options asked for in DISCOVER, REQUEST and INFORM
+const PARAMETER_REQUEST_LIST: [u8; 12] = [
+    options::SUBNET_MASK,
+    options::ROUTER,
+    options::DNS_SERVERS,
+    options::HOST_NAME,
+    options::DOMAIN_NAME,
+    options::INTERFACE_MTU,
+    options::NTP_SERVERS,
+    options::LEASE_TIME,
+    options::RENEWAL_TIME,
+    options::REBINDING_TIME,
+    options::DOMAIN_SEARCH,
+    options::CLASSLESS_STATIC_ROUTES,
+];
 
 /// DHCP message type
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
@@ -23,6 +58,18 @@ pub enum DhcpMessageType {
     Decline,
 }
 
+impl From<DhcpMessageType> for MessageType {
+    fn from(kind: DhcpMessageType) -> Self {
+        match kind {
+            DhcpMessageType::Discover => MessageType::Discover,
+            DhcpMessageType::Request => MessageType::Request,
+            DhcpMessageType::Release => MessageType::Release,
+            DhcpMessageType::Inform => MessageType::Inform,
+            DhcpMessageType::Decline => MessageType::Decline,
+        }
+    }
+}
+
 /// Configuration for DHCP testing
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DhcpTestConfig {
@@ -47,7 +94,7 @@ pub struct DhcpTestConfig {
 }
 
 /// DHCP option
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DhcpOption {
     /// Option code (e.g., 53 for message type, 50 for requested IP)
     pub code: u8,
@@ -66,6 +113,23 @@ pub struct DhcpTestResult {
     pub error: Option<String>,
     /// Round-trip time in milliseconds
     pub rtt_ms: Option<u64>,
+    /// Message type that was sent
+    #[serde(default = "default_message_type")]
+    pub message_type: DhcpMessageType,
+    /// Client hardware address the message was sent from
+    #[serde(default)]
+    pub client_mac: Option<String>,
+    /// Transaction ID of the message
+    #[serde(default)]
+    pub xid: u32,
+    /// Every reply received, in order; more than one offer means more
+    /// than one server answered
+    #[serde(default)]
+    pub responses: Vec<DhcpResponse>,
+}
+
+fn default_message_type() -> DhcpMessageType {
+    DhcpMessageType::Discover
 }
 
 /// DHCP response information
@@ -87,6 +151,74 @@ pub struct DhcpResponse {
     pub dns_servers: Option<Vec<String>>,
     /// Domain name
     pub domain_name: Option<String>,
+    /// Source address of the reply (a relay agent's if relayed)
+    #[serde(default)]
+    pub source_ip: Option<String>,
+    /// Hardware address the reply came from
+    #[serde(default)]
+    pub source_mac: Option<String>,
+    /// Time from sending the request to this reply, in milliseconds
+    #[serde(default)]
+    pub rtt_ms: u64,
+    /// Renewal time (T1) in seconds
+    #[serde(default)]
+    pub renewal_time: Option<u32>,
+    /// Rebinding time (T2) in seconds
+    #[serde(default)]
+    pub rebinding_time: Option<u32>,
+    /// NTP servers
+    #[serde(default)]
+    pub ntp_servers: Option<Vec<String>>,
+    /// Domain search list
+    #[serde(default)]
+    pub domain_search: Option<Vec<String>>,
+    /// Interface MTU
+    #[serde(default)]
+    pub mtu: Option<u16>,
+    /// Server message (option 56), usually explaining a NAK
+    #[serde(default)]
+    pub message: Option<String>,
+    /// All options as received
+    #[serde(default)]
+    pub options: Vec<DhcpOption>,
+}
+
+impl DhcpResponse {
+    /// Decode the fields of a server reply
+    pub fn from_packet(packet: &DhcpPacket, source: Option<SocketAddrV4>, source_mac: Option<[u8; 6]>, rtt: Duration) -> Self {
+        let ips = |code: u8| {
+            let ips = packet.ip_list_option(code);
+            (!ips.is_empty()).then(|| ips.iter().map(Ipv4Addr::to_string).collect())
+        };
+        let domain_search = packet.domain_search();
+        Self {
+            message_type: packet.message_type().map_or_else(|| "UNKNOWN".to_string(), |kind| kind.to_string()),
+            your_ip: (!packet.yiaddr.is_unspecified()).then(|| packet.yiaddr.to_string()),
+            server_ip: packet.server_id().map(|ip| ip.to_string()),
+            lease_time: packet.u32_option(options::LEASE_TIME),
+            subnet_mask: packet.ip_option(options::SUBNET_MASK).map(|ip| ip.to_string()),
+            router: packet.ip_option(options::ROUTER).map(|ip| ip.to_string()),
+            dns_servers: ips(options::DNS_SERVERS),
+            domain_name: packet.string_option(options::DOMAIN_NAME),
+            source_ip: source.map(|addr| addr.ip().to_string()),
+            source_mac: source_mac.map(|mac| arp::format_mac(&mac)),
+            rtt_ms: rtt.as_millis() as u64,
+            renewal_time: packet.u32_option(options::RENEWAL_TIME),
+            rebinding_time: packet.u32_option(options::REBINDING_TIME),
+            ntp_servers: ips(options::NTP_SERVERS),
+            domain_search: (!domain_search.is_empty()).then_some(domain_search),
+            mtu: packet.u16_option(options::INTERFACE_MTU),
+            message: packet.string_option(options::MESSAGE),
+            options: packet.options.iter()
+                .map(|(code, value)| DhcpOption { code: *code, value: to_hex(value) })
+                .collect(),
+        }
+    }
+
+    /// Whether the reply is of type `kind`
+    pub fn is(&self, kind: MessageType) -> bool {
+        self.message_type == kind.to_string()
+    }
 }
 
 impl Default for DhcpTestConfig {
@@ -105,11 +237,71 @@ impl Default for DhcpTestConfig {
     }
 }
 
+/// Parse an optional IPv4 address from the test configuration
+fn optional_ipv4(value: &Option<String>, what: &str) -> NetctlResult<Option<Ipv4Addr>> {
+    value.as_deref()
+        .map(|ip| ip.parse().map_err(|_| NetctlError::InvalidParameter(format!("Invalid {}: {}", what, ip))))
+        .transpose()
+}
+
+/// A random locally administered unicast hardware address
+pub fn random_mac() -> [u8; 6] {
+    let mut mac: [u8; 6] = rand::random();
+    mac[0] = (mac[0] & 0xfc) | 0x02;
+    mac
+}
+
+/// Build the message described by `config`, sent from `mac`
+///
+/// Custom options are added last and replace generated options with the
+/// same code, so malformed messages can be produced on purpose.
+pub fn build_packet(config: &DhcpTestConfig, xid: u32, mac: [u8; 6]) -> NetctlResult<DhcpPacket> {
+    let requested_ip = optional_ipv4(&config.requested_ip, "requested IP")?;
+    let server_ip = optional_ipv4(&config.server_ip, "server IP")?;
+    let client_ip = optional_ipv4(&config.client_ip, "client IP")?;
+
+    let mut packet = DhcpPacket::request(config.message_type.into(), xid, mac);
+    if config.broadcast {
+        packet.flags = FLAG_BROADCAST;
+    }
+    let mut client_id = vec![1];
+    client_id.extend_from_slice(&mac);
+    packet.set_option(options::CLIENT_ID, client_id);
+
+    match config.message_type {
+        DhcpMessageType::Discover | DhcpMessageType::Request | DhcpMessageType::Decline => {
+            if let Some(ip) = requested_ip {
+                packet.set_ip_option(options::REQUESTED_IP, ip);
+            }
+        }
+        DhcpMessageType::Release | DhcpMessageType::Inform => {
+            packet.ciaddr = client_ip.unwrap_or(Ipv4Addr::UNSPECIFIED);
+        }
+    }
+    if config.message_type != DhcpMessageType::Discover && config.message_type != DhcpMessageType::Inform {
+        if let Some(ip) = server_ip {
+            packet.set_ip_option(options::SERVER_ID, ip);
+        }
+    }
+    if matches!(config.message_type, DhcpMessageType::Discover | DhcpMessageType::Request | DhcpMessageType::Inform) {
+        packet.set_option(options::PARAMETER_REQUEST_LIST, PARAMETER_REQUEST_LIST.to_vec());
+        packet.set_option(options::MAX_MESSAGE_SIZE, 1500u16.to_be_bytes().to_vec());
+    }
+
+    for option in config.options.iter().flatten() {
+        packet.set_option(option.code, parse_hex(&option.value)?);
+    }
+    Ok(packet)
+}
+
 /// Controller for DHCP testing operations
 pub struct DhcpmController {
     /// Default interface to use
-    #[allow(dead_code)]
     default_interface: String,
+    /// Network namespace the interfaces live in
+    namespace: NetNamespace,
+    /// Time to wait for replies
+    timeout: Duration,
 }
 
 impl DhcpmController {
@@ -118,9 +310,32 @@ impl DhcpmController {
         validation::validate_interface_name(&default_interface)?;
         Ok(Self {
             default_interface,
+            namespace: NetNamespace::Current,
+            timeout: DEFAULT_TIMEOUT,
         })
     }
 
+    /// Send on interfaces in `namespace`
+    pub fn in_namespace(mut self, namespace: NetNamespace) -> Self {
+        self.namespace = namespace;
+        self
+    }
+
+    /// Time to wait for replies
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// A test configuration for the default interface
+    pub fn config(&self, message_type: DhcpMessageType) -> DhcpTestConfig {
+        DhcpTestConfig {
+            interface: self.default_interface.clone(),
+            message_type,
+            ..Default::default()
+        }
+    }
+
     /// Validate DHCP test configuration
     pub fn validate_config(&self, config: &DhcpTestConfig) -> NetctlResult<()> {
         // Validate interface name
@@ -155,6 +370,16 @@ impl DhcpmController {
             }
         }
 
+        // Validate custom options
+        for option in config.options.iter().flatten() {
+            if option.code == options::PAD || option.code == options::END {
+                return Err(NetctlError::InvalidParameter(
+                    format!("Option {} cannot be set", option.code),
+                ));
+            }
+            parse_hex(&option.value)?;
+        }
+
         Ok(())
     }
 
@@ -183,18 +408,24 @@ impl DhcpmController {
         Ok(())
     }
 
+    /// Send the message described by `config`
+    pub async fn send(&self, config: &DhcpTestConfig) -> NetctlResult<DhcpTestResult> {
+        match config.message_type {
+            DhcpMessageType::Discover => self.send_discover(config).await,
+            DhcpMessageType::Request => self.send_request(config).await,
+            DhcpMessageType::Release => self.send_release(config).await,
+            DhcpMessageType::Inform => self.send_inform(config).await,
+            DhcpMessageType::Decline => self.send_decline(config).await,
+        }
+    }
+
     /// Send DHCP discovery message
+    ///
+    /// Offers are collected for the whole wait time, so every server on the
+    /// link shows up.
     pub async fn send_discover(&self, config: &DhcpTestConfig) -> NetctlResult<DhcpTestResult> {
         self.validate_config(config)?;
-
-        // Note: Actual dhcpm integration would go here
-        // For now, return a placeholder showing the structure
-        Ok(DhcpTestResult {
-            success: false,
-            response: None,
-            error: Some("dhcpm integration not yet implemented - requires dhcpm CLI".to_string()),
-            rtt_ms: None,
-        })
+        self.exchange(config, DhcpMessageType::Discover).await
     }
 
     /// Send DHCP request message
@@ -207,12 +438,7 @@ impl DhcpmController {
             ));
         }
 
-        Ok(DhcpTestResult {
-            success: false,
-            response: None,
-            error: Some("dhcpm integration not yet implemented - requires dhcpm CLI".to_string()),
-            rtt_ms: None,
-        })
+        self.exchange(config, DhcpMessageType::Request).await
     }
 
     /// Send DHCP release message
@@ -225,45 +451,188 @@ impl DhcpmController {
             ));
         }
 
-        Ok(DhcpTestResult {
-            success: false,
-            response: None,
-            error: Some("dhcpm integration not yet implemented - requires dhcpm CLI".to_string()),
-            rtt_ms: None,
-        })
+        self.exchange(config, DhcpMessageType::Release).await
     }
 
     /// Send DHCP inform message
     pub async fn send_inform(&self, config: &DhcpTestConfig) -> NetctlResult<DhcpTestResult> {
         self.validate_config(config)?;
+        self.exchange(config, DhcpMessageType::Inform).await
+    }
+
+    /// Send DHCP decline message
+    pub async fn send_decline(&self, config: &DhcpTestConfig) -> NetctlResult<DhcpTestResult> {
+        self.validate_config(config)?;
+
+        if config.requested_ip.is_none() {
+            return Err(NetctlError::InvalidParameter(
+                "Requested IP is required for DECLINE messages".to_string(),
+            ));
+        }
+
+        self.exchange(config, DhcpMessageType::Decline).await
+    }
+
+    /// Send one message of type `kind` and collect the replies
+    async fn exchange(&self, config: &DhcpTestConfig, kind: DhcpMessageType) -> NetctlResult<DhcpTestResult> {
+        let config = DhcpTestConfig { message_type: kind, ..config.clone() };
+        let mac = match &config.client_mac {
+            Some(mac) => arp::parse_mac(Some(mac), &config.interface)?,
+            None => random_mac(),
+        };
+        let xid: u32 = rand::random();
+        let packet = build_packet(&config, xid, mac)?;
+
+        let info = InterfaceController::with_namespace(self.namespace.clone()).get_info(&config.interface).await?;
+        let ifindex = info.index
+            .ok_or_else(|| NetctlError::NotFound(format!("Interface {} has no index", config.interface)))?;
+        let socket = PacketSocket::open(&self.namespace, ifindex, ETH_P_IP)?;
 
-        Ok(DhcpTestResult {
+        let destination = match optional_ipv4(&config.server_ip, "server IP")? {
+            Some(server) if !config.broadcast => server,
+            _ => Ipv4Addr::BROADCAST,
+        };
+        let datagram = rawsock::build_udp_ipv4(
+            SocketAddrV4::new(packet.ciaddr, CLIENT_PORT),
+            SocketAddrV4::new(destination, config.server_port.unwrap_or(SERVER_PORT)),
+            &packet.encode(),
+        );
+
+        let mut result = DhcpTestResult {
             success: false,
             response: None,
-            error: Some("dhcpm integration not yet implemented - requires dhcpm CLI".to_string()),
+            error: None,
             rtt_ms: None,
-        })
+            message_type: kind,
+            client_mac: Some(arp::format_mac(&mac)),
+            xid,
+            responses: Vec::new(),
+        };
+
+        debug!("Sending {} from {} on {}", MessageType::from(kind), arp::format_mac(&mac), config.interface);
+        let sent = Instant::now();
+        socket.send_to(&datagram, BROADCAST_MAC).await?;
+
+        // RELEASE and DECLINE go unanswered
+        if matches!(kind, DhcpMessageType::Release | DhcpMessageType::Decline) {
+            result.success = true;
+            return Ok(result);
+        }
+
+        let deadline = sent + self.timeout;
+        let mut buf = vec![0u8; 2048];
+        loop {
+            let frame = match timeout_at(deadline, socket.recv(&mut buf)).await {
+                Err(_) => break,
+                Ok(frame) => frame?,
+            };
+            if frame.outgoing {
+                continue;
+            }
+            let Some((source, payload)) = rawsock::parse_udp_ipv4(&buf[..frame.len], CLIENT_PORT) else {
+                continue;
+            };
+            let reply = match DhcpPacket::decode(payload) {
+                Ok(reply) if reply.op == BOOTREPLY && reply.xid == xid && reply.chaddr == mac => reply,
+                Ok(_) => continue,
+                Err(e) => {
+                    debug!("Ignoring DHCP message from {}: {}", source, e);
+                    continue;
+                }
+            };
+            let response = DhcpResponse::from_packet(&reply, Some(source), Some(frame.source), sent.elapsed());
+            debug!("Received {} from {} after {} ms", response.message_type, source, response.rtt_ms);
+            result.responses.push(response);
+            // Only offers are worth waiting for from further servers
+            if kind != DhcpMessageType::Discover {
+                break;
+            }
+        }
+
+        result.response = result.responses.first().cloned();
+        result.rtt_ms = result.response.as_ref().map(|response| response.rtt_ms);
+        match &result.response {
+            None => result.error = Some(format!("No reply within {} ms", self.timeout.as_millis())),
+            Some(response) if response.is(MessageType::Nak) => {
+                result.error = Some(match &response.message {
+                    Some(message) => format!("Request refused (NAK): {}", message),
+                    None => "Request refused (NAK)".to_string(),
+                });
+            }
+            Some(_) => result.success = true,
+        }
+        Ok(result)
     }
 
     /// Run a comprehensive DHCP test sequence
+    ///
+    /// Performs a full DISCOVER, OFFER, REQUEST, ACK exchange from one
+    /// random client address and releases the address again, see
+    /// [`run_sequence`](Self::run_sequence).
     pub async fn run_test_sequence(
         &self,
         interface: &str,
     ) -> NetctlResult<Vec<DhcpTestResult>> {
         validation::validate_interface_name(interface)?;
+        self.run_sequence(&DhcpTestConfig {
+            interface: interface.to_string(),
+            ..Default::default()
+        }).await
+    }
+
+    /// Run a DORA exchange and release, based on `base`
+    ///
+    /// Interface, client address (random if unset), server port, broadcast
+    /// flag and custom options of `base` apply to every message. The
+    /// sequence stops at the first step that fails.
+    pub async fn run_sequence(&self, base: &DhcpTestConfig) -> NetctlResult<Vec<DhcpTestResult>> {
+        self.validate_config(base)?;
 
         let mut results = Vec::new();
+        let client_mac = base.client_mac.clone().or_else(|| Some(arp::format_mac(&random_mac())));
 
         // Test 1: Discover
         let discover_config = DhcpTestConfig {
-            interface: interface.to_string(),
             message_type: DhcpMessageType::Discover,
-            ..Default::default()
+            client_mac,
+            server_ip: None,
+            client_ip: None,
+            requested_ip: None,
+            ..base.clone()
         };
-        results.push(self.send_discover(&discover_config).await?);
+        let discover = self.send_discover(&discover_config).await?;
+        let offer = discover.responses.iter()
+            .find(|response| response.is(MessageType::Offer))
+            .cloned();
+        results.push(discover);
+        let Some(offer) = offer else {
+            return Ok(results);
+        };
+
+        // Test 2: Request the first offer
+        let request_config = DhcpTestConfig {
+            message_type: DhcpMessageType::Request,
+            requested_ip: offer.your_ip.clone(),
+            server_ip: offer.server_ip.clone(),
+            ..discover_config.clone()
+        };
+        let request = self.send_request(&request_config).await?;
+        let acked = request.response.as_ref().is_some_and(|response| response.is(MessageType::Ack));
+        results.push(request);
+        if !acked {
+            return Ok(results);
+        }
+
+        // Test 3: Give the address back, addressed to the server
+        let release_config = DhcpTestConfig {
+            message_type: DhcpMessageType::Release,
+            client_ip: offer.your_ip,
+            server_ip: offer.server_ip,
+            broadcast: false,
+            ..discover_config
+        };
+        results.push(self.send_release(&release_config).await?);
 
-        // Additional tests would follow based on discover response
-        // For now, just return the single test
         Ok(results)
     }
 }
@@ -300,4 +669,76 @@ mod tests {
         };
         assert!(controller.validate_config(&invalid_config).is_err());
     }
+
+    #[test]
+    fn test_build_packet() {
+        let mac = [0x02, 0, 0, 0, 0, 1];
+        let config = DhcpTestConfig {
+            message_type: DhcpMessageType::Request,
+            requested_ip: Some("10.0.0.50".to_string()),
+            server_ip: Some("10.0.0.1".to_string()),
+            options: Some(vec![
+                DhcpOption { code: 60, value: "6e657463746c".to_string() },
+                DhcpOption { code: 61, value: "ff:00:01".to_string() },
+            ]),
+            ..Default::default()
+        };
+        let packet = build_packet(&config, 0x1234, mac).unwrap();
+        assert_eq!(packet.message_type(), Some(MessageType::Request));
+        assert_eq!(packet.flags, FLAG_BROADCAST);
+        assert_eq!(packet.ip_option(options::REQUESTED_IP), Some(Ipv4Addr::new(10, 0, 0, 50)));
+        assert_eq!(packet.server_id(), Some(Ipv4Addr::new(10, 0, 0, 1)));
+        assert!(packet.option(options::PARAMETER_REQUEST_LIST).is_some());
+        assert_eq!(packet.string_option(options::VENDOR_CLASS_ID).as_deref(), Some("netctl"));
+        // Custom options replace generated ones
+        assert_eq!(packet.option(options::CLIENT_ID), Some(&[0xff, 0, 1][..]));
+
+        let release = DhcpTestConfig {
+            message_type: DhcpMessageType::Release,
+            client_ip: Some("10.0.0.50".to_string()),
+            broadcast: false,
+            ..Default::default()
+        };
+        let packet = build_packet(&release, 1, mac).unwrap();
+        assert_eq!(packet.ciaddr, Ipv4Addr::new(10, 0, 0, 50));
+        assert_eq!(packet.flags, 0);
+        assert!(packet.option(options::PARAMETER_REQUEST_LIST).is_none());
+        assert_eq!(packet.option(options::CLIENT_ID), Some(&[1, 2, 0, 0, 0, 0, 1][..]));
+
+        let controller = DhcpmController::new("eth0".to_string()).unwrap();
+        let bad_option = DhcpTestConfig {
+            options: Some(vec![DhcpOption { code: 255, value: "00".to_string() }]),
+            ..Default::default()
+        };
+        assert!(controller.validate_config(&bad_option).is_err());
+        let bad_hex = DhcpTestConfig {
+            options: Some(vec![DhcpOption { code: 60, value: "xyz".to_string() }]),
+            ..Default::default()
+        };
+        assert!(controller.validate_config(&bad_hex).is_err());
+    }
+
+    #[test]
+    fn test_response_from_packet() {
+        let request = DhcpPacket::request(MessageType::Discover, 7, [2, 0, 0, 0, 0, 1]);
+        let mut offer = DhcpPacket::reply(MessageType::Offer, &request);
+        offer.yiaddr = Ipv4Addr::new(10, 0, 0, 50);
+        offer.set_ip_option(options::SERVER_ID, Ipv4Addr::new(10, 0, 0, 1));
+        offer.set_ip_option(options::SUBNET_MASK, Ipv4Addr::new(255, 255, 255, 0));
+        offer.set_ip_list_option(options::DNS_SERVERS, &[Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(9, 9, 9, 9)]);
+        offer.set_option(options::LEASE_TIME, 600u32.to_be_bytes().to_vec());
+
+        let source = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 67);
+        let response = DhcpResponse::from_packet(&offer, Some(source), Some([2, 0, 0, 0, 0, 0xfe]), Duration::from_millis(12));
+        assert!(response.is(MessageType::Offer));
+        assert_eq!(response.your_ip.as_deref(), Some("10.0.0.50"));
+        assert_eq!(response.server_ip.as_deref(), Some("10.0.0.1"));
+        assert_eq!(response.subnet_mask.as_deref(), Some("255.255.255.0"));
+        assert_eq!(response.dns_servers, Some(vec!["10.0.0.1".to_string(), "9.9.9.9".to_string()]));
+        assert_eq!(response.lease_time, Some(600));
+        assert_eq!(response.router, None);
+        assert_eq!(response.source_mac.as_deref(), Some("02:00:00:00:00:fe"));
+        assert_eq!(response.rtt_ms, 12);
+        assert!(response.options.contains(&DhcpOption { code: options::LEASE_TIME, value: "00000258".to_string() }));
+    }
 }
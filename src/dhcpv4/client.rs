@@ -0,0 +1,847 @@
+//! DHCPv4 client state machine (RFC 2131 section 4.4)
+//!
+//! One task runs per interface. While the client has no address it talks
+//! through a packet socket, building the IP and UDP headers itself; once a
+//! lease is configured, renewals go through a UDP socket bound to the
+//! interface. The task owns everything it installs and removes it again
+//! when the lease ends or the client is stopped.
+
+use super::packet::{
+    mask_to_prefix, options, prefix_to_mask, ClasslessRoute, DhcpPacket, MessageType, BOOTREPLY,
+    CLIENT_PORT, SERVER_PORT,
+};
+use crate::arp::{self, ArpSocket};
+use crate::dhcp_client::{DhcpClientOptions, DhcpClientState, DhcpEvent, DhcpLease, LeaseStore};
+use crate::client_task::{self, or_command, Command};
+use crate::error::{NetctlError, NetctlResult};
+use crate::interface::InterfaceController;
+use crate::netns::NetNamespace;
+use crate::rawsock::{self, PacketSocket, BROADCAST_MAC, ETH_P_IP};
+use crate::resolver::{ResolverEntry, ResolverManager};
+use crate::routing::{Route, RouteProtocol, RoutingController};
+use rand::Rng;
+use std::net::{IpAddr, Ipv4Addr, SocketAddrV4};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::time::{sleep, sleep_until, timeout_at, Instant};
+use tracing::{debug, info, warn};
+
+/// Largest DHCP message we accept (RFC 2131 minimum is 576)
+const MAX_MESSAGE_SIZE: u16 = 1500;
+/// REQUESTs sent before giving up on an offer or a remembered address
+const REQUEST_ATTEMPTS: u32 = 4;
+/// Smallest MTU accepted from a server (RFC 2132 section 5.1)
+const MIN_MTU: u16 = 576;
+/// Priority of resolver entries registered for leases
+const DNS_PRIORITY: i32 = 100;
+
+const PARAMETER_REQUEST_LIST: [u8; 13] = [
+    options::SUBNET_MASK,
+    options::ROUTER,
+    options::DNS_SERVERS,
+    options::DOMAIN_NAME,
+    options::INTERFACE_MTU,
+    options::BROADCAST_ADDRESS,
+    options::NTP_SERVERS,
+    options::LEASE_TIME,
+    options::SERVER_ID,
+    options::RENEWAL_TIME,
+    options::REBINDING_TIME,
+    options::DOMAIN_SEARCH,
+    options::CLASSLESS_STATIC_ROUTES,
+];
+
+/// Handle to a running DHCPv4 client task
+pub(crate) type ClientHandle = client_task::ClientHandle<DhcpClientState, DhcpLease>;
+type ClientStatus = client_task::ClientStatus<DhcpClientState, DhcpLease>;
+
+impl ClientHandle {
+    /// Address of the current lease
+    pub fn address(&self) -> Option<Ipv4Addr> {
+        self.lease()?.ip_address.as_deref()?.parse().ok()
+    }
+}
+
+/// Everything a client task needs to know about its interface
+pub(crate) struct NativeClient {
+    pub interface: String,
+    pub ifindex: u32,
+    pub mac: [u8; 6],
+    pub namespace: NetNamespace,
+    pub options: DhcpClientOptions,
+    pub resolver: Option<Arc<ResolverManager>>,
+    /// Where the current lease is persisted
+    pub leases: LeaseStore,
+    pub events: broadcast::Sender<DhcpEvent>,
+}
+
+impl NativeClient {
+    pub fn spawn(self) -> ClientHandle {
+        ClientHandle::spawn("DHCP", DhcpClientState::Acquiring, |commands, status| {
+            let client = Client {
+                interfaces: InterfaceController::with_namespace(self.namespace.clone()),
+                routing: RoutingController::with_namespace(self.namespace.clone()),
+                config: self,
+                commands,
+                status,
+                lease: None,
+                applied: None,
+            };
+            client.run()
+        })
+    }
+}
+
+/// A lease as granted by a DHCPACK
+#[derive(Debug, Clone)]
+struct Lease {
+    address: Ipv4Addr,
+    prefix_len: u8,
+    server: Ipv4Addr,
+    routers: Vec<Ipv4Addr>,
+    dns_servers: Vec<Ipv4Addr>,
+    domain_name: Option<String>,
+    domain_search: Vec<String>,
+    mtu: Option<u16>,
+    routes: Vec<ClasslessRoute>,
+    lease_time: u32,
+    renewal_time: u32,
+    rebinding_time: u32,
+    acquired: Instant,
+    acquired_at: u64,
+}
+
+impl Lease {
+    /// Lease granted by `ack`; `server` is used when the ACK of a renewal
+    /// does not name the server
+    fn from_ack(ack: &DhcpPacket, server: Option<Ipv4Addr>) -> Option<Self> {
+        if ack.yiaddr.is_unspecified() {
+            return None;
+        }
+        let lease_time = ack.u32_option(options::LEASE_TIME)?;
+        let rebinding_time = ack.u32_option(options::REBINDING_TIME)
+            .unwrap_or((u64::from(lease_time) * 7 / 8) as u32)
+            .min(lease_time);
+        let renewal_time = ack.u32_option(options::RENEWAL_TIME)
+            .unwrap_or(lease_time / 2)
+            .min(rebinding_time);
+
+        Some(Self {
+            address: ack.yiaddr,
+            // Servers practically always send the mask
+            prefix_len: ack.ip_option(options::SUBNET_MASK).and_then(mask_to_prefix).unwrap_or(24),
+            server: ack.server_id().or(server)?,
+            routers: ack.ip_list_option(options::ROUTER),
+            dns_servers: ack.ip_list_option(options::DNS_SERVERS),
+            domain_name: ack.string_option(options::DOMAIN_NAME),
+            domain_search: ack.domain_search(),
+            mtu: ack.u16_option(options::INTERFACE_MTU),
+            routes: ack.classless_routes(),
+            lease_time,
+            renewal_time,
+            rebinding_time,
+            acquired: Instant::now(),
+            acquired_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        })
+    }
+
+    fn at(&self, seconds: u32) -> Instant {
+        self.acquired + Duration::from_secs(u64::from(seconds))
+    }
+
+    fn renewal(&self) -> Instant {
+        self.at(self.renewal_time)
+    }
+
+    fn rebinding(&self) -> Instant {
+        self.at(self.rebinding_time)
+    }
+
+    fn expiry(&self) -> Instant {
+        self.at(self.lease_time)
+    }
+
+    /// Routes to install: the classless routes if the server sent any,
+    /// since they replace the router option (RFC 3442), else a default route
+    fn routes(&self, interface: &str, metric: u32) -> Vec<Route> {
+        let routes: Vec<(Ipv4Addr, u8, Option<Ipv4Addr>)> = if self.routes.is_empty() {
+            self.routers.first().map(|gw| (Ipv4Addr::UNSPECIFIED, 0, Some(*gw))).into_iter().collect()
+        } else {
+            self.routes.iter()
+                .map(|r| (r.destination, r.prefix_len, Some(r.gateway).filter(|gw| !gw.is_unspecified())))
+                .collect()
+        };
+
+        routes.into_iter().map(|(destination, prefix_len, gateway)| {
+            let mut route = Route::new(IpAddr::V4(destination), prefix_len);
+            route.gateway = gateway.map(IpAddr::V4);
+            route.interface = Some(interface.to_string());
+            route.metric = Some(metric);
+            route.protocol = RouteProtocol::Dhcp;
+            route.scope = route.implied_scope();
+            // A gateway outside the leased subnet is still on this link
+            route.onlink = gateway.is_some_and(|gw| !self.contains(gw));
+            route
+        }).collect()
+    }
+
+    fn contains(&self, ip: Ipv4Addr) -> bool {
+        let mask = u32::from(prefix_to_mask(self.prefix_len));
+        u32::from(ip) & mask == u32::from(self.address) & mask
+    }
+
+    fn dns_entry(&self, interface: &str) -> ResolverEntry {
+        let mut entry = ResolverEntry {
+            interface: interface.to_string(),
+            servers: self.dns_servers.iter().map(Ipv4Addr::to_string).collect(),
+            priority: DNS_PRIORITY,
+            ..Default::default()
+        };
+        if self.domain_search.is_empty() {
+            entry.search.extend(self.domain_name.clone());
+        } else {
+            entry.search = self.domain_search.clone();
+        }
+        entry
+    }
+
+    fn to_lease(&self, interface: &str) -> DhcpLease {
+        DhcpLease {
+            interface: interface.to_string(),
+            ip_address: Some(self.address.to_string()),
+            subnet_mask: Some(prefix_to_mask(self.prefix_len).to_string()),
+            gateway: self.routers.first().map(Ipv4Addr::to_string),
+            dns_servers: self.dns_servers.iter().map(Ipv4Addr::to_string).collect(),
+            lease_time: Some(self.lease_time),
+            acquired_at: Some(self.acquired_at),
+            server_address: Some(self.server.to_string()),
+            domain_name: self.domain_name.clone(),
+            domain_search: self.domain_search.clone(),
+            mtu: self.mtu.map(u32::from),
+            routes: self.routes.iter().map(|r| {
+                if r.gateway.is_unspecified() {
+                    format!("{}/{}", r.destination, r.prefix_len)
+                } else {
+                    format!("{}/{} via {}", r.destination, r.prefix_len, r.gateway)
+                }
+            }).collect(),
+            renewal_time: Some(self.renewal_time),
+            rebinding_time: Some(self.rebinding_time),
+        }
+    }
+}
+
+/// Configuration installed for a lease
+#[derive(Debug, Default)]
+struct Applied {
+    address: Option<(Ipv4Addr, u8)>,
+    routes: Vec<Route>,
+    /// MTU before the lease changed it
+    previous_mtu: Option<u32>,
+    dns: Option<ResolverEntry>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Phase {
+    Init,
+    InitReboot(Ipv4Addr),
+    Bound,
+    Renewing,
+    Rebinding,
+}
+
+enum Step {
+    Next(Phase),
+    Exit,
+}
+
+/// Where a message goes and replies come from
+enum Link {
+    /// Packet socket, while the client has no address
+    Raw(PacketSocket),
+    /// UDP socket bound to the interface, once the lease is configured
+    Udp(UdpSocket),
+}
+
+impl Link {
+    async fn send(&self, packet: &DhcpPacket, destination: Ipv4Addr) -> NetctlResult<()> {
+        let payload = packet.encode();
+        match self {
+            Link::Raw(socket) => {
+                let datagram = rawsock::build_udp_ipv4(
+                    SocketAddrV4::new(packet.ciaddr, CLIENT_PORT),
+                    SocketAddrV4::new(destination, SERVER_PORT),
+                    &payload,
+                );
+                socket.send_to(&datagram, BROADCAST_MAC).await
+            }
+            Link::Udp(socket) => {
+                socket.send_to(&payload, SocketAddrV4::new(destination, SERVER_PORT)).await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Receive the next well-formed DHCP message
+    async fn recv(&self) -> NetctlResult<DhcpPacket> {
+        let mut buf = vec![0u8; 2048];
+        loop {
+            let payload = match self {
+                Link::Raw(socket) => {
+                    let frame = socket.recv(&mut buf).await?;
+                    if frame.outgoing {
+                        continue;
+                    }
+                    match rawsock::parse_udp_ipv4(&buf[..frame.len], CLIENT_PORT) {
+                        Some((_, payload)) => payload,
+                        None => continue,
+                    }
+                }
+                Link::Udp(socket) => {
+                    let (len, _) = socket.recv_from(&mut buf).await?;
+                    &buf[..len]
+                }
+            };
+            match DhcpPacket::decode(payload) {
+                Ok(packet) => return Ok(packet),
+                Err(e) => debug!("Ignoring DHCP message: {}", e),
+            }
+        }
+    }
+
+    /// Wait until `deadline` for a reply to transaction `xid`
+    async fn reply(&self, xid: u32, chaddr: [u8; 6], deadline: Instant) -> NetctlResult<Option<DhcpPacket>> {
+        loop {
+            match timeout_at(deadline, self.recv()).await {
+                Err(_) => return Ok(None),
+                Ok(Err(e)) => return Err(e),
+                Ok(Ok(packet)) if packet.op == BOOTREPLY && packet.xid == xid && packet.chaddr == chaddr => {
+                    if packet.message_type().is_some() {
+                        return Ok(Some(packet));
+                    }
+                }
+                Ok(Ok(_)) => {}
+            }
+        }
+    }
+}
+
+/// Retransmission delay with the ±1 s randomisation of RFC 2131 section 4.1
+fn jitter(delay: Duration) -> Duration {
+    let offset = rand::thread_rng().gen_range(-1000i64..=1000);
+    Duration::from_millis((delay.as_millis() as i64 + offset).max(0) as u64)
+}
+
+struct Client {
+    config: NativeClient,
+    interfaces: InterfaceController,
+    routing: RoutingController,
+    commands: mpsc::Receiver<Command>,
+    status: watch::Sender<ClientStatus>,
+    lease: Option<Lease>,
+    applied: Option<Applied>,
+}
+
+impl Client {
+    async fn run(mut self) {
+        let mut phase = match self.config.options.requested_address {
+            Some(address) => Phase::InitReboot(address),
+            None => Phase::Init,
+        };
+        loop {
+            let step = match phase {
+                Phase::Init => self.init().await,
+                Phase::InitReboot(address) => self.init_reboot(address).await,
+                Phase::Bound => self.bound().await,
+                Phase::Renewing => self.extend(false).await,
+                Phase::Rebinding => self.extend(true).await,
+            };
+            match step {
+                Ok(Step::Next(next)) => phase = next,
+                Ok(Step::Exit) => break,
+                Err(e) => {
+                    warn!("DHCP client on {}: {}", self.config.interface, e);
+                    self.set_state(DhcpClientState::Failed);
+                    let retry = sleep(self.config.options.timing.initial_retransmit);
+                    if let Err(command) = or_command(&mut self.commands, retry).await {
+                        if let Step::Exit = self.command(command, Phase::Init).await {
+                            break;
+                        }
+                    }
+                    // Keep a lease that is still valid; it expires on its own
+                    phase = if self.lease.is_some() { Phase::Bound } else { Phase::Init };
+                }
+            }
+        }
+        debug!("DHCP client on {} exited", self.config.interface);
+    }
+
+    fn interface(&self) -> &str {
+        &self.config.interface
+    }
+
+    fn emit(&self, event: DhcpEvent) {
+        // Nobody listening is fine
+        let _ = self.config.events.send(event);
+    }
+
+    /// Persist the current lease, or forget it once it is gone
+    async fn save_lease(&self) {
+        let result = match &self.lease {
+            Some(lease) => self.config.leases.save(&lease.to_lease(self.interface())).await,
+            None => self.config.leases.remove(self.interface()).await,
+        };
+        if let Err(e) = result {
+            warn!("Failed to update lease file of {}: {}", self.interface(), e);
+        }
+    }
+
+    fn set_state(&self, state: DhcpClientState) {
+        let lease = self.lease.as_ref().map(|lease| lease.to_lease(self.interface()));
+        self.status.send_replace(ClientStatus { state, lease });
+    }
+
+    fn raw_link(&self) -> NetctlResult<Link> {
+        Ok(Link::Raw(PacketSocket::open(&self.config.namespace, self.config.ifindex, ETH_P_IP)?))
+    }
+
+    fn udp_link(&self) -> NetctlResult<Link> {
+        Ok(Link::Udp(rawsock::udp_socket(&self.config.namespace, self.interface(), CLIENT_PORT)?))
+    }
+
+    /// A client message with the options every message carries
+    fn message(&self, kind: MessageType, xid: u32, started: Instant) -> DhcpPacket {
+        let mut packet = DhcpPacket::request(kind, xid, self.config.mac);
+        packet.secs = started.elapsed().as_secs().min(u64::from(u16::MAX)) as u16;
+
+        let client_id = self.config.options.client_id.clone().unwrap_or_else(|| {
+            let mut id = vec![1]; // hardware type: Ethernet
+            id.extend_from_slice(&self.config.mac);
+            id
+        });
+        packet.set_option(options::CLIENT_ID, client_id);
+
+        if matches!(kind, MessageType::Discover | MessageType::Request | MessageType::Inform) {
+            packet.set_option(options::PARAMETER_REQUEST_LIST, PARAMETER_REQUEST_LIST.to_vec());
+            packet.set_option(options::MAX_MESSAGE_SIZE, MAX_MESSAGE_SIZE.to_be_bytes().to_vec());
+            if let Some(hostname) = &self.config.options.hostname {
+                packet.set_option(options::HOST_NAME, hostname.as_bytes().to_vec());
+            }
+        }
+        packet
+    }
+
+    /// Handle a command that arrived in `phase`
+    async fn command(&mut self, command: Command, phase: Phase) -> Step {
+        match command {
+            // Already on the way to a (new) lease
+            Command::Renew => Step::Next(phase),
+            Command::Stop(done) => {
+                self.unconfigure().await;
+                self.set_state(DhcpClientState::Stopped);
+                self.emit(DhcpEvent::Stopped { interface: self.interface().to_string() });
+                let _ = done.send(());
+                Step::Exit
+            }
+            Command::Release(done) => {
+                if let Some(lease) = self.lease.take() {
+                    info!("Releasing {} on {}", lease.address, self.interface());
+                    if let Err(e) = self.send_release(&lease).await {
+                        warn!("Failed to send DHCPRELEASE on {}: {}", self.interface(), e);
+                    }
+                    self.unconfigure().await;
+                    self.save_lease().await;
+                    self.emit(DhcpEvent::Released { interface: self.interface().to_string() });
+                } else {
+                    self.unconfigure().await;
+                }
+                self.set_state(DhcpClientState::Stopped);
+                let _ = done.send(());
+                Step::Exit
+            }
+        }
+    }
+
+    async fn send_release(&self, lease: &Lease) -> NetctlResult<()> {
+        let mut release = self.message(MessageType::Release, rand::random(), Instant::now());
+        release.secs = 0;
+        release.ciaddr = lease.address;
+        release.set_ip_option(options::SERVER_ID, lease.server);
+        self.udp_link()?.send(&release, lease.server).await
+    }
+
+    /// INIT and SELECTING: broadcast DISCOVERs until an offer arrives,
+    /// then request it
+    async fn init(&mut self) -> NetctlResult<Step> {
+        self.set_state(DhcpClientState::Acquiring);
+        let link = self.raw_link()?;
+        let xid = rand::random();
+        let started = Instant::now();
+        let timing = self.config.options.timing.clone();
+        let mut interval = timing.initial_retransmit;
+
+        let offer = loop {
+            debug!("Sending DHCPDISCOVER on {}", self.interface());
+            link.send(&self.message(MessageType::Discover, xid, started), Ipv4Addr::BROADCAST).await?;
+            let deadline = Instant::now() + jitter(interval);
+            interval = (interval * 2).min(timing.max_retransmit);
+
+            let offer = loop {
+                match or_command(&mut self.commands, link.reply(xid, self.config.mac, deadline)).await {
+                    Err(command) => return Ok(self.command(command, Phase::Init).await),
+                    Ok(reply) => match reply? {
+                        Some(offer) if offer.message_type() == Some(MessageType::Offer)
+                            && offer.server_id().is_some() && !offer.yiaddr.is_unspecified() => break Some(offer),
+                        Some(_) => {}
+                        None => break None,
+                    },
+                }
+            };
+            if let Some(offer) = offer {
+                break offer;
+            }
+        };
+
+        let server = offer.server_id().unwrap_or(offer.siaddr);
+        info!("DHCPOFFER of {} from {} on {}", offer.yiaddr, server, self.interface());
+        let mut request = self.message(MessageType::Request, xid, started);
+        request.set_ip_option(options::REQUESTED_IP, offer.yiaddr);
+        request.set_ip_option(options::SERVER_ID, server);
+        self.request(link, request, Phase::Init).await
+    }
+
+    /// INIT-REBOOT: ask for the address of a previous lease
+    async fn init_reboot(&mut self, address: Ipv4Addr) -> NetctlResult<Step> {
+        self.set_state(DhcpClientState::Acquiring);
+        info!("Requesting previous address {} on {}", address, self.interface());
+        let link = self.raw_link()?;
+        let mut request = self.message(MessageType::Request, rand::random(), Instant::now());
+        request.set_ip_option(options::REQUESTED_IP, address);
+        self.request(link, request, Phase::InitReboot(address)).await
+    }
+
+    /// REQUESTING and REBOOTING: send `request` until the server answers
+    async fn request(&mut self, link: Link, mut request: DhcpPacket, phase: Phase) -> NetctlResult<Step> {
+        let started = Instant::now();
+        let mut interval = self.config.options.timing.initial_retransmit;
+
+        for _ in 0..REQUEST_ATTEMPTS {
+            request.secs = started.elapsed().as_secs().min(u64::from(u16::MAX)) as u16;
+            link.send(&request, Ipv4Addr::BROADCAST).await?;
+            let deadline = Instant::now() + jitter(interval);
+            interval = (interval * 2).min(self.config.options.timing.max_retransmit);
+
+            loop {
+                let reply = match or_command(&mut self.commands, link.reply(request.xid, self.config.mac, deadline)).await {
+                    Err(command) => return Ok(self.command(command, phase).await),
+                    Ok(reply) => reply?,
+                };
+                let Some(reply) = reply else { break };
+                match reply.message_type() {
+                    Some(MessageType::Ack) => return self.bind(&link, &reply).await,
+                    Some(MessageType::Nak) => {
+                        info!("DHCPNAK on {}, starting over", self.interface());
+                        return Ok(Step::Next(Phase::Init));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        debug!("No answer to DHCPREQUEST on {}", self.interface());
+        Ok(Step::Next(Phase::Init))
+    }
+
+    /// Check the acknowledged address and configure it
+    async fn bind(&mut self, link: &Link, ack: &DhcpPacket) -> NetctlResult<Step> {
+        let Some(lease) = Lease::from_ack(ack, None) else {
+            warn!("Ignoring incomplete DHCPACK on {}", self.interface());
+            return Ok(Step::Next(Phase::Init));
+        };
+
+        if let Some(arp_check) = self.config.options.arp_check.clone() {
+            let socket = ArpSocket::open(&self.config.namespace, self.config.ifindex, self.config.mac)?;
+            let conflict = match or_command(&mut self.commands, socket.probe(lease.address, &arp_check)).await {
+                Err(command) => return Ok(self.command(command, Phase::Init).await),
+                Ok(conflict) => conflict?,
+            };
+            if let Some(mac) = conflict {
+                return self.decline(link, &lease, mac).await;
+            }
+
+            let address = lease.address;
+            tokio::spawn(async move {
+                if let Err(e) = socket.announce(address, &arp_check).await {
+                    debug!("Failed to announce {}: {}", address, e);
+                }
+            });
+        }
+
+        info!("Bound to {}/{} on {} for {}s", lease.address, lease.prefix_len, self.interface(), lease.lease_time);
+        self.lease = Some(lease.clone());
+        self.configure(&lease).await?;
+        self.save_lease().await;
+        self.set_state(DhcpClientState::Bound);
+        self.emit(DhcpEvent::Bound(lease.to_lease(self.interface())));
+        Ok(Step::Next(Phase::Bound))
+    }
+
+    /// Tell the server that the address is in use and start over
+    async fn decline(&mut self, link: &Link, lease: &Lease, mac: [u8; 6]) -> NetctlResult<Step> {
+        let mac = arp::format_mac(&mac);
+        warn!("{} offered on {} is in use by {}, declining", lease.address, self.interface(), mac);
+
+        let mut decline = self.message(MessageType::Decline, rand::random(), Instant::now());
+        decline.secs = 0;
+        decline.set_ip_option(options::REQUESTED_IP, lease.address);
+        decline.set_ip_option(options::SERVER_ID, lease.server);
+        link.send(&decline, Ipv4Addr::BROADCAST).await?;
+        self.emit(DhcpEvent::Declined {
+            interface: self.interface().to_string(),
+            address: lease.address,
+            mac,
+        });
+
+        match or_command(&mut self.commands, sleep(self.config.options.timing.decline_backoff)).await {
+            Err(command) => Ok(self.command(command, Phase::Init).await),
+            Ok(()) => Ok(Step::Next(Phase::Init)),
+        }
+    }
+
+    /// BOUND: wait for T1 or a renew request
+    async fn bound(&mut self) -> NetctlResult<Step> {
+        let Some(lease) = &self.lease else {
+            return Ok(Step::Next(Phase::Init));
+        };
+        if Instant::now() >= lease.expiry() {
+            return Ok(self.expire().await);
+        }
+        match or_command(&mut self.commands, sleep_until(lease.renewal())).await {
+            Ok(()) | Err(Command::Renew) => Ok(Step::Next(Phase::Renewing)),
+            Err(command) => Ok(self.command(command, Phase::Bound).await),
+        }
+    }
+
+    /// RENEWING and REBINDING: extend the lease with the server that
+    /// granted it until T2, then with any server until it expires
+    async fn extend(&mut self, rebinding: bool) -> NetctlResult<Step> {
+        let Some(lease) = self.lease.clone() else {
+            return Ok(Step::Next(Phase::Init));
+        };
+        let (phase, state, destination, until) = if rebinding {
+            (Phase::Rebinding, DhcpClientState::Rebinding, Ipv4Addr::BROADCAST, lease.expiry())
+        } else {
+            (Phase::Renewing, DhcpClientState::Renewing, lease.server, lease.rebinding())
+        };
+        self.set_state(state);
+        debug!("{} lease of {} on {}", if rebinding { "Rebinding" } else { "Renewing" }, lease.address, self.interface());
+
+        let link = self.udp_link()?;
+        let xid = rand::random();
+        let started = Instant::now();
+
+        loop {
+            let now = Instant::now();
+            if now >= until {
+                return Ok(if rebinding { self.expire().await } else { Step::Next(Phase::Rebinding) });
+            }
+
+            let mut request = self.message(MessageType::Request, xid, started);
+            request.ciaddr = lease.address;
+            link.send(&request, destination).await?;
+
+            // Half the remaining time, but not too often (RFC 2131 section 4.4.5)
+            let wait = ((until - now) / 2).max(self.config.options.timing.min_lease_retransmit);
+            let deadline = (now + wait).min(until);
+
+            loop {
+                let reply = match or_command(&mut self.commands, link.reply(xid, self.config.mac, deadline)).await {
+                    Err(Command::Renew) => continue,
+                    Err(command) => return Ok(self.command(command, phase).await),
+                    Ok(reply) => reply?,
+                };
+                let Some(reply) = reply else { break };
+                match reply.message_type() {
+                    Some(MessageType::Ack) => return self.extended(&lease, &reply).await,
+                    Some(MessageType::Nak) => {
+                        info!("DHCPNAK for {} on {}", lease.address, self.interface());
+                        return Ok(self.expire().await);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Apply the lease from the ACK of a renewal or rebinding
+    async fn extended(&mut self, previous: &Lease, ack: &DhcpPacket) -> NetctlResult<Step> {
+        let Some(lease) = Lease::from_ack(ack, Some(previous.server)) else {
+            warn!("Ignoring incomplete DHCPACK on {}", self.interface());
+            return Ok(Step::Next(Phase::Bound));
+        };
+        if lease.address != previous.address {
+            info!("Lease on {} moved from {} to {}", self.interface(), previous.address, lease.address);
+            self.unconfigure().await;
+        }
+
+        debug!("Lease of {} on {} extended by {}s", lease.address, self.interface(), lease.lease_time);
+        self.lease = Some(lease.clone());
+        self.configure(&lease).await?;
+        self.save_lease().await;
+        self.set_state(DhcpClientState::Bound);
+        self.emit(DhcpEvent::Renewed(lease.to_lease(self.interface())));
+        Ok(Step::Next(Phase::Bound))
+    }
+
+    /// Drop a lease that can no longer be used
+    async fn expire(&mut self) -> Step {
+        if let Some(lease) = self.lease.take() {
+            info!("Lease of {} on {} ended", lease.address, self.interface());
+        }
+        self.unconfigure().await;
+        self.save_lease().await;
+        self.set_state(DhcpClientState::Acquiring);
+        self.emit(DhcpEvent::Expired { interface: self.interface().to_string() });
+        Step::Next(Phase::Init)
+    }
+
+    /// Install the address, routes, MTU and DNS servers of `lease`, only
+    /// touching what changed since the last call
+    async fn configure(&mut self, lease: &Lease) -> NetctlResult<()> {
+        let interface = self.config.interface.clone();
+        let mut applied = self.applied.take().unwrap_or_default();
+        let result = self.configure_into(&interface, lease, &mut applied).await;
+        self.applied = Some(applied);
+        result
+    }
+
+    async fn configure_into(&self, interface: &str, lease: &Lease, applied: &mut Applied) -> NetctlResult<()> {
+        if applied.address.is_none() {
+            match self.interfaces.add_ip(interface, &lease.address.to_string(), lease.prefix_len).await {
+                Ok(()) | Err(NetctlError::AlreadyExists(_)) => {}
+                Err(e) => return Err(e),
+            }
+            applied.address = Some((lease.address, lease.prefix_len));
+        }
+
+        let routes = lease.routes(interface, self.config.options.route_metric);
+        for route in applied.routes.iter().filter(|r| !routes.contains(r)) {
+            if let Err(e) = self.routing.delete_route(route).await {
+                debug!("Failed to delete route {}: {}", route, e);
+            }
+        }
+        applied.routes.retain(|r| routes.contains(r));
+        for route in routes {
+            if applied.routes.contains(&route) {
+                continue;
+            }
+            match self.routing.add_route(&route).await {
+                Ok(()) => applied.routes.push(route),
+                Err(NetctlError::AlreadyExists(_)) => debug!("Route already exists: {}", route),
+                Err(e) => warn!("Failed to add route {}: {}", route, e),
+            }
+        }
+
+        if let Some(mtu) = lease.mtu.filter(|&mtu| self.config.options.apply_mtu && mtu >= MIN_MTU) {
+            let current = self.interfaces.get_info(interface).await?.mtu;
+            if current != Some(u32::from(mtu)) {
+                match self.interfaces.set_mtu(interface, u32::from(mtu)).await {
+                    Ok(()) => {
+                        if applied.previous_mtu.is_none() {
+                            applied.previous_mtu = current;
+                        }
+                    }
+                    Err(e) => warn!("Failed to set MTU {} on {}: {}", mtu, interface, e),
+                }
+            }
+        }
+
+        if let Some(resolver) = &self.config.resolver {
+            let entry = lease.dns_entry(interface);
+            if applied.dns.as_ref() != Some(&entry) {
+                if entry.is_empty() {
+                    resolver.clear(interface).await?;
+                    applied.dns = None;
+                } else {
+                    resolver.set(entry.clone()).await?;
+                    applied.dns = Some(entry);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove everything `configure` installed
+    async fn unconfigure(&mut self) {
+        let Some(applied) = self.applied.take() else {
+            return;
+        };
+        let interface = self.config.interface.clone();
+
+        if applied.dns.is_some() {
+            if let Some(resolver) = &self.config.resolver {
+                if let Err(e) = resolver.clear(&interface).await {
+                    warn!("Failed to remove DNS settings of {}: {}", interface, e);
+                }
+            }
+        }
+        for route in &applied.routes {
+            if let Err(e) = self.routing.delete_route(route).await {
+                debug!("Failed to delete route {}: {}", route, e);
+            }
+        }
+        if let Some((address, prefix_len)) = applied.address {
+            if let Err(e) = self.interfaces.del_ip(&interface, &address.to_string(), prefix_len).await {
+                debug!("Failed to remove {}/{} from {}: {}", address, prefix_len, interface, e);
+            }
+        }
+        if let Some(mtu) = applied.previous_mtu {
+            if let Err(e) = self.interfaces.set_mtu(&interface, mtu).await {
+                warn!("Failed to restore MTU {} on {}: {}", mtu, interface, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lease_from_ack() {
+        let request = DhcpPacket::request(MessageType::Request, 7, [2, 0, 0, 0, 0, 1]);
+        let mut ack = DhcpPacket::reply(MessageType::Ack, &request);
+        ack.yiaddr = Ipv4Addr::new(192, 0, 2, 10);
+        ack.set_ip_option(options::SUBNET_MASK, Ipv4Addr::new(255, 255, 255, 0));
+        ack.set_ip_list_option(options::ROUTER, &[Ipv4Addr::new(192, 0, 2, 1)]);
+        ack.set_option(options::LEASE_TIME, 3600u32.to_be_bytes().to_vec());
+
+        // A server identifier is required
+        assert!(Lease::from_ack(&ack, None).is_none());
+        ack.set_ip_option(options::SERVER_ID, Ipv4Addr::new(192, 0, 2, 1));
+        let lease = Lease::from_ack(&ack, None).unwrap();
+        assert_eq!((lease.renewal_time, lease.rebinding_time), (1800, 3150));
+
+        let routes = lease.routes("eth0", 100);
+        assert_eq!(routes.len(), 1);
+        assert!(routes[0].is_default());
+        assert_eq!(routes[0].gateway, Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))));
+        assert_eq!(routes[0].protocol, RouteProtocol::Dhcp);
+        assert!(!routes[0].onlink);
+
+        // Classless routes replace the router option
+        ack.set_option(options::CLASSLESS_STATIC_ROUTES, super::super::packet::encode_classless_routes(&[
+            ClasslessRoute { destination: Ipv4Addr::new(10, 0, 0, 0), prefix_len: 8, gateway: Ipv4Addr::UNSPECIFIED },
+            ClasslessRoute { destination: Ipv4Addr::UNSPECIFIED, prefix_len: 0, gateway: Ipv4Addr::new(198, 51, 100, 1) },
+        ]));
+        let lease = Lease::from_ack(&ack, None).unwrap();
+        let routes = lease.routes("eth0", 100);
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].gateway, None);
+        assert_eq!(routes[0].scope, crate::routing::RouteScope::Link);
+        assert!(routes[1].onlink);
+        assert_eq!(lease.to_lease("eth0").routes, vec!["10.0.0.0/8", "0.0.0.0/0 via 198.51.100.1"]);
+    }
+}
@@ -0,0 +1,9 @@
+//! Built-in DHCPv4 client
+//!
+//! [`packet`] encodes and decodes DHCP messages; the client state machine
+//! is driven through [`crate::dhcp_client::DhcpClientController`].
+
+pub mod packet;
+pub(crate) mod client;
+
+pub use packet::{ClasslessRoute, DhcpPacket, MessageType};
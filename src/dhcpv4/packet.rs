@@ -0,0 +1,484 @@
+//! DHCPv4 message encoding and decoding (RFC 2131, RFC 2132)
+
+use crate::error::{NetctlError, NetctlResult};
+use serde::{Deserialize, Serialize};
+use std::net::Ipv4Addr;
+
+/// UDP port of DHCP servers
+pub const SERVER_PORT: u16 = 67;
+/// UDP port of DHCP clients
+pub const CLIENT_PORT: u16 = 68;
+
+/// Asks servers to broadcast their replies
+pub const FLAG_BROADCAST: u16 = 0x8000;
+
+pub const BOOTREQUEST: u8 = 1;
+pub const BOOTREPLY: u8 = 2;
+
+const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+/// Fixed BOOTP header up to and including the magic cookie
+const HEADER_LEN: usize = 240;
+/// Some relays drop anything shorter than a BOOTP message
+const MIN_LEN: usize = 300;
+
+/// Option codes
+pub mod options {
+    pub const PAD: u8 = 0;
+    pub const SUBNET_MASK: u8 = 1;
+    pub const ROUTER: u8 = 3;
+    pub const DNS_SERVERS: u8 = 6;
+    pub const HOST_NAME: u8 = 12;
+    pub const DOMAIN_NAME: u8 = 15;
+    pub const INTERFACE_MTU: u8 = 26;
+    pub const BROADCAST_ADDRESS: u8 = 28;
+    pub const NTP_SERVERS: u8 = 42;
+    pub const REQUESTED_IP: u8 = 50;
+    pub const LEASE_TIME: u8 = 51;
+    pub const MESSAGE_TYPE: u8 = 53;
+    pub const SERVER_ID: u8 = 54;
+    pub const PARAMETER_REQUEST_LIST: u8 = 55;
+    pub const MESSAGE: u8 = 56;
+    pub const MAX_MESSAGE_SIZE: u8 = 57;
+    pub const RENEWAL_TIME: u8 = 58;
+    pub const REBINDING_TIME: u8 = 59;
+    pub const VENDOR_CLASS_ID: u8 = 60;
+    pub const CLIENT_ID: u8 = 61;
+    pub const DOMAIN_SEARCH: u8 = 119;
+    pub const CLASSLESS_STATIC_ROUTES: u8 = 121;
+    pub const END: u8 = 255;
+}
+
+/// DHCP message type (option 53)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MessageType {
+    Discover = 1,
+    Offer = 2,
+    Request = 3,
+    Decline = 4,
+    Ack = 5,
+    Nak = 6,
+    Release = 7,
+    Inform = 8,
+}
+
+impl MessageType {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            1 => Self::Discover,
+            2 => Self::Offer,
+            3 => Self::Request,
+            4 => Self::Decline,
+            5 => Self::Ack,
+            6 => Self::Nak,
+            7 => Self::Release,
+            8 => Self::Inform,
+            _ => return None,
+        })
+    }
+}
+
+impl std::fmt::Display for MessageType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Discover => "DHCPDISCOVER",
+            Self::Offer => "DHCPOFFER",
+            Self::Request => "DHCPREQUEST",
+            Self::Decline => "DHCPDECLINE",
+            Self::Ack => "DHCPACK",
+            Self::Nak => "DHCPNAK",
+            Self::Release => "DHCPRELEASE",
+            Self::Inform => "DHCPINFORM",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A route from the classless static route option (RFC 3442)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClasslessRoute {
+    pub destination: Ipv4Addr,
+    pub prefix_len: u8,
+    /// The unspecified address for routes on the link itself
+    pub gateway: Ipv4Addr,
+}
+
+/// A DHCP message on an Ethernet link
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpPacket {
+    pub op: u8,
+    pub hops: u8,
+    pub xid: u32,
+    pub secs: u16,
+    pub flags: u16,
+    pub ciaddr: Ipv4Addr,
+    pub yiaddr: Ipv4Addr,
+    pub siaddr: Ipv4Addr,
+    pub giaddr: Ipv4Addr,
+    pub chaddr: [u8; 6],
+    /// Options in order, without PAD and END
+    pub options: Vec<(u8, Vec<u8>)>,
+}
+
+impl DhcpPacket {
+    /// A client message of type `kind`
+    pub fn request(kind: MessageType, xid: u32, chaddr: [u8; 6]) -> Self {
+        let mut packet = Self {
+            op: BOOTREQUEST,
+            hops: 0,
+            xid,
+            secs: 0,
+            flags: 0,
+            ciaddr: Ipv4Addr::UNSPECIFIED,
+            yiaddr: Ipv4Addr::UNSPECIFIED,
+            siaddr: Ipv4Addr::UNSPECIFIED,
+            giaddr: Ipv4Addr::UNSPECIFIED,
+            chaddr,
+            options: Vec::new(),
+        };
+        packet.set_option(options::MESSAGE_TYPE, vec![kind as u8]);
+        packet
+    }
+
+    /// A server reply of type `kind` to `request`
+    pub fn reply(kind: MessageType, request: &DhcpPacket) -> Self {
+        let mut packet = Self::request(kind, request.xid, request.chaddr);
+        packet.op = BOOTREPLY;
+        packet.flags = request.flags;
+        packet.giaddr = request.giaddr;
+        packet
+    }
+
+    pub fn message_type(&self) -> Option<MessageType> {
+        self.option(options::MESSAGE_TYPE)
+            .and_then(|value| value.first())
+            .and_then(|&value| MessageType::from_u8(value))
+    }
+
+    pub fn option(&self, code: u8) -> Option<&[u8]> {
+        self.options.iter()
+            .find(|(c, _)| *c == code)
+            .map(|(_, value)| value.as_slice())
+    }
+
+    /// Set an option, replacing any previous value
+    pub fn set_option(&mut self, code: u8, value: Vec<u8>) {
+        match self.options.iter_mut().find(|(c, _)| *c == code) {
+            Some(option) => option.1 = value,
+            None => self.options.push((code, value)),
+        }
+    }
+
+    pub fn set_ip_option(&mut self, code: u8, ip: Ipv4Addr) {
+        self.set_option(code, ip.octets().to_vec());
+    }
+
+    pub fn set_ip_list_option(&mut self, code: u8, ips: &[Ipv4Addr]) {
+        self.set_option(code, ips.iter().flat_map(|ip| ip.octets()).collect());
+    }
+
+    pub fn ip_option(&self, code: u8) -> Option<Ipv4Addr> {
+        self.ip_list_option(code).into_iter().next()
+    }
+
+    pub fn ip_list_option(&self, code: u8) -> Vec<Ipv4Addr> {
+        self.option(code)
+            .map(|value| value.chunks_exact(4).map(|ip| Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3])).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn u32_option(&self, code: u8) -> Option<u32> {
+        let value: [u8; 4] = self.option(code)?.try_into().ok()?;
+        Some(u32::from_be_bytes(value))
+    }
+
+    pub fn u16_option(&self, code: u8) -> Option<u16> {
+        let value: [u8; 2] = self.option(code)?.try_into().ok()?;
+        Some(u16::from_be_bytes(value))
+    }
+
+    /// A text option, without trailing NULs
+    pub fn string_option(&self, code: u8) -> Option<String> {
+        let value = self.option(code)?;
+        let text = String::from_utf8_lossy(value).trim_end_matches('\0').to_string();
+        (!text.is_empty()).then_some(text)
+    }
+
+    /// Server identifier (option 54)
+    pub fn server_id(&self) -> Option<Ipv4Addr> {
+        self.ip_option(options::SERVER_ID)
+    }
+
+    /// Domain search list (option 119)
+    pub fn domain_search(&self) -> Vec<String> {
+        self.option(options::DOMAIN_SEARCH)
+            .map(decode_domain_list)
+            .unwrap_or_default()
+    }
+
+    /// Classless static routes (option 121)
+    pub fn classless_routes(&self) -> Vec<ClasslessRoute> {
+        self.option(options::CLASSLESS_STATIC_ROUTES)
+            .map(decode_classless_routes)
+            .unwrap_or_default()
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(MIN_LEN);
+        buf.push(self.op);
+        buf.push(1); // htype: Ethernet
+        buf.push(6); // hlen
+        buf.push(self.hops);
+        buf.extend_from_slice(&self.xid.to_be_bytes());
+        buf.extend_from_slice(&self.secs.to_be_bytes());
+        buf.extend_from_slice(&self.flags.to_be_bytes());
+        for addr in [self.ciaddr, self.yiaddr, self.siaddr, self.giaddr] {
+            buf.extend_from_slice(&addr.octets());
+        }
+        buf.extend_from_slice(&self.chaddr);
+        buf.resize(HEADER_LEN - MAGIC_COOKIE.len(), 0); // chaddr padding, sname, file
+        buf.extend_from_slice(&MAGIC_COOKIE);
+
+        for (code, value) in &self.options {
+            // Long options are split into several instances (RFC 3396)
+            let mut chunks = value.chunks(255).peekable();
+            if chunks.peek().is_none() {
+                buf.extend_from_slice(&[*code, 0]);
+            }
+            for chunk in chunks {
+                buf.push(*code);
+                buf.push(chunk.len() as u8);
+                buf.extend_from_slice(chunk);
+            }
+        }
+        buf.push(options::END);
+        if buf.len() < MIN_LEN {
+            buf.resize(MIN_LEN, 0);
+        }
+        buf
+    }
+
+    pub fn decode(buf: &[u8]) -> NetctlResult<Self> {
+        let invalid = |what: &str| NetctlError::InvalidParameter(format!("Malformed DHCP message: {}", what));
+        if buf.len() < HEADER_LEN {
+            return Err(invalid("too short"));
+        }
+        if buf[HEADER_LEN - 4..HEADER_LEN] != MAGIC_COOKIE {
+            return Err(invalid("missing magic cookie"));
+        }
+        if buf[1] != 1 || buf[2] != 6 {
+            return Err(invalid("not an Ethernet message"));
+        }
+
+        let ip = |at: usize| Ipv4Addr::new(buf[at], buf[at + 1], buf[at + 2], buf[at + 3]);
+        let mut packet = Self {
+            op: buf[0],
+            hops: buf[3],
+            xid: u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]),
+            secs: u16::from_be_bytes([buf[8], buf[9]]),
+            flags: u16::from_be_bytes([buf[10], buf[11]]),
+            ciaddr: ip(12),
+            yiaddr: ip(16),
+            siaddr: ip(20),
+            giaddr: ip(24),
+            chaddr: buf[28..34].try_into().map_err(|_| invalid("chaddr"))?,
+            options: Vec::new(),
+        };
+
+        let mut pos = HEADER_LEN;
+        while pos < buf.len() {
+            let code = buf[pos];
+            match code {
+                options::PAD => {
+                    pos += 1;
+                    continue;
+                }
+                options::END => break,
+                _ => {}
+            }
+            let len = usize::from(*buf.get(pos + 1).ok_or_else(|| invalid("truncated option"))?);
+            let value = buf.get(pos + 2..pos + 2 + len).ok_or_else(|| invalid("truncated option"))?;
+            // Repeated options are concatenated (RFC 3396)
+            match packet.options.iter_mut().find(|(c, _)| *c == code) {
+                Some(option) => option.1.extend_from_slice(value),
+                None => packet.options.push((code, value.to_vec())),
+            }
+            pos += 2 + len;
+        }
+        Ok(packet)
+    }
+}
+
+/// Encode a domain list as in RFC 1035 section 3.1, without compression
+pub fn encode_domain_list(domains: &[String]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for domain in domains {
+        for label in domain.trim_end_matches('.').split('.').filter(|l| !l.is_empty()) {
+            let label = &label.as_bytes()[..label.len().min(63)];
+            buf.push(label.len() as u8);
+            buf.extend_from_slice(label);
+        }
+        buf.push(0);
+    }
+    buf
+}
+
+/// Decode an RFC 1035 domain list with compression pointers into the list
+pub fn decode_domain_list(data: &[u8]) -> Vec<String> {
+    let mut domains = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let mut labels: Vec<String> = Vec::new();
+        let mut cursor = pos;
+        let mut next = None;
+        // Bound the number of jumps so pointer loops cannot hang us
+        let mut jumps = 0;
+        loop {
+            let Some(&len) = data.get(cursor) else { return domains };
+            match len {
+                0 => {
+                    next.get_or_insert(cursor + 1);
+                    break;
+                }
+                len if len & 0xc0 == 0xc0 => {
+                    let Some(&low) = data.get(cursor + 1) else { return domains };
+                    next.get_or_insert(cursor + 2);
+                    cursor = usize::from(u16::from_be_bytes([len & 0x3f, low]));
+                    jumps += 1;
+                    if jumps > data.len() {
+                        return domains;
+                    }
+                }
+                len if len & 0xc0 == 0 => {
+                    let len = usize::from(len);
+                    let Some(label) = data.get(cursor + 1..cursor + 1 + len) else { return domains };
+                    labels.push(String::from_utf8_lossy(label).into_owned());
+                    cursor += 1 + len;
+                }
+                _ => return domains,
+            }
+        }
+        if !labels.is_empty() {
+            domains.push(labels.join("."));
+        }
+        pos = next.unwrap_or(data.len());
+    }
+    domains
+}
+
+/// Encode classless static routes (RFC 3442)
+pub fn encode_classless_routes(routes: &[ClasslessRoute]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for route in routes {
+        let significant = usize::from(route.prefix_len.min(32)).div_ceil(8);
+        buf.push(route.prefix_len.min(32));
+        buf.extend_from_slice(&route.destination.octets()[..significant]);
+        buf.extend_from_slice(&route.gateway.octets());
+    }
+    buf
+}
+
+/// Decode classless static routes (RFC 3442); a malformed list yields no
+/// routes at all
+pub fn decode_classless_routes(data: &[u8]) -> Vec<ClasslessRoute> {
+    let mut routes = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let prefix_len = data[pos];
+        if prefix_len > 32 {
+            return Vec::new();
+        }
+        let significant = usize::from(prefix_len).div_ceil(8);
+        let Some(entry) = data.get(pos + 1..pos + 1 + significant + 4) else {
+            return Vec::new();
+        };
+        let mut destination = [0u8; 4];
+        destination[..significant].copy_from_slice(&entry[..significant]);
+        let gateway = &entry[significant..];
+        routes.push(ClasslessRoute {
+            destination: Ipv4Addr::from(destination),
+            prefix_len,
+            gateway: Ipv4Addr::new(gateway[0], gateway[1], gateway[2], gateway[3]),
+        });
+        pos += 1 + significant + 4;
+    }
+    routes
+}
+
+/// Prefix length of a contiguous subnet mask
+pub fn mask_to_prefix(mask: Ipv4Addr) -> Option<u8> {
+    let bits = u32::from(mask);
+    let prefix = bits.leading_ones();
+    (bits.checked_shl(prefix).unwrap_or(0) == 0).then_some(prefix as u8)
+}
+
+/// Subnet mask for a prefix length
+pub fn prefix_to_mask(prefix_len: u8) -> Ipv4Addr {
+    let prefix = u32::from(prefix_len.min(32));
+    Ipv4Addr::from(u32::MAX.checked_shl(32 - prefix).unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packet_roundtrip() {
+        let mut discover = DhcpPacket::request(MessageType::Discover, 0x1234_5678, [2, 0, 0, 0, 0, 1]);
+        discover.flags = FLAG_BROADCAST;
+        discover.set_option(options::PARAMETER_REQUEST_LIST, vec![1, 3, 6]);
+        discover.set_option(options::HOST_NAME, b"router".to_vec());
+        discover.set_option(options::VENDOR_CLASS_ID, vec![b'x'; 300]);
+
+        let encoded = discover.encode();
+        assert!(encoded.len() >= MIN_LEN);
+        let decoded = DhcpPacket::decode(&encoded).unwrap();
+        assert_eq!(decoded, discover);
+        assert_eq!(decoded.message_type(), Some(MessageType::Discover));
+        assert_eq!(decoded.string_option(options::HOST_NAME).as_deref(), Some("router"));
+
+        let mut ack = DhcpPacket::reply(MessageType::Ack, &discover);
+        ack.yiaddr = Ipv4Addr::new(192, 0, 2, 10);
+        ack.set_ip_option(options::SERVER_ID, Ipv4Addr::new(192, 0, 2, 1));
+        ack.set_ip_list_option(options::DNS_SERVERS, &[Ipv4Addr::new(192, 0, 2, 53), Ipv4Addr::new(192, 0, 2, 54)]);
+        ack.set_option(options::LEASE_TIME, 3600u32.to_be_bytes().to_vec());
+        ack.set_option(options::INTERFACE_MTU, 1400u16.to_be_bytes().to_vec());
+        let decoded = DhcpPacket::decode(&ack.encode()).unwrap();
+        assert_eq!(decoded.op, BOOTREPLY);
+        assert_eq!(decoded.server_id(), Some(Ipv4Addr::new(192, 0, 2, 1)));
+        assert_eq!(decoded.ip_list_option(options::DNS_SERVERS).len(), 2);
+        assert_eq!(decoded.u32_option(options::LEASE_TIME), Some(3600));
+        assert_eq!(decoded.u16_option(options::INTERFACE_MTU), Some(1400));
+
+        assert!(DhcpPacket::decode(&encoded[..200]).is_err());
+    }
+
+    #[test]
+    fn test_domain_list() {
+        let domains = vec!["corp.example".to_string(), "example".to_string()];
+        assert_eq!(decode_domain_list(&encode_domain_list(&domains)), domains);
+
+        // "eng.corp.example" followed by a pointer-compressed "corp.example"
+        let compressed = b"\x03eng\x04corp\x07example\x00\xc0\x04";
+        assert_eq!(decode_domain_list(compressed), vec!["eng.corp.example", "corp.example"]);
+        // A pointer to itself must not hang
+        assert!(decode_domain_list(b"\xc0\x00").is_empty());
+    }
+
+    #[test]
+    fn test_classless_routes() {
+        let routes = vec![
+            ClasslessRoute { destination: Ipv4Addr::new(10, 0, 0, 0), prefix_len: 8, gateway: Ipv4Addr::new(192, 0, 2, 1) },
+            ClasslessRoute { destination: Ipv4Addr::new(172, 16, 4, 0), prefix_len: 22, gateway: Ipv4Addr::UNSPECIFIED },
+            ClasslessRoute { destination: Ipv4Addr::UNSPECIFIED, prefix_len: 0, gateway: Ipv4Addr::new(192, 0, 2, 1) },
+        ];
+        let encoded = encode_classless_routes(&routes);
+        assert_eq!(&encoded[..9], &[8, 10, 192, 0, 2, 1, 22, 172, 16]);
+        assert_eq!(decode_classless_routes(&encoded), routes);
+        assert!(decode_classless_routes(&encoded[..encoded.len() - 1]).is_empty());
+
+        assert_eq!(mask_to_prefix(Ipv4Addr::new(255, 255, 252, 0)), Some(22));
+        assert_eq!(mask_to_prefix(Ipv4Addr::new(255, 0, 255, 0)), None);
+        assert_eq!(prefix_to_mask(22), Ipv4Addr::new(255, 255, 252, 0));
+        assert_eq!(prefix_to_mask(0), Ipv4Addr::UNSPECIFIED);
+    }
+}
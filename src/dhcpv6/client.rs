@@ -0,0 +1,835 @@
+//! DHCPv6 client state machine (RFC 8415 section 18)
+//!
+//! One task runs per upstream interface, talking to servers through a UDP
+//! socket bound to the interface and the All_DHCP_Relay_Agents_and_Servers
+//! group. In stateful mode the task requests addresses and/or a delegated
+//! prefix; in stateless mode only other configuration. It owns the
+//! addresses, unreachable routes and LAN subnets it installs and removes
+//! them again when the lease ends or the client is stopped.
+
+use super::lan_subnets;
+use super::packet::{
+    duid_ll, options, status, Dhcpv6Message, Ia, IaKind, IaPrefix, MessageType, ALL_SERVERS, SERVER_PORT,
+};
+use crate::dhcpv6_client::{
+    DelegatedPrefix, Dhcpv6ClientOptions, Dhcpv6ClientState, Dhcpv6Event, Dhcpv6Lease, LanPrefix,
+};
+use crate::client_task::{self, or_command, Command};
+use crate::error::{NetctlError, NetctlResult};
+use crate::interface::InterfaceController;
+use crate::netns::NetNamespace;
+use crate::routing::{Route, RouteProtocol, RouteType, RoutingController};
+use rand::Rng;
+use std::net::{IpAddr, Ipv6Addr, SocketAddrV6};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::time::{sleep, sleep_until, timeout_at, Instant};
+use tracing::{debug, info, warn};
+
+/// REQ_MAX_RT
+const REQUEST_MAX_RETRANSMIT: Duration = Duration::from_secs(30);
+/// How long to wait for the server to acknowledge a RELEASE
+const RELEASE_TIMEOUT: Duration = Duration::from_secs(1);
+/// IRT_MINIMUM (RFC 8415 section 21.23)
+const MIN_INFORMATION_REFRESH: u32 = 600;
+/// Address of a router within a LAN subnet
+const LAN_ROUTER_HOST: u128 = 1;
+/// Prefix length of LAN subnets
+const LAN_PREFIX_LEN: u8 = 64;
+
+const OPTION_REQUEST: [u16; 3] = [
+    options::DNS_SERVERS,
+    options::DOMAIN_LIST,
+    options::INFORMATION_REFRESH_TIME,
+];
+
+/// Handle to a running DHCPv6 client task
+pub(crate) type ClientHandle = client_task::ClientHandle<Dhcpv6ClientState, Dhcpv6Lease>;
+type ClientStatus = client_task::ClientStatus<Dhcpv6ClientState, Dhcpv6Lease>;
+
+/// Everything a client task needs to know about its interface
+pub(crate) struct NativeClient {
+    pub interface: String,
+    pub ifindex: u32,
+    pub mac: [u8; 6],
+    pub namespace: NetNamespace,
+    pub options: Dhcpv6ClientOptions,
+    /// UDP socket bound to the client port on the interface
+    pub socket: UdpSocket,
+    pub events: broadcast::Sender<Dhcpv6Event>,
+}
+
+impl NativeClient {
+    pub fn spawn(self) -> ClientHandle {
+        ClientHandle::spawn("DHCPv6", Dhcpv6ClientState::Acquiring, |commands, status| {
+            let client = Client {
+                interfaces: InterfaceController::with_namespace(self.namespace.clone()),
+                routing: RoutingController::with_namespace(self.namespace.clone()),
+                duid: duid_ll(&self.mac),
+                iaid: u32::from_be_bytes([self.mac[2], self.mac[3], self.mac[4], self.mac[5]]),
+                config: self,
+                commands,
+                status,
+                lease: None,
+                applied: None,
+            };
+            client.run()
+        })
+    }
+}
+
+/// Bindings and configuration as granted by a REPLY
+#[derive(Debug, Clone)]
+struct Lease {
+    server_id: Vec<u8>,
+    /// Bound IAs; empty in stateless mode
+    ias: Vec<Ia>,
+    dns_servers: Vec<Ipv6Addr>,
+    domain_search: Vec<String>,
+    renewal_time: u32,
+    rebinding_time: u32,
+    valid_lifetime: u32,
+    acquired: Instant,
+    acquired_at: u64,
+}
+
+impl Lease {
+    /// Lease granted by `reply` for the IAs requested with `options`
+    ///
+    /// In stateless mode the lease only carries other configuration and is
+    /// refreshed after the information refresh time.
+    fn from_reply(reply: &Dhcpv6Message, options: &Dhcpv6ClientOptions, iaid: u32) -> Option<Self> {
+        let server_id = reply.server_id()?.to_vec();
+        let mut lease = Self {
+            server_id,
+            ias: Vec::new(),
+            dns_servers: reply.dns_servers(),
+            domain_search: reply.domain_list(),
+            renewal_time: 0,
+            rebinding_time: 0,
+            valid_lifetime: u32::MAX,
+            acquired: Instant::now(),
+            acquired_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        };
+
+        if options.is_stateless() {
+            let refresh = reply.u32_option(options::INFORMATION_REFRESH_TIME)
+                .unwrap_or(options.timing.information_refresh.as_secs().min(u64::from(u32::MAX)) as u32)
+                .max(MIN_INFORMATION_REFRESH);
+            lease.renewal_time = refresh;
+            lease.rebinding_time = refresh;
+            return Some(lease);
+        }
+
+        lease.ias = reply.ias().into_iter()
+            .filter(|ia| ia.iaid == iaid && match ia.kind {
+                IaKind::Na => options.request_address,
+                IaKind::Pd => options.prefix_delegation.is_some(),
+            })
+            .filter(Ia::is_bound)
+            .map(|mut ia| {
+                ia.addresses.retain(|a| a.valid_lifetime > 0 && a.preferred_lifetime <= a.valid_lifetime);
+                ia.prefixes.retain(|p| p.valid_lifetime > 0 && p.preferred_lifetime <= p.valid_lifetime);
+                ia
+            })
+            .filter(|ia| !ia.addresses.is_empty() || !ia.prefixes.is_empty())
+            .collect();
+
+        let lifetimes: Vec<(u32, u32)> = lease.ias.iter()
+            .flat_map(|ia| {
+                ia.addresses.iter().map(|a| (a.preferred_lifetime, a.valid_lifetime))
+                    .chain(ia.prefixes.iter().map(|p| (p.preferred_lifetime, p.valid_lifetime)))
+            })
+            .collect();
+        let preferred = lifetimes.iter().map(|l| l.0).min()?;
+        let valid = lifetimes.iter().map(|l| l.1).min()?;
+
+        // T1 and T2 of zero leave the choice to the client (section 21.4)
+        let t1 = lease.ias.iter().map(|ia| ia.t1).filter(|&t| t > 0).min().unwrap_or(preferred / 2);
+        let t2 = lease.ias.iter().map(|ia| ia.t2).filter(|&t| t > 0).min()
+            .unwrap_or((u64::from(preferred) * 4 / 5) as u32);
+        lease.valid_lifetime = valid;
+        lease.rebinding_time = t2.min(valid);
+        lease.renewal_time = t1.min(lease.rebinding_time);
+        Some(lease)
+    }
+
+    fn at(&self, seconds: u32) -> Instant {
+        self.acquired + Duration::from_secs(u64::from(seconds))
+    }
+
+    fn renewal(&self) -> Instant {
+        self.at(self.renewal_time)
+    }
+
+    fn rebinding(&self) -> Instant {
+        self.at(self.rebinding_time)
+    }
+
+    fn expiry(&self) -> Instant {
+        self.at(self.valid_lifetime)
+    }
+
+    fn addresses(&self) -> Vec<Ipv6Addr> {
+        self.ias.iter().flat_map(|ia| ia.addresses.iter().map(|a| a.address)).collect()
+    }
+
+    fn prefixes(&self) -> Vec<IaPrefix> {
+        self.ias.iter().flat_map(|ia| ia.prefixes.iter().copied()).collect()
+    }
+
+    /// Unreachable routes for the delegated prefixes, so that traffic to
+    /// subnets not assigned to a LAN interface does not loop upstream
+    fn routes(&self) -> Vec<Route> {
+        self.prefixes().iter().map(|prefix| {
+            let mut route = Route::new(IpAddr::V6(prefix.prefix), prefix.prefix_len);
+            route.kind = RouteType::Unreachable;
+            route.protocol = RouteProtocol::Dhcp;
+            route.scope = route.implied_scope();
+            route
+        }).collect()
+    }
+
+    /// Split the delegated prefixes into /64s, one per LAN interface
+    fn lan_prefixes(&self, lan_interfaces: &[String]) -> Vec<LanPrefix> {
+        let mut subnets = Vec::new();
+        for prefix in self.prefixes() {
+            let wanted = lan_interfaces.len().saturating_sub(subnets.len());
+            subnets.extend(lan_subnets(prefix.prefix, prefix.prefix_len, wanted).into_iter().map(|s| (s, prefix)));
+        }
+        if subnets.len() < lan_interfaces.len() {
+            warn!("Delegated prefixes only cover {} of {} LAN interfaces", subnets.len(), lan_interfaces.len());
+        }
+        lan_interfaces.iter().zip(subnets).map(|(interface, (subnet, prefix))| LanPrefix {
+            interface: interface.clone(),
+            prefix: subnet,
+            prefix_len: LAN_PREFIX_LEN,
+            address: Ipv6Addr::from(u128::from(subnet) | LAN_ROUTER_HOST),
+            preferred_lifetime: prefix.preferred_lifetime,
+            valid_lifetime: prefix.valid_lifetime,
+        }).collect()
+    }
+
+    /// IAs to send when extending the lease
+    fn renewal_ias(&self) -> Vec<Ia> {
+        self.ias.iter().map(|ia| Ia { t1: 0, t2: 0, status: None, ..ia.clone() }).collect()
+    }
+
+    fn to_lease(&self, interface: &str, lan_prefixes: Vec<LanPrefix>) -> Dhcpv6Lease {
+        let stateful = !self.ias.is_empty();
+        Dhcpv6Lease {
+            interface: interface.to_string(),
+            addresses: self.addresses(),
+            prefixes: self.prefixes().iter().map(|p| DelegatedPrefix {
+                prefix: p.prefix,
+                prefix_len: p.prefix_len,
+                preferred_lifetime: p.preferred_lifetime,
+                valid_lifetime: p.valid_lifetime,
+            }).collect(),
+            lan_prefixes,
+            dns_servers: self.dns_servers.clone(),
+            domain_search: self.domain_search.clone(),
+            server_id: Some(self.server_id.iter().map(|b| format!("{:02x}", b)).collect()),
+            renewal_time: Some(self.renewal_time),
+            rebinding_time: stateful.then_some(self.rebinding_time),
+            valid_lifetime: stateful.then_some(self.valid_lifetime),
+            acquired_at: Some(self.acquired_at),
+        }
+    }
+}
+
+/// Configuration installed for a lease
+#[derive(Debug, Default)]
+struct Applied {
+    addresses: Vec<Ipv6Addr>,
+    routes: Vec<Route>,
+    lan_prefixes: Vec<LanPrefix>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Phase {
+    Solicit,
+    Information,
+    Bound,
+    Renewing,
+    Rebinding,
+}
+
+enum Step {
+    Next(Phase),
+    Exit,
+}
+
+/// Retransmission timeout with the ±10% randomisation of RFC 8415
+/// section 15
+fn jitter(timeout: Duration) -> Duration {
+    timeout.mul_f64(rand::thread_rng().gen_range(0.9..=1.1))
+}
+
+/// Wait until `deadline` for a message of transaction `xid` addressed to
+/// the client with DUID `duid`
+async fn recv_reply(socket: &UdpSocket, duid: &[u8], xid: u32, deadline: Instant) -> NetctlResult<Option<Dhcpv6Message>> {
+    let mut buf = vec![0u8; 2048];
+    loop {
+        let len = match timeout_at(deadline, socket.recv_from(&mut buf)).await {
+            Err(_) => return Ok(None),
+            Ok(received) => received?.0,
+        };
+        match Dhcpv6Message::decode(&buf[..len]) {
+            Ok(message) if message.transaction_id == xid
+                && message.client_id() == Some(duid)
+                && message.server_id().is_some() => return Ok(Some(message)),
+            Ok(_) => {}
+            Err(e) => debug!("Ignoring DHCPv6 message: {}", e),
+        }
+    }
+}
+
+/// Elapsed time option value: hundredths of a second since `started`
+fn elapsed_time(started: Instant) -> Vec<u8> {
+    let elapsed = (started.elapsed().as_millis() / 10).min(u128::from(u16::MAX)) as u16;
+    elapsed.to_be_bytes().to_vec()
+}
+
+struct Client {
+    config: NativeClient,
+    interfaces: InterfaceController,
+    routing: RoutingController,
+    duid: Vec<u8>,
+    iaid: u32,
+    commands: mpsc::Receiver<Command>,
+    status: watch::Sender<ClientStatus>,
+    lease: Option<Lease>,
+    applied: Option<Applied>,
+}
+
+impl Client {
+    async fn run(mut self) {
+        let initial = if self.config.options.is_stateless() { Phase::Information } else { Phase::Solicit };
+        let mut phase = initial;
+        loop {
+            let step = match phase {
+                Phase::Solicit => self.solicit().await,
+                Phase::Information => self.information().await,
+                Phase::Bound => self.bound().await,
+                Phase::Renewing => self.extend(false).await,
+                Phase::Rebinding => self.extend(true).await,
+            };
+            match step {
+                Ok(Step::Next(next)) => phase = next,
+                Ok(Step::Exit) => break,
+                Err(e) => {
+                    warn!("DHCPv6 client on {}: {}", self.config.interface, e);
+                    self.set_state(Dhcpv6ClientState::Failed);
+                    let retry = sleep(self.config.options.timing.initial_retransmit);
+                    if let Err(command) = or_command(&mut self.commands, retry).await {
+                        if let Step::Exit = self.command(command, initial).await {
+                            break;
+                        }
+                    }
+                    // Keep a lease that is still valid; it expires on its own
+                    phase = if self.lease.is_some() { Phase::Bound } else { initial };
+                }
+            }
+        }
+        debug!("DHCPv6 client on {} exited", self.config.interface);
+    }
+
+    fn interface(&self) -> &str {
+        &self.config.interface
+    }
+
+    fn emit(&self, event: Dhcpv6Event) {
+        // Nobody listening is fine
+        let _ = self.config.events.send(event);
+    }
+
+    fn snapshot(&self) -> Option<Dhcpv6Lease> {
+        let lan_prefixes = self.applied.as_ref().map(|a| a.lan_prefixes.clone()).unwrap_or_default();
+        self.lease.as_ref().map(|lease| lease.to_lease(self.interface(), lan_prefixes))
+    }
+
+    fn set_state(&self, state: Dhcpv6ClientState) {
+        self.status.send_replace(ClientStatus { state, lease: self.snapshot() });
+    }
+
+    fn server_address(&self) -> SocketAddrV6 {
+        SocketAddrV6::new(ALL_SERVERS, SERVER_PORT, 0, self.config.ifindex)
+    }
+
+    /// Send `message` to all servers on the link
+    ///
+    /// Until the link-local address has passed duplicate address detection
+    /// sending fails; that is logged and left to retransmission.
+    async fn send(&self, message: &Dhcpv6Message) {
+        debug!("Sending {} on {}", message.msg_type, self.interface());
+        if let Err(e) = self.config.socket.send_to(&message.encode(), self.server_address()).await {
+            debug!("Failed to send {} on {}: {}", message.msg_type, self.interface(), e);
+        }
+    }
+
+    /// A client message with the options every message carries
+    fn message(&self, kind: MessageType, xid: u32, started: Instant) -> Dhcpv6Message {
+        let mut message = Dhcpv6Message::new(kind, xid);
+        message.set_option(options::CLIENTID, self.duid.clone());
+        message.set_option(options::ELAPSED_TIME, elapsed_time(started));
+        if kind != MessageType::Release {
+            let oro = if kind == MessageType::InformationRequest { &OPTION_REQUEST[..] } else { &OPTION_REQUEST[..2] };
+            message.set_option(options::ORO, oro.iter().flat_map(|code| code.to_be_bytes()).collect());
+        }
+        message
+    }
+
+    /// IAs to solicit, with the prefix length hint if configured
+    fn solicit_ias(&self) -> Vec<Ia> {
+        let mut ias = Vec::new();
+        if self.config.options.request_address {
+            ias.push(Ia::new(IaKind::Na, self.iaid));
+        }
+        if let Some(pd) = &self.config.options.prefix_delegation {
+            let mut ia = Ia::new(IaKind::Pd, self.iaid);
+            if let Some(prefix_len) = pd.prefix_len_hint {
+                ia.prefixes.push(IaPrefix {
+                    prefix: Ipv6Addr::UNSPECIFIED,
+                    prefix_len,
+                    preferred_lifetime: 0,
+                    valid_lifetime: 0,
+                });
+            }
+            ias.push(ia);
+        }
+        ias
+    }
+
+    /// Handle a command that arrived in `phase`
+    async fn command(&mut self, command: Command, phase: Phase) -> Step {
+        match command {
+            // Already on the way to a (new) lease
+            Command::Renew => Step::Next(phase),
+            Command::Stop(done) => {
+                self.unconfigure().await;
+                self.set_state(Dhcpv6ClientState::Stopped);
+                self.emit(Dhcpv6Event::Stopped { interface: self.interface().to_string() });
+                let _ = done.send(());
+                Step::Exit
+            }
+            Command::Release(done) => {
+                match self.lease.take() {
+                    Some(lease) if !lease.ias.is_empty() => {
+                        info!("Releasing DHCPv6 lease on {}", self.interface());
+                        self.send_release(&lease).await;
+                        self.unconfigure().await;
+                        self.emit(Dhcpv6Event::Released { interface: self.interface().to_string() });
+                    }
+                    _ => self.unconfigure().await,
+                }
+                self.set_state(Dhcpv6ClientState::Stopped);
+                let _ = done.send(());
+                Step::Exit
+            }
+        }
+    }
+
+    /// Send a RELEASE and give the server a moment to acknowledge it
+    async fn send_release(&self, lease: &Lease) {
+        let xid = rand::random();
+        let mut release = self.message(MessageType::Release, xid, Instant::now());
+        release.set_option(options::SERVERID, lease.server_id.clone());
+        for ia in lease.renewal_ias() {
+            release.add_ia(&ia);
+        }
+        self.send(&release).await;
+        if let Err(e) = recv_reply(&self.config.socket, &self.duid, release.transaction_id, Instant::now() + RELEASE_TIMEOUT).await {
+            debug!("No reply to RELEASE on {}: {}", self.interface(), e);
+        }
+    }
+
+    /// SOLICIT until advertisements arrive, pick the most preferred server
+    /// and request its bindings
+    async fn solicit(&mut self) -> NetctlResult<Step> {
+        self.set_state(Dhcpv6ClientState::Acquiring);
+        let xid = rand::random();
+        let started = Instant::now();
+        let timing = self.config.options.timing.clone();
+        let mut timeout = timing.initial_retransmit;
+        let mut solicit = self.message(MessageType::Solicit, xid, started);
+        for ia in self.solicit_ias() {
+            solicit.add_ia(&ia);
+        }
+
+        let mut best: Option<Dhcpv6Message> = None;
+        while best.is_none() {
+            solicit.set_option(options::ELAPSED_TIME, elapsed_time(started));
+            self.send(&solicit).await;
+            let deadline = Instant::now() + jitter(timeout);
+            timeout = (timeout * 2).min(timing.max_retransmit);
+
+            // Collect advertisements until the first timeout (section 18.2.1)
+            loop {
+                let reply = match or_command(&mut self.commands, recv_reply(&self.config.socket, &self.duid, solicit.transaction_id, deadline)).await {
+                    Err(command) => return Ok(self.command(command, Phase::Solicit).await),
+                    Ok(reply) => reply?,
+                };
+                let Some(advertise) = reply else { break };
+                if advertise.msg_type != MessageType::Advertise || !advertise.ias().iter().any(Ia::is_bound) {
+                    continue;
+                }
+                let preference = advertise.preference();
+                if best.as_ref().is_none_or(|best| preference > best.preference()) {
+                    best = Some(advertise);
+                }
+                if preference == u8::MAX {
+                    break;
+                }
+            }
+        }
+        let Some(advertise) = best else { return Ok(Step::Next(Phase::Solicit)) };
+        info!("DHCPv6 ADVERTISE with preference {} on {}", advertise.preference(), self.interface());
+
+        let mut request = self.message(MessageType::Request, rand::random(), Instant::now());
+        request.set_option(options::SERVERID, advertise.server_id().unwrap_or_default().to_vec());
+        let offered = advertise.ias();
+        for mut ia in self.solicit_ias() {
+            // Ask for what the server offered
+            if let Some(offer) = offered.iter().find(|o| o.kind == ia.kind && o.iaid == ia.iaid && o.is_bound()) {
+                ia.addresses = offer.addresses.clone();
+                ia.prefixes = offer.prefixes.clone();
+            }
+            request.add_ia(&ia);
+        }
+        self.request(request).await
+    }
+
+    /// REQUEST the advertised bindings until the server replies
+    async fn request(&mut self, mut request: Dhcpv6Message) -> NetctlResult<Step> {
+        let started = Instant::now();
+        let timing = self.config.options.timing.clone();
+        let mut timeout = timing.initial_retransmit;
+
+        for _ in 0..timing.request_attempts {
+            request.set_option(options::ELAPSED_TIME, elapsed_time(started));
+            self.send(&request).await;
+            let deadline = Instant::now() + jitter(timeout);
+            timeout = (timeout * 2).min(REQUEST_MAX_RETRANSMIT);
+
+            loop {
+                let reply = match or_command(&mut self.commands, recv_reply(&self.config.socket, &self.duid, request.transaction_id, deadline)).await {
+                    Err(command) => return Ok(self.command(command, Phase::Solicit).await),
+                    Ok(reply) => reply?,
+                };
+                let Some(reply) = reply else { break };
+                if reply.msg_type == MessageType::Reply {
+                    return self.bind(&reply).await;
+                }
+            }
+        }
+
+        debug!("No answer to DHCPv6 REQUEST on {}", self.interface());
+        Ok(Step::Next(Phase::Solicit))
+    }
+
+    /// Configure the bindings of a REPLY to a REQUEST
+    async fn bind(&mut self, reply: &Dhcpv6Message) -> NetctlResult<Step> {
+        if let Some(status) = reply.status().filter(|s| !s.is_success()) {
+            return Err(NetctlError::ServiceError(format!(
+                "DHCPv6 server refused request with status {}: {}", status.code, status.message
+            )));
+        }
+        let Some(lease) = Lease::from_reply(reply, &self.config.options, self.iaid) else {
+            return Err(NetctlError::ServiceError("DHCPv6 server granted no bindings".to_string()));
+        };
+
+        info!("DHCPv6 bound on {}: addresses {:?}, prefixes {:?}", self.interface(),
+            lease.addresses(), lease.prefixes().iter().map(|p| format!("{}/{}", p.prefix, p.prefix_len)).collect::<Vec<_>>());
+        self.lease = Some(lease.clone());
+        self.configure(&lease).await?;
+        self.set_state(Dhcpv6ClientState::Bound);
+        if let Some(lease) = self.snapshot() {
+            self.emit(Dhcpv6Event::Bound(lease));
+        }
+        Ok(Step::Next(Phase::Bound))
+    }
+
+    /// INFORMATION-REQUEST for stateless configuration
+    async fn information(&mut self) -> NetctlResult<Step> {
+        if self.lease.is_none() {
+            self.set_state(Dhcpv6ClientState::Acquiring);
+        }
+        let xid = rand::random();
+        let started = Instant::now();
+        let timing = self.config.options.timing.clone();
+        let mut timeout = timing.initial_retransmit;
+
+        loop {
+            let request = self.message(MessageType::InformationRequest, xid, started);
+            self.send(&request).await;
+            let deadline = Instant::now() + jitter(timeout);
+            timeout = (timeout * 2).min(timing.max_retransmit);
+
+            loop {
+                let reply = match or_command(&mut self.commands, recv_reply(&self.config.socket, &self.duid, request.transaction_id, deadline)).await {
+                    Err(Command::Renew) => continue,
+                    Err(command) => return Ok(self.command(command, Phase::Information).await),
+                    Ok(reply) => reply?,
+                };
+                let Some(reply) = reply else { break };
+                if reply.msg_type != MessageType::Reply {
+                    continue;
+                }
+                let Some(lease) = Lease::from_reply(&reply, &self.config.options, self.iaid) else { continue };
+                let refreshed = self.lease.is_some();
+                debug!("DHCPv6 configuration on {}: DNS {:?}", self.interface(), lease.dns_servers);
+                self.lease = Some(lease);
+                self.set_state(Dhcpv6ClientState::Bound);
+                if let Some(lease) = self.snapshot() {
+                    self.emit(if refreshed { Dhcpv6Event::Renewed(lease) } else { Dhcpv6Event::Bound(lease) });
+                }
+                return Ok(Step::Next(Phase::Bound));
+            }
+        }
+    }
+
+    /// BOUND: wait for T1 (or the refresh time) or a renew request
+    async fn bound(&mut self) -> NetctlResult<Step> {
+        let Some(lease) = &self.lease else {
+            return Ok(Step::Next(Phase::Solicit));
+        };
+        let next = if lease.ias.is_empty() { Phase::Information } else { Phase::Renewing };
+        if Instant::now() >= lease.expiry() {
+            return Ok(self.expire().await);
+        }
+        match or_command(&mut self.commands, sleep_until(lease.renewal())).await {
+            Ok(()) | Err(Command::Renew) => Ok(Step::Next(next)),
+            Err(command) => Ok(self.command(command, Phase::Bound).await),
+        }
+    }
+
+    /// RENEW with the server that granted the lease until T2, then REBIND
+    /// with any server until the bindings expire
+    async fn extend(&mut self, rebinding: bool) -> NetctlResult<Step> {
+        let Some(lease) = self.lease.clone() else {
+            return Ok(Step::Next(Phase::Solicit));
+        };
+        let (phase, state, kind, until) = if rebinding {
+            (Phase::Rebinding, Dhcpv6ClientState::Rebinding, MessageType::Rebind, lease.expiry())
+        } else {
+            (Phase::Renewing, Dhcpv6ClientState::Renewing, MessageType::Renew, lease.rebinding())
+        };
+        self.set_state(state);
+        debug!("Sending DHCPv6 {} on {}", kind, self.interface());
+
+        let xid = rand::random();
+        let started = Instant::now();
+        let timing = self.config.options.timing.clone();
+        let mut timeout = timing.renew_retransmit;
+
+        loop {
+            let now = Instant::now();
+            if now >= until {
+                return Ok(if rebinding { self.expire().await } else { Step::Next(Phase::Rebinding) });
+            }
+
+            let mut message = self.message(kind, xid, started);
+            if !rebinding {
+                message.set_option(options::SERVERID, lease.server_id.clone());
+            }
+            for ia in lease.renewal_ias() {
+                message.add_ia(&ia);
+            }
+            self.send(&message).await;
+            let deadline = (now + jitter(timeout)).min(until);
+            timeout = (timeout * 2).min(timing.max_renew_retransmit);
+
+            loop {
+                let reply = match or_command(&mut self.commands, recv_reply(&self.config.socket, &self.duid, message.transaction_id, deadline)).await {
+                    Err(Command::Renew) => continue,
+                    Err(command) => return Ok(self.command(command, phase).await),
+                    Ok(reply) => reply?,
+                };
+                let Some(reply) = reply else { break };
+                if reply.msg_type != MessageType::Reply {
+                    continue;
+                }
+                let no_binding = reply.ias().iter()
+                    .any(|ia| ia.status.as_ref().is_some_and(|s| s.code == status::NO_BINDING));
+                if no_binding {
+                    info!("DHCPv6 server on {} has no binding for the lease", self.interface());
+                    return Ok(self.expire().await);
+                }
+                return self.extended(&reply).await;
+            }
+        }
+    }
+
+    /// Apply the bindings from the REPLY to a RENEW or REBIND
+    async fn extended(&mut self, reply: &Dhcpv6Message) -> NetctlResult<Step> {
+        let Some(lease) = Lease::from_reply(reply, &self.config.options, self.iaid) else {
+            info!("DHCPv6 server on {} did not extend any binding", self.interface());
+            return Ok(self.expire().await);
+        };
+        debug!("DHCPv6 lease on {} extended, valid for {}s", self.interface(), lease.valid_lifetime);
+        self.lease = Some(lease.clone());
+        self.configure(&lease).await?;
+        self.set_state(Dhcpv6ClientState::Bound);
+        if let Some(lease) = self.snapshot() {
+            self.emit(Dhcpv6Event::Renewed(lease));
+        }
+        Ok(Step::Next(Phase::Bound))
+    }
+
+    /// Drop a lease that can no longer be used
+    async fn expire(&mut self) -> Step {
+        if self.lease.take().is_some() {
+            info!("DHCPv6 lease on {} ended", self.interface());
+        }
+        self.unconfigure().await;
+        self.set_state(Dhcpv6ClientState::Acquiring);
+        self.emit(Dhcpv6Event::Expired { interface: self.interface().to_string() });
+        Step::Next(Phase::Solicit)
+    }
+
+    /// Install the addresses, prefix routes and LAN subnets of `lease`,
+    /// only touching what changed since the last call
+    async fn configure(&mut self, lease: &Lease) -> NetctlResult<()> {
+        let interface = self.config.interface.clone();
+        let mut applied = self.applied.take().unwrap_or_default();
+        let result = self.configure_into(&interface, lease, &mut applied).await;
+        self.applied = Some(applied);
+        result
+    }
+
+    async fn configure_into(&self, interface: &str, lease: &Lease, applied: &mut Applied) -> NetctlResult<()> {
+        let addresses = lease.addresses();
+        for address in applied.addresses.iter().filter(|a| !addresses.contains(a)) {
+            self.remove_address(interface, *address, 128).await;
+        }
+        applied.addresses.retain(|a| addresses.contains(a));
+        for address in addresses {
+            if applied.addresses.contains(&address) {
+                continue;
+            }
+            match self.interfaces.add_ip(interface, &address.to_string(), 128).await {
+                Ok(()) | Err(NetctlError::AlreadyExists(_)) => applied.addresses.push(address),
+                Err(e) => return Err(e),
+            }
+        }
+
+        let routes = lease.routes();
+        for route in applied.routes.iter().filter(|r| !routes.contains(r)) {
+            if let Err(e) = self.routing.delete_route(route).await {
+                debug!("Failed to delete route {}: {}", route, e);
+            }
+        }
+        applied.routes.retain(|r| routes.contains(r));
+        for route in routes {
+            if applied.routes.contains(&route) {
+                continue;
+            }
+            match self.routing.add_route(&route).await {
+                Ok(()) => applied.routes.push(route),
+                Err(NetctlError::AlreadyExists(_)) => debug!("Route already exists: {}", route),
+                Err(e) => warn!("Failed to add route {}: {}", route, e),
+            }
+        }
+
+        let lan_interfaces = self.config.options.prefix_delegation.as_ref()
+            .map(|pd| pd.lan_interfaces.as_slice())
+            .unwrap_or_default();
+        let lan_prefixes = lease.lan_prefixes(lan_interfaces);
+        let same = |a: &LanPrefix, b: &LanPrefix| a.interface == b.interface && a.address == b.address;
+        for old in applied.lan_prefixes.iter().filter(|old| !lan_prefixes.iter().any(|new| same(old, new))) {
+            self.remove_address(&old.interface, old.address, old.prefix_len).await;
+        }
+        applied.lan_prefixes.retain(|old| lan_prefixes.iter().any(|new| same(old, new)));
+        for lan in lan_prefixes {
+            if let Some(existing) = applied.lan_prefixes.iter_mut().find(|old| same(old, &lan)) {
+                *existing = lan;
+                continue;
+            }
+            match self.interfaces.add_ip(&lan.interface, &lan.address.to_string(), lan.prefix_len).await {
+                Ok(()) | Err(NetctlError::AlreadyExists(_)) => {
+                    info!("Assigned {}/{} to {}", lan.address, lan.prefix_len, lan.interface);
+                    applied.lan_prefixes.push(lan);
+                }
+                Err(e) => warn!("Failed to assign {}/{} to {}: {}", lan.address, lan.prefix_len, lan.interface, e),
+            }
+        }
+        Ok(())
+    }
+
+    async fn remove_address(&self, interface: &str, address: Ipv6Addr, prefix_len: u8) {
+        if let Err(e) = self.interfaces.del_ip(interface, &address.to_string(), prefix_len).await {
+            debug!("Failed to remove {}/{} from {}: {}", address, prefix_len, interface, e);
+        }
+    }
+
+    /// Remove everything `configure` installed
+    async fn unconfigure(&mut self) {
+        let Some(applied) = self.applied.take() else {
+            return;
+        };
+        for lan in &applied.lan_prefixes {
+            info!("Withdrawing {}/{} from {}", lan.address, lan.prefix_len, lan.interface);
+            self.remove_address(&lan.interface, lan.address, lan.prefix_len).await;
+        }
+        for route in &applied.routes {
+            if let Err(e) = self.routing.delete_route(route).await {
+                debug!("Failed to delete route {}: {}", route, e);
+            }
+        }
+        for address in &applied.addresses {
+            self.remove_address(&self.config.interface, *address, 128).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dhcpv6::packet::IaAddress;
+
+    #[test]
+    fn test_lease_from_reply() {
+        let options = Dhcpv6ClientOptions::stateful().with_prefix_delegation(Some(56), vec!["lan0".to_string(), "lan1".to_string()]);
+        let mut reply = Dhcpv6Message::new(MessageType::Reply, 1);
+        reply.set_option(options::SERVERID, vec![0, 3, 0, 1, 2, 0, 0, 0, 0, 2]);
+        reply.add_ia(&Ia {
+            addresses: vec![IaAddress { address: "2001:db8::100".parse().unwrap(), preferred_lifetime: 1000, valid_lifetime: 2000 }],
+            ..Ia::new(IaKind::Na, 7)
+        });
+        reply.add_ia(&Ia {
+            prefixes: vec![IaPrefix { prefix: "2001:db8:ff00::".parse().unwrap(), prefix_len: 63, preferred_lifetime: 1500, valid_lifetime: 3000 }],
+            ..Ia::new(IaKind::Pd, 7)
+        });
+
+        // IAs of other IAIDs are not ours
+        assert!(Lease::from_reply(&reply, &options, 8).is_none());
+
+        let lease = Lease::from_reply(&reply, &options, 7).unwrap();
+        assert_eq!((lease.renewal_time, lease.rebinding_time, lease.valid_lifetime), (500, 800, 2000));
+        assert_eq!(lease.addresses(), vec!["2001:db8::100".parse::<Ipv6Addr>().unwrap()]);
+
+        let routes = lease.routes();
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].kind, RouteType::Unreachable);
+        assert_eq!(routes[0].prefix_len, 63);
+
+        let lan = lease.lan_prefixes(&["lan0".to_string(), "lan1".to_string(), "lan2".to_string()]);
+        assert_eq!(lan.len(), 2);
+        assert_eq!(lan[0].address, "2001:db8:ff00::1".parse::<Ipv6Addr>().unwrap());
+        assert_eq!((lan[1].interface.as_str(), lan[1].prefix), ("lan1", "2001:db8:ff00:1::".parse().unwrap()));
+
+        // Only prefix delegation was asked for
+        let pd_only = Dhcpv6ClientOptions::default().with_prefix_delegation(None, Vec::new());
+        let lease = Lease::from_reply(&reply, &pd_only, 7).unwrap();
+        assert!(lease.addresses().is_empty());
+        assert_eq!(lease.valid_lifetime, 3000);
+
+        // Stateless configuration is refreshed no more often than IRT_MINIMUM
+        reply.set_option(options::INFORMATION_REFRESH_TIME, 60u32.to_be_bytes().to_vec());
+        let lease = Lease::from_reply(&reply, &Dhcpv6ClientOptions::stateless(), 7).unwrap();
+        assert!(lease.ias.is_empty());
+        assert_eq!(lease.renewal_time, MIN_INFORMATION_REFRESH);
+    }
+}
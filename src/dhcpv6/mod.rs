@@ -0,0 +1,47 @@
+//! Built-in DHCPv6 client
+//!
+//! [`packet`] encodes and decodes DHCPv6 messages; the client state machine
+//! is driven through [`crate::dhcpv6_client::Dhcpv6ClientController`].
+
+pub mod packet;
+pub(crate) mod client;
+
+pub use packet::{Dhcpv6Message, Ia, IaAddress, IaKind, IaPrefix, MessageType};
+
+use std::net::Ipv6Addr;
+
+/// The first `count` /64 subnets of a delegated prefix, in order
+///
+/// Fewer are returned if the prefix is too small; a prefix longer than
+/// /64 cannot be split at all.
+pub fn lan_subnets(prefix: Ipv6Addr, prefix_len: u8, count: usize) -> Vec<Ipv6Addr> {
+    if prefix_len > 64 {
+        return Vec::new();
+    }
+    let available = 1u128.checked_shl(u32::from(64 - prefix_len)).unwrap_or(u128::MAX);
+    let mask = u128::MAX.checked_shl(u32::from(128 - prefix_len)).unwrap_or(0);
+    let base = u128::from(prefix) & mask;
+    (0..available.min(count as u128))
+        .map(|subnet| Ipv6Addr::from(base | (subnet << 64)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lan_subnets() {
+        let prefix: Ipv6Addr = "2001:db8:ff00::".parse().unwrap();
+        assert_eq!(lan_subnets(prefix, 56, 3), vec![
+            "2001:db8:ff00::".parse::<Ipv6Addr>().unwrap(),
+            "2001:db8:ff00:1::".parse().unwrap(),
+            "2001:db8:ff00:2::".parse().unwrap(),
+        ]);
+        // Host bits of the prefix are ignored
+        assert_eq!(lan_subnets("2001:db8:ff00:ab::1".parse().unwrap(), 62, 8).len(), 4);
+        assert_eq!(lan_subnets("2001:db8:ff00:ab::1".parse().unwrap(), 62, 1)[0], "2001:db8:ff00:a8::".parse::<Ipv6Addr>().unwrap());
+        assert_eq!(lan_subnets(prefix, 64, 2).len(), 1);
+        assert!(lan_subnets(prefix, 72, 1).is_empty());
+    }
+}
@@ -0,0 +1,436 @@
+//! DHCPv6 message encoding and decoding (RFC 8415)
+
+use crate::dhcpv4::packet::{decode_domain_list, encode_domain_list};
+use crate::error::{NetctlError, NetctlResult};
+use std::net::Ipv6Addr;
+
+/// UDP port of DHCPv6 clients
+pub const CLIENT_PORT: u16 = 546;
+/// UDP port of DHCPv6 servers and relays
+pub const SERVER_PORT: u16 = 547;
+/// All_DHCP_Relay_Agents_and_Servers
+pub const ALL_SERVERS: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 1, 2);
+
+/// Option codes
+pub mod options {
+    pub const CLIENTID: u16 = 1;
+    pub const SERVERID: u16 = 2;
+    pub const IA_NA: u16 = 3;
+    pub const IAADDR: u16 = 5;
+    pub const ORO: u16 = 6;
+    pub const PREFERENCE: u16 = 7;
+    pub const ELAPSED_TIME: u16 = 8;
+    pub const STATUS_CODE: u16 = 13;
+    pub const RAPID_COMMIT: u16 = 14;
+    pub const DNS_SERVERS: u16 = 23;
+    pub const DOMAIN_LIST: u16 = 24;
+    pub const IA_PD: u16 = 25;
+    pub const IAPREFIX: u16 = 26;
+    pub const INFORMATION_REFRESH_TIME: u16 = 32;
+    pub const SOL_MAX_RT: u16 = 82;
+}
+
+/// Status codes (option 13)
+pub mod status {
+    pub const SUCCESS: u16 = 0;
+    pub const UNSPEC_FAIL: u16 = 1;
+    pub const NO_ADDRS_AVAIL: u16 = 2;
+    pub const NO_BINDING: u16 = 3;
+    pub const NOT_ON_LINK: u16 = 4;
+    pub const USE_MULTICAST: u16 = 5;
+    pub const NO_PREFIX_AVAIL: u16 = 6;
+}
+
+/// DHCPv6 message type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageType {
+    Solicit = 1,
+    Advertise = 2,
+    Request = 3,
+    Confirm = 4,
+    Renew = 5,
+    Rebind = 6,
+    Reply = 7,
+    Release = 8,
+    Decline = 9,
+    Reconfigure = 10,
+    InformationRequest = 11,
+}
+
+impl MessageType {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            1 => Self::Solicit,
+            2 => Self::Advertise,
+            3 => Self::Request,
+            4 => Self::Confirm,
+            5 => Self::Renew,
+            6 => Self::Rebind,
+            7 => Self::Reply,
+            8 => Self::Release,
+            9 => Self::Decline,
+            10 => Self::Reconfigure,
+            11 => Self::InformationRequest,
+            _ => return None,
+        })
+    }
+}
+
+impl std::fmt::Display for MessageType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Solicit => "SOLICIT",
+            Self::Advertise => "ADVERTISE",
+            Self::Request => "REQUEST",
+            Self::Confirm => "CONFIRM",
+            Self::Renew => "RENEW",
+            Self::Rebind => "REBIND",
+            Self::Reply => "REPLY",
+            Self::Release => "RELEASE",
+            Self::Decline => "DECLINE",
+            Self::Reconfigure => "RECONFIGURE",
+            Self::InformationRequest => "INFORMATION-REQUEST",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Status code option: code and message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusCode {
+    pub code: u16,
+    pub message: String,
+}
+
+impl StatusCode {
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = self.code.to_be_bytes().to_vec();
+        buf.extend_from_slice(self.message.as_bytes());
+        buf
+    }
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        let code = u16::from_be_bytes(data.get(..2)?.try_into().ok()?);
+        Some(Self { code, message: String::from_utf8_lossy(&data[2..]).into_owned() })
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.code == status::SUCCESS
+    }
+}
+
+/// An address in an IA_NA
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IaAddress {
+    pub address: Ipv6Addr,
+    pub preferred_lifetime: u32,
+    pub valid_lifetime: u32,
+}
+
+/// A prefix in an IA_PD
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IaPrefix {
+    pub prefix: Ipv6Addr,
+    pub prefix_len: u8,
+    pub preferred_lifetime: u32,
+    pub valid_lifetime: u32,
+}
+
+/// Kind of identity association
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IaKind {
+    /// Non-temporary addresses
+    Na,
+    /// Delegated prefixes
+    Pd,
+}
+
+/// An identity association for addresses (IA_NA) or prefixes (IA_PD)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ia {
+    pub kind: IaKind,
+    pub iaid: u32,
+    pub t1: u32,
+    pub t2: u32,
+    pub addresses: Vec<IaAddress>,
+    pub prefixes: Vec<IaPrefix>,
+    pub status: Option<StatusCode>,
+}
+
+impl Ia {
+    /// An empty IA, as sent in a SOLICIT
+    pub fn new(kind: IaKind, iaid: u32) -> Self {
+        Self { kind, iaid, t1: 0, t2: 0, addresses: Vec::new(), prefixes: Vec::new(), status: None }
+    }
+
+    pub fn code(&self) -> u16 {
+        match self.kind {
+            IaKind::Na => options::IA_NA,
+            IaKind::Pd => options::IA_PD,
+        }
+    }
+
+    /// Whether the IA carries a binding the server did not refuse
+    pub fn is_bound(&self) -> bool {
+        self.status.as_ref().is_none_or(StatusCode::is_success)
+            && match self.kind {
+                IaKind::Na => self.addresses.iter().any(|a| a.valid_lifetime > 0),
+                IaKind::Pd => self.prefixes.iter().any(|p| p.valid_lifetime > 0),
+            }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&self.iaid.to_be_bytes());
+        buf.extend_from_slice(&self.t1.to_be_bytes());
+        buf.extend_from_slice(&self.t2.to_be_bytes());
+        for address in &self.addresses {
+            let mut value = address.address.octets().to_vec();
+            value.extend_from_slice(&address.preferred_lifetime.to_be_bytes());
+            value.extend_from_slice(&address.valid_lifetime.to_be_bytes());
+            push_option(&mut buf, options::IAADDR, &value);
+        }
+        for prefix in &self.prefixes {
+            let mut value = prefix.preferred_lifetime.to_be_bytes().to_vec();
+            value.extend_from_slice(&prefix.valid_lifetime.to_be_bytes());
+            value.push(prefix.prefix_len);
+            value.extend_from_slice(&prefix.prefix.octets());
+            push_option(&mut buf, options::IAPREFIX, &value);
+        }
+        if let Some(status) = &self.status {
+            push_option(&mut buf, options::STATUS_CODE, &status.encode());
+        }
+        buf
+    }
+
+    fn decode(kind: IaKind, data: &[u8]) -> Option<Self> {
+        let word = |at: usize| data.get(at..at + 4).map(|w| u32::from_be_bytes([w[0], w[1], w[2], w[3]]));
+        let mut ia = Self::new(kind, word(0)?);
+        ia.t1 = word(4)?;
+        ia.t2 = word(8)?;
+
+        for (code, value) in decode_options(&data[12..])? {
+            match code {
+                options::IAADDR if value.len() >= 24 => {
+                    let address: [u8; 16] = value[..16].try_into().ok()?;
+                    let lifetime = |at: usize| u32::from_be_bytes([value[at], value[at + 1], value[at + 2], value[at + 3]]);
+                    ia.addresses.push(IaAddress {
+                        address: Ipv6Addr::from(address),
+                        preferred_lifetime: lifetime(16),
+                        valid_lifetime: lifetime(20),
+                    });
+                }
+                options::IAPREFIX if value.len() >= 25 => {
+                    let prefix: [u8; 16] = value[9..25].try_into().ok()?;
+                    let lifetime = |at: usize| u32::from_be_bytes([value[at], value[at + 1], value[at + 2], value[at + 3]]);
+                    ia.prefixes.push(IaPrefix {
+                        prefix: Ipv6Addr::from(prefix),
+                        prefix_len: value[8].min(128),
+                        preferred_lifetime: lifetime(0),
+                        valid_lifetime: lifetime(4),
+                    });
+                }
+                options::STATUS_CODE => ia.status = StatusCode::decode(&value),
+                _ => {}
+            }
+        }
+        Some(ia)
+    }
+}
+
+/// A DHCPv6 client/server message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dhcpv6Message {
+    pub msg_type: MessageType,
+    /// 24-bit transaction ID
+    pub transaction_id: u32,
+    /// Options in order
+    pub options: Vec<(u16, Vec<u8>)>,
+}
+
+impl Dhcpv6Message {
+    pub fn new(msg_type: MessageType, transaction_id: u32) -> Self {
+        Self { msg_type, transaction_id: transaction_id & 0x00ff_ffff, options: Vec::new() }
+    }
+
+    pub fn option(&self, code: u16) -> Option<&[u8]> {
+        self.options.iter()
+            .find(|(c, _)| *c == code)
+            .map(|(_, value)| value.as_slice())
+    }
+
+    /// Set an option, replacing any previous value
+    pub fn set_option(&mut self, code: u16, value: Vec<u8>) {
+        match self.options.iter_mut().find(|(c, _)| *c == code) {
+            Some(option) => option.1 = value,
+            None => self.options.push((code, value)),
+        }
+    }
+
+    /// Add an IA; a message may carry several
+    pub fn add_ia(&mut self, ia: &Ia) {
+        self.options.push((ia.code(), ia.encode()));
+    }
+
+    pub fn client_id(&self) -> Option<&[u8]> {
+        self.option(options::CLIENTID)
+    }
+
+    pub fn server_id(&self) -> Option<&[u8]> {
+        self.option(options::SERVERID)
+    }
+
+    /// Top-level status code
+    pub fn status(&self) -> Option<StatusCode> {
+        self.option(options::STATUS_CODE).and_then(StatusCode::decode)
+    }
+
+    /// Server preference (option 7), 0 if absent
+    pub fn preference(&self) -> u8 {
+        self.option(options::PREFERENCE).and_then(|v| v.first().copied()).unwrap_or(0)
+    }
+
+    pub fn ias(&self) -> Vec<Ia> {
+        self.options.iter()
+            .filter_map(|(code, value)| match *code {
+                options::IA_NA => Ia::decode(IaKind::Na, value),
+                options::IA_PD => Ia::decode(IaKind::Pd, value),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn dns_servers(&self) -> Vec<Ipv6Addr> {
+        self.option(options::DNS_SERVERS)
+            .map(|value| value.chunks_exact(16)
+                .filter_map(|ip| <[u8; 16]>::try_from(ip).ok())
+                .map(Ipv6Addr::from)
+                .collect())
+            .unwrap_or_default()
+    }
+
+    pub fn set_dns_servers(&mut self, servers: &[Ipv6Addr]) {
+        self.set_option(options::DNS_SERVERS, servers.iter().flat_map(|ip| ip.octets()).collect());
+    }
+
+    pub fn domain_list(&self) -> Vec<String> {
+        self.option(options::DOMAIN_LIST).map(decode_domain_list).unwrap_or_default()
+    }
+
+    pub fn set_domain_list(&mut self, domains: &[String]) {
+        self.set_option(options::DOMAIN_LIST, encode_domain_list(domains));
+    }
+
+    pub fn u32_option(&self, code: u16) -> Option<u32> {
+        let value: [u8; 4] = self.option(code)?.try_into().ok()?;
+        Some(u32::from_be_bytes(value))
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.push(self.msg_type as u8);
+        buf.extend_from_slice(&self.transaction_id.to_be_bytes()[1..]);
+        for (code, value) in &self.options {
+            push_option(&mut buf, *code, value);
+        }
+        buf
+    }
+
+    pub fn decode(buf: &[u8]) -> NetctlResult<Self> {
+        let invalid = |what: &str| NetctlError::InvalidParameter(format!("Malformed DHCPv6 message: {}", what));
+        if buf.len() < 4 {
+            return Err(invalid("too short"));
+        }
+        let msg_type = MessageType::from_u8(buf[0]).ok_or_else(|| invalid("unknown message type"))?;
+        Ok(Self {
+            msg_type,
+            transaction_id: u32::from_be_bytes([0, buf[1], buf[2], buf[3]]),
+            options: decode_options(&buf[4..]).ok_or_else(|| invalid("truncated option"))?,
+        })
+    }
+}
+
+fn push_option(buf: &mut Vec<u8>, code: u16, value: &[u8]) {
+    buf.extend_from_slice(&code.to_be_bytes());
+    buf.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    buf.extend_from_slice(value);
+}
+
+fn decode_options(mut data: &[u8]) -> Option<Vec<(u16, Vec<u8>)>> {
+    let mut options = Vec::new();
+    while !data.is_empty() {
+        let header = data.get(..4)?;
+        let code = u16::from_be_bytes([header[0], header[1]]);
+        let len = usize::from(u16::from_be_bytes([header[2], header[3]]));
+        options.push((code, data.get(4..4 + len)?.to_vec()));
+        data = &data[4 + len..];
+    }
+    Some(options)
+}
+
+/// DUID based on the link-layer address (DUID-LL, RFC 8415 section 11.4)
+pub fn duid_ll(mac: &[u8; 6]) -> Vec<u8> {
+    let mut duid = vec![0, 3, 0, 1]; // DUID-LL, hardware type Ethernet
+    duid.extend_from_slice(mac);
+    duid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_roundtrip() {
+        let mut reply = Dhcpv6Message::new(MessageType::Reply, 0xabcdef);
+        reply.set_option(options::CLIENTID, duid_ll(&[2, 0, 0, 0, 0, 1]));
+        reply.set_option(options::SERVERID, vec![0, 3, 0, 1, 2, 0, 0, 0, 0, 2]);
+        reply.add_ia(&Ia {
+            kind: IaKind::Na,
+            iaid: 1,
+            t1: 1800,
+            t2: 2880,
+            addresses: vec![IaAddress {
+                address: "2001:db8::100".parse().unwrap(),
+                preferred_lifetime: 3600,
+                valid_lifetime: 7200,
+            }],
+            prefixes: Vec::new(),
+            status: None,
+        });
+        reply.add_ia(&Ia {
+            kind: IaKind::Pd,
+            iaid: 1,
+            t1: 0,
+            t2: 0,
+            addresses: Vec::new(),
+            prefixes: vec![IaPrefix {
+                prefix: "2001:db8:ff00::".parse().unwrap(),
+                prefix_len: 56,
+                preferred_lifetime: 3600,
+                valid_lifetime: 7200,
+            }],
+            status: Some(StatusCode { code: status::SUCCESS, message: "ok".to_string() }),
+        });
+        reply.set_dns_servers(&["2001:db8::53".parse().unwrap()]);
+        reply.set_domain_list(&["corp.example".to_string()]);
+
+        let encoded = reply.encode();
+        assert_eq!(&encoded[..4], &[7, 0xab, 0xcd, 0xef]);
+        let decoded = Dhcpv6Message::decode(&encoded).unwrap();
+        assert_eq!(decoded, reply);
+
+        let ias = decoded.ias();
+        assert_eq!(ias.len(), 2);
+        assert!(ias.iter().all(Ia::is_bound));
+        assert_eq!(ias[1].prefixes[0].prefix_len, 56);
+        assert_eq!(decoded.dns_servers(), vec!["2001:db8::53".parse::<Ipv6Addr>().unwrap()]);
+        assert_eq!(decoded.domain_list(), vec!["corp.example"]);
+
+        let mut refused = Ia::new(IaKind::Pd, 1);
+        refused.status = Some(StatusCode { code: status::NO_PREFIX_AVAIL, message: String::new() });
+        assert!(!refused.is_bound());
+
+        assert!(Dhcpv6Message::decode(&encoded[..encoded.len() - 1]).is_err());
+        assert!(Dhcpv6Message::decode(&[0, 0, 0, 0]).is_err());
+    }
+}
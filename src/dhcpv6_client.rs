@@ -0,0 +1,351 @@
+//! DHCPv6 client management
+//!
+//! The built-in DHCPv6 client ([`crate::dhcpv6`]) requests addresses
+//! (stateful), only other configuration such as DNS servers (stateless),
+//! and delegated prefixes (IA_PD). A delegated prefix is split into /64s
+//! that are assigned to the LAN interfaces given in the options and
+//! withdrawn again when the lease ends. Lease changes are published as
+//! [`Dhcpv6Event`]s; DNS servers are left to the subscriber.
+
+use crate::dhcpv6::client::{ClientHandle, NativeClient};
+use crate::error::{NetctlError, NetctlResult};
+use crate::interface::InterfaceController;
+use crate::netns::NetNamespace;
+use crate::rawsock;
+use crate::validation;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::Ipv6Addr;
+use std::time::Duration;
+use tokio::sync::{broadcast, Mutex};
+use tracing::{debug, info};
+
+/// DHCPv6 client state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Dhcpv6ClientState {
+    /// Not running
+    Stopped,
+    /// Soliciting/requesting
+    Acquiring,
+    /// Lease acquired, or configuration received in stateless mode
+    Bound,
+    /// Renewing lease
+    Renewing,
+    /// Rebinding lease
+    Rebinding,
+    /// Failed to acquire lease
+    Failed,
+}
+
+/// A prefix delegated by the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DelegatedPrefix {
+    pub prefix: Ipv6Addr,
+    pub prefix_len: u8,
+    pub preferred_lifetime: u32,
+    pub valid_lifetime: u32,
+}
+
+/// A /64 of a delegated prefix assigned to a LAN interface
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LanPrefix {
+    pub interface: String,
+    pub prefix: Ipv6Addr,
+    pub prefix_len: u8,
+    /// Router address configured on the interface
+    pub address: Ipv6Addr,
+    pub preferred_lifetime: u32,
+    pub valid_lifetime: u32,
+}
+
+/// DHCPv6 lease information
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Dhcpv6Lease {
+    /// Interface name
+    pub interface: String,
+    /// Assigned addresses (IA_NA)
+    pub addresses: Vec<Ipv6Addr>,
+    /// Delegated prefixes (IA_PD)
+    pub prefixes: Vec<DelegatedPrefix>,
+    /// LAN subnets carved out of the delegated prefixes
+    pub lan_prefixes: Vec<LanPrefix>,
+    /// DNS servers (option 23)
+    pub dns_servers: Vec<Ipv6Addr>,
+    /// Domain search list (option 24)
+    pub domain_search: Vec<String>,
+    /// Server DUID in hex
+    pub server_id: Option<String>,
+    /// Seconds until the client starts renewing (T1)
+    pub renewal_time: Option<u32>,
+    /// Seconds until the client starts rebinding (T2)
+    pub rebinding_time: Option<u32>,
+    /// Seconds until the first binding becomes invalid
+    pub valid_lifetime: Option<u32>,
+    /// Time when lease was acquired
+    pub acquired_at: Option<u64>,
+}
+
+/// Lease changes published by the DHCPv6 client
+#[derive(Debug, Clone, PartialEq)]
+pub enum Dhcpv6Event {
+    /// A new lease (or stateless configuration) was received and configured
+    Bound(Dhcpv6Lease),
+    /// The lease was extended or the configuration refreshed
+    Renewed(Dhcpv6Lease),
+    /// The lease ran out; addresses and LAN prefixes were withdrawn
+    Expired { interface: String },
+    /// The lease was given back to the server
+    Released { interface: String },
+    /// The client was stopped
+    Stopped { interface: String },
+}
+
+impl Dhcpv6Event {
+    /// Interface the event is about
+    pub fn interface(&self) -> &str {
+        match self {
+            Dhcpv6Event::Bound(lease) | Dhcpv6Event::Renewed(lease) => &lease.interface,
+            Dhcpv6Event::Expired { interface }
+            | Dhcpv6Event::Released { interface }
+            | Dhcpv6Event::Stopped { interface } => interface,
+        }
+    }
+}
+
+/// Retransmission timing (RFC 8415 section 7.6)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dhcpv6Timing {
+    /// First retransmission timeout of SOLICIT, REQUEST and
+    /// INFORMATION-REQUEST
+    pub initial_retransmit: Duration,
+    /// Upper bound of the doubling retransmission timeout
+    pub max_retransmit: Duration,
+    /// REQUESTs sent before soliciting again
+    pub request_attempts: u32,
+    /// First retransmission timeout of RENEW and REBIND
+    pub renew_retransmit: Duration,
+    /// Upper bound of the RENEW and REBIND retransmission timeout
+    pub max_renew_retransmit: Duration,
+    /// Refresh interval of stateless configuration unless the server
+    /// sends one
+    pub information_refresh: Duration,
+}
+
+impl Default for Dhcpv6Timing {
+    fn default() -> Self {
+        Self {
+            initial_retransmit: Duration::from_secs(1),
+            max_retransmit: Duration::from_secs(120),
+            request_attempts: 10,
+            renew_retransmit: Duration::from_secs(10),
+            max_renew_retransmit: Duration::from_secs(600),
+            information_refresh: Duration::from_secs(86400),
+        }
+    }
+}
+
+/// Prefix delegation settings
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PrefixDelegation {
+    /// Prefix length to ask the server for
+    pub prefix_len_hint: Option<u8>,
+    /// Interfaces that get a /64 each, in order
+    pub lan_interfaces: Vec<String>,
+}
+
+/// Per-interface settings of the DHCPv6 client
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dhcpv6ClientOptions {
+    /// Request addresses (IA_NA)
+    pub request_address: bool,
+    /// Request a delegated prefix (IA_PD)
+    pub prefix_delegation: Option<PrefixDelegation>,
+    pub timing: Dhcpv6Timing,
+}
+
+impl Dhcpv6ClientOptions {
+    /// Only other configuration (INFORMATION-REQUEST)
+    pub fn stateless() -> Self {
+        Self::default()
+    }
+
+    /// Addresses and other configuration
+    pub fn stateful() -> Self {
+        Self {
+            request_address: true,
+            ..Default::default()
+        }
+    }
+
+    /// Also request a prefix for `lan_interfaces`
+    pub fn with_prefix_delegation(mut self, prefix_len_hint: Option<u8>, lan_interfaces: Vec<String>) -> Self {
+        self.prefix_delegation = Some(PrefixDelegation { prefix_len_hint, lan_interfaces });
+        self
+    }
+
+    /// Whether no identity association is requested
+    pub fn is_stateless(&self) -> bool {
+        !self.request_address && self.prefix_delegation.is_none()
+    }
+}
+
+/// DHCPv6 client controller
+pub struct Dhcpv6ClientController {
+    /// Network namespace the interfaces live in
+    namespace: NetNamespace,
+    /// Options used by `start`
+    options: Dhcpv6ClientOptions,
+    /// Running clients by interface
+    clients: Mutex<HashMap<String, ClientHandle>>,
+    events: broadcast::Sender<Dhcpv6Event>,
+}
+
+impl Dhcpv6ClientController {
+    pub fn new() -> Self {
+        let (events, _) = broadcast::channel(64);
+        Self {
+            namespace: NetNamespace::Current,
+            options: Dhcpv6ClientOptions::stateful(),
+            clients: Mutex::new(HashMap::new()),
+            events,
+        }
+    }
+
+    /// Run clients on interfaces in `namespace`
+    pub fn with_namespace(namespace: NetNamespace) -> Self {
+        Self::new().in_namespace(namespace)
+    }
+
+    /// Builder form of [`with_namespace`](Self::with_namespace)
+    pub fn in_namespace(mut self, namespace: NetNamespace) -> Self {
+        self.namespace = namespace;
+        self
+    }
+
+    /// Options used by [`start`](Self::start)
+    pub fn with_options(mut self, options: Dhcpv6ClientOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Receive lease events
+    pub fn subscribe(&self) -> broadcast::Receiver<Dhcpv6Event> {
+        self.events.subscribe()
+    }
+
+    /// Start the DHCPv6 client on an interface
+    pub async fn start(&self, interface: &str) -> NetctlResult<()> {
+        self.start_with_options(interface, self.options.clone()).await
+    }
+
+    /// Start the DHCPv6 client on an interface with per-interface options
+    ///
+    /// Starting a client that is already running does nothing.
+    pub async fn start_with_options(&self, interface: &str, options: Dhcpv6ClientOptions) -> NetctlResult<()> {
+        validation::validate_interface_name(interface)?;
+        if let Some(pd) = &options.prefix_delegation {
+            for lan in &pd.lan_interfaces {
+                validation::validate_interface_name(lan)?;
+                if lan == interface {
+                    return Err(NetctlError::InvalidParameter(format!(
+                        "{} cannot be both the upstream and a LAN interface", interface
+                    )));
+                }
+            }
+            if let Some(len) = pd.prefix_len_hint {
+                validation::validate_prefix_len(len, true)?;
+            }
+        }
+
+        let mut clients = self.clients.lock().await;
+        if clients.get(interface).is_some_and(|client| !client.is_finished()) {
+            debug!("DHCPv6 client already running on {}", interface);
+            return Ok(());
+        }
+
+        let info = InterfaceController::with_namespace(self.namespace.clone()).get_info(interface).await?;
+        let ifindex = info.index
+            .ok_or_else(|| NetctlError::NotFound(format!("Interface {} has no index", interface)))?;
+        let mac = crate::arp::parse_mac(info.mac_address.as_deref(), interface)?;
+        let socket = rawsock::udp6_socket(&self.namespace, interface, crate::dhcpv6::packet::CLIENT_PORT)?;
+
+        info!("Starting DHCPv6 client on interface {}", interface);
+        let client = NativeClient {
+            interface: interface.to_string(),
+            ifindex,
+            mac,
+            namespace: self.namespace.clone(),
+            options,
+            socket,
+            events: self.events.clone(),
+        };
+        clients.insert(interface.to_string(), client.spawn());
+        Ok(())
+    }
+
+    /// Stop the DHCPv6 client, withdrawing its addresses and LAN prefixes
+    pub async fn stop(&self, interface: &str) -> NetctlResult<()> {
+        validation::validate_interface_name(interface)?;
+        let Some(client) = self.clients.lock().await.remove(interface) else {
+            debug!("No DHCPv6 client running on {}", interface);
+            return Ok(());
+        };
+        info!("Stopping DHCPv6 client on interface {}", interface);
+        client.stop().await;
+        Ok(())
+    }
+
+    /// Release the lease on an interface and stop the client
+    pub async fn release(&self, interface: &str) -> NetctlResult<()> {
+        validation::validate_interface_name(interface)?;
+        let Some(client) = self.clients.lock().await.remove(interface) else {
+            return Ok(());
+        };
+        info!("Releasing DHCPv6 lease on interface {}", interface);
+        client.release().await;
+        Ok(())
+    }
+
+    /// Renew the lease (or refresh stateless configuration) now
+    pub async fn renew(&self, interface: &str) -> NetctlResult<()> {
+        validation::validate_interface_name(interface)?;
+        let clients = self.clients.lock().await;
+        let client = clients.get(interface)
+            .ok_or_else(|| NetctlError::NotFound(format!("No DHCPv6 client running on {}", interface)))?;
+        info!("Renewing DHCPv6 lease on interface {}", interface);
+        client.renew().await
+    }
+
+    /// Current lease on an interface
+    ///
+    /// Returns `None` if no client runs on the interface; a running client
+    /// without a lease yields an empty lease.
+    pub async fn status(&self, interface: &str) -> NetctlResult<Option<Dhcpv6Lease>> {
+        validation::validate_interface_name(interface)?;
+        Ok(self.clients.lock().await.get(interface)
+            .filter(|client| !client.is_finished())
+            .map(|client| client.lease().unwrap_or_else(|| Dhcpv6Lease {
+                interface: interface.to_string(),
+                ..Default::default()
+            })))
+    }
+
+    /// State of the client on an interface
+    pub async fn state(&self, interface: &str) -> Dhcpv6ClientState {
+        match self.clients.lock().await.get(interface) {
+            Some(client) if !client.is_finished() => client.state(),
+            _ => Dhcpv6ClientState::Stopped,
+        }
+    }
+
+    /// Check if the DHCPv6 client is running on an interface
+    pub async fn is_running(&self, interface: &str) -> bool {
+        self.status(interface).await.ok().flatten().is_some()
+    }
+}
+
+impl Default for Dhcpv6ClientController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
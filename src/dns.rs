@@ -1,30 +1,395 @@
 //! DNS server management via unbound
+//!
+//! Renders an unbound configuration and controls the daemon through its pid
+//! file and unbound-control over a local UNIX socket. Forward zones for split
+//! DNS are kept in a separate file included from the main configuration so
+//! they can be changed while unbound is running.
 
-use crate::error::NetctlResult;
+use crate::config::NetctlConfig;
+use crate::error::{NetctlError, NetctlResult};
+use crate::validation;
 use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use tokio::fs;
+use tokio::process::Command;
+use tokio::time::{sleep, Duration};
+use tracing::{debug, info, warn};
+
+/// Default DNSSEC root trust anchor maintained by unbound-anchor
+pub const DEFAULT_TRUST_ANCHOR: &str = "/var/lib/unbound/root.key";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DnsConfig {
-    pub interface: Option<String>,
+    /// Addresses or interface names to listen on; empty means localhost only
+    pub listen: Vec<String>,
+    /// Port to listen on
+    pub port: u16,
+    /// Networks allowed to query, in CIDR notation
+    pub access_control: Vec<String>,
+    /// Upstream servers for the root zone; empty means full recursion
     pub forwarders: Vec<String>,
+    /// Additional forward zones
+    pub forward_zones: Vec<ForwardZone>,
+    /// Validate answers with DNSSEC
     pub dnssec: bool,
+    /// Root trust anchor file used when `dnssec` is enabled
+    pub trust_anchor: PathBuf,
+    /// Message cache size in megabytes (the RRset cache gets twice this)
+    pub cache_size: u32,
 }
 
 impl Default for DnsConfig {
     fn default() -> Self {
         Self {
-            interface: None,
+            listen: Vec::new(),
+            port: 53,
+            access_control: Vec::new(),
             forwarders: vec!["1.1.1.1".to_string(), "8.8.8.8".to_string()],
+            forward_zones: Vec::new(),
             dnssec: true,
+            trust_anchor: PathBuf::from(DEFAULT_TRUST_ANCHOR),
+            cache_size: NetctlConfig::default().defaults.dns_cache_size,
         }
     }
 }
 
-pub struct DnsController;
+/// Queries for `name` and its subdomains are sent to `servers`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForwardZone {
+    pub name: String,
+    pub servers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DnsStatus {
+    pub running: bool,
+    pub pid: Option<i32>,
+    pub version: Option<String>,
+    pub uptime_secs: Option<u64>,
+    pub queries: Option<u64>,
+    pub cache_hits: Option<u64>,
+}
+
+/// unbound controller
+pub struct DnsController {
+    config_dir: PathBuf,
+    pid_file: PathBuf,
+    unbound_bin: PathBuf,
+    unbound_control_bin: PathBuf,
+}
 
 impl DnsController {
+    /// Create a controller keeping its files in the netctl state directory
     pub fn new() -> Self {
-        Self
+        Self::with_config_dir(NetctlConfig::default().paths.state_dir)
+    }
+
+    /// Create a controller keeping its files in `config_dir`
+    pub fn with_config_dir(config_dir: PathBuf) -> Self {
+        Self {
+            pid_file: config_dir.join("unbound.pid"),
+            config_dir,
+            unbound_bin: PathBuf::from("/usr/sbin/unbound"),
+            unbound_control_bin: PathBuf::from("/usr/sbin/unbound-control"),
+        }
+    }
+
+    /// Path of the generated unbound configuration
+    pub fn config_path(&self) -> PathBuf {
+        self.config_dir.join("unbound.conf")
+    }
+
+    /// Path of the forward zones installed for split DNS
+    pub fn split_zones_path(&self) -> PathBuf {
+        self.config_dir.join("split-dns.conf")
+    }
+
+    fn control_socket(&self) -> PathBuf {
+        self.config_dir.join("unbound.ctl")
+    }
+
+    /// Generate unbound configuration file
+    pub fn generate_config(&self, config: &DnsConfig) -> NetctlResult<String> {
+        if config.port == 0 {
+            return Err(NetctlError::InvalidParameter("DNS port cannot be 0".to_string()));
+        }
+
+        let mut conf = String::from("# Generated by netctl\nserver:\n");
+        conf.push_str(&format!("    pidfile: \"{}\"\n", self.pid_file.display()));
+        conf.push_str("    chroot: \"\"\n");
+        conf.push_str("    do-daemonize: yes\n");
+        conf.push_str("    use-syslog: yes\n");
+        conf.push_str(&format!("    port: {}\n", config.port));
+
+        for listen in &config.listen {
+            if listen.parse::<IpAddr>().is_err() {
+                validation::validate_interface_name(listen)?;
+            }
+            conf.push_str(&format!("    interface: {}\n", listen));
+        }
+        if config.listen.is_empty() {
+            conf.push_str("    interface: 127.0.0.1\n    interface: ::1\n");
+        }
+
+        conf.push_str("    access-control: 127.0.0.0/8 allow\n");
+        conf.push_str("    access-control: ::1/128 allow\n");
+        for network in &config.access_control {
+            conf.push_str(&format!("    access-control: {} allow\n", validate_network(network)?));
+        }
+
+        conf.push_str(&format!("    msg-cache-size: {}m\n", config.cache_size));
+        conf.push_str(&format!("    rrset-cache-size: {}m\n", u64::from(config.cache_size) * 2));
+
+        if config.dnssec {
+            let anchor = validation::sanitize_config_value(&config.trust_anchor.to_string_lossy())?;
+            conf.push_str(&format!("    auto-trust-anchor-file: \"{}\"\n", anchor));
+        } else {
+            conf.push_str("    module-config: \"iterator\"\n");
+        }
+
+        conf.push_str("\nremote-control:\n");
+        conf.push_str("    control-enable: yes\n");
+        conf.push_str(&format!("    control-interface: \"{}\"\n", self.control_socket().display()));
+
+        for zone in &config.forward_zones {
+            conf.push_str(&render_forward_zone(&zone.name, &zone.servers)?);
+        }
+        if !config.forwarders.is_empty() && !config.forward_zones.iter().any(|z| z.name == ".") {
+            conf.push_str(&render_forward_zone(".", &config.forwarders)?);
+        }
+
+        conf.push_str(&format!("\ninclude: \"{}\"\n", self.split_zones_path().display()));
+
+        Ok(conf)
+    }
+
+    pub async fn write_config(&self, config: &DnsConfig) -> NetctlResult<PathBuf> {
+        let conf_content = self.generate_config(config)?;
+        let conf_path = self.config_path();
+
+        fs::create_dir_all(&self.config_dir).await?;
+
+        // Validate the config path to prevent path traversal
+        let validated_path = validation::validate_config_path(&conf_path, &self.config_dir)?;
+        fs::write(&validated_path, conf_content).await?;
+
+        // unbound refuses to start if an included file is missing
+        let split_path = validation::validate_config_path(&self.split_zones_path(), &self.config_dir)?;
+        if fs::metadata(&split_path).await.is_err() {
+            fs::write(&split_path, render_split_zones(&[])?).await?;
+        }
+        Ok(validated_path)
+    }
+
+    /// Addresses a resolv.conf can name to query the generated unbound
+    ///
+    /// Empty without a configuration or when unbound does not listen on
+    /// port 53; interfaces given by name are not included.
+    pub async fn nameserver_addresses(&self) -> Vec<IpAddr> {
+        match fs::read_to_string(self.config_path()).await {
+            Ok(content) => parse_nameserver_addresses(&content),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Forward zones currently installed for split DNS
+    pub async fn split_zones(&self) -> Vec<ForwardZone> {
+        match fs::read_to_string(self.split_zones_path()).await {
+            Ok(content) => parse_forward_zones(&content),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Replace the forward zones used for split DNS
+    ///
+    /// The zones are written to the included file and, if unbound is
+    /// running, changed in place with unbound-control. DNSSEC validation is
+    /// skipped below them since internal zones are usually unsigned.
+    pub async fn set_split_zones(&self, zones: &[ForwardZone]) -> NetctlResult<()> {
+        if zones.iter().any(|zone| zone.name.trim_end_matches('.').is_empty()) {
+            return Err(NetctlError::InvalidParameter("Split DNS zone cannot be the root".to_string()));
+        }
+        let content = render_split_zones(zones)?;
+
+        let previous = self.split_zones().await;
+        fs::create_dir_all(&self.config_dir).await?;
+        let path = validation::validate_config_path(&self.split_zones_path(), &self.config_dir)?;
+        fs::write(&path, content).await?;
+
+        if !self.is_running().await? {
+            return Ok(());
+        }
+
+        for zone in previous.iter().filter(|zone| !zones.contains(zone)) {
+            debug!("Removing split DNS zone {}", zone.name);
+            self.control(&["forward_remove", "+i", &zone.name]).await?;
+            self.control(&["flush_zone", &zone.name]).await?;
+        }
+        for zone in zones.iter().filter(|zone| !previous.contains(zone)) {
+            debug!("Adding split DNS zone {} -> {}", zone.name, zone.servers.join(", "));
+            let mut args = vec!["forward_add", "+i", zone.name.as_str()];
+            args.extend(zone.servers.iter().map(String::as_str));
+            self.control(&args).await?;
+            self.control(&["flush_zone", &zone.name]).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn start(&self, config: &DnsConfig) -> NetctlResult<()> {
+        if self.is_running().await? {
+            return Err(NetctlError::AlreadyExists("unbound already running".to_string()));
+        }
+
+        let conf_path = self.write_config(config).await?;
+        info!("Starting unbound with {}", conf_path.display());
+
+        let output = Command::new(&self.unbound_bin)
+            .arg("-c").arg(&conf_path)
+            .output()
+            .await
+            .map_err(|e| NetctlError::ServiceError(format!("Failed to start unbound: {}", e)))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(NetctlError::ServiceError(
+                format!("unbound failed: {}", validation::sanitize_error_message(&stderr))
+            ));
+        }
+
+        for _ in 0..10 {
+            if self.is_running().await? {
+                return Ok(());
+            }
+            sleep(Duration::from_millis(500)).await;
+        }
+
+        Err(NetctlError::ServiceError(
+            "unbound process did not start successfully".to_string()
+        ))
+    }
+
+    pub async fn stop(&self) -> NetctlResult<()> {
+        let Some(pid) = self.running_pid().await? else {
+            return Ok(());
+        };
+
+        info!("Stopping unbound (pid {})", pid);
+        Command::new("kill").arg("-TERM").arg(pid.to_string()).output().await?;
+
+        for _ in 0..10 {
+            sleep(Duration::from_millis(500)).await;
+            if !self.is_running().await? {
+                let _ = fs::remove_file(&self.pid_file).await;
+                return Ok(());
+            }
+        }
+
+        Err(NetctlError::Timeout("unbound did not stop".to_string()))
+    }
+
+    /// Apply a new configuration to the running server
+    pub async fn reload(&self, config: &DnsConfig) -> NetctlResult<()> {
+        if !self.is_running().await? {
+            return Err(NetctlError::NotFound("unbound is not running".to_string()));
+        }
+
+        self.write_config(config).await?;
+        match self.control(&["reload"]).await {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                // unbound also rereads its configuration on SIGHUP
+                warn!("unbound-control reload failed ({}), sending SIGHUP", e);
+                let pid = self.running_pid().await?
+                    .ok_or_else(|| NetctlError::NotFound("unbound is not running".to_string()))?;
+                Command::new("kill").arg("-HUP").arg(pid.to_string()).output().await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Flush the cache, or only the entries under `zone`
+    pub async fn flush(&self, zone: Option<&str>) -> NetctlResult<()> {
+        let zone = zone.unwrap_or(".");
+        if zone != "." {
+            validation::validate_hostname(zone.trim_end_matches('.'))?;
+        }
+        debug!("Flushing unbound cache for {}", zone);
+        self.control(&["flush_zone", zone]).await?;
+        self.control(&["flush_bogus"]).await?;
+        self.control(&["flush_negative"]).await?;
+        Ok(())
+    }
+
+    pub async fn status(&self) -> NetctlResult<DnsStatus> {
+        let Some(pid) = self.running_pid().await? else {
+            return Ok(DnsStatus::default());
+        };
+
+        let mut status = DnsStatus {
+            running: true,
+            pid: Some(pid),
+            ..Default::default()
+        };
+
+        match self.control(&["status"]).await {
+            Ok(output) => {
+                status.version = control_value(&output, "version").map(str::to_string);
+                status.uptime_secs = control_value(&output, "uptime")
+                    .and_then(|v| v.split_whitespace().next()?.parse().ok());
+            }
+            Err(e) => debug!("unbound-control status failed: {}", e),
+        }
+        match self.control(&["stats_noreset"]).await {
+            Ok(output) => {
+                status.queries = control_value(&output, "total.num.queries").and_then(|v| v.parse().ok());
+                status.cache_hits = control_value(&output, "total.num.cachehits").and_then(|v| v.parse().ok());
+            }
+            Err(e) => debug!("unbound-control stats_noreset failed: {}", e),
+        }
+
+        Ok(status)
+    }
+
+    pub async fn is_running(&self) -> NetctlResult<bool> {
+        Ok(self.running_pid().await?.is_some())
+    }
+
+    /// PID of the running unbound, verified through /proc
+    async fn running_pid(&self) -> NetctlResult<Option<i32>> {
+        let Ok(pid_str) = fs::read_to_string(&self.pid_file).await else {
+            return Ok(None);
+        };
+        let Ok(pid) = pid_str.trim().parse::<i32>() else {
+            return Ok(None);
+        };
+
+        // Make sure a stale PID file does not point at an unrelated process
+        match fs::read_to_string(format!("/proc/{}/cmdline", pid)).await {
+            Ok(cmdline) if cmdline.contains("unbound") => Ok(Some(pid)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Run an unbound-control command against the managed server
+    async fn control(&self, args: &[&str]) -> NetctlResult<String> {
+        let output = Command::new(&self.unbound_control_bin)
+            .arg("-c").arg(self.config_path())
+            .args(args)
+            .output()
+            .await
+            .map_err(|e| NetctlError::ServiceError(format!("Failed to run unbound-control: {}", e)))?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        if !output.status.success() || stdout.starts_with("error") {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(NetctlError::ServiceError(format!(
+                "unbound-control {} failed: {}",
+                args.join(" "),
+                validation::sanitize_error_message(if stderr.trim().is_empty() { &stdout } else { &stderr })
+            )));
+        }
+        Ok(stdout)
     }
 }
 
@@ -33,3 +398,159 @@ impl Default for DnsController {
         Self::new()
     }
 }
+
+/// Validate a network in CIDR notation
+fn validate_network(network: &str) -> NetctlResult<&str> {
+    let invalid = || NetctlError::InvalidParameter(format!("Invalid network: {}", network));
+    let (addr, prefix) = network.split_once('/').ok_or_else(invalid)?;
+    let addr: IpAddr = addr.parse().map_err(|_| invalid())?;
+    let prefix: u8 = prefix.parse().map_err(|_| invalid())?;
+    validation::validate_prefix_len(prefix, addr.is_ipv6())?;
+    Ok(network)
+}
+
+fn render_forward_zone(name: &str, servers: &[String]) -> NetctlResult<String> {
+    if name != "." {
+        validation::validate_hostname(name.trim_end_matches('.'))?;
+    }
+    if servers.is_empty() {
+        return Err(NetctlError::InvalidParameter(format!("Forward zone {} has no servers", name)));
+    }
+
+    let mut zone = format!("\nforward-zone:\n    name: \"{}\"\n", name);
+    for server in servers {
+        validation::validate_ip_address(server)?;
+        zone.push_str(&format!("    forward-addr: {}\n", server));
+    }
+    Ok(zone)
+}
+
+fn render_split_zones(zones: &[ForwardZone]) -> NetctlResult<String> {
+    let mut conf = String::from("# Generated by netctl: split DNS zones\n");
+    for zone in zones {
+        conf.push_str(&format!("\nserver:\n    domain-insecure: \"{}\"\n", zone.name));
+        conf.push_str(&render_forward_zone(&zone.name, &zone.servers)?);
+    }
+    Ok(conf)
+}
+
+/// Read back the forward zones of a generated file
+fn parse_forward_zones(conf: &str) -> Vec<ForwardZone> {
+    let mut zones: Vec<ForwardZone> = Vec::new();
+    for line in conf.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix("name:") {
+            zones.push(ForwardZone {
+                name: name.trim().trim_matches('"').to_string(),
+                servers: Vec::new(),
+            });
+        } else if let (Some(server), Some(zone)) = (line.strip_prefix("forward-addr:"), zones.last_mut()) {
+            zone.servers.push(server.trim().to_string());
+        }
+    }
+    zones
+}
+
+/// Listening addresses of a generated configuration, if it serves port 53
+fn parse_nameserver_addresses(conf: &str) -> Vec<IpAddr> {
+    let lines = || conf.lines().map(str::trim);
+    if lines().any(|line| line.strip_prefix("port:").is_some_and(|port| port.trim() != "53")) {
+        return Vec::new();
+    }
+    lines()
+        .filter_map(|line| line.strip_prefix("interface:"))
+        .filter_map(|address| address.trim().parse().ok())
+        .collect()
+}
+
+/// Value of a `key: value` or `key=value` line in unbound-control output
+fn control_value<'a>(output: &'a str, key: &str) -> Option<&'a str> {
+    output.lines().find_map(|line| {
+        let rest = line.strip_prefix(key)?;
+        rest.strip_prefix(':').or_else(|| rest.strip_prefix('=')).map(str::trim)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_config() {
+        let ctrl = DnsController::with_config_dir(PathBuf::from("/run/test"));
+        let config = DnsConfig {
+            listen: vec!["10.0.0.1".to_string(), "br0".to_string()],
+            access_control: vec!["10.0.0.0/24".to_string()],
+            forward_zones: vec![ForwardZone {
+                name: "corp.example".to_string(),
+                servers: vec!["10.8.0.53".to_string()],
+            }],
+            cache_size: 50,
+            ..Default::default()
+        };
+
+        let conf = ctrl.generate_config(&config).unwrap();
+        assert!(conf.contains("    interface: 10.0.0.1\n    interface: br0\n"));
+        assert!(conf.contains("    access-control: 10.0.0.0/24 allow\n"));
+        assert!(conf.contains("    msg-cache-size: 50m\n    rrset-cache-size: 100m\n"));
+        assert!(conf.contains("auto-trust-anchor-file: \"/var/lib/unbound/root.key\""));
+        assert!(conf.contains("control-interface: \"/run/test/unbound.ctl\""));
+        assert!(conf.contains("forward-zone:\n    name: \"corp.example\"\n    forward-addr: 10.8.0.53\n"));
+        assert!(conf.contains("forward-zone:\n    name: \".\"\n    forward-addr: 1.1.1.1\n    forward-addr: 8.8.8.8\n"));
+        assert!(conf.ends_with("\ninclude: \"/run/test/split-dns.conf\"\n"));
+
+        let insecure = DnsConfig { dnssec: false, forwarders: Vec::new(), ..Default::default() };
+        let conf = ctrl.generate_config(&insecure).unwrap();
+        assert!(conf.contains("module-config: \"iterator\""));
+        assert!(conf.contains("interface: 127.0.0.1"));
+        assert!(!conf.contains("forward-zone"));
+
+        let bad = DnsConfig { access_control: vec!["10.0.0.0".to_string()], ..Default::default() };
+        assert!(ctrl.generate_config(&bad).is_err());
+        let bad = DnsConfig { forwarders: vec!["1.1.1.1\nserver:".to_string()], ..Default::default() };
+        assert!(ctrl.generate_config(&bad).is_err());
+    }
+
+    #[test]
+    fn test_nameserver_addresses() {
+        let ctrl = DnsController::with_config_dir(PathBuf::from("/run/test"));
+        let config = DnsConfig { listen: vec!["10.0.0.1".to_string(), "br0".to_string()], ..Default::default() };
+        let conf = ctrl.generate_config(&config).unwrap();
+        assert_eq!(parse_nameserver_addresses(&conf), vec!["10.0.0.1".parse::<IpAddr>().unwrap()]);
+
+        let conf = ctrl.generate_config(&DnsConfig::default()).unwrap();
+        assert_eq!(parse_nameserver_addresses(&conf).len(), 2);
+        let conf = ctrl.generate_config(&DnsConfig { port: 5353, ..Default::default() }).unwrap();
+        assert!(parse_nameserver_addresses(&conf).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_split_zones() {
+        let dir = std::env::temp_dir().join(format!("netctl-split-dns-{}", std::process::id()));
+        let ctrl = DnsController::with_config_dir(dir.clone());
+        let zones = vec![
+            ForwardZone { name: "corp.example".to_string(), servers: vec!["10.8.0.53".to_string(), "fd00:8::53".to_string()] },
+            ForwardZone { name: "lab.example".to_string(), servers: vec!["10.9.0.53".to_string()] },
+        ];
+
+        ctrl.set_split_zones(&zones).await.unwrap();
+        let conf = std::fs::read_to_string(ctrl.split_zones_path()).unwrap();
+        assert!(conf.contains("server:\n    domain-insecure: \"corp.example\"\n"));
+        assert_eq!(ctrl.split_zones().await, zones);
+
+        assert!(ctrl.set_split_zones(&[ForwardZone { name: ".".to_string(), servers: vec!["10.0.0.1".to_string()] }]).await.is_err());
+        ctrl.set_split_zones(&[]).await.unwrap();
+        assert!(ctrl.split_zones().await.is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_control_value() {
+        let status = "version: 1.17.1\nverbosity: 1\nuptime: 42 seconds\nunbound (pid 123) is running...\n";
+        assert_eq!(control_value(status, "version"), Some("1.17.1"));
+        assert_eq!(control_value(status, "uptime"), Some("42 seconds"));
+        let stats = "total.num.queries=17\ntotal.num.cachehits=5\n";
+        assert_eq!(control_value(stats, "total.num.queries"), Some("17"));
+        assert_eq!(control_value(stats, "total.num.cachehits"), Some("5"));
+    }
+}
@@ -35,6 +35,10 @@ pub enum NetctlError {
     InvalidState(String),
     /// Connection failed
     ConnectionFailed { reason: String },
+    /// Credentials were rejected (wrong WiFi password, EAP failure)
+    AuthenticationFailed(String),
+    /// IPv4 address already in use by another host
+    AddressConflict { address: String, mac: String },
 }
 
 impl fmt::Display for NetctlError {
@@ -61,6 +65,10 @@ impl fmt::Display for NetctlError {
             NetctlError::NotFound(msg) => write!(f, "Not found: {}", msg),
             NetctlError::InvalidState(msg) => write!(f, "Invalid state: {}", msg),
             NetctlError::ConnectionFailed { reason } => write!(f, "Connection failed: {}", reason),
+            NetctlError::AuthenticationFailed(msg) => write!(f, "Authentication failed: {}", msg),
+            NetctlError::AddressConflict { address, mac } => {
+                write!(f, "Address conflict: {} is in use by {}", address, mac)
+            }
         }
     }
 }
@@ -0,0 +1,414 @@
+//! Generic netlink transport
+//!
+//! Message framing, attribute encoding and family resolution for the
+//! generic netlink protocols the kernel exposes (nl80211). Requests are
+//! sent on a [`GenlSocket`] opened inside a [`NetNamespace`]; replies and
+//! dumps are collected until the kernel acknowledges them, and multicast
+//! events are read from a socket that joined the family's groups.
+
+use crate::error::{NetctlError, NetctlResult};
+use crate::netns::NetNamespace;
+use netlink_sys::protocols::NETLINK_GENERIC;
+use std::collections::HashMap;
+use tokio::io::unix::AsyncFd;
+
+const NLMSG_HEADER_LEN: usize = 16;
+const GENL_HEADER_LEN: usize = 4;
+const NLA_HEADER_LEN: usize = 4;
+
+const NLMSG_ERROR: u16 = 2;
+const NLMSG_DONE: u16 = 3;
+
+const NLM_F_REQUEST: u16 = 0x1;
+const NLM_F_MULTI: u16 = 0x2;
+const NLM_F_ACK: u16 = 0x4;
+const NLM_F_DUMP: u16 = 0x300;
+
+const NLA_F_NESTED: u16 = 0x8000;
+const NLA_TYPE_MASK: u16 = 0x3fff;
+
+/// Controller family, which resolves family names
+const GENL_ID_CTRL: u16 = 0x10;
+const CTRL_CMD_GETFAMILY: u8 = 3;
+const CTRL_ATTR_FAMILY_ID: u16 = 1;
+const CTRL_ATTR_FAMILY_NAME: u16 = 2;
+const CTRL_ATTR_MCAST_GROUPS: u16 = 7;
+const CTRL_ATTR_MCAST_GRP_NAME: u16 = 1;
+const CTRL_ATTR_MCAST_GRP_ID: u16 = 2;
+
+/// Receive buffer size; one scan result with its IEs fits easily
+const RECV_BUFFER_LEN: usize = 65536;
+
+fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// Attribute payload builder
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Attrs {
+    buf: Vec<u8>,
+}
+
+impl Attrs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn put(&mut self, kind: u16, value: &[u8]) -> &mut Self {
+        let len = NLA_HEADER_LEN + value.len();
+        self.buf.extend_from_slice(&(len as u16).to_ne_bytes());
+        self.buf.extend_from_slice(&kind.to_ne_bytes());
+        self.buf.extend_from_slice(value);
+        self.buf.resize(align(self.buf.len()), 0);
+        self
+    }
+
+    pub fn put_u32(&mut self, kind: u16, value: u32) -> &mut Self {
+        self.put(kind, &value.to_ne_bytes())
+    }
+
+    /// NUL-terminated string
+    pub fn put_str(&mut self, kind: u16, value: &str) -> &mut Self {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.push(0);
+        self.put(kind, &bytes)
+    }
+
+    pub fn put_nested(&mut self, kind: u16, nested: &Attrs) -> &mut Self {
+        self.put(kind | NLA_F_NESTED, &nested.buf)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+/// Iterate the attributes in `data` as (type, payload)
+pub(crate) fn attrs(mut data: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    std::iter::from_fn(move || {
+        if data.len() < NLA_HEADER_LEN {
+            return None;
+        }
+        let len = u16::from_ne_bytes([data[0], data[1]]) as usize;
+        let kind = u16::from_ne_bytes([data[2], data[3]]) & NLA_TYPE_MASK;
+        if len < NLA_HEADER_LEN || len > data.len() {
+            return None;
+        }
+        let payload = &data[NLA_HEADER_LEN..len];
+        data = &data[align(len).min(data.len())..];
+        Some((kind, payload))
+    })
+}
+
+/// Attributes of one message or nest, indexed by type
+#[derive(Debug, Default)]
+pub(crate) struct AttrMap<'a> {
+    attrs: HashMap<u16, &'a [u8]>,
+}
+
+impl<'a> AttrMap<'a> {
+    pub fn parse(data: &'a [u8]) -> Self {
+        Self { attrs: attrs(data).collect() }
+    }
+
+    pub fn get(&self, kind: u16) -> Option<&'a [u8]> {
+        self.attrs.get(&kind).copied()
+    }
+
+    pub fn has(&self, kind: u16) -> bool {
+        self.attrs.contains_key(&kind)
+    }
+
+    pub fn nested(&self, kind: u16) -> Option<AttrMap<'a>> {
+        self.get(kind).map(AttrMap::parse)
+    }
+
+    pub fn u8(&self, kind: u16) -> Option<u8> {
+        self.get(kind)?.first().copied()
+    }
+
+    pub fn u16(&self, kind: u16) -> Option<u16> {
+        Some(u16::from_ne_bytes(self.get(kind)?.get(..2)?.try_into().ok()?))
+    }
+
+    pub fn u32(&self, kind: u16) -> Option<u32> {
+        Some(u32::from_ne_bytes(self.get(kind)?.get(..4)?.try_into().ok()?))
+    }
+
+    pub fn i32(&self, kind: u16) -> Option<i32> {
+        self.u32(kind).map(|value| value as i32)
+    }
+
+    pub fn u64(&self, kind: u16) -> Option<u64> {
+        Some(u64::from_ne_bytes(self.get(kind)?.get(..8)?.try_into().ok()?))
+    }
+
+    /// String attribute without its NUL terminator
+    pub fn string(&self, kind: u16) -> Option<String> {
+        let bytes = self.get(kind)?;
+        let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+        Some(String::from_utf8_lossy(&bytes[..end]).into_owned())
+    }
+}
+
+/// A generic netlink message
+#[derive(Debug, Clone)]
+pub(crate) struct GenlMessage {
+    pub cmd: u8,
+    pub payload: Vec<u8>,
+}
+
+impl GenlMessage {
+    pub fn attrs(&self) -> AttrMap<'_> {
+        AttrMap::parse(&self.payload)
+    }
+}
+
+/// A resolved generic netlink family
+#[derive(Debug, Clone)]
+pub(crate) struct Family {
+    pub id: u16,
+    /// Multicast group ids by name
+    pub groups: HashMap<String, u32>,
+}
+
+/// One netlink message read from the socket
+enum Received {
+    Message { seq: u32, multi: bool, message: GenlMessage },
+    /// NLMSG_ERROR; zero acknowledges a request
+    Error { seq: u32, errno: i32 },
+    Done { seq: u32, errno: i32 },
+}
+
+/// Generic netlink socket
+pub(crate) struct GenlSocket {
+    fd: AsyncFd<netlink_sys::Socket>,
+    seq: u32,
+    buf: Vec<u8>,
+    pending: std::collections::VecDeque<Received>,
+}
+
+impl GenlSocket {
+    pub fn open(namespace: &NetNamespace) -> NetctlResult<Self> {
+        let mut socket = namespace.socket(NETLINK_GENERIC)?;
+        socket.bind_auto()
+            .map_err(|e| NetctlError::ServiceError(format!("Failed to bind generic netlink socket: {}", e)))?;
+        socket.set_non_blocking(true)
+            .map_err(|e| NetctlError::ServiceError(format!("Failed to set non-blocking: {}", e)))?;
+        // Extended acks are not parsed, only the errno is used
+        let _ = socket.set_ext_ack(false);
+        Ok(Self {
+            fd: AsyncFd::new(socket)?,
+            seq: 0,
+            buf: Vec::with_capacity(RECV_BUFFER_LEN),
+            pending: Default::default(),
+        })
+    }
+
+    /// Look up a family and its multicast groups by name
+    pub async fn resolve(&mut self, name: &str) -> NetctlResult<Family> {
+        let mut attrs = Attrs::new();
+        attrs.put_str(CTRL_ATTR_FAMILY_NAME, name);
+        let replies = self.request(GENL_ID_CTRL, CTRL_CMD_GETFAMILY, false, &attrs, "resolve family", name).await
+            .map_err(|e| match e {
+                NetctlError::NotFound(_) => NetctlError::NotSupported(format!("Generic netlink family {} is not available", name)),
+                e => e,
+            })?;
+        let reply = replies.first()
+            .ok_or_else(|| NetctlError::ServiceError(format!("No reply resolving generic netlink family {}", name)))?;
+        let attrs = reply.attrs();
+        let id = attrs.u16(CTRL_ATTR_FAMILY_ID)
+            .ok_or_else(|| NetctlError::ServiceError(format!("Generic netlink family {} has no id", name)))?;
+        let groups = attrs.get(CTRL_ATTR_MCAST_GROUPS)
+            .map(|data| {
+                self::attrs(data)
+                    .filter_map(|(_, group)| {
+                        let group = AttrMap::parse(group);
+                        Some((group.string(CTRL_ATTR_MCAST_GRP_NAME)?, group.u32(CTRL_ATTR_MCAST_GRP_ID)?))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(Family { id, groups })
+    }
+
+    /// Join a multicast group to receive its events
+    pub fn join(&self, group: u32) -> NetctlResult<()> {
+        self.fd.get_ref().add_membership(group)
+            .map_err(|e| NetctlError::ServiceError(format!("Failed to join netlink group {}: {}", group, e)))
+    }
+
+    /// Send a request and collect its replies
+    ///
+    /// A dump returns every message up to NLMSG_DONE, any other request the
+    /// replies before its acknowledgement. An errno from the kernel is mapped
+    /// like rtnetlink errors, with `op` and `target` in the message.
+    pub async fn request(&mut self, family: u16, cmd: u8, dump: bool, attrs: &Attrs, op: &str, target: &str) -> NetctlResult<Vec<GenlMessage>> {
+        self.seq = self.seq.wrapping_add(1);
+        let seq = self.seq;
+        let flags = NLM_F_REQUEST | NLM_F_ACK | if dump { NLM_F_DUMP } else { 0 };
+        let packet = encode(family, flags, seq, cmd, attrs.as_bytes());
+        self.send(&packet).await?;
+
+        let mut replies = Vec::new();
+        loop {
+            match self.next().await? {
+                Received::Message { seq: s, message, .. } if s == seq => replies.push(message),
+                Received::Error { seq: s, errno } if s == seq => {
+                    // A dump is finished by NLMSG_DONE, which may follow the ack
+                    if errno != 0 {
+                        return Err(crate::netlink::errno_error(errno, op, target));
+                    }
+                    if !dump {
+                        return Ok(replies);
+                    }
+                }
+                Received::Done { seq: s, errno } if s == seq => {
+                    if errno != 0 {
+                        return Err(crate::netlink::errno_error(errno, op, target));
+                    }
+                    return Ok(replies);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Next multicast event
+    pub async fn event(&mut self) -> NetctlResult<GenlMessage> {
+        loop {
+            if let Received::Message { seq: 0, multi: false, message } = self.next().await? {
+                return Ok(message);
+            }
+        }
+    }
+
+    async fn send(&self, packet: &[u8]) -> NetctlResult<()> {
+        loop {
+            let mut guard = self.fd.writable().await?;
+            match guard.try_io(|fd| fd.get_ref().send(packet, 0)) {
+                Ok(result) => {
+                    result.map_err(|e| NetctlError::ServiceError(format!("Failed to send netlink request: {}", e)))?;
+                    return Ok(());
+                }
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    async fn next(&mut self) -> NetctlResult<Received> {
+        loop {
+            if let Some(received) = self.pending.pop_front() {
+                return Ok(received);
+            }
+            let mut guard = self.fd.readable().await?;
+            let buf = &mut self.buf;
+            match guard.try_io(|fd| {
+                buf.clear();
+                fd.get_ref().recv(buf, 0)
+            }) {
+                Ok(Ok(_)) => self.pending.extend(decode(&self.buf)),
+                Ok(Err(e)) => {
+                    // Events dropped while the socket buffer was full; the
+                    // caller sees the next ones
+                    if e.raw_os_error() == Some(libc::ENOBUFS) {
+                        continue;
+                    }
+                    return Err(NetctlError::ServiceError(format!("Failed to receive netlink message: {}", e)));
+                }
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+/// Build a request; families handled here do not check the header version
+fn encode(family: u16, flags: u16, seq: u32, cmd: u8, attrs: &[u8]) -> Vec<u8> {
+    let len = NLMSG_HEADER_LEN + GENL_HEADER_LEN + attrs.len();
+    let mut packet = Vec::with_capacity(len);
+    packet.extend_from_slice(&(len as u32).to_ne_bytes());
+    packet.extend_from_slice(&family.to_ne_bytes());
+    packet.extend_from_slice(&flags.to_ne_bytes());
+    packet.extend_from_slice(&seq.to_ne_bytes());
+    // Port id 0: the kernel fills in the socket's
+    packet.extend_from_slice(&0u32.to_ne_bytes());
+    packet.extend_from_slice(&[cmd, 1, 0, 0]);
+    packet.extend_from_slice(attrs);
+    packet
+}
+
+fn decode(mut data: &[u8]) -> Vec<Received> {
+    let mut messages = Vec::new();
+    while data.len() >= NLMSG_HEADER_LEN {
+        let len = u32::from_ne_bytes(data[0..4].try_into().unwrap()) as usize;
+        if len < NLMSG_HEADER_LEN || len > data.len() {
+            break;
+        }
+        let kind = u16::from_ne_bytes([data[4], data[5]]);
+        let flags = u16::from_ne_bytes([data[6], data[7]]);
+        let seq = u32::from_ne_bytes(data[8..12].try_into().unwrap());
+        let body = &data[NLMSG_HEADER_LEN..len];
+        let errno = || body.get(..4).map_or(0, |code| -i32::from_ne_bytes(code.try_into().unwrap()));
+
+        match kind {
+            NLMSG_ERROR => messages.push(Received::Error { seq, errno: errno() }),
+            NLMSG_DONE => messages.push(Received::Done { seq, errno: errno() }),
+            _ if kind < GENL_ID_CTRL => {}
+            _ if body.len() >= GENL_HEADER_LEN => messages.push(Received::Message {
+                seq,
+                multi: flags & NLM_F_MULTI != 0,
+                message: GenlMessage { cmd: body[0], payload: body[GENL_HEADER_LEN..].to_vec() },
+            }),
+            _ => {}
+        }
+        data = &data[align(len).min(data.len())..];
+    }
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attrs_roundtrip() {
+        let mut nested = Attrs::new();
+        nested.put(1, b"");
+        let mut attrs = Attrs::new();
+        attrs.put_u32(3, 7).put_str(2, "wlan0").put_nested(45, &nested).put(9, &[1]);
+
+        let map = AttrMap::parse(attrs.as_bytes());
+        assert_eq!(map.u32(3), Some(7));
+        assert_eq!(map.string(2).as_deref(), Some("wlan0"));
+        assert_eq!(map.u8(9), Some(1));
+        let ssids: Vec<_> = self::attrs(map.get(45).unwrap()).collect();
+        assert_eq!(ssids, vec![(1, &b""[..])]);
+        assert!(!map.has(4));
+    }
+
+    #[test]
+    fn test_decode_messages() {
+        let mut attrs = Attrs::new();
+        attrs.put_u32(3, 2);
+        let mut data = encode(0x20, NLM_F_MULTI, 5, 34, attrs.as_bytes());
+        // NLMSG_DONE with errno 0, then an error acknowledging seq 6 with -EBUSY
+        let mut done = encode(NLMSG_DONE, NLM_F_MULTI, 5, 0, &[]);
+        done.truncate(NLMSG_HEADER_LEN);
+        done.extend_from_slice(&0i32.to_ne_bytes());
+        let len = done.len() as u32;
+        done[0..4].copy_from_slice(&len.to_ne_bytes());
+        data.extend_from_slice(&done);
+        let mut error = encode(NLMSG_ERROR, 0, 6, 0, &[]);
+        error.truncate(NLMSG_HEADER_LEN);
+        error.extend_from_slice(&(-libc::EBUSY).to_ne_bytes());
+        let len = error.len() as u32;
+        error[0..4].copy_from_slice(&len.to_ne_bytes());
+        data.extend_from_slice(&error);
+
+        let received = decode(&data);
+        assert_eq!(received.len(), 3);
+        assert!(matches!(&received[0], Received::Message { seq: 5, multi: true, message } if message.cmd == 34 && message.attrs().u32(3) == Some(2)));
+        assert!(matches!(received[1], Received::Done { seq: 5, errno: 0 }));
+        assert!(matches!(received[2], Received::Error { seq: 6, errno } if errno == libc::EBUSY));
+    }
+}
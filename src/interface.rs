@@ -1,13 +1,19 @@
 //! Network interface control
 //!
-//! Low-level interface management using ip command and sysfs
+//! Low-level interface management over rtnetlink
 
 use crate::error::{NetctlError, NetctlResult};
+use crate::netlink;
+use crate::netns::NetNamespace;
 use crate::validation;
+use futures::stream::TryStreamExt;
+use netlink_packet_route::address::{AddressAttribute, AddressMessage, AddressScope};
+use netlink_packet_route::link::{LinkAttribute, LinkFlags, LinkMessage, State};
+use netlink_packet_route::AddressFamily;
+use rtnetlink::{Handle, LinkUnspec};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
-use tokio::process::Command;
-use tokio::fs;
+use std::net::IpAddr;
+use std::os::fd::AsRawFd;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InterfaceInfo {
@@ -43,30 +49,37 @@ pub struct InterfaceStats {
 
 /// Interface controller
 pub struct InterfaceController {
-    // Future: could hold state or config
+    /// Network namespace the controller operates in
+    namespace: NetNamespace,
 }
 
 impl InterfaceController {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            namespace: NetNamespace::Current,
+        }
     }
 
-    /// List all network interfaces
-    pub async fn list(&self) -> NetctlResult<Vec<String>> {
-        let net_path = Path::new("/sys/class/net");
+    /// Create a controller bound to a network namespace
+    pub fn with_namespace(namespace: NetNamespace) -> Self {
+        Self { namespace }
+    }
 
-        if !net_path.exists() {
-            return Err(NetctlError::NotSupported(
-                "/sys/class/net not available".to_string()
-            ));
-        }
+    /// Network namespace this controller operates in
+    pub fn namespace(&self) -> &NetNamespace {
+        &self.namespace
+    }
 
-        let mut entries = fs::read_dir(net_path).await?;
+    /// List all network interfaces
+    pub async fn list(&self) -> NetctlResult<Vec<String>> {
+        let handle = self.connect()?;
+        let mut links = handle.link().get().execute();
         let mut interfaces = Vec::new();
 
-        while let Some(entry) = entries.next_entry().await? {
-            if let Some(name) = entry.file_name().to_str() {
-                interfaces.push(name.to_string());
+        while let Some(link) = links.try_next().await
+            .map_err(|e| netlink::map_error(e, "list links", "all"))? {
+            if let Some(name) = netlink::link_name(&link) {
+                interfaces.push(name);
             }
         }
 
@@ -76,38 +89,47 @@ impl InterfaceController {
 
     /// Get detailed interface information
     pub async fn get_info(&self, interface: &str) -> NetctlResult<InterfaceInfo> {
-        // Validate interface name to prevent command injection
         validation::validate_interface_name(interface)?;
 
-        // Verify interface exists
-        let sys_path = format!("/sys/class/net/{}", interface);
-        if !Path::new(&sys_path).exists() {
-            return Err(NetctlError::InterfaceNotFound(interface.to_string()));
-        }
+        let handle = self.connect()?;
+        let link = netlink::get_link(&handle, interface).await?;
 
         let mut info = InterfaceInfo {
             name: interface.to_string(),
-            index: None,
+            index: Some(link.header.index),
             mac_address: None,
             mtu: None,
             state: None,
-            flags: Vec::new(),
+            flags: netlink::flag_names(link.header.flags),
             addresses: Vec::new(),
             stats: None,
         };
 
-        // Read from sysfs
-        info.index = self.read_sysfs_u32(interface, "ifindex").await;
-        info.mac_address = self.read_sysfs_string(interface, "address").await;
-        info.mtu = self.read_sysfs_u32(interface, "mtu").await;
-        info.state = self.read_sysfs_string(interface, "operstate").await;
-        info.stats = self.read_stats(interface).await;
-
-        // Get IP addresses
-        info.addresses = self.get_addresses(interface).await?;
+        for attr in &link.attributes {
+            match attr {
+                LinkAttribute::Address(mac) => info.mac_address = Some(netlink::format_mac(mac)),
+                LinkAttribute::Mtu(mtu) => info.mtu = Some(*mtu),
+                LinkAttribute::OperState(state) => info.state = Some(oper_state_name(*state)),
+                LinkAttribute::Stats64(stats) => {
+                    info.stats = Some(InterfaceStats {
+                        rx_bytes: stats.rx_bytes,
+                        tx_bytes: stats.tx_bytes,
+                        rx_packets: stats.rx_packets,
+                        tx_packets: stats.tx_packets,
+                        rx_errors: stats.rx_errors,
+                        tx_errors: stats.tx_errors,
+                        rx_dropped: stats.rx_dropped,
+                        tx_dropped: stats.tx_dropped,
+                    });
+                }
+                _ => {}
+            }
+        }
 
-        // Get flags from ip command
-        info.flags = self.get_flags(interface).await?;
+        info.addresses = self.get_addresses(&handle, link.header.index).await?
+            .iter()
+            .filter_map(address_from_message)
+            .collect();
 
         Ok(info)
     }
@@ -115,41 +137,37 @@ impl InterfaceController {
     /// Bring interface up
     pub async fn up(&self, interface: &str) -> NetctlResult<()> {
         validation::validate_interface_name(interface)?;
-        self.run_ip(&["link", "set", "dev", interface, "up"]).await
+        let handle = self.connect()?;
+        let index = netlink::link_index(&handle, interface).await?;
+        self.set_link(&handle, LinkUnspec::new_with_index(index).up().build(), "set link up", interface).await
     }
 
     /// Bring interface down
     pub async fn down(&self, interface: &str) -> NetctlResult<()> {
         validation::validate_interface_name(interface)?;
-        self.run_ip(&["link", "set", "dev", interface, "down"]).await
+        let handle = self.connect()?;
+        let index = netlink::link_index(&handle, interface).await?;
+        self.set_link(&handle, LinkUnspec::new_with_index(index).down().build(), "set link down", interface).await
     }
 
     /// Get link state (carrier status)
     pub async fn get_link_state(&self, interface: &str) -> NetctlResult<bool> {
         validation::validate_interface_name(interface)?;
 
-        // Check if interface exists
-        let sys_path = format!("/sys/class/net/{}", interface);
-        if !Path::new(&sys_path).exists() {
-            return Err(NetctlError::InterfaceNotFound(interface.to_string()));
-        }
+        let handle = self.connect()?;
+        let link = netlink::get_link(&handle, interface).await?;
 
-        // Read operstate from sysfs
-        // Possible values: up, down, unknown, notpresent, lowerlayerdown, testing, dormant
-        match self.read_sysfs_string(interface, "operstate").await {
-            Some(state) => {
-                let state = state.trim().to_lowercase();
-                // Consider "up" as link up, everything else as down
-                Ok(state == "up")
-            }
-            None => {
-                // If we can't read operstate, check carrier file
-                match self.read_sysfs_string(interface, "carrier").await {
-                    Some(carrier) => Ok(carrier.trim() == "1"),
-                    None => Ok(false),
+        // Prefer the RFC 2863 operstate; fall back to the carrier flag when
+        // the driver does not report one
+        for attr in &link.attributes {
+            if let LinkAttribute::OperState(state) = attr {
+                if *state != State::Unknown {
+                    return Ok(*state == State::Up);
                 }
             }
         }
+
+        Ok(link.header.flags.contains(LinkFlags::LowerUp))
     }
 
     /// Set IP address
@@ -158,8 +176,10 @@ impl InterfaceController {
         let ip = validation::validate_ip_address(address)?;
         validation::validate_prefix_len(prefix_len, ip.is_ipv6())?;
 
-        let addr = format!("{}/{}", address, prefix_len);
-        self.run_ip(&["addr", "add", &addr, "dev", interface]).await
+        let handle = self.connect()?;
+        let index = netlink::link_index(&handle, interface).await?;
+        handle.address().add(index, ip, prefix_len).execute().await
+            .map_err(|e| netlink::map_error(e, &format!("add address {}/{}", ip, prefix_len), interface))
     }
 
     /// Add secondary IP address
@@ -173,24 +193,55 @@ impl InterfaceController {
         let ip = validation::validate_ip_address(address)?;
         validation::validate_prefix_len(prefix_len, ip.is_ipv6())?;
 
-        let addr = format!("{}/{}", address, prefix_len);
-        self.run_ip(&["addr", "del", &addr, "dev", interface]).await
+        let handle = self.connect()?;
+        let index = netlink::link_index(&handle, interface).await?;
+        let target = self.get_addresses(&handle, index).await?
+            .into_iter()
+            .find(|msg| {
+                msg.header.prefix_len == prefix_len
+                    && address_from_message(msg).is_some_and(|a| a.address == ip.to_string())
+            })
+            .ok_or_else(|| NetctlError::NotFound(
+                format!("Address {}/{} not configured on {}", ip, prefix_len, interface)
+            ))?;
+
+        handle.address().del(target).execute().await
+            .map_err(|e| netlink::map_error(e, &format!("delete address {}/{}", ip, prefix_len), interface))
     }
 
     /// Flush all IP addresses
     pub async fn flush_addrs(&self, interface: &str) -> NetctlResult<()> {
         validation::validate_interface_name(interface)?;
-        self.run_ip(&["addr", "flush", "dev", interface]).await
+
+        let handle = self.connect()?;
+        let index = netlink::link_index(&handle, interface).await?;
+        for msg in self.get_addresses(&handle, index).await? {
+            match handle.address().del(msg).execute().await {
+                Ok(()) => {}
+                // Deleting a primary address also removes its secondaries
+                Err(e) if netlink::errno(&e) == Some(libc::EADDRNOTAVAIL) => {}
+                Err(e) => return Err(netlink::map_error(e, "flush addresses", interface)),
+            }
+        }
+        Ok(())
     }
 
     /// Set MAC address
     pub async fn set_mac(&self, interface: &str, mac: &str) -> NetctlResult<()> {
         validation::validate_interface_name(interface)?;
         validation::validate_mac_address(mac)?;
+        let mac_bytes = netlink::parse_mac(mac)?;
 
         // Must bring interface down first
         self.down(interface).await?;
-        self.run_ip(&["link", "set", "dev", interface, "address", mac]).await?;
+        let handle = self.connect()?;
+        let index = netlink::link_index(&handle, interface).await?;
+        self.set_link(
+            &handle,
+            LinkUnspec::new_with_index(index).address(mac_bytes).build(),
+            "set MAC address",
+            interface,
+        ).await?;
         self.up(interface).await?;
         Ok(())
     }
@@ -200,40 +251,39 @@ impl InterfaceController {
         validation::validate_interface_name(interface)?;
         validation::validate_mtu(mtu)?;
 
-        let mtu_str = mtu.to_string();
-        self.run_ip(&["link", "set", "dev", interface, "mtu", &mtu_str]).await
+        let handle = self.connect()?;
+        let index = netlink::link_index(&handle, interface).await?;
+        self.set_link(&handle, LinkUnspec::new_with_index(index).mtu(mtu).build(), "set MTU", interface).await
     }
 
     /// Set transmit queue length
     pub async fn set_txqueuelen(&self, interface: &str, len: u32) -> NetctlResult<()> {
         validation::validate_interface_name(interface)?;
 
-        let len_str = len.to_string();
-        self.run_ip(&["link", "set", "dev", interface, "txqueuelen", &len_str]).await
+        let handle = self.connect()?;
+        let index = netlink::link_index(&handle, interface).await?;
+        let message = LinkUnspec::new_with_index(index)
+            .append_extra_attribute(LinkAttribute::TxQueueLen(len))
+            .build();
+        self.set_link(&handle, message, "set txqueuelen", interface).await
     }
 
     /// Set promiscuous mode
     pub async fn set_promisc(&self, interface: &str, enable: bool) -> NetctlResult<()> {
         validation::validate_interface_name(interface)?;
-
-        let mode = if enable { "on" } else { "off" };
-        self.run_ip(&["link", "set", "dev", interface, "promisc", mode]).await
+        self.set_link_flag(interface, LinkFlags::Promisc, enable).await
     }
 
     /// Set multicast
     pub async fn set_multicast(&self, interface: &str, enable: bool) -> NetctlResult<()> {
         validation::validate_interface_name(interface)?;
-
-        let mode = if enable { "on" } else { "off" };
-        self.run_ip(&["link", "set", "dev", interface, "multicast", mode]).await
+        self.set_link_flag(interface, LinkFlags::Multicast, enable).await
     }
 
     /// Set all multicast
     pub async fn set_allmulticast(&self, interface: &str, enable: bool) -> NetctlResult<()> {
         validation::validate_interface_name(interface)?;
-
-        let mode = if enable { "on" } else { "off" };
-        self.run_ip(&["link", "set", "dev", interface, "allmulticast", mode]).await
+        self.set_link_flag(interface, LinkFlags::Allmulti, enable).await
     }
 
     /// Rename interface
@@ -243,7 +293,14 @@ impl InterfaceController {
 
         // Must be down to rename
         self.down(old_name).await?;
-        self.run_ip(&["link", "set", "dev", old_name, "name", new_name]).await?;
+        let handle = self.connect()?;
+        let index = netlink::link_index(&handle, old_name).await?;
+        self.set_link(
+            &handle,
+            LinkUnspec::new_with_index(index).name(new_name.to_string()).build(),
+            &format!("rename to {}", new_name),
+            old_name,
+        ).await?;
         self.up(new_name).await?;
         Ok(())
     }
@@ -252,152 +309,156 @@ impl InterfaceController {
     pub async fn delete(&self, interface: &str) -> NetctlResult<()> {
         validation::validate_interface_name(interface)?;
 
-        // Verify interface exists
-        let sys_path = format!("/sys/class/net/{}", interface);
-        if !Path::new(&sys_path).exists() {
-            return Err(NetctlError::InterfaceNotFound(interface.to_string()));
-        }
-
-        self.run_ip(&["link", "delete", "dev", interface]).await
+        let handle = self.connect()?;
+        let index = netlink::link_index(&handle, interface).await?;
+        handle.link().del(index).execute().await
+            .map_err(|e| netlink::map_error(e, "delete link", interface))
     }
 
-    // === Helper functions ===
-
-    async fn run_ip(&self, args: &[&str]) -> NetctlResult<()> {
-        let cmd_str = format!("ip {}", args.join(" "));
-        let output = Command::new("ip")
-            .args(args)
-            .output()
-            .await
-            .map_err(|e| NetctlError::CommandFailed {
-                cmd: cmd_str.clone(),
-                code: None,
-                stderr: e.to_string(),
-            })?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8(output.stderr)
-                .unwrap_or_else(|e| String::from_utf8_lossy(&e.into_bytes()).to_string());
-            return Err(NetctlError::CommandFailed {
-                cmd: cmd_str,
-                code: output.status.code(),
-                stderr,
-            });
+    /// Move an interface into another network namespace
+    ///
+    /// The interface keeps its name, so moving fails if the target namespace
+    /// already has an interface with the same name.
+    pub async fn move_to_namespace(&self, interface: &str, target: &NetNamespace) -> NetctlResult<()> {
+        validation::validate_interface_name(interface)?;
+        if *target == self.namespace {
+            return Ok(());
         }
 
-        Ok(())
+        let target_fd = target.open()?;
+        let handle = self.connect()?;
+        let index = netlink::link_index(&handle, interface).await?;
+        self.set_link(
+            &handle,
+            LinkUnspec::new_with_index(index).setns_by_fd(target_fd.as_raw_fd()).build(),
+            &format!("move to namespace {}", target),
+            interface,
+        ).await
     }
 
-    async fn read_sysfs_string(&self, interface: &str, file: &str) -> Option<String> {
-        let path = format!("/sys/class/net/{}/{}", interface, file);
-        fs::read_to_string(path).await.ok().map(|s| s.trim().to_string())
+    /// Read a per-interface IPv6 sysctl (`net.ipv6.conf.<interface>.<key>`)
+    pub async fn get_ipv6_sysctl(&self, interface: &str, key: &str) -> NetctlResult<String> {
+        let path = ipv6_sysctl_path(interface, key)?;
+        self.namespace.run_in(|| {
+            std::fs::read_to_string(&path)
+                .map(|value| value.trim().to_string())
+                .map_err(|e| sysctl_error(e, interface, key))
+        })
     }
 
-    async fn read_sysfs_u32(&self, interface: &str, file: &str) -> Option<u32> {
-        self.read_sysfs_string(interface, file).await?.parse().ok()
+    /// Write a per-interface IPv6 sysctl (`net.ipv6.conf.<interface>.<key>`)
+    pub async fn set_ipv6_sysctl(&self, interface: &str, key: &str, value: &str) -> NetctlResult<()> {
+        let path = ipv6_sysctl_path(interface, key)?;
+        self.namespace.run_in(|| {
+            std::fs::write(&path, value).map_err(|e| sysctl_error(e, interface, key))
+        })
     }
 
-    async fn read_sysfs_u64(&self, interface: &str, file: &str) -> Option<u64> {
-        self.read_sysfs_string(interface, file).await?.parse().ok()
+    // === Helper functions ===
+
+    fn connect(&self) -> NetctlResult<Handle> {
+        self.namespace.connect()
     }
 
-    async fn read_stats(&self, interface: &str) -> Option<InterfaceStats> {
-        Some(InterfaceStats {
-            rx_bytes: self.read_sysfs_u64(interface, "statistics/rx_bytes").await?,
-            tx_bytes: self.read_sysfs_u64(interface, "statistics/tx_bytes").await?,
-            rx_packets: self.read_sysfs_u64(interface, "statistics/rx_packets").await?,
-            tx_packets: self.read_sysfs_u64(interface, "statistics/tx_packets").await?,
-            rx_errors: self.read_sysfs_u64(interface, "statistics/rx_errors").await.unwrap_or(0),
-            tx_errors: self.read_sysfs_u64(interface, "statistics/tx_errors").await.unwrap_or(0),
-            rx_dropped: self.read_sysfs_u64(interface, "statistics/rx_dropped").await.unwrap_or(0),
-            tx_dropped: self.read_sysfs_u64(interface, "statistics/tx_dropped").await.unwrap_or(0),
-        })
+    async fn set_link(&self, handle: &Handle, message: LinkMessage, op: &str, interface: &str) -> NetctlResult<()> {
+        handle.link().set(message).execute().await
+            .map_err(|e| netlink::map_error(e, op, interface))
     }
 
-    async fn get_addresses(&self, interface: &str) -> NetctlResult<Vec<IpAddress>> {
-        let cmd_str = format!("ip -json addr show {}", interface);
-        let output = Command::new("ip")
-            .args(["-json", "addr", "show", interface])
-            .output()
-            .await
-            .map_err(|e| NetctlError::CommandFailed {
-                cmd: cmd_str,
-                code: None,
-                stderr: e.to_string(),
-            })?;
-
-        if !output.status.success() {
-            return Ok(Vec::new());
+    async fn set_link_flag(&self, interface: &str, flag: LinkFlags, enable: bool) -> NetctlResult<()> {
+        let handle = self.connect()?;
+        let index = netlink::link_index(&handle, interface).await?;
+
+        let mut message = LinkUnspec::new_with_index(index).build();
+        if enable {
+            message.header.flags |= flag;
         }
+        message.header.change_mask |= flag;
 
-        let json_str = String::from_utf8(output.stdout)
-            .map_err(|e| NetctlError::ParseError(format!("Invalid UTF-8 in JSON output: {}", e)))?;
-        let json: serde_json::Value = serde_json::from_str(&json_str)
-            .map_err(|e| NetctlError::ParseError(e.to_string()))?;
+        self.set_link(&handle, message, &format!("set flag {}", flag), interface).await
+    }
 
+    async fn get_addresses(&self, handle: &Handle, index: u32) -> NetctlResult<Vec<AddressMessage>> {
+        let mut stream = handle.address().get().set_link_index_filter(index).execute();
         let mut addresses = Vec::new();
-
-        if let Some(arr) = json.as_array() {
-            if let Some(iface) = arr.first() {
-                if let Some(addr_info) = iface.get("addr_info").and_then(|v| v.as_array()) {
-                    for addr in addr_info {
-                        if let (Some(local), Some(family), Some(prefixlen)) = (
-                            addr.get("local").and_then(|v| v.as_str()),
-                            addr.get("family").and_then(|v| v.as_str()),
-                            addr.get("prefixlen").and_then(|v| v.as_u64()),
-                        ) {
-                            addresses.push(IpAddress {
-                                address: local.to_string(),
-                                family: family.to_string(),
-                                prefix_len: prefixlen as u8,
-                                scope: addr.get("scope").and_then(|v| v.as_str()).map(String::from),
-                            });
-                        }
-                    }
-                }
-            }
+        while let Some(msg) = stream.try_next().await
+            .map_err(|e| netlink::map_error(e, "list addresses", &index.to_string()))? {
+            addresses.push(msg);
         }
-
         Ok(addresses)
     }
+}
 
-    async fn get_flags(&self, interface: &str) -> NetctlResult<Vec<String>> {
-        let cmd_str = format!("ip -json link show {}", interface);
-        let output = Command::new("ip")
-            .args(["-json", "link", "show", interface])
-            .output()
-            .await
-            .map_err(|e| NetctlError::CommandFailed {
-                cmd: cmd_str,
-                code: None,
-                stderr: e.to_string(),
-            })?;
-
-        if !output.status.success() {
-            return Ok(Vec::new());
-        }
+/// Convert an address message to the `ip addr` style representation
+fn address_from_message(msg: &AddressMessage) -> Option<IpAddress> {
+    // IFA_LOCAL is the interface address on point-to-point links; IFA_ADDRESS
+    // is the peer there, and identical to IFA_LOCAL everywhere else
+    let local = msg.attributes.iter().find_map(|attr| match attr {
+        AddressAttribute::Local(ip) => Some(*ip),
+        _ => None,
+    });
+    let address = local.or_else(|| msg.attributes.iter().find_map(|attr| match attr {
+        AddressAttribute::Address(ip) => Some(*ip),
+        _ => None,
+    }))?;
+
+    let family = match msg.header.family {
+        AddressFamily::Inet6 => "inet6",
+        _ => match address {
+            IpAddr::V4(_) => "inet",
+            IpAddr::V6(_) => "inet6",
+        },
+    };
+
+    let scope = match msg.header.scope {
+        AddressScope::Universe => "global".to_string(),
+        AddressScope::Site => "site".to_string(),
+        AddressScope::Link => "link".to_string(),
+        AddressScope::Host => "host".to_string(),
+        AddressScope::Nowhere => "nowhere".to_string(),
+        other => u8::from(other).to_string(),
+    };
+
+    Some(IpAddress {
+        address: address.to_string(),
+        family: family.to_string(),
+        prefix_len: msg.header.prefix_len,
+        scope: Some(scope),
+    })
+}
 
-        let json_str = String::from_utf8(output.stdout)
-            .map_err(|e| NetctlError::ParseError(format!("Invalid UTF-8 in JSON output: {}", e)))?;
-        let json: serde_json::Value = serde_json::from_str(&json_str)
-            .map_err(|e| NetctlError::ParseError(e.to_string()))?;
-
-        let mut flags = Vec::new();
-
-        if let Some(arr) = json.as_array() {
-            if let Some(iface) = arr.first() {
-                if let Some(flag_arr) = iface.get("flags").and_then(|v| v.as_array()) {
-                    flags.reserve(flag_arr.len());
-                    for flag in flag_arr {
-                        if let Some(f) = flag.as_str() {
-                            flags.push(f.to_string());
-                        }
-                    }
-                }
-            }
-        }
+/// Render an operstate the way sysfs reports it
+fn oper_state_name(state: State) -> String {
+    match state {
+        State::Unknown => "unknown".to_string(),
+        State::NotPresent => "notpresent".to_string(),
+        State::Down => "down".to_string(),
+        State::LowerLayerDown => "lowerlayerdown".to_string(),
+        State::Testing => "testing".to_string(),
+        State::Dormant => "dormant".to_string(),
+        State::Up => "up".to_string(),
+        other => u8::from(other).to_string(),
+    }
+}
+
+/// Path of a per-interface IPv6 sysctl
+fn ipv6_sysctl_path(interface: &str, key: &str) -> NetctlResult<String> {
+    validation::validate_interface_name(interface)?;
+    if key.is_empty() || !key.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_') {
+        return Err(NetctlError::InvalidParameter(format!("Invalid sysctl key: {}", key)));
+    }
+    Ok(format!("/proc/sys/net/ipv6/conf/{}/{}", interface, key))
+}
 
-        Ok(flags)
+fn sysctl_error(err: std::io::Error, interface: &str, key: &str) -> NetctlError {
+    match err.kind() {
+        std::io::ErrorKind::NotFound => {
+            NetctlError::NotFound(format!("IPv6 sysctl {} for interface {}", key, interface))
+        }
+        std::io::ErrorKind::PermissionDenied => {
+            NetctlError::PermissionDenied(format!("Cannot write IPv6 sysctl {} for {}", key, interface))
+        }
+        _ => NetctlError::Io(err),
     }
 }
 
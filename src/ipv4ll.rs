@@ -0,0 +1,374 @@
+//! IPv4 link-local addresses (RFC 3927)
+//!
+//! [`LinkLocalController`] runs one task per interface. The task picks an
+//! address in 169.254.1.0 - 169.254.254.255, probes it with ARP, configures
+//! and announces it, and then defends it. Candidates come from a generator
+//! seeded with the hardware address, so a host tends to get the same address
+//! every time. When another host claims the address twice within
+//! [`DEFEND_INTERVAL`] it is removed and the next candidate is tried; after
+//! too many conflicts only one candidate per minute is probed.
+
+use crate::arp::{self, ArpProbeConfig, ArpSocket, DEFEND_INTERVAL};
+use crate::error::{NetctlError, NetctlResult};
+use crate::interface::InterfaceController;
+use crate::netns::NetNamespace;
+use crate::validation;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::Ipv4Addr;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, oneshot, watch, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Instant};
+use tracing::{debug, info, warn};
+
+/// Prefix length of link-local addresses
+pub const PREFIX_LEN: u8 = 16;
+/// Conflicts after which probing is rate limited (MAX_CONFLICTS)
+const MAX_CONFLICTS: u32 = 10;
+/// Delay between candidates once rate limited (RATE_LIMIT_INTERVAL)
+const RATE_LIMIT_INTERVAL: Duration = Duration::from_secs(60);
+/// Wait before retrying after a socket or netlink error
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Whether `address` is in 169.254.0.0/16
+pub fn is_link_local(address: Ipv4Addr) -> bool {
+    address.octets()[..2] == [169, 254]
+}
+
+/// Whether `address` may be claimed; the first and last /24 are reserved
+fn is_claimable(address: Ipv4Addr) -> bool {
+    is_link_local(address) && (1..=254).contains(&address.octets()[2])
+}
+
+/// Next candidate address from `rng`
+fn candidate(rng: &mut StdRng) -> Ipv4Addr {
+    Ipv4Addr::new(169, 254, rng.gen_range(1..=254), rng.gen())
+}
+
+/// Candidate generator seeded with the hardware address
+fn generator(mac: [u8; 6]) -> StdRng {
+    let mut seed = [0u8; 8];
+    seed[2..].copy_from_slice(&mac);
+    StdRng::seed_from_u64(u64::from_be_bytes(seed))
+}
+
+/// Link-local address changes
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkLocalEvent {
+    /// An address was probed, configured and announced
+    Bound { interface: String, address: Ipv4Addr },
+    /// Another host claimed the address, which was removed
+    Conflict {
+        interface: String,
+        address: Ipv4Addr,
+        /// Hardware address of the other host
+        mac: String,
+    },
+    /// The task was stopped and its address removed
+    Stopped { interface: String },
+}
+
+impl LinkLocalEvent {
+    /// Interface the event is about
+    pub fn interface(&self) -> &str {
+        match self {
+            LinkLocalEvent::Bound { interface, .. }
+            | LinkLocalEvent::Conflict { interface, .. }
+            | LinkLocalEvent::Stopped { interface } => interface,
+        }
+    }
+}
+
+type StopRequest = oneshot::Sender<()>;
+
+/// Run `future` unless a stop request comes first
+async fn or_stop<T>(stop: &mut mpsc::Receiver<StopRequest>, future: impl Future<Output = T>) -> Result<T, Option<StopRequest>> {
+    tokio::select! {
+        output = future => Ok(output),
+        request = stop.recv() => Err(request),
+    }
+}
+
+/// Handle to a running task
+struct ClaimHandle {
+    stop: mpsc::Sender<StopRequest>,
+    address: watch::Receiver<Option<Ipv4Addr>>,
+    task: JoinHandle<()>,
+}
+
+impl ClaimHandle {
+    fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+}
+
+struct Claim {
+    interface: String,
+    socket: ArpSocket,
+    interfaces: InterfaceController,
+    probe: ArpProbeConfig,
+    rng: StdRng,
+    /// Address to try first
+    requested: Option<Ipv4Addr>,
+    conflicts: u32,
+    address: watch::Sender<Option<Ipv4Addr>>,
+    events: broadcast::Sender<LinkLocalEvent>,
+}
+
+impl Claim {
+    async fn run(mut self, mut stop: mpsc::Receiver<StopRequest>) {
+        let request = loop {
+            let address = match self.requested.take() {
+                Some(address) => address,
+                None => candidate(&mut self.rng),
+            };
+            if self.conflicts >= MAX_CONFLICTS {
+                debug!("Too many link-local conflicts on {}, waiting", self.interface);
+                if let Err(request) = or_stop(&mut stop, sleep(RATE_LIMIT_INTERVAL)).await {
+                    break request;
+                }
+            }
+
+            match or_stop(&mut stop, self.socket.probe(address, &self.probe)).await {
+                Err(request) => break request,
+                Ok(Ok(None)) => {}
+                Ok(Ok(Some(mac))) => {
+                    debug!("{} is in use on {} by {}", address, self.interface, arp::format_mac(&mac));
+                    self.conflicts += 1;
+                    continue;
+                }
+                Ok(Err(e)) => {
+                    warn!("Failed to probe {} on {}: {}", address, self.interface, e);
+                    if let Err(request) = or_stop(&mut stop, sleep(RETRY_INTERVAL)).await {
+                        break request;
+                    }
+                    continue;
+                }
+            }
+
+            match self.interfaces.add_ip(&self.interface, &address.to_string(), PREFIX_LEN).await {
+                Ok(()) | Err(NetctlError::AlreadyExists(_)) => {}
+                Err(e) => {
+                    warn!("Failed to add {} on {}: {}", address, self.interface, e);
+                    if let Err(request) = or_stop(&mut stop, sleep(RETRY_INTERVAL)).await {
+                        break request;
+                    }
+                    continue;
+                }
+            }
+            info!("Claimed link-local address {} on {}", address, self.interface);
+            self.address.send_replace(Some(address));
+            self.emit(LinkLocalEvent::Bound { interface: self.interface.clone(), address });
+
+            if let Err(request) = or_stop(&mut stop, self.defend(address)).await {
+                break request;
+            }
+        };
+
+        self.remove().await;
+        self.emit(LinkLocalEvent::Stopped { interface: self.interface.clone() });
+        if let Some(done) = request {
+            let _ = done.send(());
+        }
+    }
+
+    /// Announce `address` and defend it until another host insists on it
+    async fn defend(&mut self, address: Ipv4Addr) {
+        if let Err(e) = self.socket.announce(address, &self.probe).await {
+            debug!("Failed to announce {}: {}", address, e);
+        }
+
+        let mut last_defense: Option<Instant> = None;
+        let mac = loop {
+            match self.socket.wait_for_conflict(address).await {
+                Ok(mac) if last_defense.is_some_and(|at| at.elapsed() < DEFEND_INTERVAL) => break mac,
+                Ok(mac) => {
+                    info!("Defending {} on {} against {}", address, self.interface, arp::format_mac(&mac));
+                    if let Err(e) = self.socket.defend(address).await {
+                        debug!("Failed to defend {}: {}", address, e);
+                    }
+                    last_defense = Some(Instant::now());
+                }
+                Err(e) => {
+                    warn!("Failed to watch {} on {}: {}", address, self.interface, e);
+                    sleep(RETRY_INTERVAL).await;
+                }
+            }
+        };
+
+        let mac = arp::format_mac(&mac);
+        warn!("{} on {} is claimed by {}, choosing another link-local address", address, self.interface, mac);
+        self.remove().await;
+        self.conflicts += 1;
+        self.emit(LinkLocalEvent::Conflict { interface: self.interface.clone(), address, mac });
+    }
+
+    /// Remove the configured address, if any
+    async fn remove(&mut self) {
+        let Some(address) = self.address.send_replace(None) else {
+            return;
+        };
+        match self.interfaces.del_ip(&self.interface, &address.to_string(), PREFIX_LEN).await {
+            Ok(()) | Err(NetctlError::NotFound(_)) => {}
+            Err(e) => warn!("Failed to remove {} from {}: {}", address, self.interface, e),
+        }
+    }
+
+    fn emit(&self, event: LinkLocalEvent) {
+        // Nobody listening is fine
+        let _ = self.events.send(event);
+    }
+}
+
+/// IPv4 link-local address controller
+pub struct LinkLocalController {
+    /// Network namespace the interfaces live in
+    namespace: NetNamespace,
+    /// Probe and announcement timing
+    probe: ArpProbeConfig,
+    /// Running tasks by interface
+    claims: Mutex<HashMap<String, ClaimHandle>>,
+    /// Last address per interface, tried first on the next start
+    last_addresses: Mutex<HashMap<String, Ipv4Addr>>,
+    events: broadcast::Sender<LinkLocalEvent>,
+}
+
+impl LinkLocalController {
+    pub fn new() -> Self {
+        let (events, _) = broadcast::channel(64);
+        Self {
+            namespace: NetNamespace::Current,
+            probe: ArpProbeConfig::default(),
+            claims: Mutex::new(HashMap::new()),
+            last_addresses: Mutex::new(HashMap::new()),
+            events,
+        }
+    }
+
+    /// Claim addresses on interfaces in `namespace`
+    pub fn with_namespace(namespace: NetNamespace) -> Self {
+        Self::new().in_namespace(namespace)
+    }
+
+    /// Builder form of [`with_namespace`](Self::with_namespace)
+    pub fn in_namespace(mut self, namespace: NetNamespace) -> Self {
+        self.namespace = namespace;
+        self
+    }
+
+    /// Probe and announcement timing; the defaults are those of RFC 3927
+    pub fn with_probe_config(mut self, probe: ArpProbeConfig) -> Self {
+        self.probe = probe;
+        self
+    }
+
+    /// Receive address events
+    pub fn subscribe(&self) -> broadcast::Receiver<LinkLocalEvent> {
+        self.events.subscribe()
+    }
+
+    /// Start claiming a link-local address on an interface
+    ///
+    /// Returns once the task runs; the address follows as a
+    /// [`LinkLocalEvent::Bound`]. Starting on an interface that already has
+    /// a task does nothing.
+    pub async fn start(&self, interface: &str) -> NetctlResult<()> {
+        validation::validate_interface_name(interface)?;
+        let mut claims = self.claims.lock().await;
+        if claims.get(interface).is_some_and(|claim| !claim.is_finished()) {
+            debug!("Link-local address already managed on {}", interface);
+            return Ok(());
+        }
+
+        let interfaces = InterfaceController::with_namespace(self.namespace.clone());
+        let info = interfaces.get_info(interface).await?;
+        let ifindex = info.index
+            .ok_or_else(|| NetctlError::NotFound(format!("Interface {} has no index", interface)))?;
+        let mac = arp::parse_mac(info.mac_address.as_deref(), interface)?;
+        let socket = ArpSocket::open(&self.namespace, ifindex, mac)?;
+
+        info!("Starting IPv4 link-local configuration on {}", interface);
+        let (stop_tx, stop) = mpsc::channel(1);
+        let (address_tx, address) = watch::channel(None);
+        let claim = Claim {
+            interface: interface.to_string(),
+            socket,
+            interfaces,
+            probe: self.probe.clone(),
+            rng: generator(mac),
+            requested: self.last_addresses.lock().await.get(interface).copied().filter(|a| is_claimable(*a)),
+            conflicts: 0,
+            address: address_tx,
+            events: self.events.clone(),
+        };
+        claims.insert(interface.to_string(), ClaimHandle {
+            stop: stop_tx,
+            address,
+            task: tokio::spawn(claim.run(stop)),
+        });
+        Ok(())
+    }
+
+    /// Stop the task on an interface and remove its address
+    pub async fn stop(&self, interface: &str) -> NetctlResult<()> {
+        validation::validate_interface_name(interface)?;
+        let Some(claim) = self.claims.lock().await.remove(interface) else {
+            debug!("No link-local address managed on {}", interface);
+            return Ok(());
+        };
+        let address = *claim.address.borrow();
+        if let Some(address) = address {
+            self.last_addresses.lock().await.insert(interface.to_string(), address);
+        }
+        info!("Stopping IPv4 link-local configuration on {}", interface);
+        let (tx, rx) = oneshot::channel();
+        if claim.stop.send(tx).await.is_ok() {
+            let _ = rx.await;
+        }
+        let _ = claim.task.await;
+        Ok(())
+    }
+
+    /// Link-local address currently configured on an interface
+    pub async fn address(&self, interface: &str) -> Option<Ipv4Addr> {
+        self.claims.lock().await.get(interface)
+            .filter(|claim| !claim.is_finished())
+            .and_then(|claim| *claim.address.borrow())
+    }
+
+    /// Check if a link-local address is managed on an interface
+    pub async fn is_running(&self, interface: &str) -> bool {
+        self.claims.lock().await.get(interface).is_some_and(|claim| !claim.is_finished())
+    }
+}
+
+impl Default for LinkLocalController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates() {
+        let mac = [0x02, 0x11, 0x22, 0x33, 0x44, 0x55];
+        let mut rng = generator(mac);
+        let first: Vec<Ipv4Addr> = (0..100).map(|_| candidate(&mut rng)).collect();
+        assert!(first.iter().all(|a| is_claimable(*a)));
+        // The same hardware address yields the same sequence
+        let mut again = generator(mac);
+        assert_eq!(candidate(&mut again), first[0]);
+        assert_ne!(candidate(&mut generator([0x02, 0, 0, 0, 0, 1])), first[0]);
+
+        assert!(is_link_local(Ipv4Addr::new(169, 254, 0, 1)));
+        assert!(!is_claimable(Ipv4Addr::new(169, 254, 0, 1)));
+        assert!(!is_claimable(Ipv4Addr::new(169, 254, 255, 1)));
+        assert!(!is_link_local(Ipv4Addr::new(169, 253, 1, 1)));
+    }
+}
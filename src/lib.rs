@@ -6,6 +6,7 @@
 //! - Access Point (hostapd)
 //! - DHCP server (dora)
 //! - DHCP testing and diagnostics
+//! - IPv6 router advertisements
 //! - DNS configuration
 //! - Routing
 //! - VPN management (WireGuard, OpenVPN, IPsec)
@@ -14,20 +15,38 @@
 
 pub mod error;
 pub mod validation;
+pub mod config;
+pub(crate) mod netlink;
+pub(crate) mod genl;
+pub mod netns;
+pub(crate) mod rawsock;
+pub mod arp;
+pub mod ipv4ll;
 pub mod interface;
 pub mod wifi;
+pub(crate) mod nl80211;
 pub mod wpa_supplicant;
+pub mod wpa_ctrl;
 pub mod hostapd;
 pub mod dhcp;
+pub(crate) mod client_task;
 pub mod dhcp_client;
+pub mod dhcpm;
+pub mod dhcpv4;
+pub mod dhcpv6;
+pub mod dhcpv6_client;
+pub mod ra;
+pub mod dns;
 pub mod link_monitor;
 pub mod privilege_token;
 
 pub mod routing;
+pub mod resolver;
 pub mod device;
 pub mod plugin;
 pub mod connection_config;
 pub mod connection_manager;
+pub mod wifi_autoselect;
 pub mod vpn;
 pub mod network_monitor;
 pub mod libcr_compat;
@@ -42,22 +61,44 @@ pub mod dbus_integration;
 
 // Re-export commonly used types
 pub use error::{NetctlError, NetctlResult};
+pub use config::NetctlConfig;
+pub use netns::NetNamespace;
 pub use interface::{InterfaceController, InterfaceInfo, IpAddress, InterfaceStats};
-pub use wifi::{WifiController, WifiDeviceInfo, RegDomain, ScanResult};
-pub use wpa_supplicant::WpaSupplicantController;
+pub use wifi::{
+    WifiController, WifiDeviceInfo, RegDomain, ScanResult, SecurityInfo, CipherSuite, AkmSuite,
+    PhyCapabilities, CountryInfo, StationInfo, RateInfo, LinkInfo, TxPower, WifiBand,
+};
+pub use wpa_supplicant::{WpaSupplicantController, WpaNetwork, WpaSecurity, EapConfig, EapMethod, Pmf};
+pub use wpa_ctrl::{WpaCtrl, WpaEvent, WpaEvents};
 pub use hostapd::{HostapdController, AccessPointConfig};
-pub use dhcp::{DhcpController, DhcpConfig};
-pub use dhcp_client::{DhcpClientController, DhcpClientState, DhcpLease};
+pub use dhcp::{
+    DhcpController, DhcpConfig, DhcpOption, DhcpOptionValue, DhcpRange, DhcpReservation,
+    DhcpServerLease, DhcpServerStatus, DhcpStaticRoute,
+};
+pub use dhcp_client::{
+    DhcpClientController, DhcpClientState, DhcpLease, DhcpClientBackend, DhcpClientOptions,
+    DhcpEvent, DhcpTiming,
+};
+pub use dhcpv6_client::{
+    Dhcpv6ClientController, Dhcpv6ClientState, Dhcpv6Lease, Dhcpv6ClientOptions, Dhcpv6Event,
+    Dhcpv6Timing, DelegatedPrefix, LanPrefix, PrefixDelegation,
+};
+pub use ra::{RaConfig, RaController, RaPrefix, RaRoute, RaStatus, RouterPreference};
+pub use arp::ArpProbeConfig;
+pub use ipv4ll::{LinkLocalController, LinkLocalEvent};
+pub use dns::{DnsController, DnsConfig, DnsStatus, ForwardZone};
 pub use link_monitor::{LinkMonitor, LinkState, LinkStateEvent, InterfaceConfig};
-pub use connection_manager::{ConnectionManager, ActiveConnection};
+pub use connection_manager::{ConnectionManager, ActiveConnection, LeaseDns};
+pub use wifi_autoselect::WifiAutoselect;
 pub use connection_config::{
     NetctlConnectionConfig, ConnectionConfigManager,
     ConnectionSection, WifiSection, WifiSecuritySection,
-    IpConfigSection, EthernetSection, VpnSection,
+    IpConfigSection, Ipv6RaSection, EthernetSection, VpnSection, RoutingRuleSection,
     WireGuardVpnSection, WireGuardPeer, OpenVpnSection,
 };
 
-pub use routing::RoutingController;
+pub use routing::{RoutingController, Route, RouteType, RouteScope, RouteProtocol, RoutingRule, RuleAction};
+pub use resolver::ResolverManager;
 pub use device::{
     DeviceController, Device, DeviceType, DeviceState, DeviceCapabilities,
     DeviceStats, DeviceConfig,
@@ -1,6 +1,6 @@
 //! CRAccessPoint - WiFi access point (libnm NMAccessPoint equivalent)
 
-use crate::wifi::ScanResult;
+use crate::wifi::{AkmSuite, CipherSuite, ScanResult, SecurityInfo};
 use serde::{Deserialize, Serialize};
 
 /// WiFi access point (equivalent to NMAccessPoint)
@@ -48,48 +48,34 @@ pub enum CRAccessPointMode {
 impl CRAccessPoint {
     /// Creates an access point from a scan result
     pub(crate) fn from_scan_result(result: ScanResult) -> Self {
-        // Parse signal strength from signal string (e.g., "-50.00 dBm")
         let strength = result.signal
-            .and_then(|s| s.split_whitespace().next().map(|v| v.to_string()))
-            .and_then(|s| s.parse::<f32>().ok())
             .map(|dbm| {
                 // Convert dBm to 0-100 scale
                 // Typical WiFi range is -90 dBm (weak) to -30 dBm (strong)
-                let percent = ((dbm + 90.0) / 60.0 * 100.0).max(0.0).min(100.0);
-                percent as u8
+                ((dbm + 90) * 100 / 60).clamp(0, 100) as u8
             })
             .unwrap_or(0);
 
-        // Parse frequency to calculate channel
-        let frequency = result.frequency.unwrap_or(0);
-        let channel = if frequency >= 2412 && frequency <= 2484 {
-            // 2.4 GHz: channel = (freq - 2407) / 5
-            (frequency - 2407) / 5
-        } else if frequency >= 5170 && frequency <= 5825 {
-            // 5 GHz: channel = (freq - 5000) / 5
-            (frequency - 5000) / 5
+        let privacy = result.capabilities.iter().any(|c| c == "Privacy");
+        let mode = if result.capabilities.iter().any(|c| c == "IBSS") {
+            CRAccessPointMode::Adhoc
         } else {
-            0
+            CRAccessPointMode::Infra
         };
-
-        // Check capabilities for security info
-        let has_wpa = result.capabilities.iter().any(|c| c.contains("WPA"));
-        let has_wpa2 = result.capabilities.iter().any(|c| c.contains("WPA2") || c.contains("RSN"));
-        let has_wpa3 = result.capabilities.iter().any(|c| c.contains("WPA3"));
-        let has_wep = result.capabilities.iter().any(|c| c.contains("WEP") || c.contains("Privacy"));
+        let age = result.age_ms.unwrap_or(0) as i64 / 1000;
 
         Self {
             ssid: result.ssid.unwrap_or_default().into_bytes(),
             bssid: result.bssid,
-            frequency,
-            channel,
+            frequency: result.frequency.unwrap_or(0),
+            channel: result.channel.unwrap_or(0),
             strength,
-            flags: if has_wpa || has_wep { 1 } else { 0 },
-            wpa_flags: if has_wpa && !has_wpa2 { 0x344 } else { 0 },
-            rsn_flags: if has_wpa2 || has_wpa3 { 0x344 } else { 0 },
-            mode: CRAccessPointMode::Infra,
+            flags: if privacy { 1 } else { 0 },
+            wpa_flags: result.wpa.as_ref().map_or(0, security_flags),
+            rsn_flags: result.rsn.as_ref().map_or(0, security_flags),
+            mode,
             max_bitrate: 54000, // Default to 54 Mbps
-            last_seen: chrono::Utc::now().timestamp(),
+            last_seen: chrono::Utc::now().timestamp() - age,
         }
     }
 
@@ -167,7 +153,7 @@ impl CRAccessPoint {
     /// Gets security type as a string
     pub fn get_security_type(&self) -> String {
         if self.rsn_flags != 0 {
-            if self.rsn_flags & 0x100 != 0 {
+            if self.rsn_flags & sec::KEY_MGMT_SAE != 0 {
                 "WPA3".to_string()
             } else {
                 "WPA2".to_string()
@@ -181,3 +167,51 @@ impl CRAccessPoint {
         }
     }
 }
+
+/// NM80211ApSecurityFlags values
+mod sec {
+    pub const PAIR_WEP40: u32 = 0x1;
+    pub const PAIR_WEP104: u32 = 0x2;
+    pub const PAIR_TKIP: u32 = 0x4;
+    pub const PAIR_CCMP: u32 = 0x8;
+    pub const GROUP_WEP40: u32 = 0x10;
+    pub const GROUP_WEP104: u32 = 0x20;
+    pub const GROUP_TKIP: u32 = 0x40;
+    pub const GROUP_CCMP: u32 = 0x80;
+    pub const KEY_MGMT_PSK: u32 = 0x100;
+    pub const KEY_MGMT_802_1X: u32 = 0x200;
+    pub const KEY_MGMT_SAE: u32 = 0x400;
+    pub const KEY_MGMT_OWE: u32 = 0x800;
+    pub const KEY_MGMT_EAP_SUITE_B_192: u32 = 0x2000;
+}
+
+/// WPA or RSN flags of a security element
+fn security_flags(info: &SecurityInfo) -> u32 {
+    let mut flags = match info.group_cipher {
+        CipherSuite::Wep40 => sec::GROUP_WEP40,
+        CipherSuite::Wep104 => sec::GROUP_WEP104,
+        CipherSuite::Tkip => sec::GROUP_TKIP,
+        CipherSuite::Ccmp => sec::GROUP_CCMP,
+        _ => 0,
+    };
+    for cipher in &info.pairwise_ciphers {
+        flags |= match cipher {
+            CipherSuite::Wep40 => sec::PAIR_WEP40,
+            CipherSuite::Wep104 => sec::PAIR_WEP104,
+            CipherSuite::Tkip => sec::PAIR_TKIP,
+            CipherSuite::Ccmp => sec::PAIR_CCMP,
+            _ => 0,
+        };
+    }
+    for akm in &info.akm_suites {
+        flags |= match akm {
+            AkmSuite::Psk | AkmSuite::FtPsk | AkmSuite::PskSha256 => sec::KEY_MGMT_PSK,
+            AkmSuite::Sae | AkmSuite::FtSae | AkmSuite::SaeExtKey | AkmSuite::FtSaeExtKey => sec::KEY_MGMT_SAE,
+            AkmSuite::Owe => sec::KEY_MGMT_OWE,
+            AkmSuite::SuiteB192 => sec::KEY_MGMT_EAP_SUITE_B_192,
+            akm if akm.is_eap() => sec::KEY_MGMT_802_1X,
+            _ => 0,
+        };
+    }
+    flags
+}
@@ -5,6 +5,7 @@ use crate::device::DeviceController;
 use crate::interface::InterfaceController;
 use crate::wifi::WifiController;
 use crate::dhcp_client::DhcpClientController;
+use crate::connection_config::{Ieee8021xSection, WifiSection, WifiSecuritySection};
 use crate::wpa_supplicant::WpaSupplicantController;
 use super::device::CRDevice;
 use super::connection::CRConnection;
@@ -182,12 +183,32 @@ impl CRClient {
                 info!("Connecting to WiFi network '{}' on {}", ssid, iface);
 
                 // Get WiFi security settings if present
-                let psk = connection.settings.get("wifi-security")
-                    .and_then(|s| s.properties.get("psk"))
-                    .map(|s| s.as_str());
+                let security = connection.settings.get("wifi-security").map(|s| &s.properties);
+                let security = match security {
+                    Some(props) => Some(WifiSecuritySection {
+                        key_mgmt: props.get("key-mgmt").cloned()
+                            .ok_or_else(|| NetctlError::InvalidParameter("wifi-security requires key-mgmt".to_string()))?,
+                        psk: props.get("psk").cloned(),
+                        password: props.get("password").cloned(),
+                        pmf: props.get("pmf").cloned(),
+                    }),
+                    None => None,
+                };
+                let ieee8021x = connection.settings.get("802-1x")
+                    .map(|s| Ieee8021xSection::from_nm_keys(&s.properties));
+
+                let wifi = WifiSection {
+                    ssid: ssid.clone(),
+                    mode: wireless_config.mode.clone(),
+                    bssid: wireless_config.bssid.clone(),
+                    channel: wireless_config.channel,
+                    band: wireless_config.band.clone(),
+                    hidden: wireless_config.hidden,
+                };
+                let network = wifi.wpa_network(security.as_ref(), ieee8021x.as_ref())?;
 
                 // Connect to WiFi
-                self.wpa_supplicant.connect(&iface, &ssid, psk).await?;
+                self.wpa_supplicant.connect_network(&iface, &network).await?;
 
                 info!("WiFi connected on {}", iface);
             }
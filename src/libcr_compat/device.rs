@@ -300,8 +300,8 @@ impl CRDevice {
             return Ok(vec![]);
         }
 
-        // Perform WiFi scan (which returns results directly)
-        let results = self.wifi_controller.scan(self.get_iface()).await?;
+        // Access points from the last scan; wifi_request_scan refreshes them
+        let results = self.wifi_controller.scan_results(self.get_iface()).await?;
         Ok(results.into_iter().map(CRAccessPoint::from_scan_result).collect())
     }
 
@@ -322,9 +322,9 @@ impl CRDevice {
             return None;
         }
 
-        // Get currently connected AP
-        // This would require querying the WiFi interface for current connection
-        None
+        self.wifi_controller.link(self.get_iface()).await.ok()
+            .flatten()
+            .map(|link| CRAccessPoint::from_scan_result(link.bss))
     }
 
     /// Gets device statistics (equivalent to nm_device_get_statistics)
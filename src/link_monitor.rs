@@ -9,6 +9,7 @@
 use crate::error::NetctlResult;
 use crate::dhcp_client::DhcpClientController;
 use crate::interface::InterfaceController;
+use crate::netns::NetNamespace;
 use crate::network_monitor::{NetworkMonitor, NetworkEvent};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -85,8 +86,24 @@ impl LinkMonitor {
         )
     }
 
+    /// Network namespace the monitored interfaces live in
+    ///
+    /// This is the namespace of the interface controller the monitor was
+    /// created with.
+    pub fn namespace(&self) -> &NetNamespace {
+        self.interface_controller.namespace()
+    }
+
     /// Set the network monitor to subscribe to for events
+    ///
+    /// The monitor must watch the same namespace as this link monitor,
+    /// otherwise interface names and indexes would not correspond.
     pub fn set_network_monitor(&mut self, monitor: Arc<NetworkMonitor>) {
+        if monitor.namespace() != self.namespace() {
+            warn!("Ignoring NetworkMonitor for namespace {} (link monitor is bound to {})",
+                  monitor.namespace(), self.namespace());
+            return;
+        }
         self.network_monitor = Some(monitor);
     }
 
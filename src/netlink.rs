@@ -0,0 +1,169 @@
+//! Shared rtnetlink plumbing
+//!
+//! Connection setup, interface lookup and errno mapping used by the
+//! controllers that talk to the kernel over NETLINK_ROUTE.
+
+use crate::error::{NetctlError, NetctlResult};
+use futures::stream::TryStreamExt;
+use netlink_packet_route::link::{LinkAttribute, LinkFlags, LinkMessage};
+use rtnetlink::Handle;
+use std::io;
+
+/// Open a new rtnetlink connection and spawn its driver task
+pub(crate) fn connect() -> NetctlResult<Handle> {
+    let (connection, handle, _) = rtnetlink::new_connection()
+        .map_err(|e| NetctlError::ServiceError(format!("Failed to create rtnetlink connection: {}", e)))?;
+    tokio::spawn(connection);
+    Ok(handle)
+}
+
+/// Look up the link message for an interface by name
+pub(crate) async fn get_link(handle: &Handle, interface: &str) -> NetctlResult<LinkMessage> {
+    let mut links = handle.link().get().match_name(interface.to_string()).execute();
+    match links.try_next().await {
+        Ok(Some(link)) => Ok(link),
+        Ok(None) => Err(NetctlError::InterfaceNotFound(interface.to_string())),
+        Err(e) => Err(map_error(e, "get link", interface)),
+    }
+}
+
+/// Resolve an interface name to its kernel index
+pub(crate) async fn link_index(handle: &Handle, interface: &str) -> NetctlResult<u32> {
+    Ok(get_link(handle, interface).await?.header.index)
+}
+
+/// Extract the interface name from a link message
+pub(crate) fn link_name(link: &LinkMessage) -> Option<String> {
+    link.attributes.iter().find_map(|attr| match attr {
+        LinkAttribute::IfName(name) => Some(name.clone()),
+        _ => None,
+    })
+}
+
+/// Render link flags using the same names as `ip link`
+pub(crate) fn flag_names(flags: LinkFlags) -> Vec<String> {
+    const NAMES: &[(LinkFlags, &str)] = &[
+        (LinkFlags::Loopback, "LOOPBACK"),
+        (LinkFlags::Broadcast, "BROADCAST"),
+        (LinkFlags::Pointopoint, "POINTOPOINT"),
+        (LinkFlags::Multicast, "MULTICAST"),
+        (LinkFlags::Noarp, "NOARP"),
+        (LinkFlags::Allmulti, "ALLMULTI"),
+        (LinkFlags::Promisc, "PROMISC"),
+        (LinkFlags::Controller, "MASTER"),
+        (LinkFlags::Port, "SLAVE"),
+        (LinkFlags::Debug, "DEBUG"),
+        (LinkFlags::Dynamic, "DYNAMIC"),
+        (LinkFlags::Automedia, "AUTOMEDIA"),
+        (LinkFlags::Portsel, "PORTSEL"),
+        (LinkFlags::Notrailers, "NOTRAILERS"),
+        (LinkFlags::Up, "UP"),
+        (LinkFlags::LowerUp, "LOWER_UP"),
+        (LinkFlags::Dormant, "DORMANT"),
+        (LinkFlags::Echo, "ECHO"),
+    ];
+
+    let mut names = Vec::new();
+    // ip(8) reports an administratively up link without carrier as NO-CARRIER
+    if flags.contains(LinkFlags::Up) && !flags.contains(LinkFlags::Running) {
+        names.push("NO-CARRIER".to_string());
+    }
+    for (flag, name) in NAMES {
+        if flags.contains(*flag) {
+            names.push((*name).to_string());
+        }
+    }
+    names
+}
+
+/// Parse a colon separated MAC address into raw bytes
+pub(crate) fn parse_mac(mac: &str) -> NetctlResult<Vec<u8>> {
+    mac.split(':')
+        .map(|octet| {
+            u8::from_str_radix(octet, 16)
+                .map_err(|_| NetctlError::InvalidParameter(format!("Invalid MAC address: {}", mac)))
+        })
+        .collect()
+}
+
+/// Format raw hardware address bytes as a colon separated string
+pub(crate) fn format_mac(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":")
+}
+
+/// Extract the errno carried by a netlink error, if any
+pub(crate) fn errno(err: &rtnetlink::Error) -> Option<i32> {
+    match err {
+        rtnetlink::Error::NetlinkError(msg) => msg.code.map(|code| -code.get()),
+        _ => None,
+    }
+}
+
+/// Map an rtnetlink error to a `NetctlError`
+///
+/// `op` describes the request (e.g. "add address") and `target` the object
+/// it was applied to, so that the resulting message is meaningful without
+/// the original netlink context.
+pub(crate) fn map_error(err: rtnetlink::Error, op: &str, target: &str) -> NetctlError {
+    match errno(&err) {
+        Some(code) => errno_error(code, op, target),
+        None => NetctlError::ServiceError(format!("Netlink {} failed for {}: {}", op, target, err)),
+    }
+}
+
+/// Map the errno of a failed netlink request to a `NetctlError`
+pub(crate) fn errno_error(code: i32, op: &str, target: &str) -> NetctlError {
+    let os_err = io::Error::from_raw_os_error(code);
+    match code {
+        libc::ENODEV => NetctlError::InterfaceNotFound(target.to_string()),
+        libc::EEXIST => NetctlError::AlreadyExists(format!("{}: {}", op, target)),
+        libc::EPERM | libc::EACCES => {
+            NetctlError::PermissionDenied(format!("{} on {}: {}", op, target, os_err))
+        }
+        libc::EINVAL | libc::ERANGE => {
+            NetctlError::InvalidParameter(format!("{} on {}: {}", op, target, os_err))
+        }
+        libc::ENOENT | libc::ESRCH | libc::EADDRNOTAVAIL => {
+            NetctlError::NotFound(format!("{} on {}: {}", op, target, os_err))
+        }
+        libc::EOPNOTSUPP => NetctlError::NotSupported(format!("{} on {}: {}", op, target, os_err)),
+        libc::EBUSY => NetctlError::InvalidState(format!("{} on {}: {}", op, target, os_err)),
+        _ => NetctlError::Io(io::Error::new(os_err.kind(), format!("{} on {}: {}", op, target, os_err))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use netlink_packet_core::ErrorMessage;
+    use std::num::NonZeroI32;
+
+    fn nl_error(errno: i32) -> rtnetlink::Error {
+        let mut msg = ErrorMessage::default();
+        msg.code = NonZeroI32::new(-errno);
+        rtnetlink::Error::NetlinkError(msg)
+    }
+
+    #[test]
+    fn test_map_error_errno() {
+        assert!(matches!(
+            map_error(nl_error(libc::ENODEV), "set link", "eth9"),
+            NetctlError::InterfaceNotFound(ref name) if name == "eth9"
+        ));
+        assert!(matches!(map_error(nl_error(libc::EEXIST), "add address", "eth0"), NetctlError::AlreadyExists(_)));
+        assert!(matches!(map_error(nl_error(libc::EPERM), "set link", "eth0"), NetctlError::PermissionDenied(_)));
+        assert!(matches!(map_error(nl_error(libc::EADDRNOTAVAIL), "del address", "eth0"), NetctlError::NotFound(_)));
+        assert!(matches!(map_error(rtnetlink::Error::RequestFailed, "get link", "eth0"), NetctlError::ServiceError(_)));
+    }
+
+    #[test]
+    fn test_flag_names_and_mac() {
+        let names = flag_names(LinkFlags::Up | LinkFlags::Loopback | LinkFlags::Running | LinkFlags::LowerUp);
+        assert_eq!(names, vec!["LOOPBACK", "UP", "LOWER_UP"]);
+        assert_eq!(flag_names(LinkFlags::Up)[0], "NO-CARRIER");
+
+        let mac = parse_mac("02:00:5e:10:aa:ff").unwrap();
+        assert_eq!(mac, vec![0x02, 0x00, 0x5e, 0x10, 0xaa, 0xff]);
+        assert_eq!(format_mac(&mac), "02:00:5e:10:aa:ff");
+    }
+}
@@ -0,0 +1,218 @@
+//! Network namespace support
+//!
+//! Controllers operate on the namespace netctl itself runs in unless they are
+//! bound to a [`NetNamespace`]. A bound controller opens its netlink sockets
+//! (and spawns any helper processes) inside that namespace, so the same API
+//! manages interfaces in containers or in named namespaces under /run/netns.
+
+use crate::error::{NetctlError, NetctlResult};
+use crate::validation;
+use rtnetlink::Handle;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use tracing::info;
+
+/// Directory holding named namespaces (same location as `ip netns`)
+pub const NETNS_RUN_DIR: &str = "/run/netns";
+
+/// Namespace file of the netctl process itself
+const SELF_NETNS_PATH: &str = "/proc/self/ns/net";
+
+/// A network namespace that controllers can be bound to
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetNamespace {
+    /// The namespace netctl runs in
+    #[default]
+    Current,
+    /// A named namespace under /run/netns
+    Named(String),
+    /// Any nsfs path, e.g. /proc/<pid>/ns/net of a container process
+    Path(PathBuf),
+}
+
+impl NetNamespace {
+    /// Refer to an existing named namespace
+    pub fn named(name: &str) -> NetctlResult<Self> {
+        validation::validate_netns_name(name)?;
+        Ok(NetNamespace::Named(name.to_string()))
+    }
+
+    /// Refer to the namespace of a running process
+    pub fn from_pid(pid: u32) -> Self {
+        NetNamespace::Path(PathBuf::from(format!("/proc/{}/ns/net", pid)))
+    }
+
+    /// Whether this is the namespace netctl runs in
+    pub fn is_current(&self) -> bool {
+        matches!(self, NetNamespace::Current)
+    }
+
+    /// Name of the namespace, if it is a named one
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            NetNamespace::Named(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Filesystem path of the namespace handle
+    pub fn path(&self) -> PathBuf {
+        match self {
+            NetNamespace::Current => PathBuf::from(SELF_NETNS_PATH),
+            NetNamespace::Named(name) => Path::new(NETNS_RUN_DIR).join(name),
+            NetNamespace::Path(path) => path.clone(),
+        }
+    }
+
+    /// Whether the namespace handle exists
+    pub fn exists(&self) -> bool {
+        self.path().exists()
+    }
+
+    /// Create a named namespace (equivalent to `ip netns add`)
+    pub async fn create(name: &str) -> NetctlResult<Self> {
+        validation::validate_netns_name(name)?;
+        let ns = NetNamespace::Named(name.to_string());
+        if ns.exists() {
+            return Err(NetctlError::AlreadyExists(format!("Network namespace {}", name)));
+        }
+
+        rtnetlink::NetworkNamespace::add(name.to_string()).await
+            .map_err(|e| NetctlError::ServiceError(format!("Failed to create namespace {}: {}", name, e)))?;
+
+        info!("Created network namespace {}", name);
+        Ok(ns)
+    }
+
+    /// Delete a named namespace (equivalent to `ip netns delete`)
+    ///
+    /// Interfaces still inside the namespace are destroyed (virtual) or
+    /// returned to the initial namespace (physical) by the kernel once the
+    /// last reference goes away.
+    pub async fn delete(name: &str) -> NetctlResult<()> {
+        validation::validate_netns_name(name)?;
+        let ns = NetNamespace::Named(name.to_string());
+        if !ns.exists() {
+            return Err(NetctlError::NotFound(format!("Network namespace {}", name)));
+        }
+
+        rtnetlink::NetworkNamespace::del(name.to_string()).await
+            .map_err(|e| NetctlError::ServiceError(format!("Failed to delete namespace {}: {}", name, e)))?;
+
+        info!("Deleted network namespace {}", name);
+        Ok(())
+    }
+
+    /// List named namespaces
+    pub async fn list() -> NetctlResult<Vec<String>> {
+        let mut names = Vec::new();
+        let mut entries = match tokio::fs::read_dir(NETNS_RUN_DIR).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(names),
+            Err(e) => return Err(e.into()),
+        };
+
+        while let Some(entry) = entries.next_entry().await? {
+            if let Some(name) = entry.file_name().to_str() {
+                names.push(name.to_string());
+            }
+        }
+
+        names.sort();
+        Ok(names)
+    }
+
+    /// Open the namespace handle
+    pub(crate) fn open(&self) -> NetctlResult<File> {
+        File::open(self.path()).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => NetctlError::NotFound(format!("Network namespace {}", self)),
+            std::io::ErrorKind::PermissionDenied => {
+                NetctlError::PermissionDenied(format!("Cannot open network namespace {}", self))
+            }
+            _ => NetctlError::Io(e),
+        })
+    }
+
+    /// Open an rtnetlink connection inside this namespace
+    pub(crate) fn connect(&self) -> NetctlResult<Handle> {
+        self.run_in(crate::netlink::connect)
+    }
+
+    /// Open a raw netlink socket inside this namespace
+    pub(crate) fn socket(&self, protocol: isize) -> NetctlResult<netlink_sys::Socket> {
+        self.run_in(|| {
+            netlink_sys::Socket::new(protocol)
+                .map_err(|e| NetctlError::ServiceError(format!("Failed to create netlink socket: {}", e)))
+        })
+    }
+
+    /// Build a command that executes inside this namespace
+    pub fn command(&self, program: &str) -> NetctlResult<Command> {
+        let mut cmd = Command::new(program);
+        if !self.is_current() {
+            let ns = self.open()?;
+            // SAFETY: the closure only issues the setns(2) syscall, which is
+            // async-signal-safe and allowed between fork and exec
+            unsafe {
+                cmd.pre_exec(move || {
+                    if libc::setns(ns.as_raw_fd(), libc::CLONE_NEWNET) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+        }
+        Ok(cmd)
+    }
+
+    /// Run `f` on a helper thread that has entered this namespace
+    ///
+    /// Sockets keep the namespace they were created in, so anything opened by
+    /// `f` remains bound to the namespace after the thread exits. The tokio
+    /// runtime is entered on the helper thread so async sockets can register
+    /// with the reactor.
+    pub(crate) fn run_in<F, R>(&self, f: F) -> NetctlResult<R>
+    where
+        F: FnOnce() -> NetctlResult<R> + Send,
+        R: Send,
+    {
+        if self.is_current() {
+            return f();
+        }
+
+        let ns = self.open()?;
+        let runtime = tokio::runtime::Handle::try_current().ok();
+
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let _guard = runtime.as_ref().map(|rt| rt.enter());
+                    if unsafe { libc::setns(ns.as_raw_fd(), libc::CLONE_NEWNET) } != 0 {
+                        let err = std::io::Error::last_os_error();
+                        return Err(if err.kind() == std::io::ErrorKind::PermissionDenied {
+                            NetctlError::PermissionDenied(format!("Cannot enter network namespace {}: {}", self, err))
+                        } else {
+                            NetctlError::Io(err)
+                        });
+                    }
+                    f()
+                })
+                .join()
+                .map_err(|_| NetctlError::ServiceError("Namespace helper thread panicked".to_string()))?
+        })
+    }
+}
+
+impl std::fmt::Display for NetNamespace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetNamespace::Current => write!(f, "current"),
+            NetNamespace::Named(name) => write!(f, "{}", name),
+            NetNamespace::Path(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
@@ -4,6 +4,8 @@
 //! and propagates them to D-Bus and other subscribers.
 
 use crate::error::{NetctlError, NetctlResult};
+use crate::netns::NetNamespace;
+use crate::routing::Route;
 use std::sync::Arc;
 use tokio::sync::broadcast;
 use tracing::{info, warn, error, debug};
@@ -38,6 +40,22 @@ pub enum NetworkEvent {
         index: u32,
         name: String,
     },
+    /// Route added to (or replaced in) any routing table
+    RouteAdded {
+        route: Route,
+    },
+    /// Route removed from any routing table
+    RouteRemoved {
+        route: Route,
+    },
+    /// Another host claimed an address configured on the interface
+    AddressConflict {
+        index: u32,
+        name: String,
+        address: String,
+        /// Hardware address of the other host
+        mac: String,
+    },
 }
 
 /// Network monitor that watches for interface changes
@@ -46,6 +64,8 @@ pub struct NetworkMonitor {
     event_tx: broadcast::Sender<NetworkEvent>,
     /// Running flag
     running: Arc<tokio::sync::RwLock<bool>>,
+    /// Network namespace being monitored
+    namespace: NetNamespace,
 }
 
 impl NetworkMonitor {
@@ -55,14 +75,33 @@ impl NetworkMonitor {
         Self {
             event_tx,
             running: Arc::new(tokio::sync::RwLock::new(false)),
+            namespace: NetNamespace::Current,
         }
     }
 
+    /// Create a monitor that watches a specific network namespace
+    pub fn with_namespace(namespace: NetNamespace) -> Self {
+        Self {
+            namespace,
+            ..Self::new()
+        }
+    }
+
+    /// Network namespace being monitored
+    pub fn namespace(&self) -> &NetNamespace {
+        &self.namespace
+    }
+
     /// Subscribe to network events
     pub fn subscribe(&self) -> broadcast::Receiver<NetworkEvent> {
         self.event_tx.subscribe()
     }
 
+    /// Publish an event detected outside of netlink to the subscribers
+    pub fn emit(&self, event: NetworkEvent) {
+        let _ = self.event_tx.send(event);
+    }
+
     /// Start monitoring network events
     pub async fn start(&self) -> NetctlResult<()> {
         let mut running = self.running.write().await;
@@ -72,14 +111,15 @@ impl NetworkMonitor {
         *running = true;
         drop(running);
 
-        info!("Starting network event monitor");
+        info!("Starting network event monitor (namespace: {})", self.namespace);
 
         let event_tx = self.event_tx.clone();
         let running = self.running.clone();
+        let namespace = self.namespace.clone();
 
         // Spawn monitoring task
         tokio::spawn(async move {
-            if let Err(e) = Self::monitor_loop(event_tx, running).await {
+            if let Err(e) = Self::monitor_loop(event_tx, running, namespace).await {
                 error!("Network monitor error: {}", e);
             }
         });
@@ -99,11 +139,17 @@ impl NetworkMonitor {
     async fn monitor_loop(
         event_tx: broadcast::Sender<NetworkEvent>,
         running: Arc<tokio::sync::RwLock<bool>>,
+        namespace: NetNamespace,
     ) -> NetctlResult<()> {
         // Try to use rtnetlink for real netlink monitoring
         #[cfg(target_os = "linux")]
         {
-            if let Err(e) = Self::monitor_with_rtnetlink_events(event_tx.clone(), running.clone()).await {
+            if let Err(e) = Self::monitor_with_rtnetlink_events(event_tx.clone(), running.clone(), &namespace).await {
+                // sysfs only reflects the namespace it was mounted in, so
+                // polling cannot stand in for netlink in another namespace
+                if !namespace.is_current() {
+                    return Err(e);
+                }
                 warn!("rtnetlink event monitoring failed: {}, falling back to polling", e);
                 Self::monitor_with_polling(event_tx, running).await?;
             }
@@ -111,6 +157,9 @@ impl NetworkMonitor {
 
         #[cfg(not(target_os = "linux"))]
         {
+            if !namespace.is_current() {
+                return Err(NetctlError::NotSupported("Network namespaces require Linux".to_string()));
+            }
             // Fall back to polling on non-Linux systems
             Self::monitor_with_polling(event_tx, running).await?;
         }
@@ -123,13 +172,13 @@ impl NetworkMonitor {
     async fn monitor_with_rtnetlink_events(
         event_tx: broadcast::Sender<NetworkEvent>,
         running: Arc<tokio::sync::RwLock<bool>>,
+        namespace: &NetNamespace,
     ) -> NetctlResult<()> {
         use futures::stream::TryStreamExt;
-        use netlink_sys::{protocols::NETLINK_ROUTE, Socket, SocketAddr};
+        use netlink_sys::{protocols::NETLINK_ROUTE, SocketAddr};
 
         // Create a netlink socket and join multicast groups for link events
-        let mut socket = Socket::new(NETLINK_ROUTE)
-            .map_err(|e| NetctlError::ServiceError(format!("Failed to create netlink socket: {}", e)))?;
+        let mut socket = namespace.socket(NETLINK_ROUTE)?;
 
         // Bind to the socket
         let kernel_addr = SocketAddr::new(0, 0);
@@ -147,17 +196,22 @@ impl NetworkMonitor {
         socket.add_membership(RTNLGRP_IPV4_IFADDR)
             .map_err(|e| NetctlError::ServiceError(format!("Failed to join RTNLGRP_IPV4_IFADDR: {}", e)))?;
 
+        // And the IPv4/IPv6 route groups (7 and 11) for routing table changes
+        const RTNLGRP_IPV4_ROUTE: u32 = 7;
+        const RTNLGRP_IPV6_ROUTE: u32 = 11;
+        for group in [RTNLGRP_IPV4_ROUTE, RTNLGRP_IPV6_ROUTE] {
+            socket.add_membership(group)
+                .map_err(|e| NetctlError::ServiceError(format!("Failed to join route group {}: {}", group, e)))?;
+        }
+
         // Set socket to non-blocking for async operation
         socket.set_non_blocking(true)
             .map_err(|e| NetctlError::ServiceError(format!("Failed to set non-blocking: {}", e)))?;
 
-        info!("Using rtnetlink events for network monitoring (joined RTNLGRP_LINK, RTNLGRP_IPV4_IFADDR)");
+        info!("Using rtnetlink events for network monitoring (joined RTNLGRP_LINK, RTNLGRP_IPV4_IFADDR, RTNLGRP_IPV4_ROUTE, RTNLGRP_IPV6_ROUTE)");
 
         // Also create an rtnetlink handle for querying initial state
-        let (connection, handle, _) = rtnetlink::new_connection()
-            .map_err(|e| NetctlError::ServiceError(format!("Failed to create rtnetlink connection: {}", e)))?;
-
-        tokio::spawn(connection);
+        let handle = namespace.connect()?;
 
         // Track interfaces: index -> (name, is_up)
         let mut known_interfaces: std::collections::HashMap<u32, (String, bool)> = std::collections::HashMap::new();
@@ -173,8 +227,10 @@ impl NetworkMonitor {
             }
         }
 
-        // Buffer for receiving netlink messages
-        let mut buf = vec![0u8; 16384];
+        // Buffer for receiving netlink messages. Socket::recv fills the
+        // spare capacity of the buffer, so it starts empty and is cleared
+        // before every read.
+        let mut buf: Vec<u8> = Vec::with_capacity(16384);
 
         // Convert socket to async using tokio
         let async_fd = tokio::io::unix::AsyncFd::new(socket)
@@ -200,41 +256,47 @@ impl NetworkMonitor {
                 Err(_) => {
                     // Timeout - check if we need to run periodic validation
                     if last_validation.elapsed().as_secs() >= VALIDATION_INTERVAL_SECS {
-                        validate_interface_states(&mut known_interfaces, &event_tx).await;
+                        validate_interface_states(&handle, &mut known_interfaces, &event_tx).await;
                         last_validation = std::time::Instant::now();
                     }
                     continue;
                 }
             };
 
-            // Try to receive data
-            match guard.get_inner().recv(&mut buf, 0) {
-                Ok(len) if len > 0 => {
-                    // Parse the netlink messages
-                    if let Err(e) = process_netlink_messages(
-                        &buf[..len],
-                        &mut known_interfaces,
-                        &event_tx,
-                    ) {
-                        warn!("Error processing netlink message: {}", e);
+            // Drain every queued datagram; readiness is edge-triggered, so
+            // it may only be cleared once the socket reports WouldBlock
+            loop {
+                buf.clear();
+                match guard.get_inner().recv(&mut buf, 0) {
+                    Ok(len) if len > 0 => {
+                        // Parse the netlink messages
+                        if let Err(e) = process_netlink_messages(
+                            &buf,
+                            &mut known_interfaces,
+                            &event_tx,
+                        ) {
+                            warn!("Error processing netlink message: {}", e);
+                        }
+                    }
+                    Ok(_) => {
+                        // No data, continue
+                        break;
+                    }
+                    Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                        // No data available, this is expected for non-blocking
+                        guard.clear_ready();
+                        break;
+                    }
+                    Err(e) => {
+                        warn!("Error receiving netlink message: {}", e);
+                        break;
                     }
-                }
-                Ok(_) => {
-                    // No data, continue
-                }
-                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                    // No data available, this is expected for non-blocking
-                }
-                Err(e) => {
-                    warn!("Error receiving netlink message: {}", e);
                 }
             }
 
-            guard.clear_ready();
-
             // Also check validation timer after processing messages
             if last_validation.elapsed().as_secs() >= VALIDATION_INTERVAL_SECS {
-                validate_interface_states(&mut known_interfaces, &event_tx).await;
+                validate_interface_states(&handle, &mut known_interfaces, &event_tx).await;
                 last_validation = std::time::Instant::now();
             }
         }
@@ -374,9 +436,15 @@ fn process_netlink_messages(
     use netlink_packet_route::RouteNetlinkMessage;
 
     let mut offset = 0;
-    while offset < data.len() {
-        // Parse the netlink message header
-        let msg: NetlinkMessage<RouteNetlinkMessage> = match NetlinkMessage::deserialize(&data[offset..]) {
+    while offset + 4 <= data.len() {
+        // A datagram can carry several messages; each one starts with its
+        // total length (nlmsg_len) and must be parsed on its own
+        let msg_len = u32::from_ne_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]) as usize;
+        if msg_len == 0 || offset + msg_len > data.len() {
+            break;
+        }
+
+        let msg: NetlinkMessage<RouteNetlinkMessage> = match NetlinkMessage::deserialize(&data[offset..offset + msg_len]) {
             Ok(msg) => msg,
             Err(e) => {
                 debug!("Failed to parse netlink message: {}", e);
@@ -384,11 +452,6 @@ fn process_netlink_messages(
             }
         };
 
-        let msg_len = msg.header.length as usize;
-        if msg_len == 0 {
-            break;
-        }
-
         match msg.payload {
             NetlinkPayload::InnerMessage(RouteNetlinkMessage::NewLink(link)) => {
                 if let Some(name) = extract_interface_name(&link) {
@@ -468,103 +531,116 @@ fn process_netlink_messages(
                     }
                 }
             }
+            NetlinkPayload::InnerMessage(RouteNetlinkMessage::NewRoute(msg)) => {
+                if let Some(route) = monitored_route(&msg, known_interfaces) {
+                    debug!("Route added: {}", route);
+                    let _ = event_tx.send(NetworkEvent::RouteAdded { route });
+                }
+            }
+            NetlinkPayload::InnerMessage(RouteNetlinkMessage::DelRoute(msg)) => {
+                if let Some(route) = monitored_route(&msg, known_interfaces) {
+                    debug!("Route removed: {}", route);
+                    let _ = event_tx.send(NetworkEvent::RouteRemoved { route });
+                }
+            }
             _ => {
                 // Ignore other message types
             }
         }
 
-        offset += msg_len;
+        // Messages are padded to 4-byte boundaries (NLMSG_ALIGN)
+        offset += (msg_len + 3) & !3;
     }
 
     Ok(())
 }
 
-/// Periodic validation of interface states against sysfs
+/// Convert a route notification, skipping IPv6 multicast routes (the same
+/// entries `RoutingController::list_routes` leaves out)
+#[cfg(target_os = "linux")]
+fn monitored_route(
+    msg: &netlink_packet_route::route::RouteMessage,
+    known_interfaces: &std::collections::HashMap<u32, (String, bool)>,
+) -> Option<Route> {
+    let route = crate::routing::route_from_message(msg, |index| {
+        known_interfaces.get(&index).map(|(name, _)| name.clone())
+    })?;
+    if route.is_ipv6() && route.kind == crate::routing::RouteType::Multicast {
+        return None;
+    }
+    Some(route)
+}
+
+/// Periodic validation of interface states against a fresh link dump
 /// This catches any missed netlink events
 #[cfg(target_os = "linux")]
 async fn validate_interface_states(
+    handle: &rtnetlink::Handle,
     known_interfaces: &mut std::collections::HashMap<u32, (String, bool)>,
     event_tx: &broadcast::Sender<NetworkEvent>,
 ) {
+    use futures::stream::TryStreamExt;
+
     debug!("Running periodic interface state validation");
 
-    // Read current interfaces from sysfs
-    let entries = match tokio::fs::read_dir("/sys/class/net").await {
-        Ok(e) => e,
-        Err(e) => {
-            warn!("Failed to read /sys/class/net for validation: {}", e);
-            return;
-        }
-    };
-
-    let mut entries = entries;
-    let mut current_interfaces: std::collections::HashMap<String, bool> = std::collections::HashMap::new();
-
-    while let Ok(Some(entry)) = entries.next_entry().await {
-        if let Ok(name) = entry.file_name().into_string() {
-            // Read operstate
-            let operstate_path = format!("/sys/class/net/{}/operstate", name);
-            let is_up = match tokio::fs::read_to_string(&operstate_path).await {
-                Ok(state) => state.trim() == "up",
-                Err(_) => false,
-            };
-            current_interfaces.insert(name, is_up);
+    // Dump the current links over netlink; unlike sysfs this reflects the
+    // namespace the monitor is bound to
+    let mut current_interfaces: std::collections::HashMap<u32, (String, bool)> = std::collections::HashMap::new();
+    let mut links = handle.link().get().execute();
+    loop {
+        match links.try_next().await {
+            Ok(Some(link)) => {
+                if let Some(name) = extract_interface_name(&link) {
+                    current_interfaces.insert(link.header.index, (name, extract_operstate(&link)));
+                }
+            }
+            Ok(None) => break,
+            Err(e) => {
+                warn!("Failed to dump links for validation: {}", e);
+                return;
+            }
         }
     }
 
     // Check for discrepancies with known_interfaces
     for (index, (name, known_is_up)) in known_interfaces.iter_mut() {
-        if let Some(&actual_is_up) = current_interfaces.get(name) {
-            if *known_is_up != actual_is_up {
+        if let Some((_, actual_is_up)) = current_interfaces.get(index) {
+            if *known_is_up != *actual_is_up {
                 warn!("Validation: Interface {} state mismatch (known: {}, actual: {}), emitting event",
                       name,
                       if *known_is_up { "up" } else { "down" },
-                      if actual_is_up { "up" } else { "down" });
+                      if *actual_is_up { "up" } else { "down" });
 
                 // Update our state
-                *known_is_up = actual_is_up;
+                *known_is_up = *actual_is_up;
 
                 // Emit the missed event
                 let _ = event_tx.send(NetworkEvent::InterfaceStateChanged {
                     index: *index,
                     name: name.clone(),
-                    is_up: actual_is_up,
+                    is_up: *actual_is_up,
                 });
             }
         }
     }
 
     // Check for new interfaces we don't know about
-    let known_names: std::collections::HashSet<String> = known_interfaces
-        .values()
-        .map(|(name, _)| name.clone())
-        .collect();
-
-    for (name, is_up) in &current_interfaces {
-        if !known_names.contains(name) {
-            // Try to get the interface index from sysfs
-            let ifindex_path = format!("/sys/class/net/{}/ifindex", name);
-            let index = match tokio::fs::read_to_string(&ifindex_path).await {
-                Ok(idx_str) => idx_str.trim().parse::<u32>().unwrap_or(0),
-                Err(_) => 0,
-            };
-
-            if index > 0 {
-                info!("Validation: Found new interface {} (index {})", name, index);
-                known_interfaces.insert(index, (name.clone(), *is_up));
-                let _ = event_tx.send(NetworkEvent::InterfaceAdded {
-                    index,
-                    name: name.clone(),
-                });
-            }
+    for (index, (name, is_up)) in &current_interfaces {
+        if !known_interfaces.contains_key(index) {
+            info!("Validation: Found new interface {} (index {})", name, index);
+            known_interfaces.insert(*index, (name.clone(), *is_up));
+            let _ = event_tx.send(NetworkEvent::InterfaceAdded {
+                index: *index,
+                name: name.clone(),
+            });
         }
     }
 
     // Check for removed interfaces
     let removed: Vec<u32> = known_interfaces
-        .iter()
-        .filter(|(_, (name, _))| !current_interfaces.contains_key(name))
-        .map(|(idx, _)| *idx)
+        .keys()
+        .filter(|idx| !current_interfaces.contains_key(idx))
+        .copied()
         .collect();
 
     for index in removed {
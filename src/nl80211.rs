@@ -0,0 +1,363 @@
+//! nl80211 commands
+//!
+//! The subset of the nl80211 generic netlink family used by
+//! [`WifiController`](crate::wifi::WifiController): interface information,
+//! transmit power, power save, scans and station statistics. Replies are
+//! decoded into the public types of the wifi module.
+
+use crate::error::{NetctlError, NetctlResult};
+use crate::genl::{AttrMap, Attrs, Family, GenlSocket};
+use crate::netlink::{format_mac, parse_mac};
+use crate::netns::NetNamespace;
+use crate::wifi::{self, RateInfo, RateMode, ScanResult, StationInfo, TxPower, WifiDeviceInfo};
+
+const FAMILY_NAME: &str = "nl80211";
+const SCAN_GROUP: &str = "scan";
+
+/// Commands
+pub mod cmd {
+    pub const SET_WIPHY: u8 = 2;
+    pub const GET_INTERFACE: u8 = 5;
+    pub const GET_STATION: u8 = 17;
+    pub const GET_SCAN: u8 = 32;
+    pub const TRIGGER_SCAN: u8 = 33;
+    pub const NEW_SCAN_RESULTS: u8 = 34;
+    pub const SCAN_ABORTED: u8 = 35;
+    pub const SET_POWER_SAVE: u8 = 61;
+    pub const GET_POWER_SAVE: u8 = 62;
+}
+
+/// Top-level attributes
+pub mod attr {
+    pub const WIPHY: u16 = 1;
+    pub const IFINDEX: u16 = 3;
+    pub const IFTYPE: u16 = 5;
+    pub const MAC: u16 = 6;
+    pub const STA_INFO: u16 = 21;
+    pub const WIPHY_FREQ: u16 = 38;
+    pub const SCAN_SSIDS: u16 = 45;
+    pub const BSS: u16 = 47;
+    pub const SSID: u16 = 52;
+    pub const PS_STATE: u16 = 93;
+    pub const WIPHY_TX_POWER_SETTING: u16 = 97;
+    pub const WIPHY_TX_POWER_LEVEL: u16 = 98;
+    pub const CHANNEL_WIDTH: u16 = 159;
+}
+
+/// Attributes nested in [`attr::BSS`]
+pub mod bss {
+    pub const BSSID: u16 = 1;
+    pub const FREQUENCY: u16 = 2;
+    pub const BEACON_INTERVAL: u16 = 4;
+    pub const CAPABILITY: u16 = 5;
+    pub const INFORMATION_ELEMENTS: u16 = 6;
+    pub const SIGNAL_MBM: u16 = 7;
+    pub const STATUS: u16 = 9;
+    pub const SEEN_MS_AGO: u16 = 10;
+    pub const BEACON_IES: u16 = 11;
+
+    pub const STATUS_ASSOCIATED: u32 = 1;
+}
+
+/// Attributes nested in [`attr::STA_INFO`]
+pub mod sta {
+    pub const INACTIVE_TIME: u16 = 1;
+    pub const RX_BYTES: u16 = 2;
+    pub const TX_BYTES: u16 = 3;
+    pub const SIGNAL: u16 = 7;
+    pub const TX_BITRATE: u16 = 8;
+    pub const RX_PACKETS: u16 = 9;
+    pub const TX_PACKETS: u16 = 10;
+    pub const TX_RETRIES: u16 = 11;
+    pub const TX_FAILED: u16 = 12;
+    pub const SIGNAL_AVG: u16 = 13;
+    pub const RX_BITRATE: u16 = 14;
+    pub const CONNECTED_TIME: u16 = 16;
+    pub const BEACON_LOSS: u16 = 18;
+    pub const RX_BYTES64: u16 = 23;
+    pub const TX_BYTES64: u16 = 24;
+    pub const BEACON_SIGNAL_AVG: u16 = 30;
+}
+
+/// Attributes nested in the bitrate attributes of [`sta`]
+pub mod rate {
+    pub const BITRATE: u16 = 1;
+    pub const MCS: u16 = 2;
+    pub const WIDTH_40: u16 = 3;
+    pub const SHORT_GI: u16 = 4;
+    pub const BITRATE32: u16 = 5;
+    pub const VHT_MCS: u16 = 6;
+    pub const VHT_NSS: u16 = 7;
+    pub const WIDTH_80: u16 = 8;
+    pub const WIDTH_80P80: u16 = 9;
+    pub const WIDTH_160: u16 = 10;
+    pub const HE_MCS: u16 = 13;
+    pub const HE_NSS: u16 = 14;
+}
+
+/// An nl80211 socket
+pub(crate) struct Nl80211 {
+    socket: GenlSocket,
+    family: Family,
+}
+
+impl Nl80211 {
+    pub async fn open(namespace: &NetNamespace) -> NetctlResult<Self> {
+        let mut socket = GenlSocket::open(namespace)?;
+        let family = socket.resolve(FAMILY_NAME).await?;
+        Ok(Self { socket, family })
+    }
+
+    /// Receive scan events on this socket
+    pub fn join_scan_events(&self) -> NetctlResult<()> {
+        let group = self.family.groups.get(SCAN_GROUP)
+            .ok_or_else(|| NetctlError::NotSupported("nl80211 has no scan multicast group".to_string()))?;
+        self.socket.join(*group)
+    }
+
+    /// Wait until a scan on `ifindex` finishes
+    pub async fn wait_scan_done(&mut self, ifindex: u32, interface: &str) -> NetctlResult<()> {
+        loop {
+            let event = self.socket.event().await?;
+            if event.attrs().u32(attr::IFINDEX) != Some(ifindex) {
+                continue;
+            }
+            match event.cmd {
+                cmd::NEW_SCAN_RESULTS => return Ok(()),
+                cmd::SCAN_ABORTED => return Err(NetctlError::InvalidState(format!("Scan on {} was aborted", interface))),
+                _ => {}
+            }
+        }
+    }
+
+    /// Start an active scan for any SSID
+    pub async fn trigger_scan(&mut self, ifindex: u32, interface: &str) -> NetctlResult<()> {
+        let mut ssids = Attrs::new();
+        ssids.put(1, b"");
+        let mut attrs = Attrs::new();
+        attrs.put_u32(attr::IFINDEX, ifindex).put_nested(attr::SCAN_SSIDS, &ssids);
+        self.request(cmd::TRIGGER_SCAN, false, &attrs, "trigger scan", interface).await.map(|_| ())
+    }
+
+    /// BSSs in the scan cache of `ifindex`
+    pub async fn get_scan(&mut self, ifindex: u32, interface: &str) -> NetctlResult<Vec<ScanResult>> {
+        let mut attrs = Attrs::new();
+        attrs.put_u32(attr::IFINDEX, ifindex);
+        let replies = self.request(cmd::GET_SCAN, true, &attrs, "get scan results", interface).await?;
+        Ok(replies.iter().filter_map(|reply| parse_bss(&reply.attrs().nested(attr::BSS)?)).collect())
+    }
+
+    pub async fn get_interface(&mut self, ifindex: u32, interface: &str) -> NetctlResult<WifiDeviceInfo> {
+        let mut attrs = Attrs::new();
+        attrs.put_u32(attr::IFINDEX, ifindex);
+        let replies = self.request(cmd::GET_INTERFACE, false, &attrs, "get interface", interface).await?;
+        let reply = replies.first()
+            .ok_or_else(|| NetctlError::NotSupported(format!("{} is not a wireless interface", interface)))?;
+        let attrs = reply.attrs();
+        let wiphy = attrs.u32(attr::WIPHY);
+        let frequency = attrs.u32(attr::WIPHY_FREQ);
+        Ok(WifiDeviceInfo {
+            interface: interface.to_string(),
+            phy: wiphy.map(|wiphy| format!("phy{}", wiphy)),
+            type_: attrs.u32(attr::IFTYPE).map(iftype_name),
+            wiphy,
+            channel: frequency.and_then(wifi::frequency_to_channel),
+            frequency,
+            channel_width: attrs.u32(attr::CHANNEL_WIDTH).and_then(channel_width_mhz),
+            txpower: attrs.i32(attr::WIPHY_TX_POWER_LEVEL)
+                .map(|mbm| format!("{:.2} dBm", mbm as f64 / 100.0)),
+            ssid: attrs.get(attr::SSID).map(|ssid| String::from_utf8_lossy(ssid).into_owned()),
+            mac: attrs.get(attr::MAC).map(format_mac),
+        })
+    }
+
+    pub async fn set_tx_power(&mut self, ifindex: u32, interface: &str, power: TxPower) -> NetctlResult<()> {
+        let mut attrs = Attrs::new();
+        attrs.put_u32(attr::IFINDEX, ifindex);
+        match power {
+            TxPower::Auto => attrs.put_u32(attr::WIPHY_TX_POWER_SETTING, 0),
+            TxPower::Limit(mbm) => attrs.put_u32(attr::WIPHY_TX_POWER_SETTING, 1)
+                .put_u32(attr::WIPHY_TX_POWER_LEVEL, mbm as u32),
+            TxPower::Fixed(mbm) => attrs.put_u32(attr::WIPHY_TX_POWER_SETTING, 2)
+                .put_u32(attr::WIPHY_TX_POWER_LEVEL, mbm as u32),
+        };
+        self.request(cmd::SET_WIPHY, false, &attrs, "set TX power", interface).await.map(|_| ())
+    }
+
+    pub async fn set_power_save(&mut self, ifindex: u32, interface: &str, enable: bool) -> NetctlResult<()> {
+        let mut attrs = Attrs::new();
+        attrs.put_u32(attr::IFINDEX, ifindex).put_u32(attr::PS_STATE, enable as u32);
+        self.request(cmd::SET_POWER_SAVE, false, &attrs, "set power save", interface).await.map(|_| ())
+    }
+
+    pub async fn get_power_save(&mut self, ifindex: u32, interface: &str) -> NetctlResult<bool> {
+        let mut attrs = Attrs::new();
+        attrs.put_u32(attr::IFINDEX, ifindex);
+        let replies = self.request(cmd::GET_POWER_SAVE, false, &attrs, "get power save", interface).await?;
+        replies.first()
+            .and_then(|reply| reply.attrs().u32(attr::PS_STATE))
+            .map(|state| state != 0)
+            .ok_or_else(|| NetctlError::NotSupported("Power save state not available".to_string()))
+    }
+
+    pub async fn get_station(&mut self, ifindex: u32, interface: &str, mac: &str) -> NetctlResult<StationInfo> {
+        let mut attrs = Attrs::new();
+        attrs.put_u32(attr::IFINDEX, ifindex).put(attr::MAC, &parse_mac(mac)?);
+        let replies = self.request(cmd::GET_STATION, false, &attrs, "get station", interface).await?;
+        replies.first()
+            .and_then(|reply| Some(parse_station(mac, &reply.attrs().nested(attr::STA_INFO)?)))
+            .ok_or_else(|| NetctlError::NotFound(format!("Station {} on {}", mac, interface)))
+    }
+
+    async fn request(&mut self, cmd: u8, dump: bool, attrs: &Attrs, op: &str, target: &str) -> NetctlResult<Vec<crate::genl::GenlMessage>> {
+        self.socket.request(self.family.id, cmd, dump, attrs, op, target).await
+    }
+}
+
+fn parse_bss(attrs: &AttrMap<'_>) -> Option<ScanResult> {
+    let frequency = attrs.u32(bss::FREQUENCY);
+    let mut result = ScanResult {
+        bssid: format_mac(attrs.get(bss::BSSID)?),
+        frequency,
+        channel: frequency.and_then(wifi::frequency_to_channel),
+        signal: attrs.i32(bss::SIGNAL_MBM).map(|mbm| mbm / 100),
+        capabilities: attrs.u16(bss::CAPABILITY).map(wifi::capability_names).unwrap_or_default(),
+        beacon_interval: attrs.u16(bss::BEACON_INTERVAL),
+        age_ms: attrs.u32(bss::SEEN_MS_AGO),
+        associated: attrs.u32(bss::STATUS) == Some(bss::STATUS_ASSOCIATED),
+        channel_width: Some(20),
+        ..Default::default()
+    };
+    // Probe response IEs are more complete; beacons fill in when there are none
+    if let Some(ies) = attrs.get(bss::INFORMATION_ELEMENTS).or_else(|| attrs.get(bss::BEACON_IES)) {
+        result.apply_ies(ies);
+    }
+    Some(result)
+}
+
+fn parse_station(mac: &str, attrs: &AttrMap<'_>) -> StationInfo {
+    let dbm = |kind| attrs.u8(kind).map(|signal| signal as i8 as i32);
+    StationInfo {
+        mac: mac.to_string(),
+        inactive_ms: attrs.u32(sta::INACTIVE_TIME),
+        connected_secs: attrs.u32(sta::CONNECTED_TIME),
+        rx_bytes: attrs.u64(sta::RX_BYTES64).or_else(|| attrs.u32(sta::RX_BYTES).map(u64::from)),
+        tx_bytes: attrs.u64(sta::TX_BYTES64).or_else(|| attrs.u32(sta::TX_BYTES).map(u64::from)),
+        rx_packets: attrs.u32(sta::RX_PACKETS),
+        tx_packets: attrs.u32(sta::TX_PACKETS),
+        tx_retries: attrs.u32(sta::TX_RETRIES),
+        tx_failed: attrs.u32(sta::TX_FAILED),
+        beacon_loss: attrs.u32(sta::BEACON_LOSS),
+        signal: dbm(sta::SIGNAL),
+        signal_avg: dbm(sta::SIGNAL_AVG),
+        beacon_signal_avg: dbm(sta::BEACON_SIGNAL_AVG),
+        tx_bitrate: attrs.nested(sta::TX_BITRATE).map(|rate| parse_rate(&rate)),
+        rx_bitrate: attrs.nested(sta::RX_BITRATE).map(|rate| parse_rate(&rate)),
+    }
+}
+
+fn parse_rate(attrs: &AttrMap<'_>) -> RateInfo {
+    let (mode, mcs, spatial_streams) = if let Some(mcs) = attrs.u8(rate::HE_MCS) {
+        (RateMode::He, Some(mcs), attrs.u8(rate::HE_NSS))
+    } else if let Some(mcs) = attrs.u8(rate::VHT_MCS) {
+        (RateMode::Vht, Some(mcs), attrs.u8(rate::VHT_NSS))
+    } else if let Some(mcs) = attrs.u8(rate::MCS) {
+        // HT MCS indices count eight per spatial stream
+        (RateMode::Ht, Some(mcs), Some(mcs / 8 + 1))
+    } else {
+        (RateMode::Legacy, None, None)
+    };
+    let width = if attrs.has(rate::WIDTH_160) || attrs.has(rate::WIDTH_80P80) {
+        160
+    } else if attrs.has(rate::WIDTH_80) {
+        80
+    } else if attrs.has(rate::WIDTH_40) {
+        40
+    } else {
+        20
+    };
+    let bitrate = attrs.u32(rate::BITRATE32)
+        .or_else(|| attrs.u16(rate::BITRATE).map(u32::from))
+        .map(|rate| rate * 100);
+    RateInfo { bitrate, mode, mcs, spatial_streams, width, short_gi: attrs.has(rate::SHORT_GI) }
+}
+
+/// Interface type names as iw prints them
+fn iftype_name(iftype: u32) -> String {
+    match iftype {
+        1 => "IBSS",
+        2 => "managed",
+        3 => "AP",
+        4 => "AP/VLAN",
+        5 => "WDS",
+        6 => "monitor",
+        7 => "mesh point",
+        8 => "P2P-client",
+        9 => "P2P-GO",
+        10 => "P2P-device",
+        11 => "outside context of a BSS",
+        12 => "NAN",
+        _ => "unknown",
+    }
+    .to_string()
+}
+
+/// Width in MHz of an `nl80211_chan_width` value
+fn channel_width_mhz(width: u32) -> Option<u32> {
+    match width {
+        0 | 1 => Some(20),
+        2 => Some(40),
+        3 => Some(80),
+        4 | 5 => Some(160),
+        6 => Some(5),
+        7 => Some(10),
+        13 => Some(320),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bss_and_station() {
+        let mut ies = Attrs::new();
+        ies.put(bss::BSSID, &[2, 0, 0, 0, 1, 0])
+            .put_u32(bss::FREQUENCY, 5180)
+            .put_u32(bss::SIGNAL_MBM, (-4200i32) as u32)
+            .put(bss::CAPABILITY, &0x0411u16.to_ne_bytes())
+            .put_u32(bss::STATUS, bss::STATUS_ASSOCIATED)
+            .put(bss::INFORMATION_ELEMENTS, &[0, 3, b'l', b'a', b'b']);
+        let result = parse_bss(&AttrMap::parse(ies.as_bytes())).unwrap();
+        assert_eq!(result.bssid, "02:00:00:00:01:00");
+        assert_eq!(result.channel, Some(36));
+        assert_eq!(result.signal, Some(-42));
+        assert_eq!(result.capabilities, vec!["ESS", "Privacy", "ShortSlotTime"]);
+        assert_eq!(result.ssid.as_deref(), Some("lab"));
+        assert_eq!(result.channel_width, Some(20));
+        assert!(result.associated && result.is_protected());
+
+        let mut bitrate = Attrs::new();
+        bitrate.put_u32(rate::BITRATE32, 8667).put(rate::VHT_MCS, &[9]).put(rate::VHT_NSS, &[2])
+            .put(rate::WIDTH_80, &[]).put(rate::SHORT_GI, &[]);
+        let mut info = Attrs::new();
+        info.put(sta::SIGNAL, &[(-45i8) as u8])
+            .put(sta::RX_BYTES64, &1234u64.to_ne_bytes())
+            .put_u32(sta::TX_BYTES, 99)
+            .put_nested(sta::TX_BITRATE, &bitrate);
+        let station = parse_station("02:00:00:00:01:00", &AttrMap::parse(info.as_bytes()));
+        assert_eq!(station.signal, Some(-45));
+        assert_eq!(station.rx_bytes, Some(1234));
+        assert_eq!(station.tx_bytes, Some(99));
+        assert_eq!(station.tx_bitrate, Some(RateInfo {
+            bitrate: Some(866700),
+            mode: RateMode::Vht,
+            mcs: Some(9),
+            spatial_streams: Some(2),
+            width: 80,
+            short_gi: true,
+        }));
+        assert!(station.rx_bitrate.is_none());
+    }
+}
@@ -0,0 +1,845 @@
+//! IPv6 router advertisements for LAN interfaces (RFC 4861 section 6.2)
+//!
+//! [`RaController`] runs one sender task per LAN interface. The task
+//! multicasts router advertisements to all nodes at random intervals,
+//! answers router solicitations and sends a last advertisement with a
+//! router lifetime of zero when it stops. Besides the prefixes of its
+//! [`RaConfig`] it advertises the prefixes delegated to the interface by the
+//! DHCPv6 client with their remaining lifetimes. A prefix that is withdrawn
+//! is advertised as deprecated a few more times so that hosts stop using it
+//! for new connections.
+
+use crate::dhcpv4::packet::encode_domain_list;
+use crate::dhcpv6_client::LanPrefix;
+use crate::error::{NetctlError, NetctlResult};
+use crate::interface::InterfaceController;
+use crate::netns::NetNamespace;
+use crate::rawsock::{Icmp6Packet, Icmp6Socket};
+use crate::validation;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::Ipv6Addr;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{mpsc, oneshot, watch, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, sleep_until, Instant};
+use tracing::{debug, info, warn};
+
+const ROUTER_SOLICITATION: u8 = 133;
+const ROUTER_ADVERTISEMENT: u8 = 134;
+
+/// Neighbor discovery option types
+mod options {
+    pub const SOURCE_LINK_ADDRESS: u8 = 1;
+    pub const PREFIX_INFORMATION: u8 = 3;
+    pub const MTU: u8 = 5;
+    pub const ROUTE_INFORMATION: u8 = 24;
+    pub const RDNSS: u8 = 25;
+    pub const DNSSL: u8 = 31;
+}
+
+const ALL_NODES: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+const ALL_ROUTERS: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 2);
+
+/// MAX_INITIAL_RTR_ADVERT_INTERVAL
+const MAX_INITIAL_INTERVAL: Duration = Duration::from_secs(16);
+/// MAX_INITIAL_RTR_ADVERTISEMENTS
+const INITIAL_ADVERTISEMENTS: u32 = 3;
+/// MIN_DELAY_BETWEEN_RAS
+const MIN_DELAY_BETWEEN_RAS: Duration = Duration::from_secs(3);
+/// MAX_RA_DELAY_TIME
+const MAX_RA_DELAY: Duration = Duration::from_millis(500);
+/// Advertisements that still carry a withdrawn prefix
+const WITHDRAWN_ADVERTISEMENTS: u32 = 3;
+
+/// Lifetime that never runs out
+pub const INFINITE_LIFETIME: u32 = u32::MAX;
+
+/// Preference of a default router or route (RFC 4191)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RouterPreference {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+impl RouterPreference {
+    /// Two-bit field of RFC 4191 section 2.1
+    fn bits(self) -> u8 {
+        match self {
+            RouterPreference::High => 0b01,
+            RouterPreference::Medium => 0b00,
+            RouterPreference::Low => 0b11,
+        }
+    }
+}
+
+impl fmt::Display for RouterPreference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RouterPreference::Low => "low",
+            RouterPreference::Medium => "medium",
+            RouterPreference::High => "high",
+        })
+    }
+}
+
+impl FromStr for RouterPreference {
+    type Err = NetctlError;
+
+    fn from_str(s: &str) -> NetctlResult<Self> {
+        match s {
+            "low" => Ok(RouterPreference::Low),
+            "medium" => Ok(RouterPreference::Medium),
+            "high" => Ok(RouterPreference::High),
+            _ => Err(NetctlError::InvalidParameter(format!(
+                "Invalid router preference {}, expected low, medium or high", s
+            ))),
+        }
+    }
+}
+
+/// Parse "PREFIX/LEN", clearing the host bits
+fn parse_prefix(s: &str) -> NetctlResult<(Ipv6Addr, u8)> {
+    let invalid = || NetctlError::InvalidParameter(format!("Invalid IPv6 prefix {}, expected PREFIX/LEN", s));
+    let (prefix, len) = s.split_once('/').ok_or_else(invalid)?;
+    let prefix: Ipv6Addr = prefix.parse().map_err(|_| invalid())?;
+    let len: u8 = len.parse().map_err(|_| invalid())?;
+    validation::validate_prefix_len(len, true)?;
+    Ok((mask(prefix, len), len))
+}
+
+fn mask(prefix: Ipv6Addr, len: u8) -> Ipv6Addr {
+    let mask = u128::MAX.checked_shl(u32::from(128 - len)).unwrap_or(0);
+    Ipv6Addr::from(u128::from(prefix) & mask)
+}
+
+/// A prefix in a Prefix Information option
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RaPrefix {
+    pub prefix: Ipv6Addr,
+    pub prefix_len: u8,
+    /// Addresses in the prefix are on-link (L flag)
+    pub on_link: bool,
+    /// Hosts may form addresses in the prefix with SLAAC (A flag)
+    pub autonomous: bool,
+    /// Seconds
+    pub valid_lifetime: u32,
+    /// Seconds
+    pub preferred_lifetime: u32,
+}
+
+impl RaPrefix {
+    pub const DEFAULT_VALID_LIFETIME: u32 = 86400;
+    pub const DEFAULT_PREFERRED_LIFETIME: u32 = 14400;
+
+    /// On-link prefix for SLAAC with the default lifetimes
+    pub fn new(prefix: Ipv6Addr, prefix_len: u8) -> Self {
+        Self {
+            prefix: mask(prefix, prefix_len.min(128)),
+            prefix_len,
+            on_link: true,
+            autonomous: true,
+            valid_lifetime: Self::DEFAULT_VALID_LIFETIME,
+            preferred_lifetime: Self::DEFAULT_PREFERRED_LIFETIME,
+        }
+    }
+
+    /// Set the valid and preferred lifetimes in seconds
+    pub fn with_lifetimes(mut self, valid_lifetime: u32, preferred_lifetime: u32) -> Self {
+        self.valid_lifetime = valid_lifetime;
+        self.preferred_lifetime = preferred_lifetime;
+        self
+    }
+
+    fn same_prefix(&self, other: &RaPrefix) -> bool {
+        self.prefix == other.prefix && self.prefix_len == other.prefix_len
+    }
+}
+
+impl FromStr for RaPrefix {
+    type Err = NetctlError;
+
+    /// Parse "PREFIX/LEN"
+    fn from_str(s: &str) -> NetctlResult<Self> {
+        let (prefix, len) = parse_prefix(s)?;
+        Ok(RaPrefix::new(prefix, len))
+    }
+}
+
+impl From<&LanPrefix> for RaPrefix {
+    fn from(lan: &LanPrefix) -> Self {
+        RaPrefix::new(lan.prefix, lan.prefix_len).with_lifetimes(lan.valid_lifetime, lan.preferred_lifetime)
+    }
+}
+
+/// A more-specific route in a Route Information option (RFC 4191)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RaRoute {
+    pub prefix: Ipv6Addr,
+    pub prefix_len: u8,
+    pub preference: RouterPreference,
+    /// Seconds; defaults to three times the maximum advertisement interval
+    pub lifetime: Option<u32>,
+}
+
+impl FromStr for RaRoute {
+    type Err = NetctlError;
+
+    /// Parse "PREFIX/LEN[,PREFERENCE]"
+    fn from_str(s: &str) -> NetctlResult<Self> {
+        let (prefix, preference) = match s.split_once(',') {
+            Some((prefix, preference)) => (prefix, preference.trim().parse()?),
+            None => (s, RouterPreference::default()),
+        };
+        let (prefix, prefix_len) = parse_prefix(prefix.trim())?;
+        Ok(RaRoute { prefix, prefix_len, preference, lifetime: None })
+    }
+}
+
+/// Contents and timing of the advertisements on one interface
+#[derive(Debug, Clone, PartialEq)]
+pub struct RaConfig {
+    /// Shortest interval between unsolicited advertisements
+    pub min_interval: Duration,
+    /// Longest interval between unsolicited advertisements
+    pub max_interval: Duration,
+    /// Seconds hosts may use this router as their default router; zero
+    /// only announces prefixes and options
+    pub router_lifetime: u16,
+    pub router_preference: RouterPreference,
+    /// Addresses are available through DHCPv6 (M flag)
+    pub managed: bool,
+    /// Other configuration is available through DHCPv6 (O flag)
+    pub other_config: bool,
+    /// Hop limit for hosts to use, zero leaves it to them
+    pub hop_limit: u8,
+    /// Link MTU (option 5)
+    pub mtu: Option<u32>,
+    pub prefixes: Vec<RaPrefix>,
+    pub routes: Vec<RaRoute>,
+    /// Recursive DNS servers (RFC 8106)
+    pub dns_servers: Vec<Ipv6Addr>,
+    /// DNS search list (RFC 8106)
+    pub dns_search: Vec<String>,
+    /// Seconds the DNS servers and search list stay valid; defaults to
+    /// three times the maximum advertisement interval
+    pub dns_lifetime: Option<u32>,
+}
+
+impl Default for RaConfig {
+    fn default() -> Self {
+        Self {
+            min_interval: Duration::from_secs(200),
+            max_interval: Duration::from_secs(600),
+            router_lifetime: 1800,
+            router_preference: RouterPreference::Medium,
+            managed: false,
+            other_config: false,
+            hop_limit: 64,
+            mtu: None,
+            prefixes: Vec::new(),
+            routes: Vec::new(),
+            dns_servers: Vec::new(),
+            dns_search: Vec::new(),
+            dns_lifetime: None,
+        }
+    }
+}
+
+impl RaConfig {
+    /// Check the limits of RFC 4861 section 6.2.1
+    pub fn validate(&self) -> NetctlResult<()> {
+        let invalid = |message: String| Err(NetctlError::InvalidParameter(message));
+        let max = self.max_interval.as_secs();
+        if !(4..=1800).contains(&max) {
+            return invalid(format!("Maximum advertisement interval must be 4 to 1800 seconds, not {}", max));
+        }
+        if self.min_interval < Duration::from_secs(3) || self.min_interval > self.max_interval.mul_f64(0.75) {
+            return invalid(format!(
+                "Minimum advertisement interval must be 3 seconds to 3/4 of the maximum, not {}",
+                self.min_interval.as_secs()
+            ));
+        }
+        let lifetime = u64::from(self.router_lifetime);
+        if lifetime != 0 && !(max..=9000).contains(&lifetime) {
+            return invalid(format!(
+                "Router lifetime must be 0 or {} to 9000 seconds, not {}", max, lifetime
+            ));
+        }
+        if let Some(mtu) = self.mtu {
+            if mtu < 1280 {
+                return invalid(format!("IPv6 MTU must be at least 1280, not {}", mtu));
+            }
+        }
+        for prefix in &self.prefixes {
+            validation::validate_prefix_len(prefix.prefix_len, true)?;
+            if prefix.preferred_lifetime > prefix.valid_lifetime {
+                return invalid(format!(
+                    "Preferred lifetime of {}/{} exceeds its valid lifetime", prefix.prefix, prefix.prefix_len
+                ));
+            }
+        }
+        for route in &self.routes {
+            validation::validate_prefix_len(route.prefix_len, true)?;
+        }
+        for domain in &self.dns_search {
+            validation::validate_hostname(domain)?;
+        }
+        Ok(())
+    }
+
+    /// Lifetime of options without one of their own
+    fn option_lifetime(&self) -> u32 {
+        u32::try_from(self.max_interval.as_secs() * 3).unwrap_or(INFINITE_LIFETIME)
+    }
+
+    /// Encode a router advertisement announcing `prefixes`
+    ///
+    /// A final advertisement has a router lifetime of zero.
+    fn advertisement(&self, mac: Option<[u8; 6]>, prefixes: &[RaPrefix], final_advertisement: bool) -> Vec<u8> {
+        let mut flags = self.router_preference.bits() << 3;
+        if self.managed {
+            flags |= 0x80;
+        }
+        if self.other_config {
+            flags |= 0x40;
+        }
+        let router_lifetime = if final_advertisement { 0 } else { self.router_lifetime };
+
+        let mut message = vec![ROUTER_ADVERTISEMENT, 0, 0, 0, self.hop_limit, flags];
+        message.extend_from_slice(&router_lifetime.to_be_bytes());
+        // Reachable time and retransmission timer are left to the hosts
+        message.extend_from_slice(&[0; 8]);
+
+        if let Some(mac) = mac {
+            message.extend_from_slice(&[options::SOURCE_LINK_ADDRESS, 1]);
+            message.extend_from_slice(&mac);
+        }
+        if let Some(mtu) = self.mtu {
+            message.extend_from_slice(&[options::MTU, 1, 0, 0]);
+            message.extend_from_slice(&mtu.to_be_bytes());
+        }
+        for prefix in prefixes {
+            let mut flags = 0;
+            if prefix.on_link {
+                flags |= 0x80;
+            }
+            if prefix.autonomous {
+                flags |= 0x40;
+            }
+            message.extend_from_slice(&[options::PREFIX_INFORMATION, 4, prefix.prefix_len, flags]);
+            message.extend_from_slice(&prefix.valid_lifetime.to_be_bytes());
+            message.extend_from_slice(&prefix.preferred_lifetime.to_be_bytes());
+            message.extend_from_slice(&[0; 4]);
+            message.extend_from_slice(&prefix.prefix.octets());
+        }
+        for route in &self.routes {
+            // Only as many 8-octet units of the prefix as its length needs
+            let prefix_units = match route.prefix_len {
+                0 => 0,
+                1..=64 => 1,
+                _ => 2,
+            };
+            message.extend_from_slice(&[
+                options::ROUTE_INFORMATION, 1 + prefix_units, route.prefix_len, route.preference.bits() << 3,
+            ]);
+            message.extend_from_slice(&route.lifetime.unwrap_or_else(|| self.option_lifetime()).to_be_bytes());
+            message.extend_from_slice(&route.prefix.octets()[..usize::from(prefix_units) * 8]);
+        }
+
+        let dns_lifetime = self.dns_lifetime.unwrap_or_else(|| self.option_lifetime());
+        if !self.dns_servers.is_empty() {
+            message.extend_from_slice(&[options::RDNSS, 1 + 2 * self.dns_servers.len() as u8, 0, 0]);
+            message.extend_from_slice(&dns_lifetime.to_be_bytes());
+            for server in &self.dns_servers {
+                message.extend_from_slice(&server.octets());
+            }
+        }
+        if !self.dns_search.is_empty() {
+            let mut names = encode_domain_list(&self.dns_search);
+            names.resize(names.len().div_ceil(8) * 8, 0);
+            message.extend_from_slice(&[options::DNSSL, 1 + (names.len() / 8) as u8, 0, 0]);
+            message.extend_from_slice(&dns_lifetime.to_be_bytes());
+            message.extend_from_slice(&names);
+        }
+        message
+    }
+}
+
+/// Split neighbor discovery options into (type, option) pairs, `None` if
+/// one has length zero or runs past the end
+fn nd_options(mut data: &[u8]) -> Option<Vec<(u8, &[u8])>> {
+    let mut options = Vec::new();
+    while !data.is_empty() {
+        let len = usize::from(*data.get(1)?) * 8;
+        if len == 0 || len > data.len() {
+            return None;
+        }
+        options.push((data[0], &data[..len]));
+        data = &data[len..];
+    }
+    Some(options)
+}
+
+/// Whether a message passes the checks of RFC 4861 section 6.1.1 for a
+/// router solicitation
+fn is_solicitation(message: &[u8], packet: &Icmp6Packet) -> bool {
+    if message.len() < 8 || message[0] != ROUTER_SOLICITATION || message[1] != 0 || packet.hop_limit != Some(255) {
+        return false;
+    }
+    let Some(options) = nd_options(&message[8..]) else {
+        return false;
+    };
+    // A host without an address cannot be reached at a link-layer address
+    !(packet.source.is_unspecified() && options.iter().any(|(kind, _)| *kind == options::SOURCE_LINK_ADDRESS))
+}
+
+/// When to send an advertisement due after `delay`; solicited and updated
+/// advertisements still keep their distance from the last one
+fn soon(last_sent: Option<Instant>, delay: Duration) -> Instant {
+    let earliest = last_sent.map_or_else(Instant::now, |last| last + MIN_DELAY_BETWEEN_RAS);
+    (Instant::now() + delay).max(earliest)
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// State of the sender on one interface
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RaStatus {
+    pub interface: String,
+    /// Prefixes of the last advertisement with the lifetimes sent
+    pub prefixes: Vec<RaPrefix>,
+    /// Advertisements sent
+    pub advertisements: u64,
+    /// Router solicitations received
+    pub solicitations: u64,
+    /// Unix time of the last advertisement
+    pub last_advertisement: Option<u64>,
+}
+
+/// A delegated prefix and when its lifetimes were granted
+#[derive(Debug, Clone, Copy)]
+struct Delegated {
+    prefix: RaPrefix,
+    since: Instant,
+}
+
+impl Delegated {
+    /// The prefix with the lifetimes left at `now`
+    fn at(&self, now: Instant) -> RaPrefix {
+        let elapsed = u32::try_from(now.duration_since(self.since).as_secs()).unwrap_or(u32::MAX);
+        let left = |lifetime: u32| match lifetime {
+            INFINITE_LIFETIME => lifetime,
+            lifetime => lifetime.saturating_sub(elapsed),
+        };
+        self.prefix.with_lifetimes(left(self.prefix.valid_lifetime), left(self.prefix.preferred_lifetime))
+    }
+}
+
+enum Command {
+    Configure(Box<RaConfig>),
+    Delegate(Vec<Delegated>),
+    Stop(oneshot::Sender<()>),
+}
+
+/// Handle to a running sender task
+struct SenderHandle {
+    commands: mpsc::Sender<Command>,
+    status: watch::Receiver<RaStatus>,
+    task: JoinHandle<()>,
+}
+
+impl SenderHandle {
+    fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Send the final advertisement and wait for the task to end
+    async fn stop(self) {
+        let (tx, rx) = oneshot::channel();
+        if self.commands.send(Command::Stop(tx)).await.is_ok() {
+            let _ = rx.await;
+        }
+        let _ = self.task.await;
+    }
+}
+
+struct Sender {
+    interface: String,
+    mac: Option<[u8; 6]>,
+    socket: Icmp6Socket,
+    config: RaConfig,
+    delegated: Vec<Delegated>,
+    /// Withdrawn prefixes and the advertisements left to deprecate them in
+    withdrawn: Vec<(RaPrefix, u32)>,
+    status: watch::Sender<RaStatus>,
+}
+
+impl Sender {
+    async fn run(mut self, mut commands: mpsc::Receiver<Command>) {
+        let mut initial = INITIAL_ADVERTISEMENTS;
+        let mut next = Instant::now();
+        let mut last_sent: Option<Instant> = None;
+        let mut buf = vec![0u8; 1500];
+
+        loop {
+            tokio::select! {
+                _ = sleep_until(next) => {
+                    self.advertise(false).await;
+                    last_sent = Some(Instant::now());
+                    next = Instant::now() + self.interval(&mut initial);
+                }
+                received = self.socket.recv(&mut buf) => match received {
+                    Ok(packet) if is_solicitation(&buf[..packet.len], &packet) => {
+                        debug!("Router solicitation from {} on {}", packet.source, self.interface);
+                        self.status.send_modify(|status| status.solicitations += 1);
+                        let delay = rand::thread_rng().gen_range(Duration::ZERO..=MAX_RA_DELAY);
+                        next = next.min(soon(last_sent, delay));
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!("Failed to receive on {}: {}", self.interface, e);
+                        sleep(Duration::from_secs(1)).await;
+                    }
+                },
+                command = commands.recv() => match command {
+                    Some(Command::Configure(config)) => {
+                        self.config = *config;
+                        initial = INITIAL_ADVERTISEMENTS;
+                        next = soon(last_sent, Duration::ZERO);
+                    }
+                    Some(Command::Delegate(delegated)) => {
+                        self.delegate(delegated);
+                        next = soon(last_sent, Duration::ZERO);
+                    }
+                    Some(Command::Stop(done)) => {
+                        self.advertise(true).await;
+                        let _ = done.send(());
+                        return;
+                    }
+                    None => {
+                        self.advertise(true).await;
+                        return;
+                    }
+                },
+            }
+        }
+    }
+
+    /// Random interval to the next unsolicited advertisement
+    fn interval(&self, initial: &mut u32) -> Duration {
+        let interval = rand::thread_rng().gen_range(self.config.min_interval..=self.config.max_interval);
+        if *initial > 0 {
+            *initial -= 1;
+            return interval.min(MAX_INITIAL_INTERVAL);
+        }
+        interval
+    }
+
+    /// Replace the delegated prefixes, deprecating the ones that are gone
+    fn delegate(&mut self, delegated: Vec<Delegated>) {
+        for old in &self.delegated {
+            if !delegated.iter().any(|new| new.prefix.same_prefix(&old.prefix)) {
+                info!("Withdrawing prefix {}/{} on {}", old.prefix.prefix, old.prefix.prefix_len, self.interface);
+                self.withdrawn.push((old.prefix.with_lifetimes(0, 0), WITHDRAWN_ADVERTISEMENTS));
+            }
+        }
+        self.withdrawn.retain(|(prefix, _)| !delegated.iter().any(|new| new.prefix.same_prefix(prefix)));
+        self.delegated = delegated;
+    }
+
+    /// Configured, delegated and withdrawn prefixes as of now
+    fn prefixes(&self) -> Vec<RaPrefix> {
+        let now = Instant::now();
+        let mut prefixes = self.config.prefixes.clone();
+        let candidates = self.delegated.iter().map(|delegated| delegated.at(now))
+            .chain(self.withdrawn.iter().map(|(prefix, _)| *prefix));
+        for prefix in candidates {
+            if !prefixes.iter().any(|known| known.same_prefix(&prefix)) {
+                prefixes.push(prefix);
+            }
+        }
+        prefixes
+    }
+
+    async fn advertise(&mut self, final_advertisement: bool) {
+        let prefixes = self.prefixes();
+        let message = self.config.advertisement(self.mac, &prefixes, final_advertisement);
+        if let Err(e) = self.socket.send_to(&message, ALL_NODES).await {
+            warn!("Failed to send router advertisement on {}: {}", self.interface, e);
+            return;
+        }
+        debug!("Sent router advertisement on {} with {} prefixes", self.interface, prefixes.len());
+
+        for (_, left) in &mut self.withdrawn {
+            *left -= 1;
+        }
+        self.withdrawn.retain(|(_, left)| *left > 0);
+        self.status.send_modify(|status| {
+            status.prefixes = prefixes;
+            status.advertisements += 1;
+            status.last_advertisement = Some(unix_time());
+        });
+    }
+}
+
+/// Router advertisement controller
+///
+/// Prefixes delegated to an interface are remembered even while no sender
+/// runs on it, so the order in which the DHCPv6 client and the sender start
+/// does not matter.
+pub struct RaController {
+    /// Network namespace the interfaces live in
+    namespace: NetNamespace,
+    /// Running senders by interface
+    senders: Mutex<HashMap<String, SenderHandle>>,
+    /// Delegated prefixes by interface
+    delegated: Mutex<HashMap<String, Vec<Delegated>>>,
+}
+
+impl RaController {
+    pub fn new() -> Self {
+        Self {
+            namespace: NetNamespace::Current,
+            senders: Mutex::new(HashMap::new()),
+            delegated: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Send advertisements on interfaces in `namespace`
+    pub fn with_namespace(namespace: NetNamespace) -> Self {
+        Self::new().in_namespace(namespace)
+    }
+
+    /// Builder form of [`with_namespace`](Self::with_namespace)
+    pub fn in_namespace(mut self, namespace: NetNamespace) -> Self {
+        self.namespace = namespace;
+        self
+    }
+
+    /// Start sending router advertisements on an interface
+    ///
+    /// If a sender already runs on the interface, its configuration is
+    /// replaced.
+    pub async fn start(&self, interface: &str, config: RaConfig) -> NetctlResult<()> {
+        validation::validate_interface_name(interface)?;
+        config.validate()?;
+
+        let mut senders = self.senders.lock().await;
+        if let Some(sender) = senders.get(interface).filter(|sender| !sender.is_finished()) {
+            info!("Updating router advertisements on {}", interface);
+            return sender.commands.send(Command::Configure(Box::new(config))).await
+                .map_err(|_| NetctlError::InvalidState(format!("Router advertisement sender on {} has exited", interface)));
+        }
+
+        let info = InterfaceController::with_namespace(self.namespace.clone()).get_info(interface).await?;
+        let ifindex = info.index
+            .ok_or_else(|| NetctlError::NotFound(format!("Interface {} has no index", interface)))?;
+        // Links without a hardware address (tun, wireguard) go without the option
+        let mac = crate::arp::parse_mac(info.mac_address.as_deref(), interface).ok();
+        let socket = Icmp6Socket::open(&self.namespace, interface, ifindex, &[ALL_ROUTERS], &[ROUTER_SOLICITATION])?;
+
+        info!("Starting router advertisements on {}", interface);
+        let (commands_tx, commands) = mpsc::channel(8);
+        let (status_tx, status) = watch::channel(RaStatus {
+            interface: interface.to_string(),
+            ..Default::default()
+        });
+        let sender = Sender {
+            interface: interface.to_string(),
+            mac,
+            socket,
+            config,
+            delegated: self.delegated.lock().await.get(interface).cloned().unwrap_or_default(),
+            withdrawn: Vec::new(),
+            status: status_tx,
+        };
+        senders.insert(interface.to_string(), SenderHandle {
+            commands: commands_tx,
+            status,
+            task: tokio::spawn(sender.run(commands)),
+        });
+        Ok(())
+    }
+
+    /// Stop the advertisements on an interface
+    ///
+    /// A last advertisement tells hosts to stop using this router.
+    pub async fn stop(&self, interface: &str) -> NetctlResult<()> {
+        validation::validate_interface_name(interface)?;
+        let Some(sender) = self.senders.lock().await.remove(interface) else {
+            debug!("No router advertisements on {}", interface);
+            return Ok(());
+        };
+        info!("Stopping router advertisements on {}", interface);
+        sender.stop().await;
+        Ok(())
+    }
+
+    /// Set the prefixes delegated to an interface, replacing earlier ones
+    ///
+    /// Their lifetimes count down from now. Prefixes that are no longer
+    /// delegated are advertised as deprecated.
+    pub async fn set_delegated_prefixes(&self, interface: &str, prefixes: Vec<RaPrefix>) -> NetctlResult<()> {
+        validation::validate_interface_name(interface)?;
+        let since = Instant::now();
+        let delegated: Vec<Delegated> = prefixes.into_iter()
+            .map(|prefix| Delegated { prefix, since })
+            .collect();
+
+        {
+            let mut known = self.delegated.lock().await;
+            if delegated.is_empty() {
+                known.remove(interface);
+            } else {
+                known.insert(interface.to_string(), delegated.clone());
+            }
+        }
+        if let Some(sender) = self.senders.lock().await.get(interface) {
+            let _ = sender.commands.send(Command::Delegate(delegated)).await;
+        }
+        Ok(())
+    }
+
+    /// Status of the sender on an interface, `None` if none runs
+    pub async fn status(&self, interface: &str) -> Option<RaStatus> {
+        self.senders.lock().await.get(interface)
+            .filter(|sender| !sender.is_finished())
+            .map(|sender| sender.status.borrow().clone())
+    }
+
+    /// Check if router advertisements are sent on an interface
+    pub async fn is_running(&self, interface: &str) -> bool {
+        self.status(interface).await.is_some()
+    }
+}
+
+impl Default for RaController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config_items() {
+        let prefix: RaPrefix = "2001:db8:1:2::5/64".parse().unwrap();
+        assert_eq!(prefix.prefix, "2001:db8:1:2::".parse::<Ipv6Addr>().unwrap());
+        assert!(prefix.on_link && prefix.autonomous);
+        assert!("2001:db8::/129".parse::<RaPrefix>().is_err());
+        assert!("10.0.0.0/8".parse::<RaPrefix>().is_err());
+
+        let route: RaRoute = "2001:db8:100::/48,high".parse().unwrap();
+        assert_eq!((route.prefix_len, route.preference), (48, RouterPreference::High));
+        assert_eq!("::/0".parse::<RaRoute>().unwrap().preference, RouterPreference::Medium);
+        assert!("2001:db8:100::/48,urgent".parse::<RaRoute>().is_err());
+    }
+
+    #[test]
+    fn test_validate_config() {
+        assert!(RaConfig::default().validate().is_ok());
+        let config = |f: fn(&mut RaConfig)| {
+            let mut config = RaConfig::default();
+            f(&mut config);
+            config.validate()
+        };
+        assert!(config(|c| c.max_interval = Duration::from_secs(3)).is_err());
+        assert!(config(|c| c.min_interval = Duration::from_secs(500)).is_err());
+        assert!(config(|c| c.router_lifetime = 300).is_err());
+        assert!(config(|c| c.router_lifetime = 0).is_ok());
+        assert!(config(|c| c.mtu = Some(1200)).is_err());
+        assert!(config(|c| c.prefixes.push(RaPrefix::new(Ipv6Addr::UNSPECIFIED, 64).with_lifetimes(10, 20))).is_err());
+        assert!(config(|c| c.dns_search.push("bad domain".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_advertisement() {
+        let config = RaConfig {
+            router_preference: RouterPreference::High,
+            other_config: true,
+            mtu: Some(1480),
+            routes: vec!["2001:db8:100::/48".parse().unwrap(), "::/0,low".parse().unwrap()],
+            dns_servers: vec!["2001:db8:1::1".parse().unwrap()],
+            dns_search: vec!["lan".to_string()],
+            ..Default::default()
+        };
+        let prefix = RaPrefix::new("2001:db8:1::".parse().unwrap(), 64);
+        let mac = [0x02, 0, 0, 0, 0, 1];
+        let message = config.advertisement(Some(mac), &[prefix], false);
+
+        assert_eq!(&message[..8], &[ROUTER_ADVERTISEMENT, 0, 0, 0, 64, 0x40 | 0x08, 0x07, 0x08]);
+        let options = nd_options(&message[16..]).unwrap();
+        let kinds: Vec<u8> = options.iter().map(|(kind, _)| *kind).collect();
+        assert_eq!(kinds, vec![
+            options::SOURCE_LINK_ADDRESS, options::MTU, options::PREFIX_INFORMATION,
+            options::ROUTE_INFORMATION, options::ROUTE_INFORMATION, options::RDNSS, options::DNSSL,
+        ]);
+        assert_eq!(&options[0].1[2..], &mac);
+        assert_eq!(&options[1].1[4..], &1480u32.to_be_bytes());
+
+        let pio = options[2].1;
+        assert_eq!(pio.len(), 32);
+        assert_eq!(&pio[2..4], &[64, 0xc0]);
+        assert_eq!(&pio[4..8], &86400u32.to_be_bytes());
+        assert_eq!(&pio[8..12], &14400u32.to_be_bytes());
+        assert_eq!(&pio[16..], &prefix.prefix.octets());
+
+        // A /48 needs one unit of prefix, the default route none
+        assert_eq!(options[3].1.len(), 16);
+        assert_eq!(&options[3].1[2..8], &[48, 0, 0, 0, 0x07, 0x08]);
+        assert_eq!(options[4].1.len(), 8);
+        assert_eq!(options[4].1[3], 0b11 << 3);
+
+        assert_eq!(options[5].1.len(), 24);
+        assert_eq!(&options[5].1[8..], &"2001:db8:1::1".parse::<Ipv6Addr>().unwrap().octets());
+        assert_eq!(options[6].1.len(), 16);
+        assert_eq!(&options[6].1[8..13], b"\x03lan\x00");
+
+        // Leaving the link
+        let last = config.advertisement(None, &[], true);
+        assert_eq!(&last[6..8], &[0, 0]);
+        assert!(nd_options(&last[16..]).unwrap().iter().all(|(kind, _)| *kind != options::SOURCE_LINK_ADDRESS));
+    }
+
+    #[test]
+    fn test_solicitation_checks() {
+        let packet = |source: &str, hop_limit| Icmp6Packet { len: 0, source: source.parse().unwrap(), hop_limit };
+        let plain = [ROUTER_SOLICITATION, 0, 0, 0, 0, 0, 0, 0];
+        let with_sll = [&plain[..], &[options::SOURCE_LINK_ADDRESS, 1, 2, 0, 0, 0, 0, 1]].concat();
+
+        assert!(is_solicitation(&plain, &packet("fe80::1", Some(255))));
+        assert!(is_solicitation(&with_sll, &packet("fe80::1", Some(255))));
+        assert!(is_solicitation(&plain, &packet("::", Some(255))));
+        assert!(!is_solicitation(&with_sll, &packet("::", Some(255))));
+        assert!(!is_solicitation(&plain, &packet("fe80::1", Some(64))));
+        assert!(!is_solicitation(&with_sll[..12], &packet("fe80::1", Some(255))));
+        assert!(!is_solicitation(&[ROUTER_SOLICITATION, 0, 0, 0, 0, 0, 0, 0, 1, 0], &packet("fe80::1", Some(255))));
+    }
+
+    #[test]
+    fn test_delegated_lifetimes() {
+        let since = Instant::now();
+        let delegated = Delegated {
+            prefix: RaPrefix::new("2001:db8:ff00::".parse().unwrap(), 64).with_lifetimes(7200, 3600),
+            since,
+        };
+        let later = delegated.at(since + Duration::from_secs(600));
+        assert_eq!((later.valid_lifetime, later.preferred_lifetime), (6600, 3000));
+        let expired = delegated.at(since + Duration::from_secs(5000));
+        assert_eq!((expired.valid_lifetime, expired.preferred_lifetime), (2200, 0));
+
+        let forever = Delegated { prefix: delegated.prefix.with_lifetimes(INFINITE_LIFETIME, INFINITE_LIFETIME), since };
+        assert_eq!(forever.at(since + Duration::from_secs(600)).valid_lifetime, INFINITE_LIFETIME);
+    }
+}
@@ -0,0 +1,454 @@
+//! Link-layer and device-bound sockets
+//!
+//! Address configuration protocols have to talk on a link before it has a
+//! usable address: DHCP replies are dropped by reverse path filtering until
+//! the client has an address and route, and ARP has no IP layer at all.
+//! These helpers open packet sockets, device-bound UDP sockets and raw
+//! ICMPv6 sockets for neighbor discovery inside a [`NetNamespace`] and
+//! register them with tokio.
+
+use crate::error::{NetctlError, NetctlResult};
+use crate::netns::NetNamespace;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use tokio::io::unix::AsyncFd;
+use tokio::io::Interest;
+use tokio::net::UdpSocket;
+
+pub(crate) const ETH_P_IP: u16 = 0x0800;
+pub(crate) const ETH_P_ARP: u16 = 0x0806;
+pub(crate) const BROADCAST_MAC: [u8; 6] = [0xff; 6];
+
+const IPPROTO_UDP: u8 = 17;
+const IPV4_HEADER_LEN: usize = 20;
+const UDP_HEADER_LEN: usize = 8;
+
+fn last_error(op: &str) -> NetctlError {
+    let err = std::io::Error::last_os_error();
+    match err.kind() {
+        std::io::ErrorKind::PermissionDenied => NetctlError::PermissionDenied(format!("{}: {}", op, err)),
+        _ => NetctlError::ServiceError(format!("{}: {}", op, err)),
+    }
+}
+
+fn setsockopt<T>(fd: &OwnedFd, level: i32, name: i32, value: &T, op: &str) -> NetctlResult<()> {
+    // SAFETY: value points to a live T and its size is passed along
+    let ret = unsafe {
+        libc::setsockopt(
+            fd.as_raw_fd(),
+            level,
+            name,
+            value as *const T as *const libc::c_void,
+            std::mem::size_of::<T>() as libc::socklen_t,
+        )
+    };
+    if ret != 0 {
+        return Err(last_error(op));
+    }
+    Ok(())
+}
+
+fn new_socket(domain: i32, kind: i32, protocol: i32) -> NetctlResult<OwnedFd> {
+    // SAFETY: plain socket(2) call; ownership of the descriptor is taken below
+    let fd = unsafe { libc::socket(domain, kind | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC, protocol) };
+    if fd < 0 {
+        return Err(last_error("Failed to create socket"));
+    }
+    // SAFETY: fd is a freshly created descriptor nobody else owns
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+fn link_address(ifindex: u32, protocol: u16, mac: [u8; 6]) -> libc::sockaddr_ll {
+    // SAFETY: sockaddr_ll is plain old data for which all zeroes is valid
+    let mut addr: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+    addr.sll_family = libc::AF_PACKET as u16;
+    addr.sll_protocol = protocol.to_be();
+    addr.sll_ifindex = ifindex as i32;
+    addr.sll_halen = 6;
+    addr.sll_addr[..6].copy_from_slice(&mac);
+    addr
+}
+
+/// A frame received on a packet socket
+pub(crate) struct Frame {
+    pub len: usize,
+    /// Link-layer source address
+    pub source: [u8; 6],
+    /// Sent by this host (`PACKET_OUTGOING`)
+    pub outgoing: bool,
+}
+
+/// Cooked (`SOCK_DGRAM`) packet socket for one EtherType on one interface
+pub(crate) struct PacketSocket {
+    fd: AsyncFd<OwnedFd>,
+    ifindex: u32,
+    protocol: u16,
+}
+
+impl PacketSocket {
+    pub fn open(namespace: &NetNamespace, ifindex: u32, protocol: u16) -> NetctlResult<Self> {
+        namespace.run_in(|| {
+            let fd = new_socket(libc::AF_PACKET, libc::SOCK_DGRAM, i32::from(protocol.to_be()))?;
+            let addr = link_address(ifindex, protocol, [0; 6]);
+            // SAFETY: addr is a valid sockaddr_ll of the given size
+            let ret = unsafe {
+                libc::bind(
+                    fd.as_raw_fd(),
+                    &addr as *const libc::sockaddr_ll as *const libc::sockaddr,
+                    std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+                )
+            };
+            if ret != 0 {
+                return Err(last_error("Failed to bind packet socket"));
+            }
+            Ok(Self { fd: AsyncFd::new(fd)?, ifindex, protocol })
+        })
+    }
+
+    /// Send `payload` to the link-layer address `dest`
+    pub async fn send_to(&self, payload: &[u8], dest: [u8; 6]) -> NetctlResult<()> {
+        let addr = link_address(self.ifindex, self.protocol, dest);
+        self.fd.async_io(Interest::WRITABLE, |fd| {
+            // SAFETY: payload and addr are valid for the duration of the call
+            let ret = unsafe {
+                libc::sendto(
+                    fd.as_raw_fd(),
+                    payload.as_ptr() as *const libc::c_void,
+                    payload.len(),
+                    0,
+                    &addr as *const libc::sockaddr_ll as *const libc::sockaddr,
+                    std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+                )
+            };
+            if ret < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        }).await?;
+        Ok(())
+    }
+
+    /// Receive the next frame into `buf`
+    pub async fn recv(&self, buf: &mut [u8]) -> NetctlResult<Frame> {
+        let frame = self.fd.async_io(Interest::READABLE, |fd| {
+            // SAFETY: sockaddr_ll is plain old data for which all zeroes is valid
+            let mut addr: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+            let mut addr_len = std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t;
+            // SAFETY: buf and addr are valid for writes of the given sizes
+            let ret = unsafe {
+                libc::recvfrom(
+                    fd.as_raw_fd(),
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                    0,
+                    &mut addr as *mut libc::sockaddr_ll as *mut libc::sockaddr,
+                    &mut addr_len,
+                )
+            };
+            if ret < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            let mut source = [0u8; 6];
+            source.copy_from_slice(&addr.sll_addr[..6]);
+            Ok(Frame {
+                len: ret as usize,
+                source,
+                outgoing: addr.sll_pkttype == libc::PACKET_OUTGOING,
+            })
+        }).await?;
+        Ok(frame)
+    }
+}
+
+/// UDP socket bound to `port` on one interface, able to send broadcasts
+pub(crate) fn udp_socket(namespace: &NetNamespace, interface: &str, port: u16) -> NetctlResult<UdpSocket> {
+    namespace.run_in(|| {
+        let fd = new_socket(libc::AF_INET, libc::SOCK_DGRAM, 0)?;
+        let one: libc::c_int = 1;
+        setsockopt(&fd, libc::SOL_SOCKET, libc::SO_REUSEADDR, &one, "Failed to set SO_REUSEADDR")?;
+        setsockopt(&fd, libc::SOL_SOCKET, libc::SO_BROADCAST, &one, "Failed to set SO_BROADCAST")?;
+
+        let mut device = [0u8; libc::IFNAMSIZ];
+        device[..interface.len()].copy_from_slice(interface.as_bytes());
+        setsockopt(&fd, libc::SOL_SOCKET, libc::SO_BINDTODEVICE, &device, "Failed to bind socket to device")?;
+
+        let addr = libc::sockaddr_in {
+            sin_family: libc::AF_INET as libc::sa_family_t,
+            sin_port: port.to_be(),
+            sin_addr: libc::in_addr { s_addr: u32::from(Ipv4Addr::UNSPECIFIED).to_be() },
+            sin_zero: [0; 8],
+        };
+        // SAFETY: addr is a valid sockaddr_in of the given size
+        let ret = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_in as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+            )
+        };
+        if ret != 0 {
+            return Err(last_error(&format!("Failed to bind UDP port {} on {}", port, interface)));
+        }
+        Ok(UdpSocket::from_std(std::net::UdpSocket::from(fd))?)
+    })
+}
+
+/// IPv6 UDP socket bound to `port` on one interface
+///
+/// Link-local and multicast peers need the interface index as scope ID.
+pub(crate) fn udp6_socket(namespace: &NetNamespace, interface: &str, port: u16) -> NetctlResult<UdpSocket> {
+    namespace.run_in(|| {
+        let fd = new_socket(libc::AF_INET6, libc::SOCK_DGRAM, 0)?;
+        let one: libc::c_int = 1;
+        setsockopt(&fd, libc::SOL_SOCKET, libc::SO_REUSEADDR, &one, "Failed to set SO_REUSEADDR")?;
+        setsockopt(&fd, libc::IPPROTO_IPV6, libc::IPV6_V6ONLY, &one, "Failed to set IPV6_V6ONLY")?;
+
+        let mut device = [0u8; libc::IFNAMSIZ];
+        device[..interface.len()].copy_from_slice(interface.as_bytes());
+        setsockopt(&fd, libc::SOL_SOCKET, libc::SO_BINDTODEVICE, &device, "Failed to bind socket to device")?;
+
+        // SAFETY: sockaddr_in6 is plain old data for which all zeroes is the unspecified address
+        let mut addr: libc::sockaddr_in6 = unsafe { std::mem::zeroed() };
+        addr.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+        addr.sin6_port = port.to_be();
+        // SAFETY: addr is a valid sockaddr_in6 of the given size
+        let ret = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_in6 as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_in6>() as libc::socklen_t,
+            )
+        };
+        if ret != 0 {
+            return Err(last_error(&format!("Failed to bind UDP port {} on {}", port, interface)));
+        }
+        Ok(UdpSocket::from_std(std::net::UdpSocket::from(fd))?)
+    })
+}
+
+/// `ICMP6_FILTER` of `<netinet/icmp6.h>`, missing from libc
+const ICMP6_FILTER: libc::c_int = 1;
+
+/// An ICMPv6 message received on an [`Icmp6Socket`]
+pub(crate) struct Icmp6Packet {
+    pub len: usize,
+    pub source: Ipv6Addr,
+    /// Hop limit of the IPv6 header; neighbor discovery only accepts 255
+    pub hop_limit: Option<u8>,
+}
+
+/// Raw ICMPv6 socket on one interface, for neighbor discovery
+///
+/// Sends with hop limit 255 and only delivers the ICMPv6 types in `accept`.
+/// The kernel fills in the checksum.
+pub(crate) struct Icmp6Socket {
+    fd: AsyncFd<OwnedFd>,
+    ifindex: u32,
+}
+
+impl Icmp6Socket {
+    /// Open the socket on `interface` and join the multicast `groups`
+    pub fn open(namespace: &NetNamespace, interface: &str, ifindex: u32, groups: &[Ipv6Addr], accept: &[u8]) -> NetctlResult<Self> {
+        namespace.run_in(|| {
+            let fd = new_socket(libc::AF_INET6, libc::SOCK_RAW, libc::IPPROTO_ICMPV6)?;
+
+            let mut device = [0u8; libc::IFNAMSIZ];
+            device[..interface.len()].copy_from_slice(interface.as_bytes());
+            setsockopt(&fd, libc::SOL_SOCKET, libc::SO_BINDTODEVICE, &device, "Failed to bind socket to device")?;
+
+            let hops: libc::c_int = 255;
+            setsockopt(&fd, libc::IPPROTO_IPV6, libc::IPV6_UNICAST_HOPS, &hops, "Failed to set IPV6_UNICAST_HOPS")?;
+            setsockopt(&fd, libc::IPPROTO_IPV6, libc::IPV6_MULTICAST_HOPS, &hops, "Failed to set IPV6_MULTICAST_HOPS")?;
+            let index = ifindex as libc::c_int;
+            setsockopt(&fd, libc::IPPROTO_IPV6, libc::IPV6_MULTICAST_IF, &index, "Failed to set IPV6_MULTICAST_IF")?;
+            let zero: libc::c_int = 0;
+            setsockopt(&fd, libc::IPPROTO_IPV6, libc::IPV6_MULTICAST_LOOP, &zero, "Failed to set IPV6_MULTICAST_LOOP")?;
+            let one: libc::c_int = 1;
+            setsockopt(&fd, libc::IPPROTO_IPV6, libc::IPV6_RECVHOPLIMIT, &one, "Failed to set IPV6_RECVHOPLIMIT")?;
+
+            // A set bit blocks the type
+            let mut filter = [u32::MAX; 8];
+            for kind in accept {
+                filter[usize::from(kind >> 5)] &= !(1 << (kind & 31));
+            }
+            setsockopt(&fd, libc::IPPROTO_ICMPV6, ICMP6_FILTER, &filter, "Failed to set ICMP6_FILTER")?;
+
+            for group in groups {
+                let membership = libc::ipv6_mreq {
+                    ipv6mr_multiaddr: libc::in6_addr { s6_addr: group.octets() },
+                    ipv6mr_interface: ifindex,
+                };
+                setsockopt(&fd, libc::IPPROTO_IPV6, libc::IPV6_ADD_MEMBERSHIP, &membership,
+                    &format!("Failed to join {} on {}", group, interface))?;
+            }
+            Ok(Self { fd: AsyncFd::new(fd)?, ifindex })
+        })
+    }
+
+    /// Send an ICMPv6 message to `dest`
+    pub async fn send_to(&self, payload: &[u8], dest: Ipv6Addr) -> NetctlResult<()> {
+        // SAFETY: sockaddr_in6 is plain old data for which all zeroes is valid
+        let mut addr: libc::sockaddr_in6 = unsafe { std::mem::zeroed() };
+        addr.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+        addr.sin6_addr = libc::in6_addr { s6_addr: dest.octets() };
+        addr.sin6_scope_id = self.ifindex;
+        self.fd.async_io(Interest::WRITABLE, |fd| {
+            // SAFETY: payload and addr are valid for the duration of the call
+            let ret = unsafe {
+                libc::sendto(
+                    fd.as_raw_fd(),
+                    payload.as_ptr() as *const libc::c_void,
+                    payload.len(),
+                    0,
+                    &addr as *const libc::sockaddr_in6 as *const libc::sockaddr,
+                    std::mem::size_of::<libc::sockaddr_in6>() as libc::socklen_t,
+                )
+            };
+            if ret < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        }).await?;
+        Ok(())
+    }
+
+    /// Receive the next ICMPv6 message into `buf`
+    pub async fn recv(&self, buf: &mut [u8]) -> NetctlResult<Icmp6Packet> {
+        let packet = self.fd.async_io(Interest::READABLE, |fd| {
+            // SAFETY: sockaddr_in6 and msghdr are plain old data for which all zeroes is valid
+            let mut addr: libc::sockaddr_in6 = unsafe { std::mem::zeroed() };
+            let mut control = [0u64; 8];
+            let mut iov = libc::iovec { iov_base: buf.as_mut_ptr() as *mut libc::c_void, iov_len: buf.len() };
+            // SAFETY: as above
+            let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+            msg.msg_name = &mut addr as *mut libc::sockaddr_in6 as *mut libc::c_void;
+            msg.msg_namelen = std::mem::size_of::<libc::sockaddr_in6>() as libc::socklen_t;
+            msg.msg_iov = &mut iov;
+            msg.msg_iovlen = 1;
+            msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+            msg.msg_controllen = std::mem::size_of_val(&control) as _;
+            // SAFETY: msg points to live buffers of the given sizes
+            let ret = unsafe { libc::recvmsg(fd.as_raw_fd(), &mut msg, 0) };
+            if ret < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+
+            let mut hop_limit = None;
+            // SAFETY: the control messages were filled in by recvmsg within msg_controllen
+            unsafe {
+                let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+                while !cmsg.is_null() {
+                    if (*cmsg).cmsg_level == libc::IPPROTO_IPV6 && (*cmsg).cmsg_type == libc::IPV6_HOPLIMIT {
+                        let value = std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::c_int);
+                        hop_limit = u8::try_from(value).ok();
+                    }
+                    cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+                }
+            }
+            Ok(Icmp6Packet {
+                len: ret as usize,
+                source: Ipv6Addr::from(addr.sin6_addr.s6_addr),
+                hop_limit,
+            })
+        }).await?;
+        Ok(packet)
+    }
+}
+
+fn checksum(data: &[u8], initial: u32) -> u16 {
+    let mut sum = initial;
+    for chunk in data.chunks(2) {
+        let word = u16::from_be_bytes([chunk[0], chunk.get(1).copied().unwrap_or(0)]);
+        sum += u32::from(word);
+    }
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Wrap a UDP payload in UDP and IPv4 headers for a packet socket
+pub(crate) fn build_udp_ipv4(src: SocketAddrV4, dst: SocketAddrV4, payload: &[u8]) -> Vec<u8> {
+    let udp_len = (UDP_HEADER_LEN + payload.len()) as u16;
+    let total_len = IPV4_HEADER_LEN as u16 + udp_len;
+
+    let mut packet = Vec::with_capacity(usize::from(total_len));
+    packet.extend_from_slice(&[0x45, 0x10]); // version 4, IHL 5, low delay
+    packet.extend_from_slice(&total_len.to_be_bytes());
+    packet.extend_from_slice(&[0, 0, 0, 0]); // identification, flags, fragment offset
+    packet.extend_from_slice(&[64, IPPROTO_UDP, 0, 0]); // TTL, protocol, checksum
+    packet.extend_from_slice(&src.ip().octets());
+    packet.extend_from_slice(&dst.ip().octets());
+    let header_sum = checksum(&packet, 0);
+    packet[10..12].copy_from_slice(&header_sum.to_be_bytes());
+
+    let mut udp = Vec::with_capacity(usize::from(udp_len));
+    udp.extend_from_slice(&src.port().to_be_bytes());
+    udp.extend_from_slice(&dst.port().to_be_bytes());
+    udp.extend_from_slice(&udp_len.to_be_bytes());
+    udp.extend_from_slice(&[0, 0]);
+    udp.extend_from_slice(payload);
+
+    // Pseudo header: source, destination, protocol, UDP length
+    let mut pseudo = Vec::with_capacity(12);
+    pseudo.extend_from_slice(&src.ip().octets());
+    pseudo.extend_from_slice(&dst.ip().octets());
+    pseudo.extend_from_slice(&[0, IPPROTO_UDP]);
+    pseudo.extend_from_slice(&udp_len.to_be_bytes());
+    let pseudo_sum: u32 = pseudo.chunks(2).map(|w| u32::from(u16::from_be_bytes([w[0], w[1]]))).sum();
+    let udp_sum = match checksum(&udp, pseudo_sum) {
+        0 => 0xffff,
+        sum => sum,
+    };
+    udp[6..8].copy_from_slice(&udp_sum.to_be_bytes());
+
+    packet.extend_from_slice(&udp);
+    packet
+}
+
+/// Extract the payload of an unfragmented IPv4 UDP datagram sent to `port`
+///
+/// Returns the source address and the payload.
+pub(crate) fn parse_udp_ipv4(packet: &[u8], port: u16) -> Option<(SocketAddrV4, &[u8])> {
+    if packet.len() < IPV4_HEADER_LEN || packet[0] >> 4 != 4 {
+        return None;
+    }
+    let header_len = usize::from(packet[0] & 0x0f) * 4;
+    let total_len = usize::from(u16::from_be_bytes([packet[2], packet[3]]));
+    let fragmented = u16::from_be_bytes([packet[6], packet[7]]) & 0x3fff != 0;
+    if header_len < IPV4_HEADER_LEN || total_len > packet.len() || total_len < header_len + UDP_HEADER_LEN
+        || fragmented || packet[9] != IPPROTO_UDP
+    {
+        return None;
+    }
+
+    let src_ip = Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]);
+    let udp = &packet[header_len..total_len];
+    let src_port = u16::from_be_bytes([udp[0], udp[1]]);
+    let dst_port = u16::from_be_bytes([udp[2], udp[3]]);
+    let udp_len = usize::from(u16::from_be_bytes([udp[4], udp[5]]));
+    if dst_port != port || udp_len < UDP_HEADER_LEN || udp_len > udp.len() {
+        return None;
+    }
+    Some((SocketAddrV4::new(src_ip, src_port), &udp[UDP_HEADER_LEN..udp_len]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_udp_ipv4_roundtrip() {
+        let src = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 68);
+        let dst = SocketAddrV4::new(Ipv4Addr::BROADCAST, 67);
+        let packet = build_udp_ipv4(src, dst, b"payload");
+
+        assert_eq!(packet.len(), 20 + 8 + 7);
+        // A correct header checksums to zero
+        assert_eq!(checksum(&packet[..20], 0), 0);
+
+        assert_eq!(parse_udp_ipv4(&packet, 67), Some((src, &b"payload"[..])));
+        assert_eq!(parse_udp_ipv4(&packet, 68), None);
+        assert_eq!(parse_udp_ipv4(&packet[..25], 67), None);
+    }
+}
@@ -0,0 +1,629 @@
+//! System resolver management for per-connection DNS settings
+//!
+//! Connections register their nameservers and search domains by interface.
+//! Depending on the system, the merged result is written to resolv.conf,
+//! handed to systemd-resolved per link, or passed to resolvconf. With the
+//! resolv.conf backend, the first registration saves the existing file and
+//! removing the last one puts it back.
+//!
+//! Routing domains (`~corp.example`) are only looked up through the servers
+//! of the connection that owns them. systemd-resolved does this natively;
+//! with the other backends the domains become forward zones of the local
+//! unbound. The owning connection's servers are left out of resolv.conf only
+//! while that unbound is running and is the first nameserver, since nothing
+//! else would answer for the domains.
+
+use crate::dns::{DnsController, ForwardZone};
+use crate::error::{NetctlError, NetctlResult};
+use crate::interface::InterfaceController;
+use crate::validation;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+/// Default resolver configuration file
+pub const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+/// Directory systemd-resolved keeps its resolv.conf variants in
+const RESOLVED_RUN_DIR: &str = "/run/systemd/resolve";
+
+const RESOLVCONF_BIN: &str = "/sbin/resolvconf";
+
+/// glibc only uses the first three nameservers
+const MAXNS: usize = 3;
+
+/// DNS settings contributed by one connection
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResolverEntry {
+    /// Interface the connection is active on
+    pub interface: String,
+    /// Nameserver addresses
+    pub servers: Vec<String>,
+    /// Search domains
+    pub search: Vec<String>,
+    /// Domains resolved only through `servers`, without the leading `~`
+    #[serde(default)]
+    pub routing_domains: Vec<String>,
+    /// Lower values come first; equal priorities keep registration order
+    pub priority: i32,
+}
+
+impl ResolverEntry {
+    /// Add a search domain, or a routing domain if it starts with `~`
+    pub fn add_domain(&mut self, domain: &str) {
+        let (list, domain) = match domain.strip_prefix('~') {
+            Some(domain) => (&mut self.routing_domains, domain),
+            None => (&mut self.search, domain),
+        };
+        if !list.iter().any(|d| d == domain) {
+            list.push(domain.to_string());
+        }
+    }
+
+    /// Whether the entry carries anything for the resolver
+    pub fn is_empty(&self) -> bool {
+        self.servers.is_empty() && self.search.is_empty() && self.routing_domains.is_empty()
+    }
+
+    fn validate(&self) -> NetctlResult<()> {
+        validation::validate_interface_name(&self.interface)?;
+        for server in &self.servers {
+            validation::validate_ip_address(server)?;
+        }
+        for domain in self.search.iter().chain(&self.routing_domains) {
+            validation::validate_hostname(domain.trim_end_matches('.'))?;
+        }
+        if !self.routing_domains.is_empty() && self.servers.is_empty() {
+            return Err(NetctlError::InvalidParameter(format!(
+                "Routing domains on {} need DNS servers", self.interface
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Where the merged configuration goes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolverBackend {
+    /// Write a resolv.conf file directly
+    File(PathBuf),
+    /// Configure links of systemd-resolved over D-Bus
+    Resolved,
+    /// Register per-interface records with resolvconf
+    Resolvconf,
+}
+
+impl ResolverBackend {
+    /// Pick the backend that owns /etc/resolv.conf on this system
+    pub fn detect() -> Self {
+        if let Ok(target) = std::fs::read_link(RESOLV_CONF) {
+            if target.starts_with(RESOLVED_RUN_DIR) || target.starts_with(format!("..{}", RESOLVED_RUN_DIR)) {
+                return Self::Resolved;
+            }
+        }
+        if Path::new(RESOLVCONF_BIN).exists() {
+            return Self::Resolvconf;
+        }
+        Self::File(PathBuf::from(RESOLV_CONF))
+    }
+}
+
+/// resolv.conf as it was before netctl took over
+#[derive(Debug, Clone)]
+enum Original {
+    Missing,
+    File(String),
+    Symlink(PathBuf),
+}
+
+#[derive(Default)]
+struct ResolverState {
+    /// Registered entries, in registration order
+    entries: Vec<ResolverEntry>,
+    /// Saved resolv.conf for the file backend
+    original: Option<Original>,
+}
+
+impl ResolverState {
+    /// Entries ordered by priority
+    fn ordered(&self) -> Vec<&ResolverEntry> {
+        let mut entries: Vec<&ResolverEntry> = self.entries.iter().collect();
+        entries.sort_by_key(|entry| entry.priority);
+        entries
+    }
+
+    /// Nameservers for resolv.conf
+    ///
+    /// With split DNS, the servers of entries that own routing domains only
+    /// answer for those domains.
+    fn servers(&self, split: bool) -> Vec<String> {
+        merge(self.ordered().into_iter()
+            .filter(|entry| !split || entry.routing_domains.is_empty())
+            .flat_map(|entry| &entry.servers))
+    }
+
+    /// Forward zones for the routing domains; the entry with the lowest
+    /// priority value owns a domain claimed by several
+    fn split_zones(&self) -> Vec<ForwardZone> {
+        let mut zones: Vec<ForwardZone> = Vec::new();
+        for entry in self.ordered() {
+            for domain in &entry.routing_domains {
+                let name = domain.trim_end_matches('.');
+                if !zones.iter().any(|zone| zone.name == name) {
+                    zones.push(ForwardZone {
+                        name: name.to_string(),
+                        servers: entry.servers.clone(),
+                    });
+                }
+            }
+        }
+        zones
+    }
+
+    fn search(&self) -> Vec<String> {
+        merge(self.ordered().into_iter().flat_map(|entry| &entry.search))
+    }
+}
+
+/// Resolver manager
+pub struct ResolverManager {
+    backend: ResolverBackend,
+    /// Local unbound that routing domains are forwarded through
+    split_dns: Option<Arc<DnsController>>,
+    state: Mutex<ResolverState>,
+}
+
+impl ResolverManager {
+    /// Create a resolver manager for the backend detected on this system
+    pub fn new() -> Self {
+        Self::with_backend(ResolverBackend::detect())
+    }
+
+    /// Create a resolver manager that writes a resolv.conf at `path`
+    pub fn with_path(path: impl AsRef<Path>) -> Self {
+        Self::with_backend(ResolverBackend::File(path.as_ref().to_path_buf()))
+    }
+
+    /// Create a resolver manager for a specific backend
+    pub fn with_backend(backend: ResolverBackend) -> Self {
+        Self {
+            backend,
+            split_dns: None,
+            state: Mutex::new(ResolverState::default()),
+        }
+    }
+
+    /// Forward routing domains through the unbound managed by `dns`
+    ///
+    /// Not used with systemd-resolved, which routes by domain itself.
+    pub fn with_split_dns(mut self, dns: Arc<DnsController>) -> Self {
+        self.split_dns = Some(dns);
+        self
+    }
+
+    /// Backend in use
+    pub fn backend(&self) -> &ResolverBackend {
+        &self.backend
+    }
+
+    /// unbound used for split DNS, if routing domains go through one
+    fn split_controller(&self) -> Option<&DnsController> {
+        match self.backend {
+            ResolverBackend::Resolved => None,
+            _ => self.split_dns.as_deref(),
+        }
+    }
+
+    /// Whether routing domains are answered by the managed unbound
+    ///
+    /// It has to be running and be the first nameserver that remains in
+    /// resolv.conf for the `entries`.
+    async fn split_active(&self, entries: &[ResolverEntry]) -> bool {
+        let Some(dns) = self.split_controller() else { return false };
+        if !dns.is_running().await.unwrap_or(false) {
+            return false;
+        }
+        let state = ResolverState { entries: entries.to_vec(), ..Default::default() };
+        let Some(first) = state.servers(true).first().and_then(|server| server.parse::<IpAddr>().ok()) else {
+            return false;
+        };
+        dns.nameserver_addresses().await.contains(&first)
+    }
+
+    /// Push the forward zones of the routing domains to unbound
+    ///
+    /// Without `split`, the servers owning the domains stay in resolv.conf.
+    async fn update_split_zones(&self, state: &ResolverState, split: bool) -> NetctlResult<()> {
+        let zones = state.split_zones();
+        match self.split_controller() {
+            Some(dns) => {
+                if !zones.is_empty() && !split {
+                    warn!("Split DNS resolver is not the system nameserver, routing domains are not honoured");
+                }
+                dns.set_split_zones(&zones).await
+            }
+            None => {
+                if !zones.is_empty() && self.backend != ResolverBackend::Resolved {
+                    warn!("No split DNS resolver configured, routing domains are not honoured");
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Contents of resolv.conf for the current entries
+    fn render_state(state: &ResolverState, split: bool) -> String {
+        render(&state.servers(split), &state.search())
+    }
+
+    /// Register the DNS settings of an interface, replacing previous ones
+    pub async fn set(&self, entry: ResolverEntry) -> NetctlResult<()> {
+        entry.validate()?;
+
+        let mut state = self.state.lock().await;
+        info!(
+            "Setting DNS for {}: servers [{}], search [{}], routing [{}], priority {}",
+            entry.interface, entry.servers.join(", "), entry.search.join(", "),
+            entry.routing_domains.join(", "), entry.priority
+        );
+        let mut entries = state.entries.clone();
+        upsert(&mut entries, entry.clone());
+        let split = self.split_active(&entries).await;
+
+        match &self.backend {
+            ResolverBackend::File(path) => {
+                if state.original.is_none() {
+                    state.original = Some(save_original(path).await?);
+                }
+                upsert(&mut state.entries, entry);
+                write_atomic(path, &Self::render_state(&state, split)).await?;
+            }
+            ResolverBackend::Resolved => {
+                resolved_set_link(&entry).await?;
+                upsert(&mut state.entries, entry);
+            }
+            ResolverBackend::Resolvconf => {
+                resolvconf_add(&entry, split && !entry.routing_domains.is_empty()).await?;
+                upsert(&mut state.entries, entry);
+            }
+        }
+        self.update_split_zones(&state, split).await
+    }
+
+    /// Remove the DNS settings of an interface
+    ///
+    /// With the resolv.conf backend, the original file is restored when no
+    /// interface has settings left.
+    pub async fn clear(&self, interface: &str) -> NetctlResult<()> {
+        let mut state = self.state.lock().await;
+        let Some(pos) = state.entries.iter().position(|e| e.interface == interface) else {
+            return Ok(());
+        };
+        state.entries.remove(pos);
+        debug!("Removing DNS settings of {}", interface);
+
+        let split = self.split_active(&state.entries).await;
+        self.update_split_zones(&state, split).await?;
+
+        match &self.backend {
+            ResolverBackend::File(path) => {
+                if !state.entries.is_empty() {
+                    return write_atomic(path, &Self::render_state(&state, split)).await;
+                }
+                info!("Restoring {}", path.display());
+                match state.original.take() {
+                    Some(original) => restore_original(path, original).await,
+                    None => Ok(()),
+                }
+            }
+            ResolverBackend::Resolved => resolved_revert_link(interface).await,
+            ResolverBackend::Resolvconf => resolvconf_delete(interface).await,
+        }
+    }
+
+    /// Nameservers used for all domains, in priority order
+    pub async fn servers(&self) -> Vec<String> {
+        let state = self.state.lock().await;
+        state.servers(self.split_active(&state.entries).await)
+    }
+
+    /// Forward zones of the routing domains of all interfaces
+    pub async fn split_zones(&self) -> Vec<ForwardZone> {
+        self.state.lock().await.split_zones()
+    }
+
+    /// Search domains of all interfaces, in priority order
+    pub async fn search_domains(&self) -> Vec<String> {
+        self.state.lock().await.search()
+    }
+
+    /// Registered entries, in priority order
+    pub async fn entries(&self) -> Vec<ResolverEntry> {
+        self.state.lock().await.ordered().into_iter().cloned().collect()
+    }
+}
+
+impl Default for ResolverManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn upsert(entries: &mut Vec<ResolverEntry>, entry: ResolverEntry) {
+    match entries.iter_mut().find(|e| e.interface == entry.interface) {
+        Some(existing) => *existing = entry,
+        None => entries.push(entry),
+    }
+}
+
+/// Concatenate values, dropping duplicates
+fn merge<'a>(values: impl Iterator<Item = &'a String>) -> Vec<String> {
+    let mut merged: Vec<String> = Vec::new();
+    for value in values {
+        if !merged.contains(value) {
+            merged.push(value.clone());
+        }
+    }
+    merged
+}
+
+fn render(servers: &[String], search: &[String]) -> String {
+    let mut out = String::from("# Generated by netctl\n");
+    if !search.is_empty() {
+        out.push_str(&format!("search {}\n", search.join(" ")));
+    }
+    if servers.len() > MAXNS {
+        out.push_str("# NOTE: the libc resolver may not support more than 3 nameservers\n");
+    }
+    for server in servers {
+        out.push_str(&format!("nameserver {}\n", server));
+    }
+    out
+}
+
+async fn save_original(path: &Path) -> NetctlResult<Original> {
+    match fs::symlink_metadata(path).await {
+        Ok(meta) if meta.file_type().is_symlink() => Ok(Original::Symlink(fs::read_link(path).await?)),
+        Ok(_) => Ok(Original::File(fs::read_to_string(path).await?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Original::Missing),
+        Err(e) => Err(NetctlError::Io(e)),
+    }
+}
+
+async fn restore_original(path: &Path, original: Original) -> NetctlResult<()> {
+    match original {
+        Original::File(contents) => write_atomic(path, &contents).await,
+        Original::Symlink(target) => {
+            remove_if_exists(path).await?;
+            fs::symlink(target, path).await?;
+            Ok(())
+        }
+        Original::Missing => remove_if_exists(path).await,
+    }
+}
+
+async fn remove_if_exists(path: &Path) -> NetctlResult<()> {
+    match fs::remove_file(path).await {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(NetctlError::Io(e)),
+    }
+}
+
+/// Replace `path` without readers ever seeing a partial file
+async fn write_atomic(path: &Path, contents: &str) -> NetctlResult<()> {
+    let file_name = path.file_name()
+        .ok_or_else(|| NetctlError::InvalidParameter(format!("Invalid path: {}", path.display())))?;
+    let tmp = path.with_file_name(format!(".{}.netctl-tmp", file_name.to_string_lossy()));
+
+    let mut file = fs::File::create(&tmp).await?;
+    file.write_all(contents.as_bytes()).await?;
+    file.sync_all().await?;
+    drop(file);
+
+    if let Err(e) = fs::rename(&tmp, path).await {
+        let _ = fs::remove_file(&tmp).await;
+        return Err(NetctlError::Io(e));
+    }
+    Ok(())
+}
+
+// === systemd-resolved ===
+
+const RESOLVED_SERVICE: &str = "org.freedesktop.resolve1";
+const RESOLVED_PATH: &str = "/org/freedesktop/resolve1";
+const RESOLVED_MANAGER: &str = "org.freedesktop.resolve1.Manager";
+
+async fn link_index(interface: &str) -> NetctlResult<i32> {
+    InterfaceController::new().get_info(interface).await?
+        .index
+        .and_then(|index| i32::try_from(index).ok())
+        .ok_or_else(|| NetctlError::NotFound(format!("Interface index of {}", interface)))
+}
+
+async fn resolved_call<B>(method: &str, body: &B) -> NetctlResult<()>
+where
+    B: serde::Serialize + zbus::zvariant::DynamicType,
+{
+    let conn = zbus::Connection::system().await
+        .map_err(|e| NetctlError::ServiceError(format!("Failed to connect to system bus: {}", e)))?;
+    conn.call_method(Some(RESOLVED_SERVICE), RESOLVED_PATH, Some(RESOLVED_MANAGER), method, body)
+        .await
+        .map_err(|e| NetctlError::ServiceError(format!("systemd-resolved {} failed: {}", method, e)))?;
+    Ok(())
+}
+
+async fn resolved_set_link(entry: &ResolverEntry) -> NetctlResult<()> {
+    let index = link_index(&entry.interface).await?;
+
+    let servers: Vec<(i32, Vec<u8>)> = entry.servers.iter()
+        .filter_map(|server| match server.parse::<IpAddr>().ok()? {
+            IpAddr::V4(v4) => Some((libc::AF_INET, v4.octets().to_vec())),
+            IpAddr::V6(v6) => Some((libc::AF_INET6, v6.octets().to_vec())),
+        })
+        .collect();
+    let domains: Vec<(String, bool)> = entry.search.iter()
+        .map(|domain| (domain.clone(), false))
+        .chain(entry.routing_domains.iter().map(|domain| (domain.clone(), true)))
+        .collect();
+
+    resolved_call("SetLinkDNS", &(index, servers)).await?;
+    resolved_call("SetLinkDomains", &(index, domains)).await?;
+    // Links with routing domains only answer for those
+    resolved_call("SetLinkDefaultRoute", &(index, entry.routing_domains.is_empty())).await
+}
+
+async fn resolved_revert_link(interface: &str) -> NetctlResult<()> {
+    match link_index(interface).await {
+        Ok(index) => resolved_call("RevertLink", &(index,)).await,
+        // resolved forgets links that are gone
+        Err(NetctlError::NotFound(_)) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+// === resolvconf ===
+
+fn resolvconf_record(interface: &str) -> String {
+    format!("{}.netctl", interface)
+}
+
+/// Register an interface with resolvconf; with `split`, its servers are left
+/// to the split DNS resolver
+async fn resolvconf_add(entry: &ResolverEntry, split: bool) -> NetctlResult<()> {
+    let mut child = Command::new(RESOLVCONF_BIN)
+        .arg("-a").arg(resolvconf_record(&entry.interface))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| NetctlError::ServiceError(format!("Failed to run resolvconf: {}", e)))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        let servers: &[String] = if split { &[] } else { &entry.servers };
+        stdin.write_all(render(servers, &entry.search).as_bytes()).await?;
+    }
+
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Err(NetctlError::ServiceError(format!(
+            "resolvconf -a failed: {}",
+            validation::sanitize_error_message(&String::from_utf8_lossy(&output.stderr))
+        )));
+    }
+    Ok(())
+}
+
+async fn resolvconf_delete(interface: &str) -> NetctlResult<()> {
+    let output = Command::new(RESOLVCONF_BIN)
+        .arg("-d").arg(resolvconf_record(interface))
+        .output()
+        .await
+        .map_err(|e| NetctlError::ServiceError(format!("Failed to run resolvconf: {}", e)))?;
+
+    if !output.status.success() {
+        warn!("resolvconf -d {} failed: {}", interface, String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(interface: &str, servers: &[&str], domains: &[&str], priority: i32) -> ResolverEntry {
+        let mut entry = ResolverEntry {
+            interface: interface.to_string(),
+            servers: servers.iter().map(|s| s.to_string()).collect(),
+            priority,
+            ..Default::default()
+        };
+        for domain in domains {
+            entry.add_domain(domain);
+        }
+        entry
+    }
+
+    #[tokio::test]
+    async fn test_merge_and_restore() {
+        let dir = std::env::temp_dir().join(format!("netctl-resolver-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("resolv.conf");
+        std::fs::write(&path, "nameserver 192.0.2.53\n").unwrap();
+
+        let resolver = ResolverManager::with_path(&path);
+        resolver.set(entry("eth0", &["10.0.0.53", "10.0.0.54"], &["lan"], 100)).await.unwrap();
+        resolver.set(entry("wg0", &["10.8.0.53", "10.0.0.54"], &["corp.example"], 50)).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "# Generated by netctl\nsearch corp.example lan\n\
+             nameserver 10.8.0.53\nnameserver 10.0.0.54\nnameserver 10.0.0.53\n"
+        );
+        assert!(resolver.set(entry("eth0", &["not-an-ip"], &[], 100)).await.is_err());
+        assert!(resolver.set(entry("eth0", &[], &["bad domain"], 100)).await.is_err());
+
+        resolver.clear("wg0").await.unwrap();
+        assert_eq!(resolver.servers().await, vec!["10.0.0.53", "10.0.0.54"]);
+        assert_eq!(resolver.search_domains().await, vec!["lan"]);
+        resolver.clear("eth0").await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "nameserver 192.0.2.53\n");
+
+        // A symlinked resolv.conf is put back as a symlink
+        std::fs::remove_file(&path).unwrap();
+        std::os::unix::fs::symlink("stub-resolv.conf", &path).unwrap();
+        resolver.set(entry("eth0", &["10.0.0.53"], &[], 100)).await.unwrap();
+        assert!(!std::fs::symlink_metadata(&path).unwrap().file_type().is_symlink());
+        resolver.clear("eth0").await.unwrap();
+        assert_eq!(std::fs::read_link(&path).unwrap(), PathBuf::from("stub-resolv.conf"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_split_dns() {
+        let dir = std::env::temp_dir().join(format!("netctl-resolver-split-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("resolv.conf");
+        let dns = Arc::new(DnsController::with_config_dir(dir.join("unbound")));
+
+        let resolver = ResolverManager::with_path(&path).with_split_dns(dns.clone());
+        resolver.set(entry("eth0", &["192.168.1.1"], &["lan"], 100)).await.unwrap();
+        resolver.set(entry("wg0", &["10.8.0.53"], &["~corp.example", "~lab.example"], 50)).await.unwrap();
+        resolver.set(entry("tun0", &["10.9.0.53"], &["~corp.example"], 60)).await.unwrap();
+        assert!(resolver.set(entry("tun1", &[], &["~other.example"], 60)).await.is_err());
+
+        // unbound is not running, so the VPN servers still answer for everything
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "# Generated by netctl\nsearch lan\nnameserver 10.8.0.53\nnameserver 10.9.0.53\nnameserver 192.168.1.1\n"
+        );
+        assert_eq!(resolver.servers().await.len(), 3);
+        // With unbound as the nameserver they only answer for their routing domains
+        assert_eq!(resolver.state.lock().await.servers(true), vec!["192.168.1.1".to_string()]);
+        let zones = vec![
+            ForwardZone { name: "corp.example".to_string(), servers: vec!["10.8.0.53".to_string()] },
+            ForwardZone { name: "lab.example".to_string(), servers: vec!["10.8.0.53".to_string()] },
+        ];
+        assert_eq!(resolver.split_zones().await, zones);
+        assert_eq!(dns.split_zones().await, zones);
+
+        // The next owner takes over when a VPN goes away
+        resolver.clear("wg0").await.unwrap();
+        assert_eq!(dns.split_zones().await, vec![
+            ForwardZone { name: "corp.example".to_string(), servers: vec!["10.9.0.53".to_string()] },
+        ]);
+        resolver.clear("tun0").await.unwrap();
+        assert!(dns.split_zones().await.is_empty());
+        resolver.clear("eth0").await.unwrap();
+        assert!(!path.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
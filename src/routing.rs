@@ -1,49 +1,1083 @@
 //! Routing table management
+//!
+//! Routes and policy rules are read and programmed over rtnetlink. Both
+//! address families and all routing tables are supported; routes and rules
+//! are exchanged as [`Route`] and [`RoutingRule`] values which mirror what
+//! `ip route` and `ip rule` show.
 
 use crate::error::{NetctlError, NetctlResult};
+use crate::netlink;
+use crate::netns::NetNamespace;
 use crate::validation;
-use tokio::process::Command;
+use futures::stream::TryStreamExt;
+use netlink_packet_route::route::{
+    RouteAddress, RouteAttribute, RouteFlags, RouteMessage,
+    RouteProtocol as NlRouteProtocol, RouteScope as NlRouteScope, RouteType as NlRouteType,
+};
+use netlink_packet_route::AddressFamily;
+use netlink_packet_route::rule::{
+    RuleAction as NlRuleAction, RuleAttribute, RuleFlags, RuleMessage, RuleUidRange,
+};
+use rtnetlink::{Handle, IpVersion, RouteMessageBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use tracing::info;
 
-pub struct RoutingController;
+/// Well-known routing table: `default`
+pub const RT_TABLE_DEFAULT: u32 = 253;
+/// Well-known routing table: `main`
+pub const RT_TABLE_MAIN: u32 = 254;
+/// Well-known routing table: `local`
+pub const RT_TABLE_LOCAL: u32 = 255;
+
+/// Route type (`ip route add <type> ...`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RouteType {
+    #[default]
+    Unicast,
+    Local,
+    Broadcast,
+    Anycast,
+    Multicast,
+    Blackhole,
+    Unreachable,
+    Prohibit,
+    Throw,
+    Other(u8),
+}
+
+/// Route scope
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RouteScope {
+    #[default]
+    Global,
+    Site,
+    Link,
+    Host,
+    Nowhere,
+    Other(u8),
+}
+
+/// Routing protocol that installed a route
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RouteProtocol {
+    Unspec,
+    Redirect,
+    Kernel,
+    Boot,
+    #[default]
+    Static,
+    Ra,
+    Dhcp,
+    Other(u8),
+}
+
+/// A routing table entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Route {
+    /// Destination network (the unspecified address for default routes)
+    pub destination: IpAddr,
+    pub prefix_len: u8,
+    /// Next hop
+    pub gateway: Option<IpAddr>,
+    /// Output interface
+    pub interface: Option<String>,
+    /// Preferred source address (`src`)
+    pub source: Option<IpAddr>,
+    /// Route priority (`metric`)
+    pub metric: Option<u32>,
+    pub table: u32,
+    pub scope: RouteScope,
+    pub protocol: RouteProtocol,
+    pub kind: RouteType,
+    /// Treat the gateway as directly reachable even if no prefix covers it
+    pub onlink: bool,
+}
+
+impl Route {
+    /// A unicast route to `destination/prefix_len` in the main table
+    pub fn new(destination: IpAddr, prefix_len: u8) -> Self {
+        Self {
+            destination,
+            prefix_len,
+            gateway: None,
+            interface: None,
+            source: None,
+            metric: None,
+            table: RT_TABLE_MAIN,
+            scope: RouteScope::Global,
+            protocol: RouteProtocol::Static,
+            kind: RouteType::Unicast,
+            onlink: false,
+        }
+    }
+
+    /// A default route via `gateway`
+    pub fn default_via(gateway: IpAddr) -> Self {
+        let unspecified = match gateway {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        let mut route = Self::new(unspecified, 0);
+        route.gateway = Some(gateway);
+        route
+    }
+
+    /// Parse a destination as accepted by `ip route`: `default`, an address
+    /// or a prefix in CIDR notation. `ipv6` selects the family of `default`.
+    pub fn parse_destination(destination: &str, ipv6: bool) -> NetctlResult<(IpAddr, u8)> {
+        if destination == "default" {
+            return Ok(if ipv6 {
+                (IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0)
+            } else {
+                (IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)
+            });
+        }
+
+        let (addr, prefix) = match destination.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (destination, None),
+        };
+        let addr: IpAddr = addr.parse()
+            .map_err(|_| NetctlError::InvalidParameter(format!("Invalid route destination: {}", destination)))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix {
+            Some(p) => p.parse::<u8>()
+                .ok()
+                .filter(|p| *p <= max)
+                .ok_or_else(|| NetctlError::InvalidParameter(format!("Invalid prefix length: {}", destination)))?,
+            None => max,
+        };
+        Ok((addr, prefix_len))
+    }
+
+    /// Destination as `ip route` prints it: `default` or `prefix/len`
+    pub fn destination_prefix(&self) -> String {
+        if self.is_default() {
+            "default".to_string()
+        } else {
+            format!("{}/{}", self.destination, self.prefix_len)
+        }
+    }
+
+    /// Scope `ip route` picks when none is given: link for directly
+    /// connected unicast routes, global otherwise
+    pub fn implied_scope(&self) -> RouteScope {
+        if self.kind == RouteType::Unicast && self.gateway.is_none() && self.interface.is_some() {
+            RouteScope::Link
+        } else {
+            RouteScope::Global
+        }
+    }
+
+    pub fn is_ipv6(&self) -> bool {
+        self.destination.is_ipv6()
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.prefix_len == 0
+    }
+
+    /// Check the route is internally consistent before sending it to the kernel
+    pub fn validate(&self) -> NetctlResult<()> {
+        let max = if self.is_ipv6() { 128 } else { 32 };
+        if self.prefix_len > max {
+            return Err(NetctlError::InvalidParameter(format!("Invalid prefix length: {}", self.prefix_len)));
+        }
+        for (what, addr) in [("gateway", self.gateway), ("source", self.source)] {
+            if let Some(addr) = addr {
+                if addr.is_ipv6() != self.is_ipv6() {
+                    return Err(NetctlError::InvalidParameter(format!(
+                        "Route {} {} does not match the destination address family", what, addr
+                    )));
+                }
+            }
+        }
+        if let Some(iface) = &self.interface {
+            validation::validate_interface_name(iface)?;
+        }
+        if matches!(self.kind, RouteType::Blackhole | RouteType::Unreachable | RouteType::Prohibit | RouteType::Throw)
+            && (self.gateway.is_some() || self.interface.is_some())
+        {
+            return Err(NetctlError::InvalidParameter(format!(
+                "{} routes cannot have a gateway or interface", self.kind
+            )));
+        }
+        if self.onlink && self.gateway.is_none() {
+            return Err(NetctlError::InvalidParameter("onlink requires a gateway".to_string()));
+        }
+        if self.table == 0 {
+            return Err(NetctlError::InvalidParameter("Invalid routing table 0".to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Route {
+    /// Format the route the way `ip route` prints it
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.kind != RouteType::Unicast {
+            write!(f, "{} ", self.kind)?;
+        }
+        write!(f, "{}", self.destination_prefix())?;
+        if let Some(gw) = self.gateway {
+            write!(f, " via {}", gw)?;
+        }
+        if let Some(iface) = &self.interface {
+            write!(f, " dev {}", iface)?;
+        }
+        if self.table != RT_TABLE_MAIN {
+            write!(f, " table {}", table_name(self.table))?;
+        }
+        if self.protocol != RouteProtocol::Boot {
+            write!(f, " proto {}", self.protocol)?;
+        }
+        if self.scope != RouteScope::Global {
+            write!(f, " scope {}", self.scope)?;
+        }
+        if let Some(src) = self.source {
+            write!(f, " src {}", src)?;
+        }
+        if let Some(metric) = self.metric {
+            write!(f, " metric {}", metric)?;
+        }
+        if self.onlink {
+            write!(f, " onlink")?;
+        }
+        Ok(())
+    }
+}
+
+/// Name of a routing table as shown by `ip route`
+pub fn table_name(table: u32) -> String {
+    match table {
+        RT_TABLE_DEFAULT => "default".to_string(),
+        RT_TABLE_MAIN => "main".to_string(),
+        RT_TABLE_LOCAL => "local".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Parse a routing table name or number
+pub fn parse_table(table: &str) -> NetctlResult<u32> {
+    match table {
+        "default" => Ok(RT_TABLE_DEFAULT),
+        "main" => Ok(RT_TABLE_MAIN),
+        "local" => Ok(RT_TABLE_LOCAL),
+        other => other.parse::<u32>()
+            .ok()
+            .filter(|t| *t != 0)
+            .ok_or_else(|| NetctlError::InvalidParameter(format!("Invalid routing table: {}", table))),
+    }
+}
+
+impl fmt::Display for RouteType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouteType::Unicast => write!(f, "unicast"),
+            RouteType::Local => write!(f, "local"),
+            RouteType::Broadcast => write!(f, "broadcast"),
+            RouteType::Anycast => write!(f, "anycast"),
+            RouteType::Multicast => write!(f, "multicast"),
+            RouteType::Blackhole => write!(f, "blackhole"),
+            RouteType::Unreachable => write!(f, "unreachable"),
+            RouteType::Prohibit => write!(f, "prohibit"),
+            RouteType::Throw => write!(f, "throw"),
+            RouteType::Other(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+impl FromStr for RouteType {
+    type Err = NetctlError;
+
+    fn from_str(s: &str) -> NetctlResult<Self> {
+        match s {
+            "unicast" => Ok(RouteType::Unicast),
+            "local" => Ok(RouteType::Local),
+            "broadcast" => Ok(RouteType::Broadcast),
+            "anycast" => Ok(RouteType::Anycast),
+            "multicast" => Ok(RouteType::Multicast),
+            "blackhole" => Ok(RouteType::Blackhole),
+            "unreachable" => Ok(RouteType::Unreachable),
+            "prohibit" => Ok(RouteType::Prohibit),
+            "throw" => Ok(RouteType::Throw),
+            _ => Err(NetctlError::InvalidParameter(format!("Invalid route type: {}", s))),
+        }
+    }
+}
+
+impl fmt::Display for RouteScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouteScope::Global => write!(f, "global"),
+            RouteScope::Site => write!(f, "site"),
+            RouteScope::Link => write!(f, "link"),
+            RouteScope::Host => write!(f, "host"),
+            RouteScope::Nowhere => write!(f, "nowhere"),
+            RouteScope::Other(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+impl FromStr for RouteScope {
+    type Err = NetctlError;
+
+    fn from_str(s: &str) -> NetctlResult<Self> {
+        match s {
+            "global" | "universe" => Ok(RouteScope::Global),
+            "site" => Ok(RouteScope::Site),
+            "link" => Ok(RouteScope::Link),
+            "host" => Ok(RouteScope::Host),
+            "nowhere" => Ok(RouteScope::Nowhere),
+            _ => Err(NetctlError::InvalidParameter(format!("Invalid route scope: {}", s))),
+        }
+    }
+}
+
+impl fmt::Display for RouteProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouteProtocol::Unspec => write!(f, "unspec"),
+            RouteProtocol::Redirect => write!(f, "redirect"),
+            RouteProtocol::Kernel => write!(f, "kernel"),
+            RouteProtocol::Boot => write!(f, "boot"),
+            RouteProtocol::Static => write!(f, "static"),
+            RouteProtocol::Ra => write!(f, "ra"),
+            RouteProtocol::Dhcp => write!(f, "dhcp"),
+            RouteProtocol::Other(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+impl FromStr for RouteProtocol {
+    type Err = NetctlError;
+
+    fn from_str(s: &str) -> NetctlResult<Self> {
+        match s {
+            "unspec" => Ok(RouteProtocol::Unspec),
+            "redirect" => Ok(RouteProtocol::Redirect),
+            "kernel" => Ok(RouteProtocol::Kernel),
+            "boot" => Ok(RouteProtocol::Boot),
+            "static" => Ok(RouteProtocol::Static),
+            "ra" => Ok(RouteProtocol::Ra),
+            "dhcp" => Ok(RouteProtocol::Dhcp),
+            other => other.parse::<u8>()
+                .map(RouteProtocol::Other)
+                .map_err(|_| NetctlError::InvalidParameter(format!("Invalid route protocol: {}", s))),
+        }
+    }
+}
+
+impl From<NlRouteType> for RouteType {
+    fn from(kind: NlRouteType) -> Self {
+        match kind {
+            NlRouteType::Unicast => RouteType::Unicast,
+            NlRouteType::Local => RouteType::Local,
+            NlRouteType::Broadcast => RouteType::Broadcast,
+            NlRouteType::Anycast => RouteType::Anycast,
+            NlRouteType::Multicast => RouteType::Multicast,
+            NlRouteType::BlackHole => RouteType::Blackhole,
+            NlRouteType::Unreachable => RouteType::Unreachable,
+            NlRouteType::Prohibit => RouteType::Prohibit,
+            NlRouteType::Throw => RouteType::Throw,
+            other => RouteType::Other(other.into()),
+        }
+    }
+}
+
+impl From<RouteType> for NlRouteType {
+    fn from(kind: RouteType) -> Self {
+        match kind {
+            RouteType::Unicast => NlRouteType::Unicast,
+            RouteType::Local => NlRouteType::Local,
+            RouteType::Broadcast => NlRouteType::Broadcast,
+            RouteType::Anycast => NlRouteType::Anycast,
+            RouteType::Multicast => NlRouteType::Multicast,
+            RouteType::Blackhole => NlRouteType::BlackHole,
+            RouteType::Unreachable => NlRouteType::Unreachable,
+            RouteType::Prohibit => NlRouteType::Prohibit,
+            RouteType::Throw => NlRouteType::Throw,
+            RouteType::Other(n) => NlRouteType::from(n),
+        }
+    }
+}
+
+impl From<NlRouteScope> for RouteScope {
+    fn from(scope: NlRouteScope) -> Self {
+        match scope {
+            NlRouteScope::Universe => RouteScope::Global,
+            NlRouteScope::Site => RouteScope::Site,
+            NlRouteScope::Link => RouteScope::Link,
+            NlRouteScope::Host => RouteScope::Host,
+            NlRouteScope::NoWhere => RouteScope::Nowhere,
+            other => RouteScope::Other(other.into()),
+        }
+    }
+}
+
+impl From<RouteScope> for NlRouteScope {
+    fn from(scope: RouteScope) -> Self {
+        match scope {
+            RouteScope::Global => NlRouteScope::Universe,
+            RouteScope::Site => NlRouteScope::Site,
+            RouteScope::Link => NlRouteScope::Link,
+            RouteScope::Host => NlRouteScope::Host,
+            RouteScope::Nowhere => NlRouteScope::NoWhere,
+            RouteScope::Other(n) => NlRouteScope::Other(n),
+        }
+    }
+}
+
+impl From<NlRouteProtocol> for RouteProtocol {
+    fn from(protocol: NlRouteProtocol) -> Self {
+        match protocol {
+            NlRouteProtocol::Unspec => RouteProtocol::Unspec,
+            NlRouteProtocol::IcmpRedirect => RouteProtocol::Redirect,
+            NlRouteProtocol::Kernel => RouteProtocol::Kernel,
+            NlRouteProtocol::Boot => RouteProtocol::Boot,
+            NlRouteProtocol::Static => RouteProtocol::Static,
+            NlRouteProtocol::Ra => RouteProtocol::Ra,
+            NlRouteProtocol::Dhcp => RouteProtocol::Dhcp,
+            other => RouteProtocol::Other(other.into()),
+        }
+    }
+}
+
+impl From<RouteProtocol> for NlRouteProtocol {
+    fn from(protocol: RouteProtocol) -> Self {
+        match protocol {
+            RouteProtocol::Unspec => NlRouteProtocol::Unspec,
+            RouteProtocol::Redirect => NlRouteProtocol::IcmpRedirect,
+            RouteProtocol::Kernel => NlRouteProtocol::Kernel,
+            RouteProtocol::Boot => NlRouteProtocol::Boot,
+            RouteProtocol::Static => NlRouteProtocol::Static,
+            RouteProtocol::Ra => NlRouteProtocol::Ra,
+            RouteProtocol::Dhcp => NlRouteProtocol::Dhcp,
+            RouteProtocol::Other(n) => NlRouteProtocol::from(n),
+        }
+    }
+}
+
+/// Action of a routing policy rule (`ip rule ... <action>`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    /// Look the destination up in the rule's table
+    #[default]
+    Lookup,
+    Nop,
+    Blackhole,
+    Unreachable,
+    Prohibit,
+    Other(u8),
+}
+
+/// A routing policy rule
+///
+/// Selectors left unset match everything; the rule applies to one address
+/// family, chosen by `ipv6` (and checked against `from`/`to`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoutingRule {
+    pub ipv6: bool,
+    /// Rule priority; the kernel assigns one below the previous rule if unset
+    pub priority: Option<u32>,
+    /// Source prefix
+    pub from: Option<IpAddr>,
+    pub from_len: u8,
+    /// Destination prefix
+    pub to: Option<IpAddr>,
+    pub to_len: u8,
+    /// Firewall mark, optionally masked
+    pub fwmark: Option<u32>,
+    pub fwmask: Option<u32>,
+    /// Incoming interface
+    pub iif: Option<String>,
+    /// Outgoing interface
+    pub oif: Option<String>,
+    /// Inclusive range of socket owner UIDs
+    pub uid_range: Option<(u32, u32)>,
+    /// Table to look up (required for [`RuleAction::Lookup`])
+    pub table: Option<u32>,
+    pub action: RuleAction,
+    /// Match packets that do *not* match the selectors (`not`)
+    pub invert: bool,
+}
+
+impl RoutingRule {
+    /// A rule that looks up `table`
+    pub fn lookup(table: u32) -> Self {
+        Self {
+            table: Some(table),
+            ..Default::default()
+        }
+    }
+
+    /// Set the source selector, switching the rule to its address family
+    pub fn from_prefix(mut self, addr: IpAddr, prefix_len: u8) -> Self {
+        self.ipv6 = addr.is_ipv6();
+        self.from = Some(addr);
+        self.from_len = prefix_len;
+        self
+    }
+
+    /// Set the destination selector, switching the rule to its address family
+    pub fn to_prefix(mut self, addr: IpAddr, prefix_len: u8) -> Self {
+        self.ipv6 = addr.is_ipv6();
+        self.to = Some(addr);
+        self.to_len = prefix_len;
+        self
+    }
+
+    /// Parse `mark` or `mark/mask` (decimal or 0x-prefixed hex)
+    pub fn parse_fwmark(fwmark: &str) -> NetctlResult<(u32, Option<u32>)> {
+        let parse = |v: &str| {
+            let parsed = match v.strip_prefix("0x").or_else(|| v.strip_prefix("0X")) {
+                Some(hex) => u32::from_str_radix(hex, 16),
+                None => v.parse::<u32>(),
+            };
+            parsed.map_err(|_| NetctlError::InvalidParameter(format!("Invalid fwmark: {}", fwmark)))
+        };
+        match fwmark.split_once('/') {
+            Some((mark, mask)) => Ok((parse(mark)?, Some(parse(mask)?))),
+            None => Ok((parse(fwmark)?, None)),
+        }
+    }
+
+    /// Parse a UID range `start-end` (or a single UID)
+    pub fn parse_uid_range(range: &str) -> NetctlResult<(u32, u32)> {
+        let invalid = || NetctlError::InvalidParameter(format!("Invalid UID range: {}", range));
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        let start = start.trim().parse::<u32>().map_err(|_| invalid())?;
+        let end = end.trim().parse::<u32>().map_err(|_| invalid())?;
+        if start > end {
+            return Err(invalid());
+        }
+        Ok((start, end))
+    }
+
+    /// Check the rule is internally consistent before sending it to the kernel
+    pub fn validate(&self) -> NetctlResult<()> {
+        self.validate_selectors()?;
+        match (self.action, self.table) {
+            (RuleAction::Lookup, None) | (RuleAction::Lookup, Some(0)) => {
+                Err(NetctlError::InvalidParameter("Lookup rules need a routing table".to_string()))
+            }
+            (RuleAction::Lookup, _) | (_, None) => Ok(()),
+            (action, Some(_)) => Err(NetctlError::InvalidParameter(format!(
+                "{} rules cannot have a table", action
+            ))),
+        }
+    }
+
+    fn validate_selectors(&self) -> NetctlResult<()> {
+        let max = if self.ipv6 { 128 } else { 32 };
+        for (what, addr, len) in [("from", self.from, self.from_len), ("to", self.to, self.to_len)] {
+            if let Some(addr) = addr {
+                if addr.is_ipv6() != self.ipv6 {
+                    return Err(NetctlError::InvalidParameter(format!(
+                        "Rule {} address {} does not match the rule address family", what, addr
+                    )));
+                }
+                if len > max {
+                    return Err(NetctlError::InvalidParameter(format!("Invalid prefix length: {}", len)));
+                }
+            }
+        }
+        for iface in [&self.iif, &self.oif].into_iter().flatten() {
+            validation::validate_interface_name(iface)?;
+        }
+        if let Some((start, end)) = self.uid_range {
+            if start > end {
+                return Err(NetctlError::InvalidParameter(format!("Invalid UID range: {}-{}", start, end)));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for RoutingRule {
+    /// Format the rule the way `ip rule` prints it
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(priority) = self.priority {
+            write!(f, "{}:\t", priority)?;
+        }
+        if self.invert {
+            write!(f, "not ")?;
+        }
+        match self.from {
+            Some(from) => write!(f, "from {}/{}", from, self.from_len)?,
+            None => write!(f, "from all")?,
+        }
+        if let Some(to) = self.to {
+            write!(f, " to {}/{}", to, self.to_len)?;
+        }
+        if let Some(mark) = self.fwmark {
+            write!(f, " fwmark {:#x}", mark)?;
+            if let Some(mask) = self.fwmask {
+                write!(f, "/{:#x}", mask)?;
+            }
+        }
+        if let Some(iif) = &self.iif {
+            write!(f, " iif {}", iif)?;
+        }
+        if let Some(oif) = &self.oif {
+            write!(f, " oif {}", oif)?;
+        }
+        if let Some((start, end)) = self.uid_range {
+            write!(f, " uidrange {}-{}", start, end)?;
+        }
+        match (self.action, self.table) {
+            (RuleAction::Lookup, Some(table)) => write!(f, " lookup {}", table_name(table)),
+            (RuleAction::Lookup, None) => Ok(()),
+            (action, _) => write!(f, " {}", action),
+        }
+    }
+}
+
+impl fmt::Display for RuleAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleAction::Lookup => write!(f, "lookup"),
+            RuleAction::Nop => write!(f, "nop"),
+            RuleAction::Blackhole => write!(f, "blackhole"),
+            RuleAction::Unreachable => write!(f, "unreachable"),
+            RuleAction::Prohibit => write!(f, "prohibit"),
+            RuleAction::Other(n) => write!(f, "action {}", n),
+        }
+    }
+}
+
+impl FromStr for RuleAction {
+    type Err = NetctlError;
+
+    fn from_str(s: &str) -> NetctlResult<Self> {
+        match s {
+            "lookup" | "table" => Ok(RuleAction::Lookup),
+            "nop" => Ok(RuleAction::Nop),
+            "blackhole" => Ok(RuleAction::Blackhole),
+            "unreachable" => Ok(RuleAction::Unreachable),
+            "prohibit" => Ok(RuleAction::Prohibit),
+            _ => Err(NetctlError::InvalidParameter(format!("Invalid rule action: {}", s))),
+        }
+    }
+}
+
+impl From<NlRuleAction> for RuleAction {
+    fn from(action: NlRuleAction) -> Self {
+        match action {
+            NlRuleAction::ToTable => RuleAction::Lookup,
+            NlRuleAction::Nop => RuleAction::Nop,
+            NlRuleAction::Blackhole => RuleAction::Blackhole,
+            NlRuleAction::Unreachable => RuleAction::Unreachable,
+            NlRuleAction::Prohibit => RuleAction::Prohibit,
+            other => RuleAction::Other(other.into()),
+        }
+    }
+}
+
+impl From<RuleAction> for NlRuleAction {
+    fn from(action: RuleAction) -> Self {
+        match action {
+            RuleAction::Lookup => NlRuleAction::ToTable,
+            RuleAction::Nop => NlRuleAction::Nop,
+            RuleAction::Blackhole => NlRuleAction::Blackhole,
+            RuleAction::Unreachable => NlRuleAction::Unreachable,
+            RuleAction::Prohibit => NlRuleAction::Prohibit,
+            RuleAction::Other(n) => NlRuleAction::from(n),
+        }
+    }
+}
+
+pub struct RoutingController {
+    /// Network namespace the controller operates in
+    namespace: NetNamespace,
+}
 
 impl RoutingController {
     pub fn new() -> Self {
-        Self
+        Self {
+            namespace: NetNamespace::Current,
+        }
+    }
+
+    /// Create a controller bound to a network namespace
+    pub fn with_namespace(namespace: NetNamespace) -> Self {
+        Self { namespace }
+    }
+
+    /// Network namespace this controller operates in
+    pub fn namespace(&self) -> &NetNamespace {
+        &self.namespace
+    }
+
+    /// List IPv4 and IPv6 routes, from one table or from all tables
+    ///
+    /// Kernel-internal IPv6 cache and multicast entries are skipped.
+    pub async fn list_routes(&self, table: Option<u32>) -> NetctlResult<Vec<Route>> {
+        let handle = self.connect()?;
+        let names = self.link_names(&handle).await?;
+
+        let requests = [
+            RouteMessageBuilder::<Ipv4Addr>::new().build(),
+            RouteMessageBuilder::<Ipv6Addr>::new().build(),
+        ];
+        let mut routes = Vec::new();
+        for request in requests {
+            let mut stream = handle.route().get(request).execute();
+            while let Some(msg) = stream.try_next().await
+                .map_err(|e| netlink::map_error(e, "list routes", "routing table"))?
+            {
+                let Some(route) = route_from_message(&msg, |index| names.get(&index).cloned()) else { continue };
+                if route.kind == RouteType::Multicast && route.is_ipv6() {
+                    continue;
+                }
+                if table.is_none_or(|t| t == route.table) {
+                    routes.push(route);
+                }
+            }
+        }
+        Ok(routes)
+    }
+
+    /// Add a route; fails with `AlreadyExists` if an identical route exists
+    pub async fn add_route(&self, route: &Route) -> NetctlResult<()> {
+        route.validate()?;
+        let handle = self.connect()?;
+        let msg = self.build_message(&handle, route).await?;
+        handle.route().add(msg).execute().await
+            .map_err(|e| netlink::map_error(e, "add route", &route.to_string()))?;
+        info!("Added route {}", route);
+        Ok(())
+    }
+
+    /// Add a route or replace an existing one with the same destination,
+    /// table and metric
+    pub async fn replace_route(&self, route: &Route) -> NetctlResult<()> {
+        route.validate()?;
+        let handle = self.connect()?;
+        let msg = self.build_message(&handle, route).await?;
+        handle.route().add(msg).replace().execute().await
+            .map_err(|e| netlink::map_error(e, "replace route", &route.to_string()))?;
+        info!("Replaced route {}", route);
+        Ok(())
+    }
+
+    /// Delete a route
+    ///
+    /// Gateway, interface and metric narrow the match when set; scope and
+    /// protocol are ignored so that routes returned by [`list_routes`]
+    /// (or written by hand) can be passed back unchanged.
+    ///
+    /// [`list_routes`]: RoutingController::list_routes
+    pub async fn delete_route(&self, route: &Route) -> NetctlResult<()> {
+        route.validate()?;
+        let handle = self.connect()?;
+        let mut msg = self.build_message(&handle, route).await?;
+        msg.header.scope = NlRouteScope::NoWhere;
+        msg.header.protocol = NlRouteProtocol::Unspec;
+        msg.header.flags = RouteFlags::empty();
+        if route.kind == RouteType::Unicast {
+            msg.header.kind = NlRouteType::Unspec;
+        }
+        msg.attributes.retain(|attr| !matches!(attr, RouteAttribute::PrefSource(_)));
+
+        handle.route().del(msg).execute().await
+            .map_err(|e| netlink::map_error(e, "delete route", &route.to_string()))?;
+        info!("Deleted route {}", route);
+        Ok(())
     }
 
     pub async fn add_default_gateway(&self, gateway: &str, interface: Option<&str>) -> NetctlResult<()> {
         validation::validate_ip_address(gateway)?;
+        let gateway: IpAddr = gateway.parse()
+            .map_err(|_| NetctlError::InvalidParameter(format!("Invalid gateway: {}", gateway)))?;
+
+        let mut route = Route::default_via(gateway);
+        route.interface = interface.map(str::to_string);
+        self.add_route(&route).await
+    }
+
+    /// Delete default routes from the main table
+    ///
+    /// With no gateway all IPv4 default routes (optionally only those on
+    /// `interface`) are removed; with a gateway only routes via that gateway
+    /// of its address family. Returns the routes that were deleted.
+    pub async fn del_default_gateway(&self, gateway: Option<&str>, interface: Option<&str>) -> NetctlResult<Vec<Route>> {
+        let gateway = match gateway {
+            Some(gw) => {
+                validation::validate_ip_address(gw)?;
+                Some(gw.parse::<IpAddr>()
+                    .map_err(|_| NetctlError::InvalidParameter(format!("Invalid gateway: {}", gw)))?)
+            }
+            None => None,
+        };
         if let Some(iface) = interface {
             validation::validate_interface_name(iface)?;
         }
+        let ipv6 = gateway.is_some_and(|gw| gw.is_ipv6());
 
-        let mut args = vec!["route", "add", "default", "via", gateway];
-        if let Some(iface) = interface {
-            args.extend_from_slice(&["dev", iface]);
-        }
-
-        let cmd_str = format!("ip {}", args.join(" "));
-        let output = Command::new("ip")
-            .args(&args)
-            .output()
-            .await
-            .map_err(|e| NetctlError::CommandFailed {
-                cmd: cmd_str.clone(),
-                code: None,
-                stderr: e.to_string(),
-            })?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8(output.stderr)
-                .unwrap_or_else(|e| String::from_utf8_lossy(&e.into_bytes()).to_string());
-            return Err(NetctlError::CommandFailed {
-                cmd: cmd_str,
-                code: output.status.code(),
-                stderr,
-            });
+        let matching: Vec<Route> = self.default_routes(ipv6).await?
+            .into_iter()
+            .filter(|r| gateway.is_none() || r.gateway == gateway)
+            .filter(|r| interface.is_none() || r.interface.as_deref() == interface)
+            .collect();
+
+        if matching.is_empty() {
+            return Err(NetctlError::NotFound("No matching default route".to_string()));
+        }
+        for route in &matching {
+            self.delete_route(route).await?;
+        }
+        Ok(matching)
+    }
+
+    /// Unicast default routes of one address family in the main table,
+    /// preferred (lowest metric) first
+    pub async fn default_routes(&self, ipv6: bool) -> NetctlResult<Vec<Route>> {
+        let mut routes: Vec<Route> = self.list_routes(Some(RT_TABLE_MAIN)).await?
+            .into_iter()
+            .filter(|r| r.is_default() && r.kind == RouteType::Unicast && r.is_ipv6() == ipv6)
+            .collect();
+        routes.sort_by_key(|r| r.metric.unwrap_or(0));
+        Ok(routes)
+    }
+
+    /// List IPv4 and IPv6 policy rules in priority order
+    pub async fn list_rules(&self) -> NetctlResult<Vec<RoutingRule>> {
+        let handle = self.connect()?;
+        let mut rules = Vec::new();
+        for version in [IpVersion::V4, IpVersion::V6] {
+            let mut stream = handle.rule().get(version).execute();
+            while let Some(msg) = stream.try_next().await
+                .map_err(|e| netlink::map_error(e, "list rules", "routing policy"))?
+            {
+                if let Some(rule) = rule_from_message(&msg) {
+                    rules.push(rule);
+                }
+            }
         }
+        rules.sort_by_key(|r| (r.ipv6, r.priority.unwrap_or(0)));
+        Ok(rules)
+    }
+
+    /// Add a policy rule; fails with `AlreadyExists` if an identical rule exists
+    pub async fn add_rule(&self, rule: &RoutingRule) -> NetctlResult<()> {
+        rule.validate()?;
+        let handle = self.connect()?;
+        let mut request = handle.rule().add();
+        *request.message_mut() = rule_message(rule);
+        request.execute().await
+            .map_err(|e| netlink::map_error(e, "add rule", &rule.to_string()))?;
+        info!("Added rule {}", rule);
+        Ok(())
+    }
+
+    /// Delete the first policy rule matching every selector set in `rule`
+    ///
+    /// A lookup rule without a table matches rules of any action, so a rule
+    /// can be deleted by its priority alone.
+    pub async fn delete_rule(&self, rule: &RoutingRule) -> NetctlResult<()> {
+        rule.validate_selectors()?;
+        let handle = self.connect()?;
+        let mut msg = rule_message(rule);
+        if rule.action == RuleAction::Lookup && rule.table.is_none() {
+            msg.header.action = NlRuleAction::Unspec;
+        }
+        handle.rule().del(msg).execute().await
+            .map_err(|e| netlink::map_error(e, "delete rule", &rule.to_string()))?;
+        info!("Deleted rule {}", rule);
         Ok(())
     }
+
+    fn connect(&self) -> NetctlResult<Handle> {
+        self.namespace.connect()
+    }
+
+    /// Map interface indices to names
+    async fn link_names(&self, handle: &Handle) -> NetctlResult<HashMap<u32, String>> {
+        let mut names = HashMap::new();
+        let mut links = handle.link().get().execute();
+        while let Some(link) = links.try_next().await
+            .map_err(|e| netlink::map_error(e, "list links", "all"))?
+        {
+            if let Some(name) = netlink::link_name(&link) {
+                names.insert(link.header.index, name);
+            }
+        }
+        Ok(names)
+    }
+
+    async fn build_message(&self, handle: &Handle, route: &Route) -> NetctlResult<RouteMessage> {
+        let mut builder = RouteMessageBuilder::<IpAddr>::new()
+            .destination_prefix(route.destination, route.prefix_len)
+            .map_err(invalid_route)?;
+        if let Some(gw) = route.gateway {
+            builder = builder.gateway(gw).map_err(invalid_route)?;
+        }
+        if let Some(src) = route.source {
+            builder = builder.pref_source(src).map_err(invalid_route)?;
+        }
+        if let Some(iface) = &route.interface {
+            builder = builder.output_interface(netlink::link_index(handle, iface).await?);
+        }
+        if let Some(metric) = route.metric {
+            builder = builder.priority(metric);
+        }
+        if route.onlink {
+            builder = builder.onlink();
+        }
+        Ok(builder
+            .table_id(route.table)
+            .scope(route.scope.into())
+            .protocol(route.protocol.into())
+            .kind(route.kind.into())
+            .build())
+    }
+}
+
+/// Convert a kernel route message into a [`Route`]
+///
+/// `interface_name` resolves output interface indices; unknown indices are
+/// reported as `if<index>`.
+pub(crate) fn route_from_message(msg: &RouteMessage, interface_name: impl Fn(u32) -> Option<String>) -> Option<Route> {
+    let unspecified = match msg.header.address_family {
+        AddressFamily::Inet => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        AddressFamily::Inet6 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        _ => return None,
+    };
+
+    let mut route = Route::new(unspecified, msg.header.destination_prefix_length);
+    route.table = msg.header.table as u32;
+    route.scope = msg.header.scope.into();
+    route.protocol = msg.header.protocol.into();
+    route.kind = msg.header.kind.into();
+    route.onlink = msg.header.flags.contains(RouteFlags::Onlink);
+
+    for attr in &msg.attributes {
+        match attr {
+            RouteAttribute::Destination(addr) => route.destination = route_address(addr)?,
+            RouteAttribute::Gateway(addr) => route.gateway = route_address(addr),
+            RouteAttribute::PrefSource(addr) => route.source = route_address(addr),
+            RouteAttribute::Oif(index) => {
+                route.interface = Some(interface_name(*index).unwrap_or_else(|| format!("if{}", index)));
+            }
+            RouteAttribute::Priority(metric) => route.metric = Some(*metric),
+            RouteAttribute::Table(table) => route.table = *table,
+            _ => {}
+        }
+    }
+    Some(route)
+}
+
+/// Convert a kernel rule message into a [`RoutingRule`]
+fn rule_from_message(msg: &RuleMessage) -> Option<RoutingRule> {
+    let ipv6 = match msg.header.family {
+        AddressFamily::Inet => false,
+        AddressFamily::Inet6 => true,
+        _ => return None,
+    };
+
+    let mut rule = RoutingRule {
+        ipv6,
+        // The kernel leaves out the priority attribute for priority 0
+        priority: Some(0),
+        from_len: msg.header.src_len,
+        to_len: msg.header.dst_len,
+        action: msg.header.action.into(),
+        invert: msg.header.flags.contains(RuleFlags::Invert),
+        ..Default::default()
+    };
+    if msg.header.table != 0 {
+        rule.table = Some(msg.header.table as u32);
+    }
+
+    for attr in &msg.attributes {
+        match attr {
+            RuleAttribute::Priority(priority) => rule.priority = Some(*priority),
+            RuleAttribute::Source(addr) => rule.from = Some(*addr),
+            RuleAttribute::Destination(addr) => rule.to = Some(*addr),
+            RuleAttribute::FwMark(mark) => rule.fwmark = Some(*mark),
+            RuleAttribute::FwMask(mask) => rule.fwmask = Some(*mask),
+            RuleAttribute::Iifname(name) => rule.iif = Some(name.clone()),
+            RuleAttribute::Oifname(name) => rule.oif = Some(name.clone()),
+            RuleAttribute::UidRange(range) => rule.uid_range = Some((range.start, range.end)),
+            RuleAttribute::Table(table) if *table != 0 => rule.table = Some(*table),
+            _ => {}
+        }
+    }
+
+    // The kernel reports an all-ones mask whenever a mark is set
+    if rule.fwmask == Some(u32::MAX) {
+        rule.fwmask = None;
+    }
+    if rule.action != RuleAction::Lookup {
+        rule.table = None;
+    }
+    Some(rule)
+}
+
+/// Build the netlink message for a [`RoutingRule`]
+fn rule_message(rule: &RoutingRule) -> RuleMessage {
+    let mut msg = RuleMessage::default();
+    msg.header.family = if rule.ipv6 { AddressFamily::Inet6 } else { AddressFamily::Inet };
+    msg.header.action = rule.action.into();
+    if rule.invert {
+        msg.header.flags |= RuleFlags::Invert;
+    }
+
+    if let Some(table) = rule.table {
+        if table > 255 {
+            msg.attributes.push(RuleAttribute::Table(table));
+        } else {
+            msg.header.table = table as u8;
+        }
+    }
+    if let Some(priority) = rule.priority {
+        msg.attributes.push(RuleAttribute::Priority(priority));
+    }
+    if let Some(from) = rule.from {
+        msg.header.src_len = rule.from_len;
+        msg.attributes.push(RuleAttribute::Source(from));
+    }
+    if let Some(to) = rule.to {
+        msg.header.dst_len = rule.to_len;
+        msg.attributes.push(RuleAttribute::Destination(to));
+    }
+    if let Some(mark) = rule.fwmark {
+        msg.attributes.push(RuleAttribute::FwMark(mark));
+    }
+    if let Some(mask) = rule.fwmask {
+        msg.attributes.push(RuleAttribute::FwMask(mask));
+    }
+    if let Some(iif) = &rule.iif {
+        msg.attributes.push(RuleAttribute::Iifname(iif.clone()));
+    }
+    if let Some(oif) = &rule.oif {
+        msg.attributes.push(RuleAttribute::Oifname(oif.clone()));
+    }
+    if let Some((start, end)) = rule.uid_range {
+        msg.attributes.push(RuleAttribute::UidRange(RuleUidRange { start, end }));
+    }
+    msg
+}
+
+fn invalid_route(err: impl fmt::Display) -> NetctlError {
+    NetctlError::InvalidParameter(err.to_string())
+}
+
+fn route_address(addr: &RouteAddress) -> Option<IpAddr> {
+    match addr {
+        RouteAddress::Inet(v4) => Some(IpAddr::V4(*v4)),
+        RouteAddress::Inet6(v6) => Some(IpAddr::V6(*v6)),
+        _ => None,
+    }
 }
 
 impl Default for RoutingController {
@@ -51,3 +1085,83 @@ impl Default for RoutingController {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_destination() {
+        assert_eq!(
+            Route::parse_destination("default", false).unwrap(),
+            (IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)
+        );
+        assert_eq!(
+            Route::parse_destination("default", true).unwrap(),
+            (IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0)
+        );
+        assert_eq!(
+            Route::parse_destination("10.1.0.0/16", false).unwrap(),
+            ("10.1.0.0".parse().unwrap(), 16)
+        );
+        assert_eq!(
+            Route::parse_destination("2001:db8::1", false).unwrap(),
+            ("2001:db8::1".parse().unwrap(), 128)
+        );
+        assert!(Route::parse_destination("10.0.0.0/33", false).is_err());
+        assert!(Route::parse_destination("nonsense", false).is_err());
+    }
+
+    #[test]
+    fn test_route_validation_and_display() {
+        let mut route = Route::default_via("192.168.1.1".parse().unwrap());
+        route.interface = Some("eth0".to_string());
+        route.metric = Some(100);
+        assert!(route.validate().is_ok());
+        assert_eq!(route.to_string(), "default via 192.168.1.1 dev eth0 proto static metric 100");
+
+        route.gateway = Some("fe80::1".parse().unwrap());
+        assert!(route.validate().is_err());
+
+        let mut blackhole = Route::new("10.9.0.0".parse().unwrap(), 16);
+        blackhole.kind = RouteType::Blackhole;
+        blackhole.table = 100;
+        assert!(blackhole.validate().is_ok());
+        assert_eq!(blackhole.to_string(), "blackhole 10.9.0.0/16 table 100 proto static");
+        blackhole.interface = Some("eth0".to_string());
+        assert!(blackhole.validate().is_err());
+
+        assert_eq!(parse_table("main").unwrap(), RT_TABLE_MAIN);
+        assert_eq!(parse_table("100").unwrap(), 100);
+        assert!(parse_table("0").is_err());
+        assert_eq!("dhcp".parse::<RouteProtocol>().unwrap(), RouteProtocol::Dhcp);
+        assert_eq!("link".parse::<RouteScope>().unwrap(), RouteScope::Link);
+    }
+
+    #[test]
+    fn test_rule_parsing_and_display() {
+        assert_eq!(RoutingRule::parse_fwmark("0x10/0xff").unwrap(), (0x10, Some(0xff)));
+        assert_eq!(RoutingRule::parse_fwmark("7").unwrap(), (7, None));
+        assert!(RoutingRule::parse_fwmark("0xzz").is_err());
+        assert_eq!(RoutingRule::parse_uid_range("1000-1999").unwrap(), (1000, 1999));
+        assert!(RoutingRule::parse_uid_range("20-10").is_err());
+        assert_eq!("table".parse::<RuleAction>().unwrap(), RuleAction::Lookup);
+
+        let mut rule = RoutingRule::lookup(100).from_prefix("10.8.0.0".parse().unwrap(), 24);
+        rule.priority = Some(1000);
+        rule.fwmark = Some(0x10);
+        assert!(rule.validate().is_ok());
+        assert_eq!(rule.to_string(), "1000:\tfrom 10.8.0.0/24 fwmark 0x10 lookup 100");
+
+        rule.to = Some("2001:db8::".parse().unwrap());
+        rule.to_len = 32;
+        assert!(rule.validate().is_err());
+
+        let mut prohibit = RoutingRule { action: RuleAction::Prohibit, invert: true, ..Default::default() };
+        prohibit.iif = Some("eth1".to_string());
+        assert!(prohibit.validate().is_ok());
+        prohibit.table = Some(RT_TABLE_MAIN);
+        assert!(prohibit.validate().is_err());
+        assert!(RoutingRule::default().validate().is_err());
+    }
+}
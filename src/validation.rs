@@ -9,6 +9,9 @@ use std::path::{Path, PathBuf};
 /// Maximum length for interface names (Linux kernel limit is 15)
 const MAX_INTERFACE_NAME_LEN: usize = 15;
 
+/// Maximum length for network namespace names
+const MAX_NETNS_NAME_LEN: usize = 64;
+
 /// Maximum length for configuration values
 const MAX_CONFIG_VALUE_LEN: usize = 255;
 
@@ -52,6 +55,40 @@ pub fn validate_interface_name(name: &str) -> NetctlResult<()> {
     Ok(())
 }
 
+/// Validate network namespace name
+///
+/// Namespace names become file names under /run/netns, so they are restricted
+/// to alphanumerics, dash, underscore and dot, and may not be "." or ".."
+pub fn validate_netns_name(name: &str) -> NetctlResult<()> {
+    if name.is_empty() {
+        return Err(NetctlError::InvalidParameter(
+            "Namespace name cannot be empty".to_string()
+        ));
+    }
+
+    if name.len() > MAX_NETNS_NAME_LEN {
+        return Err(NetctlError::InvalidParameter(
+            format!("Namespace name too long (max {} characters)", MAX_NETNS_NAME_LEN)
+        ));
+    }
+
+    if name == "." || name == ".." || name.starts_with('-') {
+        return Err(NetctlError::InvalidParameter(
+            format!("Invalid namespace name '{}'", name)
+        ));
+    }
+
+    for c in name.chars() {
+        if !c.is_ascii_alphanumeric() && c != '-' && c != '_' && c != '.' {
+            return Err(NetctlError::InvalidParameter(
+                format!("Invalid namespace name '{}': contains invalid character '{}'", name, c)
+            ));
+        }
+    }
+
+    Ok(())
+}
+
 /// Validate IP address
 ///
 /// Uses Rust's built-in IP address parser to ensure valid format
@@ -397,6 +434,18 @@ mod tests {
         assert!(validate_interface_name("").is_err());
     }
 
+    #[test]
+    fn test_netns_name_validation() {
+        assert!(validate_netns_name("guest").is_ok());
+        assert!(validate_netns_name("ci-test_1.a").is_ok());
+
+        assert!(validate_netns_name("").is_err());
+        assert!(validate_netns_name("..").is_err());
+        assert!(validate_netns_name("../etc").is_err());
+        assert!(validate_netns_name("a/b").is_err());
+        assert!(validate_netns_name("-x").is_err());
+    }
+
     #[test]
     fn test_ip_validation() {
         // Valid IPv4
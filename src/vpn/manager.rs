@@ -7,6 +7,7 @@ use tracing::{debug, info, warn};
 
 use crate::plugin::ConnectionConfig;
 use crate::error::{NetctlError, NetctlResult};
+use crate::resolver::{ResolverEntry, ResolverManager};
 use super::backend::{VpnBackend, VpnBackendFactory, VpnState, VpnStats};
 
 /// Represents an active VPN connection
@@ -25,6 +26,8 @@ pub struct VpnManager {
     connections: Arc<RwLock<HashMap<String, VpnConnection>>>,
     /// Registered backend factories
     backends: HashMap<String, VpnBackendFactory>,
+    /// Resolver the DNS settings of connected VPNs are registered with
+    resolver: Option<Arc<ResolverManager>>,
     /// Configuration directory
     #[allow(dead_code)]
     config_dir: PathBuf,
@@ -36,6 +39,7 @@ impl VpnManager {
         Self {
             connections: Arc::new(RwLock::new(HashMap::new())),
             backends: HashMap::new(),
+            resolver: None,
             config_dir,
         }
     }
@@ -46,6 +50,12 @@ impl VpnManager {
         self.backends.insert(name.to_string(), factory);
     }
 
+    /// Register the DNS settings of VPNs with `resolver` while they are
+    /// connected
+    pub fn set_resolver(&mut self, resolver: Arc<ResolverManager>) {
+        self.resolver = Some(resolver);
+    }
+
     /// Get a list of registered backend names
     pub fn available_backends(&self) -> Vec<String> {
         self.backends.keys().cloned().collect()
@@ -101,6 +111,17 @@ impl VpnManager {
 
         info!("Connecting VPN: {}", uuid);
         let interface_name = connection.backend.connect(&connection.config).await?;
+
+        if let Some(resolver) = &self.resolver {
+            let entry = vpn_dns_entry(&interface_name, &connection.config);
+            if !entry.is_empty() {
+                if let Err(e) = resolver.set(entry).await {
+                    warn!("Failed to apply DNS settings of VPN {}: {}", uuid, e);
+                    let _ = connection.backend.disconnect().await;
+                    return Err(e);
+                }
+            }
+        }
         connection.interface_name = Some(interface_name.clone());
 
         info!("VPN connected: {} (interface: {})", uuid, interface_name);
@@ -114,8 +135,10 @@ impl VpnManager {
             .ok_or_else(|| NetctlError::NotFound(format!("VPN connection {} not found", uuid)))?;
 
         info!("Disconnecting VPN: {}", uuid);
+        if let Some(interface) = connection.interface_name.take() {
+            self.clear_dns(&interface).await;
+        }
         connection.backend.disconnect().await?;
-        connection.interface_name = None;
 
         info!("VPN disconnected: {}", uuid);
         Ok(())
@@ -129,6 +152,9 @@ impl VpnManager {
             // Disconnect if connected
             if connection.backend.state().await != VpnState::Disconnected {
                 warn!("Disconnecting VPN {} before deletion", uuid);
+                if let Some(interface) = connection.interface_name.take() {
+                    self.clear_dns(&interface).await;
+                }
                 let _ = connection.backend.disconnect().await;
             }
             info!("Deleted VPN connection: {}", uuid);
@@ -254,6 +280,15 @@ impl VpnManager {
         Ok(connection.backend.interface_name())
     }
 
+    /// Remove the DNS settings of a VPN interface from the resolver
+    async fn clear_dns(&self, interface: &str) {
+        if let Some(resolver) = &self.resolver {
+            if let Err(e) = resolver.clear(interface).await {
+                warn!("Failed to remove DNS settings of {}: {}", interface, e);
+            }
+        }
+    }
+
     /// Disconnect all VPNs
     pub async fn disconnect_all(&self) -> NetctlResult<()> {
         let uuids: Vec<String> = {
@@ -271,6 +306,48 @@ impl VpnManager {
     }
 }
 
+/// Resolver entry for the DNS settings of a VPN connection
+///
+/// `dns` may be a list or a wg-quick style comma-separated string mixing
+/// servers and domains; `dns_search` adds domains, and domains starting
+/// with `~` are routing domains. VPNs default to priority 50, ahead of
+/// other connections.
+fn vpn_dns_entry(interface: &str, config: &ConnectionConfig) -> ResolverEntry {
+    let mut entry = ResolverEntry {
+        interface: interface.to_string(),
+        priority: config.settings.get("dns_priority")
+            .and_then(|v| v.as_i64())
+            .and_then(|p| i32::try_from(p).ok())
+            .unwrap_or(50),
+        ..Default::default()
+    };
+
+    let values = |key: &str| -> Vec<String> {
+        match config.settings.get(key) {
+            Some(Value::String(list)) => list.split(',').map(|v| v.trim().to_string()).collect(),
+            Some(Value::Array(list)) => list.iter().filter_map(|v| v.as_str()).map(|v| v.trim().to_string()).collect(),
+            _ => Vec::new(),
+        }
+    };
+
+    let servers = values("dns").into_iter()
+        .chain(values("ipv4_dns"))
+        .chain(values("ipv6_dns"));
+    for value in servers.filter(|v| !v.is_empty()) {
+        if value.parse::<std::net::IpAddr>().is_ok() {
+            if !entry.servers.contains(&value) {
+                entry.servers.push(value);
+            }
+        } else {
+            entry.add_domain(&value);
+        }
+    }
+    for domain in values("dns_search").iter().filter(|v| !v.is_empty()) {
+        entry.add_domain(domain);
+    }
+    entry
+}
+
 impl Drop for VpnManager {
     fn drop(&mut self) {
         // Note: async drop is not yet stable, so we can't properly disconnect here
@@ -278,3 +355,32 @@ impl Drop for VpnManager {
         debug!("VpnManager dropped");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_vpn_dns_entry() {
+        let mut config = ConnectionConfig {
+            uuid: "a1b2c3d4".to_string(),
+            name: "corp".to_string(),
+            conn_type: "vpn".to_string(),
+            settings: HashMap::new(),
+            autoconnect: false,
+        };
+        config.settings.insert("dns".to_string(), json!("10.8.0.53, fd00:8::53, corp.example"));
+        config.settings.insert("dns_search".to_string(), json!(["~lab.example"]));
+        config.settings.insert("ipv4_dns".to_string(), json!(["10.8.0.53", "10.8.0.54"]));
+
+        let entry = vpn_dns_entry("wg0", &config);
+        assert_eq!(entry.servers, vec!["10.8.0.53", "fd00:8::53", "10.8.0.54"]);
+        assert_eq!(entry.search, vec!["corp.example"]);
+        assert_eq!(entry.routing_domains, vec!["lab.example"]);
+        assert_eq!(entry.priority, 50);
+
+        config.settings.clear();
+        assert!(vpn_dns_entry("wg0", &config).is_empty());
+    }
+}
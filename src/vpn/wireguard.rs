@@ -29,7 +29,10 @@ impl WireGuardBackend {
     }
 
     /// Build WireGuard configuration file content
-    fn build_config_content(&self, config: &ConnectionConfig) -> NetctlResult<String> {
+    ///
+    /// `DNS` is only written for export: at runtime the settings are
+    /// applied by VpnManager, as wg-quick would need resolvconf for them.
+    fn build_config_content(&self, config: &ConnectionConfig, with_dns: bool) -> NetctlResult<String> {
         let settings = &config.settings;
         let mut cfg = String::new();
 
@@ -50,7 +53,7 @@ impl WireGuardBackend {
             cfg.push_str(&format!("ListenPort = {}\n", listen_port));
         }
 
-        if let Some(dns) = settings.get("dns").and_then(|v| v.as_str()) {
+        if let Some(dns) = settings.get("dns").and_then(|v| v.as_str()).filter(|_| with_dns) {
             cfg.push_str(&format!("DNS = {}\n", dns));
         }
 
@@ -284,7 +287,7 @@ impl VpnBackend for WireGuardBackend {
         let interface_name = Self::generate_interface_name(&config.uuid);
 
         // Build configuration content
-        let config_content = self.build_config_content(config)?;
+        let config_content = self.build_config_content(config, false)?;
 
         // Write configuration to temporary file
         let config_path = std::env::temp_dir().join(format!("{}.conf", interface_name));
@@ -437,7 +440,7 @@ impl VpnBackend for WireGuardBackend {
 
     async fn export_config(&self, config: &ConnectionConfig, path: &Path) -> NetctlResult<()> {
         info!("Exporting WireGuard configuration to: {:?}", path);
-        let config_content = self.build_config_content(config)?;
+        let config_content = self.build_config_content(config, true)?;
         common::write_secure_config(path, &config_content, 0o600).await
     }
 }
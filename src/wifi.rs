@@ -1,11 +1,21 @@
 //! WiFi device control
 //!
-//! Low-level WiFi management using iw command
+//! Low-level WiFi management over nl80211: interface information, transmit
+//! power, power save, scanning and station statistics. Scan results carry
+//! the information elements of each BSS decoded into security suites,
+//! HT/VHT/HE capabilities and the country element. Only the regulatory
+//! domain still goes through the iw command.
 
 use crate::error::{NetctlError, NetctlResult};
+use crate::netns::NetNamespace;
+use crate::nl80211::Nl80211;
 use crate::validation;
 use serde::{Deserialize, Serialize};
-use tokio::process::Command;
+use std::time::Duration;
+use tracing::debug;
+
+/// Time to wait for the kernel to report a triggered scan as done
+const SCAN_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WifiDeviceInfo {
@@ -15,8 +25,11 @@ pub struct WifiDeviceInfo {
     pub wiphy: Option<u32>,
     pub channel: Option<u32>,
     pub frequency: Option<u32>,
+    /// Channel width in MHz
+    pub channel_width: Option<u32>,
     pub txpower: Option<String>,
     pub ssid: Option<String>,
+    pub mac: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,59 +48,156 @@ pub struct RegDomain {
     pub dfs_region: Option<String>,
 }
 
+/// Transmit power setting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TxPower {
+    /// Chosen by the driver
+    Auto,
+    /// At most this many mBm (1/100 dBm)
+    Limit(i32),
+    /// Exactly this many mBm
+    Fixed(i32),
+}
+
+impl std::str::FromStr for TxPower {
+    type Err = NetctlError;
+
+    /// Parse "auto" or "[fixed|limit] VALUE[dBm|mBm|mW]"; like iw, a bare
+    /// value is in mBm and the default is a limit
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || NetctlError::InvalidParameter(format!("Invalid TX power: {}", s));
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(TxPower::Auto);
+        }
+        let (fixed, value) = match s.split_once(char::is_whitespace) {
+            Some(("fixed", value)) => (true, value.trim()),
+            Some(("limit", value)) => (false, value.trim()),
+            Some(_) => return Err(invalid()),
+            None => (false, s),
+        };
+        let split = value.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(value.len());
+        let (number, unit) = value.split_at(split);
+        let number: f64 = number.trim().parse().map_err(|_| invalid())?;
+        let mbm = match unit.trim().to_ascii_lowercase().as_str() {
+            "" | "mbm" => number,
+            "dbm" => number * 100.0,
+            "mw" if number > 0.0 => number.log10() * 1000.0,
+            _ => return Err(invalid()),
+        };
+        if !(-10000.0..=10000.0).contains(&mbm) {
+            return Err(invalid());
+        }
+        let mbm = mbm.round() as i32;
+        Ok(if fixed { TxPower::Fixed(mbm) } else { TxPower::Limit(mbm) })
+    }
+}
+
+/// Channel number of a frequency in MHz (2.4, 5 and 6 GHz bands)
+pub fn frequency_to_channel(frequency: u32) -> Option<u32> {
+    match frequency {
+        2484 => Some(14),
+        2412..=2472 => Some((frequency - 2407) / 5),
+        5935 => Some(2),
+        5955..=7115 => Some((frequency - 5950) / 5),
+        5150..=5895 => Some((frequency - 5000) / 5),
+        _ => None,
+    }
+}
+
+/// WiFi frequency band
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WifiBand {
+    Band2GHz,
+    Band5GHz,
+    Band6GHz,
+}
+
+impl WifiBand {
+    /// Band of a channel number where it is unambiguous (2.4 and 5 GHz)
+    pub fn of_channel(channel: u32) -> Option<Self> {
+        match channel {
+            1..=14 => Some(WifiBand::Band2GHz),
+            _ if is_5ghz_channel(channel) => Some(WifiBand::Band5GHz),
+            _ => None,
+        }
+    }
+
+    /// Center frequency in MHz of a channel in this band
+    pub fn channel_frequency(self, channel: u32) -> Option<u32> {
+        match (self, channel) {
+            (WifiBand::Band2GHz, 14) => Some(2484),
+            (WifiBand::Band2GHz, 1..=13) => Some(2407 + channel * 5),
+            (WifiBand::Band5GHz, _) if is_5ghz_channel(channel) => Some(5000 + channel * 5),
+            (WifiBand::Band6GHz, 2) => Some(5935),
+            (WifiBand::Band6GHz, 1..=233) if channel % 4 == 1 => Some(5950 + channel * 5),
+            _ => None,
+        }
+    }
+
+    /// Center frequencies of the 20 MHz channels in this band
+    pub fn frequencies(self) -> Vec<u32> {
+        let channels: Vec<u32> = match self {
+            WifiBand::Band2GHz => (1..=14).collect(),
+            WifiBand::Band5GHz => (36..=177).filter(|&channel| is_5ghz_channel(channel)).collect(),
+            WifiBand::Band6GHz => (1..=233).step_by(4).collect(),
+        };
+        channels.into_iter().filter_map(|channel| self.channel_frequency(channel)).collect()
+    }
+}
+
+/// Whether a channel is a 20 MHz channel of the 5 GHz band
+///
+/// UNII-1 to UNII-2e (36-64, 100-144) use every fourth channel, UNII-3 and
+/// UNII-4 (149-177) are offset by one.
+fn is_5ghz_channel(channel: u32) -> bool {
+    match channel {
+        36..=64 | 100..=144 => channel.is_multiple_of(4),
+        149..=177 => channel % 4 == 1,
+        _ => false,
+    }
+}
+
+impl std::str::FromStr for WifiBand {
+    type Err = NetctlError;
+
+    /// Parse a band as "2.4GHz", "5GHz" or "6GHz", or NetworkManager's "bg" and "a"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().trim_end_matches("ghz") {
+            "2.4" | "bg" => Ok(WifiBand::Band2GHz),
+            "5" | "a" => Ok(WifiBand::Band5GHz),
+            "6" => Ok(WifiBand::Band6GHz),
+            _ => Err(NetctlError::InvalidParameter(format!("Invalid WiFi band: {}", s))),
+        }
+    }
+}
+
 /// WiFi controller
 pub struct WifiController {
+    namespace: NetNamespace,
 }
 
 impl WifiController {
     pub fn new() -> Self {
-        Self {}
+        Self { namespace: NetNamespace::Current }
+    }
+
+    /// Control the WiFi interfaces of a network namespace
+    pub fn with_namespace(namespace: NetNamespace) -> Self {
+        Self::new().in_namespace(namespace)
+    }
+
+    /// Builder form of [`with_namespace`](Self::with_namespace)
+    pub fn in_namespace(mut self, namespace: NetNamespace) -> Self {
+        self.namespace = namespace;
+        self
     }
 
     /// Get WiFi device information
     pub async fn get_dev_info(&self, interface: &str) -> NetctlResult<WifiDeviceInfo> {
         validation::validate_interface_name(interface)?;
-        let output = self.run_iw(&["dev", interface, "info"]).await?;
-
-        let mut info = WifiDeviceInfo {
-            interface: interface.to_string(),
-            phy: None,
-            type_: None,
-            wiphy: None,
-            channel: None,
-            frequency: None,
-            txpower: None,
-            ssid: None,
-        };
-
-        for line in output.lines() {
-            let line = line.trim();
-            if line.starts_with("wiphy ") {
-                info.wiphy = line.strip_prefix("wiphy ").and_then(|s| s.parse().ok());
-            } else if line.starts_with("type ") {
-                info.type_ = line.strip_prefix("type ").map(|s| s.to_string());
-            } else if line.starts_with("channel ") {
-                // Parse "channel 6 (2437 MHz), width: 20 MHz"
-                if let Some(parts) = line.strip_prefix("channel ") {
-                    if let Some(ch_str) = parts.split_whitespace().next() {
-                        info.channel = ch_str.parse().ok();
-                    }
-                    if let Some(start) = parts.find('(') {
-                        if let Some(end) = parts.find(" MHz") {
-                            if let Some(freq_str) = parts.get(start + 1..end) {
-                                info.frequency = freq_str.parse().ok();
-                            }
-                        }
-                    }
-                }
-            } else if line.starts_with("txpower ") {
-                info.txpower = line.strip_prefix("txpower ").map(|s| s.to_string());
-            } else if line.starts_with("ssid ") {
-                info.ssid = line.strip_prefix("ssid ").map(|s| s.to_string());
-            }
-        }
-
-        Ok(info)
+        let ifindex = self.ifindex(interface).await?;
+        Nl80211::open(&self.namespace).await?.get_interface(ifindex, interface).await
     }
 
     /// Get physical device name (phy)
@@ -137,79 +247,95 @@ impl WifiController {
         info.txpower.ok_or_else(|| NetctlError::NotSupported("TX power not available".to_string()))
     }
 
-    /// Set transmit power (in dBm or mW)
+    /// Set transmit power: "auto", or a value in dBm, mBm or mW such as
+    /// "20dBm" or "fixed 100mW" (see [`TxPower`])
     pub async fn set_txpower(&self, interface: &str, power: &str) -> NetctlResult<()> {
         validation::validate_interface_name(interface)?;
-        // power can be like "20dBm" or "fixed 100mW" or "auto"
-        self.run_iw_no_output(&["dev", interface, "set", "txpower", power]).await
+        let power: TxPower = power.parse()?;
+        let ifindex = self.ifindex(interface).await?;
+        Nl80211::open(&self.namespace).await?.set_tx_power(ifindex, interface, power).await
     }
 
     /// Set power save mode
     pub async fn set_power_save(&self, interface: &str, enable: bool) -> NetctlResult<()> {
         validation::validate_interface_name(interface)?;
-        let mode = if enable { "on" } else { "off" };
-        self.run_iw_no_output(&["dev", interface, "set", "power_save", mode]).await
+        let ifindex = self.ifindex(interface).await?;
+        Nl80211::open(&self.namespace).await?.set_power_save(ifindex, interface, enable).await
     }
 
     /// Get power save status
     pub async fn get_power_save(&self, interface: &str) -> NetctlResult<bool> {
         validation::validate_interface_name(interface)?;
-        let output = self.run_iw(&["dev", interface, "get", "power_save"]).await?;
-        Ok(output.contains("Power save: on"))
+        let ifindex = self.ifindex(interface).await?;
+        Nl80211::open(&self.namespace).await?.get_power_save(ifindex, interface).await
     }
 
     /// Scan for WiFi networks
+    ///
+    /// Triggers an active scan and returns the results once the kernel
+    /// reports it done. If another scan is already running (for example one
+    /// started by wpa_supplicant), its results are returned instead.
     pub async fn scan(&self, interface: &str) -> NetctlResult<Vec<ScanResult>> {
         validation::validate_interface_name(interface)?;
-        // Get scan results (will trigger scan if needed)
-        let output = self.run_iw(&["dev", interface, "scan"]).await?;
+        let ifindex = self.ifindex(interface).await?;
 
-        let mut results = Vec::new();
-        let mut current: Option<ScanResult> = None;
+        // Subscribe before triggering so the scan-done event cannot be missed
+        let mut events = Nl80211::open(&self.namespace).await?;
+        events.join_scan_events()?;
+        let mut nl = Nl80211::open(&self.namespace).await?;
+        match nl.trigger_scan(ifindex, interface).await {
+            Ok(()) => {}
+            Err(NetctlError::InvalidState(_)) => debug!("Scan already running on {}, waiting for it", interface),
+            Err(e) => return Err(e),
+        }
+        tokio::time::timeout(SCAN_TIMEOUT, events.wait_scan_done(ifindex, interface))
+            .await
+            .map_err(|_| NetctlError::Timeout(format!("Scan on {} did not finish", interface)))??;
 
-        for line in output.lines() {
-            let line = line.trim();
+        nl.get_scan(ifindex, interface).await
+    }
 
-            if line.starts_with("BSS ") {
-                if let Some(result) = current.take() {
-                    results.push(result);
-                }
-                let bssid = line.strip_prefix("BSS ")
-                    .and_then(|s| s.split('(').next())
-                    .map(|s| s.trim().to_string())
-                    .unwrap_or_default();
-                current = Some(ScanResult {
-                    bssid,
-                    ssid: None,
-                    frequency: None,
-                    signal: None,
-                    capabilities: Vec::new(),
-                });
-            } else if let Some(ref mut result) = current {
-                if let Some(ssid) = line.strip_prefix("SSID: ") {
-                    result.ssid = Some(ssid.to_string());
-                } else if let Some(freq_str) = line.strip_prefix("freq: ") {
-                    result.frequency = freq_str.parse().ok();
-                } else if let Some(sig) = line.strip_prefix("signal: ") {
-                    result.signal = Some(sig.to_string());
-                } else if let Some(caps) = line.strip_prefix("capability: ") {
-                    result.capabilities = caps.split_whitespace().map(String::from).collect();
-                }
-            }
-        }
+    /// BSSs from the kernel's scan cache, without scanning
+    pub async fn scan_results(&self, interface: &str) -> NetctlResult<Vec<ScanResult>> {
+        validation::validate_interface_name(interface)?;
+        let ifindex = self.ifindex(interface).await?;
+        Nl80211::open(&self.namespace).await?.get_scan(ifindex, interface).await
+    }
 
-        if let Some(result) = current {
-            results.push(result);
-        }
+    /// The BSS the interface is associated with and its station statistics,
+    /// `None` while not connected
+    pub async fn link(&self, interface: &str) -> NetctlResult<Option<LinkInfo>> {
+        validation::validate_interface_name(interface)?;
+        let ifindex = self.ifindex(interface).await?;
+        let mut nl = Nl80211::open(&self.namespace).await?;
+        let Some(bss) = nl.get_scan(ifindex, interface).await?.into_iter().find(|bss| bss.associated) else {
+            return Ok(None);
+        };
+        let station = match nl.get_station(ifindex, interface, &bss.bssid).await {
+            Ok(station) => Some(station),
+            Err(NetctlError::NotFound(_)) => None,
+            Err(e) => return Err(e),
+        };
+        Ok(Some(LinkInfo { bss, station }))
+    }
 
-        Ok(results)
+    /// Statistics of the station `mac`; on a client interface, the AP
+    pub async fn station(&self, interface: &str, mac: &str) -> NetctlResult<StationInfo> {
+        validation::validate_interface_name(interface)?;
+        let ifindex = self.ifindex(interface).await?;
+        Nl80211::open(&self.namespace).await?.get_station(ifindex, interface, mac).await
     }
 
     // === Helper functions ===
 
+    async fn ifindex(&self, interface: &str) -> NetctlResult<u32> {
+        let handle = self.namespace.connect()?;
+        crate::netlink::link_index(&handle, interface).await
+    }
+
     async fn run_iw(&self, args: &[&str]) -> NetctlResult<String> {
         let cmd_str = format!("iw {}", args.join(" "));
-        let output = Command::new("iw")
+        let output = self.namespace.command("iw")?
             .args(args)
             .output()
             .await
@@ -244,11 +370,662 @@ impl Default for WifiController {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A BSS found by a scan
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ScanResult {
     pub bssid: String,
     pub ssid: Option<String>,
     pub frequency: Option<u32>,
-    pub signal: Option<String>,
+    pub channel: Option<u32>,
+    /// Signal strength in dBm
+    pub signal: Option<i32>,
+    /// Capability field flags ("ESS", "Privacy", ...)
     pub capabilities: Vec<String>,
+    /// Beacon interval in TUs
+    pub beacon_interval: Option<u16>,
+    /// Milliseconds since the BSS was last seen
+    pub age_ms: Option<u32>,
+    /// Whether the interface is associated with this BSS
+    pub associated: bool,
+    /// Operating channel width in MHz
+    pub channel_width: Option<u32>,
+    /// RSN element (WPA2/WPA3)
+    pub rsn: Option<SecurityInfo>,
+    /// WPA vendor element
+    pub wpa: Option<SecurityInfo>,
+    pub ht: Option<PhyCapabilities>,
+    pub vht: Option<PhyCapabilities>,
+    pub he: Option<PhyCapabilities>,
+    pub country: Option<CountryInfo>,
+}
+
+impl ScanResult {
+    /// Whether the BSS requires any kind of encryption
+    pub fn is_protected(&self) -> bool {
+        self.rsn.is_some() || self.wpa.is_some() || self.capabilities.iter().any(|c| c == "Privacy")
+    }
+
+    /// Security in the form nmcli shows it, e.g. "WPA2 WPA3"; empty if open
+    pub fn security_label(&self) -> String {
+        let mut labels = Vec::new();
+        if self.wpa.is_some() {
+            labels.push("WPA1");
+        }
+        if let Some(rsn) = &self.rsn {
+            let akms = &rsn.akm_suites;
+            if akms.iter().any(|a| matches!(a, AkmSuite::Psk | AkmSuite::FtPsk | AkmSuite::PskSha256)) {
+                labels.push("WPA2");
+            }
+            if akms.iter().any(|a| matches!(a, AkmSuite::Sae | AkmSuite::FtSae | AkmSuite::SaeExtKey | AkmSuite::FtSaeExtKey)) {
+                labels.push("WPA3");
+            }
+            if akms.iter().any(|a| matches!(a, AkmSuite::Owe)) {
+                labels.push("OWE");
+            }
+            if akms.iter().any(AkmSuite::is_eap) {
+                labels.push("802.1X");
+            }
+        }
+        if labels.is_empty() && self.is_protected() {
+            labels.push("WEP");
+        }
+        labels.join(" ")
+    }
+
+    /// Fill in the fields carried by information elements
+    pub(crate) fn apply_ies(&mut self, ies: &[u8]) {
+        let mut ht_width = None;
+        let mut vht_width = None;
+        for (id, data) in elements(ies) {
+            match id {
+                // Hidden networks send an empty or zeroed SSID
+                ie::SSID if data.iter().any(|b| *b != 0) => {
+                    self.ssid = Some(String::from_utf8_lossy(data).into_owned());
+                }
+                ie::COUNTRY => self.country = CountryInfo::parse(data),
+                ie::HT_CAPABILITIES => self.ht = PhyCapabilities::parse_ht(data),
+                ie::HT_OPERATION => {
+                    // Secondary channel offset and STA channel width
+                    ht_width = data.get(1).map(|b| if b & 0x03 != 0 && b & 0x04 != 0 { 40 } else { 20 });
+                }
+                ie::RSN => self.rsn = SecurityInfo::parse(data, RSN_OUI),
+                ie::VHT_CAPABILITIES => self.vht = PhyCapabilities::parse_vht(data),
+                ie::VHT_OPERATION => {
+                    vht_width = match data {
+                        // A second center frequency means 160 or 80+80 MHz
+                        [1, _, ccfs1, ..] if *ccfs1 != 0 => Some(160),
+                        [1, ..] => Some(80),
+                        [2, ..] | [3, ..] => Some(160),
+                        _ => None,
+                    };
+                }
+                ie::VENDOR if data.starts_with(&WPA_VENDOR_TYPE) => {
+                    self.wpa = SecurityInfo::parse(&data[WPA_VENDOR_TYPE.len()..], WPA_OUI);
+                }
+                ie::EXTENSION if data.first() == Some(&ie::EXT_HE_CAPABILITIES) => {
+                    self.he = PhyCapabilities::parse_he(&data[1..]);
+                }
+                _ => {}
+            }
+        }
+        // VHT operation only means more than 40 MHz when it says so
+        self.channel_width = vht_width.or(ht_width).or(self.channel_width);
+    }
+}
+
+/// Information element ids
+mod ie {
+    pub const SSID: u8 = 0;
+    pub const COUNTRY: u8 = 7;
+    pub const HT_CAPABILITIES: u8 = 45;
+    pub const RSN: u8 = 48;
+    pub const HT_OPERATION: u8 = 61;
+    pub const VHT_CAPABILITIES: u8 = 191;
+    pub const VHT_OPERATION: u8 = 192;
+    pub const VENDOR: u8 = 221;
+    pub const EXTENSION: u8 = 255;
+    pub const EXT_HE_CAPABILITIES: u8 = 35;
+}
+
+const RSN_OUI: [u8; 3] = [0x00, 0x0f, 0xac];
+const WPA_OUI: [u8; 3] = [0x00, 0x50, 0xf2];
+/// Microsoft OUI and vendor type 1 of the WPA element
+const WPA_VENDOR_TYPE: [u8; 4] = [0x00, 0x50, 0xf2, 0x01];
+
+/// Iterate the information elements in `data` as (id, payload)
+fn elements(mut data: &[u8]) -> impl Iterator<Item = (u8, &[u8])> {
+    std::iter::from_fn(move || {
+        let (&id, rest) = data.split_first()?;
+        let (&len, rest) = rest.split_first()?;
+        let payload = rest.get(..len as usize)?;
+        data = &rest[len as usize..];
+        Some((id, payload))
+    })
+}
+
+/// Capability field flag names, as printed by iw
+const CAPABILITY_NAMES: [&str; 16] = [
+    "ESS", "IBSS", "CfPollable", "CfPollReq", "Privacy", "ShortPreamble", "PBCC", "ChannelAgility",
+    "SpectrumMgmt", "QoS", "ShortSlotTime", "APSD", "RadioMeasure", "DSSS-OFDM", "DelayedBACK", "ImmediateBACK",
+];
+
+/// Names of the flags set in a BSS capability field
+pub(crate) fn capability_names(capability: u16) -> Vec<String> {
+    CAPABILITY_NAMES.iter().enumerate()
+        .filter(|(bit, _)| capability & (1 << bit) != 0)
+        .map(|(_, name)| name.to_string())
+        .collect()
+}
+
+/// Cipher suite of an RSN or WPA element
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CipherSuite {
+    Wep40,
+    Tkip,
+    Ccmp,
+    Wep104,
+    BipCmac128,
+    /// Group addressed traffic not allowed
+    NoGroupTraffic,
+    Gcmp128,
+    Gcmp256,
+    Ccmp256,
+    BipGmac128,
+    BipGmac256,
+    BipCmac256,
+    /// Any other suite selector (OUI and type)
+    Other(u32),
+}
+
+impl CipherSuite {
+    fn from_selector(oui: [u8; 3], kind: u8) -> Self {
+        match (oui, kind) {
+            (RSN_OUI | WPA_OUI, 1) => CipherSuite::Wep40,
+            (RSN_OUI | WPA_OUI, 2) => CipherSuite::Tkip,
+            (RSN_OUI | WPA_OUI, 4) => CipherSuite::Ccmp,
+            (RSN_OUI | WPA_OUI, 5) => CipherSuite::Wep104,
+            (RSN_OUI, 6) => CipherSuite::BipCmac128,
+            (RSN_OUI, 7) => CipherSuite::NoGroupTraffic,
+            (RSN_OUI, 8) => CipherSuite::Gcmp128,
+            (RSN_OUI, 9) => CipherSuite::Gcmp256,
+            (RSN_OUI, 10) => CipherSuite::Ccmp256,
+            (RSN_OUI, 11) => CipherSuite::BipGmac128,
+            (RSN_OUI, 12) => CipherSuite::BipGmac256,
+            (RSN_OUI, 13) => CipherSuite::BipCmac256,
+            _ => CipherSuite::Other(selector(oui, kind)),
+        }
+    }
+}
+
+impl std::fmt::Display for CipherSuite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CipherSuite::Wep40 => write!(f, "WEP-40"),
+            CipherSuite::Tkip => write!(f, "TKIP"),
+            CipherSuite::Ccmp => write!(f, "CCMP"),
+            CipherSuite::Wep104 => write!(f, "WEP-104"),
+            CipherSuite::BipCmac128 => write!(f, "BIP-CMAC-128"),
+            CipherSuite::NoGroupTraffic => write!(f, "GTK-NOT-USED"),
+            CipherSuite::Gcmp128 => write!(f, "GCMP-128"),
+            CipherSuite::Gcmp256 => write!(f, "GCMP-256"),
+            CipherSuite::Ccmp256 => write!(f, "CCMP-256"),
+            CipherSuite::BipGmac128 => write!(f, "BIP-GMAC-128"),
+            CipherSuite::BipGmac256 => write!(f, "BIP-GMAC-256"),
+            CipherSuite::BipCmac256 => write!(f, "BIP-CMAC-256"),
+            CipherSuite::Other(selector) => write_selector(f, *selector),
+        }
+    }
+}
+
+/// Authentication and key management suite of an RSN or WPA element
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AkmSuite {
+    Ieee8021x,
+    Psk,
+    FtIeee8021x,
+    FtPsk,
+    Ieee8021xSha256,
+    PskSha256,
+    Tdls,
+    Sae,
+    FtSae,
+    SuiteB,
+    SuiteB192,
+    FtIeee8021xSha384,
+    FilsSha256,
+    FilsSha384,
+    FtFilsSha256,
+    FtFilsSha384,
+    Owe,
+    FtPskSha384,
+    PskSha384,
+    SaeExtKey,
+    FtSaeExtKey,
+    /// Any other suite selector (OUI and type)
+    Other(u32),
+}
+
+impl AkmSuite {
+    fn from_selector(oui: [u8; 3], kind: u8) -> Self {
+        match (oui, kind) {
+            (RSN_OUI | WPA_OUI, 1) => AkmSuite::Ieee8021x,
+            (RSN_OUI | WPA_OUI, 2) => AkmSuite::Psk,
+            (RSN_OUI, 3) => AkmSuite::FtIeee8021x,
+            (RSN_OUI, 4) => AkmSuite::FtPsk,
+            (RSN_OUI, 5) => AkmSuite::Ieee8021xSha256,
+            (RSN_OUI, 6) => AkmSuite::PskSha256,
+            (RSN_OUI, 7) => AkmSuite::Tdls,
+            (RSN_OUI, 8) => AkmSuite::Sae,
+            (RSN_OUI, 9) => AkmSuite::FtSae,
+            (RSN_OUI, 11) => AkmSuite::SuiteB,
+            (RSN_OUI, 12) => AkmSuite::SuiteB192,
+            (RSN_OUI, 13) => AkmSuite::FtIeee8021xSha384,
+            (RSN_OUI, 14) => AkmSuite::FilsSha256,
+            (RSN_OUI, 15) => AkmSuite::FilsSha384,
+            (RSN_OUI, 16) => AkmSuite::FtFilsSha256,
+            (RSN_OUI, 17) => AkmSuite::FtFilsSha384,
+            (RSN_OUI, 18) => AkmSuite::Owe,
+            (RSN_OUI, 19) => AkmSuite::FtPskSha384,
+            (RSN_OUI, 20) => AkmSuite::PskSha384,
+            (RSN_OUI, 24) => AkmSuite::SaeExtKey,
+            (RSN_OUI, 25) => AkmSuite::FtSaeExtKey,
+            _ => AkmSuite::Other(selector(oui, kind)),
+        }
+    }
+
+    /// Whether the suite authenticates with EAP (802.1X)
+    pub fn is_eap(&self) -> bool {
+        matches!(
+            self,
+            AkmSuite::Ieee8021x | AkmSuite::FtIeee8021x | AkmSuite::Ieee8021xSha256 | AkmSuite::SuiteB
+                | AkmSuite::SuiteB192 | AkmSuite::FtIeee8021xSha384 | AkmSuite::FilsSha256
+                | AkmSuite::FilsSha384 | AkmSuite::FtFilsSha256 | AkmSuite::FtFilsSha384
+        )
+    }
+}
+
+impl std::fmt::Display for AkmSuite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            AkmSuite::Ieee8021x => "802.1X",
+            AkmSuite::Psk => "PSK",
+            AkmSuite::FtIeee8021x => "FT/802.1X",
+            AkmSuite::FtPsk => "FT/PSK",
+            AkmSuite::Ieee8021xSha256 => "802.1X/SHA-256",
+            AkmSuite::PskSha256 => "PSK/SHA-256",
+            AkmSuite::Tdls => "TDLS",
+            AkmSuite::Sae => "SAE",
+            AkmSuite::FtSae => "FT/SAE",
+            AkmSuite::SuiteB => "802.1X/SUITE-B",
+            AkmSuite::SuiteB192 => "802.1X/SUITE-B-192",
+            AkmSuite::FtIeee8021xSha384 => "FT/802.1X/SHA-384",
+            AkmSuite::FilsSha256 => "FILS/SHA-256",
+            AkmSuite::FilsSha384 => "FILS/SHA-384",
+            AkmSuite::FtFilsSha256 => "FT/FILS/SHA-256",
+            AkmSuite::FtFilsSha384 => "FT/FILS/SHA-384",
+            AkmSuite::Owe => "OWE",
+            AkmSuite::FtPskSha384 => "FT/PSK/SHA-384",
+            AkmSuite::PskSha384 => "PSK/SHA-384",
+            AkmSuite::SaeExtKey => "SAE-EXT-KEY",
+            AkmSuite::FtSaeExtKey => "FT/SAE-EXT-KEY",
+            AkmSuite::Other(selector) => return write_selector(f, *selector),
+        };
+        write!(f, "{}", name)
+    }
+}
+
+fn selector(oui: [u8; 3], kind: u8) -> u32 {
+    u32::from_be_bytes([oui[0], oui[1], oui[2], kind])
+}
+
+fn write_selector(f: &mut std::fmt::Formatter<'_>, selector: u32) -> std::fmt::Result {
+    let [a, b, c, kind] = selector.to_be_bytes();
+    write!(f, "{:02x}-{:02x}-{:02x}:{}", a, b, c, kind)
+}
+
+/// Contents of an RSN element or WPA vendor element
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SecurityInfo {
+    pub version: u16,
+    pub group_cipher: CipherSuite,
+    pub pairwise_ciphers: Vec<CipherSuite>,
+    pub akm_suites: Vec<AkmSuite>,
+    /// Management frame protection capable (RSN only)
+    pub mfp_capable: bool,
+    /// Management frame protection required (RSN only)
+    pub mfp_required: bool,
+}
+
+impl SecurityInfo {
+    /// Parse the element body; suites left out take the defaults of the
+    /// element (CCMP and 802.1X for RSN, TKIP and 802.1X for WPA)
+    fn parse(data: &[u8], oui: [u8; 3]) -> Option<Self> {
+        let default_cipher = if oui == RSN_OUI { CipherSuite::Ccmp } else { CipherSuite::Tkip };
+        let version = u16::from_le_bytes(data.get(..2)?.try_into().ok()?);
+        let mut info = SecurityInfo {
+            version,
+            group_cipher: default_cipher,
+            pairwise_ciphers: vec![default_cipher],
+            akm_suites: vec![AkmSuite::Ieee8021x],
+            mfp_capable: false,
+            mfp_required: false,
+        };
+        let mut rest = &data[2..];
+
+        let suite = |data: &[u8]| ([data[0], data[1], data[2]], data[3]);
+        let suite_list = |rest: &mut &[u8]| -> Option<Vec<([u8; 3], u8)>> {
+            let count = u16::from_le_bytes(rest.get(..2)?.try_into().ok()?) as usize;
+            let list = rest.get(2..2 + count * 4)?;
+            *rest = &rest[2 + count * 4..];
+            Some(list.chunks_exact(4).map(suite).collect())
+        };
+
+        let Some(group) = rest.get(..4) else { return Some(info) };
+        let (group_oui, kind) = suite(group);
+        info.group_cipher = CipherSuite::from_selector(group_oui, kind);
+        rest = &rest[4..];
+
+        let Some(pairwise) = suite_list(&mut rest) else { return Some(info) };
+        info.pairwise_ciphers = pairwise.into_iter().map(|(oui, kind)| CipherSuite::from_selector(oui, kind)).collect();
+        let Some(akms) = suite_list(&mut rest) else { return Some(info) };
+        info.akm_suites = akms.into_iter().map(|(oui, kind)| AkmSuite::from_selector(oui, kind)).collect();
+
+        if oui == RSN_OUI {
+            if let Some(caps) = rest.get(..2) {
+                let caps = u16::from_le_bytes([caps[0], caps[1]]);
+                info.mfp_required = caps & 0x40 != 0;
+                info.mfp_capable = caps & 0x80 != 0;
+            }
+        }
+        Some(info)
+    }
+}
+
+/// HT, VHT or HE capabilities of a BSS
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhyCapabilities {
+    /// Widest supported channel in MHz
+    pub max_width: u32,
+    /// Receive spatial streams
+    pub spatial_streams: u8,
+    /// Short guard interval at the widest channel (HT and VHT)
+    pub short_gi: bool,
+}
+
+impl PhyCapabilities {
+    fn parse_ht(data: &[u8]) -> Option<Self> {
+        let caps = u16::from_le_bytes(data.get(..2)?.try_into().ok()?);
+        // Rx MCS bitmask, one byte per spatial stream
+        let mcs = data.get(3..7)?;
+        let width_40 = caps & 0x0002 != 0;
+        Some(Self {
+            max_width: if width_40 { 40 } else { 20 },
+            spatial_streams: mcs.iter().take_while(|b| **b != 0).count() as u8,
+            short_gi: if width_40 { caps & 0x0040 != 0 } else { caps & 0x0020 != 0 },
+        })
+    }
+
+    fn parse_vht(data: &[u8]) -> Option<Self> {
+        let caps = u32::from_le_bytes(data.get(..4)?.try_into().ok()?);
+        let rx_mcs = u16::from_le_bytes(data.get(4..6)?.try_into().ok()?);
+        let width_160 = (caps >> 2) & 0x3 != 0;
+        Some(Self {
+            max_width: if width_160 { 160 } else { 80 },
+            spatial_streams: mcs_map_streams(rx_mcs),
+            short_gi: if width_160 { caps & 0x40 != 0 } else { caps & 0x20 != 0 },
+        })
+    }
+
+    /// `data` follows the extension id: MAC capabilities (6), PHY
+    /// capabilities (11), then the HE-MCS maps starting with Rx <= 80 MHz
+    fn parse_he(data: &[u8]) -> Option<Self> {
+        let width_set = data.get(6)? >> 1;
+        let rx_mcs = u16::from_le_bytes(data.get(17..19)?.try_into().ok()?);
+        let max_width = if width_set & 0b1100 != 0 {
+            160
+        } else if width_set & 0b0010 != 0 {
+            80
+        } else if width_set & 0b0001 != 0 {
+            40
+        } else {
+            20
+        };
+        Some(Self { max_width, spatial_streams: mcs_map_streams(rx_mcs), short_gi: false })
+    }
+}
+
+/// Spatial streams in a VHT/HE MCS map: two bits per stream, 3 = unsupported
+fn mcs_map_streams(map: u16) -> u8 {
+    (0..8).take_while(|n| (map >> (n * 2)) & 0x3 != 0x3).count() as u8
+}
+
+/// Where the regulatory limits of a country element apply
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CountryEnvironment {
+    Any,
+    Indoor,
+    Outdoor,
+    /// Not a country's regulations (e.g. a testing domain)
+    NonCountry,
+}
+
+/// Transmit power limit for a range of channels
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelPower {
+    pub first_channel: u8,
+    pub channels: u8,
+    /// Maximum transmit power in dBm
+    pub max_power: i8,
+}
+
+/// Country element
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CountryInfo {
+    pub code: String,
+    pub environment: CountryEnvironment,
+    pub channels: Vec<ChannelPower>,
+}
+
+impl CountryInfo {
+    fn parse(data: &[u8]) -> Option<Self> {
+        let code = std::str::from_utf8(data.get(..2)?).ok()?.to_string();
+        let environment = match data.get(2)? {
+            b'I' => CountryEnvironment::Indoor,
+            b'O' => CountryEnvironment::Outdoor,
+            b'X' => CountryEnvironment::NonCountry,
+            _ => CountryEnvironment::Any,
+        };
+        let channels = data[3..].chunks_exact(3)
+            // First channel numbers from 201 start operating class triplets
+            .filter(|triplet| triplet[0] != 0 && triplet[0] <= 200)
+            .map(|triplet| ChannelPower {
+                first_channel: triplet[0],
+                channels: triplet[1],
+                max_power: triplet[2] as i8,
+            })
+            .collect();
+        Some(Self { code, environment, channels })
+    }
+}
+
+/// Modulation of a bitrate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RateMode {
+    Legacy,
+    Ht,
+    Vht,
+    He,
+}
+
+/// Transmit or receive bitrate of a station
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateInfo {
+    /// Bitrate in kbit/s
+    pub bitrate: Option<u32>,
+    pub mode: RateMode,
+    pub mcs: Option<u8>,
+    pub spatial_streams: Option<u8>,
+    /// Channel width in MHz
+    pub width: u32,
+    pub short_gi: bool,
+}
+
+/// Statistics of a station
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StationInfo {
+    pub mac: String,
+    /// Milliseconds since the last activity
+    pub inactive_ms: Option<u32>,
+    /// Seconds since the station connected
+    pub connected_secs: Option<u32>,
+    pub rx_bytes: Option<u64>,
+    pub tx_bytes: Option<u64>,
+    pub rx_packets: Option<u32>,
+    pub tx_packets: Option<u32>,
+    pub tx_retries: Option<u32>,
+    pub tx_failed: Option<u32>,
+    pub beacon_loss: Option<u32>,
+    /// Signal of the last received frame in dBm
+    pub signal: Option<i32>,
+    /// Average signal in dBm
+    pub signal_avg: Option<i32>,
+    /// Average beacon signal in dBm
+    pub beacon_signal_avg: Option<i32>,
+    pub tx_bitrate: Option<RateInfo>,
+    pub rx_bitrate: Option<RateInfo>,
+}
+
+/// The BSS an interface is associated with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkInfo {
+    pub bss: ScanResult,
+    /// Statistics of the AP, if the driver reports them
+    pub station: Option<StationInfo>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element(id: u8, data: &[u8]) -> Vec<u8> {
+        let mut ie = vec![id, data.len() as u8];
+        ie.extend_from_slice(data);
+        ie
+    }
+
+    #[test]
+    fn test_parse_security_elements() {
+        // WPA2/WPA3 transition: CCMP, PSK + SAE, MFP capable
+        let rsn = [
+            1, 0, 0x00, 0x0f, 0xac, 4, 1, 0, 0x00, 0x0f, 0xac, 4,
+            2, 0, 0x00, 0x0f, 0xac, 2, 0x00, 0x0f, 0xac, 8, 0x80, 0,
+        ];
+        // WPA1 with TKIP and PSK
+        let wpa = [
+            0x00, 0x50, 0xf2, 1, 1, 0, 0x00, 0x50, 0xf2, 2, 1, 0, 0x00, 0x50, 0xf2, 2,
+            1, 0, 0x00, 0x50, 0xf2, 2,
+        ];
+        let mut ies = element(0, b"home");
+        ies.extend(element(48, &rsn));
+        ies.extend(element(221, &wpa));
+
+        let mut bss = ScanResult::default();
+        bss.apply_ies(&ies);
+        assert_eq!(bss.ssid.as_deref(), Some("home"));
+        let rsn = bss.rsn.as_ref().unwrap();
+        assert_eq!(rsn.group_cipher, CipherSuite::Ccmp);
+        assert_eq!(rsn.pairwise_ciphers, vec![CipherSuite::Ccmp]);
+        assert_eq!(rsn.akm_suites, vec![AkmSuite::Psk, AkmSuite::Sae]);
+        assert!(rsn.mfp_capable && !rsn.mfp_required);
+        let wpa = bss.wpa.as_ref().unwrap();
+        assert_eq!(wpa.pairwise_ciphers, vec![CipherSuite::Tkip]);
+        assert_eq!(wpa.akm_suites, vec![AkmSuite::Psk]);
+        assert_eq!(bss.security_label(), "WPA1 WPA2 WPA3");
+
+        // An RSN element with only the version uses the defaults
+        let mut bss = ScanResult::default();
+        bss.apply_ies(&element(48, &[1, 0]));
+        assert_eq!(bss.rsn.unwrap().akm_suites, vec![AkmSuite::Ieee8021x]);
+        assert_eq!(AkmSuite::from_selector([0x50, 0x6f, 0x9a], 1).to_string(), "50-6f-9a:1");
+    }
+
+    #[test]
+    fn test_parse_phy_and_country_elements() {
+        let mut ht = vec![0x62, 0x00, 0x17];
+        ht.extend([0xff, 0xff, 0, 0]);
+        ht.resize(26, 0);
+        let mut ht_op = vec![36, 0x07];
+        ht_op.resize(22, 0);
+        // 160 MHz, short GI at 160, 4 streams
+        let vht = [0x64, 0x00, 0x00, 0x00, 0x00, 0xff, 0, 0, 0, 0, 0, 0];
+        let vht_op = [1, 42, 50, 0, 0];
+        let mut he = vec![35];
+        he.extend([0; 6]);
+        he.extend([0b0000_0110, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        he.extend([0xfa, 0xff, 0xfa, 0xff]);
+        let country = [b'D', b'E', b' ', 36, 4, 23, 100, 11, 30, 201, 1, 0];
+
+        let mut ies = element(45, &ht);
+        ies.extend(element(61, &ht_op));
+        ies.extend(element(191, &vht));
+        ies.extend(element(192, &vht_op));
+        ies.extend(element(255, &he));
+        ies.extend(element(7, &country));
+
+        let mut bss = ScanResult::default();
+        bss.apply_ies(&ies);
+        assert_eq!(bss.ht, Some(PhyCapabilities { max_width: 40, spatial_streams: 2, short_gi: true }));
+        assert_eq!(bss.vht, Some(PhyCapabilities { max_width: 160, spatial_streams: 4, short_gi: true }));
+        assert_eq!(bss.he, Some(PhyCapabilities { max_width: 80, spatial_streams: 2, short_gi: false }));
+        assert_eq!(bss.channel_width, Some(160));
+        let country = bss.country.as_ref().unwrap();
+        assert_eq!(country.code, "DE");
+        assert_eq!(country.environment, CountryEnvironment::Any);
+        assert_eq!(country.channels, vec![
+            ChannelPower { first_channel: 36, channels: 4, max_power: 23 },
+            ChannelPower { first_channel: 100, channels: 11, max_power: 30 },
+        ]);
+        assert!(bss.ssid.is_none());
+        assert_eq!(bss.security_label(), "");
+    }
+
+    #[test]
+    fn test_parse_tx_power() {
+        assert_eq!("auto".parse::<TxPower>().unwrap(), TxPower::Auto);
+        assert_eq!("20dBm".parse::<TxPower>().unwrap(), TxPower::Limit(2000));
+        assert_eq!("fixed 100mW".parse::<TxPower>().unwrap(), TxPower::Fixed(2000));
+        assert_eq!("limit 1500".parse::<TxPower>().unwrap(), TxPower::Limit(1500));
+        assert!("fixed".parse::<TxPower>().is_err());
+        assert!("20 dBi".parse::<TxPower>().is_err());
+        assert!("0mW".parse::<TxPower>().is_err());
+        assert_eq!(frequency_to_channel(2437), Some(6));
+        assert_eq!(frequency_to_channel(5180), Some(36));
+        assert_eq!(frequency_to_channel(5975), Some(5));
+    }
+
+    #[test]
+    fn test_band_frequencies() {
+        assert_eq!("2.4GHz".parse::<WifiBand>().unwrap(), WifiBand::Band2GHz);
+        assert_eq!("a".parse::<WifiBand>().unwrap(), WifiBand::Band5GHz);
+        assert!("60GHz".parse::<WifiBand>().is_err());
+        assert_eq!(WifiBand::of_channel(11), Some(WifiBand::Band2GHz));
+        assert_eq!(WifiBand::of_channel(200), None);
+        assert_eq!(WifiBand::of_channel(36), Some(WifiBand::Band5GHz));
+        assert_eq!(WifiBand::of_channel(165), Some(WifiBand::Band5GHz));
+        assert_eq!(WifiBand::of_channel(37), None);
+        assert_eq!(WifiBand::of_channel(80), None);
+
+        assert_eq!(WifiBand::Band2GHz.channel_frequency(14), Some(2484));
+        assert_eq!(WifiBand::Band5GHz.channel_frequency(6), None);
+        assert_eq!(WifiBand::Band5GHz.channel_frequency(149), Some(5745));
+        assert_eq!(WifiBand::Band5GHz.channel_frequency(150), None);
+        assert_eq!(WifiBand::Band6GHz.channel_frequency(3), None);
+        for band in [WifiBand::Band2GHz, WifiBand::Band5GHz, WifiBand::Band6GHz] {
+            for freq in band.frequencies() {
+                let channel = frequency_to_channel(freq).unwrap();
+                assert_eq!(band.channel_frequency(channel), Some(freq));
+            }
+        }
+        assert_eq!(WifiBand::Band5GHz.frequencies().len(), 28);
+    }
 }
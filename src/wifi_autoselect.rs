@@ -0,0 +1,526 @@
+//! WiFi network auto-selection
+//!
+//! Decides which saved WiFi profile an interface connects to. A scan is
+//! matched against every WiFi profile with `autoconnect` set, and the
+//! candidates are ranked by `autoconnect-priority`, then seen before unseen
+//! hidden networks, then by security and then by signal, and tried in that
+//! order. Profiles that keep failing are skipped for a while, and profiles
+//! whose credentials were rejected are skipped until the profile is changed.
+//! Once connected, the interface's wpa_supplicant events are followed and
+//! the selection runs again when the network is lost for good.
+
+use crate::connection_config::NetctlConnectionConfig;
+use crate::connection_manager::ConnectionManager;
+use crate::error::{NetctlError, NetctlResult};
+use crate::wpa_ctrl::{self, WpaEvent, WpaEvents};
+use crate::wpa_supplicant::{WpaNetwork, WpaScanResult, WpaSecurity, WpaState, WpaSupplicantController};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// Failed attempts a profile gets before it is skipped for a while
+const MAX_RETRIES: u32 = 3;
+
+/// How long a profile that ran out of retries is skipped
+const RETRY_BLOCK: Duration = Duration::from_secs(300);
+
+/// How long wpa_supplicant may take to get back to a lost network itself
+const RECONNECT_GRACE: Duration = Duration::from_secs(10);
+
+/// Delay between selections while no known network can be connected
+const RESELECT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A saved profile that can be connected to
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    /// Name of the connection profile
+    pub name: String,
+    pub network: WpaNetwork,
+    pub priority: i32,
+    /// Strongest matching access point in dBm; `None` for a hidden network
+    /// that did not show up in the scan
+    pub signal: Option<i32>,
+}
+
+impl Candidate {
+    /// Preference of the candidate's security, higher is better
+    pub fn security_rank(&self) -> u8 {
+        match &self.network.security {
+            WpaSecurity::Open => 0,
+            WpaSecurity::Owe => 1,
+            WpaSecurity::Psk(_) => 2,
+            WpaSecurity::PskSae(_) => 3,
+            WpaSecurity::Sae(_) => 4,
+            WpaSecurity::Eap { suite_b_192: false, .. } => 5,
+            WpaSecurity::Eap { suite_b_192: true, .. } => 6,
+        }
+    }
+}
+
+/// Whether an access point advertising `flags` offers `security`
+fn offers(security: &WpaSecurity, flags: &str) -> bool {
+    match security {
+        WpaSecurity::Open => !["WPA", "RSN", "WEP", "OSEN"].iter().any(|f| flags.contains(f)),
+        WpaSecurity::Owe => flags.contains("OWE"),
+        WpaSecurity::Psk(_) => flags.contains("PSK"),
+        WpaSecurity::Sae(_) => flags.contains("SAE"),
+        WpaSecurity::PskSae(_) => flags.contains("PSK") || flags.contains("SAE"),
+        WpaSecurity::Eap { suite_b_192: true, .. } => flags.contains("EAP-SUITE-B-192"),
+        WpaSecurity::Eap { .. } => flags.contains("EAP"),
+    }
+}
+
+/// Whether an access point in the scan can serve `network`
+fn serves(network: &WpaNetwork, ap: &WpaScanResult) -> bool {
+    ap.ssid == network.ssid
+        && network.bssid.as_ref().is_none_or(|bssid| bssid.eq_ignore_ascii_case(&ap.bssid))
+        && (network.frequencies.is_empty() || network.frequencies.contains(&ap.frequency))
+        && offers(&network.security, &ap.flags)
+}
+
+/// Whether auto-selection may connect `interface` with a profile
+///
+/// That is a WiFi client profile with `autoconnect` set that is not bound
+/// to another interface.
+pub fn applies(interface: &str, config: &NetctlConnectionConfig) -> bool {
+    config.connection.autoconnect
+        && config.wifi.as_ref().is_some_and(|wifi| wifi.mode == "infrastructure")
+        && config.connection.interface_name.as_deref().is_none_or(|iface| iface == interface)
+}
+
+/// Match saved profiles against a scan of `interface` and rank them, best first
+///
+/// Only the profiles that [`applies`] to the interface are considered.
+/// Hidden networks are candidates even when the scan does not show them,
+/// after the seen ones of their priority.
+pub fn rank_candidates(
+    interface: &str,
+    profiles: &[(String, NetctlConnectionConfig)],
+    scan: &[WpaScanResult],
+) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+    for (name, config) in profiles.iter().filter(|(_, config)| applies(interface, config)) {
+        let network = match config.wpa_network() {
+            Ok(network) => network,
+            Err(e) => {
+                debug!("Skipping WiFi profile '{}': {}", name, e);
+                continue;
+            }
+        };
+
+        let signal = scan.iter().filter(|ap| serves(&network, ap)).map(|ap| ap.signal_level).max();
+        if signal.is_none() && !network.hidden {
+            continue;
+        }
+        candidates.push(Candidate {
+            name: name.clone(),
+            priority: config.connection.autoconnect_priority,
+            network,
+            signal,
+        });
+    }
+
+    candidates.sort_by_key(|c| {
+        (Reverse(c.priority), Reverse(c.signal.is_some()), Reverse(c.security_rank()), Reverse(c.signal))
+    });
+    candidates
+}
+
+/// Why a profile is skipped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+    /// Out of retries until then
+    Until(Instant),
+    /// The credentials were rejected
+    AuthFailed,
+}
+
+#[derive(Debug)]
+struct ProfileState {
+    /// The network the failures were counted for
+    network: WpaNetwork,
+    failures: u32,
+    block: Option<Block>,
+}
+
+/// Failed attempts and blocked profiles
+///
+/// The record of a profile is dropped once its network changes, e.g. when
+/// the profile is saved with new credentials.
+#[derive(Debug, Default)]
+pub struct Blocklist {
+    profiles: HashMap<String, ProfileState>,
+}
+
+impl Blocklist {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether `name` with `network` is skipped at `now`
+    pub fn is_blocked(&mut self, name: &str, network: &WpaNetwork, now: Instant) -> bool {
+        let Some(state) = self.profiles.get_mut(name) else { return false };
+        if state.network != *network {
+            debug!("WiFi profile '{}' changed, forgetting its failures", name);
+            self.profiles.remove(name);
+            return false;
+        }
+        match state.block {
+            Some(Block::AuthFailed) => true,
+            Some(Block::Until(until)) if now < until => true,
+            Some(Block::Until(_)) => {
+                self.profiles.remove(name);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Count a failed attempt to connect `name` with `network`
+    ///
+    /// Rejected credentials block the profile right away; other failures
+    /// block it for a while once it is out of retries.
+    pub fn record_failure(&mut self, name: &str, network: &WpaNetwork, error: &NetctlError, now: Instant) {
+        let fresh = || ProfileState { network: network.clone(), failures: 0, block: None };
+        let state = self.profiles.entry(name.to_string()).or_insert_with(fresh);
+        if state.network != *network {
+            *state = fresh();
+        }
+        if matches!(error, NetctlError::AuthenticationFailed(_)) {
+            warn!("Blocking WiFi profile '{}' from auto-connect: {}", name, error);
+            state.block = Some(Block::AuthFailed);
+            return;
+        }
+        state.failures += 1;
+        if state.failures >= MAX_RETRIES {
+            warn!("WiFi profile '{}' failed {} times, skipping it for {}s", name, state.failures, RETRY_BLOCK.as_secs());
+            state.failures = 0;
+            state.block = Some(Block::Until(now + RETRY_BLOCK));
+        }
+    }
+
+    /// Forget the failures of `name`, after it connected
+    pub fn clear(&mut self, name: &str) {
+        self.profiles.remove(name);
+    }
+}
+
+/// WiFi auto-selection engine
+///
+/// Cloning is cheap and clones share their state.
+#[derive(Clone)]
+pub struct WifiAutoselect {
+    wpa_supplicant: Arc<WpaSupplicantController>,
+    connection_manager: Arc<ConnectionManager>,
+    blocklist: Arc<Mutex<Blocklist>>,
+    /// Serializes selections, which scan and reconfigure wpa_supplicant
+    selecting: Arc<tokio::sync::Mutex<()>>,
+    /// Interfaces whose connection is being followed
+    watched: Arc<Mutex<HashSet<String>>>,
+    /// wpa_supplicant id and SSID of the network the last selection added,
+    /// by interface
+    added: Arc<Mutex<HashMap<String, (String, String)>>>,
+}
+
+impl WifiAutoselect {
+    pub fn new(wpa_supplicant: Arc<WpaSupplicantController>, connection_manager: Arc<ConnectionManager>) -> Self {
+        Self {
+            wpa_supplicant,
+            connection_manager,
+            blocklist: Arc::new(Mutex::new(Blocklist::new())),
+            selecting: Arc::new(tokio::sync::Mutex::new(())),
+            watched: Arc::new(Mutex::new(HashSet::new())),
+            added: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Scan and connect `interface` to the best known network in range
+    ///
+    /// Candidates are tried in turn until one connects; its profile name is
+    /// returned and the connection is followed from then on. Fails with the
+    /// error of the last candidate, or `NotFound` if there was none.
+    pub async fn select(&self, interface: &str) -> NetctlResult<String> {
+        let _selecting = self.selecting.lock().await;
+
+        let profiles = self.load_profiles().await?;
+        // Leave wpa_supplicant alone on interfaces no profile could use
+        if !profiles.iter().any(|(_, config)| applies(interface, config)) {
+            return Err(NetctlError::NotFound(format!("No WiFi connection auto-connects {}", interface)));
+        }
+        let scan = self.wpa_supplicant.scan_and_wait(interface).await?;
+        let candidates = rank_candidates(interface, &profiles, &scan);
+
+        let mut last_error = NetctlError::NotFound(format!("No known WiFi network in range of {}", interface));
+        for candidate in candidates {
+            if self.blocklist.lock().unwrap().is_blocked(&candidate.name, &candidate.network, Instant::now()) {
+                debug!("Skipping blocked WiFi profile '{}'", candidate.name);
+                continue;
+            }
+            info!(
+                "Auto-connecting {} to '{}' (profile '{}', priority {}, signal {})",
+                interface,
+                candidate.network.ssid,
+                candidate.name,
+                candidate.priority,
+                candidate.signal.map_or("unseen".to_string(), |dbm| format!("{} dBm", dbm)),
+            );
+            match self.wpa_supplicant.connect_network(interface, &candidate.network).await {
+                Ok(network_id) => {
+                    self.blocklist.lock().unwrap().clear(&candidate.name);
+                    self.forget_previous(interface, network_id, &candidate.network.ssid).await;
+                    self.watch(interface);
+                    return Ok(candidate.name);
+                }
+                Err(e) => {
+                    warn!("Auto-connect of {} to profile '{}' failed: {}", interface, candidate.name, e);
+                    let now = Instant::now();
+                    self.blocklist.lock().unwrap().record_failure(&candidate.name, &candidate.network, &e, now);
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Whether any saved profile [`applies`] to `interface`
+    pub async fn has_profiles(&self, interface: &str) -> NetctlResult<bool> {
+        Ok(self.load_profiles().await?.iter().any(|(_, config)| applies(interface, config)))
+    }
+
+    /// Whether the connection of `interface` is being followed
+    pub fn is_watching(&self, interface: &str) -> bool {
+        self.watched.lock().unwrap().contains(interface)
+    }
+
+    /// Remove the network the previous selection added to wpa_supplicant,
+    /// so networks do not pile up in its configuration with every selection
+    async fn forget_previous(&self, interface: &str, network_id: String, ssid: &str) {
+        let added = (network_id.clone(), ssid.to_string());
+        let previous = self.added.lock().unwrap().insert(interface.to_string(), added);
+        let Some((previous_id, previous_ssid)) = previous.filter(|(id, _)| *id != network_id) else { return };
+
+        // Ids start over when wpa_supplicant restarts; only remove our own network
+        let networks = match self.wpa_supplicant.list_networks(interface).await {
+            Ok(networks) => networks,
+            Err(e) => {
+                debug!("Failed to list WiFi networks of {}: {}", interface, e);
+                return;
+            }
+        };
+        if networks.iter().any(|(id, ssid, _)| *id == previous_id && *ssid == previous_ssid) {
+            debug!("Removing previously selected network {} ('{}') from {}", previous_id, previous_ssid, interface);
+            if let Err(e) = self.wpa_supplicant.remove_network(interface, &previous_id).await {
+                debug!("Failed to remove network {} from {}: {}", previous_id, interface, e);
+            }
+        }
+    }
+
+    /// All saved profiles that could be loaded
+    async fn load_profiles(&self) -> NetctlResult<Vec<(String, NetctlConnectionConfig)>> {
+        let mut profiles = Vec::new();
+        for name in self.connection_manager.list_connections().await? {
+            match self.connection_manager.load_connection(&name).await {
+                Ok(config) => profiles.push((name, config)),
+                Err(e) => debug!("Failed to load connection '{}': {}", name, e),
+            }
+        }
+        Ok(profiles)
+    }
+
+    /// Follow the connection of `interface` in the background
+    fn watch(&self, interface: &str) {
+        if !self.watched.lock().unwrap().insert(interface.to_string()) {
+            return;
+        }
+        let autoselect = self.clone();
+        let interface = interface.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = autoselect.follow(&interface).await {
+                debug!("Stopped following WiFi connection of {}: {}", interface, e);
+            }
+            autoselect.watched.lock().unwrap().remove(&interface);
+        });
+    }
+
+    /// Select a network again whenever the current one is lost
+    async fn follow(&self, interface: &str) -> NetctlResult<()> {
+        let mut events = self.wpa_supplicant.events(interface).await?;
+        loop {
+            match events.next().await? {
+                // Disconnects asked for locally, e.g. by switching networks, are not a loss
+                WpaEvent::Disconnected { locally_generated: false, reason, .. } => {
+                    info!("Lost WiFi connection on {}: {}", interface, wpa_ctrl::disconnect_reason(reason));
+                }
+                WpaEvent::Terminating => return Ok(()),
+                _ => continue,
+            }
+
+            // wpa_supplicant roams and reconnects on its own; give it a chance
+            if Self::reconnects(&mut events).await? {
+                debug!("{} reconnected by itself", interface);
+                continue;
+            }
+            events = self.reselect(interface).await?;
+        }
+    }
+
+    /// Whether the connection comes back within the grace period
+    async fn reconnects(events: &mut WpaEvents) -> NetctlResult<bool> {
+        let reconnected = tokio::time::timeout(RECONNECT_GRACE, async {
+            loop {
+                match events.next().await? {
+                    WpaEvent::Connected { .. } => return Ok(()),
+                    WpaEvent::Terminating => {
+                        return Err(NetctlError::ServiceError("wpa_supplicant is exiting".to_string()));
+                    }
+                    _ => {}
+                }
+            }
+        })
+        .await;
+        match reconnected {
+            Ok(result) => result.map(|()| true),
+            Err(_) => Ok(false),
+        }
+    }
+
+    /// Select until `interface` is connected again, and return a fresh
+    /// subscription so the events of the attempts are not seen as losses
+    async fn reselect(&self, interface: &str) -> NetctlResult<WpaEvents> {
+        loop {
+            info!("Re-evaluating WiFi networks for {}", interface);
+            match self.select(interface).await {
+                Ok(name) => info!("{} is now connected with profile '{}'", interface, name),
+                Err(e) => warn!("No WiFi network could be selected for {}: {}", interface, e),
+            }
+            // Connected by this selection or by anyone else in the meantime
+            if self.wpa_supplicant.status(interface).await?.state == WpaState::Completed {
+                return self.wpa_supplicant.events(interface).await;
+            }
+            tokio::time::sleep(RESELECT_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(name: &str, toml: &str) -> (String, NetctlConnectionConfig) {
+        let config = format!(
+            "[connection]\nname = \"{}\"\nuuid = \"00000000-0000-0000-0000-000000000000\"\ntype = \"wifi\"\nautoconnect = true\n{}",
+            name, toml
+        );
+        (name.to_string(), toml::from_str(&config).unwrap())
+    }
+
+    fn ap(ssid: &str, bssid: &str, signal: i32, flags: &str) -> WpaScanResult {
+        WpaScanResult {
+            bssid: bssid.to_string(),
+            frequency: 2412,
+            signal_level: signal,
+            flags: flags.to_string(),
+            ssid: ssid.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_rank_candidates() {
+        let psk = "[wifi-security]\nkey-mgmt = \"wpa-psk\"\npsk = \"password123\"\n";
+        let mut profiles = vec![
+            profile("cafe", "[wifi]\nssid = \"cafe\"\n"),
+            profile("home", &format!("[wifi]\nssid = \"home\"\n{}", psk)),
+            profile("home-wpa3", "[wifi]\nssid = \"home\"\n[wifi-security]\nkey-mgmt = \"sae\"\npsk = \"correct horse\"\n"),
+            profile("work", &format!("[wifi]\nssid = \"work\"\n{}", psk)),
+            profile("pinned", &format!("[wifi]\nssid = \"home\"\nbssid = \"02:00:00:00:00:09\"\n{}", psk)),
+            profile("stealth", &format!("[wifi]\nssid = \"stealth\"\nhidden = true\n{}", psk)),
+            profile("other-if", &format!("[wifi]\nssid = \"home\"\n{}", psk)),
+        ];
+        profiles[3].1.connection.autoconnect_priority = 10;
+        profiles[6].1.connection.interface_name = Some("wlan1".to_string());
+
+        let scan = vec![
+            ap("cafe", "02:00:00:00:00:01", -40, "[ESS]"),
+            ap("home", "02:00:00:00:00:02", -70, "[WPA2-PSK-CCMP][ESS]"),
+            ap("home", "02:00:00:00:00:03", -60, "[WPA2-PSK+SAE-CCMP][ESS]"),
+            ap("work", "02:00:00:00:00:04", -85, "[WPA2-PSK-CCMP][ESS]"),
+            ap("rogue", "02:00:00:00:00:05", -30, "[ESS]"),
+        ];
+        let ranked = rank_candidates("wlan0", &profiles, &scan);
+        let names: Vec<&str> = ranked.iter().map(|c| c.name.as_str()).collect();
+        // Priority beats signal, security beats signal, unseen hidden networks come last
+        assert_eq!(names, vec!["work", "home-wpa3", "home", "cafe", "stealth"]);
+        assert_eq!(ranked[1].signal, Some(-60));
+        assert_eq!(ranked[2].signal, Some(-60));
+        assert_eq!(ranked[4].signal, None);
+
+        // Seen beats unseen even with the weaker signal of the same priority and security
+        let same = vec![
+            profile("stealth", &format!("[wifi]\nssid = \"stealth\"\nhidden = true\n{}", psk)),
+            profile("work", &format!("[wifi]\nssid = \"work\"\n{}", psk)),
+        ];
+        let names: Vec<String> = rank_candidates("wlan0", &same, &scan).into_iter().map(|c| c.name).collect();
+        assert_eq!(names, vec!["work", "stealth"]);
+
+        // but not a higher priority
+        let mut preferred = same.clone();
+        preferred[0].1.connection.autoconnect_priority = 1;
+        let names: Vec<String> = rank_candidates("wlan0", &preferred, &scan).into_iter().map(|c| c.name).collect();
+        assert_eq!(names, vec!["stealth", "work"]);
+
+        // An open profile does not match a protected network of the same name
+        let open_home = vec![profile("open-home", "[wifi]\nssid = \"home\"\n")];
+        assert!(rank_candidates("wlan0", &open_home, &scan).is_empty());
+
+        let mut manual = profile("cafe", "[wifi]\nssid = \"cafe\"\n");
+        manual.1.connection.autoconnect = false;
+        assert!(!applies("wlan0", &manual.1));
+        assert!(rank_candidates("wlan0", &[manual], &scan).is_empty());
+
+        let access_point = profile("hotspot", "[wifi]\nssid = \"hotspot\"\nmode = \"ap\"\n");
+        assert!(!applies("wlan0", &access_point.1));
+        assert!(applies("wlan1", &profiles[6].1));
+        assert!(!applies("wlan0", &profiles[6].1));
+        assert!(!applies("wlan0", &profile("wired", "").1));
+    }
+
+    #[test]
+    fn test_blocklist() {
+        let mut blocklist = Blocklist::new();
+        let now = Instant::now();
+        let timeout = NetctlError::Timeout("no connection".to_string());
+        let network = |ssid: &str, psk: &str| WpaNetwork {
+            ssid: ssid.to_string(),
+            security: WpaSecurity::Psk(psk.to_string()),
+            ..Default::default()
+        };
+        let home = network("home", "password123");
+
+        for _ in 1..MAX_RETRIES {
+            blocklist.record_failure("home", &home, &timeout, now);
+            assert!(!blocklist.is_blocked("home", &home, now));
+        }
+        blocklist.record_failure("home", &home, &timeout, now);
+        assert!(blocklist.is_blocked("home", &home, now));
+        assert!(blocklist.is_blocked("home", &home, now + RETRY_BLOCK - Duration::from_secs(1)));
+        assert!(!blocklist.is_blocked("home", &home, now + RETRY_BLOCK));
+
+        // Retries start over once the block expired
+        blocklist.record_failure("home", &home, &timeout, now);
+        assert!(!blocklist.is_blocked("home", &home, now));
+
+        let work = network("work", "wrong password");
+        blocklist.record_failure("work", &work, &NetctlError::AuthenticationFailed("wrong password".to_string()), now);
+        assert!(blocklist.is_blocked("work", &work, now + RETRY_BLOCK * 10));
+        blocklist.clear("work");
+        assert!(!blocklist.is_blocked("work", &work, now));
+
+        // Saving the profile with other credentials lifts the block
+        blocklist.record_failure("work", &work, &NetctlError::AuthenticationFailed("wrong password".to_string()), now);
+        let fixed = network("work", "right password");
+        assert!(!blocklist.is_blocked("work", &fixed, now));
+        assert!(!blocklist.is_blocked("work", &work, now));
+    }
+}
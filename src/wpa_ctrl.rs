@@ -0,0 +1,449 @@
+//! wpa_supplicant control interface client
+//!
+//! wpa_supplicant creates one UNIX datagram socket per interface in its
+//! ctrl_interface directory. A client sends a text command and reads the
+//! reply; after `ATTACH` the same socket also receives unsolicited events,
+//! prefixed with their priority as in `<3>CTRL-EVENT-CONNECTED ...`.
+//!
+//! [`WpaCtrl`] issues requests and [`WpaEvents`] is an attached socket that
+//! yields parsed [`WpaEvent`]s, so callers learn about connections and
+//! authentication failures as they happen instead of polling `STATUS`.
+
+use crate::error::{NetctlError, NetctlResult};
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::os::linux::net::SocketAddrExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::net::UnixDatagram;
+use tracing::debug;
+
+/// How long wpa_supplicant gets to answer a request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Replies such as SCAN_RESULTS can be large
+const RECV_BUFFER_LEN: usize = 65536;
+
+/// Distinguishes the client sockets of one process
+static CLIENT_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// An event reported by wpa_supplicant on an attached control socket
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WpaEvent {
+    /// Association and key handshake completed
+    Connected { bssid: String, network_id: Option<u32> },
+    /// The connection was lost or a connection attempt failed
+    Disconnected { bssid: Option<String>, reason: u16, locally_generated: bool },
+    /// A network was disabled for a while after failing to connect
+    SsidTempDisabled {
+        network_id: u32,
+        ssid: String,
+        auth_failures: u32,
+        /// Seconds until the network is retried
+        duration: u32,
+        /// Why it was disabled, e.g. `WRONG_KEY` or `AUTH_FAILED`
+        reason: String,
+    },
+    /// A temporarily disabled network may be used again
+    SsidReenabled { network_id: u32, ssid: String },
+    /// The AP rejected the association
+    AssocReject { bssid: Option<String>, status_code: u16 },
+    /// EAP authentication failed
+    EapFailure,
+    /// No enabled network was found in the scan results
+    NetworkNotFound,
+    /// New scan results are available
+    ScanResults,
+    /// A scan could not be started
+    ScanFailed,
+    /// wpa_supplicant is exiting
+    Terminating,
+    /// Any other message
+    Other { priority: u8, message: String },
+}
+
+impl WpaEvent {
+    /// Parse an unsolicited message; `None` if it is not one
+    pub fn parse(message: &str) -> Option<Self> {
+        // The global control interface prefixes events with the interface
+        let message = match message.strip_prefix("IFNAME=") {
+            Some(rest) => rest.split_once(' ')?.1,
+            None => message,
+        };
+        let rest = message.strip_prefix('<')?;
+        let (priority, text) = rest.split_once('>')?;
+        let priority = priority.parse().ok()?;
+        let text = text.trim_end();
+        let (name, args) = text.split_once(' ').unwrap_or((text, ""));
+        let fields = fields(args);
+
+        let event = match name {
+            "CTRL-EVENT-CONNECTED" => {
+                // "- Connection to 02:00:00:00:01:00 completed [id=0 id_str=]"
+                let bssid = args.split_whitespace().skip_while(|word| *word != "to").nth(1)?;
+                let network_id = args.split_once("[id=")
+                    .and_then(|(_, id)| id.split(|c: char| !c.is_ascii_digit()).next()?.parse().ok());
+                WpaEvent::Connected { bssid: bssid.to_string(), network_id }
+            }
+            "CTRL-EVENT-DISCONNECTED" => WpaEvent::Disconnected {
+                bssid: fields.get("bssid").cloned(),
+                reason: number(&fields, "reason").unwrap_or(0),
+                locally_generated: fields.get("locally_generated").is_some_and(|value| value == "1"),
+            },
+            "CTRL-EVENT-SSID-TEMP-DISABLED" => WpaEvent::SsidTempDisabled {
+                network_id: number(&fields, "id")?,
+                ssid: fields.get("ssid").cloned().unwrap_or_default(),
+                auth_failures: number(&fields, "auth_failures").unwrap_or(0),
+                duration: number(&fields, "duration").unwrap_or(0),
+                reason: fields.get("reason").cloned().unwrap_or_default(),
+            },
+            "CTRL-EVENT-SSID-REENABLED" => WpaEvent::SsidReenabled {
+                network_id: number(&fields, "id")?,
+                ssid: fields.get("ssid").cloned().unwrap_or_default(),
+            },
+            "CTRL-EVENT-ASSOC-REJECT" => WpaEvent::AssocReject {
+                bssid: fields.get("bssid").cloned(),
+                status_code: number(&fields, "status_code").unwrap_or(0),
+            },
+            "CTRL-EVENT-EAP-FAILURE" => WpaEvent::EapFailure,
+            "CTRL-EVENT-NETWORK-NOT-FOUND" => WpaEvent::NetworkNotFound,
+            "CTRL-EVENT-SCAN-RESULTS" => WpaEvent::ScanResults,
+            "CTRL-EVENT-SCAN-FAILED" => WpaEvent::ScanFailed,
+            "CTRL-EVENT-TERMINATING" => WpaEvent::Terminating,
+            _ => WpaEvent::Other { priority, message: text.to_string() },
+        };
+        Some(event)
+    }
+}
+
+/// Split `key=value` pairs; quoted values may contain spaces and the
+/// escapes wpa_supplicant uses when printing SSIDs
+fn fields(args: &str) -> HashMap<&str, String> {
+    let mut fields = HashMap::new();
+    let mut rest = args.trim_start();
+    while !rest.is_empty() {
+        let end = rest.find([' ', '=']).unwrap_or(rest.len());
+        let (key, after) = rest.split_at(end);
+        let Some(value) = after.strip_prefix('=') else {
+            // A word that is not a key=value pair, such as a leading "-"
+            rest = after.trim_start();
+            continue;
+        };
+        let (parsed, remainder) = if let Some(quoted) = value.strip_prefix('"') {
+            unquote(quoted)
+        } else {
+            let end = value.find(' ').unwrap_or(value.len());
+            (value[..end].to_string(), &value[end..])
+        };
+        fields.insert(key, parsed);
+        rest = remainder.trim_start();
+    }
+    fields
+}
+
+fn number<T: std::str::FromStr>(fields: &HashMap<&str, String>, key: &str) -> Option<T> {
+    fields.get(key)?.parse().ok()
+}
+
+/// Decode a quoted value up to its closing quote
+fn unquote(value: &str) -> (String, &str) {
+    let mut bytes = Vec::new();
+    let mut chars = value.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return (String::from_utf8_lossy(&bytes).into_owned(), &value[index + 1..]),
+            '\\' => match chars.next() {
+                Some((_, 'n')) => bytes.push(b'\n'),
+                Some((_, 'r')) => bytes.push(b'\r'),
+                Some((_, 't')) => bytes.push(b'\t'),
+                Some((_, 'e')) => bytes.push(0x1b),
+                Some((start, 'x')) => {
+                    let hex = value.get(start + 1..start + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                    if let Some(byte) = hex {
+                        bytes.push(byte);
+                        chars.nth(1);
+                    }
+                }
+                Some((_, c)) => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+                None => break,
+            },
+            c => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    (String::from_utf8_lossy(&bytes).into_owned(), "")
+}
+
+/// Description of an IEEE 802.11 reason code, as carried by
+/// [`WpaEvent::Disconnected`]
+pub fn disconnect_reason(code: u16) -> &'static str {
+    match code {
+        1 => "unspecified",
+        2 => "previous authentication no longer valid",
+        3 => "station is leaving",
+        4 => "disassociated due to inactivity",
+        5 => "AP is unable to handle all associated stations",
+        6 => "class 2 frame received from nonauthenticated station",
+        7 => "class 3 frame received from nonassociated station",
+        8 => "station has left the BSS",
+        9 => "station requesting association is not authenticated",
+        13 => "invalid information element",
+        14 => "message integrity check failure",
+        15 => "4-way handshake timeout",
+        16 => "group key handshake timeout",
+        17 => "information element in 4-way handshake differs",
+        18 => "invalid group cipher",
+        19 => "invalid pairwise cipher",
+        20 => "invalid AKMP",
+        21 => "unsupported RSNE version",
+        22 => "invalid RSNE capabilities",
+        23 => "IEEE 802.1X authentication failed",
+        24 => "cipher suite rejected by security policy",
+        34 => "too many frames not acknowledged",
+        _ => "unknown reason",
+    }
+}
+
+/// A client socket of a wpa_supplicant control interface
+pub struct WpaCtrl {
+    socket: UnixDatagram,
+    path: PathBuf,
+}
+
+impl WpaCtrl {
+    /// Connect to the control socket at `path`, usually
+    /// `/var/run/wpa_supplicant/<interface>`
+    pub fn open(path: impl AsRef<Path>) -> NetctlResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        // wpa_supplicant replies to the sender's address, so the client needs
+        // one; an abstract name leaves nothing behind in the filesystem
+        let name = format!(
+            "netctl-wpa-{}-{}",
+            std::process::id(),
+            CLIENT_COUNTER.fetch_add(1, Ordering::Relaxed),
+        );
+        let address = std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())?;
+        let socket = std::os::unix::net::UnixDatagram::bind_addr(&address)?;
+        socket.connect(&path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound | std::io::ErrorKind::ConnectionRefused => {
+                NetctlError::NotFound(format!("wpa_supplicant control socket {}", path.display()))
+            }
+            _ => NetctlError::Io(e),
+        })?;
+        socket.set_nonblocking(true)?;
+        Ok(Self { socket: UnixDatagram::from_std(socket)?, path })
+    }
+
+    /// Send a command and return its reply
+    ///
+    /// A `FAIL` reply is an error: `FAIL-BUSY` maps to `InvalidState`, an
+    /// unknown command to `NotSupported`. Only the first words of the
+    /// command appear in errors, so passphrases are never echoed.
+    pub async fn request(&self, command: &str) -> NetctlResult<String> {
+        let name = command.split_whitespace().take(3).collect::<Vec<_>>().join(" ");
+        debug!("wpa_supplicant request on {}: {}", self.path.display(), name);
+        self.socket.send(command.as_bytes()).await?;
+
+        let reply = tokio::time::timeout(REQUEST_TIMEOUT, async {
+            let mut buf = vec![0u8; RECV_BUFFER_LEN];
+            loop {
+                let len = self.socket.recv(&mut buf).await?;
+                // Events of an attached socket may arrive before the reply
+                if !is_unsolicited(&buf[..len]) {
+                    return Ok::<_, NetctlError>(String::from_utf8_lossy(&buf[..len]).into_owned());
+                }
+            }
+        })
+        .await
+        .map_err(|_| NetctlError::Timeout(format!("wpa_supplicant did not answer {}", name)))??;
+
+        match reply.trim_end() {
+            "FAIL-BUSY" => Err(NetctlError::InvalidState(format!("wpa_supplicant is busy: {}", name))),
+            "UNKNOWN COMMAND" => Err(NetctlError::NotSupported(format!("wpa_supplicant command {}", name))),
+            failure if failure.starts_with("FAIL") => {
+                Err(NetctlError::ServiceError(format!("wpa_supplicant rejected {}: {}", name, failure)))
+            }
+            _ => Ok(reply),
+        }
+    }
+
+    /// Subscribe this socket to events
+    pub async fn attach(self) -> NetctlResult<WpaEvents> {
+        let reply = self.request("ATTACH").await?;
+        if reply.trim_end() != "OK" {
+            return Err(NetctlError::ServiceError(format!("wpa_supplicant refused ATTACH: {}", reply.trim_end())));
+        }
+        Ok(WpaEvents { ctrl: self, buf: vec![0u8; RECV_BUFFER_LEN] })
+    }
+}
+
+/// Whether a datagram is an event rather than a reply
+///
+/// Events start with their priority (`<3>`), or with `IFNAME=<name> ` on the
+/// global control interface.
+fn is_unsolicited(message: &[u8]) -> bool {
+    message.starts_with(b"<") || message.starts_with(b"IFNAME=")
+}
+
+/// A control socket attached to wpa_supplicant's events
+///
+/// The socket detaches when dropped.
+pub struct WpaEvents {
+    ctrl: WpaCtrl,
+    buf: Vec<u8>,
+}
+
+impl WpaEvents {
+    /// Wait for the next event
+    pub async fn next(&mut self) -> NetctlResult<WpaEvent> {
+        loop {
+            let len = self.ctrl.socket.recv(&mut self.buf).await?;
+            let message = String::from_utf8_lossy(&self.buf[..len]);
+            if let Some(event) = WpaEvent::parse(&message) {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// Send a command on the attached socket
+    pub async fn request(&self, command: &str) -> NetctlResult<String> {
+        self.ctrl.request(command).await
+    }
+
+    /// The events as a stream, ending at the first receive error
+    pub fn into_stream(self) -> impl Stream<Item = WpaEvent> {
+        futures::stream::unfold(self, |mut events| async move {
+            match events.next().await {
+                Ok(event) => Some((event, events)),
+                Err(e) => {
+                    debug!("wpa_supplicant event stream ended: {}", e);
+                    None
+                }
+            }
+        })
+    }
+}
+
+impl Drop for WpaEvents {
+    fn drop(&mut self) {
+        // Best effort: wpa_supplicant also drops monitors it cannot reach
+        let _ = self.ctrl.socket.try_send(b"DETACH");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_events() {
+        assert_eq!(
+            WpaEvent::parse("<3>CTRL-EVENT-CONNECTED - Connection to 02:00:00:00:01:00 completed [id=2 id_str=]"),
+            Some(WpaEvent::Connected { bssid: "02:00:00:00:01:00".to_string(), network_id: Some(2) }),
+        );
+        assert_eq!(
+            WpaEvent::parse("<3>CTRL-EVENT-DISCONNECTED bssid=02:00:00:00:01:00 reason=15 locally_generated=1"),
+            Some(WpaEvent::Disconnected {
+                bssid: Some("02:00:00:00:01:00".to_string()),
+                reason: 15,
+                locally_generated: true,
+            }),
+        );
+        assert_eq!(
+            WpaEvent::parse("<3>CTRL-EVENT-SSID-TEMP-DISABLED id=0 ssid=\"my \\\"net\\\"\" auth_failures=1 duration=10 reason=WRONG_KEY"),
+            Some(WpaEvent::SsidTempDisabled {
+                network_id: 0,
+                ssid: "my \"net\"".to_string(),
+                auth_failures: 1,
+                duration: 10,
+                reason: "WRONG_KEY".to_string(),
+            }),
+        );
+        assert_eq!(
+            WpaEvent::parse("IFNAME=wlan0 <3>CTRL-EVENT-ASSOC-REJECT bssid=02:00:00:00:01:00 status_code=17"),
+            Some(WpaEvent::AssocReject { bssid: Some("02:00:00:00:01:00".to_string()), status_code: 17 }),
+        );
+        assert_eq!(WpaEvent::parse("<3>CTRL-EVENT-SCAN-RESULTS "), Some(WpaEvent::ScanResults));
+        assert_eq!(
+            WpaEvent::parse("<2>WPS-AP-AVAILABLE"),
+            Some(WpaEvent::Other { priority: 2, message: "WPS-AP-AVAILABLE".to_string() }),
+        );
+        assert_eq!(WpaEvent::parse("OK\n"), None);
+        assert_eq!(fields("ssid=\"caf\\xc3\\xa9\" id=1").get("ssid").map(String::as_str), Some("café"));
+        assert_eq!(disconnect_reason(15), "4-way handshake timeout");
+    }
+
+    #[tokio::test]
+    async fn test_request_and_events() {
+        let dir = std::env::temp_dir().join(format!("netctl-wpa-ctrl-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("wlan0");
+        let _ = std::fs::remove_file(&path);
+        let server = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+
+        // A minimal wpa_supplicant: answers requests and, once attached,
+        // follows every reply with an event
+        let responder = std::thread::spawn(move || {
+            let mut buf = [0u8; 256];
+            let mut attached = None;
+            loop {
+                let (len, from) = server.recv_from(&mut buf).unwrap();
+                let reply: &[u8] = match &buf[..len] {
+                    b"PING" => b"PONG\n",
+                    b"ATTACH" => {
+                        attached = Some(from.clone());
+                        b"OK\n"
+                    }
+                    b"SCAN" => b"FAIL-BUSY\n",
+                    b"DETACH" => return,
+                    _ => b"UNKNOWN COMMAND\n",
+                };
+                server.send_to_addr(reply, &from).unwrap();
+                if let Some(attached) = &attached {
+                    let event = b"<3>CTRL-EVENT-SSID-TEMP-DISABLED id=0 ssid=\"lab\" auth_failures=1 duration=10 reason=WRONG_KEY";
+                    server.send_to_addr(event, attached).unwrap();
+                }
+            }
+        });
+
+        let ctrl = WpaCtrl::open(&path).unwrap();
+        assert_eq!(ctrl.request("PING").await.unwrap(), "PONG\n");
+        assert!(matches!(ctrl.request("SCAN").await, Err(NetctlError::InvalidState(_))));
+        assert!(matches!(ctrl.request("SET_NETWORK 0 psk \"secret\"").await,
+            Err(NetctlError::NotSupported(msg)) if !msg.contains("secret")));
+
+        let mut events = WpaCtrl::open(&path).unwrap().attach().await.unwrap();
+        assert!(matches!(events.next().await.unwrap(), WpaEvent::SsidTempDisabled { reason, .. } if reason == "WRONG_KEY"));
+        drop(events);
+        responder.join().unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert!(matches!(WpaCtrl::open(&path), Err(NetctlError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_request_skips_events() {
+        let dir = std::env::temp_dir().join(format!("netctl-wpa-ctrl-events-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("wlan0");
+        let _ = std::fs::remove_file(&path);
+        let server = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+
+        // Events of both control interfaces queued ahead of the reply
+        let responder = std::thread::spawn(move || {
+            let mut buf = [0u8; 256];
+            let (_, from) = server.recv_from(&mut buf).unwrap();
+            server.send_to_addr(b"IFNAME=wlan0 <3>CTRL-EVENT-SCAN-STARTED ", &from).unwrap();
+            server.send_to_addr(b"<3>CTRL-EVENT-SCAN-RESULTS ", &from).unwrap();
+            server.send_to_addr(b"PONG\n", &from).unwrap();
+        });
+
+        let ctrl = WpaCtrl::open(&path).unwrap();
+        assert_eq!(ctrl.request("PING").await.unwrap(), "PONG\n");
+        responder.join().unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(is_unsolicited(b"IFNAME=wlan0 <3>CTRL-EVENT-SCAN-RESULTS "));
+        assert!(!is_unsolicited(b"OK\n"));
+    }
+}
@@ -1,13 +1,17 @@
 //! WPA Supplicant control
 //!
 //! This module provides control over wpa_supplicant for WiFi connections.
-//! It uses wpa_cli for runtime control when wpa_supplicant is already running,
-//! and can start/stop wpa_supplicant as needed.
+//! Runtime commands go over wpa_supplicant's control socket (see
+//! [`wpa_ctrl`](crate::wpa_ctrl)), and connection attempts follow its events,
+//! so success, a wrong password or a lost connection is known immediately.
+//! wpa_supplicant can also be started and stopped as needed.
 
 use crate::error::{NetctlError, NetctlResult};
 use crate::validation;
+use crate::wpa_ctrl::{self, WpaCtrl, WpaEvent, WpaEvents};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::time::Duration;
 use tokio::fs;
 use tokio::process::Command;
 use tracing::{debug, info, warn};
@@ -15,6 +19,12 @@ use tracing::{debug, info, warn};
 /// Control interface directory for wpa_supplicant
 const CTRL_INTERFACE: &str = "/var/run/wpa_supplicant";
 
+/// How long a connection attempt may take
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a scan may take
+const SCAN_TIMEOUT: Duration = Duration::from_secs(15);
+
 /// WPA Supplicant connection state
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum WpaState {
@@ -106,12 +116,280 @@ impl WpaScanResult {
     }
 }
 
+/// EAP method of an 802.1X network
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EapMethod {
+    Tls,
+    Peap,
+    Ttls,
+    Pwd,
+}
+
+impl std::str::FromStr for EapMethod {
+    type Err = NetctlError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "tls" => Ok(EapMethod::Tls),
+            "peap" => Ok(EapMethod::Peap),
+            "ttls" => Ok(EapMethod::Ttls),
+            "pwd" => Ok(EapMethod::Pwd),
+            _ => Err(NetctlError::InvalidParameter(format!("Unsupported EAP method: {}", s))),
+        }
+    }
+}
+
+impl std::fmt::Display for EapMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EapMethod::Tls => write!(f, "TLS"),
+            EapMethod::Peap => write!(f, "PEAP"),
+            EapMethod::Ttls => write!(f, "TTLS"),
+            EapMethod::Pwd => write!(f, "PWD"),
+        }
+    }
+}
+
+/// 802.1X settings of a WPA-Enterprise network
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EapConfig {
+    /// Methods to try, in order
+    pub methods: Vec<EapMethod>,
+    pub identity: Option<String>,
+    /// Outer identity of tunneled methods (PEAP, TTLS)
+    pub anonymous_identity: Option<String>,
+    pub password: Option<String>,
+    /// Inner non-EAP authentication of PEAP or TTLS, e.g. "mschapv2"
+    pub phase2_auth: Option<String>,
+    /// Inner EAP method of TTLS, e.g. "mschapv2"
+    pub phase2_autheap: Option<String>,
+    /// CA certificate file used to verify the server
+    pub ca_cert: Option<String>,
+    pub client_cert: Option<String>,
+    pub private_key: Option<String>,
+    pub private_key_password: Option<String>,
+    /// The server certificate must be for this domain or a subdomain of it
+    pub domain_suffix_match: Option<String>,
+}
+
+/// Management frame protection (802.11w) of a network
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Pmf {
+    Disabled,
+    Optional,
+    Required,
+}
+
+impl Pmf {
+    /// Value of wpa_supplicant's `ieee80211w` network variable
+    pub fn ieee80211w(self) -> u8 {
+        match self {
+            Pmf::Disabled => 0,
+            Pmf::Optional => 1,
+            Pmf::Required => 2,
+        }
+    }
+}
+
+impl std::str::FromStr for Pmf {
+    type Err = NetctlError;
+
+    /// Parse a PMF setting; NetworkManager's numeric values 1-3 are accepted too
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "disable" | "disabled" | "1" => Ok(Pmf::Disabled),
+            "optional" | "2" => Ok(Pmf::Optional),
+            "required" | "3" => Ok(Pmf::Required),
+            _ => Err(NetctlError::InvalidParameter(format!("Invalid PMF setting: {}", s))),
+        }
+    }
+}
+
+/// Security of a wpa_supplicant network
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WpaSecurity {
+    #[default]
+    Open,
+    /// Opportunistic Wireless Encryption (Enhanced Open)
+    Owe,
+    /// WPA/WPA2 personal with a passphrase
+    Psk(String),
+    /// WPA3 personal with a password
+    Sae(String),
+    /// WPA2/WPA3 transition mode: SAE where the AP offers it, PSK otherwise
+    PskSae(String),
+    /// WPA2/WPA3 enterprise; Suite B 192-bit mode when `suite_b_192` is set
+    Eap { config: Box<EapConfig>, suite_b_192: bool },
+}
+
+impl WpaSecurity {
+    /// PMF used when the network does not set it
+    fn default_pmf(&self) -> Option<Pmf> {
+        match self {
+            WpaSecurity::Open | WpaSecurity::Psk(_) => None,
+            WpaSecurity::Owe | WpaSecurity::Sae(_) => Some(Pmf::Required),
+            WpaSecurity::Eap { suite_b_192: true, .. } => Some(Pmf::Required),
+            WpaSecurity::PskSae(_) | WpaSecurity::Eap { .. } => Some(Pmf::Optional),
+        }
+    }
+
+    /// Whether the key management cannot work without PMF
+    fn requires_pmf(&self) -> bool {
+        matches!(self, WpaSecurity::Owe | WpaSecurity::Sae(_) | WpaSecurity::Eap { suite_b_192: true, .. })
+    }
+}
+
+/// A network to configure in wpa_supplicant
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WpaNetwork {
+    pub ssid: String,
+    pub security: WpaSecurity,
+    /// Probe for the SSID, for networks that do not broadcast it
+    pub hidden: bool,
+    /// Management frame protection; the default depends on the security
+    pub pmf: Option<Pmf>,
+    /// Only associate with this access point
+    pub bssid: Option<String>,
+    /// Only scan and associate on these frequencies (MHz)
+    pub frequencies: Vec<u32>,
+}
+
+impl WpaNetwork {
+    /// Check that wpa_supplicant can be given this network
+    pub fn validate(&self) -> NetctlResult<()> {
+        validation::validate_ssid(&self.ssid)?;
+        match &self.security {
+            WpaSecurity::Psk(psk) | WpaSecurity::PskSae(psk) => validation::validate_wifi_password(psk)?,
+            WpaSecurity::Sae(password) if password.is_empty() => {
+                return Err(NetctlError::InvalidParameter("SAE password cannot be empty".to_string()));
+            }
+            _ => {}
+        }
+        if self.security.requires_pmf() && self.pmf.is_some_and(|pmf| pmf != Pmf::Required) {
+            return Err(NetctlError::InvalidParameter(format!(
+                "Network '{}' requires management frame protection", self.ssid
+            )));
+        }
+        if let Some(bssid) = &self.bssid {
+            validation::validate_mac_address(bssid)?;
+        }
+        if let Some(freq) = self.frequencies.iter().find(|&&freq| crate::wifi::frequency_to_channel(freq).is_none()) {
+            return Err(NetctlError::InvalidParameter(format!("Invalid WiFi frequency: {} MHz", freq)));
+        }
+        Ok(())
+    }
+
+    /// Network variables in the order they are set; values are formatted
+    /// for wpa_supplicant, strings quoted or hex encoded
+    pub fn settings(&self) -> Vec<(&'static str, String)> {
+        let mut settings = vec![("ssid", quote(&self.ssid))];
+        if self.hidden {
+            settings.push(("scan_ssid", "1".to_string()));
+        }
+        if let Some(bssid) = &self.bssid {
+            settings.push(("bssid", bssid.to_ascii_lowercase()));
+        }
+        if !self.frequencies.is_empty() {
+            let freqs: Vec<String> = self.frequencies.iter().map(ToString::to_string).collect();
+            // Restricting the scan as well saves scanning channels that cannot be used
+            settings.push(("scan_freq", freqs.join(" ")));
+            settings.push(("freq_list", freqs.join(" ")));
+        }
+
+        match &self.security {
+            WpaSecurity::Open => settings.push(("key_mgmt", "NONE".to_string())),
+            WpaSecurity::Owe => settings.push(("key_mgmt", "OWE".to_string())),
+            WpaSecurity::Psk(psk) => {
+                // A passphrase is always quoted, validation keeps it printable;
+                // hex would mean a raw PSK
+                settings.push(("psk", quote(psk)));
+                settings.push(("key_mgmt", "WPA-PSK".to_string()));
+            }
+            WpaSecurity::Sae(password) => {
+                settings.push(("sae_password", quote(password)));
+                settings.push(("key_mgmt", "SAE".to_string()));
+            }
+            WpaSecurity::PskSae(psk) => {
+                // SAE falls back to the passphrase when no sae_password is set
+                settings.push(("psk", quote(psk)));
+                settings.push(("key_mgmt", "WPA-PSK SAE".to_string()));
+            }
+            WpaSecurity::Eap { config, suite_b_192 } => {
+                if *suite_b_192 {
+                    settings.push(("key_mgmt", "WPA-EAP-SUITE-B-192".to_string()));
+                    settings.push(("pairwise", "GCMP-256".to_string()));
+                    settings.push(("group", "GCMP-256".to_string()));
+                } else {
+                    // WPA3-Enterprise uses SHA-256 key derivation with PMF
+                    settings.push(("key_mgmt", "WPA-EAP WPA-EAP-SHA256".to_string()));
+                }
+                let methods: Vec<String> = config.methods.iter().map(ToString::to_string).collect();
+                settings.push(("eap", methods.join(" ")));
+
+                let strings = [
+                    ("identity", &config.identity),
+                    ("anonymous_identity", &config.anonymous_identity),
+                    ("password", &config.password),
+                    ("ca_cert", &config.ca_cert),
+                    ("client_cert", &config.client_cert),
+                    ("private_key", &config.private_key),
+                    ("private_key_passwd", &config.private_key_password),
+                    ("domain_suffix_match", &config.domain_suffix_match),
+                ];
+                for (name, value) in strings {
+                    if let Some(value) = value {
+                        settings.push((name, quote(value)));
+                    }
+                }
+
+                let mut phase2 = Vec::new();
+                if let Some(auth) = &config.phase2_auth {
+                    phase2.push(format!("auth={}", auth.to_ascii_uppercase()));
+                }
+                if let Some(autheap) = &config.phase2_autheap {
+                    phase2.push(format!("autheap={}", autheap.to_ascii_uppercase()));
+                }
+                if !phase2.is_empty() {
+                    settings.push(("phase2", format!("\"{}\"", phase2.join(" "))));
+                }
+            }
+        }
+
+        if let Some(pmf) = self.pmf.or_else(|| self.security.default_pmf()) {
+            settings.push(("ieee80211w", pmf.ieee80211w().to_string()));
+        }
+        settings
+    }
+
+    /// Network block for a wpa_supplicant configuration file
+    pub fn config_block(&self) -> String {
+        let mut config = String::from("network={\n");
+        for (name, value) in self.settings() {
+            config.push_str(&format!("    {}={}\n", name, value));
+        }
+        config.push_str("}\n");
+        config
+    }
+}
+
+/// Quote a string value, or hex encode it if quoting cannot represent it
+///
+/// wpa_supplicant ends a quoted value at its last `"` and does not unescape
+/// it, so quotes and backslashes inside are taken literally. Only control
+/// characters, which would split the config line or control command, need
+/// hex.
+fn quote(value: &str) -> String {
+    if value.chars().any(char::is_control) {
+        value.bytes().map(|b| format!("{:02x}", b)).collect()
+    } else {
+        format!("\"{}\"", value)
+    }
+}
+
 /// WPA Supplicant controller
 pub struct WpaSupplicantController {
     /// Path to wpa_supplicant binary
     wpa_bin: PathBuf,
-    /// Path to wpa_cli binary
-    wpa_cli_bin: PathBuf,
     /// Configuration directory
     config_dir: PathBuf,
     /// Control interface directory
@@ -123,7 +401,6 @@ impl WpaSupplicantController {
     pub fn new() -> Self {
         Self {
             wpa_bin: PathBuf::from("/usr/sbin/wpa_supplicant"),
-            wpa_cli_bin: PathBuf::from("/usr/sbin/wpa_cli"),
             config_dir: PathBuf::from("/etc/wpa_supplicant"),
             ctrl_interface: PathBuf::from(CTRL_INTERFACE),
         }
@@ -134,11 +411,6 @@ impl WpaSupplicantController {
         tokio::fs::metadata(&self.wpa_bin).await.is_ok()
     }
 
-    /// Check if wpa_cli is installed
-    pub async fn is_cli_installed(&self) -> bool {
-        tokio::fs::metadata(&self.wpa_cli_bin).await.is_ok()
-    }
-
     /// Check if wpa_supplicant is running on an interface
     pub async fn is_running(&self, interface: &str) -> bool {
         if validation::validate_interface_name(interface).is_err() {
@@ -149,10 +421,7 @@ impl WpaSupplicantController {
         let socket_path = self.ctrl_interface.join(interface);
         if tokio::fs::metadata(&socket_path).await.is_ok() {
             // Verify it's responsive
-            match self.wpa_cli(interface, &["status"]).await {
-                Ok(_) => true,
-                Err(_) => false,
-            }
+            matches!(self.request(interface, "PING").await, Ok(reply) if reply.trim_end() == "PONG")
         } else {
             // Fallback to pgrep
             let output = Command::new("pgrep")
@@ -250,8 +519,8 @@ impl WpaSupplicantController {
 
         info!("Stopping wpa_supplicant on {}", interface);
 
-        // Try graceful termination via wpa_cli first
-        let _ = self.wpa_cli(interface, &["terminate"]).await;
+        // Try graceful termination over the control socket first
+        let _ = self.request(interface, "TERMINATE").await;
 
         // Wait a moment
         tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
@@ -273,7 +542,7 @@ impl WpaSupplicantController {
     pub async fn status(&self, interface: &str) -> NetctlResult<WpaStatus> {
         validation::validate_interface_name(interface)?;
 
-        let output = self.wpa_cli(interface, &["status"]).await?;
+        let output = self.request(interface, "STATUS").await?;
 
         let mut status = WpaStatus {
             state: WpaState::Unknown,
@@ -314,15 +583,45 @@ impl WpaSupplicantController {
             self.start(interface).await?;
         }
 
-        self.wpa_cli(interface, &["scan"]).await?;
-        Ok(())
+        match self.request(interface, "SCAN").await {
+            // A scan is already running; its results will do
+            Ok(_) | Err(NetctlError::InvalidState(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Trigger a scan and return the results once wpa_supplicant has them
+    pub async fn scan_and_wait(&self, interface: &str) -> NetctlResult<Vec<WpaScanResult>> {
+        validation::validate_interface_name(interface)?;
+
+        if !self.is_running(interface).await {
+            self.start(interface).await?;
+        }
+
+        let mut events = self.events(interface).await?;
+        self.scan(interface).await?;
+        tokio::time::timeout(SCAN_TIMEOUT, async {
+            loop {
+                match events.next().await? {
+                    WpaEvent::ScanResults => return Ok(()),
+                    WpaEvent::ScanFailed => {
+                        return Err(NetctlError::ServiceError(format!("Scan on {} failed", interface)));
+                    }
+                    _ => {}
+                }
+            }
+        })
+        .await
+        .map_err(|_| NetctlError::Timeout(format!("Scan on {} did not finish", interface)))??;
+
+        self.scan_results(interface).await
     }
 
     /// Get scan results
     pub async fn scan_results(&self, interface: &str) -> NetctlResult<Vec<WpaScanResult>> {
         validation::validate_interface_name(interface)?;
 
-        let output = self.wpa_cli(interface, &["scan_results"]).await?;
+        let output = self.request(interface, "SCAN_RESULTS").await?;
 
         let mut results = Vec::new();
 
@@ -343,14 +642,30 @@ impl WpaSupplicantController {
         Ok(results)
     }
 
-    /// Connect to a WiFi network
+    /// Connect to a WPA-PSK or open WiFi network
     pub async fn connect(
         &self,
         interface: &str,
         ssid: &str,
         psk: Option<&str>,
     ) -> NetctlResult<()> {
+        let network = WpaNetwork {
+            ssid: ssid.to_string(),
+            security: psk.map_or(WpaSecurity::Open, |psk| WpaSecurity::Psk(psk.to_string())),
+            ..Default::default()
+        };
+        self.connect_network(interface, &network).await.map(drop)
+    }
+
+    /// Connect to a WiFi network
+    ///
+    /// Returns wpa_supplicant's id of the added network once it reports the
+    /// connection. Rejected credentials fail with `AuthenticationFailed` as
+    /// soon as wpa_supplicant gives up on the network; other failures end in
+    /// `ConnectionFailed`.
+    pub async fn connect_network(&self, interface: &str, network: &WpaNetwork) -> NetctlResult<String> {
         validation::validate_interface_name(interface)?;
+        network.validate()?;
 
         if !self.is_installed().await {
             return Err(NetctlError::NotFound(
@@ -358,6 +673,7 @@ impl WpaSupplicantController {
             ));
         }
 
+        let ssid = network.ssid.as_str();
         info!("Connecting to WiFi network '{}' on {}", ssid, interface);
 
         // Ensure wpa_supplicant is running
@@ -365,94 +681,106 @@ impl WpaSupplicantController {
             self.start(interface).await?;
         }
 
+        // Subscribe before selecting the network so no event is missed
+        let mut events = self.events(interface).await?;
+
         // Add network
-        let output = self.wpa_cli(interface, &["add_network"]).await?;
+        let output = self.request(interface, "ADD_NETWORK").await?;
         let network_id = output.trim().to_string();
 
         debug!("Added network with id: {}", network_id);
 
-        // Set SSID
-        self.wpa_cli(
-            interface,
-            &["set_network", &network_id, "ssid", &format!("\"{}\"", ssid)],
-        )
-        .await?;
-
-        // Set PSK or open network
-        if let Some(password) = psk {
-            self.wpa_cli(
-                interface,
-                &[
-                    "set_network",
-                    &network_id,
-                    "psk",
-                    &format!("\"{}\"", password),
-                ],
-            )
-            .await?;
-        } else {
-            self.wpa_cli(
-                interface,
-                &["set_network", &network_id, "key_mgmt", "NONE"],
-            )
-            .await?;
+        let result = self.configure_and_select(interface, &network_id, network).await;
+        let result = match result {
+            Ok(()) => self.wait_for_connection(&mut events, &network_id, ssid).await,
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(()) => {
+                info!("Successfully connected to '{}'", ssid);
+                // Save configuration
+                let _ = self.request(interface, "SAVE_CONFIG").await;
+                Ok(network_id)
+            }
+            Err(e) => {
+                // Connection failed, clean up
+                let _ = self.request(interface, &format!("REMOVE_NETWORK {}", network_id)).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Set up a newly added network and switch to it
+    async fn configure_and_select(
+        &self,
+        interface: &str,
+        network_id: &str,
+        network: &WpaNetwork,
+    ) -> NetctlResult<()> {
+        for (name, value) in network.settings() {
+            self.request(interface, &format!("SET_NETWORK {} {} {}", network_id, name, value)).await?;
         }
 
         // Enable network
-        self.wpa_cli(interface, &["enable_network", &network_id])
-            .await?;
+        self.request(interface, &format!("ENABLE_NETWORK {}", network_id)).await?;
 
         // Select network (disconnect from any current and connect to this one)
-        self.wpa_cli(interface, &["select_network", &network_id])
-            .await?;
-
-        // Save configuration
-        let _ = self.wpa_cli(interface, &["save_config"]).await;
-
-        // Wait for connection with timeout
-        let timeout = tokio::time::Duration::from_secs(30);
-        let start = tokio::time::Instant::now();
-
-        while start.elapsed() < timeout {
-            tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-
-            match self.status(interface).await {
-                Ok(status) => {
-                    debug!("Connection state: {:?}", status.state);
-                    match status.state {
-                        WpaState::Completed => {
-                            info!("Successfully connected to '{}'", ssid);
-                            return Ok(());
-                        }
-                        WpaState::Disconnected => {
-                            // Check if we were trying to connect
-                            if start.elapsed() > tokio::time::Duration::from_secs(5) {
-                                // Might be auth failure
-                                break;
-                            }
-                        }
-                        _ => {
-                            // Still trying to connect
-                        }
+        self.request(interface, &format!("SELECT_NETWORK {}", network_id)).await?;
+        Ok(())
+    }
+
+    /// Follow the events of a connection attempt to `network_id` until it
+    /// succeeds, is rejected or times out
+    async fn wait_for_connection(&self, events: &mut WpaEvents, network_id: &str, ssid: &str) -> NetctlResult<()> {
+        let id: u32 = network_id.parse()
+            .map_err(|_| NetctlError::ParseError(format!("Invalid network id from wpa_supplicant: {}", network_id)))?;
+        // The most specific reason seen so far, reported on timeout
+        let mut last_failure = None;
+
+        let outcome = tokio::time::timeout(CONNECT_TIMEOUT, async {
+            loop {
+                let event = events.next().await?;
+                debug!("wpa_supplicant event while connecting to '{}': {:?}", ssid, event);
+                match event {
+                    WpaEvent::Connected { network_id, .. } if network_id.is_none_or(|n| n == id) => return Ok(()),
+                    WpaEvent::SsidTempDisabled { network_id, reason, .. } if network_id == id => {
+                        return Err(match reason.as_str() {
+                            "WRONG_KEY" => NetctlError::AuthenticationFailed(format!("Wrong password for '{}'", ssid)),
+                            "AUTH_FAILED" => NetctlError::AuthenticationFailed(format!("Authentication with '{}' failed", ssid)),
+                            _ => NetctlError::ConnectionFailed {
+                                reason: format!("'{}' was temporarily disabled: {}", ssid, reason),
+                            },
+                        });
                     }
-                }
-                Err(e) => {
-                    warn!("Failed to get status: {}", e);
+                    WpaEvent::EapFailure => {
+                        return Err(NetctlError::AuthenticationFailed(format!("EAP authentication with '{}' failed", ssid)));
+                    }
+                    WpaEvent::Terminating => {
+                        return Err(NetctlError::ConnectionFailed { reason: "wpa_supplicant exited".to_string() });
+                    }
+                    WpaEvent::Disconnected { reason, .. } => {
+                        last_failure = Some(format!("disconnected: {}", wpa_ctrl::disconnect_reason(reason)));
+                    }
+                    WpaEvent::AssocReject { status_code, .. } => {
+                        last_failure = Some(format!("association rejected with status {}", status_code));
+                    }
+                    WpaEvent::NetworkNotFound => last_failure = Some("network not found".to_string()),
+                    _ => {}
                 }
             }
-        }
-
-        // Connection failed, clean up
-        let _ = self
-            .wpa_cli(interface, &["remove_network", &network_id])
-            .await;
-
-        Err(NetctlError::ConnectionFailed {
-            reason: format!(
-                "Failed to connect to '{}' within timeout. Check password and signal.",
-                ssid
-            ),
         })
+        .await;
+
+        match outcome {
+            Ok(result) => result,
+            Err(_) => Err(NetctlError::ConnectionFailed {
+                reason: match last_failure {
+                    Some(failure) => format!("Failed to connect to '{}' within timeout: {}", ssid, failure),
+                    None => format!("Failed to connect to '{}' within timeout. Check signal.", ssid),
+                },
+            }),
+        }
     }
 
     /// Disconnect from current network
@@ -466,7 +794,7 @@ impl WpaSupplicantController {
 
         info!("Disconnecting WiFi on {}", interface);
 
-        self.wpa_cli(interface, &["disconnect"]).await?;
+        self.request(interface, "DISCONNECT").await?;
 
         Ok(())
     }
@@ -475,7 +803,7 @@ impl WpaSupplicantController {
     pub async fn list_networks(&self, interface: &str) -> NetctlResult<Vec<(String, String, String)>> {
         validation::validate_interface_name(interface)?;
 
-        let output = self.wpa_cli(interface, &["list_networks"]).await?;
+        let output = self.request(interface, "LIST_NETWORKS").await?;
 
         let mut networks = Vec::new();
 
@@ -498,9 +826,8 @@ impl WpaSupplicantController {
     pub async fn remove_network(&self, interface: &str, network_id: &str) -> NetctlResult<()> {
         validation::validate_interface_name(interface)?;
 
-        self.wpa_cli(interface, &["remove_network", network_id])
-            .await?;
-        let _ = self.wpa_cli(interface, &["save_config"]).await;
+        self.request(interface, &format!("REMOVE_NETWORK {}", network_id)).await?;
+        let _ = self.request(interface, "SAVE_CONFIG").await;
 
         Ok(())
     }
@@ -509,7 +836,7 @@ impl WpaSupplicantController {
     pub async fn reconnect(&self, interface: &str) -> NetctlResult<()> {
         validation::validate_interface_name(interface)?;
 
-        self.wpa_cli(interface, &["reconnect"]).await?;
+        self.request(interface, "RECONNECT").await?;
         Ok(())
     }
 
@@ -517,7 +844,7 @@ impl WpaSupplicantController {
     pub async fn reassociate(&self, interface: &str) -> NetctlResult<()> {
         validation::validate_interface_name(interface)?;
 
-        self.wpa_cli(interface, &["reassociate"]).await?;
+        self.request(interface, "REASSOCIATE").await?;
         Ok(())
     }
 
@@ -525,7 +852,7 @@ impl WpaSupplicantController {
     pub async fn signal_poll(&self, interface: &str) -> NetctlResult<i32> {
         validation::validate_interface_name(interface)?;
 
-        let output = self.wpa_cli(interface, &["signal_poll"]).await?;
+        let output = self.request(interface, "SIGNAL_POLL").await?;
 
         for line in output.lines() {
             if let Some(rssi) = line.strip_prefix("RSSI=") {
@@ -540,42 +867,15 @@ impl WpaSupplicantController {
 
     // === Helper functions ===
 
-    /// Run wpa_cli command
-    async fn wpa_cli(&self, interface: &str, args: &[&str]) -> NetctlResult<String> {
-        let mut cmd = Command::new(&self.wpa_cli_bin);
-        cmd.arg("-i").arg(interface);
-        cmd.args(args);
-
-        let cmd_str = format!("wpa_cli -i {} {}", interface, args.join(" "));
-        debug!("Running: {}", cmd_str);
-
-        let output = cmd.output().await.map_err(|e| NetctlError::CommandFailed {
-            cmd: cmd_str.clone(),
-            code: None,
-            stderr: e.to_string(),
-        })?;
-
-        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-
-        if !output.status.success() {
-            return Err(NetctlError::CommandFailed {
-                cmd: cmd_str,
-                code: output.status.code(),
-                stderr,
-            });
-        }
-
-        // wpa_cli returns "FAIL" on errors even with exit code 0
-        if stdout.trim() == "FAIL" {
-            return Err(NetctlError::CommandFailed {
-                cmd: cmd_str,
-                code: None,
-                stderr: "wpa_cli returned FAIL".to_string(),
-            });
-        }
+    /// Subscribe to the events of wpa_supplicant on an interface
+    pub async fn events(&self, interface: &str) -> NetctlResult<WpaEvents> {
+        validation::validate_interface_name(interface)?;
+        WpaCtrl::open(self.ctrl_interface.join(interface))?.attach().await
+    }
 
-        Ok(stdout)
+    /// Send a command to the control socket of an interface
+    async fn request(&self, interface: &str, command: &str) -> NetctlResult<String> {
+        WpaCtrl::open(self.ctrl_interface.join(interface))?.request(command).await
     }
 
     /// Generate base wpa_supplicant configuration
@@ -589,29 +889,9 @@ impl WpaSupplicantController {
     }
 
     /// Generate network block for config file
-    pub fn generate_network_config(
-        &self,
-        ssid: &str,
-        psk: Option<&str>,
-        hidden: bool,
-    ) -> String {
-        let mut config = String::new();
-        config.push_str("network={\n");
-        config.push_str(&format!("    ssid=\"{}\"\n", ssid));
-
-        if hidden {
-            config.push_str("    scan_ssid=1\n");
-        }
-
-        if let Some(password) = psk {
-            config.push_str(&format!("    psk=\"{}\"\n", password));
-            config.push_str("    key_mgmt=WPA-PSK\n");
-        } else {
-            config.push_str("    key_mgmt=NONE\n");
-        }
-
-        config.push_str("}\n");
-        config
+    pub fn generate_network_config(&self, network: &WpaNetwork) -> NetctlResult<String> {
+        network.validate()?;
+        Ok(network.config_block())
     }
 }
 
@@ -666,19 +946,131 @@ mod tests {
     #[test]
     fn test_generate_network_config() {
         let controller = WpaSupplicantController::new();
+        let psk = |ssid: &str, psk: &str, hidden: bool| WpaNetwork {
+            ssid: ssid.to_string(),
+            security: WpaSecurity::Psk(psk.to_string()),
+            hidden,
+            ..Default::default()
+        };
 
-        let config = controller.generate_network_config("TestSSID", Some("password123"), false);
+        let config = controller.generate_network_config(&psk("TestSSID", "password123", false)).unwrap();
         assert!(config.contains("ssid=\"TestSSID\""));
         assert!(config.contains("psk=\"password123\""));
         assert!(config.contains("key_mgmt=WPA-PSK"));
         assert!(!config.contains("scan_ssid=1"));
+        assert!(!config.contains("ieee80211w"));
 
         let config_hidden =
-            controller.generate_network_config("HiddenNetwork", Some("secret"), true);
+            controller.generate_network_config(&psk("HiddenNetwork", "secret123", true)).unwrap();
         assert!(config_hidden.contains("scan_ssid=1"));
+        assert!(controller.generate_network_config(&psk("HiddenNetwork", "secret", true)).is_err());
 
-        let config_open = controller.generate_network_config("OpenNetwork", None, false);
+        let open = WpaNetwork { ssid: "OpenNetwork".to_string(), ..Default::default() };
+        let config_open = controller.generate_network_config(&open).unwrap();
         assert!(config_open.contains("key_mgmt=NONE"));
         assert!(!config_open.contains("psk="));
     }
+
+    #[test]
+    fn test_personal_network_settings() {
+        let controller = WpaSupplicantController::new();
+
+        let sae = WpaNetwork {
+            ssid: "home".to_string(),
+            security: WpaSecurity::Sae("short".to_string()),
+            bssid: Some("02:00:00:AA:BB:CC".to_string()),
+            frequencies: vec![5180],
+            ..Default::default()
+        };
+        let config = controller.generate_network_config(&sae).unwrap();
+        assert!(config.contains("    bssid=02:00:00:aa:bb:cc\n"));
+        assert!(config.contains("    freq_list=5180\n"));
+        assert!(config.contains("    scan_freq=5180\n"));
+        assert!(config.contains("    sae_password=\"short\"\n"));
+        assert!(config.contains("    key_mgmt=SAE\n"));
+        assert!(config.contains("    ieee80211w=2\n"));
+        assert!(!config.contains("psk="));
+
+        // SAE cannot work without management frame protection
+        let unprotected = WpaNetwork { pmf: Some(Pmf::Optional), ..sae.clone() };
+        assert!(controller.generate_network_config(&unprotected).is_err());
+        let bad_bssid = WpaNetwork { bssid: Some("02:00:00".to_string()), ..sae.clone() };
+        assert!(controller.generate_network_config(&bad_bssid).is_err());
+        let bad_freq = WpaNetwork { frequencies: vec![1000], ..sae.clone() };
+        assert!(controller.generate_network_config(&bad_freq).is_err());
+
+        let transition = WpaNetwork {
+            ssid: "home".to_string(),
+            security: WpaSecurity::PskSae("password123".to_string()),
+            ..Default::default()
+        };
+        let config = controller.generate_network_config(&transition).unwrap();
+        assert!(config.contains("    psk=\"password123\"\n"));
+        assert!(config.contains("    key_mgmt=WPA-PSK SAE\n"));
+
+        // Quotes and backslashes stay literal; control characters need hex
+        let quoted = WpaNetwork { security: WpaSecurity::PskSae("pass\"wo\\rd".to_string()), ..transition.clone() };
+        let config = controller.generate_network_config(&quoted).unwrap();
+        assert!(config.contains("    psk=\"pass\"wo\\rd\"\n"));
+        assert_eq!(quote("a\nb"), "610a62");
+        assert!(config.contains("    ieee80211w=1\n"));
+
+        let owe = WpaNetwork { ssid: "cafe".to_string(), security: WpaSecurity::Owe, ..Default::default() };
+        let config = controller.generate_network_config(&owe).unwrap();
+        assert!(config.contains("    key_mgmt=OWE\n"));
+        assert!(config.contains("    ieee80211w=2\n"));
+
+        let forced = WpaNetwork { pmf: Some(Pmf::Required), ..transition };
+        assert!(forced.settings().contains(&("ieee80211w", "2".to_string())));
+
+        assert_eq!("optional".parse::<Pmf>().unwrap(), Pmf::Optional);
+        assert_eq!("3".parse::<Pmf>().unwrap(), Pmf::Required);
+        assert!("sometimes".parse::<Pmf>().is_err());
+    }
+
+    #[test]
+    fn test_eap_network_settings() {
+        let network = WpaNetwork {
+            ssid: "office".to_string(),
+            security: WpaSecurity::Eap {
+                config: Box::new(EapConfig {
+                    methods: vec![EapMethod::Ttls],
+                    identity: Some("alice".to_string()),
+                    anonymous_identity: Some("anonymous@example.com".to_string()),
+                    password: Some("pa\"ss".to_string()),
+                    phase2_autheap: Some("mschapv2".to_string()),
+                    ca_cert: Some("/etc/ssl/ca.pem".to_string()),
+                    domain_suffix_match: Some("example.com".to_string()),
+                    ..Default::default()
+                }),
+                suite_b_192: false,
+            },
+            hidden: true,
+            ..Default::default()
+        };
+        let settings = network.settings();
+        let get = |name: &str| settings.iter().find(|(n, _)| *n == name).map(|(_, v)| v.as_str());
+        assert_eq!(get("key_mgmt"), Some("WPA-EAP WPA-EAP-SHA256"));
+        assert_eq!(get("ieee80211w"), Some("1"));
+        assert_eq!(get("eap"), Some("TTLS"));
+        assert_eq!(get("identity"), Some("\"alice\""));
+        // The value ends at the last quote, so inner quotes need no escaping
+        assert_eq!(get("password"), Some("\"pa\"ss\""));
+        assert_eq!(get("phase2"), Some("\"autheap=MSCHAPV2\""));
+        assert_eq!(get("domain_suffix_match"), Some("\"example.com\""));
+        assert_eq!(get("scan_ssid"), Some("1"));
+        assert_eq!(get("private_key"), None);
+
+        let block = network.config_block();
+        assert!(block.starts_with("network={\n    ssid=\"office\"\n"));
+        assert!(block.contains("    ca_cert=\"/etc/ssl/ca.pem\"\n"));
+
+        let suite_b = WpaNetwork {
+            security: WpaSecurity::Eap { config: Box::new(EapConfig { methods: vec![EapMethod::Tls], ..Default::default() }), suite_b_192: true },
+            ..network
+        };
+        let settings = suite_b.settings();
+        assert!(settings.contains(&("key_mgmt", "WPA-EAP-SUITE-B-192".to_string())));
+        assert!(settings.contains(&("ieee80211w", "2".to_string())));
+    }
 }
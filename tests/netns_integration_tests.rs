@@ -0,0 +1,1237 @@
+//! Network Namespace Integration Tests
+//!
+//! Each test creates a throwaway network namespace with veth pairs inside it,
+//! binds the controllers to that namespace and removes it afterwards, so the
+//! host network configuration is never touched.
+//!
+//! Requires root (CAP_SYS_ADMIN + CAP_NET_ADMIN); tests are skipped otherwise.
+
+use libnetctl::{InterfaceController, NetNamespace, NetworkMonitor, NetworkEvent};
+use libnetctl::{Route, RouteScope, RouteType, RoutingController, RoutingRule, RoutingRuleSection, RuleAction};
+use std::process::Command;
+use std::time::Duration;
+
+/// Check if running as root
+fn is_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// A named namespace that is deleted when dropped
+struct TestNamespace {
+    name: String,
+    ns: NetNamespace,
+}
+
+impl TestNamespace {
+    /// Create a namespace, or `None` if the environment does not allow it
+    async fn create(tag: &str) -> Option<Self> {
+        if !is_root() {
+            println!("SKIP: Requires root privileges");
+            return None;
+        }
+
+        let name = format!("nctest-{}-{}", tag, std::process::id());
+        match NetNamespace::create(&name).await {
+            Ok(ns) => Some(Self { name, ns }),
+            Err(e) => {
+                println!("SKIP: Cannot create network namespace: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Run `ip -n <ns> ...` and report success
+    fn ip(&self, args: &[&str]) -> bool {
+        Command::new("ip")
+            .arg("-n")
+            .arg(&self.name)
+            .args(args)
+            .output()
+            .map(|o| o.status.success())
+            .unwrap_or(false)
+    }
+
+    /// Create a veth pair inside the namespace
+    fn add_veth(&self, a: &str, b: &str) -> bool {
+        self.ip(&["link", "add", a, "type", "veth", "peer", "name", b])
+    }
+}
+
+impl Drop for TestNamespace {
+    fn drop(&mut self) {
+        let _ = Command::new("ip").args(["netns", "delete", &self.name]).output();
+    }
+}
+
+#[tokio::test]
+async fn test_interface_controller_in_namespace() {
+    let Some(test_ns) = TestNamespace::create("iface").await else { return };
+    if !test_ns.add_veth("nct0", "nct1") {
+        println!("SKIP: Cannot create veth pair");
+        return;
+    }
+
+    let ctrl = InterfaceController::with_namespace(test_ns.ns.clone());
+    let interfaces = ctrl.list().await.expect("list interfaces in namespace");
+    assert_eq!(interfaces, vec!["lo", "nct0", "nct1"]);
+
+    // The host namespace must not see them
+    let host = InterfaceController::new().list().await.expect("list host interfaces");
+    assert!(!host.contains(&"nct0".to_string()));
+
+    ctrl.up("nct0").await.expect("bring nct0 up");
+    ctrl.up("nct1").await.expect("bring nct1 up");
+    ctrl.set_mtu("nct0", 1400).await.expect("set MTU");
+    ctrl.set_mac("nct0", "02:00:00:00:00:01").await.expect("set MAC");
+    ctrl.set_ip("nct0", "10.200.0.1", 24).await.expect("add address");
+
+    let info = ctrl.get_info("nct0").await.expect("get info");
+    assert_eq!(info.mtu, Some(1400));
+    assert_eq!(info.mac_address.as_deref(), Some("02:00:00:00:00:01"));
+    assert!(info.flags.contains(&"UP".to_string()));
+    assert!(info.addresses.iter().any(|a| a.address == "10.200.0.1" && a.prefix_len == 24 && a.family == "inet"));
+
+    // Adding the same address twice is reported as a structured error
+    let dup = ctrl.set_ip("nct0", "10.200.0.1", 24).await;
+    assert!(matches!(dup, Err(libnetctl::NetctlError::AlreadyExists(_))));
+
+    ctrl.del_ip("nct0", "10.200.0.1", 24).await.expect("delete address");
+    let info = ctrl.get_info("nct0").await.expect("get info");
+    assert!(!info.addresses.iter().any(|a| a.address == "10.200.0.1"));
+
+    assert!(matches!(
+        ctrl.get_info("nosuchdev0").await,
+        Err(libnetctl::NetctlError::InterfaceNotFound(_))
+    ));
+
+    ctrl.rename("nct1", "nctpeer").await.expect("rename");
+    ctrl.delete("nct0").await.expect("delete veth");
+    assert_eq!(ctrl.list().await.unwrap(), vec!["lo"]);
+}
+
+#[tokio::test]
+async fn test_move_link_between_namespaces() {
+    let Some(ns_a) = TestNamespace::create("mva").await else { return };
+    let Some(ns_b) = TestNamespace::create("mvb").await else { return };
+    if !ns_a.add_veth("mv0", "mv1") {
+        println!("SKIP: Cannot create veth pair");
+        return;
+    }
+
+    let ctrl_a = InterfaceController::with_namespace(ns_a.ns.clone());
+    let ctrl_b = InterfaceController::with_namespace(ns_b.ns.clone());
+
+    ctrl_a.move_to_namespace("mv1", &ns_b.ns).await.expect("move mv1");
+
+    assert_eq!(ctrl_a.list().await.unwrap(), vec!["lo", "mv0"]);
+    assert_eq!(ctrl_b.list().await.unwrap(), vec!["lo", "mv1"]);
+
+    // Addresses configured across the pair can reach each other
+    ctrl_a.set_ip("mv0", "10.201.0.1", 30).await.unwrap();
+    ctrl_b.set_ip("mv1", "10.201.0.2", 30).await.unwrap();
+    ctrl_a.up("mv0").await.unwrap();
+    ctrl_b.up("mv1").await.unwrap();
+
+    let ping = ns_a.ip(&["route", "get", "10.201.0.2"]);
+    assert!(ping, "route to peer should exist in namespace A");
+}
+
+#[tokio::test]
+async fn test_network_monitor_in_namespace() {
+    let Some(test_ns) = TestNamespace::create("mon").await else { return };
+
+    let monitor = NetworkMonitor::with_namespace(test_ns.ns.clone());
+    let mut events = monitor.subscribe();
+    monitor.start().await.expect("start monitor");
+
+    // Give the monitor time to open its socket in the namespace
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(test_ns.add_veth("ev0", "ev1"), "create veth pair");
+
+    let added = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match events.recv().await {
+                Ok(NetworkEvent::InterfaceAdded { name, .. }) if name == "ev0" => return true,
+                Ok(_) => continue,
+                Err(_) => return false,
+            }
+        }
+    })
+    .await
+    .unwrap_or(false);
+
+    monitor.stop().await.unwrap();
+    assert!(added, "InterfaceAdded for ev0 should be reported from the namespace");
+}
+
+#[tokio::test]
+async fn test_namespace_lifecycle() {
+    let Some(test_ns) = TestNamespace::create("life").await else { return };
+
+    assert!(test_ns.ns.exists());
+    assert!(NetNamespace::list().await.unwrap().contains(&test_ns.name));
+    assert!(matches!(
+        NetNamespace::create(&test_ns.name).await,
+        Err(libnetctl::NetctlError::AlreadyExists(_))
+    ));
+
+    NetNamespace::delete(&test_ns.name).await.expect("delete namespace");
+    assert!(!test_ns.ns.exists());
+    assert!(InterfaceController::with_namespace(test_ns.ns.clone()).list().await.is_err());
+}
+
+#[tokio::test]
+async fn test_routing_controller_in_namespace() {
+    let Some(test_ns) = TestNamespace::create("route").await else { return };
+    if !test_ns.add_veth("rt0", "rt1") {
+        println!("SKIP: Cannot create veth pair");
+        return;
+    }
+
+    let ifaces = InterfaceController::with_namespace(test_ns.ns.clone());
+
+    // IPv6 sysctls are read and written inside the namespace
+    ifaces.set_ipv6_sysctl("rt0", "accept_ra", "0").await.expect("set accept_ra");
+    assert_eq!(ifaces.get_ipv6_sysctl("rt0", "accept_ra").await.unwrap(), "0");
+    assert_eq!(ifaces.get_ipv6_sysctl("rt0", "disable_ipv6").await.unwrap(), "0");
+    assert!(ifaces.get_ipv6_sysctl("rt0", "../forwarding").await.is_err());
+    assert!(matches!(
+        ifaces.get_ipv6_sysctl("nonexistent0", "accept_ra").await,
+        Err(libnetctl::NetctlError::NotFound(_))
+    ));
+
+    ifaces.set_ip("rt0", "10.202.0.1", 24).await.unwrap();
+    ifaces.add_ip("rt0", "fd00:202::1", 64).await.unwrap();
+    ifaces.up("rt0").await.unwrap();
+    ifaces.up("rt1").await.unwrap();
+
+    let ctrl = RoutingController::with_namespace(test_ns.ns.clone());
+
+    // The connected route shows up as a structured kernel route
+    let routes = ctrl.list_routes(Some(libnetctl::routing::RT_TABLE_MAIN)).await.unwrap();
+    assert!(routes.iter().any(|r| r.destination.to_string() == "10.202.0.0"
+        && r.prefix_len == 24
+        && r.interface.as_deref() == Some("rt0")
+        && r.scope == RouteScope::Link
+        && r.source == Some("10.202.0.1".parse().unwrap())));
+
+    // IPv4 default route with a metric
+    ctrl.add_default_gateway("10.202.0.254", Some("rt0")).await.expect("add default gateway");
+    let mut route = Route::default_via("10.202.0.254".parse().unwrap());
+    route.interface = Some("rt0".to_string());
+    assert!(matches!(ctrl.add_route(&route).await, Err(libnetctl::NetctlError::AlreadyExists(_))));
+
+    // Replace changes attributes of the same route in place
+    route.source = Some("10.202.0.1".parse().unwrap());
+    ctrl.replace_route(&route).await.expect("replace default route");
+    let defaults: Vec<Route> = ctrl.list_routes(None).await.unwrap()
+        .into_iter()
+        .filter(|r| r.is_default() && !r.is_ipv6())
+        .collect();
+    assert_eq!(defaults.len(), 1);
+    assert_eq!(defaults[0].source, route.source);
+
+    // IPv6 route in a custom table with metric and onlink gateway
+    let mut v6 = Route::new("2001:db8:100::".parse().unwrap(), 48);
+    v6.gateway = Some("fd00:202::fe".parse().unwrap());
+    v6.interface = Some("rt0".to_string());
+    v6.metric = Some(512);
+    v6.table = 100;
+    v6.onlink = true;
+    ctrl.add_route(&v6).await.expect("add IPv6 route");
+
+    // Unreachable / blackhole / prohibit routes
+    for (dst, kind) in [
+        ("192.0.2.0", RouteType::Blackhole),
+        ("198.51.100.0", RouteType::Unreachable),
+        ("203.0.113.0", RouteType::Prohibit),
+    ] {
+        let mut r = Route::new(dst.parse().unwrap(), 24);
+        r.kind = kind;
+        r.table = 100;
+        ctrl.add_route(&r).await.expect("add special route");
+    }
+
+    let table100 = ctrl.list_routes(Some(100)).await.unwrap();
+    assert_eq!(table100.len(), 4, "routes in table 100: {:?}", table100);
+    let listed_v6 = table100.iter().find(|r| r.is_ipv6()).unwrap();
+    assert_eq!(listed_v6.metric, Some(512));
+    assert_eq!(listed_v6.gateway, v6.gateway);
+    assert!(listed_v6.onlink);
+    assert!(table100.iter().any(|r| r.kind == RouteType::Prohibit));
+
+    // Routes returned by list_routes can be deleted as-is
+    for r in &table100 {
+        ctrl.delete_route(r).await.expect("delete listed route");
+    }
+    assert!(ctrl.list_routes(Some(100)).await.unwrap().is_empty());
+    assert!(matches!(ctrl.delete_route(&v6).await, Err(libnetctl::NetctlError::NotFound(_))));
+
+    let removed = ctrl.del_default_gateway(None, None).await.expect("delete default gateway");
+    assert_eq!(removed.len(), 1);
+    assert!(matches!(
+        ctrl.del_default_gateway(None, None).await,
+        Err(libnetctl::NetctlError::NotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_network_monitor_route_events() {
+    let Some(test_ns) = TestNamespace::create("rtev").await else { return };
+    if !test_ns.add_veth("re0", "re1") {
+        println!("SKIP: Cannot create veth pair");
+        return;
+    }
+    InterfaceController::with_namespace(test_ns.ns.clone()).up("re0").await.unwrap();
+
+    let monitor = NetworkMonitor::with_namespace(test_ns.ns.clone());
+    let mut events = monitor.subscribe();
+    monitor.start().await.expect("start monitor");
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    // A route added behind netctl's back is reported with its attributes
+    assert!(test_ns.ip(&["route", "add", "10.203.0.0/16", "dev", "re0", "table", "42", "metric", "7"]));
+    assert!(test_ns.ip(&["route", "del", "10.203.0.0/16", "dev", "re0", "table", "42", "metric", "7"]));
+
+    let is_test_route = |route: &Route| route.destination_prefix() == "10.203.0.0/16" && route.table == 42;
+    let (added, removed) = tokio::time::timeout(Duration::from_secs(5), async {
+        let mut added = None;
+        loop {
+            match events.recv().await {
+                Ok(NetworkEvent::RouteAdded { route }) if is_test_route(&route) => added = Some(route),
+                Ok(NetworkEvent::RouteRemoved { route }) if is_test_route(&route) => return (added, true),
+                Ok(_) => continue,
+                Err(_) => return (added, false),
+            }
+        }
+    })
+    .await
+    .unwrap_or((None, false));
+
+    monitor.stop().await.unwrap();
+    let added = added.expect("RouteAdded for 10.203.0.0/16");
+    assert_eq!(added.interface.as_deref(), Some("re0"));
+    assert_eq!(added.metric, Some(7));
+    assert!(removed, "RouteRemoved for 10.203.0.0/16");
+}
+
+#[tokio::test]
+async fn test_routing_rules_in_namespace() {
+    let Some(test_ns) = TestNamespace::create("rule").await else { return };
+    let ctrl = RoutingController::with_namespace(test_ns.ns.clone());
+    let baseline = ctrl.list_rules().await.expect("list rules");
+    assert!(baseline.iter().any(|r| r.table == Some(libnetctl::routing::RT_TABLE_MAIN)));
+
+    // Rules as they would come from a connection profile
+    let section: RoutingRuleSection = toml::from_str(r#"
+        priority = 1000
+        from = "10.8.0.0/24"
+        fwmark = "0x10/0xff"
+        table = "100"
+    "#).unwrap();
+    let from_profile = section.to_rule().expect("profile rule");
+    ctrl.add_rule(&from_profile).await.expect("add rule");
+    assert!(matches!(ctrl.add_rule(&from_profile).await, Err(libnetctl::NetctlError::AlreadyExists(_))));
+
+    let mut v6 = RoutingRule { action: RuleAction::Prohibit, invert: true, ..Default::default() };
+    v6.ipv6 = true;
+    v6.priority = Some(1001);
+    v6.iif = Some("lo".to_string());
+    v6.uid_range = Some((1000, 1999));
+    ctrl.add_rule(&v6).await.expect("add IPv6 rule");
+
+    let rules = ctrl.list_rules().await.unwrap();
+    let listed = rules.iter().find(|r| r.priority == Some(1000)).expect("listed rule");
+    assert_eq!(listed.from, from_profile.from);
+    assert_eq!(listed.from_len, 24);
+    assert_eq!((listed.fwmark, listed.fwmask), (Some(0x10), Some(0xff)));
+    assert_eq!(listed.table, Some(100));
+    let listed_v6 = rules.iter().find(|r| r.priority == Some(1001)).expect("listed IPv6 rule");
+    assert!(listed_v6.ipv6 && listed_v6.invert);
+    assert_eq!(listed_v6.action, RuleAction::Prohibit);
+    assert_eq!(listed_v6.uid_range, Some((1000, 1999)));
+
+    // Listed rules can be deleted as-is, and by priority alone
+    ctrl.delete_rule(listed).await.expect("delete listed rule");
+    let by_priority = RoutingRule { ipv6: true, priority: Some(1001), ..Default::default() };
+    ctrl.delete_rule(&by_priority).await.expect("delete rule by priority");
+    assert_eq!(ctrl.list_rules().await.unwrap().len(), baseline.len());
+    assert!(matches!(ctrl.delete_rule(&from_profile).await, Err(libnetctl::NetctlError::NotFound(_))));
+}
+
+/// Minimal DHCP server for the client tests
+///
+/// Offers 10.77.0.50 first, which the server namespace also uses, so the
+/// client has to decline it and gets 10.77.0.100 instead.
+mod test_dhcp_server {
+    use libnetctl::dhcpv4::packet::{self, options, ClasslessRoute, DhcpPacket, MessageType};
+    use std::net::{Ipv4Addr, SocketAddrV4, UdpSocket};
+    use std::os::fd::AsRawFd;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    pub const SERVER: Ipv4Addr = Ipv4Addr::new(10, 77, 0, 1);
+    pub const CONFLICT: Ipv4Addr = Ipv4Addr::new(10, 77, 0, 50);
+    pub const FREE: Ipv4Addr = Ipv4Addr::new(10, 77, 0, 100);
+
+    /// Messages the server received, by type
+    #[derive(Default)]
+    pub struct Seen {
+        pub messages: Vec<(MessageType, Option<Ipv4Addr>, Option<Ipv4Addr>)>,
+    }
+
+    pub struct Server {
+        stop: Arc<AtomicBool>,
+        pub seen: Arc<Mutex<Seen>>,
+        thread: Option<std::thread::JoinHandle<()>>,
+    }
+
+    impl Server {
+        pub fn start(netns: &str, interface: &str) -> Self {
+            let stop = Arc::new(AtomicBool::new(false));
+            let seen = Arc::new(Mutex::new(Seen::default()));
+            let ns = std::fs::File::open(format!("/run/netns/{}", netns)).expect("open server namespace");
+            let interface = interface.to_string();
+            let thread = {
+                let (stop, seen) = (stop.clone(), seen.clone());
+                std::thread::spawn(move || {
+                    assert_eq!(unsafe { libc::setns(ns.as_raw_fd(), libc::CLONE_NEWNET) }, 0);
+                    serve(&interface, &stop, &seen);
+                })
+            };
+            Self { stop, seen, thread: Some(thread) }
+        }
+
+        pub fn saw(&self, kind: MessageType) -> bool {
+            self.seen.lock().unwrap().messages.iter().any(|(k, _, _)| *k == kind)
+        }
+    }
+
+    impl Drop for Server {
+        fn drop(&mut self) {
+            self.stop.store(true, Ordering::Relaxed);
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    fn serve(interface: &str, stop: &AtomicBool, seen: &Mutex<Seen>) {
+        let socket = UdpSocket::bind("0.0.0.0:67").expect("bind DHCP server port");
+        socket.set_broadcast(true).unwrap();
+        socket.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+        // Limited broadcasts need an outgoing device
+        let mut device = [0u8; libc::IFNAMSIZ];
+        device[..interface.len()].copy_from_slice(interface.as_bytes());
+        let ret = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_BINDTODEVICE,
+                device.as_ptr() as *const libc::c_void,
+                device.len() as libc::socklen_t,
+            )
+        };
+        assert_eq!(ret, 0);
+
+        let mut declined = false;
+        let mut buf = [0u8; 1500];
+        while !stop.load(Ordering::Relaxed) {
+            let Ok((len, _)) = socket.recv_from(&mut buf) else { continue };
+            let Ok(request) = DhcpPacket::decode(&buf[..len]) else { continue };
+            let Some(kind) = request.message_type() else { continue };
+            let requested = request.ip_option(options::REQUESTED_IP);
+            seen.lock().unwrap().messages.push((kind, requested, request.server_id()));
+
+            let offer = if declined { FREE } else { CONFLICT };
+            let reply = match kind {
+                MessageType::Discover => Some((MessageType::Offer, offer)),
+                MessageType::Request => {
+                    let address = requested.unwrap_or(request.ciaddr);
+                    if address == offer || address == FREE {
+                        Some((MessageType::Ack, address))
+                    } else {
+                        Some((MessageType::Nak, Ipv4Addr::UNSPECIFIED))
+                    }
+                }
+                MessageType::Decline => {
+                    declined = true;
+                    None
+                }
+                _ => None,
+            };
+            let Some((kind, address)) = reply else { continue };
+
+            let mut reply = DhcpPacket::reply(kind, &request);
+            reply.set_ip_option(options::SERVER_ID, SERVER);
+            if kind != MessageType::Nak {
+                reply.yiaddr = address;
+                reply.set_ip_option(options::SUBNET_MASK, packet::prefix_to_mask(24));
+                reply.set_ip_list_option(options::ROUTER, &[SERVER]);
+                reply.set_ip_list_option(options::DNS_SERVERS, &[Ipv4Addr::new(10, 77, 0, 53)]);
+                reply.set_option(options::DOMAIN_SEARCH, packet::encode_domain_list(&["corp.test".to_string()]));
+                reply.set_option(options::LEASE_TIME, 6u32.to_be_bytes().to_vec());
+                reply.set_option(options::RENEWAL_TIME, 2u32.to_be_bytes().to_vec());
+                reply.set_option(options::REBINDING_TIME, 5u32.to_be_bytes().to_vec());
+                reply.set_option(options::INTERFACE_MTU, 1400u16.to_be_bytes().to_vec());
+                reply.set_option(options::CLASSLESS_STATIC_ROUTES, packet::encode_classless_routes(&[
+                    ClasslessRoute { destination: Ipv4Addr::new(10, 99, 0, 0), prefix_len: 16, gateway: SERVER },
+                    ClasslessRoute { destination: Ipv4Addr::UNSPECIFIED, prefix_len: 0, gateway: SERVER },
+                ]));
+            }
+            let destination = if request.ciaddr.is_unspecified() { Ipv4Addr::BROADCAST } else { request.ciaddr };
+            let _ = socket.send_to(&reply.encode(), SocketAddrV4::new(destination, packet::CLIENT_PORT));
+        }
+    }
+}
+
+/// Wait for the next event of `kind` on the DHCP event stream
+async fn next_dhcp_event(
+    events: &mut tokio::sync::broadcast::Receiver<libnetctl::DhcpEvent>,
+    matches: impl Fn(&libnetctl::DhcpEvent) -> bool,
+    within: Duration,
+) -> libnetctl::DhcpEvent {
+    tokio::time::timeout(within, async {
+        loop {
+            let event = events.recv().await.expect("DHCP event stream closed");
+            if matches(&event) {
+                return event;
+            }
+        }
+    }).await.expect("timed out waiting for DHCP event")
+}
+
+#[tokio::test]
+async fn test_native_dhcp_client_on_veth() {
+    use libnetctl::dhcpv4::MessageType;
+    use libnetctl::{ArpProbeConfig, DhcpClientController, DhcpClientOptions, DhcpEvent, DhcpTiming, RouteProtocol};
+    use test_dhcp_server::{CONFLICT, FREE};
+
+    let Some(client_ns) = TestNamespace::create("dhcpc").await else { return };
+    let Some(server_ns) = TestNamespace::create("dhcps").await else { return };
+    if !client_ns.add_veth("dh0", "dh1") || !client_ns.ip(&["link", "set", "dh1", "netns", &server_ns.name]) {
+        println!("SKIP: Cannot create veth pair");
+        return;
+    }
+    assert!(server_ns.ip(&["addr", "add", "10.77.0.1/24", "dev", "dh1"]));
+    assert!(server_ns.ip(&["addr", "add", "10.77.0.50/24", "dev", "dh1"]));
+    assert!(server_ns.ip(&["link", "set", "dh1", "up"]));
+    let ifaces = InterfaceController::with_namespace(client_ns.ns.clone());
+    ifaces.up("dh0").await.unwrap();
+
+    let server = test_dhcp_server::Server::start(&server_ns.name, "dh1");
+
+    let options = DhcpClientOptions {
+        arp_check: Some(ArpProbeConfig {
+            probe_wait: Duration::from_millis(50),
+            probe_num: 2,
+            probe_min: Duration::from_millis(100),
+            probe_max: Duration::from_millis(200),
+            announce_wait: Duration::from_millis(300),
+            announce_num: 1,
+            announce_interval: Duration::from_millis(100),
+        }),
+        timing: DhcpTiming {
+            initial_retransmit: Duration::from_secs(1),
+            max_retransmit: Duration::from_secs(2),
+            min_lease_retransmit: Duration::from_secs(1),
+            decline_backoff: Duration::from_millis(200),
+        },
+        ..Default::default()
+    };
+    let lease_dir = tempfile::tempdir().unwrap();
+    let client = DhcpClientController::with_namespace(client_ns.ns.clone())
+        .with_lease_dir(lease_dir.path())
+        .with_options(options);
+    let mut events = client.subscribe();
+    client.start("dh0").await.expect("start DHCP client");
+
+    // The first offer is in use and gets declined
+    let declined = next_dhcp_event(&mut events, |e| matches!(e, DhcpEvent::Declined { .. }), Duration::from_secs(15)).await;
+    assert!(matches!(declined, DhcpEvent::Declined { address, .. } if address == CONFLICT));
+    let DhcpEvent::Bound(lease) = next_dhcp_event(&mut events, |e| matches!(e, DhcpEvent::Bound(_)), Duration::from_secs(15)).await else {
+        unreachable!()
+    };
+    assert_eq!(lease.ip_address, Some(FREE.to_string()));
+    assert_eq!(lease.dns_servers, vec!["10.77.0.53"]);
+    assert_eq!(lease.domain_search, vec!["corp.test"]);
+    assert!(server.saw(MessageType::Decline));
+
+    // Address, classless routes and MTU are configured
+    let info = ifaces.get_info("dh0").await.unwrap();
+    assert!(info.addresses.iter().any(|a| a.address == FREE.to_string() && a.prefix_len == 24));
+    assert_eq!(info.mtu, Some(1400));
+    let routing = RoutingController::with_namespace(client_ns.ns.clone());
+    let routes = routing.list_routes(None).await.unwrap();
+    let dhcp_routes: Vec<&Route> = routes.iter().filter(|r| r.protocol == RouteProtocol::Dhcp).collect();
+    assert_eq!(dhcp_routes.len(), 2, "DHCP routes: {:?}", dhcp_routes);
+    assert!(dhcp_routes.iter().any(|r| r.destination.to_string() == "10.99.0.0" && r.prefix_len == 16));
+    assert!(dhcp_routes.iter().all(|r| r.metric == Some(100)));
+    assert_eq!(client.status("dh0").await.unwrap(), Some(lease.clone()));
+    assert_eq!(client.saved_lease("dh0").await.unwrap(), Some(lease.clone()));
+
+    // T1 is two seconds; the renewal is unicast to the server
+    let renewed = next_dhcp_event(&mut events, |e| matches!(e, DhcpEvent::Renewed(_)), Duration::from_secs(10)).await;
+    assert!(matches!(renewed, DhcpEvent::Renewed(l) if l.ip_address == lease.ip_address));
+
+    // Stopping removes the configuration but keeps the lease
+    client.stop("dh0").await.unwrap();
+    next_dhcp_event(&mut events, |e| matches!(e, DhcpEvent::Stopped { .. }), Duration::from_secs(5)).await;
+    let info = ifaces.get_info("dh0").await.unwrap();
+    assert!(!info.addresses.iter().any(|a| a.address == FREE.to_string()));
+    assert_eq!(info.mtu, Some(1500));
+    assert!(!routing.list_routes(None).await.unwrap().iter().any(|r| r.protocol == RouteProtocol::Dhcp));
+    assert!(client.status("dh0").await.unwrap().is_none());
+    let saved = client.saved_lease("dh0").await.unwrap().expect("lease kept after stop");
+    assert_eq!(saved.ip_address, Some(FREE.to_string()));
+
+    // INIT-REBOOT asks for the previous address without a server identifier
+    server.seen.lock().unwrap().messages.clear();
+    client.start("dh0").await.expect("restart DHCP client");
+    next_dhcp_event(&mut events, |e| matches!(e, DhcpEvent::Bound(_)), Duration::from_secs(15)).await;
+    let first = server.seen.lock().unwrap().messages.first().copied();
+    assert_eq!(first, Some((MessageType::Request, Some(FREE), None)));
+
+    // Releasing tells the server and removes the address
+    client.release("dh0").await.unwrap();
+    next_dhcp_event(&mut events, |e| matches!(e, DhcpEvent::Released { .. }), Duration::from_secs(5)).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(server.saw(MessageType::Release));
+    let info = ifaces.get_info("dh0").await.unwrap();
+    assert!(!info.addresses.iter().any(|a| a.address == FREE.to_string()));
+    assert_eq!(client.saved_lease("dh0").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_dhcpm_sequence_on_veth() {
+    use libnetctl::dhcpm::{DhcpMessageType, DhcpOption, DhcpTestConfig, DhcpmController};
+    use libnetctl::dhcpv4::MessageType;
+    use test_dhcp_server::{CONFLICT, SERVER};
+
+    let Some(client_ns) = TestNamespace::create("dhcpmc").await else { return };
+    let Some(server_ns) = TestNamespace::create("dhcpms").await else { return };
+    if !client_ns.add_veth("dm0", "dm1") || !client_ns.ip(&["link", "set", "dm1", "netns", &server_ns.name]) {
+        println!("SKIP: Cannot create veth pair");
+        return;
+    }
+    assert!(server_ns.ip(&["addr", "add", "10.77.0.1/24", "dev", "dm1"]));
+    assert!(server_ns.ip(&["link", "set", "dm1", "up"]));
+    InterfaceController::with_namespace(client_ns.ns.clone()).up("dm0").await.unwrap();
+
+    let server = test_dhcp_server::Server::start(&server_ns.name, "dm1");
+    let tester = DhcpmController::new("dm0".to_string()).unwrap()
+        .in_namespace(client_ns.ns.clone())
+        .with_timeout(Duration::from_secs(2));
+
+    let base = DhcpTestConfig {
+        interface: "dm0".to_string(),
+        options: Some(vec![DhcpOption { code: 60, value: "6e657463746c".to_string() }]),
+        ..Default::default()
+    };
+    let results = tester.run_sequence(&base).await.expect("DHCP test sequence");
+    assert_eq!(results.len(), 3, "{:?}", results);
+    assert!(results.iter().all(|r| r.success), "{:?}", results);
+    assert!(results.iter().all(|r| r.client_mac == results[0].client_mac));
+
+    // One server answered the DISCOVER
+    let offer = results[0].response.as_ref().expect("offer");
+    assert_eq!(results[0].responses.len(), 1);
+    assert!(offer.is(MessageType::Offer));
+    assert_eq!(offer.your_ip, Some(CONFLICT.to_string()));
+    assert_eq!(offer.server_ip, Some(SERVER.to_string()));
+    assert_eq!(offer.source_ip, Some(SERVER.to_string()));
+    assert_eq!(offer.mtu, Some(1400));
+    assert_eq!(offer.domain_search, Some(vec!["corp.test".to_string()]));
+    assert!(results[0].rtt_ms.is_some());
+
+    let ack = results[1].response.as_ref().expect("ack");
+    assert!(ack.is(MessageType::Ack));
+    assert_eq!(ack.your_ip, Some(CONFLICT.to_string()));
+    assert_eq!(results[2].message_type, DhcpMessageType::Release);
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let seen = server.seen.lock().unwrap().messages.clone();
+    assert_eq!(seen, vec![
+        (MessageType::Discover, None, None),
+        (MessageType::Request, Some(CONFLICT), Some(SERVER)),
+        (MessageType::Release, None, Some(SERVER)),
+    ]);
+
+    // A REQUEST for an address the server does not hand out is refused
+    let refused = tester.send_request(&DhcpTestConfig {
+        interface: "dm0".to_string(),
+        message_type: DhcpMessageType::Request,
+        requested_ip: Some("10.77.0.200".to_string()),
+        ..Default::default()
+    }).await.unwrap();
+    assert!(!refused.success);
+    assert!(refused.response.as_ref().is_some_and(|r| r.is(MessageType::Nak)));
+}
+
+/// Minimal DHCPv6 server for the client tests
+///
+/// Hands out 2001:db8:1::100 and delegates 2001:db8:ff00::/56 with short
+/// lifetimes so that renewals happen within the test.
+mod test_dhcpv6_server {
+    use libnetctl::dhcpv6::packet::{options, status, Ia, IaAddress, IaPrefix};
+    use libnetctl::dhcpv6::{Dhcpv6Message, IaKind, MessageType};
+    use std::net::{Ipv6Addr, SocketAddr, UdpSocket};
+    use std::os::fd::AsRawFd;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    pub const ADDRESS: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 0x100);
+    pub const PREFIX: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0xff00, 0, 0, 0, 0, 0);
+    pub const DNS: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 0x53);
+    const SERVER_ID: [u8; 10] = [0, 3, 0, 1, 2, 0, 0, 0, 0, 0x47];
+
+    pub struct Server {
+        stop: Arc<AtomicBool>,
+        pub seen: Arc<Mutex<Vec<MessageType>>>,
+        thread: Option<std::thread::JoinHandle<()>>,
+    }
+
+    impl Server {
+        pub fn start(netns: &str, interface: &str) -> Self {
+            let stop = Arc::new(AtomicBool::new(false));
+            let seen = Arc::new(Mutex::new(Vec::new()));
+            let ns = std::fs::File::open(format!("/run/netns/{}", netns)).expect("open server namespace");
+            let interface = interface.to_string();
+            let thread = {
+                let (stop, seen) = (stop.clone(), seen.clone());
+                std::thread::spawn(move || {
+                    assert_eq!(unsafe { libc::setns(ns.as_raw_fd(), libc::CLONE_NEWNET) }, 0);
+                    serve(&interface, &stop, &seen);
+                })
+            };
+            Self { stop, seen, thread: Some(thread) }
+        }
+
+        pub fn saw(&self, kind: MessageType) -> bool {
+            self.seen.lock().unwrap().contains(&kind)
+        }
+    }
+
+    impl Drop for Server {
+        fn drop(&mut self) {
+            self.stop.store(true, Ordering::Relaxed);
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    /// Bindings for the IAs of `request`
+    fn bindings(request: &Dhcpv6Message, reply: &mut Dhcpv6Message) {
+        for ia in request.ias() {
+            let mut granted = Ia { t1: 2, t2: 4, ..Ia::new(ia.kind, ia.iaid) };
+            match ia.kind {
+                IaKind::Na => granted.addresses.push(IaAddress {
+                    address: ADDRESS,
+                    preferred_lifetime: 5,
+                    valid_lifetime: 8,
+                }),
+                IaKind::Pd => granted.prefixes.push(IaPrefix {
+                    prefix: PREFIX,
+                    prefix_len: 56,
+                    preferred_lifetime: 5,
+                    valid_lifetime: 8,
+                }),
+            }
+            reply.add_ia(&granted);
+        }
+    }
+
+    fn serve(interface: &str, stop: &AtomicBool, seen: &Mutex<Vec<MessageType>>) {
+        let name = std::ffi::CString::new(interface).unwrap();
+        let ifindex = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        assert_ne!(ifindex, 0);
+        let socket = UdpSocket::bind("[::]:547").expect("bind DHCPv6 server port");
+        socket.join_multicast_v6(&libnetctl::dhcpv6::packet::ALL_SERVERS, ifindex).expect("join multicast group");
+        socket.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+
+        let mut buf = [0u8; 1500];
+        while !stop.load(Ordering::Relaxed) {
+            let Ok((len, SocketAddr::V6(source))) = socket.recv_from(&mut buf) else { continue };
+            let Ok(request) = Dhcpv6Message::decode(&buf[..len]) else { continue };
+            seen.lock().unwrap().push(request.msg_type);
+
+            let kind = match request.msg_type {
+                MessageType::Solicit => MessageType::Advertise,
+                MessageType::Request | MessageType::Renew | MessageType::Rebind
+                | MessageType::Release | MessageType::InformationRequest => MessageType::Reply,
+                _ => continue,
+            };
+            let mut reply = Dhcpv6Message::new(kind, request.transaction_id);
+            reply.set_option(options::CLIENTID, request.client_id().unwrap_or_default().to_vec());
+            reply.set_option(options::SERVERID, SERVER_ID.to_vec());
+            match request.msg_type {
+                MessageType::Release => {
+                    reply.set_option(options::STATUS_CODE, [&status::SUCCESS.to_be_bytes()[..], b"released"].concat());
+                }
+                MessageType::InformationRequest => {}
+                _ => bindings(&request, &mut reply),
+            }
+            reply.set_dns_servers(&[DNS]);
+            reply.set_domain_list(&["v6.test".to_string()]);
+            let _ = socket.send_to(&reply.encode(), source);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_dhcpv6_prefix_delegation_on_veth() {
+    use libnetctl::dhcpv6::MessageType;
+    use libnetctl::{Dhcpv6ClientController, Dhcpv6ClientOptions, Dhcpv6Event, RouteProtocol};
+    use test_dhcpv6_server::{ADDRESS, DNS};
+
+    let Some(client_ns) = TestNamespace::create("dhcp6c").await else { return };
+    let Some(server_ns) = TestNamespace::create("dhcp6s").await else { return };
+    // Skip duplicate address detection so link-local addresses are usable at once
+    for ns in [&client_ns, &server_ns] {
+        for scope in ["all", "default"] {
+            let _ = Command::new("ip")
+                .args(["netns", "exec", &ns.name, "sysctl", "-qw", &format!("net.ipv6.conf.{}.accept_dad=0", scope)])
+                .output();
+        }
+    }
+    if !client_ns.add_veth("wan0", "wan1") || !client_ns.ip(&["link", "set", "wan1", "netns", &server_ns.name]) {
+        println!("SKIP: Cannot create veth pair");
+        return;
+    }
+    for (lan, peer) in [("lan0", "lanp0"), ("lan1", "lanp1")] {
+        assert!(client_ns.add_veth(lan, peer));
+        assert!(client_ns.ip(&["link", "set", lan, "up"]));
+        assert!(client_ns.ip(&["link", "set", peer, "up"]));
+    }
+    assert!(server_ns.ip(&["link", "set", "wan1", "up"]));
+    let ifaces = InterfaceController::with_namespace(client_ns.ns.clone());
+    ifaces.up("wan0").await.unwrap();
+
+    let server = test_dhcpv6_server::Server::start(&server_ns.name, "wan1");
+
+    let client = Dhcpv6ClientController::with_namespace(client_ns.ns.clone());
+    let mut events = client.subscribe();
+    let options = Dhcpv6ClientOptions::stateful()
+        .with_prefix_delegation(Some(56), vec!["lan0".to_string(), "lan1".to_string()]);
+    client.start_with_options("wan0", options).await.expect("start DHCPv6 client");
+
+    let wait = |within: u64| Duration::from_secs(within);
+    let bound = tokio::time::timeout(wait(15), async {
+        loop {
+            if let Dhcpv6Event::Bound(lease) = events.recv().await.expect("DHCPv6 event stream closed") {
+                return lease;
+            }
+        }
+    }).await.expect("timed out waiting for DHCPv6 lease");
+    assert_eq!(bound.addresses, vec![ADDRESS]);
+    assert_eq!(bound.prefixes.len(), 1);
+    assert_eq!(bound.prefixes[0].prefix_len, 56);
+    assert_eq!(bound.dns_servers, vec![DNS]);
+    assert_eq!(bound.domain_search, vec!["v6.test"]);
+    assert_eq!(bound.lan_prefixes.len(), 2);
+
+    // Address on the upstream link, a /64 on each LAN interface and an
+    // unreachable route for the rest of the prefix
+    let has = |info: &libnetctl::InterfaceInfo, address: &str, prefix_len: u8| {
+        info.addresses.iter().any(|a| a.address == address && a.prefix_len == prefix_len)
+    };
+    assert!(has(&ifaces.get_info("wan0").await.unwrap(), &ADDRESS.to_string(), 128));
+    assert!(has(&ifaces.get_info("lan0").await.unwrap(), "2001:db8:ff00::1", 64));
+    assert!(has(&ifaces.get_info("lan1").await.unwrap(), "2001:db8:ff00:1::1", 64));
+    let routing = RoutingController::with_namespace(client_ns.ns.clone());
+    let routes = routing.list_routes(None).await.unwrap();
+    assert!(routes.iter().any(|r| r.protocol == RouteProtocol::Dhcp && r.kind == RouteType::Unreachable
+        && r.destination.to_string() == "2001:db8:ff00::" && r.prefix_len == 56), "routes: {:?}", routes);
+
+    // T1 is two seconds
+    tokio::time::timeout(wait(10), async {
+        while !matches!(events.recv().await.expect("DHCPv6 event stream closed"), Dhcpv6Event::Renewed(_)) {}
+    }).await.expect("timed out waiting for DHCPv6 renewal");
+    assert!(server.saw(MessageType::Renew));
+    assert_eq!(client.status("wan0").await.unwrap().unwrap().lan_prefixes.len(), 2);
+
+    // Releasing withdraws everything
+    client.release("wan0").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(server.saw(MessageType::Release));
+    assert!(!has(&ifaces.get_info("wan0").await.unwrap(), &ADDRESS.to_string(), 128));
+    assert!(!has(&ifaces.get_info("lan0").await.unwrap(), "2001:db8:ff00::1", 64));
+    assert!(!has(&ifaces.get_info("lan1").await.unwrap(), "2001:db8:ff00:1::1", 64));
+    assert!(!routing.list_routes(None).await.unwrap().iter().any(|r| r.protocol == RouteProtocol::Dhcp));
+    assert!(client.status("wan0").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_router_advertisements_on_veth() {
+    use libnetctl::{RaConfig, RaController, RaPrefix, RouteProtocol};
+
+    let Some(router_ns) = TestNamespace::create("rar").await else { return };
+    let Some(host_ns) = TestNamespace::create("rah").await else { return };
+    // Usable link-local addresses at once, and routes from Route Information options
+    let sysctls = ["all.accept_dad=0", "default.accept_dad=0", "default.accept_ra_rt_info_max_plen=64"];
+    for ns in [&router_ns, &host_ns] {
+        for sysctl in sysctls {
+            let _ = Command::new("ip")
+                .args(["netns", "exec", &ns.name, "sysctl", "-qw", &format!("net.ipv6.conf.{}", sysctl)])
+                .output();
+        }
+    }
+    if !router_ns.add_veth("ra0", "ra1") || !router_ns.ip(&["link", "set", "ra1", "netns", &host_ns.name]) {
+        println!("SKIP: Cannot create veth pair");
+        return;
+    }
+    assert!(router_ns.ip(&["link", "set", "ra0", "up"]));
+
+    let ra = RaController::with_namespace(router_ns.ns.clone());
+    let config = RaConfig {
+        prefixes: vec!["2001:db8:1::/64".parse().unwrap()],
+        routes: vec!["2001:db8:100::/48".parse().unwrap()],
+        dns_servers: vec!["2001:db8:1::1".parse().unwrap()],
+        ..Default::default()
+    };
+    ra.start("ra0", config).await.expect("start router advertisements");
+    // The host solicits an advertisement when its link comes up
+    assert!(host_ns.ip(&["link", "set", "ra1", "up"]));
+
+    let host = InterfaceController::with_namespace(host_ns.ns.clone());
+    let routing = RoutingController::with_namespace(host_ns.ns.clone());
+    let has_address_in = |info: &libnetctl::InterfaceInfo, prefix: &str| {
+        info.addresses.iter().any(|a| a.address.starts_with(prefix) && a.prefix_len == 64)
+    };
+    let ra_routes = || async {
+        routing.list_routes(None).await.unwrap().into_iter()
+            .filter(|r| r.is_ipv6() && r.protocol == RouteProtocol::Ra)
+            .collect::<Vec<_>>()
+    };
+
+    let wait = Duration::from_secs(10);
+    tokio::time::timeout(wait, async {
+        while !has_address_in(&host.get_info("ra1").await.unwrap(), "2001:db8:1:0:") {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }).await.expect("timed out waiting for a SLAAC address");
+    let routes = ra_routes().await;
+    assert!(routes.iter().any(|r| r.is_default()), "routes: {:?}", routes);
+    assert!(routes.iter().any(|r| r.destination.to_string() == "2001:db8:100::" && r.prefix_len == 48), "routes: {:?}", routes);
+    let status = ra.status("ra0").await.expect("sender running");
+    assert!(status.advertisements >= 1);
+    assert!(status.solicitations >= 1);
+
+    // A delegated prefix is advertised next to the configured one
+    let delegated = RaPrefix::new("2001:db8:ff00::".parse().unwrap(), 64).with_lifetimes(7200, 3600);
+    ra.set_delegated_prefixes("ra0", vec![delegated]).await.unwrap();
+    tokio::time::timeout(wait, async {
+        while !has_address_in(&host.get_info("ra1").await.unwrap(), "2001:db8:ff00:0:") {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }).await.expect("timed out waiting for an address in the delegated prefix");
+    assert_eq!(ra.status("ra0").await.unwrap().prefixes.len(), 2);
+
+    // The last advertisement removes the default route
+    ra.stop("ra0").await.unwrap();
+    assert!(!ra.is_running("ra0").await);
+    tokio::time::timeout(wait, async {
+        while ra_routes().await.iter().any(|r| r.is_default()) {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }).await.expect("default route still present after the final advertisement");
+}
+
+/// Wait for the next event on the link-local event stream
+async fn next_link_local_event(
+    events: &mut tokio::sync::broadcast::Receiver<libnetctl::LinkLocalEvent>,
+) -> libnetctl::LinkLocalEvent {
+    tokio::time::timeout(Duration::from_secs(10), events.recv())
+        .await.expect("timed out waiting for link-local event")
+        .expect("link-local event stream closed")
+}
+
+#[tokio::test]
+async fn test_ipv4_link_local_on_veth() {
+    use libnetctl::arp::ArpSocket;
+    use libnetctl::ipv4ll::is_link_local;
+    use libnetctl::{ArpProbeConfig, LinkLocalController, LinkLocalEvent};
+
+    let Some(host_ns) = TestNamespace::create("llh").await else { return };
+    let Some(peer_ns) = TestNamespace::create("llp").await else { return };
+    if !host_ns.add_veth("ll0", "ll1") || !host_ns.ip(&["link", "set", "ll1", "netns", &peer_ns.name]) {
+        println!("SKIP: Cannot create veth pair");
+        return;
+    }
+    assert!(peer_ns.ip(&["link", "set", "ll1", "up"]));
+    let ifaces = InterfaceController::with_namespace(host_ns.ns.clone());
+    ifaces.up("ll0").await.unwrap();
+
+    let controller = LinkLocalController::with_namespace(host_ns.ns.clone())
+        .with_probe_config(ArpProbeConfig {
+            probe_wait: Duration::from_millis(50),
+            probe_num: 2,
+            probe_min: Duration::from_millis(100),
+            probe_max: Duration::from_millis(200),
+            announce_wait: Duration::from_millis(300),
+            announce_num: 1,
+            announce_interval: Duration::from_millis(100),
+        });
+    let mut events = controller.subscribe();
+    let has = |info: &libnetctl::InterfaceInfo, address: std::net::Ipv4Addr| {
+        info.addresses.iter().any(|a| a.address == address.to_string() && a.prefix_len == 16)
+    };
+
+    controller.start("ll0").await.expect("start link-local configuration");
+    let LinkLocalEvent::Bound { address, .. } = next_link_local_event(&mut events).await else { panic!("expected Bound") };
+    assert!(is_link_local(address));
+    assert_eq!(controller.address("ll0").await, Some(address));
+    assert!(has(&ifaces.get_info("ll0").await.unwrap(), address));
+
+    // A peer claiming the address twice within the defense interval wins it
+    let peer_info = InterfaceController::with_namespace(peer_ns.ns.clone()).get_info("ll1").await.unwrap();
+    let peer = ArpSocket::open(&peer_ns.ns, peer_info.index.unwrap(), [0x02, 0, 0, 0, 0x4c, 0x4c]).unwrap();
+    peer.send_request(address, address).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    peer.send_request(address, address).await.unwrap();
+    let event = next_link_local_event(&mut events).await;
+    assert_eq!(event, LinkLocalEvent::Conflict {
+        interface: "ll0".to_string(),
+        address,
+        mac: "02:00:00:00:4c:4c".to_string(),
+    });
+    let LinkLocalEvent::Bound { address: next, .. } = next_link_local_event(&mut events).await else { panic!("expected Bound") };
+    assert_ne!(next, address);
+    let info = ifaces.get_info("ll0").await.unwrap();
+    assert!(!has(&info, address) && has(&info, next));
+
+    controller.stop("ll0").await.unwrap();
+    assert_eq!(next_link_local_event(&mut events).await, LinkLocalEvent::Stopped { interface: "ll0".to_string() });
+    assert!(!controller.is_running("ll0").await);
+    assert!(!has(&ifaces.get_info("ll0").await.unwrap(), next));
+}
+
+/// Run `f` on a thread inside `ns` with its own runtime, for components that
+/// always work in the namespace they run in
+fn run_in_namespace<F, Fut>(ns: &NetNamespace, f: F)
+where
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: std::future::Future<Output = ()>,
+{
+    use std::os::fd::AsRawFd;
+
+    let path = ns.path();
+    let thread = std::thread::spawn(move || {
+        let file = std::fs::File::open(&path).expect("open namespace");
+        assert_eq!(unsafe { libc::setns(file.as_raw_fd(), libc::CLONE_NEWNET) }, 0, "enter namespace");
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(f());
+    });
+    if let Err(panic) = thread.join() {
+        std::panic::resume_unwind(panic);
+    }
+}
+
+#[tokio::test]
+async fn test_static_address_conflict_on_veth() {
+    use libnetctl::arp::ArpSocket;
+    use libnetctl::{ConnectionManager, NetctlConnectionConfig, NetctlError};
+    use std::net::Ipv4Addr;
+    use std::sync::Arc;
+
+    let Some(host_ns) = TestNamespace::create("acdh").await else { return };
+    let Some(peer_ns) = TestNamespace::create("acdp").await else { return };
+    if !host_ns.add_veth("acd0", "acd1") || !host_ns.ip(&["link", "set", "acd1", "netns", &peer_ns.name]) {
+        println!("SKIP: Cannot create veth pair");
+        return;
+    }
+    assert!(peer_ns.ip(&["link", "set", "acd1", "address", "02:00:00:00:ac:d1"]));
+    assert!(peer_ns.ip(&["addr", "add", "10.78.0.2/24", "dev", "acd1"]));
+    assert!(peer_ns.ip(&["link", "set", "acd1", "up"]));
+
+    let config_dir = std::env::temp_dir().join(format!("nctest-acd-{}", std::process::id()));
+    let peer = peer_ns.ns.clone();
+    let dir = config_dir.clone();
+    run_in_namespace(&host_ns.ns, move || async move {
+        let profile = |name: &str, address: &str| -> NetctlConnectionConfig {
+            toml::from_str(&format!(
+                "[connection]\nname = \"{}\"\nuuid = \"00000000-0000-0000-0000-0000000000ac\"\n\
+                 type = \"ethernet\"\ninterface-name = \"acd0\"\n\
+                 [ipv4]\nmethod = \"manual\"\naddress = \"{}/24\"\n",
+                name, address
+            )).unwrap()
+        };
+        let monitor = Arc::new(NetworkMonitor::new());
+        let mut events = monitor.subscribe();
+        let manager = ConnectionManager::new(Some(dir.to_str().unwrap())).with_network_monitor(monitor.clone());
+        manager.initialize().await.unwrap();
+        manager.save_connection("taken", &profile("taken", "10.78.0.2")).await.unwrap();
+        manager.save_connection("free", &profile("free", "10.78.0.3")).await.unwrap();
+        let ifaces = InterfaceController::new();
+        let has = |info: &libnetctl::InterfaceInfo, address: &str| info.addresses.iter().any(|a| a.address == address);
+
+        // The peer answers the probes for its own address
+        let err = manager.activate_connection("taken").await.expect_err("activation of a used address");
+        assert!(matches!(
+            &err,
+            NetctlError::AddressConflict { address, mac } if address == "10.78.0.2" && mac == "02:00:00:00:ac:d1"
+        ), "unexpected error: {}", err);
+        assert!(!has(&ifaces.get_info("acd0").await.unwrap(), "10.78.0.2"));
+
+        manager.activate_connection("free").await.expect("activate free address");
+        assert!(has(&ifaces.get_info("acd0").await.unwrap(), "10.78.0.3"));
+
+        // A later claim by the peer is reported by the conflict monitor
+        let claimed = Ipv4Addr::new(10, 78, 0, 3);
+        let peer_index = InterfaceController::with_namespace(peer.clone()).get_info("acd1").await.unwrap().index.unwrap();
+        let socket = ArpSocket::open(&peer, peer_index, [0x02, 0, 0, 0, 0xac, 0xd1]).unwrap();
+        let conflict = tokio::time::timeout(Duration::from_secs(15), async {
+            loop {
+                socket.send_request(claimed, claimed).await.unwrap();
+                if let Ok(Ok(event)) = tokio::time::timeout(Duration::from_millis(500), events.recv()).await {
+                    return event;
+                }
+            }
+        }).await.expect("no AddressConflict event");
+        assert!(matches!(
+            conflict,
+            NetworkEvent::AddressConflict { ref name, ref address, ref mac, .. }
+                if name == "acd0" && address == "10.78.0.3" && mac == "02:00:00:00:ac:d1"
+        ), "unexpected event: {:?}", conflict);
+
+        manager.deactivate_connection("acd0").await.unwrap();
+        assert!(!has(&ifaces.get_info("acd0").await.unwrap(), "10.78.0.3"));
+    });
+    let _ = std::fs::remove_dir_all(&config_dir);
+}
+
+/// A step failing after the clients started stops them again
+#[tokio::test]
+async fn test_failed_activation_stops_clients() {
+    use libnetctl::{ConnectionManager, DhcpClientState, Dhcpv6ClientState, NetctlConnectionConfig, NetctlError};
+
+    let Some(ns) = TestNamespace::create("actf").await else { return };
+    if !ns.add_veth("actf0", "actf1") || !ns.ip(&["link", "set", "actf1", "up"]) {
+        println!("SKIP: Cannot create veth pair");
+        return;
+    }
+
+    let config_dir = std::env::temp_dir().join(format!("nctest-actf-{}", std::process::id()));
+    let dir = config_dir.clone();
+    run_in_namespace(&ns.ns, move || async move {
+        // The router advertisements come last and cannot be configured
+        let profile = |name: &str, ip: &str| -> NetctlConnectionConfig {
+            toml::from_str(&format!(
+                "[connection]\nname = \"{}\"\nuuid = \"00000000-0000-0000-0000-0000000000af\"\n\
+                 type = \"ethernet\"\ninterface-name = \"actf0\"\n{}\n\
+                 [ipv6-ra]\nprefixes = [\"not-a-prefix\"]\n",
+                name, ip
+            )).unwrap()
+        };
+        let manager = ConnectionManager::new(Some(dir.to_str().unwrap()));
+        manager.initialize().await.unwrap();
+        manager.save_connection("dhcp", &profile("dhcp", "[ipv4]\nmethod = \"auto\"\n[ipv6]\nmethod = \"dhcp\"")).await.unwrap();
+        manager.save_connection("ll", &profile("ll", "[ipv4]\nmethod = \"link-local\"")).await.unwrap();
+
+        let err = manager.activate_connection("dhcp").await.expect_err("activation with a broken [ipv6-ra]");
+        assert!(matches!(err, NetctlError::ConfigError(_)), "unexpected error: {}", err);
+        assert_eq!(manager.dhcp_client().state("actf0").await, DhcpClientState::Stopped);
+        assert_eq!(manager.dhcpv6_client().state("actf0").await, Dhcpv6ClientState::Stopped);
+        assert!(manager.get_active_connection("actf0").await.is_none());
+
+        assert!(manager.activate_connection("ll").await.is_err());
+        assert!(!manager.link_local_controller().is_running("actf0").await);
+        assert!(manager.get_active_connection("actf0").await.is_none());
+    });
+    let _ = std::fs::remove_dir_all(&config_dir);
+}
+
+/// Names of the first two mac80211_hwsim radios and their interfaces
+fn hwsim_radios() -> Option<[(String, String); 2]> {
+    if !std::path::Path::new("/sys/module/mac80211_hwsim").exists() {
+        println!("SKIP: mac80211_hwsim is not loaded");
+        return None;
+    }
+    for tool in ["iw", "hostapd"] {
+        if Command::new("which").arg(tool).output().map(|o| !o.status.success()).unwrap_or(true) {
+            println!("SKIP: {} not found", tool);
+            return None;
+        }
+    }
+    let mut phys: Vec<String> = std::fs::read_dir("/sys/class/ieee80211").ok()?
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .collect();
+    phys.sort();
+    let radio = |phy: &String| {
+        let iface = std::fs::read_dir(format!("/sys/class/ieee80211/{}/device/net", phy)).ok()?
+            .next()?.ok()?.file_name().into_string().ok()?;
+        Some((phy.clone(), iface))
+    };
+    match phys.as_slice() {
+        [a, b, ..] => Some([radio(a)?, radio(b)?]),
+        _ => {
+            println!("SKIP: Need two hwsim radios");
+            None
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_wifi_scan_with_hwsim() {
+    use libnetctl::{AkmSuite, WifiController};
+
+    let Some([(ap_phy, ap_if), (sta_phy, sta_if)]) = hwsim_radios() else { return };
+    let Some(ap_ns) = TestNamespace::create("wap").await else { return };
+    let Some(sta_ns) = TestNamespace::create("wsta").await else { return };
+    for (phy, ns) in [(&ap_phy, &ap_ns), (&sta_phy, &sta_ns)] {
+        let moved = Command::new("iw").args(["phy", phy, "set", "netns", "name", &ns.name]).status();
+        assert!(moved.map(|s| s.success()).unwrap_or(false), "move {} to {}", phy, ns.name);
+    }
+
+    // WPA2/WPA3 transition AP on channel 6
+    let conf = std::env::temp_dir().join(format!("nctest-hostapd-{}.conf", std::process::id()));
+    std::fs::write(&conf, format!(
+        "interface={}\ndriver=nl80211\nssid=nctest\nhw_mode=g\nchannel=6\nieee80211n=1\n\
+         country_code=DE\nieee80211d=1\nwpa=2\nwpa_key_mgmt=WPA-PSK SAE\nrsn_pairwise=CCMP\n\
+         wpa_passphrase=correct-horse\nieee80211w=1\n",
+        ap_if,
+    )).unwrap();
+    let mut hostapd = Command::new("ip")
+        .args(["netns", "exec", &ap_ns.name, "hostapd"])
+        .arg(&conf)
+        .spawn()
+        .expect("start hostapd");
+
+    InterfaceController::with_namespace(sta_ns.ns.clone()).up(&sta_if).await.unwrap();
+    let wifi = WifiController::with_namespace(sta_ns.ns.clone());
+    let info = wifi.get_dev_info(&sta_if).await.unwrap();
+    assert_eq!(info.type_.as_deref(), Some("managed"));
+    assert_eq!(info.phy.as_deref(), Some(sta_phy.as_str()));
+
+    let mut found = None;
+    for _ in 0..5 {
+        let results = wifi.scan(&sta_if).await.unwrap();
+        found = results.into_iter().find(|bss| bss.ssid.as_deref() == Some("nctest"));
+        if found.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    let _ = hostapd.kill();
+    let _ = hostapd.wait();
+    let _ = std::fs::remove_file(&conf);
+
+    let bss = found.expect("hostapd BSS in scan results");
+    assert_eq!(bss.frequency, Some(2437));
+    assert_eq!(bss.channel, Some(6));
+    assert!(bss.signal.is_some());
+    assert!(bss.ht.is_some());
+    let rsn = bss.rsn.as_ref().expect("RSN element");
+    assert!(rsn.akm_suites.contains(&AkmSuite::Psk) && rsn.akm_suites.contains(&AkmSuite::Sae));
+    assert!(rsn.mfp_capable && !rsn.mfp_required);
+    assert_eq!(bss.security_label(), "WPA2 WPA3");
+    assert_eq!(bss.country.as_ref().map(|c| c.code.as_str()), Some("DE"));
+    assert!(!bss.associated);
+    assert!(wifi.link(&sta_if).await.unwrap().is_none());
+}